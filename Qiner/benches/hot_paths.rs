@@ -0,0 +1,106 @@
+//! Benchmarks of the hot code paths, to quantify SIMD and layout changes.
+//!
+//! Every code path the CPU supports and both backends are measured, so a change can be compared
+//! across them. Select one with a filter, e.g. `cargo bench -- score/x86-64-v3`, or build other
+//! code paths with `--features portable` or `--features native`.
+//!
+//! The full-size network takes tens of milliseconds per nonce; set `NUMBER_OF_NEURONS` and
+//! `MINING_DATA_LENGTH` for quicker runs.
+
+use std::hint::black_box;
+use std::sync::Arc;
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use lib::params::AlgorithmParams;
+use lib::types::{Id, Nonce64, PublicKey64};
+use qiner::arch::{compiled_code_paths, CodePath};
+use qiner::backend::create_backend;
+use qiner::converters::{get_id_from_public_key_64, parse_id};
+use qiner::math::KECCAK_LANES;
+use qiner::miner::{MinerShared, NeuronData};
+use qiner::network::Packet;
+use qiner::nonce::NonceStream;
+
+const PUBLIC_KEY: PublicKey64 = [1, 2, 3, 4];
+const RANDOM_SEED: PublicKey64 = [9, 10, 11, 12];
+const NONCES: [Nonce64; KECCAK_LANES] = [[34, 6, 7, 8], [35, 6, 7, 8], [36, 6, 7, 8], [37, 6, 7, 8]];
+
+/// Gets the parameters of the benchmarks, the current ones unless overridden by the environment.
+fn params() -> AlgorithmParams {
+    let get = |name: &str| std::env::var(name).ok().and_then(|value| value.trim().parse::<usize>().ok());
+    let current = AlgorithmParams::CURRENT;
+    let params = AlgorithmParams {
+        number_of_neurons: get("NUMBER_OF_NEURONS").unwrap_or(current.number_of_neurons),
+        mining_data_length: get("MINING_DATA_LENGTH").unwrap_or(current.mining_data_length),
+    };
+    params.validate().expect("invalid NUMBER_OF_NEURONS or MINING_DATA_LENGTH");
+    params
+}
+
+/// Gets the data shared by the mining threads, with a threshold no nonce reaches.
+fn shared(params: AlgorithmParams) -> Arc<MinerShared> {
+    Arc::new(MinerShared::new(PUBLIC_KEY, params, MinerShared::generate_mining_data(&RANDOM_SEED, &params), usize::MAX, None))
+}
+
+/// Benchmarks the generation of the neuron links, `math::random_64` behind `expand_links`.
+fn bench_random_64(c: &mut Criterion) {
+    let shared = shared(params());
+    let mut group = c.benchmark_group("random_64");
+    group.sample_size(10);
+
+    let mut neuron_data = Box::new(NeuronData::new(shared.get_params()));
+    group.throughput(Throughput::Elements(1));
+    group.bench_function("scalar", |b| b.iter(|| shared.expand_links(black_box(&NONCES[0]), &mut neuron_data)));
+
+    let mut neuron_data: [Box<NeuronData>; KECCAK_LANES] = std::array::from_fn(|_| Box::new(NeuronData::new(shared.get_params())));
+    group.throughput(Throughput::Elements(KECCAK_LANES as u64));
+    group.bench_function("x4", |b| b.iter(|| {
+        let [data0, data1, data2, data3] = &mut neuron_data;
+        shared.expand_links_x4(black_box(&NONCES), [data0, data1, data2, data3]);
+    }));
+    group.finish();
+}
+
+/// Benchmarks the neuron update loop of every code path the CPU supports.
+fn bench_score(c: &mut Criterion) {
+    let shared = shared(params());
+    let mut neuron_data = Box::new(NeuronData::new(shared.get_params()));
+    shared.expand_links(&NONCES[0], &mut neuron_data);
+
+    let mut group = c.benchmark_group("score");
+    group.sample_size(10);
+    for code_path in compiled_code_paths().into_iter().filter(CodePath::is_supported) {
+        group.bench_function(code_path.name(), |b| b.iter(|| shared.score_with(code_path, &mut neuron_data)));
+    }
+    group.finish();
+}
+
+/// Benchmarks the backends a mining thread dispatches to, links and score of whole batches.
+fn bench_backends(c: &mut Criterion) {
+    let shared = shared(params());
+    let mut group = c.benchmark_group("backend");
+    group.sample_size(10);
+    group.throughput(Throughput::Elements(KECCAK_LANES as u64));
+    for keccak_lanes in [1, KECCAK_LANES] {
+        let mut backend = create_backend(shared.clone(), NonceStream::new(0, 0), keccak_lanes);
+        group.bench_function(BenchmarkId::from_parameter(backend.name()), |b| b.iter(|| backend.solve_batch(KECCAK_LANES)));
+    }
+    group.finish();
+}
+
+/// Benchmarks the construction of a solution packet, gamming and signature included.
+fn bench_packet(c: &mut Criterion) {
+    c.bench_function("packet/solution", |b| b.iter(|| Packet::solutions_to_bytes(142, black_box(&PUBLIC_KEY), [&NONCES[0]])));
+}
+
+/// Benchmarks the conversions between public keys and IDs.
+fn bench_id(c: &mut Criterion) {
+    let mut id: Id = [0; 60];
+    get_id_from_public_key_64(&PUBLIC_KEY, &mut id);
+    let id_str = String::from_utf8(id.to_vec()).unwrap();
+
+    c.bench_function("id/from_public_key", |b| b.iter(|| get_id_from_public_key_64(black_box(&PUBLIC_KEY), &mut id)));
+    c.bench_function("id/parse", |b| b.iter(|| parse_id(black_box(&id_str))));
+}
+
+criterion_group!(benches, bench_random_64, bench_score, bench_backends, bench_packet, bench_id);
+criterion_main!(benches);
@@ -0,0 +1,105 @@
+//! Embeds the git commit and the build date, shown by `qiner --version` and logged on start.
+//!
+//! With the `native` feature, also detects the CPU features of the build machine, so the
+//! hot code paths are compiled for them (see `src/arch/`).
+
+use std::env;
+use std::path::PathBuf;
+use std::process::Command;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+fn main() {
+    println!("cargo:rustc-env=QINER_GIT_HASH={}", git_hash().unwrap_or_else(|| "unknown".to_string()));
+    println!("cargo:rustc-env=QINER_BUILD_DATE={}", build_date());
+    println!("cargo:rerun-if-env-changed=SOURCE_DATE_EPOCH");
+    native_features();
+
+    // Rebuild when the checked out commit changes
+    if let Some(git_dir) = git(&["rev-parse", "--git-dir"]).map(PathBuf::from) {
+        let git_dir = if git_dir.is_absolute() { git_dir } else { PathBuf::from(env::var("CARGO_MANIFEST_DIR").unwrap()).join(git_dir) };
+        println!("cargo:rerun-if-changed={}", git_dir.join("HEAD").display());
+        if let Some(head_ref) = git(&["symbolic-ref", "-q", "HEAD"]) {
+            println!("cargo:rerun-if-changed={}", git_dir.join(head_ref).display());
+        }
+    }
+}
+
+/// CPU features the hot code paths are compiled for with the `native` feature, when the build machine has them.
+const NATIVE_FEATURES: [&str; 10] = ["sse4.1", "sse4.2", "popcnt", "avx", "avx2", "bmi1", "bmi2", "fma", "lzcnt", "avx512f"];
+
+/// Emits `qiner_native` and one `qiner_native_feature` per detected CPU feature with the `native` feature.
+///
+/// Nothing is detected when cross compiling, as the build machine says nothing about the target,
+/// nor with the `portable` feature, which takes precedence so features stay additive.
+fn native_features() {
+    println!("cargo:rustc-check-cfg=cfg(qiner_native)");
+    println!("cargo:rustc-check-cfg=cfg(qiner_native_feature, values({}))", NATIVE_FEATURES.map(|feature| format!("\"{feature}\"")).join(", "));
+    if env::var_os("CARGO_FEATURE_NATIVE").is_none() {
+        return;
+    }
+    if env::var_os("CARGO_FEATURE_PORTABLE").is_some() {
+        println!("cargo:warning=the portable feature takes precedence over the native feature, building the portable code paths");
+        return;
+    }
+    if env::var("HOST").ok() != env::var("TARGET").ok() || env::var("CARGO_CFG_TARGET_ARCH").ok().as_deref() != Some("x86_64") {
+        println!("cargo:warning=the native feature only applies to x86_64 builds for the build machine, building the portable code paths");
+        return;
+    }
+
+    println!("cargo:rustc-cfg=qiner_native");
+    #[cfg(target_arch = "x86_64")]
+    for feature in NATIVE_FEATURES {
+        let is_detected = match feature {
+            "sse4.1" => is_x86_feature_detected!("sse4.1"),
+            "sse4.2" => is_x86_feature_detected!("sse4.2"),
+            "popcnt" => is_x86_feature_detected!("popcnt"),
+            "avx" => is_x86_feature_detected!("avx"),
+            "avx2" => is_x86_feature_detected!("avx2"),
+            "bmi1" => is_x86_feature_detected!("bmi1"),
+            "bmi2" => is_x86_feature_detected!("bmi2"),
+            "fma" => is_x86_feature_detected!("fma"),
+            "lzcnt" => is_x86_feature_detected!("lzcnt"),
+            "avx512f" => is_x86_feature_detected!("avx512f"),
+            _ => false,
+        };
+        if is_detected {
+            println!("cargo:rustc-cfg=qiner_native_feature=\"{feature}\"");
+        }
+    }
+}
+
+/// Gets the short hash of the checked out commit, suffixed with `-dirty` if there are local changes.
+fn git_hash() -> Option<String> {
+    let hash = git(&["rev-parse", "--short", "HEAD"])?;
+    let is_dirty = git(&["status", "--porcelain", "--untracked-files=no"]).is_some_and(|status| !status.is_empty());
+    Some(if is_dirty { format!("{hash}-dirty") } else { hash })
+}
+
+/// Runs git in the package directory.
+///
+/// # Returns
+/// The trimmed output, or `None` if git is missing or failed.
+fn git(args: &[&str]) -> Option<String> {
+    let output = Command::new("git").args(args).current_dir(env::var("CARGO_MANIFEST_DIR").ok()?).output().ok()?;
+    output.status.success().then(|| String::from_utf8_lossy(&output.stdout).trim().to_string())
+}
+
+/// Gets the build date as `YYYY-MM-DD`, from `SOURCE_DATE_EPOCH` for reproducible builds.
+fn build_date() -> String {
+    let secs = env::var("SOURCE_DATE_EPOCH").ok()
+        .and_then(|value| value.trim().parse::<u64>().ok())
+        .unwrap_or_else(|| SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |duration| duration.as_secs()));
+
+    // Civil date of a day count, from Howard Hinnant's `civil_from_days`
+    let days = (secs / 86_400) as i64 + 719_468;
+    let era = days.div_euclid(146_097);
+    let day_of_era = days.rem_euclid(146_097);
+    let year_of_era = (day_of_era - day_of_era / 1_460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month_index = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * month_index + 2) / 5 + 1;
+    let month = if month_index < 10 { month_index + 3 } else { month_index - 9 };
+    let year = year_of_era + era * 400 + i64::from(month <= 2);
+
+    format!("{year:04}-{month:02}-{day:02}")
+}
@@ -0,0 +1,134 @@
+//! Versioned scoring algorithms.
+//!
+//! When the network switches scoring rules, the new rules are added as another version next to
+//! the current one, so the miner can switch between them without a rebuild during the transition.
+
+use std::fmt::Debug;
+use std::sync::Arc;
+use lib::types::Version;
+use crate::backend::{self, Backend};
+use crate::miner::MinerShared;
+use crate::nonce::NonceStream;
+
+/// A version of the scoring rules of the network
+pub trait Algorithm: Debug + Send + Sync {
+    /// Gets the version of the algorithm
+    fn version(&self) -> u32;
+
+    /// Gets the name of the algorithm, for the logs
+    fn name(&self) -> &'static str;
+
+    /// Creates the backend scoring the nonces of a mining thread
+    ///
+    /// # Arguments
+    /// * `nonce_stream` - The nonces of the thread
+    /// * `keccak_lanes` - Number of nonces whose links are generated in one pass, 1 or `KECCAK_LANES`
+    ///
+    /// # Returns
+    /// The backend of the thread
+    fn create_backend(&self, nonce_stream: NonceStream, keccak_lanes: usize) -> Box<dyn Backend>;
+}
+
+/// The neuron network scoring, with the parameters of `MinerShared`
+#[derive(Debug)]
+pub struct NeuronNetworkV1 {
+    shared: Arc<MinerShared>,
+}
+
+impl NeuronNetworkV1 {
+    /// Creates the algorithm
+    ///
+    /// # Arguments
+    /// * `shared` - The mining data and the thresholds the nonces are scored against
+    ///
+    /// # Returns
+    /// The algorithm, shared by the mining threads
+    pub fn create(shared: Arc<MinerShared>) -> Arc<dyn Algorithm> {
+        Arc::new(NeuronNetworkV1 { shared })
+    }
+}
+
+impl Algorithm for NeuronNetworkV1 {
+    fn version(&self) -> u32 {
+        1
+    }
+
+    fn name(&self) -> &'static str {
+        "neuron-network"
+    }
+
+    fn create_backend(&self, nonce_stream: NonceStream, keccak_lanes: usize) -> Box<dyn Backend> {
+        backend::create_backend(self.shared.clone(), nonce_stream, keccak_lanes)
+    }
+}
+
+/// An algorithm of the registry
+#[derive(Debug)]
+pub struct AlgorithmEntry {
+    pub version: u32,
+    pub name: &'static str,
+    /// First version of Qubic scoring with the algorithm
+    pub first_qubic_version: Version,
+    /// Creates the algorithm from the data shared by the mining threads
+    pub create: fn(Arc<MinerShared>) -> Arc<dyn Algorithm>,
+}
+
+/// Every algorithm the miner supports, ordered by the first version of Qubic using them.
+pub const ALGORITHMS: &[AlgorithmEntry] = &[
+    AlgorithmEntry { version: 1, name: "neuron-network", first_qubic_version: [1, 0, 0], create: NeuronNetworkV1::create },
+];
+
+impl AlgorithmEntry {
+    /// Gets the algorithm of a Qubic version.
+    ///
+    /// # Arguments
+    /// * `version` - The version of Qubic
+    ///
+    /// # Returns
+    /// The latest algorithm not introduced after the version, the first one if there is none
+    pub fn for_qubic_version(version: &Version) -> &'static AlgorithmEntry {
+        ALGORITHMS.iter()
+            .rev()
+            .find(|entry| entry.first_qubic_version <= *version)
+            .unwrap_or(&ALGORITHMS[0])
+    }
+
+    /// Finds an algorithm of the registry.
+    ///
+    /// # Arguments
+    /// * `value` - The version, with or without a `v` prefix, or the name of the algorithm
+    ///
+    /// # Returns
+    /// The algorithm, or why none matches
+    pub fn parse(value: &str) -> Result<&'static AlgorithmEntry, String> {
+        let value = value.trim().to_ascii_lowercase();
+        let version = value.strip_prefix('v').unwrap_or(&value).parse::<u32>().ok();
+        ALGORITHMS.iter()
+            .find(|entry| Some(entry.version) == version || entry.name == value)
+            .ok_or_else(|| {
+                let known = ALGORITHMS.iter().map(|entry| format!("v{} ({})", entry.version, entry.name)).collect::<Vec<_>>();
+                format!("unknown algorithm {value:?}, expected one of {}", known.join(", "))
+            })
+    }
+}
+
+#[test]
+/// Tests the lookup of the registry and that v1 scores like the backends it wraps.
+fn test_algorithm_registry() {
+    use lib::params::AlgorithmParams;
+
+    assert_eq!(AlgorithmEntry::parse("V1").unwrap().name, "neuron-network");
+    assert_eq!(AlgorithmEntry::parse(" neuron-network ").unwrap().version, 1);
+    assert!(AlgorithmEntry::parse("v99").unwrap_err().contains("v1 (neuron-network)"));
+    assert_eq!(AlgorithmEntry::for_qubic_version(&[1, 142, 1]).version, 1);
+    assert_eq!(AlgorithmEntry::for_qubic_version(&[0, 1, 0]).version, 1);
+
+    let params = AlgorithmParams { number_of_neurons: 4096, mining_data_length: 4 };
+    let shared = Arc::new(MinerShared::new([3; 4], params, MinerShared::generate_mining_data(&[7; 4], &params), usize::MAX, None));
+    let algorithm = (AlgorithmEntry::parse("1").unwrap().create)(shared.clone());
+    assert_eq!(algorithm.version(), 1);
+
+    let nonce_stream = NonceStream::new(0, 0);
+    let expected = backend::create_backend(shared, nonce_stream.clone(), 1).solve_batch(3);
+    assert_eq!(algorithm.create_backend(nonce_stream, 1).solve_batch(3), expected);
+}
@@ -0,0 +1,9 @@
+//! Portable implementations, used on every target without a faster path.
+
+/// Generates a random 64-bit number with the random generator of the OS.
+///
+/// # Panics
+/// If the OS generator is unavailable, nonces cannot be generated safely then.
+pub fn random_u64() -> u64 {
+    getrandom::u64().expect("the OS random generator is available")
+}
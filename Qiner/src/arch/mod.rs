@@ -0,0 +1,126 @@
+//! Platform abstraction: the CPU-specific instructions the miner uses, with a portable
+//! implementation for every target so the crate builds and runs (scalar) everywhere.
+//!
+//! The rest of the crate calls these functions instead of `std::arch` directly.
+//!
+//! The hot code paths are compiled several times, for the `CodePath`s below, and the fastest
+//! one the CPU supports is picked at runtime. The `portable` feature keeps the baseline only,
+//! the `native` feature compiles them for the CPU of the build machine instead. With both,
+//! `portable` takes precedence.
+//!
+//! Without the `std` feature, the CPU features are not detected at runtime: the code paths are
+//! only used if the target enables their features at compile time.
+
+use alloc::vec::Vec;
+#[cfg(feature = "std")]
+use std::sync::OnceLock;
+
+#[cfg(feature = "miner")]
+mod generic;
+#[cfg(feature = "miner")]
+mod random;
+#[cfg(target_arch = "x86_64")]
+mod x86_64;
+
+#[cfg(feature = "miner")]
+pub use random::{has_hardware_random, hardware_random_health, random_u32, random_u64};
+
+/// A set of CPU features the hot code paths are compiled for.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CodePath {
+    /// The features of the target, e.g. plain x86_64.
+    Baseline,
+    /// The x86-64-v3 level: AVX2, BMI1, BMI2, FMA, LZCNT, MOVBE.
+    #[cfg(target_arch = "x86_64")]
+    X86_64V3,
+    /// The features of the CPU of the build machine, with the `native` feature.
+    #[cfg(qiner_native)]
+    Native,
+}
+
+impl CodePath {
+    /// Gets the name of the code path, for the logs.
+    pub fn name(&self) -> &'static str {
+        match self {
+            CodePath::Baseline => "baseline",
+            #[cfg(target_arch = "x86_64")]
+            CodePath::X86_64V3 => "x86-64-v3",
+            #[cfg(qiner_native)]
+            CodePath::Native => "native",
+        }
+    }
+
+    /// Checks whether the CPU supports the code path.
+    pub fn is_supported(&self) -> bool {
+        match self {
+            CodePath::Baseline => true,
+            #[cfg(target_arch = "x86_64")]
+            CodePath::X86_64V3 => x86_64::has_v3(),
+            // The build machine is the machine the binary is built for
+            #[cfg(qiner_native)]
+            CodePath::Native => true,
+        }
+    }
+}
+
+/// Lists the code paths compiled in, fastest first.
+pub fn compiled_code_paths() -> Vec<CodePath> {
+    COMPILED_CODE_PATHS.to_vec()
+}
+
+/// The code paths compiled in, fastest first.
+const COMPILED_CODE_PATHS: &[CodePath] = &[
+    #[cfg(qiner_native)]
+    CodePath::Native,
+    #[cfg(all(target_arch = "x86_64", not(feature = "portable"), not(qiner_native)))]
+    CodePath::X86_64V3,
+    CodePath::Baseline,
+];
+
+/// Gets the fastest code path the CPU supports, detected once.
+pub fn code_path() -> CodePath {
+    #[cfg(feature = "std")]
+    {
+        static CODE_PATH: OnceLock<CodePath> = OnceLock::new();
+        *CODE_PATH.get_or_init(detect_code_path)
+    }
+    // The features are known at compile time, nothing to cache
+    #[cfg(not(feature = "std"))]
+    detect_code_path()
+}
+
+/// Finds the fastest code path the CPU supports.
+fn detect_code_path() -> CodePath {
+    COMPILED_CODE_PATHS.iter().copied().find(CodePath::is_supported).unwrap_or(CodePath::Baseline)
+}
+
+/// Whether the CPU runs four Keccak states at once.
+///
+/// # Returns
+/// `true` with AVX2 on x86_64, `false` elsewhere or with the `portable` feature, where the states are permuted one by one.
+pub fn has_keccak_x4() -> bool {
+    #[cfg(all(target_arch = "x86_64", not(feature = "portable")))]
+    return x86_64::has_avx2();
+    #[cfg(any(not(target_arch = "x86_64"), feature = "portable"))]
+    return false;
+}
+
+/// Hints the CPU to load the cache line holding a value, a no-op where there is no such hint.
+///
+/// # Arguments
+/// * `value` - The value to prefetch
+#[inline(always)]
+pub fn prefetch<T>(value: &T) {
+    #[cfg(target_arch = "x86_64")]
+    x86_64::prefetch(value);
+    #[cfg(not(target_arch = "x86_64"))]
+    let _ = value;
+}
+
+#[test]
+/// Tests that the baseline is always available and that the detected code path is supported.
+fn test_code_path() {
+    assert_eq!(compiled_code_paths().last(), Some(&CodePath::Baseline));
+    assert!(code_path().is_supported());
+    assert!(compiled_code_paths().contains(&code_path()));
+}
@@ -0,0 +1,114 @@
+//! Random numbers for the nonces and the signatures, from RDRAND when it is healthy and from the
+//! OS generator otherwise. Only built with the `miner` feature, verification needs no randomness.
+
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::OnceLock;
+use super::generic;
+#[cfg(target_arch = "x86_64")]
+use super::x86_64;
+
+/// Set once the hardware generator failed while mining, every number comes from the OS generator afterwards.
+static HARDWARE_RANDOM_FAILED: AtomicBool = AtomicBool::new(false);
+
+/// Number of values the health check of the hardware generator draws.
+const HEALTH_CHECK_SAMPLES: usize = 16;
+
+/// Whether the CPU has a hardware random generator the miner uses.
+///
+/// # Returns
+/// `true` with a healthy RDRAND on x86_64, `false` elsewhere or once it failed, where the OS generator is used.
+pub fn has_hardware_random() -> bool {
+    hardware_random_health().is_ok() && !HARDWARE_RANDOM_FAILED.load(Ordering::Relaxed)
+}
+
+/// Checks the hardware random generator once, the first time random numbers are needed.
+///
+/// Some CPUs report RDRAND but return errors or a constant value, e.g. all ones after a
+/// suspend on buggy firmware, which would repeat nonces and signatures.
+///
+/// # Returns
+/// Why the hardware generator is not used, if it is not.
+pub fn hardware_random_health() -> Result<(), String> {
+    static HEALTH: OnceLock<Result<(), String>> = OnceLock::new();
+    HEALTH.get_or_init(|| {
+        #[cfg(target_arch = "x86_64")]
+        if x86_64::has_rdrand() {
+            // Safety: RDRAND support was just checked
+            let samples = (0..HEALTH_CHECK_SAMPLES).map(|_| unsafe { x86_64::rdrand_u64() }).collect::<Option<Vec<_>>>();
+            return check_samples(samples.as_deref());
+        }
+        Err("not available".to_string())
+    }).clone()
+}
+
+/// Checks values drawn from a random generator.
+///
+/// # Arguments
+/// * `samples` - The values, `None` if drawing one failed.
+///
+/// # Returns
+/// Why the generator is broken, if it is.
+fn check_samples(samples: Option<&[u64]>) -> Result<(), String> {
+    let samples = samples.ok_or("RDRAND keeps failing")?;
+    let distinct = samples.iter().collect::<std::collections::HashSet<_>>().len();
+    if distinct < samples.len() {
+        return Err(format!("RDRAND repeats values, {distinct} distinct out of {}", samples.len()));
+    }
+    Ok(())
+}
+
+/// Stops using the hardware generator after it failed.
+#[cfg(target_arch = "x86_64")]
+fn disable_hardware_random() {
+    if !HARDWARE_RANDOM_FAILED.swap(true, Ordering::Relaxed) {
+        log::warn!("RDRAND failed {} times in a row, using the OS random generator", x86_64::RDRAND_RETRIES);
+    }
+}
+
+/// Generates a random 64-bit number, from the hardware generator when available.
+///
+/// # Returns
+/// A 64-bit random number, from the OS generator if the hardware one is missing or broken.
+pub fn random_u64() -> u64 {
+    #[cfg(target_arch = "x86_64")]
+    if has_hardware_random() {
+        // Safety: RDRAND support was checked by the health check
+        match unsafe { x86_64::rdrand_u64() } {
+            Some(value) => return value,
+            None => disable_hardware_random(),
+        }
+    }
+    generic::random_u64()
+}
+
+/// Generates a random 32-bit number, from the hardware generator when available.
+///
+/// # Returns
+/// A 32-bit random number, from the OS generator if the hardware one is missing or broken.
+pub fn random_u32() -> u32 {
+    #[cfg(target_arch = "x86_64")]
+    if has_hardware_random() {
+        // Safety: RDRAND support was checked by the health check
+        match unsafe { x86_64::rdrand_u32() } {
+            Some(value) => return value,
+            None => disable_hardware_random(),
+        }
+    }
+    generic::random_u64() as u32
+}
+
+#[test]
+/// Tests that both random generators produce distinct values.
+fn test_random() {
+    let values = (0..64).map(|_| random_u64()).chain((0..64).map(|_| generic::random_u64())).collect::<std::collections::HashSet<_>>();
+    assert_eq!(values.len(), 128);
+}
+
+#[test]
+/// Tests that the health check rejects failing and repeating generators.
+fn test_hardware_random_health() {
+    assert!(check_samples(Some(&[1, 2, 3])).is_ok());
+    assert!(check_samples(None).unwrap_err().contains("failing"));
+    assert!(check_samples(Some(&[u64::MAX; 4])).unwrap_err().contains("1 distinct out of 4"));
+    assert_eq!(has_hardware_random(), hardware_random_health().is_ok());
+}
@@ -0,0 +1,72 @@
+//! x86_64 implementations, selected at runtime from the features of the CPU.
+
+use core::arch::x86_64::{_mm_prefetch, _MM_HINT_T0};
+#[cfg(feature = "miner")]
+use core::arch::x86_64::{_rdrand32_step, _rdrand64_step};
+
+/// Whether the CPU supports RDRAND.
+#[cfg(feature = "miner")]
+pub fn has_rdrand() -> bool {
+    is_x86_feature_detected!("rdrand")
+}
+
+/// Whether the CPU supports AVX2.
+#[cfg(not(feature = "portable"))]
+pub fn has_avx2() -> bool {
+    #[cfg(feature = "std")]
+    return is_x86_feature_detected!("avx2");
+    #[cfg(not(feature = "std"))]
+    return cfg!(target_feature = "avx2");
+}
+
+/// Whether the CPU supports the x86-64-v3 level.
+pub fn has_v3() -> bool {
+    #[cfg(feature = "std")]
+    return is_x86_feature_detected!("avx2")
+        && is_x86_feature_detected!("bmi1")
+        && is_x86_feature_detected!("bmi2")
+        && is_x86_feature_detected!("fma")
+        && is_x86_feature_detected!("lzcnt")
+        && is_x86_feature_detected!("movbe");
+    #[cfg(not(feature = "std"))]
+    return cfg!(all(target_feature = "avx2", target_feature = "bmi1", target_feature = "bmi2", target_feature = "fma", target_feature = "lzcnt", target_feature = "movbe"));
+}
+
+/// Number of RDRAND attempts before giving up, as recommended by Intel for a transient underflow.
+#[cfg(feature = "miner")]
+pub const RDRAND_RETRIES: usize = 10;
+
+/// Generates a random 64-bit number with RDRAND.
+///
+/// # Safety
+/// The CPU must support RDRAND, see `has_rdrand`.
+///
+/// # Returns
+/// The number, or `None` if RDRAND failed `RDRAND_RETRIES` times in a row.
+#[cfg(feature = "miner")]
+#[target_feature(enable = "rdrand")]
+pub unsafe fn rdrand_u64() -> Option<u64> {
+    let mut value = 0;
+    (0..RDRAND_RETRIES).any(|_| _rdrand64_step(&mut value) == 1).then_some(value)
+}
+
+/// Generates a random 32-bit number with RDRAND.
+///
+/// # Safety
+/// The CPU must support RDRAND, see `has_rdrand`.
+///
+/// # Returns
+/// The number, or `None` if RDRAND failed `RDRAND_RETRIES` times in a row.
+#[cfg(feature = "miner")]
+#[target_feature(enable = "rdrand")]
+pub unsafe fn rdrand_u32() -> Option<u32> {
+    let mut value = 0;
+    (0..RDRAND_RETRIES).any(|_| _rdrand32_step(&mut value) == 1).then_some(value)
+}
+
+/// Hints the CPU to load the cache line holding a value into every cache level.
+#[inline(always)]
+pub fn prefetch<T>(value: &T) {
+    // Safety: prefetching is a hint, any address is valid, and SSE is part of x86_64
+    unsafe { _mm_prefetch::<_MM_HINT_T0>(value as *const T as *const i8) };
+}
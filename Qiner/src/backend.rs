@@ -0,0 +1,171 @@
+//! Backends evaluating nonces for a mining thread.
+//!
+//! A backend takes nonces from the stream of its thread in batches, so backends with a setup
+//! cost per call, like the four-lane Keccak, spread it over many nonces.
+
+use std::fmt::Debug;
+use std::sync::Arc;
+use lib::types::Nonce64;
+use crate::math::KECCAK_LANES;
+use crate::miner::{MinerShared, NeuronData};
+use crate::nonce::NonceStream;
+
+/// Evaluates batches of nonces for a mining thread
+pub trait Backend: Debug + Send {
+    /// Gets the name of the backend, for the logs
+    fn name(&self) -> &'static str;
+
+    /// Gets the number of nonces the backend evaluates in one pass
+    ///
+    /// # Returns
+    /// The number of nonces, batches are best a multiple of it
+    fn batch_size(&self) -> usize;
+
+    /// Evaluates the next nonces of the stream of the thread
+    ///
+    /// # Arguments
+    /// * `count` - Number of nonces to evaluate
+    ///
+    /// # Returns
+    /// Every evaluated nonce with its score, in the order of the stream
+    fn solve_batch(&mut self, count: usize) -> Vec<(Nonce64, usize)>;
+}
+
+/// Creates the backend of a mining thread
+///
+/// # Arguments
+/// * `shared` - The data shared by the mining threads
+/// * `nonce_stream` - The nonces of the thread
+/// * `keccak_lanes` - Number of nonces whose links are generated in one pass, 1 or `KECCAK_LANES`
+///
+/// # Returns
+/// The four-lane backend if `keccak_lanes` is `KECCAK_LANES`, the scalar one otherwise
+pub fn create_backend(shared: Arc<MinerShared>, nonce_stream: NonceStream, keccak_lanes: usize) -> Box<dyn Backend> {
+    if keccak_lanes == KECCAK_LANES {
+        Box::new(Keccak4Backend::new(shared, nonce_stream))
+    } else {
+        Box::new(ScalarBackend::new(shared, nonce_stream))
+    }
+}
+
+/// Backend generating the neuron links of one nonce at a time
+#[derive(Debug)]
+pub struct ScalarBackend {
+    shared: Arc<MinerShared>,
+    nonce_stream: NonceStream,
+    /// Boxed so it is not part of the task future, which is moved through the stack
+    neuron_data: Box<NeuronData>,
+}
+
+impl ScalarBackend {
+    /// Creates a scalar backend
+    ///
+    /// # Arguments
+    /// * `shared` - The data shared by the mining threads
+    /// * `nonce_stream` - The nonces of the thread
+    ///
+    /// # Returns
+    /// A new instance of the ScalarBackend struct
+    pub fn new(shared: Arc<MinerShared>, nonce_stream: NonceStream) -> Self {
+        ScalarBackend {
+            neuron_data: Box::new(NeuronData::new(shared.get_params())),
+            shared,
+            nonce_stream,
+        }
+    }
+}
+
+impl Backend for ScalarBackend {
+    fn name(&self) -> &'static str {
+        "scalar"
+    }
+
+    fn batch_size(&self) -> usize {
+        1
+    }
+
+    fn solve_batch(&mut self, count: usize) -> Vec<(Nonce64, usize)> {
+        (0..count).map(|_| {
+            let nonce = self.nonce_stream.next_nonce();
+            self.shared.expand_links(&nonce, &mut self.neuron_data);
+            (nonce, self.shared.score(&mut self.neuron_data))
+        }).collect()
+    }
+}
+
+/// Backend generating the neuron links of `KECCAK_LANES` nonces in one pass
+#[derive(Debug)]
+pub struct Keccak4Backend {
+    shared: Arc<MinerShared>,
+    nonce_stream: NonceStream,
+    neuron_data: [Box<NeuronData>; KECCAK_LANES],
+}
+
+impl Keccak4Backend {
+    /// Creates a four-lane backend
+    ///
+    /// # Arguments
+    /// * `shared` - The data shared by the mining threads
+    /// * `nonce_stream` - The nonces of the thread
+    ///
+    /// # Returns
+    /// A new instance of the Keccak4Backend struct
+    pub fn new(shared: Arc<MinerShared>, nonce_stream: NonceStream) -> Self {
+        Keccak4Backend {
+            neuron_data: std::array::from_fn(|_| Box::new(NeuronData::new(shared.get_params()))),
+            shared,
+            nonce_stream,
+        }
+    }
+}
+
+impl Backend for Keccak4Backend {
+    fn name(&self) -> &'static str {
+        "keccak-x4"
+    }
+
+    fn batch_size(&self) -> usize {
+        KECCAK_LANES
+    }
+
+    fn solve_batch(&mut self, count: usize) -> Vec<(Nonce64, usize)> {
+        let mut results = Vec::with_capacity(count);
+        for _ in 0..count / KECCAK_LANES {
+            let nonces: [Nonce64; KECCAK_LANES] = std::array::from_fn(|_| self.nonce_stream.next_nonce());
+            let [data0, data1, data2, data3] = &mut self.neuron_data;
+            self.shared.expand_links_x4(&nonces, [data0, data1, data2, data3]);
+            for (nonce, data) in nonces.into_iter().zip(self.neuron_data.iter_mut()) {
+                results.push((nonce, self.shared.score(data)));
+            }
+        }
+
+        // The rest of the batch does not fill the lanes
+        for _ in 0..count % KECCAK_LANES {
+            let nonce = self.nonce_stream.next_nonce();
+            self.shared.expand_links(&nonce, &mut self.neuron_data[0]);
+            results.push((nonce, self.shared.score(&mut self.neuron_data[0])));
+        }
+        results
+    }
+}
+
+#[test]
+/// Tests that both backends give the same scores for the same nonces, with a batch not filling the lanes.
+fn test_backends_agree() {
+    use lib::params::AlgorithmParams;
+
+    // Small parameters keep the scores fast in debug builds
+    let params = AlgorithmParams { number_of_neurons: 4096, mining_data_length: 4 };
+    let mining_data = MinerShared::generate_mining_data(&[7; 4], &params);
+    let shared = Arc::new(MinerShared::new([3; 4], params, mining_data, usize::MAX, None));
+    let nonce_stream = NonceStream::new(0, 0);
+
+    let mut scalar = create_backend(shared.clone(), nonce_stream.clone(), 1);
+    let mut keccak_x4 = create_backend(shared, nonce_stream, KECCAK_LANES);
+    assert_eq!((scalar.name(), keccak_x4.name()), ("scalar", "keccak-x4"));
+
+    let batch = keccak_x4.solve_batch(2 * KECCAK_LANES + 1);
+    assert_eq!(batch.len(), 2 * KECCAK_LANES + 1);
+    assert_eq!(scalar.solve_batch(2 * KECCAK_LANES + 1), batch);
+    assert!(scalar.solve_batch(0).is_empty());
+}
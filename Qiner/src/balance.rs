@@ -0,0 +1,135 @@
+//! Periodic check of what the network credited the ID with.
+//!
+//! The miner only knows the solutions it sent and how many connections a node answered after
+//! them, not whether they counted. The balance of the ID in the spectrum of the best node is requested every few
+//! minutes and logged next to the local totals of the epoch, so a rig whose solutions stopped
+//! being rewarded is noticed before the end of the epoch.
+
+use std::io;
+use std::mem::size_of;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use crate::settings::Settings;
+use lib::types::network::protocols::RESPOND_ENTITY;
+use lib::types::network::Protocol;
+use lib::types::PublicKey64;
+use tokio::io::{AsyncRead, AsyncWrite, AsyncWriteExt};
+use crate::clock::read_response;
+use crate::messages::{Request, RequestEntity};
+use crate::network::{Entity, NetStats, RequestResponseHeader};
+use crate::peers::QUIC_SCHEME;
+use crate::socks::{self, SocksProxy};
+use crate::submission::SubmissionStats;
+use crate::targets::TargetSelector;
+
+/// Time to wait for the entity of a node.
+const ENTITY_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Requests the entity of a public key from a node, skipping the other messages it sends meanwhile.
+///
+/// # Arguments
+/// * `stream` - The connection to the node
+/// * `public_key` - The public key whose entity is requested
+/// * `protocol` - The protocol version of the request
+///
+/// # Returns
+/// The entity, or the error of the connection or of the framing.
+pub async fn request_entity(stream: &mut (impl AsyncRead + AsyncWrite + Unpin), public_key: PublicKey64, protocol: Protocol) -> io::Result<Entity> {
+    let request = RequestEntity::new(public_key).to_bytes(protocol).map_err(|err| io::Error::new(io::ErrorKind::InvalidInput, err))?;
+    stream.write_all(&request).await?;
+    loop {
+        let payload = read_response(stream, RESPOND_ENTITY).await?;
+        let entity = Entity::from_bytes(&payload).ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "short entity"))?;
+        // Another client of the node may have requested another entity
+        if entity.public_key == public_key {
+            return Ok(entity);
+        }
+    }
+}
+
+/// Describes the balance of the ID against the solutions sent.
+///
+/// # Arguments
+/// * `entity` - The entity of the ID.
+/// * `previous_balance` - The balance at the previous check, `None` for the first one.
+/// * `submission_stats` - The totals of the epoch.
+///
+/// # Returns
+/// The line to log.
+pub fn describe_balance(entity: &Entity, previous_balance: Option<i64>, submission_stats: &SubmissionStats) -> String {
+    let solutions = format!("{} solutions sent and {} connections answered this epoch", submission_stats.get_sent(), submission_stats.get_answered_connections());
+    if !entity.is_in_spectrum() {
+        return format!("The ID is not in the spectrum at tick {}, {solutions}", entity.tick);
+    }
+    let balance = entity.get_balance();
+    match previous_balance {
+        Some(previous_balance) => format!("Balance of the ID: {balance} qu at tick {} ({:+} since the last check), {solutions}", entity.tick, balance - previous_balance),
+        None => format!("Balance of the ID: {balance} qu at tick {}, {solutions}", entity.tick),
+    }
+}
+
+/// Asynchronous task requesting the entity of the ID from the best node periodically and logging
+/// its balance next to the solutions sent.
+///
+/// The requests are deferred by the bandwidth cap, and nodes reached over QUIC are skipped.
+///
+/// # Arguments
+/// * `targets` - The shared target selector, whose best node is asked
+/// * `socks` - The SOCKS proxy the node is reached through, `None` to connect directly
+/// * `net_stats` - Shared network counters, holding the bandwidth cap
+/// * `settings` - The settings, giving the protocol version of the requests to each node
+/// * `public_key` - Public key of the ID
+/// * `submission_stats` - The totals of the epoch, logged with the balance
+/// * `interval` - Interval between two requests
+pub async fn balance_task(targets: Arc<Mutex<TargetSelector>>, socks: Option<SocksProxy>, net_stats: Arc<NetStats>, settings: Arc<Settings>, public_key: PublicKey64, submission_stats: Arc<SubmissionStats>, interval: Duration) {
+    let request_size = size_of::<RequestResponseHeader>() + size_of::<PublicKey64>();
+    let mut previous_balance = None;
+    loop {
+        let addr = targets.lock().unwrap().get_nodes().best().to_string();
+        if !addr.is_empty() && !addr.starts_with(QUIC_SCHEME) && net_stats.get_bandwidth().try_consume(request_size) {
+            let result = tokio::time::timeout(ENTITY_TIMEOUT, async {
+                let mut stream = socks::connect(socks.as_ref(), &addr).await?;
+                request_entity(&mut stream, public_key, settings.nodes.get_peer_protocol(&addr)).await
+            }).await;
+            match result {
+                Ok(Ok(entity)) => {
+                    log::info!("{}", describe_balance(&entity, previous_balance, &submission_stats));
+                    previous_balance = entity.is_in_spectrum().then(|| entity.get_balance());
+                }
+                Ok(Err(err)) => log::debug!("No entity from {addr}: {:?}", err),
+                Err(_) => log::debug!("No entity from {addr} within {:?}", ENTITY_TIMEOUT),
+            }
+        }
+
+        tokio::time::sleep(interval).await;
+    }
+}
+
+#[test]
+/// Tests the entity of a node and the balance logged against the solutions sent.
+fn test_request_entity() {
+    use crate::mocknode::{MockNode, MockNodeConfig};
+
+    let public_key: PublicKey64 = [1, 2, 3, u64::MAX];
+    let submission_stats = SubmissionStats::new(&Default::default(), None);
+    submission_stats.record_sent(3);
+    submission_stats.record_answered_connection();
+    submission_stats.record_answered_connection();
+
+    let runtime = tokio::runtime::Builder::new_current_thread().enable_all().build().unwrap();
+    runtime.block_on(async {
+        let node = MockNode::start("127.0.0.1:0", MockNodeConfig { tick: 1234, balance: 5000, ..Default::default() }).await.unwrap();
+        let mut stream = tokio::net::TcpStream::connect(node.local_addr()).await.unwrap();
+        let entity = request_entity(&mut stream, public_key, 142).await.unwrap();
+        assert_eq!((entity.public_key, entity.get_balance(), entity.tick, entity.latest_incoming_transfer_tick), (public_key, 5000, 1234, 1234));
+        assert!(entity.is_in_spectrum());
+        assert_eq!(describe_balance(&entity, None, &submission_stats), "Balance of the ID: 5000 qu at tick 1234, 3 solutions sent and 2 connections answered this epoch");
+        assert_eq!(describe_balance(&entity, Some(6000), &submission_stats), "Balance of the ID: 5000 qu at tick 1234 (-1000 since the last check), 3 solutions sent and 2 connections answered this epoch");
+
+        let node = MockNode::start("127.0.0.1:0", MockNodeConfig { tick: 1234, ..Default::default() }).await.unwrap();
+        let mut stream = tokio::net::TcpStream::connect(node.local_addr()).await.unwrap();
+        let entity = request_entity(&mut stream, public_key, 142).await.unwrap();
+        assert!(!entity.is_in_spectrum());
+        assert_eq!(describe_balance(&entity, Some(6000), &submission_stats), "The ID is not in the spectrum at tick 1234, 3 solutions sent and 2 connections answered this epoch");
+    });
+}
@@ -0,0 +1,148 @@
+//! Accounting of the bytes sent over the last hour, with an optional cap for metered links.
+//!
+//! Solutions are always sent and counted. The other traffic, such as keep-alives and shares,
+//! is only sent while it fits in the cap, and deferred otherwise until older traffic leaves
+//! the window.
+
+use std::collections::VecDeque;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// Window of the cap.
+const WINDOW: Duration = Duration::from_secs(60 * 60);
+
+/// Duration of every slot of the window, the window moves one slot at a time.
+const SLOT: Duration = Duration::from_secs(60);
+
+/// Bytes sent over the last hour, and the cap on them.
+#[derive(Debug, Default)]
+pub struct BandwidthBudget {
+    /// Cap on the bytes sent per hour, `0` for no cap
+    max_bytes_per_hour: AtomicU64,
+    /// Bytes sent per slot, oldest first, with the start of the slot
+    slots: Mutex<VecDeque<(Instant, u64)>>,
+    /// Non-essential sends deferred because of the cap
+    deferred: AtomicU64,
+    /// Whether the last non-essential send was deferred, to log the changes only
+    is_capped: AtomicBool,
+}
+
+impl BandwidthBudget {
+    /// Creates a new `BandwidthBudget`.
+    ///
+    /// # Arguments
+    /// * `max_bytes_per_hour` - The cap on the bytes sent per hour, `None` for no cap
+    ///
+    /// # Returns
+    /// A new `BandwidthBudget`.
+    pub fn new(max_bytes_per_hour: Option<u64>) -> Self {
+        let budget = BandwidthBudget::default();
+        budget.set_max_bytes_per_hour(max_bytes_per_hour);
+        budget
+    }
+
+    /// Gets the cap on the bytes sent per hour, `None` for no cap.
+    pub fn get_max_bytes_per_hour(&self) -> Option<u64> {
+        Some(self.max_bytes_per_hour.load(Ordering::Relaxed)).filter(|max| *max > 0)
+    }
+
+    /// Sets the cap on the bytes sent per hour.
+    ///
+    /// # Arguments
+    /// * `max_bytes_per_hour` - The cap, `None` for no cap
+    pub fn set_max_bytes_per_hour(&self, max_bytes_per_hour: Option<u64>) {
+        self.max_bytes_per_hour.store(max_bytes_per_hour.unwrap_or_default(), Ordering::Relaxed);
+    }
+
+    /// Records bytes that were sent regardless of the cap, such as solutions.
+    ///
+    /// # Arguments
+    /// * `bytes` - The number of bytes sent
+    pub fn record(&self, bytes: usize) {
+        self.record_at(bytes, Instant::now());
+    }
+
+    /// Reserves bytes of non-essential traffic, as long as they fit in the cap.
+    ///
+    /// # Arguments
+    /// * `bytes` - The number of bytes to send
+    ///
+    /// # Returns
+    /// `true` if the bytes may be sent and were counted, `false` if they must be deferred.
+    pub fn try_consume(&self, bytes: usize) -> bool {
+        self.try_consume_at(bytes, Instant::now())
+    }
+
+    /// Gets the bytes sent over the last hour.
+    pub fn get_bytes_last_hour(&self) -> u64 {
+        self.bytes_at(Instant::now())
+    }
+
+    /// Gets the number of non-essential sends deferred because of the cap.
+    pub fn get_deferred(&self) -> u64 {
+        self.deferred.load(Ordering::Relaxed)
+    }
+
+    fn record_at(&self, bytes: usize, now: Instant) {
+        let mut slots = self.slots.lock().unwrap();
+        match slots.back_mut() {
+            Some((started_at, slot_bytes)) if now.saturating_duration_since(*started_at) < SLOT => *slot_bytes += bytes as u64,
+            _ => slots.push_back((now, bytes as u64)),
+        }
+    }
+
+    fn bytes_at(&self, now: Instant) -> u64 {
+        let mut slots = self.slots.lock().unwrap();
+        while slots.front().is_some_and(|(started_at, _)| now.saturating_duration_since(*started_at) >= WINDOW) {
+            slots.pop_front();
+        }
+        slots.iter().map(|(_, bytes)| bytes).sum()
+    }
+
+    fn try_consume_at(&self, bytes: usize, now: Instant) -> bool {
+        let Some(max_bytes_per_hour) = self.get_max_bytes_per_hour() else {
+            self.record_at(bytes, now);
+            return true;
+        };
+
+        let is_allowed = self.bytes_at(now) + bytes as u64 <= max_bytes_per_hour;
+        if self.is_capped.swap(!is_allowed, Ordering::Relaxed) == is_allowed {
+            if is_allowed {
+                log::info!("Back under the bandwidth cap of {max_bytes_per_hour} bytes per hour, resuming the deferred traffic");
+            } else {
+                log::warn!("Bandwidth cap of {max_bytes_per_hour} bytes per hour reached, deferring all but the solutions");
+            }
+        }
+
+        if is_allowed {
+            self.record_at(bytes, now);
+        } else {
+            self.deferred.fetch_add(1, Ordering::Relaxed);
+        }
+        is_allowed
+    }
+}
+
+#[test]
+/// Tests that solutions always pass, other traffic only under the cap, and that the window moves.
+fn test_bandwidth_budget() {
+    let now = Instant::now();
+    let budget = BandwidthBudget::new(Some(1000));
+
+    assert!(budget.try_consume_at(600, now));
+    assert!(!budget.try_consume_at(600, now));
+    budget.record_at(800, now + SLOT);
+    assert_eq!(budget.bytes_at(now + SLOT), 1400);
+    assert!(!budget.try_consume_at(8, now + SLOT));
+    assert_eq!(budget.get_deferred(), 2);
+
+    // The first slot leaves the window first
+    assert_eq!(budget.bytes_at(now + WINDOW), 800);
+    assert!(budget.try_consume_at(200, now + WINDOW));
+    assert_eq!(budget.bytes_at(now + WINDOW + SLOT), 200);
+
+    let unlimited = BandwidthBudget::default();
+    assert!(unlimited.try_consume_at(usize::MAX / 2, now));
+    assert_eq!(unlimited.get_max_bytes_per_hour(), None);
+}
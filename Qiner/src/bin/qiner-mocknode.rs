@@ -0,0 +1,73 @@
+use std::path::PathBuf;
+use clap::Parser;
+use lib::types::network::Protocol;
+use qiner::cli::LONG_VERSION;
+use qiner::mocknode::{DEFAULT_MOCK_NODE_LISTEN, MockNode, MockNodeConfig};
+
+/// A fake Qubic node recording the solutions of miners, for tests and local development.
+#[derive(Debug, Parser)]
+#[command(version = LONG_VERSION, about)]
+struct MockNodeCli {
+    /// Address to listen on for miners.
+    #[arg(long, default_value = DEFAULT_MOCK_NODE_LISTEN)]
+    listen: String,
+
+    /// Protocol version the packets must have, any if not set.
+    #[arg(long)]
+    protocol: Option<Protocol>,
+
+    /// Respond with the current tick info to every packet, as a node does.
+    #[arg(long)]
+    respond: bool,
+
+    /// Epoch of the tick info.
+    #[arg(long, default_value_t = 0)]
+    epoch: u16,
+
+    /// Tick of the tick info.
+    #[arg(long, default_value_t = 0)]
+    tick: u32,
+
+    /// Balance of every entity requested, `0` for public keys not in the spectrum.
+    #[arg(long, default_value_t = 0)]
+    balance: u64,
+
+    /// File the received solutions are appended to, one `<public key> <nonce>` hex pair per line.
+    #[arg(long)]
+    record: Option<PathBuf>,
+}
+
+#[tokio::main]
+async fn main() {
+    let cli = MockNodeCli::parse();
+    qiner::logger::init();
+
+    let config = MockNodeConfig {
+        protocol: cli.protocol,
+        respond: cli.respond,
+        epoch: cli.epoch,
+        tick: cli.tick,
+        balance: cli.balance,
+        record_file: cli.record,
+    };
+    let node = match MockNode::start(&cli.listen, config).await {
+        Ok(node) => node,
+        Err(err) => {
+            log::error!("Failed to listen on {}: {:?}", cli.listen, err);
+            std::process::exit(1);
+        }
+    };
+    log::info!("Mock node listening on {}", node.local_addr());
+
+    if let Err(err) = tokio::signal::ctrl_c().await {
+        log::error!("Failed to install the Ctrl-C handler: {:?}", err);
+    }
+    let stats = node.stats();
+    log::info!(
+        "mock node: {} connections | {} packets | {} solutions | {} invalid",
+        stats.connections,
+        stats.packets,
+        stats.solutions.len(),
+        stats.invalid.len()
+    );
+}
@@ -0,0 +1,339 @@
+//! Capture of the messages exchanged with the nodes, for diagnosing why a node ignores submissions.
+//!
+//! With `PACKET_CAPTURE` set, every message written to or read from a peer is appended to a binary
+//! file with the moment and the peer, and `qiner dump` prints it back field by field. The file
+//! starts with `QCAP` and a version byte, then holds one record per write or read: the microseconds
+//! since the Unix epoch (u64), the direction (u8, `0` sent, `1` received), the peer (u16 length and
+//! UTF-8) and the bytes (u32 length and data), integers little endian.
+
+use std::fmt;
+use std::fs::{File, OpenOptions};
+use std::io::{self, BufReader, BufWriter, Read, Write};
+use std::mem::size_of;
+use std::net::Ipv4Addr;
+use std::path::Path;
+use std::sync::{Mutex, RwLock};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use lib::types::network::protocols::{BROADCAST_MESSAGE, EXCHANGE_PUBLIC_PEERS, REQUEST_CURRENT_TICK_INFO, RESPOND_CURRENT_TICK_INFO};
+use lib::types::network::Type;
+use lib::types::PublicKey64;
+use crate::converters::get_id_from_public_key_64;
+use crate::journal::nonce_to_hex;
+use crate::network::{Packet, RequestResponseHeader, TickInfo};
+
+/// First bytes of a capture file.
+pub const CAPTURE_MAGIC: &[u8; 4] = b"QCAP";
+
+/// Version of the capture format, the byte after the magic.
+pub const CAPTURE_VERSION: u8 = 1;
+
+/// Size of the header every message starts with.
+const HEADER_SIZE: usize = size_of::<RequestResponseHeader>();
+
+/// The capture every message is recorded in, `None` until `init` is called.
+static CAPTURE: RwLock<Option<PacketCapture>> = RwLock::new(None);
+
+/// Whether a message was written to or read from the peer.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Direction {
+    Sent,
+    Received,
+}
+
+impl Direction {
+    /// Gets the name of the direction
+    pub fn name(&self) -> &'static str {
+        match self {
+            Direction::Sent => "sent",
+            Direction::Received => "received",
+        }
+    }
+}
+
+impl fmt::Display for Direction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+/// A write to or a read from a peer.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CaptureRecord {
+    /// Moment of the write or the read
+    pub at: SystemTime,
+    pub direction: Direction,
+    /// Address of the peer, as `ip:port`
+    pub peer: String,
+    /// The bytes written or read, one or more messages
+    pub data: Vec<u8>,
+}
+
+impl CaptureRecord {
+    /// Writes the record in the capture format.
+    fn write_to(&self, writer: &mut impl Write) -> io::Result<()> {
+        let micros = self.at.duration_since(UNIX_EPOCH).unwrap_or_default().as_micros() as u64;
+        let peer = &self.peer.as_bytes()[..self.peer.len().min(u16::MAX as usize)];
+        writer.write_all(&micros.to_le_bytes())?;
+        writer.write_all(&[if self.direction == Direction::Sent { 0 } else { 1 }])?;
+        writer.write_all(&(peer.len() as u16).to_le_bytes())?;
+        writer.write_all(peer)?;
+        writer.write_all(&(self.data.len() as u32).to_le_bytes())?;
+        writer.write_all(&self.data)
+    }
+
+    /// Reads a record in the capture format.
+    ///
+    /// # Returns
+    /// The record, `None` at the end of the capture.
+    fn read_from(reader: &mut impl Read) -> io::Result<Option<Self>> {
+        let mut micros = [0u8; 8];
+        match reader.read_exact(&mut micros) {
+            Ok(_) => {}
+            Err(err) if err.kind() == io::ErrorKind::UnexpectedEof => return Ok(None),
+            Err(err) => return Err(err),
+        }
+        let mut direction = [0u8; 1];
+        reader.read_exact(&mut direction)?;
+        let mut peer_len = [0u8; 2];
+        reader.read_exact(&mut peer_len)?;
+        let mut peer = vec![0u8; u16::from_le_bytes(peer_len) as usize];
+        reader.read_exact(&mut peer)?;
+        let mut data_len = [0u8; 4];
+        reader.read_exact(&mut data_len)?;
+        let mut data = vec![0u8; u32::from_le_bytes(data_len) as usize];
+        reader.read_exact(&mut data)?;
+
+        Ok(Some(CaptureRecord {
+            at: UNIX_EPOCH + Duration::from_micros(u64::from_le_bytes(micros)),
+            direction: if direction[0] == 0 { Direction::Sent } else { Direction::Received },
+            peer: String::from_utf8_lossy(&peer).into_owned(),
+            data,
+        }))
+    }
+}
+
+/// A capture file records are appended to.
+#[derive(Debug)]
+pub struct PacketCapture {
+    writer: Mutex<BufWriter<File>>,
+}
+
+impl PacketCapture {
+    /// Opens a capture file, appending to it if it is already a capture.
+    ///
+    /// # Arguments
+    /// * `path` - The capture file, created if missing.
+    ///
+    /// # Returns
+    /// The capture, or the error of the file, `InvalidData` if the file is not a capture.
+    pub fn open(path: &Path) -> io::Result<Self> {
+        let mut file = OpenOptions::new().read(true).append(true).create(true).open(path)?;
+        if file.metadata()?.len() == 0 {
+            file.write_all(CAPTURE_MAGIC)?;
+            file.write_all(&[CAPTURE_VERSION])?;
+        } else {
+            check_magic(&mut File::open(path)?)?;
+        }
+        Ok(PacketCapture { writer: Mutex::new(BufWriter::new(file)) })
+    }
+
+    /// Appends a record, flushed at once so a crash keeps it.
+    ///
+    /// # Arguments
+    /// * `record` - The write or the read.
+    pub fn record(&self, record: &CaptureRecord) -> io::Result<()> {
+        let mut writer = self.writer.lock().unwrap();
+        record.write_to(&mut *writer)?;
+        writer.flush()
+    }
+}
+
+/// Starts capturing the messages exchanged with the peers.
+///
+/// # Arguments
+/// * `path` - The capture file, appended to if it exists.
+pub fn init(path: &Path) -> io::Result<()> {
+    *CAPTURE.write().unwrap() = Some(PacketCapture::open(path)?);
+    Ok(())
+}
+
+/// Records the messages written to a peer, if capturing.
+///
+/// # Arguments
+/// * `peer` - The address of the peer.
+/// * `data` - The bytes written.
+pub fn record_sent(peer: &str, data: &[u8]) {
+    record(Direction::Sent, peer, data);
+}
+
+/// Records the messages read from a peer, if capturing.
+///
+/// # Arguments
+/// * `peer` - The address of the peer.
+/// * `data` - The bytes read.
+pub fn record_received(peer: &str, data: &[u8]) {
+    record(Direction::Received, peer, data);
+}
+
+fn record(direction: Direction, peer: &str, data: &[u8]) {
+    if let Some(capture) = CAPTURE.read().unwrap().as_ref() {
+        let record = CaptureRecord { at: SystemTime::now(), direction, peer: peer.to_string(), data: data.to_vec() };
+        if let Err(err) = capture.record(&record) {
+            log::warn!("Failed to capture {} bytes {direction} with {peer}: {:?}", data.len(), err);
+        }
+    }
+}
+
+/// Checks that a file starts with the magic and the version of a capture.
+fn check_magic(reader: &mut impl Read) -> io::Result<()> {
+    let mut magic = [0u8; 5];
+    reader.read_exact(&mut magic).map_err(|_| io::Error::new(io::ErrorKind::InvalidData, "not a packet capture"))?;
+    if &magic[..4] != CAPTURE_MAGIC {
+        return Err(io::Error::new(io::ErrorKind::InvalidData, "not a packet capture"));
+    }
+    if magic[4] != CAPTURE_VERSION {
+        return Err(io::Error::new(io::ErrorKind::InvalidData, format!("capture version {} instead of {CAPTURE_VERSION}", magic[4])));
+    }
+    Ok(())
+}
+
+/// Reads every record of a capture file.
+///
+/// # Arguments
+/// * `path` - The capture file.
+///
+/// # Returns
+/// The records in the order they were captured, or the error of the file.
+pub fn read_capture(path: &Path) -> io::Result<Vec<CaptureRecord>> {
+    let mut reader = BufReader::new(File::open(path)?);
+    check_magic(&mut reader)?;
+    let mut records = Vec::new();
+    while let Some(record) = CaptureRecord::read_from(&mut reader)? {
+        records.push(record);
+    }
+    Ok(records)
+}
+
+/// Gets the name of a message type.
+fn type_name(r#type: Type) -> &'static str {
+    match r#type {
+        EXCHANGE_PUBLIC_PEERS => "exchange public peers",
+        BROADCAST_MESSAGE => "broadcast message",
+        REQUEST_CURRENT_TICK_INFO => "request current tick info",
+        RESPOND_CURRENT_TICK_INFO => "respond current tick info",
+        _ => "unknown",
+    }
+}
+
+fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{byte:02x}")).collect()
+}
+
+fn to_id(bytes: &[u8]) -> String {
+    let public_key: PublicKey64 = core::array::from_fn(|idx| u64::from_le_bytes(bytes[idx * 8..idx * 8 + 8].try_into().unwrap()));
+    if public_key == PublicKey64::default() {
+        return "none".to_string();
+    }
+    let mut id = [0u8; 60];
+    get_id_from_public_key_64(&public_key, &mut id);
+    String::from_utf8_lossy(&id).into_owned()
+}
+
+/// Describes the messages of a write or a read field by field.
+///
+/// # Arguments
+/// * `data` - The bytes, one or more messages back to back.
+///
+/// # Returns
+/// One line per message and one indented line per field of its payload.
+pub fn describe(data: &[u8]) -> Vec<String> {
+    let mut lines = Vec::new();
+    let mut rest = data;
+    while !rest.is_empty() {
+        let Some(header) = RequestResponseHeader::from_bytes(rest) else {
+            lines.push(format!("{} bytes left, too short for a header: {}", rest.len(), to_hex(rest)));
+            break;
+        };
+        let size = header.get_size();
+        lines.push(format!(
+            "{} ({}): size {size}, protocol {}, dejavu {:06x}",
+            type_name(header.get_type()), header.get_type(), header.get_protocol(), header.get_dejavu()
+        ));
+        if size < HEADER_SIZE {
+            lines.push(format!("  size below the {HEADER_SIZE} bytes of the header: {}", to_hex(rest)));
+            break;
+        }
+        if size > rest.len() {
+            // E.g. a header read alone, its payload being skipped
+            lines.push(format!("  {} bytes of the message not captured, {} bytes captured: {}", size - rest.len(), rest.len(), to_hex(rest)));
+            break;
+        }
+
+        let (message, next) = rest.split_at(size);
+        let payload = &message[HEADER_SIZE..];
+        match header.get_type() {
+            BROADCAST_MESSAGE if payload.len() >= 96 => {
+                lines.push(format!("  source: {}", to_id(&payload[..32])));
+                lines.push(format!("  destination: {}", to_id(&payload[32..64])));
+                lines.push(format!("  gamming nonce: {}", to_hex(&payload[64..96])));
+                match Packet::validate_solution(message, None) {
+                    Ok((_, nonce)) => lines.push(format!("  solution nonce: {}", nonce_to_hex(&nonce))),
+                    Err(err) => lines.push(format!("  not a solution: {err}")),
+                }
+                if let Some(signature) = payload.len().checked_sub(64).map(|start| &payload[start..]) {
+                    lines.push(format!("  signature: {}", to_hex(signature)));
+                }
+            }
+            EXCHANGE_PUBLIC_PEERS if payload.len() % 4 == 0 => {
+                let peers = payload.chunks_exact(4).map(|octets| Ipv4Addr::new(octets[0], octets[1], octets[2], octets[3]).to_string()).collect::<Vec<_>>();
+                lines.push(format!("  peers: {}", peers.join(", ")));
+            }
+            RESPOND_CURRENT_TICK_INFO => match TickInfo::from_bytes(payload) {
+                Some(tick_info) => lines.push(format!(
+                    "  epoch {}, tick {}, initial tick {}, tick duration {} ms, votes {} aligned {} misaligned",
+                    tick_info.epoch, tick_info.tick, tick_info.initial_tick, tick_info.tick_duration,
+                    tick_info.number_of_aligned_votes, tick_info.number_of_misaligned_votes
+                )),
+                None => lines.push(format!("  payload: {}", to_hex(payload))),
+            },
+            _ if !payload.is_empty() => lines.push(format!("  payload: {}", to_hex(payload))),
+            _ => {}
+        }
+        rest = next;
+    }
+    lines
+}
+
+#[test]
+/// Tests that records read back as written and that their messages are described field by field.
+fn test_capture() {
+    use crate::messages::{Request, RequestTickInfo};
+
+    let path = std::env::temp_dir().join(format!("qiner-capture-test-{}.qcap", std::process::id()));
+    let _ = std::fs::remove_file(&path);
+    let public_key: PublicKey64 = [1, 2, 3, 4];
+    let nonce = [5, 6, 7, 8];
+    let solution = Packet::solutions_to_bytes(142, &public_key, [&nonce]);
+    let records = [
+        CaptureRecord { at: UNIX_EPOCH + Duration::from_micros(1_700_000_000_000_001), direction: Direction::Sent, peer: "10.0.0.1:21841".to_string(), data: solution.clone() },
+        CaptureRecord { at: UNIX_EPOCH + Duration::from_micros(1_700_000_000_000_002), direction: Direction::Received, peer: "10.0.0.1:21841".to_string(), data: RequestTickInfo.to_bytes(142).unwrap() },
+    ];
+    let capture = PacketCapture::open(&path).unwrap();
+    capture.record(&records[0]).unwrap();
+    drop(capture);
+    // A second run appends
+    PacketCapture::open(&path).unwrap().record(&records[1]).unwrap();
+    assert_eq!(read_capture(&path).unwrap(), records);
+    std::fs::write(&path, b"not a capture").unwrap();
+    assert_eq!(PacketCapture::open(&path).unwrap_err().kind(), io::ErrorKind::InvalidData);
+    std::fs::remove_file(&path).unwrap();
+
+    let lines = describe(&solution);
+    assert!(lines[0].starts_with("broadcast message (1): size 200, protocol 142"));
+    assert!(lines.contains(&format!("  solution nonce: {}", nonce_to_hex(&nonce))));
+    assert!(lines.contains(&"  source: none".to_string()));
+    let lines = describe(&[&RequestTickInfo.to_bytes(142).unwrap()[..], &[1, 2]].concat());
+    assert!(lines[0].starts_with("request current tick info (27): size 8"));
+    assert_eq!(lines[1], "2 bytes left, too short for a header: 0102");
+    assert_eq!(describe(&solution[..HEADER_SIZE])[1], format!("  192 bytes of the message not captured, 8 bytes captured: {}", to_hex(&solution[..HEADER_SIZE])));
+}
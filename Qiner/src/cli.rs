@@ -0,0 +1,271 @@
+use std::path::PathBuf;
+use std::time::Duration;
+use clap::{Args, Parser, Subcommand};
+use crate::config_file::DEFAULT_STRUCTURED_CONFIG_FILE;
+use crate::keys::KeyRole;
+use crate::profile::Profile;
+use crate::proxy::{DEFAULT_PROXY_LISTEN, DEFAULT_PROXY_SPILL_FILE};
+use crate::stats::Verbosity;
+use crate::transactions::{DEFAULT_ATTEMPTS, DEFAULT_TICK_OFFSET};
+use crate::wizard::DEFAULT_CONFIG_FILE;
+
+/// Version of Qiner with the commit and the date it was built from.
+pub const LONG_VERSION: &str = concat!(env!("CARGO_PKG_VERSION"), " (", env!("QINER_GIT_HASH"), ", built ", env!("QINER_BUILD_DATE"), ")");
+
+/// Command line options of Qiner.
+///
+/// Mining settings are read from the environment (or the `qiner.toml` or `.env` file); the command line
+/// only selects how Qiner runs.
+#[derive(Debug, Default, Parser)]
+#[command(version = LONG_VERSION, about)]
+pub struct Cli {
+    /// Show a live dashboard instead of log output.
+    #[arg(long)]
+    pub tui: bool,
+
+    /// Exit when the miner becomes unhealthy (no iterations or no successful connect for too long).
+    #[arg(long)]
+    pub health_exit_on_stall: bool,
+
+    /// Exit when mining stays degraded (hashrate or solution rate below the ALERT_* limits).
+    #[arg(long)]
+    pub exit_on_degradation: bool,
+
+    /// Mine with a tiny solution threshold against a local mock node, to exercise the submission in seconds.
+    #[arg(long)]
+    pub simulate: bool,
+
+    /// Interval between two stats lines, e.g. `10s`, `5m` or `500ms`, overriding STATS_INTERVAL.
+    #[arg(long, value_parser = parse_interval)]
+    pub stats_interval: Option<Duration>,
+
+    /// Only log the progress when solutions are found or sent.
+    #[arg(long, conflicts_with = "verbose")]
+    pub quiet: bool,
+
+    /// Also log the iterations of every thread.
+    #[arg(long)]
+    pub verbose: bool,
+
+    /// Tuning preset, `eco`, `balanced` or `max`, filling the settings the environment leaves unset.
+    #[arg(long, value_parser = Profile::parse)]
+    pub profile: Option<Profile>,
+
+    /// Run something else than the miner.
+    #[command(subcommand)]
+    pub command: Option<Command>,
+}
+
+impl Cli {
+    /// Gets how much of the progress the console logs, that of the profile without `--quiet` or `--verbose`.
+    pub fn get_verbosity(&self) -> Verbosity {
+        match (self.quiet, self.verbose) {
+            (true, _) => Verbosity::Quiet,
+            (_, true) => Verbosity::Verbose,
+            _ => self.profile.map_or(Verbosity::Normal, |profile| profile.verbosity()),
+        }
+    }
+}
+
+/// Subcommands of Qiner.
+#[derive(Debug, Subcommand)]
+pub enum Command {
+    /// Create the configuration interactively.
+    Init(InitArgs),
+    /// Convert a legacy `.env` file to the structured `qiner.toml`, after validating its settings.
+    MigrateConfig(MigrateConfigArgs),
+    /// Generate a random seed and print its identity.
+    GenerateId(GenerateIdArgs),
+    /// Convert between a seed, an identity and a hex public key, validating the checksum.
+    Convert(ConvertArgs),
+    /// Validate the configuration, probe the peers and check the CPU features, without mining.
+    Check,
+    /// Run the known answer tests of the cryptographic primitives and the score.
+    Selftest,
+    /// Measure the throughput of the random sources of the nonces and packets.
+    Bench,
+    /// Forward the solutions of LAN miners to `SERVER_IP`/`SERVER_PORT` over a single connection.
+    Proxy(ProxyArgs),
+    /// Submit recorded solutions again, e.g. after a node outage or to move to another node.
+    Replay(ReplayArgs),
+    /// Print a `PACKET_CAPTURE` file message by message, field by field.
+    Dump(DumpArgs),
+    /// Sign a transfer with the operator or mining seed of the keystore, or the seed read from the standard input, and send it to the nodes.
+    Send(SendArgs),
+    /// Manage the encrypted seeds of the keystore.
+    #[command(subcommand)]
+    Keys(KeysCommand),
+}
+
+/// Subcommands of `keys`.
+#[derive(Debug, Subcommand)]
+pub enum KeysCommand {
+    /// Encrypt a seed read from the standard input into the keystore, replacing the seed of its role.
+    Import(KeysImportArgs),
+    /// List the roles with a seed in the keystore and their IDs, without decrypting them.
+    List,
+}
+
+/// Options of the `keys import` subcommand.
+#[derive(Debug, Args)]
+pub struct KeysImportArgs {
+    /// Role of the seed: `operator` signs transactions and control messages, `mining` is the ID mined for.
+    #[arg(long, value_parser = KeyRole::parse)]
+    pub role: KeyRole,
+}
+
+/// Options of the `proxy` subcommand.
+#[derive(Debug, Args)]
+pub struct ProxyArgs {
+    /// Address to listen on for LAN miners.
+    #[arg(long, default_value = DEFAULT_PROXY_LISTEN)]
+    pub listen: String,
+
+    /// UDP address to also listen on for LAN miners submitting over QUIC, needs the `quic` feature.
+    #[arg(long)]
+    pub quic_listen: Option<String>,
+
+    /// File the solutions not forwarded yet are spilled to on exit, and reloaded from on start.
+    #[arg(long, default_value = DEFAULT_PROXY_SPILL_FILE)]
+    pub spill_file: PathBuf,
+
+    /// Interval between two keep-alives of the idle upstream connection, e.g. `30s` or `2m`.
+    #[arg(long, default_value = "30s", value_parser = parse_interval)]
+    pub keep_alive: Duration,
+
+    /// Never send keep-alives, the upstream connection is then only opened to forward solutions.
+    #[arg(long)]
+    pub no_keep_alive: bool,
+}
+
+/// Options of the `replay` subcommand.
+#[derive(Debug, Args)]
+pub struct ReplayArgs {
+    /// File of the solutions: a spill file, a proxy spill file, a mock node record or a JSON array.
+    #[arg(long)]
+    pub file: PathBuf,
+
+    /// Node the solutions are submitted to, the first of `SERVER_IP`/`SERVER_PORT` if not set.
+    #[arg(long)]
+    pub peer: Option<String>,
+}
+
+/// Options of the `dump` subcommand.
+#[derive(Debug, Args)]
+pub struct DumpArgs {
+    /// The capture file, as written with `PACKET_CAPTURE`.
+    #[arg(long)]
+    pub file: PathBuf,
+}
+
+/// Options of the `send` subcommand.
+#[derive(Debug, Args)]
+pub struct SendArgs {
+    /// Identity receiving the amount, 60 uppercase letters.
+    #[arg(long)]
+    pub to: String,
+
+    /// Amount transferred, in qu.
+    #[arg(long, value_parser = clap::value_parser!(i64).range(1..))]
+    pub amount: i64,
+
+    /// Tick of the first attempt, `--tick-offset` ticks after the current tick of the nodes if not set.
+    #[arg(long)]
+    pub tick: Option<u32>,
+
+    /// Number of ticks between the current tick of the nodes and the tick of the transaction.
+    #[arg(long, default_value_t = DEFAULT_TICK_OFFSET)]
+    pub tick_offset: u32,
+
+    /// Number of ticks the transaction is scheduled for, each later than the previous, before giving up.
+    #[arg(long, default_value_t = DEFAULT_ATTEMPTS, value_parser = clap::builder::RangedU64ValueParser::<usize>::new().range(1..))]
+    pub attempts: usize,
+
+    /// Node the transaction is sent to, every node of `SERVER_IP`/`SERVER_PORT` if not set.
+    #[arg(long)]
+    pub peer: Option<String>,
+}
+
+/// Options of the `init` subcommand.
+#[derive(Debug, Args)]
+pub struct InitArgs {
+    /// File the configuration is written to.
+    #[arg(long, default_value = DEFAULT_CONFIG_FILE)]
+    pub config: PathBuf,
+}
+
+/// Options of the `migrate-config` subcommand.
+#[derive(Debug, Args)]
+pub struct MigrateConfigArgs {
+    /// The legacy configuration file, left as it is.
+    #[arg(long, default_value = DEFAULT_CONFIG_FILE)]
+    pub env_file: PathBuf,
+
+    /// The structured configuration file to write, which must not exist yet.
+    #[arg(long, default_value = DEFAULT_STRUCTURED_CONFIG_FILE)]
+    pub config: PathBuf,
+}
+
+/// Options of the `generate-id` subcommand.
+#[derive(Debug, Args)]
+pub struct GenerateIdArgs {
+    /// Write the identity as `ID` into the configuration file. The seed is never written.
+    #[arg(long)]
+    pub write: bool,
+
+    /// Configuration file the identity is written to.
+    #[arg(long, default_value = DEFAULT_CONFIG_FILE)]
+    pub config: PathBuf,
+}
+
+/// Options of the `convert` subcommand.
+#[derive(Debug, Args)]
+pub struct ConvertArgs {
+    /// A 55-letter lowercase seed, a 60-letter uppercase identity or a 64-character hex public key.
+    pub value: String,
+}
+
+/// Parses an interval given on the command line.
+///
+/// # Arguments
+/// * `value` - A number followed by `ms`, `s`, `m` or `h`, seconds if there is no unit.
+///
+/// # Returns
+/// The interval, or why it is invalid.
+pub fn parse_interval(value: &str) -> Result<Duration, String> {
+    let value = value.trim();
+    let (number, unit) = value.split_at(value.find(|character: char| !character.is_ascii_digit()).unwrap_or(value.len()));
+    let number = number.parse::<u64>().map_err(|_| format!("expected a number followed by ms, s, m or h, got {value:?}"))?;
+    let interval = match unit {
+        "ms" => Duration::from_millis(number),
+        "" | "s" => Duration::from_secs(number),
+        "m" => Duration::from_secs(number * 60),
+        "h" => Duration::from_secs(number * 60 * 60),
+        _ => return Err(format!("unknown unit {unit:?}, expected ms, s, m or h")),
+    };
+    if interval.is_zero() {
+        return Err("the interval must not be zero".to_string());
+    }
+    Ok(interval)
+}
+
+#[test]
+/// Tests the parsing of the intervals and the verbosity options.
+fn test_stats_options() {
+    assert_eq!(parse_interval("10s"), Ok(Duration::from_secs(10)));
+    assert_eq!(parse_interval("5m"), Ok(Duration::from_secs(300)));
+    assert_eq!(parse_interval("500ms"), Ok(Duration::from_millis(500)));
+    assert_eq!(parse_interval("30"), Ok(Duration::from_secs(30)));
+    assert!(parse_interval("0s").is_err());
+    assert!(parse_interval("10 days").is_err());
+    assert!(parse_interval("s").is_err());
+
+    let cli = Cli::try_parse_from(["qiner", "--stats-interval", "1m", "--quiet"]).unwrap();
+    assert_eq!(cli.stats_interval, Some(Duration::from_secs(60)));
+    assert_eq!(cli.get_verbosity(), Verbosity::Quiet);
+    assert_eq!(Cli::try_parse_from(["qiner", "--verbose"]).unwrap().get_verbosity(), Verbosity::Verbose);
+    assert!(Cli::try_parse_from(["qiner", "--quiet", "--verbose"]).is_err());
+    assert_eq!(Cli::try_parse_from(["qiner", "--profile", "eco"]).unwrap().get_verbosity(), Verbosity::Quiet);
+    assert_eq!(Cli::try_parse_from(["qiner", "--profile", "eco", "--verbose"]).unwrap().get_verbosity(), Verbosity::Verbose);
+    assert!(Cli::try_parse_from(["qiner", "--profile", "turbo"]).is_err());
+}
@@ -0,0 +1,260 @@
+//! Detection of a local clock drifting away from the network.
+//!
+//! Nodes report their current tick and the duration of the last one, not the time, so the
+//! time elapsed on the network since a first tick info is estimated from the ticks that
+//! passed, at the average tick duration the node reported meanwhile. A wall clock running
+//! ahead of or behind that estimate makes the timestamps of solutions, traces and reports
+//! disagree with the epochs the operators compare them to.
+
+use std::io;
+use std::mem::size_of;
+use std::sync::atomic::{AtomicBool, AtomicI64, AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, SystemTime};
+use crate::settings::Settings;
+use lib::types::network::protocols::RESPOND_CURRENT_TICK_INFO;
+use lib::types::network::{Protocol, Type};
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+use crate::connection::keep_alive_to_bytes;
+use crate::network::{NetStats, RequestResponseHeader, TickInfo};
+use crate::peers::QUIC_SCHEME;
+use crate::socks::{self, SocksProxy};
+use crate::targets::TargetSelector;
+
+/// Default skew beyond which the clock is reported.
+pub const DEFAULT_MAX_CLOCK_SKEW: Duration = Duration::from_secs(120);
+
+/// Interval between two tick info requests.
+const CLOCK_CHECK_INTERVAL: Duration = Duration::from_secs(5 * 60);
+
+/// Time to wait for the tick info of a node.
+const TICK_INFO_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Ticks that must pass before the skew is estimated, so a few slow ticks do not skew the average.
+const MIN_TICKS: u32 = 300;
+
+/// Largest message skipped while waiting for a response, bigger ones end the request.
+const MAX_SKIPPED_SIZE: usize = 64 * 1024;
+
+/// Size of the header every message starts with.
+const HEADER_SIZE: usize = size_of::<RequestResponseHeader>();
+
+/// The first tick info of the epoch, which the progression of the ticks is measured from.
+#[derive(Debug, Clone, Copy)]
+struct Anchor {
+    observed_at: SystemTime,
+    epoch: u16,
+    tick: u32,
+    /// Sum of the tick durations reported since, in milliseconds
+    tick_durations_ms: u64,
+    /// Number of tick durations reported since
+    samples: u64,
+}
+
+/// Skew of the local clock against the progression of the ticks.
+#[derive(Debug, Default)]
+pub struct ClockSkew {
+    /// Skew beyond which the clock is reported, in milliseconds, `0` for no report
+    max_skew_ms: AtomicU64,
+    anchor: Mutex<Option<Anchor>>,
+    /// Last estimated skew, in milliseconds, positive when the local clock runs ahead
+    skew_ms: AtomicI64,
+    is_measured: AtomicBool,
+    /// Whether the last skew was beyond the maximum, to log the changes only
+    is_skewed: AtomicBool,
+}
+
+impl ClockSkew {
+    /// Creates a new `ClockSkew`.
+    ///
+    /// # Arguments
+    /// * `max_skew` - The skew beyond which the clock is reported, `None` to never report it
+    ///
+    /// # Returns
+    /// A new `ClockSkew`.
+    pub fn new(max_skew: Option<Duration>) -> Self {
+        let clock = ClockSkew::default();
+        clock.set_max_skew(max_skew);
+        clock
+    }
+
+    /// Gets the skew beyond which the clock is reported, `None` if it is never reported.
+    pub fn get_max_skew(&self) -> Option<Duration> {
+        Some(self.max_skew_ms.load(Ordering::Relaxed)).filter(|max| *max > 0).map(Duration::from_millis)
+    }
+
+    /// Sets the skew beyond which the clock is reported.
+    ///
+    /// # Arguments
+    /// * `max_skew` - The skew, `None` to never report it
+    pub fn set_max_skew(&self, max_skew: Option<Duration>) {
+        self.max_skew_ms.store(max_skew.map_or(0, |max| max.as_millis() as u64), Ordering::Relaxed);
+    }
+
+    /// Gets the last estimated skew in milliseconds, positive when the local clock runs ahead.
+    ///
+    /// # Returns
+    /// The skew, `None` until enough ticks passed to estimate it.
+    pub fn get_skew_ms(&self) -> Option<i64> {
+        self.is_measured.load(Ordering::Relaxed).then(|| self.skew_ms.load(Ordering::Relaxed))
+    }
+
+    /// Checks if the last estimated skew was beyond the maximum.
+    pub fn is_skewed(&self) -> bool {
+        self.is_skewed.load(Ordering::Relaxed)
+    }
+
+    /// Compares the local clock to the progression of the ticks since the first tick info of the epoch.
+    ///
+    /// # Arguments
+    /// * `tick_info` - The tick info a node just answered
+    pub fn observe(&self, tick_info: &TickInfo) {
+        self.observe_at(tick_info, SystemTime::now());
+    }
+
+    fn observe_at(&self, tick_info: &TickInfo, now: SystemTime) {
+        let mut anchor = self.anchor.lock().unwrap();
+        // A new epoch, or a node behind the first one, starts the measure over
+        let Some(current) = anchor.as_mut().filter(|anchor| anchor.epoch == tick_info.epoch && anchor.tick <= tick_info.tick) else {
+            *anchor = Some(Anchor { observed_at: now, epoch: tick_info.epoch, tick: tick_info.tick, tick_durations_ms: 0, samples: 0 });
+            return;
+        };
+        if tick_info.tick_duration > 0 {
+            current.tick_durations_ms += tick_info.tick_duration as u64;
+            current.samples += 1;
+        }
+
+        let ticks = tick_info.tick - current.tick;
+        if ticks < MIN_TICKS || current.samples == 0 {
+            return;
+        }
+        let expected_ms = ticks as i64 * (current.tick_durations_ms / current.samples) as i64;
+        let elapsed_ms = match now.duration_since(current.observed_at) {
+            Ok(elapsed) => elapsed.as_millis() as i64,
+            Err(err) => -(err.duration().as_millis() as i64),
+        };
+        let skew_ms = elapsed_ms - expected_ms;
+        self.skew_ms.store(skew_ms, Ordering::Relaxed);
+        self.is_measured.store(true, Ordering::Relaxed);
+
+        let Some(max_skew) = self.get_max_skew() else {
+            return;
+        };
+        let is_skewed = skew_ms.unsigned_abs() > max_skew.as_millis() as u64;
+        if self.is_skewed.swap(is_skewed, Ordering::Relaxed) != is_skewed {
+            let direction = if skew_ms > 0 { "ahead of" } else { "behind" };
+            let skew = Duration::from_millis(skew_ms.unsigned_abs());
+            if is_skewed {
+                log::warn!("The local clock runs {skew:?} {direction} the ticks of epoch {}, beyond {max_skew:?}: check NTP, timestamps of solutions and reports are off", tick_info.epoch);
+            } else {
+                log::info!("The local clock is back within {max_skew:?} of the ticks, {skew:?} {direction}");
+            }
+        }
+    }
+}
+
+/// Requests the current tick info of a node, skipping the other messages it sends meanwhile.
+///
+/// # Arguments
+/// * `stream` - The connection to the node
+/// * `protocol` - The protocol version of the request
+///
+/// # Returns
+/// The tick info, or the error of the connection or of the framing.
+pub async fn request_tick_info(stream: &mut (impl AsyncRead + AsyncWrite + Unpin), protocol: Protocol) -> io::Result<TickInfo> {
+    stream.write_all(&keep_alive_to_bytes(protocol)).await?;
+    let payload = read_response(stream, RESPOND_CURRENT_TICK_INFO).await?;
+    TickInfo::from_bytes(&payload).ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "short tick info"))
+}
+
+/// Reads the messages of a node until one of the expected type, skipping the others.
+///
+/// # Arguments
+/// * `stream` - The connection to the node
+/// * `response_type` - The type of the expected message
+///
+/// # Returns
+/// The payload of the message, after the header, or the error of the connection or of the framing.
+pub async fn read_response(stream: &mut (impl AsyncRead + Unpin), response_type: Type) -> io::Result<Vec<u8>> {
+    loop {
+        let mut header = [0u8; HEADER_SIZE];
+        stream.read_exact(&mut header).await?;
+        let header = RequestResponseHeader::from_bytes(&header).ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "invalid header"))?;
+        let size = header.get_size();
+        if !(HEADER_SIZE..=MAX_SKIPPED_SIZE).contains(&size) {
+            return Err(io::Error::new(io::ErrorKind::InvalidData, format!("message of {size} bytes")));
+        }
+        let mut payload = vec![0u8; size - HEADER_SIZE];
+        stream.read_exact(&mut payload).await?;
+        if header.get_type() == response_type {
+            return Ok(payload);
+        }
+    }
+}
+
+/// Asynchronous task requesting the tick info of the best node periodically, recording the epoch
+/// and feeding the clock skew detection.
+///
+/// The requests are deferred by the bandwidth cap, and nodes reached over QUIC are skipped.
+///
+/// # Arguments
+/// * `targets` - The shared target selector, whose best node is asked
+/// * `socks` - The SOCKS proxy the node is reached through, `None` to connect directly
+/// * `net_stats` - Shared network counters, holding the epoch and the clock skew detection
+/// * `settings` - The settings, giving the protocol version of the requests to each node
+pub async fn tick_info_task(targets: Arc<Mutex<TargetSelector>>, socks: Option<SocksProxy>, net_stats: Arc<NetStats>, settings: Arc<Settings>) {
+    loop {
+        let addr = targets.lock().unwrap().get_nodes().best().to_string();
+        if !addr.is_empty() && !addr.starts_with(QUIC_SCHEME) && net_stats.get_bandwidth().try_consume(HEADER_SIZE) {
+            let result = tokio::time::timeout(TICK_INFO_TIMEOUT, async {
+                let mut stream = socks::connect(socks.as_ref(), &addr).await?;
+                request_tick_info(&mut stream, settings.nodes.get_peer_protocol(&addr)).await
+            }).await;
+            match result {
+                Ok(Ok(tick_info)) => {
+                    net_stats.record_epoch(tick_info.epoch);
+                    net_stats.get_clock().observe(&tick_info);
+                }
+                Ok(Err(err)) => log::debug!("No tick info from {addr}: {:?}", err),
+                Err(_) => log::debug!("No tick info from {addr} within {:?}", TICK_INFO_TIMEOUT),
+            }
+        }
+
+        tokio::time::sleep(CLOCK_CHECK_INTERVAL).await;
+    }
+}
+
+#[test]
+/// Tests the skew against the progression of the ticks, its reset on a new epoch, and the tick info of a node.
+fn test_clock_skew() {
+    use crate::mocknode::{MockNode, MockNodeConfig};
+
+    let now = SystemTime::now();
+    let tick_info = |epoch: u16, tick: u32| TickInfo { tick_duration: 2000, epoch, tick, ..Default::default() };
+    let clock = ClockSkew::new(Some(Duration::from_secs(60)));
+    clock.observe_at(&tick_info(150, 1000), now);
+    // Too few ticks passed to estimate
+    clock.observe_at(&tick_info(150, 1100), now + Duration::from_secs(200));
+    assert_eq!(clock.get_skew_ms(), None);
+
+    // 600 ticks of 2 seconds, on time then with a clock 5 minutes ahead
+    clock.observe_at(&tick_info(150, 1600), now + Duration::from_secs(1210));
+    assert_eq!(clock.get_skew_ms(), Some(10_000));
+    assert!(!clock.is_skewed());
+    clock.observe_at(&tick_info(150, 1600), now + Duration::from_secs(1500));
+    assert_eq!(clock.get_skew_ms(), Some(300_000));
+    assert!(clock.is_skewed());
+
+    // A new epoch starts over, the last skew is kept meanwhile
+    clock.observe_at(&tick_info(151, 10), now + Duration::from_secs(1600));
+    clock.observe_at(&tick_info(151, 1010), now + Duration::from_secs(1600));
+    assert_eq!(clock.get_skew_ms(), Some(-2_000_000));
+
+    let runtime = tokio::runtime::Builder::new_current_thread().enable_all().build().unwrap();
+    runtime.block_on(async {
+        let node = MockNode::start("127.0.0.1:0", MockNodeConfig { epoch: 150, tick: 1234, ..Default::default() }).await.unwrap();
+        let mut stream = tokio::net::TcpStream::connect(node.local_addr()).await.unwrap();
+        let tick_info = request_tick_info(&mut stream, 142).await.unwrap();
+        assert_eq!((tick_info.epoch, tick_info.tick, tick_info.initial_tick), (150, 1234, 1234));
+    });
+}
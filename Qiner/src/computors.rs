@@ -0,0 +1,166 @@
+//! The computors of the epoch, as the nodes broadcast them.
+//!
+//! Solutions stay addressed to the ID being mined, since the nodes score the nonce of a solution
+//! against the public key it is addressed to. The computor list tells whether that ID is one of the
+//! computors of the epoch, which the operators otherwise only learn from an explorer. The signature
+//! of the arbitrator is not verified: the list is only reported, never acted upon.
+
+use std::io;
+use std::mem::size_of;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use crate::settings::Settings;
+use lib::types::network::protocols::BROADCAST_COMPUTORS;
+use lib::types::network::{NUMBER_OF_COMPUTORS, Protocol};
+use lib::types::{PublicKey64, Signature};
+use tokio::io::{AsyncRead, AsyncWrite, AsyncWriteExt};
+use crate::clock::read_response;
+use crate::messages::{Request, RequestComputors};
+use crate::network::{NetStats, RequestResponseHeader};
+use crate::peers::QUIC_SCHEME;
+use crate::socks::{self, SocksProxy};
+use crate::targets::TargetSelector;
+
+/// Interval between two checks of the epoch of the computor list.
+const COMPUTORS_CHECK_INTERVAL: Duration = Duration::from_secs(10 * 60);
+
+/// Time to wait for the computors of a node.
+const COMPUTORS_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Offsets of the public keys after the epoch: packed, or aligned on 32 bytes as the nodes lay them out.
+const PUBLIC_KEYS_OFFSETS: [usize; 2] = [size_of::<u16>(), size_of::<PublicKey64>()];
+
+/// The computors of an epoch.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ComputorList {
+    pub epoch: u16,
+    pub public_keys: Vec<PublicKey64>,
+}
+
+impl ComputorList {
+    /// Parses the payload of a computors broadcast.
+    ///
+    /// # Arguments
+    /// * `bytes` - The payload, after the header: the epoch, the public keys and the signature of the arbitrator.
+    ///
+    /// # Returns
+    /// The computors, `None` if the payload has neither the packed nor the aligned size.
+    pub fn from_bytes(bytes: &[u8]) -> Option<Self> {
+        let offset = PUBLIC_KEYS_OFFSETS.into_iter()
+            .find(|offset| bytes.len() == offset + NUMBER_OF_COMPUTORS * size_of::<PublicKey64>() + size_of::<Signature>())?;
+        let public_keys = bytes[offset..offset + NUMBER_OF_COMPUTORS * size_of::<PublicKey64>()]
+            .chunks_exact(size_of::<PublicKey64>())
+            .map(|key| std::array::from_fn(|idx| u64::from_le_bytes(key[idx * 8..idx * 8 + 8].try_into().unwrap())))
+            .collect();
+        Some(ComputorList { epoch: u16::from_le_bytes([bytes[0], bytes[1]]), public_keys })
+    }
+
+    /// Gets the index of a public key among the computors.
+    ///
+    /// # Returns
+    /// The index, `None` if the public key is not a computor of the epoch.
+    pub fn index_of(&self, public_key: &PublicKey64) -> Option<usize> {
+        self.public_keys.iter().position(|computor| computor == public_key)
+    }
+}
+
+/// Requests the computors of the epoch from a node, skipping the other messages it sends meanwhile.
+///
+/// # Arguments
+/// * `stream` - The connection to the node
+/// * `protocol` - The protocol version of the request
+///
+/// # Returns
+/// The computors, or the error of the connection or of the framing.
+pub async fn request_computors(stream: &mut (impl AsyncRead + AsyncWrite + Unpin), protocol: Protocol) -> io::Result<ComputorList> {
+    let request = RequestComputors.to_bytes(protocol).map_err(|err| io::Error::new(io::ErrorKind::InvalidInput, err))?;
+    stream.write_all(&request).await?;
+    let payload = read_response(stream, BROADCAST_COMPUTORS).await?;
+    ComputorList::from_bytes(&payload).ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, format!("computors of {} bytes", payload.len())))
+}
+
+/// Asynchronous task requesting the computors from the best node whenever the epoch changes,
+/// and reporting whether the ID is one of them.
+///
+/// The requests are deferred by the bandwidth cap, and nodes reached over QUIC are skipped.
+///
+/// # Arguments
+/// * `targets` - The shared target selector, whose best node is asked
+/// * `socks` - The SOCKS proxy the node is reached through, `None` to connect directly
+/// * `net_stats` - Shared network counters, holding the epoch of the nodes and recording the index of the ID
+/// * `settings` - The settings, giving the protocol version of the requests to each node
+/// * `public_key` - Public key of the ID
+pub async fn computors_task(targets: Arc<Mutex<TargetSelector>>, socks: Option<SocksProxy>, net_stats: Arc<NetStats>, settings: Arc<Settings>, public_key: PublicKey64) {
+    loop {
+        // A new list is only needed once the tick info shows another epoch
+        let is_outdated = match (net_stats.get_computor(), net_stats.get_epoch()) {
+            (Some((list_epoch, _)), Some(epoch)) => list_epoch != epoch,
+            (known, _) => known.is_none(),
+        };
+        let addr = targets.lock().unwrap().get_nodes().best().to_string();
+        if is_outdated && !addr.is_empty() && !addr.starts_with(QUIC_SCHEME) && net_stats.get_bandwidth().try_consume(size_of::<RequestResponseHeader>()) {
+            let result = tokio::time::timeout(COMPUTORS_TIMEOUT, async {
+                let mut stream = socks::connect(socks.as_ref(), &addr).await?;
+                request_computors(&mut stream, settings.nodes.get_peer_protocol(&addr)).await
+            }).await;
+            match result {
+                Ok(Ok(computors)) => {
+                    let index = computors.index_of(&public_key);
+                    match index {
+                        Some(index) => log::info!("The ID is computor #{index} of epoch {}", computors.epoch),
+                        None => log::info!("The ID is not among the {} computors of epoch {}", computors.public_keys.len(), computors.epoch),
+                    }
+                    net_stats.record_computor(computors.epoch, index);
+                }
+                Ok(Err(err)) => log::debug!("No computors from {addr}: {:?}", err),
+                Err(_) => log::debug!("No computors from {addr} within {:?}", COMPUTORS_TIMEOUT),
+            }
+        }
+
+        tokio::time::sleep(COMPUTORS_CHECK_INTERVAL).await;
+    }
+}
+
+#[test]
+/// Tests the computors of both layouts, the index of an ID and the request to a node.
+fn test_computor_list() {
+    use lib::types::network::protocols::{REQUEST_COMPUTORS, RESPOND_CURRENT_TICK_INFO};
+    use tokio::io::AsyncReadExt;
+
+    let computors = |offset: usize| {
+        let mut bytes = vec![0u8; offset + NUMBER_OF_COMPUTORS * 32 + 64];
+        bytes[..2].copy_from_slice(&150u16.to_le_bytes());
+        for index in 0..NUMBER_OF_COMPUTORS {
+            bytes[offset + index * 32..offset + index * 32 + 8].copy_from_slice(&(index as u64 + 1).to_le_bytes());
+        }
+        bytes
+    };
+    for offset in PUBLIC_KEYS_OFFSETS {
+        let list = ComputorList::from_bytes(&computors(offset)).unwrap();
+        assert_eq!((list.epoch, list.public_keys.len()), (150, NUMBER_OF_COMPUTORS));
+        assert_eq!(list.index_of(&[5, 0, 0, 0]), Some(4));
+        assert_eq!(list.index_of(&[5, 0, 0, 1]), None);
+    }
+    assert!(ComputorList::from_bytes(&computors(1)).is_none());
+
+    let runtime = tokio::runtime::Builder::new_current_thread().enable_all().build().unwrap();
+    runtime.block_on(async {
+        let (mut client, mut node) = tokio::io::duplex(64 * 1024);
+        let node = tokio::spawn(async move {
+            let mut request = [0u8; size_of::<RequestResponseHeader>()];
+            node.read_exact(&mut request).await.unwrap();
+            assert_eq!(RequestResponseHeader::from_bytes(&request).unwrap().get_type(), REQUEST_COMPUTORS);
+            // Other messages come before the answer
+            let tick_info = RequestResponseHeader::new(&RESPOND_CURRENT_TICK_INFO, &(size_of::<RequestResponseHeader>() + 16), &142).unwrap();
+            node.write_all(&tick_info.to_bytes()).await.unwrap();
+            node.write_all(&[0; 16]).await.unwrap();
+            let payload = computors(32);
+            let header = RequestResponseHeader::new(&BROADCAST_COMPUTORS, &(size_of::<RequestResponseHeader>() + payload.len()), &142).unwrap();
+            node.write_all(&header.to_bytes()).await.unwrap();
+            node.write_all(&payload).await.unwrap();
+        });
+        let list = request_computors(&mut client, 142).await.unwrap();
+        assert_eq!(list.index_of(&[676, 0, 0, 0]), Some(675));
+        node.await.unwrap();
+    });
+}
@@ -0,0 +1,218 @@
+//! The structured configuration file, `qiner.toml`, grouping the variables of the legacy `.env` by section.
+//!
+//! A key is the name of a variable in lowercase, e.g. `solution_threshold`, in the section it is
+//! listed under here; the variables of no section go to `[other]`. The section only helps
+//! reading the file: a key is the same variable in any section. The file is loaded into the
+//! environment like the `.env` file was, so the variables set explicitly still win over it.
+
+use std::collections::BTreeMap;
+use std::env;
+use std::fs;
+use std::path::Path;
+use serde_json::Value;
+use crate::settings::Settings;
+use lib::env_names::{
+    ENV_ALGORITHM, ENV_BANDWIDTH_MAX_BYTES_PER_HOUR, ENV_CORE_POLICY, ENV_ID, ENV_KECCAK_LANES, ENV_MINING_DATA_LENGTH, ENV_NUMBER_OF_NEURONS,
+    ENV_NUMBER_OF_THREADS, ENV_PEER_ALLOWLIST, ENV_PEER_DENYLIST, ENV_PEER_PROTOCOLS, ENV_POOL_FAILOVER_MINUTES, ENV_POOL_SERVER, ENV_PRIORITY,
+    ENV_RANDOM_SEED, ENV_RANDOM_SOURCE, ENV_RIG_NAME, ENV_SCHEDULER, ENV_SERVER_IP, ENV_SERVER_PORT, ENV_SHARE_SERVER, ENV_SHARE_THRESHOLD,
+    ENV_SOCKS_ISOLATION, ENV_SOCKS_PROXY, ENV_SOLUTION_THRESHOLD, ENV_SUBMIT_CONNECTIONS_PER_NODE, ENV_SUBMIT_MAX_PACKETS_PER_CONNECTION, ENV_SUBMIT_MAX_PACKETS_PER_SECOND,
+    ENV_SUBMIT_MIN_CONNECT_INTERVAL_MS, ENV_VERSION, ENV_WORKER_NAME,
+};
+use crate::peers::PEERS_SPLIT_CHAR;
+use crate::reload::{ConfigValues, read_config};
+
+/// Default structured configuration file, loaded on start instead of the `.env` file when it exists.
+pub const DEFAULT_STRUCTURED_CONFIG_FILE: &str = "qiner.toml";
+
+/// Section of the variables not listed in `SECTIONS`.
+const OTHER_SECTION: &str = "other";
+
+/// The sections of the file and the variables they hold, in the order they are written.
+const SECTIONS: [(&str, &[&str]); 3] = [
+    ("identity", &[ENV_ID, ENV_WORKER_NAME, ENV_RIG_NAME]),
+    ("mining", &[
+        ENV_RANDOM_SEED, ENV_SOLUTION_THRESHOLD, ENV_VERSION, ENV_NUMBER_OF_THREADS, ENV_ALGORITHM, ENV_NUMBER_OF_NEURONS, ENV_MINING_DATA_LENGTH,
+        ENV_SHARE_THRESHOLD, ENV_PRIORITY, ENV_SCHEDULER, ENV_CORE_POLICY, ENV_KECCAK_LANES, ENV_RANDOM_SOURCE,
+    ]),
+    ("network", &[
+        ENV_SERVER_IP, ENV_SERVER_PORT, ENV_POOL_SERVER, ENV_POOL_FAILOVER_MINUTES, ENV_SHARE_SERVER, ENV_PEER_ALLOWLIST, ENV_PEER_DENYLIST,
+        ENV_PEER_PROTOCOLS, ENV_SOCKS_PROXY, ENV_SOCKS_ISOLATION, ENV_BANDWIDTH_MAX_BYTES_PER_HOUR, ENV_SUBMIT_MAX_PACKETS_PER_CONNECTION,
+        ENV_SUBMIT_MAX_PACKETS_PER_SECOND, ENV_SUBMIT_MIN_CONNECT_INTERVAL_MS, ENV_SUBMIT_CONNECTIONS_PER_NODE,
+    ]),
+];
+
+/// Variables written as arrays, their comma-separated items being the elements.
+const LIST_SETTINGS: [&str; 3] = [ENV_SERVER_IP, ENV_PEER_ALLOWLIST, ENV_PEER_DENYLIST];
+
+/// Checks if a configuration file is in the structured format, by its extension.
+pub fn is_structured_config(path: &Path) -> bool {
+    path.extension().is_some_and(|extension| extension == "toml")
+}
+
+/// Parses the variables of a structured configuration file.
+///
+/// # Arguments
+/// * `text` - The content of the file.
+///
+/// # Returns
+/// The variables, by name, or why the file is invalid.
+pub fn parse_structured_config(text: &str) -> Result<ConfigValues, String> {
+    let sections = basic_toml::from_str::<BTreeMap<String, BTreeMap<String, Value>>>(text).map_err(|err| err.to_string())?;
+    let mut values = ConfigValues::new();
+    for (section, keys) in sections {
+        for (key, value) in keys {
+            let value = match value {
+                Value::String(value) => value,
+                Value::Number(_) | Value::Bool(_) => value.to_string(),
+                Value::Array(items) => items.into_iter()
+                    .map(|item| match item {
+                        Value::String(item) => Ok(item),
+                        Value::Number(_) | Value::Bool(_) => Ok(item.to_string()),
+                        _ => Err(format!("[{section}] {key}: expected strings or numbers")),
+                    })
+                    .collect::<Result<Vec<_>, _>>()?
+                    .join(&PEERS_SPLIT_CHAR.to_string()),
+                _ => return Err(format!("[{section}] {key}: expected a string, a number, a boolean or an array")),
+            };
+            let name = key.to_ascii_uppercase();
+            if values.insert(name.clone(), value).is_some() {
+                return Err(format!("{key} is set twice"));
+            }
+        }
+    }
+    Ok(values)
+}
+
+/// Renders variables in the structured format, by section.
+///
+/// # Arguments
+/// * `values` - The variables, by name.
+///
+/// # Returns
+/// The content of the file; numbers are written as numbers, lists as arrays, and the rest as strings.
+pub fn render_structured_config(values: &ConfigValues) -> String {
+    let render_value = |name: &str, value: &str| {
+        if LIST_SETTINGS.contains(&name) {
+            let items = split_list(value).map(|item| Value::from(item).to_string()).collect::<Vec<_>>();
+            format!("[{}]", items.join(", "))
+        } else if value.parse::<u64>().is_ok_and(|number| number.to_string() == value) {
+            value.to_string()
+        } else {
+            // A JSON string is a valid TOML basic string
+            Value::from(value).to_string()
+        }
+    };
+
+    let others = values.keys().filter(|name| !SECTIONS.iter().any(|(_, names)| names.contains(&name.as_str()))).map(String::as_str).collect::<Vec<_>>();
+    let mut text = String::new();
+    for (section, names) in SECTIONS.iter().map(|(section, names)| (*section, names.to_vec())).chain([(OTHER_SECTION, others)]) {
+        let lines = names.iter()
+            .filter_map(|name| values.get(*name).map(|value| format!("{} = {}\n", name.to_ascii_lowercase(), render_value(name, value))))
+            .collect::<String>();
+        if !lines.is_empty() {
+            if !text.is_empty() {
+                text.push('\n');
+            }
+            text.push_str(&format!("[{section}]\n{lines}"));
+        }
+    }
+    text
+}
+
+/// Splits a comma-separated list, e.g. of nodes, skipping the empty items.
+fn split_list(value: &str) -> impl Iterator<Item = &str> {
+    value.split(PEERS_SPLIT_CHAR).map(str::trim).filter(|item| !item.is_empty())
+}
+
+/// Reads the variables of a structured configuration file, without touching the environment.
+///
+/// # Arguments
+/// * `path` - The configuration file.
+///
+/// # Returns
+/// The variables, or why the file cannot be read.
+pub fn read_structured_config(path: &Path) -> Result<ConfigValues, String> {
+    parse_structured_config(&fs::read_to_string(path).map_err(|err| err.to_string())?)
+}
+
+/// Loads a structured configuration file into the environment, to be called before any thread is spawned.
+///
+/// # Arguments
+/// * `path` - The configuration file.
+///
+/// # Returns
+/// `Ok`, the variables already set being left as they are, or why the file cannot be read.
+pub fn load_structured_config(path: &Path) -> Result<(), String> {
+    for (name, value) in read_structured_config(path)? {
+        if env::var_os(&name).is_none() {
+            env::set_var(name, value);
+        }
+    }
+    Ok(())
+}
+
+/// Converts a legacy `.env` file to the structured format, after validating its settings.
+///
+/// # Arguments
+/// * `legacy` - The `.env` file, left as it is.
+/// * `path` - The structured configuration file to write, which must not exist yet.
+///
+/// # Returns
+/// The number of variables written, or why the file was not written.
+pub fn migrate_config(legacy: &Path, path: &Path) -> Result<usize, String> {
+    if path.exists() {
+        return Err(format!("{} already exists", path.display()));
+    }
+    let mut values = read_config(legacy).map_err(|err| format!("cannot read {}: {err}", legacy.display()))?;
+    for name in LIST_SETTINGS {
+        if let Some(value) = values.get_mut(name) {
+            *value = split_list(value).collect::<Vec<_>>().join(&PEERS_SPLIT_CHAR.to_string());
+        }
+    }
+
+    // The variables set in the environment rather than in the file are those the miner would use
+    if let Err(errors) = Settings::from_lookup(|name| values.get(name).cloned().or_else(|| env::var(name).ok())) {
+        return Err(errors.iter().map(ToString::to_string).collect::<Vec<_>>().join(", "));
+    }
+
+    let text = format!("# Migrated from {} by `qiner migrate-config`\n\n{}", legacy.display(), render_structured_config(&values));
+    // Written through the same parser as on start, so a value the format cannot hold is caught now
+    if parse_structured_config(&text).as_ref() != Ok(&values) {
+        return Err("some values cannot be written in the structured format".to_string());
+    }
+    fs::write(path, text).map_err(|err| format!("cannot write {}: {err}", path.display()))?;
+    Ok(values.len())
+}
+
+#[test]
+/// Tests that a legacy file is validated and written by section, and reads back to the same variables.
+fn test_migrate_config() {
+    let dir = env::temp_dir().join(format!("qiner-migrate-test-{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    let legacy = dir.join(".env");
+    let path = dir.join(DEFAULT_STRUCTURED_CONFIG_FILE);
+    let _ = fs::remove_file(&path);
+
+    fs::write(&legacy, "ID=BZBQFLLBNCXEMGLOBHUVFTLUPLVCPQUASSILFABOFFBCADQSSUPNWLZBQEXK\nSERVER_IP=\"127.0.0.1, 127.0.0.2\"\nSERVER_PORT=21841\nRANDOM_SEED=0x0100e909\nSOLUTION_THRESHOLD=abc\n").unwrap();
+    assert_eq!(migrate_config(&legacy, &path), Err("SOLUTION_THRESHOLD: not a number".to_string()));
+    assert!(!path.exists());
+
+    fs::write(&legacy, "ID=BZBQFLLBNCXEMGLOBHUVFTLUPLVCPQUASSILFABOFFBCADQSSUPNWLZBQEXK\nSERVER_IP=\"127.0.0.1, 127.0.0.2\"\nSERVER_PORT=21841\nRANDOM_SEED=0x0100e909\nSOLUTION_THRESHOLD=22\nWORKER_NAME='rig \"7\"'\nRUST_LOG=info,qiner=debug\n").unwrap();
+    assert_eq!(migrate_config(&legacy, &path), Ok(7));
+    let text = fs::read_to_string(&path).unwrap();
+    assert!(text.contains("[identity]\nid = \"BZBQFLLBNCXEMGLOBHUVFTLUPLVCPQUASSILFABOFFBCADQSSUPNWLZBQEXK\"\nworker_name = \"rig \\\"7\\\"\"\n"));
+    assert!(text.contains("[mining]\nrandom_seed = \"0x0100e909\"\nsolution_threshold = 22\n"));
+    assert!(text.contains("[network]\nserver_ip = [\"127.0.0.1\", \"127.0.0.2\"]\nserver_port = 21841\n"));
+    assert!(text.contains("[other]\nrust_log = \"info,qiner=debug\"\n"));
+
+    let values = read_structured_config(&path).unwrap();
+    assert_eq!(values.get(ENV_SERVER_IP).map(String::as_str), Some("127.0.0.1,127.0.0.2"));
+    assert_eq!(values.get(ENV_WORKER_NAME).map(String::as_str), Some("rig \"7\""));
+    assert_eq!(read_config(&path), Ok(values));
+    // An existing file is never overwritten
+    assert!(migrate_config(&legacy, &path).is_err());
+    fs::remove_dir_all(&dir).unwrap();
+
+    assert!(parse_structured_config("[mining]\nsolution_threshold = 22\n[other]\nSOLUTION_THRESHOLD = 23\n").is_err());
+    assert!(parse_structured_config("[mining]\nsolution_threshold = { value = 22 }\n").is_err());
+}
@@ -0,0 +1,223 @@
+//! A connection to a node kept open between submissions.
+//!
+//! While no solution is waiting, the connection is kept alive with current tick info requests,
+//! so NAT and firewall state is not dropped during long dry spells, and a dead connection is
+//! noticed and replaced before a solution is written to it.
+
+use std::io;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use lib::types::network::Protocol;
+use tokio::io::AsyncWriteExt;
+use tokio::net::TcpStream;
+use crate::bandwidth::BandwidthBudget;
+use crate::capture;
+use crate::messages::{Request, RequestTickInfo};
+
+/// Default interval between two keep-alives of an idle connection.
+pub const DEFAULT_KEEP_ALIVE_INTERVAL: Duration = Duration::from_secs(30);
+
+/// Builds a keep-alive, a current tick info request every node answers.
+///
+/// # Arguments
+/// * `protocol` - The protocol version of the request.
+///
+/// # Returns
+/// The bytes of the request, a header alone.
+pub fn keep_alive_to_bytes(protocol: Protocol) -> Vec<u8> {
+    // A random dejavu keeps the node from relaying the request to its peers
+    RequestTickInfo.to_bytes(protocol).expect("a keep-alive fits in a header")
+}
+
+/// A connection to a node, reopened on demand and kept alive while idle.
+#[derive(Debug)]
+pub struct NodeConnection {
+    addr: String,
+    protocol: Protocol,
+    keep_alive_interval: Option<Duration>,
+    stream: Option<TcpStream>,
+    /// Last write or connect attempt, keep-alives are only sent after `keep_alive_interval` of silence
+    last_activity_at: Instant,
+    /// Whether a keep-alive is waiting for its answer
+    is_keep_alive_pending: bool,
+    /// Accounting of the bytes written, keep-alives are deferred once its cap is reached
+    bandwidth: Arc<BandwidthBudget>,
+}
+
+impl NodeConnection {
+    /// Creates a new `NodeConnection`, not connected yet.
+    ///
+    /// # Arguments
+    /// * `addr` - The address of the node, as `host:port`.
+    /// * `protocol` - The protocol version of the keep-alives.
+    /// * `keep_alive_interval` - The interval between two keep-alives, `None` to never send any.
+    pub fn new(addr: String, protocol: Protocol, keep_alive_interval: Option<Duration>) -> Self {
+        NodeConnection {
+            addr,
+            protocol,
+            keep_alive_interval,
+            stream: None,
+            last_activity_at: Instant::now(),
+            is_keep_alive_pending: false,
+            bandwidth: Arc::new(BandwidthBudget::default()),
+        }
+    }
+
+    /// Sets the bandwidth accounting the writes are counted in, uncapped by default.
+    ///
+    /// # Arguments
+    /// * `bandwidth` - The shared accounting.
+    pub fn set_bandwidth(&mut self, bandwidth: Arc<BandwidthBudget>) {
+        self.bandwidth = bandwidth;
+    }
+
+    /// Gets the address of the node.
+    pub fn get_addr(&self) -> &str {
+        &self.addr
+    }
+
+    /// Checks if the connection is open, as far as is known.
+    pub fn is_connected(&self) -> bool {
+        self.stream.is_some()
+    }
+
+    /// Reads whatever the node sent without waiting, closing the connection if the node closed it.
+    fn drain_incoming(&mut self) {
+        let Some(stream) = &self.stream else {
+            return;
+        };
+
+        let mut buffer = [0u8; 4096];
+        loop {
+            match stream.try_read(&mut buffer) {
+                Ok(0) => {
+                    self.stream = None;
+                    return;
+                }
+                // Any message answers the pending keep-alive
+                Ok(_) => self.is_keep_alive_pending = false,
+                Err(err) if err.kind() == io::ErrorKind::WouldBlock => return,
+                Err(_) => {
+                    self.stream = None;
+                    return;
+                }
+            }
+        }
+    }
+
+    /// Opens the connection unless it is already open.
+    async fn connect(&mut self) -> io::Result<&mut TcpStream> {
+        self.drain_incoming();
+        if self.stream.is_none() {
+            self.last_activity_at = Instant::now();
+            self.is_keep_alive_pending = false;
+            self.stream = Some(TcpStream::connect(&self.addr).await?);
+        }
+        Ok(self.stream.as_mut().unwrap())
+    }
+
+    /// Writes to the node, connecting first if needed, whatever the bandwidth cap.
+    ///
+    /// # Arguments
+    /// * `data` - The bytes to write.
+    ///
+    /// # Returns
+    /// The error of the connection or of the write, after which the connection is closed.
+    pub async fn write_all(&mut self, data: &[u8]) -> io::Result<()> {
+        self.bandwidth.record(data.len());
+        self.write(data).await
+    }
+
+    /// Writes to the node, connecting first if needed, without counting the bytes.
+    async fn write(&mut self, data: &[u8]) -> io::Result<()> {
+        let result = match self.connect().await {
+            Ok(stream) => stream.write_all(data).await,
+            Err(err) => Err(err),
+        };
+        if result.is_ok() {
+            capture::record_sent(&self.addr, data);
+        }
+        self.last_activity_at = Instant::now();
+        if result.is_err() {
+            self.stream = None;
+        }
+        result
+    }
+
+    /// Keeps the connection alive while idle, to be called periodically.
+    ///
+    /// Once the keep-alive interval passes without a write, the connection is opened if needed
+    /// and a keep-alive is sent, unless the bandwidth cap is reached. A keep-alive still
+    /// unanswered when the next one is due means the connection is dead: it is closed, and
+    /// opened again on the next call.
+    ///
+    /// # Returns
+    /// Why the connection is dead, `Ok` while it is alive or not due for a keep-alive.
+    pub async fn keep_alive(&mut self) -> io::Result<()> {
+        self.drain_incoming();
+        let Some(interval) = self.keep_alive_interval else {
+            return Ok(());
+        };
+        if self.last_activity_at.elapsed() < interval {
+            return Ok(());
+        }
+
+        if self.is_keep_alive_pending && self.stream.is_some() {
+            self.stream = None;
+            self.is_keep_alive_pending = false;
+            return Err(io::Error::new(io::ErrorKind::TimedOut, format!("no answer to the keep-alive within {interval:?}")));
+        }
+
+        let keep_alive = keep_alive_to_bytes(self.protocol);
+        if !self.bandwidth.try_consume(keep_alive.len()) {
+            return Ok(());
+        }
+        self.write(&keep_alive).await?;
+        self.is_keep_alive_pending = true;
+        Ok(())
+    }
+}
+
+#[test]
+/// Tests that answered keep-alives keep the connection and unanswered ones replace it.
+fn test_keep_alive() {
+    use lib::types::network::protocols::REQUEST_CURRENT_TICK_INFO;
+    use crate::mocknode::{MockNode, MockNodeConfig};
+    use crate::network::RequestResponseHeader;
+    use tokio::io::AsyncReadExt;
+    use tokio::net::TcpListener;
+
+    let interval = Duration::from_millis(50);
+    let runtime = tokio::runtime::Builder::new_current_thread().enable_all().build().unwrap();
+    runtime.block_on(async {
+        // The mock node answers keep-alives with the current tick info
+        let node = MockNode::start("127.0.0.1:0", MockNodeConfig { protocol: Some(142), ..Default::default() }).await.unwrap();
+        let mut connection = NodeConnection::new(node.local_addr().to_string(), 142, Some(interval));
+        assert!(connection.keep_alive().await.is_ok());
+        assert!(!connection.is_connected());
+        for _ in 0..3 {
+            tokio::time::sleep(interval).await;
+            assert!(connection.keep_alive().await.is_ok());
+            assert!(connection.is_connected());
+        }
+        // Gives the node the time to count the last request
+        tokio::time::sleep(interval).await;
+        assert_eq!(node.stats().connections, 1);
+        assert_eq!(node.stats().tick_info_requests, 3);
+        assert!(node.stats().invalid.is_empty());
+
+        // A peer that reads but never answers is dropped on the next keep-alive
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let mut connection = NodeConnection::new(listener.local_addr().unwrap().to_string(), 142, Some(interval));
+        tokio::time::sleep(interval).await;
+        assert!(connection.keep_alive().await.is_ok());
+        let (mut silent, _) = listener.accept().await.unwrap();
+        let mut keep_alive = [0u8; std::mem::size_of::<RequestResponseHeader>()];
+        silent.read_exact(&mut keep_alive).await.unwrap();
+        assert_eq!(RequestResponseHeader::from_bytes(&keep_alive).unwrap().get_type(), REQUEST_CURRENT_TICK_INFO);
+
+        tokio::time::sleep(interval).await;
+        assert_eq!(connection.keep_alive().await.unwrap_err().kind(), io::ErrorKind::TimedOut);
+        assert!(!connection.is_connected());
+    });
+}
@@ -1,8 +1,9 @@
-use k12::digest::{ExtendableOutput, Update};
-use k12::KangarooTwelve;
+use alloc::format;
+use alloc::string::String;
 use lib::types::{Id, PublicKey, PublicKey64};
+use crate::crypto::k12_into;
 
-const A: u8 = 'A' as u8;
+const A: u8 = b'A';
 
 /// Converts an `Id` to a `PublicKey64`.
 ///
@@ -19,7 +20,7 @@ pub fn get_public_key_64_from_id(id: &Id, public_key: &mut PublicKey64) -> bool
         for j in (0..14).rev() {
             let id_value = id[i * 14 + j];
             // Check if the ID value is within the range 'A' to 'Z'
-            if id_value < 'A' as u8 || id_value > 'Z' as u8 {
+            if !id_value.is_ascii_uppercase() {
                 *public_key = Default::default();
                 return false;
             }
@@ -38,8 +39,8 @@ pub fn get_public_key_64_from_id(id: &Id, public_key: &mut PublicKey64) -> bool
 /// * `public_key` - The `PublicKey64` to be converted.
 /// * `id` - A mutable reference to an `Id` where the result will be stored.
 pub fn get_id_from_public_key_64(public_key: &PublicKey64, id: &mut Id) {
-    for i in 0..4 {
-        let mut public_key_fragment = public_key[i];
+    for (i, public_key_fragment) in public_key.iter().enumerate() {
+        let mut public_key_fragment = *public_key_fragment;
         for j in 0..14 {
             let id_idx = i * 14usize + j;
             id[id_idx] = (public_key_fragment % 26u64 + ('A' as u64)) as u8;
@@ -50,18 +51,12 @@ pub fn get_id_from_public_key_64(public_key: &PublicKey64, id: &mut Id) {
     // Calculate the Identity Bytes Checksum
     let mut identity_bytes_checksum: u32;
     {
-        let mut kangaroo_twelve = KangarooTwelve::default();
         let ptr_public_key_8 = public_key.as_ptr() as *const PublicKey;
-        unsafe {
-            // Update the hash with the public key
-            kangaroo_twelve.update(&ptr_public_key_8.read());
-
-            // Finalize the hash and obtain the first 3 bytes of the output
-            let mut result: [u8; 3] = Default::default();
-            kangaroo_twelve.finalize_xof_into(&mut result);
-            // Combine the 3 bytes into a single 24-bit integer
-            identity_bytes_checksum = result[0] as u32 | (result[1] as u32) << 8 | (result[2] as u32) << 16;
-        }
+        // Hash the public key and obtain the first 3 bytes of the output
+        let mut result: [u8; 3] = Default::default();
+        k12_into(unsafe { &ptr_public_key_8.read() }, &mut result);
+        // Combine the 3 bytes into a single 24-bit integer
+        identity_bytes_checksum = result[0] as u32 | (result[1] as u32) << 8 | (result[2] as u32) << 16;
     }
 
     // Mask to fit within 18 bits
@@ -72,3 +67,101 @@ pub fn get_id_from_public_key_64(public_key: &PublicKey64, id: &mut Id) {
         identity_bytes_checksum /= 26;
     }
 }
+
+/// Parses an `Id`, checking its checksum.
+///
+/// # Arguments
+/// * `id` - The 60 uppercase letters of the ID.
+///
+/// # Returns
+/// The `PublicKey64` of the ID, or `None` if it is malformed or its checksum does not match.
+pub fn parse_id(id: &str) -> Option<PublicKey64> {
+    let id: Id = id.trim().as_bytes().try_into().ok()?;
+
+    let mut public_key = PublicKey64::default();
+    if !get_public_key_64_from_id(&id, &mut public_key) {
+        return None;
+    }
+
+    // The checksum letters are recomputed from the public key
+    let mut expected_id: Id = [0; 60];
+    get_id_from_public_key_64(&public_key, &mut expected_id);
+    (expected_id == id).then_some(public_key)
+}
+
+/// Converts the bytes of a public key to a `PublicKey64`.
+///
+/// # Arguments
+/// * `public_key` - The 32 bytes of the public key.
+///
+/// # Returns
+/// The public key as little endian 64-bit words.
+pub fn get_public_key_64_from_public_key(public_key: &PublicKey) -> PublicKey64 {
+    core::array::from_fn(|idx| u64::from_le_bytes(public_key[idx * 8..idx * 8 + 8].try_into().unwrap()))
+}
+
+/// Converts a `PublicKey64` to the bytes of the public key.
+///
+/// # Arguments
+/// * `public_key` - The public key as little endian 64-bit words.
+///
+/// # Returns
+/// The 32 bytes of the public key.
+pub fn get_public_key_from_public_key_64(public_key: &PublicKey64) -> PublicKey {
+    core::array::from_fn(|idx| public_key[idx / 8].to_le_bytes()[idx % 8])
+}
+
+/// Formats a public key as a hex string of its bytes.
+///
+/// # Arguments
+/// * `public_key` - The public key to format.
+///
+/// # Returns
+/// The public key as 64 lowercase hex characters.
+pub fn public_key_to_hex(public_key: &PublicKey) -> String {
+    public_key.iter().map(|byte| format!("{byte:02x}")).collect()
+}
+
+/// Parses a public key from the hex string of its bytes.
+///
+/// # Arguments
+/// * `hex` - The 64 hex characters of the public key.
+///
+/// # Returns
+/// The public key, or `None` if the string is not 64 hex characters.
+pub fn public_key_from_hex(hex: &str) -> Option<PublicKey> {
+    let hex = hex.trim();
+    if hex.len() != 64 || !hex.is_ascii() {
+        return None;
+    }
+
+    let mut public_key = PublicKey::default();
+    for (byte, pair) in public_key.iter_mut().zip(hex.as_bytes().chunks(2)) {
+        *byte = u8::from_str_radix(core::str::from_utf8(pair).ok()?, 16).ok()?;
+    }
+    Some(public_key)
+}
+
+#[test]
+/// Tests that IDs with a wrong checksum are rejected.
+fn test_parse_id() {
+    let id = "UBAZRCVPOZTDKGCBNPGYFUPLZXDDNHSEGJRTAJKWJBHJDKHMAKVVFAKCZGRI";
+    assert!(parse_id(id).is_some());
+    assert!(parse_id(&id.replace("CZGRI", "CZGRA")).is_none());
+    assert!(parse_id(&id[1..]).is_none());
+    assert!(parse_id(&id.to_lowercase()).is_none());
+}
+
+#[test]
+/// Tests that public keys round trip through hex and match their ID.
+fn test_public_key_hex() {
+    let id = "UBAZRCVPOZTDKGCBNPGYFUPLZXDDNHSEGJRTAJKWJBHJDKHMAKVVFAKCZGRI";
+    let public_key_64 = parse_id(id).unwrap();
+    let public_key = get_public_key_from_public_key_64(&public_key_64);
+
+    let hex = public_key_to_hex(&public_key);
+    assert_eq!(public_key_from_hex(&hex.to_uppercase()), Some(public_key));
+    assert_eq!(get_public_key_64_from_public_key(&public_key), public_key_64);
+    assert!(public_key_from_hex(&hex[1..]).is_none());
+    assert!(public_key_from_hex(&hex.replace(&hex[..2], "zz")).is_none());
+}
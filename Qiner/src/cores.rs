@@ -0,0 +1,293 @@
+//! Detection of performance and efficiency cores on hybrid CPUs, and the placement of the
+//! mining threads on them.
+
+use std::fmt;
+use std::path::Path;
+
+/// Number of CPUs in the affinity mask passed to the OS, the size of `cpu_set_t` on Linux.
+const MAX_CPUS: usize = 1024;
+
+/// The kind of a core of a hybrid CPU.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CoreType {
+    /// A performance core (P-core, or big core on ARM).
+    Performance,
+    /// An efficiency core (E-core, or LITTLE core on ARM).
+    Efficiency,
+}
+
+impl CoreType {
+    /// Gets the short name of the core type, for the logs.
+    pub fn name(&self) -> &'static str {
+        match self {
+            CoreType::Performance => "P-core",
+            CoreType::Efficiency => "E-core",
+        }
+    }
+}
+
+/// The logical CPUs of each core type of a hybrid CPU.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CoreTopology {
+    pub performance: Vec<usize>,
+    pub efficiency: Vec<usize>,
+}
+
+impl CoreTopology {
+    /// Gets the logical CPUs of a core type.
+    pub fn cpus(&self, core_type: CoreType) -> &[usize] {
+        match core_type {
+            CoreType::Performance => &self.performance,
+            CoreType::Efficiency => &self.efficiency,
+        }
+    }
+}
+
+/// Detects the performance and efficiency cores of the CPU.
+///
+/// # Returns
+/// The topology, or `None` if the CPU is not hybrid or the OS does not tell.
+pub fn detect_topology() -> Option<CoreTopology> {
+    #[cfg(target_os = "linux")]
+    return detect_topology_in(Path::new("/sys/devices"));
+    #[cfg(not(target_os = "linux"))]
+    return None;
+}
+
+/// Detects the performance and efficiency cores from a sysfs tree.
+///
+/// Intel hybrid CPUs have a PMU per core type listing its CPUs. Elsewhere, e.g. ARM
+/// big.LITTLE, cores with less than the highest `cpu_capacity` are efficiency cores.
+///
+/// # Arguments
+/// * `devices` - The `/sys/devices` directory.
+///
+/// # Returns
+/// The topology, or `None` if there are not two kinds of cores.
+pub fn detect_topology_in(devices: &Path) -> Option<CoreTopology> {
+    let read_cpus = |pmu: &str| std::fs::read_to_string(devices.join(pmu).join("cpus")).ok().map(|list| parse_cpu_list(&list));
+    if let (Some(performance), Some(efficiency)) = (read_cpus("cpu_core"), read_cpus("cpu_atom")) {
+        return (!performance.is_empty() && !efficiency.is_empty()).then_some(CoreTopology { performance, efficiency });
+    }
+
+    let mut capacities = std::fs::read_dir(devices.join("system/cpu")).ok()?
+        .filter_map(|entry| {
+            let entry = entry.ok()?;
+            let cpu = entry.file_name().to_str()?.strip_prefix("cpu")?.parse::<usize>().ok()?;
+            let capacity = std::fs::read_to_string(entry.path().join("cpu_capacity")).ok()?.trim().parse::<u32>().ok()?;
+            Some((cpu, capacity))
+        })
+        .collect::<Vec<_>>();
+    capacities.sort_unstable();
+    let max_capacity = capacities.iter().map(|(_, capacity)| *capacity).max()?;
+    let (performance, efficiency): (Vec<_>, Vec<_>) = capacities.iter().partition(|(_, capacity)| *capacity == max_capacity);
+    (!efficiency.is_empty()).then(|| CoreTopology {
+        performance: performance.into_iter().map(|(cpu, _)| cpu).collect(),
+        efficiency: efficiency.into_iter().map(|(cpu, _)| cpu).collect(),
+    })
+}
+
+/// Parses a CPU list of sysfs, e.g. `0-7,16,18-19`.
+///
+/// # Returns
+/// The CPUs, in the order listed, invalid parts being skipped.
+pub fn parse_cpu_list(list: &str) -> Vec<usize> {
+    list.trim().split(',').filter(|part| !part.is_empty()).flat_map(|part| {
+        let (first, last) = part.split_once('-').unwrap_or((part, part));
+        match (first.trim().parse::<usize>(), last.trim().parse::<usize>()) {
+            (Ok(first), Ok(last)) if first <= last => (first..=last).collect(),
+            _ => Vec::new(),
+        }
+    }).collect()
+}
+
+/// Which cores the mining threads run on.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum CorePolicy {
+    /// Every core, the threads being placed by the OS.
+    #[default]
+    All,
+    /// Only the performance cores, one thread per logical CPU unless `NUMBER_OF_THREADS` is set.
+    PerformanceOnly,
+    /// Only the efficiency cores, one thread per logical CPU unless `NUMBER_OF_THREADS` is set.
+    EfficiencyOnly,
+    /// The given numbers of threads on the performance and on the efficiency cores.
+    Weighted { performance: usize, efficiency: usize },
+}
+
+impl CorePolicy {
+    /// Parses the policy.
+    ///
+    /// # Arguments
+    /// * `value` - `all`, `p-cores`, `e-cores`, or `<p>:<e>` for `p` threads on P-cores and `e` on E-cores.
+    ///
+    /// # Returns
+    /// The policy, or why the value is invalid.
+    pub fn parse(value: &str) -> Result<Self, String> {
+        match value.trim().to_ascii_lowercase().as_str() {
+            "all" => Ok(CorePolicy::All),
+            "p-cores" => Ok(CorePolicy::PerformanceOnly),
+            "e-cores" => Ok(CorePolicy::EfficiencyOnly),
+            weighted => {
+                let counts = weighted.split_once(':').map(|(performance, efficiency)| (performance.trim().parse::<usize>(), efficiency.trim().parse::<usize>()));
+                match counts {
+                    Some((Ok(performance), Ok(efficiency))) if performance + efficiency > 0 => Ok(CorePolicy::Weighted { performance, efficiency }),
+                    _ => Err(format!("expected all, p-cores, e-cores or <p>:<e> thread counts, got {weighted:?}")),
+                }
+            }
+        }
+    }
+}
+
+impl fmt::Display for CorePolicy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CorePolicy::All => write!(f, "all"),
+            CorePolicy::PerformanceOnly => write!(f, "p-cores"),
+            CorePolicy::EfficiencyOnly => write!(f, "e-cores"),
+            CorePolicy::Weighted { performance, efficiency } => write!(f, "{performance}:{efficiency}"),
+        }
+    }
+}
+
+/// The core type every mining thread is pinned to.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ThreadPlacement {
+    /// Per thread, its core type and the CPUs it may run on; empty when threads are not pinned
+    threads: Vec<(CoreType, Vec<usize>)>,
+    num_threads: usize,
+}
+
+impl ThreadPlacement {
+    /// Places the mining threads according to a policy.
+    ///
+    /// # Arguments
+    /// * `policy` - Which cores the threads run on.
+    /// * `topology` - The cores of the CPU, `None` if it is not hybrid, in which case threads are not pinned.
+    /// * `requested_threads` - `NUMBER_OF_THREADS`, if set.
+    /// * `available_cpus` - The number of CPUs the process may use.
+    ///
+    /// # Returns
+    /// The placement, whose number of threads replaces `NUMBER_OF_THREADS` with a weighted policy.
+    pub fn plan(policy: CorePolicy, topology: Option<&CoreTopology>, requested_threads: Option<usize>, available_cpus: usize) -> Self {
+        let unpinned = |num_threads| ThreadPlacement { threads: Vec::new(), num_threads };
+        let Some(topology) = topology else {
+            return match policy {
+                CorePolicy::Weighted { performance, efficiency } => unpinned(performance + efficiency),
+                _ => unpinned(requested_threads.unwrap_or(available_cpus)),
+            };
+        };
+
+        let pinned = |counts: &[(CoreType, usize)]| {
+            let threads = counts.iter()
+                .flat_map(|(core_type, count)| (0..*count).map(|_| (*core_type, topology.cpus(*core_type).to_vec())))
+                .collect::<Vec<_>>();
+            ThreadPlacement { num_threads: threads.len(), threads }
+        };
+        match policy {
+            CorePolicy::All => unpinned(requested_threads.unwrap_or(available_cpus)),
+            CorePolicy::PerformanceOnly => pinned(&[(CoreType::Performance, requested_threads.unwrap_or(topology.performance.len().min(available_cpus)))]),
+            CorePolicy::EfficiencyOnly => pinned(&[(CoreType::Efficiency, requested_threads.unwrap_or(topology.efficiency.len().min(available_cpus)))]),
+            CorePolicy::Weighted { performance, efficiency } => pinned(&[(CoreType::Performance, performance), (CoreType::Efficiency, efficiency)]),
+        }
+    }
+
+    /// Gets the number of mining threads.
+    pub fn get_num_threads(&self) -> usize {
+        self.num_threads
+    }
+
+    /// Gets the core type a thread is pinned to.
+    ///
+    /// # Returns
+    /// The core type, `None` if the thread is not pinned.
+    pub fn core_type(&self, idx: usize) -> Option<CoreType> {
+        self.threads.get(idx).map(|(core_type, _)| *core_type)
+    }
+
+    /// Gets the CPUs a thread may run on.
+    ///
+    /// # Returns
+    /// The CPUs, empty if the thread is not pinned.
+    pub fn cpus(&self, idx: usize) -> &[usize] {
+        self.threads.get(idx).map_or(&[], |(_, cpus)| cpus.as_slice())
+    }
+
+    /// Counts the threads pinned to each core type.
+    ///
+    /// # Returns
+    /// The number of threads on performance and on efficiency cores.
+    pub fn counts(&self) -> (usize, usize) {
+        let performance = self.threads.iter().filter(|(core_type, _)| *core_type == CoreType::Performance).count();
+        (performance, self.threads.len() - performance)
+    }
+}
+
+/// Restricts the calling OS thread to a set of CPUs.
+///
+/// # Arguments
+/// * `cpus` - The CPUs the thread may run on, nothing is done if empty.
+///
+/// # Returns
+/// `true` if the thread is pinned, `false` if pinning failed or is not supported on this OS.
+pub fn pin_current_thread(cpus: &[usize]) -> bool {
+    if cpus.is_empty() {
+        return false;
+    }
+
+    #[cfg(target_os = "linux")]
+    {
+        extern "C" {
+            fn sched_setaffinity(pid: i32, size: usize, mask: *const u64) -> i32;
+        }
+
+        let mut mask = [0u64; MAX_CPUS / 64];
+        for cpu in cpus.iter().filter(|cpu| **cpu < MAX_CPUS) {
+            mask[cpu / 64] |= 1 << (cpu % 64);
+        }
+        // A pid of 0 is the calling thread
+        unsafe { sched_setaffinity(0, size_of_val(&mask), mask.as_ptr()) == 0 }
+    }
+    #[cfg(not(target_os = "linux"))]
+    {
+        let _ = MAX_CPUS;
+        false
+    }
+}
+
+#[test]
+/// Tests the detection of both kinds of sysfs trees and the placement of the threads.
+fn test_thread_placement() {
+    assert_eq!(parse_cpu_list("0-3,8,10-11\n"), [0, 1, 2, 3, 8, 10, 11]);
+    assert_eq!(parse_cpu_list(""), Vec::<usize>::new());
+
+    let devices = std::env::temp_dir().join(format!("qiner-cores-test-{}", std::process::id()));
+    for (pmu, cpus) in [("cpu_core", "0-3"), ("cpu_atom", "4-7")] {
+        std::fs::create_dir_all(devices.join(pmu)).unwrap();
+        std::fs::write(devices.join(pmu).join("cpus"), cpus).unwrap();
+    }
+    let intel = detect_topology_in(&devices).unwrap();
+    assert_eq!(intel, CoreTopology { performance: vec![0, 1, 2, 3], efficiency: vec![4, 5, 6, 7] });
+
+    std::fs::remove_dir_all(&devices).unwrap();
+    for (cpu, capacity) in [(0, 446), (1, 446), (2, 1024)] {
+        std::fs::create_dir_all(devices.join(format!("system/cpu/cpu{cpu}"))).unwrap();
+        std::fs::write(devices.join(format!("system/cpu/cpu{cpu}/cpu_capacity")), capacity.to_string()).unwrap();
+    }
+    let arm = detect_topology_in(&devices).unwrap();
+    std::fs::remove_dir_all(&devices).unwrap();
+    assert_eq!(arm, CoreTopology { performance: vec![2], efficiency: vec![0, 1] });
+
+    assert_eq!(CorePolicy::parse("P-Cores"), Ok(CorePolicy::PerformanceOnly));
+    assert_eq!(CorePolicy::parse("6:2"), Ok(CorePolicy::Weighted { performance: 6, efficiency: 2 }));
+    assert!(CorePolicy::parse("0:0").is_err());
+
+    let placement = ThreadPlacement::plan(CorePolicy::PerformanceOnly, Some(&intel), None, 8);
+    assert_eq!(placement.get_num_threads(), 4);
+    assert_eq!(placement.cpus(3), [0, 1, 2, 3]);
+    let placement = ThreadPlacement::plan(CorePolicy::Weighted { performance: 3, efficiency: 1 }, Some(&intel), Some(16), 8);
+    assert_eq!((placement.get_num_threads(), placement.counts()), (4, (3, 1)));
+    assert_eq!(placement.core_type(3), Some(CoreType::Efficiency));
+    let placement = ThreadPlacement::plan(CorePolicy::PerformanceOnly, None, None, 8);
+    assert_eq!((placement.get_num_threads(), placement.cpus(0)), (8, &[][..]));
+}
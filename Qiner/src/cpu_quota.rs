@@ -0,0 +1,147 @@
+/// Gets the number of CPUs the process may use, accounting for container limits.
+///
+/// Cgroup v1/v2 CPU quotas on Linux and job object CPU rate limits on Windows are
+/// taken into account, so a container limited to 4 CPUs on a 64 core host gets 4.
+///
+/// # Returns
+/// The number of usable CPUs, at least 1.
+pub fn get_available_cpus() -> usize {
+    let cpus = num_cpus::get();
+    match get_cpu_quota() {
+        Some(quota) => (quota.ceil() as usize).clamp(1, cpus.max(1)),
+        None => cpus.max(1),
+    }
+}
+
+/// Gets the CPU quota of the process, in CPUs.
+///
+/// # Returns
+/// The quota, e.g. `2.5` for 250 ms of CPU time every 100 ms, or `None` if the process is not limited.
+#[cfg(target_os = "linux")]
+pub fn get_cpu_quota() -> Option<f64> {
+    use std::fs;
+
+    // cgroup v2: the "0::" line of /proc/self/cgroup is the path in the unified hierarchy
+    let cgroup_path = fs::read_to_string("/proc/self/cgroup").ok()
+        .and_then(|content| content.lines().find_map(|line| line.strip_prefix("0::").map(str::to_string)))
+        .unwrap_or_default();
+    for dir in [format!("/sys/fs/cgroup{}", cgroup_path.trim_end_matches('/')), "/sys/fs/cgroup".to_string()] {
+        if let Ok(content) = fs::read_to_string(format!("{dir}/cpu.max")) {
+            return parse_cpu_max(&content);
+        }
+    }
+
+    // cgroup v1
+    for dir in ["/sys/fs/cgroup/cpu", "/sys/fs/cgroup/cpu,cpuacct"] {
+        let quota = fs::read_to_string(format!("{dir}/cpu.cfs_quota_us"));
+        let period = fs::read_to_string(format!("{dir}/cpu.cfs_period_us"));
+        if let (Ok(quota), Ok(period)) = (quota, period) {
+            return parse_cfs_quota(&quota, &period);
+        }
+    }
+
+    None
+}
+
+/// Gets the CPU quota of the process, in CPUs.
+///
+/// # Returns
+/// The quota, e.g. `2.5` for a hard cap of 25% of a 10 CPU machine, or `None` if the process is not limited.
+#[cfg(windows)]
+pub fn get_cpu_quota() -> Option<f64> {
+    use std::ffi::c_void;
+    use std::mem::size_of;
+    use std::ptr;
+
+    const JOB_OBJECT_CPU_RATE_CONTROL_INFORMATION: i32 = 15;
+    const JOB_OBJECT_CPU_RATE_CONTROL_ENABLE: u32 = 0x1;
+    const JOB_OBJECT_CPU_RATE_CONTROL_HARD_CAP: u32 = 0x4;
+    const JOB_OBJECT_CPU_RATE_CONTROL_MIN_MAX_RATE: u32 = 0x10;
+
+    /// JOBOBJECT_CPU_RATE_CONTROL_INFORMATION, the rate being in 1/100 of a percent of all CPUs
+    #[repr(C)]
+    #[derive(Default)]
+    struct CpuRateControlInformation {
+        control_flags: u32,
+        rate: u32,
+    }
+
+    #[link(name = "kernel32")]
+    extern "system" {
+        fn QueryInformationJobObject(job: *mut c_void, class: i32, info: *mut c_void, length: u32, return_length: *mut u32) -> i32;
+    }
+
+    // A null handle queries the job of the current process
+    let mut info = CpuRateControlInformation::default();
+    let is_ok = unsafe {
+        QueryInformationJobObject(
+            ptr::null_mut(),
+            JOB_OBJECT_CPU_RATE_CONTROL_INFORMATION,
+            &mut info as *mut CpuRateControlInformation as *mut c_void,
+            size_of::<CpuRateControlInformation>() as u32,
+            ptr::null_mut(),
+        )
+    } != 0;
+    if !is_ok || info.control_flags & JOB_OBJECT_CPU_RATE_CONTROL_ENABLE == 0 {
+        return None;
+    }
+
+    let rate = if info.control_flags & JOB_OBJECT_CPU_RATE_CONTROL_MIN_MAX_RATE != 0 {
+        // The maximum rate is the high word
+        info.rate >> 16
+    } else if info.control_flags & JOB_OBJECT_CPU_RATE_CONTROL_HARD_CAP != 0 {
+        info.rate
+    } else {
+        // Weight based scheduling does not cap the CPU time
+        return None;
+    };
+
+    Some(num_cpus::get() as f64 * rate as f64 / 10000.0)
+}
+
+/// Gets the CPU quota of the process, in CPUs.
+///
+/// # Returns
+/// `None`, CPU quotas are not detected on this platform.
+#[cfg(not(any(target_os = "linux", windows)))]
+pub fn get_cpu_quota() -> Option<f64> {
+    None
+}
+
+/// Parses the content of a cgroup v2 `cpu.max` file.
+///
+/// # Arguments
+/// * `content` - The content of the file, `"<quota> <period>"` or `"max <period>"`.
+///
+/// # Returns
+/// The quota in CPUs, or `None` if unlimited or invalid.
+pub fn parse_cpu_max(content: &str) -> Option<f64> {
+    let mut parts = content.split_whitespace();
+    let quota = parts.next()?.parse::<f64>().ok()?;
+    let period = parts.next()?.parse::<f64>().ok()?;
+    (quota > 0.0 && period > 0.0).then(|| quota / period)
+}
+
+/// Parses the content of the cgroup v1 `cpu.cfs_quota_us` and `cpu.cfs_period_us` files.
+///
+/// # Arguments
+/// * `quota` - The content of `cpu.cfs_quota_us`, `-1` if unlimited.
+/// * `period` - The content of `cpu.cfs_period_us`.
+///
+/// # Returns
+/// The quota in CPUs, or `None` if unlimited or invalid.
+pub fn parse_cfs_quota(quota: &str, period: &str) -> Option<f64> {
+    let quota = quota.trim().parse::<f64>().ok()?;
+    let period = period.trim().parse::<f64>().ok()?;
+    (quota > 0.0 && period > 0.0).then(|| quota / period)
+}
+
+#[test]
+/// Tests the parsing of cgroup v1 and v2 CPU quotas.
+fn test_parse_cpu_quota() {
+    assert_eq!(parse_cpu_max("400000 100000\n"), Some(4.0));
+    assert_eq!(parse_cpu_max("150000 100000"), Some(1.5));
+    assert_eq!(parse_cpu_max("max 100000\n"), None);
+    assert_eq!(parse_cfs_quota("200000\n", "100000\n"), Some(2.0));
+    assert_eq!(parse_cfs_quota("-1\n", "100000\n"), None);
+}
@@ -0,0 +1,73 @@
+//! KangarooTwelve hashing shared by the packets, the IDs, the identities and the snapshots.
+//!
+//! A hasher carries an 8 KiB chunk buffer besides its two TurboSHAKE states, so creating one per
+//! hash moves a lot of memory when a burst of packets is built, each grinding its gamming key
+//! hundreds of times. With `std`, every thread keeps one hasher and resets it after each hash;
+//! without `std`, a hasher is created per hash.
+
+use k12::digest::{ExtendableOutputReset, Update};
+use k12::KangarooTwelve;
+
+#[cfg(feature = "std")]
+std::thread_local! {
+    /// The hasher of the thread, always reset between two hashes
+    static KANGAROO_TWELVE: core::cell::RefCell<KangarooTwelve<'static>> = core::cell::RefCell::new(KangarooTwelve::default());
+}
+
+/// Hashes data with KangarooTwelve.
+///
+/// # Arguments
+/// * `data` - The data to hash.
+/// * `output` - The buffer filled with the hash, of any length.
+pub fn k12_into(data: &[u8], output: &mut [u8]) {
+    #[cfg(feature = "std")]
+    {
+        // The hasher of the thread is gone while the thread exits
+        let is_hashed = KANGAROO_TWELVE.try_with(|kangaroo_twelve| hash_with(&mut kangaroo_twelve.borrow_mut(), data, output)).is_ok();
+        if is_hashed {
+            return;
+        }
+    }
+    hash_with(&mut KangarooTwelve::default(), data, output);
+}
+
+/// Hashes data into 32 bytes with KangarooTwelve.
+///
+/// # Arguments
+/// * `data` - The data to hash.
+///
+/// # Returns
+/// The 32 bytes of the hash.
+pub fn k12_32(data: &[u8]) -> [u8; 32] {
+    let mut output = [0u8; 32];
+    k12_into(data, &mut output);
+    output
+}
+
+/// Hashes data, leaving the hasher reset for the next hash.
+fn hash_with(kangaroo_twelve: &mut KangarooTwelve<'static>, data: &[u8], output: &mut [u8]) {
+    kangaroo_twelve.update(data);
+    kangaroo_twelve.finalize_xof_reset_into(output);
+}
+
+#[test]
+/// Tests that the reused hasher gives the hashes of a fresh one, whatever was hashed before.
+fn test_k12_reuse() {
+    use k12::digest::ExtendableOutput;
+
+    let fresh = |data: &[u8]| {
+        let mut kangaroo_twelve = KangarooTwelve::default();
+        kangaroo_twelve.update(data);
+        let mut output = [0u8; 32];
+        kangaroo_twelve.finalize_xof_into(&mut output);
+        output
+    };
+
+    let long = [7u8; 20_000];
+    for data in [&b""[..], b"qubic", &long, b"qubic"] {
+        assert_eq!(k12_32(data), fresh(data));
+    }
+    let mut short = [0u8; 3];
+    k12_into(b"qubic", &mut short);
+    assert_eq!(short, fresh(b"qubic")[..3]);
+}
@@ -0,0 +1,302 @@
+use std::collections::VecDeque;
+use std::env;
+use std::fmt;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use lib::env_names::{ENV_ALERT_HASHRATE_DROP_PERCENT, ENV_ALERT_MIN_HASHRATE, ENV_ALERT_MIN_SOLUTIONS_PER_HOUR, ENV_ALERT_SUSTAINED_MINUTES};
+use crate::miner::Miner;
+use crate::notifier::{Notifier, NotifyEvent};
+
+/// Default drop of the hashrate below the session baseline that is reported, in percent.
+pub const DEFAULT_HASHRATE_DROP_PERCENT: f64 = 30.0;
+
+/// Default time a degradation must last before it is reported.
+pub const DEFAULT_SUSTAINED: Duration = Duration::from_secs(5 * 60);
+
+/// Exit code of the process when it exits on a sustained degradation.
+pub const DEGRADED_EXIT_CODE: i32 = 76;
+
+/// Window the rolling hashrate is measured over.
+const HASHRATE_WINDOW: Duration = Duration::from_secs(5 * 60);
+
+/// Window the rolling solution rate is measured over, solutions being rare.
+const SOLUTION_RATE_WINDOW: Duration = Duration::from_secs(60 * 60);
+
+/// Interval between two samples of the miner counters.
+const WATCHDOG_INTERVAL: Duration = Duration::from_secs(10);
+
+/// The limits below which mining is considered degraded.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct AlertConfig {
+    /// Minimum rolling it/s, `0` to disable
+    pub min_hashrate: f64,
+    /// Minimum rolling solutions per hour, `0` to disable
+    pub min_solutions_per_hour: f64,
+    /// Drop below the best rolling it/s of the session, in percent, `0` to disable
+    pub hashrate_drop_percent: f64,
+    /// Time a degradation must last before it is reported
+    pub sustained: Duration,
+}
+
+impl Default for AlertConfig {
+    fn default() -> Self {
+        AlertConfig {
+            min_hashrate: 0.0,
+            min_solutions_per_hour: 0.0,
+            hashrate_drop_percent: DEFAULT_HASHRATE_DROP_PERCENT,
+            sustained: DEFAULT_SUSTAINED,
+        }
+    }
+}
+
+impl AlertConfig {
+    /// Creates an `AlertConfig` from the environment variables, with the defaults for the ones not set.
+    pub fn from_env() -> Self {
+        let value = |name: &str| env::var(name).ok().and_then(|value| value.trim().parse::<f64>().ok()).filter(|value| *value >= 0.0);
+        let defaults = AlertConfig::default();
+        AlertConfig {
+            min_hashrate: value(ENV_ALERT_MIN_HASHRATE).unwrap_or(defaults.min_hashrate),
+            min_solutions_per_hour: value(ENV_ALERT_MIN_SOLUTIONS_PER_HOUR).unwrap_or(defaults.min_solutions_per_hour),
+            hashrate_drop_percent: value(ENV_ALERT_HASHRATE_DROP_PERCENT).map_or(defaults.hashrate_drop_percent, |percent| percent.min(100.0)),
+            sustained: value(ENV_ALERT_SUSTAINED_MINUTES).map_or(defaults.sustained, |minutes| Duration::from_secs_f64(minutes * 60.0)),
+        }
+    }
+
+    /// Checks if at least one limit is enabled.
+    pub fn is_enabled(&self) -> bool {
+        self.min_hashrate > 0.0 || self.min_solutions_per_hour > 0.0 || self.hashrate_drop_percent > 0.0
+    }
+}
+
+/// A limit mining fell below.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Degradation {
+    /// The rolling hashrate is below the configured minimum.
+    BelowMinimumHashrate { it_per_sec: f64, minimum: f64 },
+    /// The rolling hashrate dropped too far below the best one of the session.
+    BelowBaseline { it_per_sec: f64, baseline: f64 },
+    /// The rolling solution rate is below the configured minimum.
+    BelowMinimumSolutionRate { per_hour: f64, minimum: f64 },
+}
+
+impl fmt::Display for Degradation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Degradation::BelowMinimumHashrate { it_per_sec, minimum } => write!(f, "{it_per_sec:.1} it/s, below the minimum of {minimum}"),
+            Degradation::BelowBaseline { it_per_sec, baseline } => {
+                write!(f, "{it_per_sec:.1} it/s, {:.0}% below the session baseline of {baseline:.1}", 100.0 * (1.0 - it_per_sec / baseline))
+            }
+            Degradation::BelowMinimumSolutionRate { per_hour, minimum } => write!(f, "{per_hour:.2} solutions per hour, below the minimum of {minimum}"),
+        }
+    }
+}
+
+/// A change of the state of the watchdog worth reporting.
+#[derive(Debug, Clone, PartialEq)]
+pub enum WatchdogEvent {
+    /// Mining has been degraded for the sustained duration.
+    Degraded(Vec<Degradation>),
+    /// Mining is back above every limit after a reported degradation.
+    Recovered,
+}
+
+/// Counters of the miner at one moment.
+#[derive(Debug, Clone, Copy)]
+struct Sample {
+    at: Instant,
+    iterations: usize,
+    solutions: usize,
+}
+
+/// Compares the rolling hashrate and solution rate with the limits, reporting sustained degradations once.
+#[derive(Debug)]
+pub struct DegradationWatchdog {
+    config: AlertConfig,
+    samples: VecDeque<Sample>,
+    /// Best rolling it/s of the session, since the last change of the number of active threads
+    baseline: f64,
+    active_threads: usize,
+    degraded_since: Option<Instant>,
+    is_reported: bool,
+}
+
+impl DegradationWatchdog {
+    /// Creates a new `DegradationWatchdog`.
+    ///
+    /// # Arguments
+    /// * `config` - The limits below which mining is degraded.
+    ///
+    /// # Returns
+    /// A new `DegradationWatchdog`.
+    pub fn new(config: AlertConfig) -> Self {
+        DegradationWatchdog {
+            config,
+            samples: VecDeque::new(),
+            baseline: 0.0,
+            active_threads: 0,
+            degraded_since: None,
+            is_reported: false,
+        }
+    }
+
+    /// Records the counters of the miner and checks the rolling rates against the limits.
+    ///
+    /// # Arguments
+    /// * `now` - The current time.
+    /// * `iterations` - The iteration count of the miner.
+    /// * `solutions` - The number of solutions found.
+    /// * `active_threads` - The number of threads allowed to mine, `0` while paused.
+    ///
+    /// # Returns
+    /// The event to report, if the state changed.
+    pub fn record(&mut self, now: Instant, iterations: usize, solutions: usize, active_threads: usize) -> Option<WatchdogEvent> {
+        // The rates of another number of threads are not comparable, so measuring starts over
+        if active_threads != self.active_threads {
+            self.active_threads = active_threads;
+            self.samples.clear();
+            self.baseline = 0.0;
+            self.degraded_since = None;
+        }
+        self.samples.push_back(Sample { at: now, iterations, solutions });
+        while self.samples.get(1).is_some_and(|second| now.saturating_duration_since(second.at) >= SOLUTION_RATE_WINDOW) {
+            self.samples.pop_front();
+        }
+
+        let degradations = if active_threads > 0 { self.check(now) } else { Vec::new() };
+        if degradations.is_empty() {
+            self.degraded_since = None;
+            return std::mem::take(&mut self.is_reported).then_some(WatchdogEvent::Recovered);
+        }
+
+        let degraded_since = *self.degraded_since.get_or_insert(now);
+        if !self.is_reported && now.saturating_duration_since(degraded_since) >= self.config.sustained {
+            self.is_reported = true;
+            return Some(WatchdogEvent::Degraded(degradations));
+        }
+        None
+    }
+
+    /// Lists the limits the rolling rates are below, updating the baseline.
+    fn check(&mut self, now: Instant) -> Vec<Degradation> {
+        let mut degradations = Vec::new();
+
+        if let Some(it_per_sec) = self.rate(now, HASHRATE_WINDOW, |sample| sample.iterations) {
+            if self.config.min_hashrate > 0.0 && it_per_sec < self.config.min_hashrate {
+                degradations.push(Degradation::BelowMinimumHashrate { it_per_sec, minimum: self.config.min_hashrate });
+            }
+            if self.config.hashrate_drop_percent > 0.0 && it_per_sec < self.baseline * (1.0 - self.config.hashrate_drop_percent / 100.0) {
+                degradations.push(Degradation::BelowBaseline { it_per_sec, baseline: self.baseline });
+            }
+            self.baseline = self.baseline.max(it_per_sec);
+        }
+
+        if self.config.min_solutions_per_hour > 0.0 {
+            if let Some(per_sec) = self.rate(now, SOLUTION_RATE_WINDOW, |sample| sample.solutions) {
+                let per_hour = per_sec * 3600.0;
+                if per_hour < self.config.min_solutions_per_hour {
+                    degradations.push(Degradation::BelowMinimumSolutionRate { per_hour, minimum: self.config.min_solutions_per_hour });
+                }
+            }
+        }
+
+        degradations
+    }
+
+    /// Computes the rate of a counter over the window.
+    ///
+    /// # Returns
+    /// The rate per second, or `None` until a full window was sampled.
+    fn rate(&self, now: Instant, window: Duration, counter: impl Fn(&Sample) -> usize) -> Option<f64> {
+        let last = self.samples.back()?;
+        let start = self.samples.iter().rev().find(|sample| now.saturating_duration_since(sample.at) >= window)?;
+        let secs = last.at.saturating_duration_since(start.at).as_secs_f64();
+        (secs > 0.0).then(|| counter(last).saturating_sub(counter(start)) as f64 / secs)
+    }
+}
+
+/// Asynchronous task logging and notifying sustained degradations of mining and their recovery.
+///
+/// # Arguments
+/// * `miner` - Shared reference to the Miner instance
+/// * `config` - The limits below which mining is degraded
+/// * `notifier` - Optional notifier for degradations
+/// * `is_exit` - Whether to return on the first sustained degradation, for the process to exit
+///
+/// # Returns
+/// The degradations, once sustained, if `is_exit` is set; never returns otherwise.
+pub async fn watch_degradation(miner: Arc<Miner>, config: AlertConfig, notifier: Option<Notifier>, is_exit: bool) -> Vec<Degradation> {
+    let mut watchdog = DegradationWatchdog::new(config);
+
+    loop {
+        tokio::time::sleep(WATCHDOG_INTERVAL).await;
+
+        let snapshot = miner.snapshot();
+        let active_threads = if snapshot.is_paused { 0 } else { snapshot.active_threads };
+        match watchdog.record(Instant::now(), snapshot.iterations, snapshot.solutions_found, active_threads) {
+            Some(WatchdogEvent::Degraded(degradations)) => {
+                let message = degradations.iter().map(ToString::to_string).collect::<Vec<_>>().join(", ");
+                log::warn!("Mining degraded for {:?}: {message}", config.sustained);
+                if let Some(notifier) = &notifier {
+                    notifier.notify(NotifyEvent::MiningDegraded(message));
+                }
+                if is_exit {
+                    return degradations;
+                }
+            }
+            Some(WatchdogEvent::Recovered) => {
+                log::info!("Mining recovered from the degradation");
+                if let Some(notifier) = &notifier {
+                    notifier.notify(NotifyEvent::MiningRecovered);
+                }
+            }
+            None => {}
+        }
+    }
+}
+
+#[test]
+/// Tests that only sustained degradations are reported, once, and that recovery is reported.
+fn test_degradation_watchdog() {
+    let config = AlertConfig { min_hashrate: 0.0, min_solutions_per_hour: 0.0, hashrate_drop_percent: 30.0, sustained: Duration::from_secs(60) };
+    let mut watchdog = DegradationWatchdog::new(config);
+    let started_at = Instant::now();
+    let mut iterations = 0;
+
+    // 100 it/s for 10 minutes sets the baseline
+    let mut events = Vec::new();
+    for secs in (0..=600).step_by(10) {
+        events.extend(watchdog.record(started_at + Duration::from_secs(secs), iterations, 0, 4));
+        iterations += 1000;
+    }
+    assert!(events.is_empty());
+
+    // Throttled to 10 it/s: the rolling rate falls below 70 it/s, then must stay there a minute
+    for secs in (610..=1200).step_by(10) {
+        iterations += 100;
+        events.extend(watchdog.record(started_at + Duration::from_secs(secs), iterations, 0, 4));
+    }
+    assert!(matches!(events.as_slice(), [WatchdogEvent::Degraded(degradations)] if matches!(degradations[0], Degradation::BelowBaseline { .. })));
+
+    // Back to 100 it/s
+    events.clear();
+    for secs in (1210..=1800).step_by(10) {
+        iterations += 1000;
+        events.extend(watchdog.record(started_at + Duration::from_secs(secs), iterations, 0, 4));
+    }
+    assert_eq!(events, [WatchdogEvent::Recovered]);
+
+    // Fewer threads reset the baseline instead of reporting a drop
+    for secs in (1810..=2400).step_by(10) {
+        iterations += 100;
+        events.extend(watchdog.record(started_at + Duration::from_secs(secs), iterations, 0, 1));
+    }
+    assert_eq!(events, [WatchdogEvent::Recovered]);
+
+    // Solutions are checked over an hour
+    let config = AlertConfig { min_hashrate: 0.0, min_solutions_per_hour: 2.0, hashrate_drop_percent: 0.0, sustained: Duration::ZERO };
+    let mut watchdog = DegradationWatchdog::new(config);
+    assert_eq!(watchdog.record(started_at, 0, 0, 1), None);
+    assert_eq!(watchdog.record(started_at + Duration::from_secs(1800), 0, 0, 1), None);
+    let event = watchdog.record(started_at + Duration::from_secs(3600), 0, 1, 1);
+    assert_eq!(event, Some(WatchdogEvent::Degraded(vec![Degradation::BelowMinimumSolutionRate { per_hour: 1.0, minimum: 2.0 }])));
+}
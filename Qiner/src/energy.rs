@@ -0,0 +1,183 @@
+//! Estimation of the energy the CPU spends mining, from the RAPL counters of the packages.
+//!
+//! The counters are cumulative microjoules wrapping around at `max_energy_range_uj`, so the
+//! meter keeps its own total from the deltas between two readings. Recent kernels restrict
+//! `energy_uj` to root, the meter is then unavailable.
+
+use std::path::{Path, PathBuf};
+use std::time::Instant;
+
+/// Directory of the powercap zones, RAPL packages being `intel-rapl:N` on Intel and AMD alike.
+#[cfg(target_os = "linux")]
+const POWERCAP_DIR: &str = "/sys/class/powercap";
+
+/// A RAPL package and its last reading.
+#[derive(Debug, Clone)]
+struct RaplDomain {
+    energy_file: PathBuf,
+    /// Value the counter wraps around at, in microjoules
+    max_range_uj: u64,
+    last_uj: u64,
+}
+
+impl RaplDomain {
+    /// Opens a RAPL package.
+    ///
+    /// # Arguments
+    /// * `dir` - The powercap zone of the package
+    ///
+    /// # Returns
+    /// The package with its current reading, or `None` if its counter is not readable.
+    fn open(dir: &Path) -> Option<Self> {
+        let energy_file = dir.join("energy_uj");
+        let last_uj = read_microjoules(&energy_file)?;
+        let max_range_uj = read_microjoules(&dir.join("max_energy_range_uj")).unwrap_or(0);
+        Some(RaplDomain { energy_file, max_range_uj, last_uj })
+    }
+}
+
+/// The energy spent since the meter was opened and the power drawn lately.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct EnergyReading {
+    /// Energy spent by the packages since the meter was opened, in joules
+    pub joules: f64,
+    /// Power drawn by the packages since the previous reading, in watts
+    pub watts: f64,
+}
+
+impl EnergyReading {
+    /// Computes the energy spent per solution.
+    ///
+    /// # Arguments
+    /// * `solutions` - The solutions found while the energy was spent
+    ///
+    /// # Returns
+    /// The joules per solution, `None` before the first solution.
+    pub fn joules_per_solution(&self, solutions: usize) -> Option<f64> {
+        (solutions > 0).then(|| self.joules / solutions as f64)
+    }
+
+    /// Computes the iterations per joule, the efficiency comparable across thread counts and backends.
+    ///
+    /// # Arguments
+    /// * `iterations` - The iterations done while the energy was spent
+    ///
+    /// # Returns
+    /// The iterations per joule, `0` before any energy was measured.
+    pub fn it_per_joule(&self, iterations: usize) -> f64 {
+        if self.joules > 0.0 { iterations as f64 / self.joules } else { 0.0 }
+    }
+}
+
+/// Accumulates the energy of the RAPL packages across readings.
+#[derive(Debug, Clone)]
+pub struct EnergyMeter {
+    domains: Vec<RaplDomain>,
+    joules: f64,
+    last_read_at: Instant,
+}
+
+impl EnergyMeter {
+    /// Opens the RAPL packages of the machine.
+    ///
+    /// # Returns
+    /// The meter, or `None` if no package counter is readable.
+    #[cfg(target_os = "linux")]
+    pub fn open() -> Option<Self> {
+        let mut zones = std::fs::read_dir(POWERCAP_DIR).ok()?
+            .filter_map(|entry| entry.ok())
+            .map(|entry| entry.path())
+            // Sub-zones, like `intel-rapl:0:0` for the cores, are already counted in their package
+            .filter(|path| path.file_name().is_some_and(|name| name.to_string_lossy().starts_with("intel-rapl:") && name.to_string_lossy().matches(':').count() == 1))
+            .collect::<Vec<_>>();
+        zones.sort();
+        EnergyMeter::from_domains(zones.iter().filter_map(|zone| RaplDomain::open(zone)).collect())
+    }
+
+    /// Opens the RAPL packages of the machine.
+    ///
+    /// # Returns
+    /// Always `None`, RAPL is only read on Linux.
+    #[cfg(not(target_os = "linux"))]
+    pub fn open() -> Option<Self> {
+        None
+    }
+
+    fn from_domains(domains: Vec<RaplDomain>) -> Option<Self> {
+        (!domains.is_empty()).then(|| EnergyMeter { domains, joules: 0.0, last_read_at: Instant::now() })
+    }
+
+    /// Reads the counters of the packages, a package failing to read counting for nothing.
+    ///
+    /// # Returns
+    /// The energy spent since the meter was opened and the power drawn since the previous reading.
+    pub fn read(&mut self) -> EnergyReading {
+        let mut delta_uj = 0;
+        for domain in self.domains.iter_mut() {
+            if let Some(current_uj) = read_microjoules(&domain.energy_file) {
+                delta_uj += energy_delta_uj(domain.last_uj, current_uj, domain.max_range_uj);
+                domain.last_uj = current_uj;
+            }
+        }
+        self.account(delta_uj)
+    }
+
+    fn account(&mut self, delta_uj: u64) -> EnergyReading {
+        let joules = delta_uj as f64 / 1_000_000.0;
+        let elapsed = self.last_read_at.elapsed().as_secs_f64();
+        self.last_read_at = Instant::now();
+        self.joules += joules;
+        EnergyReading { joules: self.joules, watts: if elapsed > 0.0 { joules / elapsed } else { 0.0 } }
+    }
+}
+
+/// Reads a counter of a powercap zone.
+fn read_microjoules(file: &Path) -> Option<u64> {
+    std::fs::read_to_string(file).ok()?.trim().parse().ok()
+}
+
+/// Computes the energy spent between two readings of a counter, which wraps around.
+///
+/// # Arguments
+/// * `previous_uj` - The previous reading, in microjoules
+/// * `current_uj` - The current reading, in microjoules
+/// * `max_range_uj` - The value the counter wraps around at, `0` if unknown
+///
+/// # Returns
+/// The microjoules spent, `0` when the counter went back without a known range.
+pub fn energy_delta_uj(previous_uj: u64, current_uj: u64, max_range_uj: u64) -> u64 {
+    if current_uj >= previous_uj {
+        current_uj - previous_uj
+    } else if max_range_uj >= previous_uj {
+        max_range_uj - previous_uj + current_uj
+    } else {
+        0
+    }
+}
+
+#[test]
+/// Tests the deltas of wrapping counters and the efficiency derived from the energy.
+fn test_energy_meter() {
+    assert_eq!(energy_delta_uj(1_000, 5_000, 10_000), 4_000);
+    assert_eq!(energy_delta_uj(9_000, 1_000, 10_000), 2_000);
+    assert_eq!(energy_delta_uj(9_000, 1_000, 0), 0);
+
+    let dir = std::env::temp_dir().join(format!("qiner-rapl-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    std::fs::write(dir.join("energy_uj"), "262143000000\n").unwrap();
+    std::fs::write(dir.join("max_energy_range_uj"), "262143328850\n").unwrap();
+    let mut meter = EnergyMeter::from_domains(vec![RaplDomain::open(&dir).unwrap()]).unwrap();
+    // 10 joules across the wrap around of the counter
+    std::fs::write(dir.join("energy_uj"), "9671150\n").unwrap();
+    let reading = meter.read();
+    assert!((reading.joules - 10.0).abs() < 1e-6);
+    assert!(reading.watts > 0.0);
+    std::fs::remove_dir_all(&dir).unwrap();
+
+    assert!(EnergyMeter::from_domains(Vec::new()).is_none());
+    let reading = EnergyReading { joules: 500.0, watts: 50.0 };
+    assert_eq!(reading.joules_per_solution(0), None);
+    assert_eq!(reading.joules_per_solution(4), Some(125.0));
+    assert_eq!(reading.it_per_joule(1_000_000), 2_000.0);
+    assert_eq!(EnergyReading::default().it_per_joule(1_000_000), 0.0);
+}
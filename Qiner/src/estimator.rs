@@ -0,0 +1,99 @@
+use std::time::Duration;
+
+/// Estimates the probability that a single nonce reaches the solution threshold.
+///
+/// Every point of score requires the output neuron to match one bit of the mining data,
+/// which happens with a probability of one half, so a score of at least `threshold`
+/// is expected with a probability of `2^-threshold`.
+///
+/// # Arguments
+/// * `threshold` - The solution threshold.
+///
+/// # Returns
+/// The probability for one iteration to produce a solution.
+pub fn solution_probability(threshold: usize) -> f64 {
+    0.5f64.powi(threshold.min(i32::MAX as usize) as i32)
+}
+
+/// Computes the expected time between two solutions.
+///
+/// # Arguments
+/// * `threshold` - The solution threshold.
+/// * `it_per_sec` - The observed number of iterations per second.
+///
+/// # Returns
+/// The expected time between solutions, or `None` if nothing is being mined.
+pub fn expected_time_between_solutions(threshold: usize, it_per_sec: f64) -> Option<Duration> {
+    if it_per_sec <= 0.0 {
+        return None;
+    }
+    Duration::try_from_secs_f64(1.0 / (solution_probability(threshold) * it_per_sec)).ok()
+}
+
+/// Computes the number of solutions expected after the given number of iterations.
+///
+/// # Arguments
+/// * `threshold` - The solution threshold.
+/// * `iterations` - The number of iterations done so far.
+///
+/// # Returns
+/// The expected number of solutions.
+pub fn expected_solutions(threshold: usize, iterations: usize) -> f64 {
+    solution_probability(threshold) * iterations as f64
+}
+
+/// Computes the probability of finding at most `found` solutions when `expected` were expected.
+///
+/// A low value means the result is unlikely to be bad luck and hints at a misconfiguration.
+///
+/// # Arguments
+/// * `found` - The number of solutions found.
+/// * `expected` - The number of solutions expected.
+///
+/// # Returns
+/// The Poisson cumulative probability `P(X <= found)`.
+pub fn probability_of_at_most(found: usize, expected: f64) -> f64 {
+    let mut term = (-expected).exp();
+    let mut sum = term;
+    for k in 1..=found {
+        term *= expected / k as f64;
+        sum += term;
+    }
+    sum.min(1.0)
+}
+
+/// Formats a duration with its two most significant units, e.g. `2d 03h` or `4m 10s`.
+///
+/// # Arguments
+/// * `duration` - The duration to format.
+///
+/// # Returns
+/// The formatted duration.
+pub fn format_duration(duration: Duration) -> String {
+    let secs = duration.as_secs();
+    let (days, hours, minutes, seconds) = (secs / 86_400, secs / 3_600 % 24, secs / 60 % 60, secs % 60);
+
+    if days > 0 {
+        format!("{days}d {hours:02}h")
+    } else if hours > 0 {
+        format!("{hours}h {minutes:02}m")
+    } else if minutes > 0 {
+        format!("{minutes}m {seconds:02}s")
+    } else {
+        format!("{seconds}s")
+    }
+}
+
+#[test]
+/// Tests the expected time between solutions and the Poisson probability.
+fn test_estimator() {
+    assert_eq!(expected_time_between_solutions(10, 1024.0), Some(Duration::from_secs(1)));
+    assert_eq!(expected_time_between_solutions(10, 0.0), None);
+    assert_eq!(expected_solutions(2, 8), 2.0);
+
+    assert!((probability_of_at_most(0, 2.0) - (-2.0f64).exp()).abs() < 1e-12);
+    assert!((probability_of_at_most(1, 2.0) - 3.0 * (-2.0f64).exp()).abs() < 1e-12);
+
+    assert_eq!(format_duration(Duration::from_secs(2 * 86_400 + 3 * 3_600)), "2d 03h");
+    assert_eq!(format_duration(Duration::from_secs(250)), "4m 10s");
+}
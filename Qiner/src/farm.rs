@@ -0,0 +1,188 @@
+use std::env;
+use std::sync::Arc;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use hmac::{Hmac, Mac};
+use serde_json::{json, Value};
+use sha2::Sha256;
+use lib::env_names::{ENV_FARM_REPORT_INTERVAL, ENV_FARM_REPORT_SECRET, ENV_FARM_REPORT_URL};
+use crate::rig::{get_host_name, get_worker_name};
+use crate::sensors::read_temperatures;
+use crate::summary::SessionReporter;
+
+/// Default interval between two farm reports.
+const DEFAULT_FARM_REPORT_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Timeout applied to every farm report request.
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Header carrying the signature of the report.
+pub const SIGNATURE_HEADER: &str = "X-Qiner-Signature";
+
+/// Periodically posts the stats of the rig to a self-hosted farm dashboard.
+///
+/// Disabled unless `FARM_REPORT_URL` is set. With `FARM_REPORT_SECRET`, every report
+/// carries the HMAC-SHA256 of its body in the `X-Qiner-Signature` header, as
+/// `sha256=<hex>`, so the dashboard can reject reports from other sources.
+#[derive(Debug, Clone)]
+pub struct FarmReporter {
+    url: String,
+    secret: Option<String>,
+    interval: Duration,
+    rig_name: String,
+}
+
+impl FarmReporter {
+    /// Creates a `FarmReporter` from the environment variables.
+    ///
+    /// # Returns
+    /// `Some(FarmReporter)` if a report URL is configured, `None` otherwise.
+    pub fn from_env() -> Option<Self> {
+        let url = env::var(ENV_FARM_REPORT_URL).ok()
+            .map(|url| url.trim().to_string())
+            .filter(|url| !url.is_empty())?;
+
+        let secret = env::var(ENV_FARM_REPORT_SECRET).ok()
+            .map(|secret| secret.trim().to_string())
+            .filter(|secret| !secret.is_empty());
+
+        let interval = env::var(ENV_FARM_REPORT_INTERVAL).ok()
+            .and_then(|value| value.trim().parse::<u64>().ok())
+            .filter(|secs| *secs > 0)
+            .map(Duration::from_secs)
+            .unwrap_or(DEFAULT_FARM_REPORT_INTERVAL);
+
+        // The host name identifies the rig unless a name is given
+        let rig_name = get_worker_name().or_else(get_host_name).unwrap_or_else(|| "qiner".to_string());
+
+        Some(FarmReporter {
+            url,
+            secret,
+            interval,
+            rig_name,
+        })
+    }
+
+    /// Gets the interval between two reports.
+    pub fn get_interval(&self) -> Duration {
+        self.interval
+    }
+
+    /// Gets the name the rig reports under.
+    pub fn get_rig_name(&self) -> &str {
+        &self.rig_name
+    }
+
+    /// Checks if the reports are signed.
+    pub fn is_signed(&self) -> bool {
+        self.secret.is_some()
+    }
+
+    /// Builds the report payload from the session summary.
+    ///
+    /// # Arguments
+    /// * `id` - The ID of the computor.
+    /// * `summary` - The session summary, see `SessionReporter::summary`.
+    /// * `it_per_sec` - The iteration rate since the previous report.
+    ///
+    /// # Returns
+    /// The report as a JSON value.
+    pub fn payload(&self, id: &str, summary: &Value, it_per_sec: f64) -> Value {
+        let timestamp = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default().as_secs();
+        let temperatures = read_temperatures();
+
+        json!({
+            "rig": self.rig_name,
+            "id": id,
+            "timestamp": timestamp,
+            "uptime_secs": summary["runtime_secs"].as_f64().unwrap_or_default() as u64,
+            "it_per_sec": it_per_sec,
+            "iterations": summary["iterations"],
+            "solutions_found": summary["solutions_found"],
+            "solutions_sent": summary["solutions_sent"],
+            "shares_found": summary["shares_found"],
+            "worker_restarts": summary["worker_restarts"],
+            "config_fingerprint": summary["config"]["config_fingerprint"],
+            "temperatures_c": if temperatures.is_empty() { Value::Null } else { json!(temperatures) },
+        })
+    }
+
+    /// Signs a report body.
+    ///
+    /// # Arguments
+    /// * `body` - The serialized report.
+    ///
+    /// # Returns
+    /// The value of the signature header, or `None` if no secret is configured.
+    pub fn sign(&self, body: &[u8]) -> Option<String> {
+        let secret = self.secret.as_ref()?;
+        let mut mac = Hmac::<Sha256>::new_from_slice(secret.as_bytes()).ok()?;
+        mac.update(body);
+        let signature = mac.finalize().into_bytes().iter().map(|byte| format!("{byte:02x}")).collect::<String>();
+        Some(format!("sha256={signature}"))
+    }
+
+    /// Posts a report, on the blocking pool so a slow dashboard never delays mining.
+    ///
+    /// # Arguments
+    /// * `report` - The report to post.
+    pub async fn post(&self, report: &Value) {
+        let body = report.to_string();
+        let signature = self.sign(body.as_bytes());
+        let url = self.url.clone();
+
+        let result = tokio::task::spawn_blocking(move || {
+            let mut request = ureq::post(&url)
+                .timeout(REQUEST_TIMEOUT)
+                .set("Content-Type", "application/json");
+            if let Some(signature) = &signature {
+                request = request.set(SIGNATURE_HEADER, signature);
+            }
+            // The error is reduced to its kind, since the URL may contain a token
+            request.send_string(&body).map(|_| ()).map_err(|err| err.kind())
+        }).await;
+
+        match result {
+            Ok(Ok(_)) => log::debug!("Farm report sent"),
+            Ok(Err(kind)) => log::warn!("Failed to send the farm report: {}", kind),
+            Err(err) => log::warn!("Failed to send the farm report: {:?}", err),
+        }
+    }
+}
+
+/// Asynchronous task posting the farm report periodically.
+///
+/// # Arguments
+/// * `farm_reporter` - The farm reporter.
+/// * `session_reporter` - The session reporter the stats are taken from.
+/// * `id` - The ID of the computor.
+pub async fn farm_report_task(farm_reporter: FarmReporter, session_reporter: Arc<SessionReporter>, id: String) {
+    let mut prev_iterations = 0u64;
+
+    loop {
+        tokio::time::sleep(farm_reporter.get_interval()).await;
+
+        let summary = session_reporter.summary().await;
+        let iterations = summary["iterations"].as_u64().unwrap_or_default();
+        let it_per_sec = iterations.saturating_sub(prev_iterations) as f64 / farm_reporter.get_interval().as_secs_f64();
+        prev_iterations = iterations;
+
+        farm_reporter.post(&farm_reporter.payload(&id, &summary, it_per_sec)).await;
+    }
+}
+
+#[test]
+/// Tests the report signature against a known HMAC-SHA256 value.
+fn test_farm_report_signature() {
+    let reporter = FarmReporter {
+        url: String::new(),
+        secret: Some("key".to_string()),
+        interval: DEFAULT_FARM_REPORT_INTERVAL,
+        rig_name: "rig".to_string(),
+    };
+
+    assert_eq!(
+        reporter.sign(b"The quick brown fox jumps over the lazy dog").as_deref(),
+        Some("sha256=f7bc83f430538424b13298e6aa6fb143ef4d59a14946175997479dbc2d1a3cd8")
+    );
+    assert_eq!(FarmReporter { secret: None, ..reporter }.sign(b"body"), None);
+}
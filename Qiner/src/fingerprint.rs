@@ -0,0 +1,63 @@
+//! A short fingerprint of the settings deciding what a rig mines, so the one rig of a farm still
+//! mining with last epoch's seed stands out among the others.
+//!
+//! It covers the public key of the ID, once its checksum is verified, the random seed, the solution
+//! threshold and the nodes, in whatever order they are listed. The threads, the names and the logging
+//! are left out, so rigs mining the same thing share their fingerprint whatever their hardware.
+
+use crate::settings::Settings;
+use lib::types::PublicKey64;
+use crate::crypto::k12_32;
+use crate::peers::PEERS_SPLIT_CHAR;
+
+/// Number of hexadecimal digits of a fingerprint.
+pub const FINGERPRINT_LENGTH: usize = 8;
+
+/// Computes the fingerprint of the settings.
+///
+/// # Arguments
+/// * `public_key` - The public key of the ID, from `get_public_key_64_from_id` which verifies its checksum.
+/// * `settings` - The settings, giving the seed, the threshold and the nodes.
+///
+/// # Returns
+/// The `FINGERPRINT_LENGTH` lowercase hexadecimal digits of the fingerprint.
+pub fn config_fingerprint(public_key: &PublicKey64, settings: &Settings) -> String {
+    let mut nodes = settings.nodes.server_ip.split(PEERS_SPLIT_CHAR).map(str::trim).filter(|ip| !ip.is_empty()).collect::<Vec<_>>();
+    nodes.sort_unstable();
+    nodes.dedup();
+
+    let mut data = public_key.iter().flat_map(|value| value.to_le_bytes()).collect::<Vec<_>>();
+    data.extend_from_slice(&settings.random_seed);
+    data.extend_from_slice(&(settings.solution_threshold as u64).to_le_bytes());
+    data.extend_from_slice(format!("{}:{}", nodes.join(","), settings.nodes.server_port.unwrap_or_default()).as_bytes());
+
+    k12_32(&data).iter().take(FINGERPRINT_LENGTH / 2).map(|byte| format!("{byte:02x}")).collect()
+}
+
+#[test]
+/// Tests that the fingerprint ignores the order of the nodes and changes with the seed, the threshold and the ID.
+fn test_config_fingerprint() {
+    use lib::env_names::{ENV_ID, ENV_RANDOM_SEED, ENV_SERVER_IP, ENV_SERVER_PORT, ENV_SOLUTION_THRESHOLD};
+
+    let settings = |server_ip: &str, random_seed: &str, solution_threshold: &str| {
+        let config = [
+            (ENV_ID, "BZBQFLLBNCXEMGLOBHUVFTLUPLVCPQUASSILFABOFFBCADQSSUPNWLZBQEXK"),
+            (ENV_SERVER_IP, server_ip),
+            (ENV_SERVER_PORT, "21841"),
+            (ENV_RANDOM_SEED, random_seed),
+            (ENV_SOLUTION_THRESHOLD, solution_threshold),
+        ];
+        Settings::from_lookup(|name| config.iter().find(|(key, _)| *key == name).map(|(_, value)| value.to_string())).unwrap()
+    };
+    let public_key: PublicKey64 = [1, 2, 3, 4];
+
+    let fingerprint = config_fingerprint(&public_key, &settings("127.0.0.1, 127.0.0.2", "0x0100e909", "22"));
+    assert_eq!(fingerprint.len(), FINGERPRINT_LENGTH);
+    assert!(fingerprint.bytes().all(|digit| digit.is_ascii_hexdigit() && !digit.is_ascii_uppercase()));
+    assert_eq!(config_fingerprint(&public_key, &settings("127.0.0.2,127.0.0.1,127.0.0.2", "0x0100e909", "22")), fingerprint);
+
+    assert_ne!(config_fingerprint(&public_key, &settings("127.0.0.1, 127.0.0.2", "0x0100e90a", "22")), fingerprint);
+    assert_ne!(config_fingerprint(&public_key, &settings("127.0.0.1, 127.0.0.2", "0x0100e909", "23")), fingerprint);
+    assert_ne!(config_fingerprint(&public_key, &settings("127.0.0.1", "0x0100e909", "22")), fingerprint);
+    assert_ne!(config_fingerprint(&[1, 2, 3, 5], &settings("127.0.0.1, 127.0.0.2", "0x0100e909", "22")), fingerprint);
+}
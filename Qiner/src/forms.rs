@@ -0,0 +1,145 @@
+//! Serde forms of the keys and nonces, for `#[serde(with = "...")]`.
+//!
+//! Human-readable formats such as JSON get the forms users already know: IDs for the public
+//! keys shown as identities, and the hex of the bytes for nonces and keys, as in the spill
+//! files. Binary formats get the 64-bit words unchanged.
+
+use alloc::format;
+use alloc::string::String;
+use alloc::vec::Vec;
+use lib::types::{Id, Nonce64, PublicKey64};
+use serde::de::{self, Deserializer};
+use serde::ser::Serializer;
+use serde::{Deserialize, Serialize};
+use crate::converters::{get_id_from_public_key_64, parse_id};
+
+/// Words as the hex of their little endian bytes, the form of `qiner convert` and the spill files.
+pub mod hex {
+    use super::*;
+
+    /// Serializes words as hex in human-readable formats.
+    pub fn serialize<S: Serializer, const N: usize>(words: &[u64; N], serializer: S) -> Result<S::Ok, S::Error> {
+        if serializer.is_human_readable() {
+            serializer.serialize_str(&to_hex(words))
+        } else {
+            words.serialize(serializer)
+        }
+    }
+
+    /// Deserializes words from hex in human-readable formats.
+    pub fn deserialize<'de, D: Deserializer<'de>, const N: usize>(deserializer: D) -> Result<[u64; N], D::Error> {
+        if deserializer.is_human_readable() {
+            let hex = String::deserialize(deserializer)?;
+            from_hex(&hex).ok_or_else(|| de::Error::custom(format!("expected {} hex characters, got {hex:?}", N * 16)))
+        } else {
+            // Arrays of any length do not implement `Deserialize`, so the words are read as a sequence
+            let words = Vec::<u64>::deserialize(deserializer)?;
+            words.as_slice().try_into().map_err(|_| de::Error::invalid_length(words.len(), &"the words of the value"))
+        }
+    }
+
+    /// Formats words as the hex of their little endian bytes.
+    fn to_hex<const N: usize>(words: &[u64; N]) -> String {
+        words.iter().flat_map(|word| word.to_le_bytes()).map(|byte| format!("{byte:02x}")).collect()
+    }
+
+    /// Parses words from the hex of their little endian bytes.
+    fn from_hex<const N: usize>(hex: &str) -> Option<[u64; N]> {
+        let hex = hex.trim();
+        if hex.len() != N * 16 || !hex.is_ascii() {
+            return None;
+        }
+
+        let mut words = [0u64; N];
+        for (word, chunk) in words.iter_mut().zip(hex.as_bytes().chunks(16)) {
+            let mut bytes = [0u8; 8];
+            for (byte, pair) in bytes.iter_mut().zip(chunk.chunks(2)) {
+                *byte = u8::from_str_radix(core::str::from_utf8(pair).ok()?, 16).ok()?;
+            }
+            *word = u64::from_le_bytes(bytes);
+        }
+        Some(words)
+    }
+}
+
+/// Public keys as their 60-letter ID, checksum included.
+pub mod id {
+    use super::*;
+
+    /// Serializes a public key as its ID in human-readable formats.
+    pub fn serialize<S: Serializer>(public_key: &PublicKey64, serializer: S) -> Result<S::Ok, S::Error> {
+        if serializer.is_human_readable() {
+            let mut id: Id = [0; 60];
+            get_id_from_public_key_64(public_key, &mut id);
+            serializer.serialize_str(core::str::from_utf8(&id).map_err(serde::ser::Error::custom)?)
+        } else {
+            public_key.serialize(serializer)
+        }
+    }
+
+    /// Deserializes a public key from its ID in human-readable formats, checking the checksum.
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<PublicKey64, D::Error> {
+        if deserializer.is_human_readable() {
+            let id = String::deserialize(deserializer)?;
+            parse_id(&id).ok_or_else(|| de::Error::custom(format!("invalid ID {id:?}")))
+        } else {
+            PublicKey64::deserialize(deserializer)
+        }
+    }
+}
+
+/// Solutions as `{"public_key": <hex>, "nonce": <hex>}` objects, the JSON form `qiner replay` reads.
+pub mod solutions {
+    use super::*;
+
+    #[derive(Serialize, Deserialize)]
+    struct Solution {
+        #[serde(with = "hex")]
+        public_key: PublicKey64,
+        #[serde(with = "hex")]
+        nonce: Nonce64,
+    }
+
+    /// Serializes solutions as objects.
+    pub fn serialize<S: Serializer>(solutions: &[(PublicKey64, Nonce64)], serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_seq(solutions.iter().map(|&(public_key, nonce)| Solution { public_key, nonce }))
+    }
+
+    /// Deserializes solutions from objects.
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Vec<(PublicKey64, Nonce64)>, D::Error> {
+        let solutions = Vec::<Solution>::deserialize(deserializer)?;
+        Ok(solutions.into_iter().map(|solution| (solution.public_key, solution.nonce)).collect())
+    }
+}
+
+#[test]
+/// Tests the JSON forms of keys, nonces and solutions and the rejection of malformed values.
+fn test_forms() {
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    struct Forms {
+        #[serde(with = "id")]
+        computor: PublicKey64,
+        #[serde(with = "hex")]
+        nonce: Nonce64,
+        #[serde(with = "solutions")]
+        solutions: Vec<(PublicKey64, Nonce64)>,
+    }
+
+    let id = "UBAZRCVPOZTDKGCBNPGYFUPLZXDDNHSEGJRTAJKWJBHJDKHMAKVVFAKCZGRI";
+    let computor = parse_id(id).unwrap();
+    let nonce: Nonce64 = [0x0123456789abcdef, 0, u64::MAX, 42];
+    let forms = Forms { computor, nonce, solutions: alloc::vec![(computor, nonce)] };
+
+    let json = serde_json::to_value(&forms).unwrap();
+    assert_eq!(json["computor"], id);
+    assert!(json["nonce"].as_str().unwrap().starts_with("efcdab8967452301"));
+    assert_eq!(json["solutions"][0]["nonce"], json["nonce"]);
+    assert_eq!(serde_json::from_value::<Forms>(json.clone()).unwrap(), forms);
+
+    let mut invalid = json.clone();
+    invalid["computor"] = serde_json::Value::from(id.replace("CZGRI", "CZGRA"));
+    assert!(format!("{}", serde_json::from_value::<Forms>(invalid).unwrap_err()).contains("invalid ID"));
+    invalid = json;
+    invalid["nonce"] = serde_json::Value::from("abcd");
+    assert!(serde_json::from_value::<Forms>(invalid).is_err());
+}
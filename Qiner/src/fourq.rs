@@ -0,0 +1,365 @@
+//! Minimal FourQ arithmetic, the curve Qubic derives public keys on.
+//!
+//! FourQ is the twisted Edwards curve `-x^2 + y^2 = 1 + d x^2 y^2` over GF(p^2), with
+//! `p = 2^127 - 1` and `i^2 = -1`. Only what key derivation and SchnorrQ signing need is
+//! implemented, with straightforward formulas. Whatever touches a private key or a nonce, the
+//! scalar multiplication, the scalar reductions and the field reduction, runs without branches or
+//! memory accesses depending on the secret values.
+
+use std::ops::{Add, Mul, Sub};
+
+/// The prime `2^127 - 1`.
+const P: u128 = (1 << 127) - 1;
+
+/// An element `re + im * i` of GF(p^2), both parts fully reduced.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Fp2 {
+    pub re: u128,
+    pub im: u128,
+}
+
+/// The curve parameter `d`.
+const D: Fp2 = Fp2 {
+    re: 0x0000_0000_0000_00E4_0000_0000_0000_0142,
+    im: 0x5E47_2F84_6657_E0FC_B382_1488_F1FC_0C8D,
+};
+
+/// The x coordinate of the generator.
+const GENERATOR_X: Fp2 = Fp2 {
+    re: 0x1A34_7223_7C2F_B305_2865_92AD_7B38_33AA,
+    im: 0x1E1F_553F_2878_AA9C_9686_9FB3_60AC_77F6,
+};
+
+/// The y coordinate of the generator.
+const GENERATOR_Y: Fp2 = Fp2 {
+    re: 0x0E3F_EE9B_A120_785A_B924_A246_2BCB_B287,
+    im: 0x6E1C_4AF8_630E_0242_49A7_C344_844C_8B5C,
+};
+
+/// The order of the generator, as little endian 64-bit words.
+pub const CURVE_ORDER: [u64; 4] = [0x2FB2_540E_C776_8CE7, 0xDFBD_004D_FE0F_7999, 0xF053_9782_9CBC_14E5, 0x0029_CBC1_4E5E_0A72];
+
+/// Reduces a scalar of 512 bits, as little endian 64-bit words, modulo the curve order.
+fn scalar_reduce_wide(value: &[u64; 8]) -> [u64; 4] {
+    // Bit by bit, the remainder staying below twice the order, which fits in 248 bits
+    let mut remainder = [0u64; 4];
+    for bit in (0..512).rev() {
+        for word in (1..4).rev() {
+            remainder[word] = remainder[word] << 1 | remainder[word - 1] >> 63;
+        }
+        remainder[0] = remainder[0] << 1 | (value[bit / 64] >> (bit % 64)) & 1;
+        let (difference, borrow) = scalar_sub_words(&remainder, &CURVE_ORDER);
+        remainder = select_words(&difference, &remainder, borrow);
+    }
+    remainder
+}
+
+/// Selects between two scalars without branching on the condition.
+///
+/// # Returns
+/// `b` if `condition` is set, `a` otherwise.
+fn select_words(a: &[u64; 4], b: &[u64; 4], condition: bool) -> [u64; 4] {
+    let mask = 0u64.wrapping_sub(u64::from(condition));
+    std::array::from_fn(|word| a[word] ^ (mask & (a[word] ^ b[word])))
+}
+
+/// Subtracts two scalars of 256 bits.
+///
+/// # Returns
+/// The difference modulo `2^256`, and whether it borrowed.
+fn scalar_sub_words(a: &[u64; 4], b: &[u64; 4]) -> ([u64; 4], bool) {
+    let mut difference = [0u64; 4];
+    let mut borrow = false;
+    for word in 0..4 {
+        let (value, borrow_b) = a[word].overflowing_sub(b[word]);
+        let (value, borrow_carry) = value.overflowing_sub(borrow as u64);
+        difference[word] = value;
+        borrow = borrow_b || borrow_carry;
+    }
+    (difference, borrow)
+}
+
+/// Reduces a scalar modulo the curve order.
+pub fn scalar_reduce(value: &[u64; 4]) -> [u64; 4] {
+    let mut wide = [0u64; 8];
+    wide[..4].copy_from_slice(value);
+    scalar_reduce_wide(&wide)
+}
+
+/// Multiplies two scalars modulo the curve order.
+pub fn scalar_mul(a: &[u64; 4], b: &[u64; 4]) -> [u64; 4] {
+    let mut product = [0u64; 8];
+    for i in 0..4 {
+        let mut carry = 0u128;
+        for j in 0..4 {
+            let value = a[i] as u128 * b[j] as u128 + product[i + j] as u128 + carry;
+            product[i + j] = value as u64;
+            carry = value >> 64;
+        }
+        product[i + 4] = carry as u64;
+    }
+    scalar_reduce_wide(&product)
+}
+
+/// Subtracts two scalars reduced modulo the curve order.
+pub fn scalar_sub(a: &[u64; 4], b: &[u64; 4]) -> [u64; 4] {
+    let (difference, borrow) = scalar_sub_words(a, b);
+    // The order is added back only if the subtraction borrowed, wrapping around `2^256` as the borrow did
+    let order = select_words(&[0; 4], &CURVE_ORDER, borrow);
+    let mut sum = [0u64; 4];
+    let mut carry = false;
+    for word in 0..4 {
+        let (value, carry_a) = difference[word].overflowing_add(order[word]);
+        let (value, carry_carry) = value.overflowing_add(carry as u64);
+        sum[word] = value;
+        carry = carry_a || carry_carry;
+    }
+    sum
+}
+
+/// Reduces a value below `2^128` modulo `p`.
+fn fp_reduce(value: u128) -> u128 {
+    let value = (value & P) + (value >> 127);
+    // p is subtracted unless that borrows, without branching on the value
+    let (reduced, borrow) = value.overflowing_sub(P);
+    let mask = 0u128.wrapping_sub(u128::from(borrow));
+    reduced ^ (mask & (reduced ^ value))
+}
+
+fn fp_add(a: u128, b: u128) -> u128 {
+    fp_reduce(a + b)
+}
+
+fn fp_sub(a: u128, b: u128) -> u128 {
+    fp_reduce(a + (P - b))
+}
+
+fn fp_mul(a: u128, b: u128) -> u128 {
+    let (a0, a1) = (a as u64 as u128, a >> 64);
+    let (b0, b1) = (b as u64 as u128, b >> 64);
+
+    // The 254-bit product as high * 2^128 + low
+    let middle = a0 * b1 + a1 * b0;
+    let low = (a0 * b0).wrapping_add(middle << 64);
+    let carry = u128::from(low < middle << 64);
+    let high = a1 * b1 + (middle >> 64) + carry;
+
+    // 2^127 = 1 and 2^128 = 2 modulo p
+    fp_add(fp_reduce(low), fp_reduce(high << 1))
+}
+
+fn fp_pow(mut base: u128, mut exponent: u128) -> u128 {
+    let mut result = 1;
+    while exponent > 0 {
+        if exponent & 1 == 1 {
+            result = fp_mul(result, base);
+        }
+        base = fp_mul(base, base);
+        exponent >>= 1;
+    }
+    result
+}
+
+impl Add for Fp2 {
+    type Output = Fp2;
+
+    fn add(self, other: Fp2) -> Fp2 {
+        Fp2 { re: fp_add(self.re, other.re), im: fp_add(self.im, other.im) }
+    }
+}
+
+impl Sub for Fp2 {
+    type Output = Fp2;
+
+    fn sub(self, other: Fp2) -> Fp2 {
+        Fp2 { re: fp_sub(self.re, other.re), im: fp_sub(self.im, other.im) }
+    }
+}
+
+impl Mul for Fp2 {
+    type Output = Fp2;
+
+    fn mul(self, other: Fp2) -> Fp2 {
+        Fp2 {
+            re: fp_sub(fp_mul(self.re, other.re), fp_mul(self.im, other.im)),
+            im: fp_add(fp_mul(self.re, other.im), fp_mul(self.im, other.re)),
+        }
+    }
+}
+
+impl Fp2 {
+    pub const ZERO: Fp2 = Fp2 { re: 0, im: 0 };
+    pub const ONE: Fp2 = Fp2 { re: 1, im: 0 };
+
+    /// Inverts the element, zero being mapped to zero.
+    pub fn inv(self) -> Fp2 {
+        // 1 / (a + bi) = (a - bi) / (a^2 + b^2)
+        let norm_inv = fp_pow(fp_add(fp_mul(self.re, self.re), fp_mul(self.im, self.im)), P - 2);
+        Fp2 { re: fp_mul(self.re, norm_inv), im: fp_mul(fp_sub(0, self.im), norm_inv) }
+    }
+
+    /// Selects between two elements without branching on the mask.
+    ///
+    /// # Returns
+    /// `other` if `mask` is all ones, `self` if it is zero.
+    fn select(self, other: Fp2, mask: u128) -> Fp2 {
+        Fp2 { re: self.re ^ (mask & (self.re ^ other.re)), im: self.im ^ (mask & (self.im ^ other.im)) }
+    }
+}
+
+/// A point in extended projective coordinates, `x = X/Z`, `y = Y/Z` and `T = XY/Z`.
+#[derive(Debug, Clone, Copy)]
+pub struct Point {
+    x: Fp2,
+    y: Fp2,
+    z: Fp2,
+    t: Fp2,
+}
+
+impl Point {
+    /// The neutral element `(0, 1)`.
+    pub const IDENTITY: Point = Point { x: Fp2::ZERO, y: Fp2::ONE, z: Fp2::ONE, t: Fp2::ZERO };
+
+    /// Creates a point from its affine coordinates.
+    pub fn from_affine(x: Fp2, y: Fp2) -> Point {
+        Point { x, y, z: Fp2::ONE, t: x * y }
+    }
+
+    /// Gets the generator of the prime order subgroup.
+    pub fn generator() -> Point {
+        Point::from_affine(GENERATOR_X, GENERATOR_Y)
+    }
+
+    /// Gets the affine coordinates of the point.
+    pub fn to_affine(self) -> (Fp2, Fp2) {
+        let z_inv = self.z.inv();
+        (self.x * z_inv, self.y * z_inv)
+    }
+
+    /// Multiplies the point by a scalar given as little endian 64-bit words.
+    ///
+    /// The scalar is processed in fixed windows of 4 bits, each doubling 4 times then adding a
+    /// multiple read by scanning the whole table, so neither the time nor the memory accesses
+    /// depend on the scalar.
+    pub fn mul_scalar(self, scalar: &[u64; 4]) -> Point {
+        // The multiples 0 to 15 of the point, the unified formulas adding the neutral element like any other
+        let mut table = [Point::IDENTITY; 16];
+        let mut multiple = Point::IDENTITY;
+        for entry in table.iter_mut() {
+            *entry = multiple;
+            multiple = multiple + self;
+        }
+
+        let mut result = Point::IDENTITY;
+        for window in (0..64).rev() {
+            for _ in 0..4 {
+                result = result + result;
+            }
+            let digit = (scalar[window / 16] >> (window % 16 * 4)) & 0xF;
+            result = result + Point::lookup(&table, digit);
+        }
+        result
+    }
+
+    /// Reads an entry of a table of points, touching every entry.
+    fn lookup(table: &[Point; 16], idx: u64) -> Point {
+        let mut selected = Point::IDENTITY;
+        for (entry_idx, entry) in table.iter().enumerate() {
+            // All ones for the entry looked up, zero for the others
+            let difference = entry_idx as u64 ^ idx;
+            let mask = 0u128.wrapping_sub(u128::from(((difference | difference.wrapping_neg()) >> 63) ^ 1));
+            selected = Point {
+                x: selected.x.select(entry.x, mask),
+                y: selected.y.select(entry.y, mask),
+                z: selected.z.select(entry.z, mask),
+                t: selected.t.select(entry.t, mask),
+            };
+        }
+        selected
+    }
+
+    /// Checks if the point is the neutral element.
+    pub fn is_identity(self) -> bool {
+        let (x, y) = self.to_affine();
+        x == Fp2::ZERO && y == Fp2::ONE
+    }
+
+    /// Checks if the point satisfies the curve equation.
+    pub fn is_on_curve(self) -> bool {
+        let (x, y) = self.to_affine();
+        let (xx, yy) = (x * x, y * y);
+        yy - xx == Fp2::ONE + D * xx * yy
+    }
+
+    /// Encodes the point in the 32 bytes of the FourQ format.
+    ///
+    /// The bytes are the little endian y coordinate, real part first, with the top bit
+    /// holding the sign of x: bit 126 of its real part, or of its imaginary part if the
+    /// real part is zero.
+    pub fn encode(self) -> [u8; 32] {
+        let (x, y) = self.to_affine();
+        let mut encoded = [0u8; 32];
+        encoded[..16].copy_from_slice(&y.re.to_le_bytes());
+        encoded[16..].copy_from_slice(&y.im.to_le_bytes());
+
+        let sign = if x.re == 0 { x.im >> 126 } else { x.re >> 126 } & 1;
+        encoded[31] |= (sign as u8) << 7;
+        encoded
+    }
+}
+
+impl Add for Point {
+    type Output = Point;
+
+    /// Adds two points with the unified formulas, which also double.
+    fn add(self, other: Point) -> Point {
+        let a = (self.y - self.x) * (other.y - other.x);
+        let b = (self.y + self.x) * (other.y + other.x);
+        let c = self.t * (D + D) * other.t;
+        let d = (self.z + self.z) * other.z;
+        let (e, f, g, h) = (b - a, d - c, d + c, b + a);
+
+        Point { x: e * f, y: g * h, z: f * g, t: e * h }
+    }
+}
+
+#[test]
+/// Tests the curve constants: the generator is on the curve and has the curve order.
+fn test_fourq_generator() {
+    let generator = Point::generator();
+    assert!(generator.is_on_curve());
+    assert!(!generator.is_identity());
+    assert!(generator.mul_scalar(&CURVE_ORDER).is_identity());
+
+    let double = generator + generator;
+    assert!(double.is_on_curve());
+    assert_eq!(double.encode(), generator.mul_scalar(&[2, 0, 0, 0]).encode());
+}
+
+#[test]
+/// Tests the scalar arithmetic modulo the curve order on edge values.
+fn test_fourq_scalar() {
+    let order_minus_one = scalar_sub(&[0; 4], &[1, 0, 0, 0]);
+    assert_eq!(scalar_sub_words(&CURVE_ORDER, &[1, 0, 0, 0]).0, order_minus_one);
+    assert_eq!(scalar_reduce(&CURVE_ORDER), [0; 4]);
+    assert_eq!(scalar_reduce(&[u64::MAX; 4]), scalar_sub(&scalar_mul(&[1 << 63, 0, 0, 0], &[0, 0, 0, 2]), &[1, 0, 0, 0]));
+    assert_eq!(scalar_mul(&order_minus_one, &order_minus_one), [1, 0, 0, 0]);
+    assert_eq!(scalar_sub(&[5, 0, 0, 0], &[3, 0, 0, 0]), [2, 0, 0, 0]);
+
+    // The points agree with the scalars
+    let generator = Point::generator();
+    let (a, b) = ([0x1234_5678_9ABC_DEF0, 7, 0, 1 << 40], [u64::MAX, u64::MAX, 3, 0]);
+    assert_eq!(generator.mul_scalar(&scalar_mul(&a, &b)).encode(), generator.mul_scalar(&a).mul_scalar(&b).encode());
+    assert_eq!((generator.mul_scalar(&scalar_sub(&a, &b)) + generator.mul_scalar(&b)).encode(), generator.mul_scalar(&a).encode());
+}
+
+#[test]
+/// Tests the field arithmetic on edge values.
+fn test_fourq_field() {
+    assert_eq!(fp_mul(P - 1, P - 1), 1);
+    assert_eq!(fp_add(P - 1, 1), 0);
+    assert_eq!(fp_sub(0, 1), P - 1);
+
+    let value = Fp2 { re: 0x1234_5678_9ABC_DEF0_0FED_CBA9_8765_4321, im: 42 };
+    assert_eq!(value * value.inv(), Fp2::ONE);
+}
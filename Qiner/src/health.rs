@@ -0,0 +1,161 @@
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use serde_json::{json, Value};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+use crate::miner::Miner;
+use crate::network::NetStats;
+
+/// Default time without any iteration after which the miner is unhealthy.
+pub const DEFAULT_HEALTH_STALL_TIMEOUT: Duration = Duration::from_secs(2 * 60);
+
+/// Default time connect attempts may keep failing before the miner is unhealthy.
+pub const DEFAULT_HEALTH_CONNECT_TIMEOUT: Duration = Duration::from_secs(30 * 60);
+
+/// Exit code of the process when it exits because it became unhealthy.
+pub const UNHEALTHY_EXIT_CODE: i32 = 75;
+
+/// Interval between two health checks when exiting on stall.
+const CHECK_INTERVAL: Duration = Duration::from_secs(5);
+
+/// Decides whether the miner is healthy, for container orchestrators.
+///
+/// The miner is unhealthy when no iteration has happened for the stall timeout (unless
+/// mining is paused), or when connect attempts have kept failing for the connect timeout.
+pub struct HealthMonitor {
+    miner: Arc<Miner>,
+    net_stats: Arc<NetStats>,
+    stall_timeout: Duration,
+    connect_timeout: Duration,
+    /// Iteration count last seen, and when it last moved
+    progress: Mutex<(usize, Instant)>,
+}
+
+impl HealthMonitor {
+    /// Creates a new `HealthMonitor`.
+    ///
+    /// # Arguments
+    /// * `miner` - Shared reference to the Miner instance.
+    /// * `net_stats` - Shared network counters.
+    /// * `stall_timeout` - Time without any iteration after which the miner is unhealthy.
+    /// * `connect_timeout` - Time connect attempts may keep failing before the miner is unhealthy.
+    ///
+    /// # Returns
+    /// A new `HealthMonitor`.
+    pub fn new(miner: Arc<Miner>, net_stats: Arc<NetStats>, stall_timeout: Duration, connect_timeout: Duration) -> Self {
+        let iterations = miner.get_iteration_count();
+        HealthMonitor {
+            miner,
+            net_stats,
+            stall_timeout,
+            connect_timeout,
+            progress: Mutex::new((iterations, Instant::now())),
+        }
+    }
+
+    /// Checks the health of the miner.
+    ///
+    /// # Returns
+    /// `true` if the miner is healthy, and the report as a JSON value.
+    pub fn check(&self) -> (bool, Value) {
+        let since_progress = {
+            let mut progress = self.progress.lock().unwrap();
+            let iterations = self.miner.get_iteration_count();
+            if iterations != progress.0 || self.miner.is_paused() {
+                *progress = (iterations, Instant::now());
+            }
+            progress.1.elapsed()
+        };
+        let failing_for = self.net_stats.get_failing_since().map(|since| since.elapsed());
+
+        let is_stalled = since_progress >= self.stall_timeout;
+        let is_disconnected = failing_for.is_some_and(|failing_for| failing_for >= self.connect_timeout);
+        let is_healthy = !is_stalled && !is_disconnected;
+
+        (is_healthy, json!({
+            "status": if is_healthy { "ok" } else { "unhealthy" },
+            "stalled": is_stalled,
+            "disconnected": is_disconnected,
+            "secs_since_progress": since_progress.as_secs(),
+            "connect_failing_secs": failing_for.map(|failing_for| failing_for.as_secs()),
+            "node_protocol": self.net_stats.get_node_protocol(),
+            "protocol_mismatches": self.net_stats.get_protocol_mismatches(),
+        }))
+    }
+}
+
+/// Asynchronous task serving `GET /healthz` over plain HTTP.
+///
+/// Answers `200` while the miner is healthy and `503` otherwise, with the JSON report as body.
+///
+/// # Arguments
+/// * `monitor` - The health monitor answering the checks
+/// * `port` - The port to listen on, on all interfaces
+pub async fn serve_health(monitor: Arc<HealthMonitor>, port: u16) {
+    let listener = match TcpListener::bind(("0.0.0.0", port)).await {
+        Ok(listener) => listener,
+        Err(err) => {
+            log::error!("Failed to listen for health checks on port {port}: {:?}", err);
+            return;
+        }
+    };
+    log::info!("Health endpoint listening on http://0.0.0.0:{port}/healthz");
+
+    loop {
+        match listener.accept().await {
+            Ok((stream, _)) => {
+                tokio::spawn(answer_health(monitor.clone(), stream));
+            }
+            Err(err) => log::warn!("Failed to accept a health check: {:?}", err),
+        }
+    }
+}
+
+/// Answers a single health check request.
+///
+/// # Arguments
+/// * `monitor` - The health monitor answering the check
+/// * `stream` - The connection of the client
+async fn answer_health(monitor: Arc<HealthMonitor>, mut stream: TcpStream) {
+    // Only the request line matters
+    let mut buffer = [0u8; 1024];
+    let read = match stream.read(&mut buffer).await {
+        Ok(read) => read,
+        Err(_) => return,
+    };
+    let request = String::from_utf8_lossy(&buffer[..read]);
+    let path = request.split_whitespace().nth(1).unwrap_or_default();
+
+    let (status, body) = if path == "/healthz" {
+        let (is_healthy, report) = monitor.check();
+        (if is_healthy { "200 OK" } else { "503 Service Unavailable" }, report.to_string())
+    } else {
+        ("404 Not Found", String::new())
+    };
+
+    let response = format!(
+        "HTTP/1.1 {status}\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{body}",
+        body.len()
+    );
+    if let Err(err) = stream.write_all(response.as_bytes()).await {
+        log::debug!("Failed to answer a health check: {:?}", err);
+    }
+}
+
+/// Asynchronous task waiting until the miner becomes unhealthy.
+///
+/// # Arguments
+/// * `monitor` - The health monitor answering the checks
+///
+/// # Returns
+/// The report of the failed check.
+pub async fn wait_unhealthy(monitor: Arc<HealthMonitor>) -> Value {
+    loop {
+        tokio::time::sleep(CHECK_INTERVAL).await;
+
+        let (is_healthy, report) = monitor.check();
+        if !is_healthy {
+            return report;
+        }
+    }
+}
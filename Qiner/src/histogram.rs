@@ -0,0 +1,125 @@
+//! Distribution of the scores of every evaluated nonce, so a build can be checked against
+//! the expected distribution long before its first solution.
+
+use std::sync::atomic::{AtomicU64, Ordering};
+use serde_json::{json, Value};
+use crate::estimator::expected_solutions;
+
+/// Number of score buckets, the last one also counting every higher score.
+pub const SCORE_BUCKETS: usize = 64;
+
+/// Number of scores right below the solution threshold reported as near misses.
+pub const NEAR_MISS_SCORES: usize = 3;
+
+/// Number of nonces per score, as counted locally by a mining thread.
+pub type ScoreCounts = [u64; SCORE_BUCKETS];
+
+/// Number of nonces per score, shared by the mining threads.
+#[derive(Debug)]
+pub struct ScoreHistogram {
+    buckets: [AtomicU64; SCORE_BUCKETS],
+}
+
+impl Default for ScoreHistogram {
+    fn default() -> Self {
+        ScoreHistogram { buckets: std::array::from_fn(|_| AtomicU64::new(0)) }
+    }
+}
+
+impl ScoreHistogram {
+    /// Gets the bucket a score is counted in.
+    pub fn bucket(score: usize) -> usize {
+        score.min(SCORE_BUCKETS - 1)
+    }
+
+    /// Folds the counts of a mining thread into the histogram, resetting them.
+    ///
+    /// # Arguments
+    /// * `counts` - The counts of the thread since its last fold.
+    pub fn add(&self, counts: &mut ScoreCounts) {
+        for (bucket, count) in self.buckets.iter().zip(counts.iter_mut()) {
+            if *count > 0 {
+                bucket.fetch_add(*count, Ordering::Relaxed);
+                *count = 0;
+            }
+        }
+    }
+
+    /// Gets the number of nonces per score.
+    pub fn get_counts(&self) -> ScoreCounts {
+        std::array::from_fn(|idx| self.buckets[idx].load(Ordering::Relaxed))
+    }
+}
+
+/// The nonces that reached a score right below the solution threshold.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct NearMiss {
+    pub score: usize,
+    /// The number of nonces reaching at least `score`
+    pub count: u64,
+    /// The number of nonces expected to reach at least `score`
+    pub expected: f64,
+}
+
+/// Compares the nonces reaching the scores right below the threshold with the expectation.
+///
+/// # Arguments
+/// * `counts` - The number of nonces per score.
+/// * `threshold` - The solution threshold.
+///
+/// # Returns
+/// One near miss per score, lowest first.
+pub fn near_misses(counts: &ScoreCounts, threshold: usize) -> Vec<NearMiss> {
+    let total = counts.iter().sum::<u64>();
+    (threshold.saturating_sub(NEAR_MISS_SCORES)..threshold).map(|score| NearMiss {
+        score,
+        count: counts[ScoreHistogram::bucket(score)..].iter().sum(),
+        expected: expected_solutions(score, total as usize),
+    }).collect()
+}
+
+/// Builds the machine-readable report of the histogram.
+///
+/// # Arguments
+/// * `counts` - The number of nonces per score.
+/// * `threshold` - The solution threshold.
+///
+/// # Returns
+/// A JSON object with the counts per score, without the trailing empty ones, and the near misses.
+pub fn report(counts: &ScoreCounts, threshold: usize) -> Value {
+    let used = counts.iter().rposition(|count| *count > 0).map_or(0, |last| last + 1);
+    json!({
+        "counts": counts[..used],
+        "near_misses": near_misses(counts, threshold).iter().map(|near_miss| json!({
+            "score": near_miss.score,
+            "count": near_miss.count,
+            "expected": near_miss.expected,
+        })).collect::<Vec<_>>(),
+    })
+}
+
+#[test]
+/// Tests that thread counts are folded and that near misses count the nonces reaching each score.
+fn test_score_histogram() {
+    let histogram = ScoreHistogram::default();
+    let mut counts: ScoreCounts = [0; SCORE_BUCKETS];
+    counts[..4].copy_from_slice(&[512, 256, 128, 128]);
+    counts[ScoreHistogram::bucket(100)] += 1;
+    histogram.add(&mut counts);
+    histogram.add(&mut counts);
+    assert_eq!(counts, [0; SCORE_BUCKETS]);
+
+    let counts = histogram.get_counts();
+    assert_eq!(counts[SCORE_BUCKETS - 1], 1);
+    assert_eq!(near_misses(&counts, 3), [
+        NearMiss { score: 0, count: 1025, expected: 1025.0 },
+        NearMiss { score: 1, count: 513, expected: 512.5 },
+        NearMiss { score: 2, count: 257, expected: 256.25 },
+    ]);
+    assert_eq!(near_misses(&counts, 1).len(), 1);
+
+    let report = report(&counts, 3);
+    assert_eq!(report["counts"].as_array().unwrap().len(), SCORE_BUCKETS);
+    assert_eq!(report["near_misses"][2]["count"], 257);
+}
@@ -0,0 +1,270 @@
+use zeroize::Zeroizing;
+use lib::types::{Id, PublicKey, PublicKey64, Signature};
+use crate::converters::{get_id_from_public_key_64, get_public_key_64_from_id, get_public_key_64_from_public_key, get_public_key_from_public_key_64, parse_id, public_key_from_hex};
+use crate::arch;
+use crate::crypto::{k12_32, k12_into};
+use crate::fourq::{Point, scalar_mul, scalar_reduce, scalar_sub};
+use crate::secret::Secret;
+
+/// Number of letters of a seed.
+pub const SEED_LENGTH: usize = 55;
+
+/// A seed, 55 lowercase letters from which the keys of an identity are derived.
+pub type IdentitySeed = Secret<[u8; SEED_LENGTH]>;
+
+impl IdentitySeed {
+    /// Gets the letters of the seed, to show them to the user.
+    pub fn as_str(&self) -> &str {
+        std::str::from_utf8(self.expose()).unwrap_or_default()
+    }
+}
+
+/// The keys and identity derived from a seed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Identity {
+    /// Hash of the seed, which the private key and the nonces of the signatures derive from
+    subseed: Secret<[u8; 32]>,
+    pub private_key: Secret<[u8; 32]>,
+    pub public_key: [u8; 32],
+    pub id: Id,
+}
+
+/// Serializes the ID and the hex of the public key, never the private key.
+#[cfg(feature = "serde")]
+impl serde::Serialize for Identity {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        use serde::ser::SerializeStruct;
+
+        let mut identity = serializer.serialize_struct("Identity", 2)?;
+        identity.serialize_field("id", std::str::from_utf8(&self.id).unwrap_or_default())?;
+        identity.serialize_field("public_key", &crate::converters::public_key_to_hex(&self.public_key))?;
+        identity.end()
+    }
+}
+
+/// Generates a random seed.
+///
+/// # Returns
+/// 55 letters drawn uniformly from `a` to `z` with RDRAND, or the OS generator without it.
+pub fn generate_seed() -> IdentitySeed {
+    // Values from the incomplete last block of 26 are rejected, so every letter is equally likely
+    const LIMIT: u64 = u64::MAX - u64::MAX % 26;
+
+    let mut seed = Secret::new([0u8; SEED_LENGTH]);
+    for letter in seed.expose_mut().iter_mut() {
+        let value = loop {
+            let value = arch::random_u64();
+            if value < LIMIT {
+                break value;
+            }
+        };
+        *letter = b'a' + (value % 26) as u8;
+    }
+    seed
+}
+
+/// Derives the keys and the identity of a seed, the way the Qubic wallet does.
+///
+/// # Arguments
+/// * `seed` - The 55 lowercase letters of the seed.
+///
+/// # Returns
+/// The identity, or `None` if the seed is not 55 lowercase letters.
+pub fn derive_identity(seed: &[u8]) -> Option<Identity> {
+    if seed.len() != SEED_LENGTH || !seed.iter().all(u8::is_ascii_lowercase) {
+        return None;
+    }
+
+    // The subseed hashes the letters as values from 0 to 25, the intermediate values are wiped too
+    let seed_values = Zeroizing::new(seed.iter().map(|letter| letter - b'a').collect::<Vec<_>>());
+    let mut subseed = Secret::new([0u8; 32]);
+    *subseed.expose_mut() = k12_32(&seed_values);
+    let mut private_key = Secret::new([0u8; 32]);
+    *private_key.expose_mut() = k12_32(subseed.expose());
+
+    let scalar = Zeroizing::new(scalar_from_bytes(private_key.expose()));
+    let public_key = Point::generator().mul_scalar(&scalar).encode();
+
+    Some(Identity { subseed, private_key, public_key, id: get_id_from_public_key(&public_key) })
+}
+
+impl Identity {
+    /// Signs a message with SchnorrQ, the way the Qubic nodes verify it.
+    ///
+    /// # Arguments
+    /// * `message_digest` - The 32-byte KangarooTwelve hash of the message.
+    ///
+    /// # Returns
+    /// The signature: the encoded commitment point followed by the scalar.
+    pub fn sign(&self, message_digest: &[u8; 32]) -> Signature {
+        // The first half of the expanded subseed is the private key, the second half keys the nonces
+        let mut expanded = Zeroizing::new([0u8; 64]);
+        k12_into(self.subseed.expose(), expanded.as_mut());
+        let mut nonce_input = Zeroizing::new([0u8; 64]);
+        nonce_input[..32].copy_from_slice(&expanded[32..]);
+        nonce_input[32..].copy_from_slice(message_digest);
+        let nonce = Zeroizing::new(scalar_reduce(&scalar_from_bytes(&k12_32(nonce_input.as_ref()))));
+
+        let commitment = Point::generator().mul_scalar(&nonce).encode();
+        let mut challenge_input = [0u8; 96];
+        challenge_input[..32].copy_from_slice(&commitment);
+        challenge_input[32..64].copy_from_slice(&self.public_key);
+        challenge_input[64..].copy_from_slice(message_digest);
+        let challenge = scalar_reduce(&scalar_from_bytes(&k12_32(&challenge_input)));
+
+        let private_key = Zeroizing::new(scalar_reduce(&scalar_from_bytes(self.private_key.expose())));
+        let response = scalar_sub(&nonce, &scalar_mul(&challenge, &private_key));
+
+        let mut signature = Signature::default();
+        signature[..4].iter_mut().zip(commitment.chunks_exact(8)).for_each(|(value, bytes)| *value = u64::from_le_bytes(bytes.try_into().unwrap()));
+        signature[4..].copy_from_slice(&response);
+        signature
+    }
+}
+
+/// Reads a scalar from the first 32 bytes of a key or hash.
+fn scalar_from_bytes(bytes: &[u8; 32]) -> [u64; 4] {
+    std::array::from_fn(|idx| u64::from_le_bytes(bytes[idx * 8..idx * 8 + 8].try_into().unwrap()))
+}
+
+/// The representations of a key given in any of them.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct KeyForms {
+    /// The seed, only known when the seed was given
+    pub seed: Option<IdentitySeed>,
+    pub id: String,
+    pub public_key: PublicKey,
+}
+
+/// Converts a seed, an identity or a hex public key to the other representations.
+///
+/// # Arguments
+/// * `value` - 55 lowercase letters of a seed, 60 uppercase letters of an identity or
+///   64 hex characters of a public key.
+///
+/// # Returns
+/// The representations of the key, or why the value is not a valid key.
+pub fn convert_key(value: &str) -> Result<KeyForms, String> {
+    let value = value.trim();
+
+    if value.len() == SEED_LENGTH && value.bytes().all(|letter| letter.is_ascii_lowercase()) {
+        let identity = derive_identity(value.as_bytes()).ok_or("invalid seed")?;
+        let mut seed = Secret::new([0u8; SEED_LENGTH]);
+        seed.expose_mut().copy_from_slice(value.as_bytes());
+        return Ok(KeyForms {
+            seed: Some(seed),
+            id: String::from_utf8_lossy(&identity.id).to_string(),
+            public_key: identity.public_key,
+        });
+    }
+
+    if value.len() == 60 && value.bytes().all(|letter| letter.is_ascii_uppercase()) {
+        // The public key only depends on the first 56 letters, the last 4 are the checksum
+        let mut public_key_64 = PublicKey64::default();
+        let id: Id = value.as_bytes().try_into().map_err(|_| "invalid identity")?;
+        if !get_public_key_64_from_id(&id, &mut public_key_64) {
+            return Err("invalid identity".to_string());
+        }
+        let public_key = get_public_key_from_public_key_64(&public_key_64);
+        if parse_id(value).is_none() {
+            let expected = get_id_from_public_key(&public_key);
+            return Err(format!("the identity checksum does not match, expected {}", String::from_utf8_lossy(&expected)));
+        }
+        return Ok(KeyForms { seed: None, id: value.to_string(), public_key });
+    }
+
+    if let Some(public_key) = public_key_from_hex(value) {
+        let id = get_id_from_public_key(&public_key);
+        return Ok(KeyForms { seed: None, id: String::from_utf8_lossy(&id).to_string(), public_key });
+    }
+
+    Err("expected a 55-letter lowercase seed, a 60-letter uppercase identity or a 64-character hex public key".to_string())
+}
+
+/// Gets the ID of a public key, checksum included.
+fn get_id_from_public_key(public_key: &PublicKey) -> Id {
+    let mut id: Id = [0; 60];
+    get_id_from_public_key_64(&get_public_key_64_from_public_key(public_key), &mut id);
+    id
+}
+
+#[test]
+/// Tests that a signature verifies: the response and the challenge give back the commitment.
+fn test_sign() {
+    let identity = derive_identity(&[b'a'; SEED_LENGTH]).unwrap();
+    let digest = k12_32(b"transaction");
+    let signature = identity.sign(&digest);
+    assert_eq!(identity.sign(&digest), signature);
+    assert_ne!(identity.sign(&k12_32(b"another transaction")), signature);
+
+    let commitment = signature[..4].iter().flat_map(|value| value.to_le_bytes()).collect::<Vec<_>>();
+    let mut challenge_input = commitment.clone();
+    challenge_input.extend_from_slice(&identity.public_key);
+    challenge_input.extend_from_slice(&digest);
+    let challenge = scalar_reduce(&scalar_from_bytes(&k12_32(&challenge_input)));
+    let public_key = Point::generator().mul_scalar(&scalar_from_bytes(identity.private_key.expose()));
+    let response: [u64; 4] = signature[4..].try_into().unwrap();
+    assert_eq!((Point::generator().mul_scalar(&response) + public_key.mul_scalar(&challenge)).encode().to_vec(), commitment);
+}
+
+#[test]
+/// Tests a signature against a fixed vector, so a change of the arithmetic cannot go unnoticed.
+fn test_sign_known_answer() {
+    // The public key is the one of the wallet identity of the seed, the signature is a regression
+    // vector recorded from this implementation and verified by `test_sign`
+    let identity = derive_identity(&[b'a'; SEED_LENGTH]).unwrap();
+    assert_eq!(crate::converters::public_key_to_hex(&identity.public_key), "1f590d03e613bdded38b4c0820ac44615f91af12435980b3ede3c08c315a2544");
+    let expected: Signature = [
+        0xC3EB_6A5B_42D2_3EC3, 0x2303_1645_BCA0_0E9E, 0x27F1_328C_5350_6135, 0xA48F_0926_AFC3_15E3,
+        0xFF8B_27D9_7C94_0552, 0xA3C4_49B3_EB60_18ED, 0x642E_E772_B37E_DC2E, 0x0011_E5BB_E9B2_FDC4,
+    ];
+    assert_eq!(identity.sign(&k12_32(b"transaction")), expected);
+}
+
+#[test]
+/// Tests the derivation against an identity produced by the Qubic wallet.
+fn test_derive_identity() {
+    let identity = derive_identity(&[b'a'; SEED_LENGTH]).unwrap();
+    assert_eq!(std::str::from_utf8(&identity.id).unwrap(), "BZBQFLLBNCXEMGLOBHUVFTLUPLVCPQUASSILFABOFFBCADQSSUPNWLZBQEXK");
+
+    assert!(derive_identity(b"abc").is_none());
+    assert!(derive_identity(&[b'A'; SEED_LENGTH]).is_none());
+
+    let seed = generate_seed();
+    let identity = derive_identity(seed.expose()).unwrap();
+    assert_eq!(seed.as_str().len(), SEED_LENGTH);
+    assert!(!format!("{seed:?}{identity:?}").contains(seed.as_str()));
+}
+
+#[cfg(feature = "serde")]
+#[test]
+/// Tests that the JSON form of an identity leaves the private key out.
+fn test_serialize_identity() {
+    let identity = derive_identity(&[b'a'; SEED_LENGTH]).unwrap();
+    let json = serde_json::to_value(&identity).unwrap();
+    assert_eq!(json["id"], "BZBQFLLBNCXEMGLOBHUVFTLUPLVCPQUASSILFABOFFBCADQSSUPNWLZBQEXK");
+    assert_eq!(json["public_key"], crate::converters::public_key_to_hex(&identity.public_key));
+    assert_eq!(json.as_object().unwrap().len(), 2);
+}
+
+#[test]
+/// Tests the conversions between seeds, identities and public keys.
+fn test_convert_key() {
+    let seed = "a".repeat(SEED_LENGTH);
+    let from_seed = convert_key(&seed).unwrap();
+    assert_eq!(from_seed.seed.as_ref().unwrap().as_str(), seed);
+    assert!(!format!("{from_seed:?}").contains(&seed));
+    assert_eq!(from_seed.id, "BZBQFLLBNCXEMGLOBHUVFTLUPLVCPQUASSILFABOFFBCADQSSUPNWLZBQEXK");
+
+    let from_id = convert_key(&from_seed.id).unwrap();
+    assert_eq!(from_id.seed, None);
+    assert_eq!(from_id.public_key, from_seed.public_key);
+
+    let hex = crate::converters::public_key_to_hex(&from_seed.public_key);
+    assert_eq!(convert_key(&hex).unwrap().id, from_seed.id);
+
+    let error = convert_key(&from_seed.id.replace("QEXK", "QEXA")).unwrap_err();
+    assert!(error.contains("expected BZBQFLLBNCXEMGLOBHUVFTLUPLVCPQUASSILFABOFFBCADQSSUPNWLZBQEXK"));
+    assert!(convert_key("not a key").is_err());
+}
+
@@ -0,0 +1,99 @@
+//! Detection of a second Qiner mining for the same ID on the machine.
+//!
+//! Two instances of the same ID split the CPU between them and find, then submit, the
+//! solutions of the same seed twice. Every instance holds an exclusive lock on a file named
+//! after its ID, which the OS releases when the process ends, even on a crash, so a stale lock
+//! file never blocks a restart.
+
+use std::fmt;
+use std::fs::{File, OpenOptions, TryLockError};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+
+/// Exit code of the process when another instance mines for the same ID.
+pub const DUPLICATE_INSTANCE_EXIT_CODE: i32 = 73;
+
+/// Why the lock of an ID was not acquired.
+#[derive(Debug)]
+pub enum InstanceLockError {
+    /// Another process holds the lock, its PID if it could be read.
+    Held { path: PathBuf, pid: Option<u32> },
+    /// The lock file could not be opened or locked.
+    Io(io::Error),
+}
+
+impl fmt::Display for InstanceLockError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            InstanceLockError::Held { path, pid: Some(pid) } => write!(f, "{} is held by process {pid}", path.display()),
+            InstanceLockError::Held { path, pid: None } => write!(f, "{} is held by another process", path.display()),
+            InstanceLockError::Io(err) => write!(f, "{err}"),
+        }
+    }
+}
+
+/// The exclusive lock of an ID, held until dropped or until the process ends.
+#[derive(Debug)]
+pub struct InstanceLock {
+    path: PathBuf,
+    /// The locked file, the lock goes with it
+    _file: File,
+}
+
+impl InstanceLock {
+    /// Gets the lock file of an ID.
+    ///
+    /// # Arguments
+    /// * `dir` - The directory of the lock files, e.g. the temporary directory.
+    /// * `id` - The ID mined for.
+    ///
+    /// # Returns
+    /// The path of the lock file.
+    pub fn lock_file(dir: &Path, id: &str) -> PathBuf {
+        dir.join(format!("qiner-{id}.lock"))
+    }
+
+    /// Acquires the lock, writing the PID of the process in the file for the next instances.
+    ///
+    /// # Arguments
+    /// * `path` - The lock file, created if missing.
+    ///
+    /// # Returns
+    /// The lock, or why it was not acquired.
+    pub fn acquire(path: PathBuf) -> Result<Self, InstanceLockError> {
+        let mut file = OpenOptions::new().read(true).write(true).create(true).truncate(false).open(&path).map_err(InstanceLockError::Io)?;
+        match file.try_lock() {
+            Ok(()) => {}
+            Err(TryLockError::WouldBlock) => {
+                let pid = std::fs::read_to_string(&path).ok().and_then(|content| content.trim().parse().ok());
+                return Err(InstanceLockError::Held { path, pid });
+            }
+            Err(TryLockError::Error(err)) => return Err(InstanceLockError::Io(err)),
+        }
+
+        file.set_len(0).and_then(|_| file.write_all(std::process::id().to_string().as_bytes())).map_err(InstanceLockError::Io)?;
+        Ok(InstanceLock { path, _file: file })
+    }
+
+    /// Gets the path of the lock file.
+    pub fn get_path(&self) -> &Path {
+        &self.path
+    }
+}
+
+#[test]
+/// Tests that a held lock is refused with the PID of its holder and acquired again once released.
+fn test_instance_lock() {
+    let path = InstanceLock::lock_file(&std::env::temp_dir(), &format!("TEST{}", std::process::id()));
+    let lock = InstanceLock::acquire(path.clone()).unwrap();
+    match InstanceLock::acquire(path.clone()) {
+        Err(InstanceLockError::Held { pid, .. }) => assert_eq!(pid, Some(std::process::id())),
+        result => panic!("expected a held lock, got {result:?}"),
+    }
+
+    drop(lock);
+    let lock = InstanceLock::acquire(path.clone()).unwrap();
+    assert_eq!(lock.get_path(), path);
+    drop(lock);
+    std::fs::remove_file(path).unwrap();
+}
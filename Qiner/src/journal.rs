@@ -0,0 +1,199 @@
+use std::fs::{self, OpenOptions};
+use std::io::{self, Write};
+use std::path::Path;
+use lib::types::{Nonce64, PublicKey64, NUMBER_OF_NONCE_64};
+use serde_json::Value;
+
+/// Default file unsent solutions are spilled to.
+pub const DEFAULT_SPILL_FILE: &str = "qiner-spill.txt";
+
+/// Formats a nonce as a hex string of its bytes.
+///
+/// # Arguments
+/// * `nonce` - The nonce to format.
+///
+/// # Returns
+/// The nonce as 64 hex characters.
+pub fn nonce_to_hex(nonce: &Nonce64) -> String {
+    nonce.iter().flat_map(|word| word.to_le_bytes()).map(|byte| format!("{byte:02x}")).collect()
+}
+
+/// Parses a nonce from the hex string of its bytes.
+///
+/// # Arguments
+/// * `hex` - The 64 hex characters of the nonce.
+///
+/// # Returns
+/// `Some(Nonce64)` if the string is a valid nonce, `None` otherwise.
+pub fn nonce_from_hex(hex: &str) -> Option<Nonce64> {
+    let hex = hex.trim();
+    if hex.len() != NUMBER_OF_NONCE_64 * 16 || !hex.is_ascii() {
+        return None;
+    }
+
+    let mut nonce = Nonce64::default();
+    for (word, chunk) in nonce.iter_mut().zip(hex.as_bytes().chunks(16)) {
+        let mut bytes = [0u8; 8];
+        for (byte, pair) in bytes.iter_mut().zip(chunk.chunks(2)) {
+            *byte = u8::from_str_radix(std::str::from_utf8(pair).ok()?, 16).ok()?;
+        }
+        *word = u64::from_le_bytes(bytes);
+    }
+
+    Some(nonce)
+}
+
+/// Appends nonces to the spill file, one hex nonce per line.
+///
+/// Only std and no locks are used, so it is safe to call from a panic hook.
+///
+/// # Arguments
+/// * `path` - The spill file.
+/// * `nonces` - The nonces to spill.
+///
+/// # Returns
+/// `Ok(())` once the nonces are written and synced to disk.
+pub fn spill(path: &Path, nonces: &[Nonce64]) -> io::Result<()> {
+    let mut file = OpenOptions::new().create(true).append(true).open(path)?;
+    for nonce in nonces {
+        writeln!(file, "{}", nonce_to_hex(nonce))?;
+    }
+    file.sync_all()
+}
+
+/// Reads back the nonces of the spill file and removes it.
+///
+/// # Arguments
+/// * `path` - The spill file.
+///
+/// # Returns
+/// The spilled nonces, empty if the file does not exist. Invalid lines are skipped.
+pub fn take_spilled(path: &Path) -> io::Result<Vec<Nonce64>> {
+    let content = match fs::read_to_string(path) {
+        Ok(content) => content,
+        Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(err) => return Err(err),
+    };
+
+    let nonces = content.lines().filter_map(nonce_from_hex).collect();
+    fs::remove_file(path)?;
+
+    Ok(nonces)
+}
+
+/// Reads back the first nonces of the spill file, leaving the others in it.
+///
+/// # Arguments
+/// * `path` - The spill file.
+/// * `limit` - The maximum number of nonces to read.
+///
+/// # Returns
+/// The nonces read, empty if the file does not exist. The file is removed once every nonce is read.
+pub fn take_spilled_at_most(path: &Path, limit: usize) -> io::Result<Vec<Nonce64>> {
+    let content = match fs::read_to_string(path) {
+        Ok(content) => content,
+        Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(err) => return Err(err),
+    };
+
+    let mut nonces = content.lines().filter_map(nonce_from_hex).collect::<Vec<_>>();
+    if nonces.len() <= limit {
+        fs::remove_file(path)?;
+        return Ok(nonces);
+    }
+
+    // The rest replaces the file at once, so a crash meanwhile loses no nonce
+    let rest = nonces.split_off(limit);
+    let tmp = path.with_extension("tmp");
+    let _ = fs::remove_file(&tmp);
+    spill(&tmp, &rest)?;
+    fs::rename(&tmp, path)?;
+
+    Ok(nonces)
+}
+
+/// Parses recorded solutions, to submit them again.
+///
+/// Reads the formats Qiner writes: the spill file (one hex nonce per line), the proxy spill file
+/// and the mock node record (`<public key> <nonce>` hex pairs), and a JSON array of hex nonces or
+/// of `{"public_key": ..., "nonce": ...}` objects. Empty lines and lines starting with `#` are skipped.
+///
+/// # Arguments
+/// * `content` - The content of the file.
+/// * `public_key` - The public key of the solutions given without one, e.g. those of the spill file.
+///
+/// # Returns
+/// The public keys and nonces, or why an entry is invalid.
+pub fn parse_solutions(content: &str, public_key: Option<&PublicKey64>) -> Result<Vec<(PublicKey64, Nonce64)>, String> {
+    let solution = |entry: usize, key: Option<&str>, nonce: &str| -> Result<(PublicKey64, Nonce64), String> {
+        let key = match key {
+            Some(key) => nonce_from_hex(key).ok_or_else(|| format!("entry {entry}: invalid public key {key:?}"))?,
+            None => *public_key.ok_or_else(|| format!("entry {entry}: no public key, and no ID to use instead"))?,
+        };
+        Ok((key, nonce_from_hex(nonce).ok_or_else(|| format!("entry {entry}: invalid nonce {nonce:?}"))?))
+    };
+
+    if content.trim_start().starts_with('[') {
+        let entries: Vec<Value> = serde_json::from_str(content).map_err(|err| format!("invalid JSON: {err}"))?;
+        return entries.iter().enumerate().map(|(idx, entry)| match entry {
+            Value::String(nonce) => solution(idx + 1, None, nonce),
+            Value::Object(fields) => {
+                let nonce = fields.get("nonce").and_then(Value::as_str).ok_or_else(|| format!("entry {}: no nonce", idx + 1))?;
+                solution(idx + 1, fields.get("public_key").and_then(Value::as_str), nonce)
+            }
+            _ => Err(format!("entry {}: expected a nonce or an object", idx + 1)),
+        }).collect();
+    }
+
+    content.lines()
+        .enumerate()
+        .filter(|(_, line)| !line.trim().is_empty() && !line.trim_start().starts_with('#'))
+        .map(|(idx, line)| match line.split_whitespace().collect::<Vec<_>>()[..] {
+            [nonce] => solution(idx + 1, None, nonce),
+            [key, nonce] => solution(idx + 1, Some(key), nonce),
+            _ => Err(format!("entry {}: expected a nonce or a public key and a nonce", idx + 1)),
+        })
+        .collect()
+}
+
+#[test]
+/// Tests that a nonce survives the hex round trip and that invalid strings are rejected.
+fn test_nonce_hex() {
+    let nonce: Nonce64 = [0x0123456789abcdef, 0, u64::MAX, 42];
+    let hex = nonce_to_hex(&nonce);
+
+    assert_eq!(hex.len(), 64);
+    assert!(hex.starts_with("efcdab8967452301"));
+    assert_eq!(nonce_from_hex(&hex), Some(nonce));
+    assert_eq!(nonce_from_hex(&hex[1..]), None);
+    assert_eq!(nonce_from_hex(&hex.replace('e', "g")), None);
+}
+
+#[test]
+/// Tests the parsing of every format solutions are recorded in.
+fn test_parse_solutions() {
+    let (public_key, nonce): (PublicKey64, Nonce64) = ([1, 2, 3, 4], [5, 6, 7, 8]);
+    let (key_hex, nonce_hex) = (nonce_to_hex(&public_key), nonce_to_hex(&nonce));
+
+    let lines = format!("# spilled\n{nonce_hex}\n\n{key_hex} {nonce_hex}\n");
+    assert_eq!(parse_solutions(&lines, Some(&[9; 4])), Ok(vec![([9; 4], nonce), (public_key, nonce)]));
+    assert_eq!(parse_solutions(&lines, None), Err("entry 2: no public key, and no ID to use instead".to_string()));
+    assert!(parse_solutions("abc", Some(&public_key)).unwrap_err().starts_with("entry 1: invalid nonce"));
+
+    let json = format!(r#"["{nonce_hex}", {{"public_key": "{key_hex}", "nonce": "{nonce_hex}"}}]"#);
+    assert_eq!(parse_solutions(&json, Some(&[9; 4])), Ok(vec![([9; 4], nonce), (public_key, nonce)]));
+    assert_eq!(parse_solutions("[1]", None), Err("entry 1: expected a nonce or an object".to_string()));
+}
+
+#[test]
+/// Tests that the spilled nonces are read back in order, a limited number at a time.
+fn test_take_spilled_at_most() {
+    let path = std::env::temp_dir().join(format!("qiner-spill-{}.txt", std::process::id()));
+    let nonces = (0..5).map(|idx| [idx; NUMBER_OF_NONCE_64]).collect::<Vec<Nonce64>>();
+    spill(&path, &nonces).unwrap();
+
+    assert_eq!(take_spilled_at_most(&path, 2).unwrap(), nonces[..2]);
+    assert_eq!(take_spilled_at_most(&path, 10).unwrap(), nonces[2..]);
+    assert!(!path.exists());
+    assert!(take_spilled_at_most(&path, 10).unwrap().is_empty());
+}
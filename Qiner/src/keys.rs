@@ -0,0 +1,348 @@
+//! The seeds Qiner signs with, kept encrypted on disk.
+//!
+//! Mining only needs the public key of the ID, but transactions and control messages are signed,
+//! which needs a seed. The mining identity and a separate operator identity are kept apart, so a
+//! machine that only signs transfers never holds the seed of the ID the farm mines for, and the
+//! other way round. Each seed is sealed with XChaCha20-Poly1305 under a key derived from a
+//! passphrase with Argon2id. The role and the ID are authenticated with it, so an entry moved to
+//! another role or given another ID fails to decrypt.
+//!
+//! Without any seed, Qiner runs read-only: it mines to the public key of the ID, which is all a
+//! solution needs, and everything that signs asks `Keys` for an identity, getting an error naming
+//! what needs a seed instead.
+
+use std::fs;
+use std::path::Path;
+use argon2::{Algorithm, Argon2, Params, Version};
+use chacha20poly1305::aead::{Aead, KeyInit, Payload};
+use chacha20poly1305::{XChaCha20Poly1305, XNonce};
+use serde_json::{json, Value};
+use zeroize::Zeroizing;
+use crate::identity::{derive_identity, Identity};
+
+/// Default file of the keystore, in `STATE_DIR`.
+pub const DEFAULT_KEYSTORE_FILE: &str = "qiner-keys.json";
+
+/// Version of the keystore format.
+const KEYSTORE_VERSION: u64 = 1;
+
+/// Size of the salt of the key derivation.
+const SALT_SIZE: usize = 16;
+
+/// Size of the nonce of XChaCha20-Poly1305.
+const NONCE_SIZE: usize = 24;
+
+/// What an identity of the keystore signs for.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum KeyRole {
+    /// The ID solutions are mined for
+    Mining,
+    /// The identity signing transactions and control messages
+    Operator,
+}
+
+impl KeyRole {
+    /// Every role, in the order they are listed.
+    pub const ALL: [KeyRole; 2] = [KeyRole::Mining, KeyRole::Operator];
+
+    /// Gets the name of the role, as written in the keystore and on the command line.
+    pub fn name(self) -> &'static str {
+        match self {
+            KeyRole::Mining => "mining",
+            KeyRole::Operator => "operator",
+        }
+    }
+
+    /// Parses the name of a role.
+    ///
+    /// # Arguments
+    /// * `value` - `mining` or `operator`.
+    ///
+    /// # Returns
+    /// The role, or why the name is unknown.
+    pub fn parse(value: &str) -> Result<Self, String> {
+        let value = value.trim().to_lowercase();
+        KeyRole::ALL.into_iter().find(|role| role.name() == value).ok_or_else(|| format!("unknown role {value:?}, expected mining or operator"))
+    }
+}
+
+/// Cost of the Argon2id derivation of the key of a seed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct KdfCost {
+    /// Memory, in KiB
+    pub memory_kib: u32,
+    pub iterations: u32,
+    pub parallelism: u32,
+}
+
+impl Default for KdfCost {
+    /// The cost recommended for Argon2id: 19 MiB, 2 iterations, 1 lane.
+    fn default() -> Self {
+        KdfCost { memory_kib: Params::DEFAULT_M_COST, iterations: Params::DEFAULT_T_COST, parallelism: Params::DEFAULT_P_COST }
+    }
+}
+
+/// A seed encrypted with a passphrase.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EncryptedSeed {
+    /// ID of the seed, readable without the passphrase
+    pub id: String,
+    cost: KdfCost,
+    salt: [u8; SALT_SIZE],
+    nonce: [u8; NONCE_SIZE],
+    ciphertext: Vec<u8>,
+}
+
+impl EncryptedSeed {
+    /// Encrypts a seed.
+    ///
+    /// # Arguments
+    /// * `role` - The role the seed is stored for, authenticated with it.
+    /// * `seed` - The 55 lowercase letters of the seed.
+    /// * `passphrase` - The passphrase the key is derived from.
+    /// * `cost` - The cost of the key derivation.
+    ///
+    /// # Returns
+    /// The encrypted seed, or why the seed cannot be encrypted.
+    pub fn encrypt(role: KeyRole, seed: &[u8], passphrase: &str, cost: KdfCost) -> Result<Self, String> {
+        let identity = derive_identity(seed).ok_or("invalid seed: expected 55 lowercase letters")?;
+        let id = String::from_utf8_lossy(&identity.id).into_owned();
+        let mut salt = [0u8; SALT_SIZE];
+        let mut nonce = [0u8; NONCE_SIZE];
+        getrandom::fill(&mut salt).and_then(|_| getrandom::fill(&mut nonce)).map_err(|err| format!("no random salt: {err}"))?;
+
+        let cipher = new_cipher(passphrase, &salt, cost)?;
+        let aad = associated_data(role, &id);
+        let ciphertext = cipher.encrypt(XNonce::from_slice(&nonce), Payload { msg: seed, aad: aad.as_bytes() }).map_err(|_| "failed to encrypt the seed")?;
+        Ok(EncryptedSeed { id, cost, salt, nonce, ciphertext })
+    }
+
+    /// Decrypts the seed and derives its identity.
+    ///
+    /// # Arguments
+    /// * `role` - The role the seed is stored for.
+    /// * `passphrase` - The passphrase the seed was encrypted with.
+    ///
+    /// # Returns
+    /// The identity, or why the seed cannot be decrypted: usually a wrong passphrase.
+    pub fn decrypt(&self, role: KeyRole, passphrase: &str) -> Result<Identity, String> {
+        let cipher = new_cipher(passphrase, &self.salt, self.cost)?;
+        let aad = associated_data(role, &self.id);
+        let seed = Zeroizing::new(cipher.decrypt(XNonce::from_slice(&self.nonce), Payload { msg: &self.ciphertext, aad: aad.as_bytes() })
+            .map_err(|_| format!("failed to decrypt the {} seed: wrong passphrase or altered keystore", role.name()))?);
+        let identity = derive_identity(&seed).ok_or("the decrypted seed is invalid")?;
+        if identity.id != self.id.as_bytes() {
+            return Err(format!("the decrypted seed is not the one of {}", self.id));
+        }
+        Ok(identity)
+    }
+
+    fn to_json(&self) -> Value {
+        json!({
+            "id": self.id,
+            "kdf": { "algorithm": "argon2id", "memory_kib": self.cost.memory_kib, "iterations": self.cost.iterations, "parallelism": self.cost.parallelism, "salt": to_hex(&self.salt) },
+            "cipher": { "algorithm": "xchacha20poly1305", "nonce": to_hex(&self.nonce), "ciphertext": to_hex(&self.ciphertext) },
+        })
+    }
+
+    fn from_json(value: &Value) -> Option<Self> {
+        let (kdf, cipher) = (&value["kdf"], &value["cipher"]);
+        if kdf["algorithm"] != "argon2id" || cipher["algorithm"] != "xchacha20poly1305" {
+            return None;
+        }
+        let number = |value: &Value| value.as_u64().and_then(|number| u32::try_from(number).ok());
+        Some(EncryptedSeed {
+            id: value["id"].as_str()?.to_string(),
+            cost: KdfCost { memory_kib: number(&kdf["memory_kib"])?, iterations: number(&kdf["iterations"])?, parallelism: number(&kdf["parallelism"])? },
+            salt: from_hex(kdf["salt"].as_str()?)?.try_into().ok()?,
+            nonce: from_hex(cipher["nonce"].as_str()?)?.try_into().ok()?,
+            ciphertext: from_hex(cipher["ciphertext"].as_str()?)?,
+        })
+    }
+}
+
+/// The encrypted seeds of every role.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Keystore {
+    seeds: Vec<(KeyRole, EncryptedSeed)>,
+}
+
+impl Keystore {
+    /// Reads a keystore file.
+    ///
+    /// # Arguments
+    /// * `path` - The file, as written by `write`.
+    ///
+    /// # Returns
+    /// The keystore, empty if the file does not exist, or why it cannot be read.
+    pub fn read(path: &Path) -> Result<Self, String> {
+        let content = match fs::read_to_string(path) {
+            Ok(content) => content,
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => return Ok(Keystore::default()),
+            Err(err) => return Err(format!("failed to read {}: {err}", path.display())),
+        };
+        let value = serde_json::from_str::<Value>(&content).map_err(|err| format!("invalid {}: {err}", path.display()))?;
+        if value["version"].as_u64() != Some(KEYSTORE_VERSION) {
+            return Err(format!("unsupported version {} of {}", value["version"], path.display()));
+        }
+        let mut keystore = Keystore::default();
+        for role in KeyRole::ALL {
+            let seed = &value["keys"][role.name()];
+            if !seed.is_null() {
+                let seed = EncryptedSeed::from_json(seed).ok_or_else(|| format!("invalid {} seed in {}", role.name(), path.display()))?;
+                keystore.insert(role, seed);
+            }
+        }
+        Ok(keystore)
+    }
+
+    /// Writes the keystore, readable by its owner only.
+    ///
+    /// # Arguments
+    /// * `path` - The file, replaced at once so a crash never leaves it half written.
+    ///
+    /// # Returns
+    /// Why the keystore could not be written, if it could not.
+    pub fn write(&self, path: &Path) -> Result<(), String> {
+        let keys = self.seeds.iter().map(|(role, seed)| (role.name().to_string(), seed.to_json())).collect::<serde_json::Map<_, _>>();
+        let content = serde_json::to_string_pretty(&json!({ "version": KEYSTORE_VERSION, "keys": keys })).map_err(|err| err.to_string())?;
+
+        let temporary = path.with_extension("tmp");
+        let mut options = fs::OpenOptions::new();
+        options.write(true).create(true).truncate(true);
+        #[cfg(unix)]
+        std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+        let result = options.open(&temporary)
+            .and_then(|mut file| std::io::Write::write_all(&mut file, content.as_bytes()).and_then(|_| file.sync_all()))
+            .and_then(|_| fs::rename(&temporary, path));
+        result.map_err(|err| format!("failed to write {}: {err}", path.display()))
+    }
+
+    /// Gets the encrypted seed of a role.
+    pub fn get(&self, role: KeyRole) -> Option<&EncryptedSeed> {
+        self.seeds.iter().find(|(seed_role, _)| *seed_role == role).map(|(_, seed)| seed)
+    }
+
+    /// Stores the encrypted seed of a role, replacing the previous one.
+    pub fn insert(&mut self, role: KeyRole, seed: EncryptedSeed) {
+        self.seeds.retain(|(seed_role, _)| *seed_role != role);
+        self.seeds.push((role, seed));
+        self.seeds.sort_by_key(|(role, _)| *role);
+    }
+
+    /// Gets the roles with a seed and their IDs.
+    pub fn list(&self) -> impl Iterator<Item = (KeyRole, &str)> {
+        self.seeds.iter().map(|(role, seed)| (*role, seed.id.as_str()))
+    }
+}
+
+/// The unlocked identities.
+#[derive(Debug, Default)]
+pub struct Keys {
+    mining: Option<Identity>,
+    operator: Option<Identity>,
+}
+
+impl Keys {
+    /// Creates new `Keys`.
+    ///
+    /// # Arguments
+    /// * `mining` - The identity of the ID mined for, if its seed is known.
+    /// * `operator` - The identity signing transactions and control messages, if configured.
+    ///
+    /// # Returns
+    /// The keys.
+    pub fn new(mining: Option<Identity>, operator: Option<Identity>) -> Self {
+        Keys { mining, operator }
+    }
+
+    /// Gets the identity of a role.
+    pub fn get(&self, role: KeyRole) -> Option<&Identity> {
+        match role {
+            KeyRole::Mining => self.mining.as_ref(),
+            KeyRole::Operator => self.operator.as_ref(),
+        }
+    }
+
+    /// Checks if no seed is held, mining to the public key of the ID only.
+    pub fn is_read_only(&self) -> bool {
+        self.mining.is_none() && self.operator.is_none()
+    }
+
+    /// Gets the identity transactions and control messages are signed with: the operator, or the mining identity without one.
+    ///
+    /// # Arguments
+    /// * `action` - What is signed, named in the error.
+    ///
+    /// # Returns
+    /// The identity, or why `action` is not available in read-only mode.
+    pub fn get_signing_identity(&self, action: &str) -> Result<&Identity, String> {
+        self.operator.as_ref().or(self.mining.as_ref()).ok_or_else(|| {
+            format!("{action} needs a seed, but Qiner holds none and only mines to the public key of the ID: import an operator seed with `qiner keys import --role operator`")
+        })
+    }
+}
+
+/// Derives the key of a passphrase and creates the cipher of a seed.
+fn new_cipher(passphrase: &str, salt: &[u8], cost: KdfCost) -> Result<XChaCha20Poly1305, String> {
+    let params = Params::new(cost.memory_kib, cost.iterations, cost.parallelism, Some(32)).map_err(|err| format!("invalid key derivation cost: {err}"))?;
+    let mut key = Zeroizing::new([0u8; 32]);
+    Argon2::new(Algorithm::Argon2id, Version::V0x13, params)
+        .hash_password_into(passphrase.as_bytes(), salt, key.as_mut())
+        .map_err(|err| format!("failed to derive the key: {err}"))?;
+    XChaCha20Poly1305::new_from_slice(key.as_ref()).map_err(|err| err.to_string())
+}
+
+/// Gets the data authenticated with a seed: its role and ID.
+fn associated_data(role: KeyRole, id: &str) -> String {
+    format!("qiner:{}:{id}", role.name())
+}
+
+fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{byte:02x}")).collect()
+}
+
+fn from_hex(hex: &str) -> Option<Vec<u8>> {
+    if !hex.len().is_multiple_of(2) || !hex.is_ascii() {
+        return None;
+    }
+    hex.as_bytes().chunks(2).map(|pair| u8::from_str_radix(std::str::from_utf8(pair).ok()?, 16).ok()).collect()
+}
+
+#[test]
+/// Tests that seeds decrypt with their passphrase and role only, and survive the keystore file.
+fn test_keystore() {
+    let cost = KdfCost { memory_kib: 64, iterations: 1, parallelism: 1 };
+    let seed = [b'a'; crate::identity::SEED_LENGTH];
+    let encrypted = EncryptedSeed::encrypt(KeyRole::Operator, &seed, "correct horse", cost).unwrap();
+    assert_eq!(encrypted.id, "BZBQFLLBNCXEMGLOBHUVFTLUPLVCPQUASSILFABOFFBCADQSSUPNWLZBQEXK");
+    assert!(!to_hex(&encrypted.ciphertext).contains(&to_hex(&seed)));
+    assert_eq!(encrypted.decrypt(KeyRole::Operator, "correct horse").unwrap(), derive_identity(&seed).unwrap());
+    assert!(encrypted.decrypt(KeyRole::Operator, "wrong horse").is_err());
+    assert!(encrypted.decrypt(KeyRole::Mining, "correct horse").is_err());
+    assert!(EncryptedSeed { id: "A".repeat(60), ..encrypted.clone() }.decrypt(KeyRole::Operator, "correct horse").is_err());
+    assert!(EncryptedSeed::encrypt(KeyRole::Operator, b"abc", "correct horse", cost).is_err());
+
+    let path = std::env::temp_dir().join(format!("qiner-keystore-{}.json", std::process::id()));
+    assert_eq!(Keystore::read(&path).unwrap(), Keystore::default());
+    let mut keystore = Keystore::default();
+    keystore.insert(KeyRole::Operator, encrypted.clone());
+    keystore.insert(KeyRole::Mining, EncryptedSeed::encrypt(KeyRole::Mining, &[b'b'; crate::identity::SEED_LENGTH], "other", cost).unwrap());
+    keystore.write(&path).unwrap();
+    #[cfg(unix)]
+    assert_eq!(std::os::unix::fs::PermissionsExt::mode(&fs::metadata(&path).unwrap().permissions()) & 0o777, 0o600);
+    let read = Keystore::read(&path).unwrap();
+    fs::remove_file(&path).unwrap();
+    assert_eq!(read, keystore);
+    assert_eq!(read.list().map(|(role, _)| role).collect::<Vec<_>>(), KeyRole::ALL);
+    assert_eq!(read.get(KeyRole::Operator), Some(&encrypted));
+
+    let operator = encrypted.decrypt(KeyRole::Operator, "correct horse").unwrap();
+    let keys = Keys::new(None, Some(operator.clone()));
+    assert_eq!((keys.get_signing_identity("a transfer"), keys.get(KeyRole::Mining)), (Ok(&operator), None));
+    assert!(!keys.is_read_only());
+    assert!(Keys::default().is_read_only());
+    assert!(Keys::default().get_signing_identity("a transfer").unwrap_err().starts_with("a transfer needs a seed"));
+    assert_eq!(KeyRole::parse(" Operator "), Ok(KeyRole::Operator));
+    assert!(KeyRole::parse("root").is_err());
+}
@@ -1,4 +1,137 @@
-pub mod miner;
+#![cfg_attr(not(feature = "std"), no_std)]
+
+// The ID conversions and the scoring only need `alloc`, for embedded and verification builds
+extern crate alloc;
+
+pub mod arch;
 pub mod math;
 pub mod converters;
+pub mod crypto;
+pub mod neurons;
+pub mod score;
+#[cfg(feature = "serde")]
+pub mod forms;
+#[cfg(feature = "miner")]
+pub mod algorithm;
+#[cfg(feature = "miner")]
+pub mod backend;
+#[cfg(feature = "miner")]
+pub mod balance;
+#[cfg(feature = "miner")]
+pub mod messages;
+#[cfg(feature = "miner")]
+pub mod miner;
+#[cfg(feature = "miner")]
+pub mod metrics;
+#[cfg(feature = "miner")]
+pub mod mocknode;
+#[cfg(feature = "miner")]
 pub mod network;
+#[cfg(feature = "miner")]
+pub mod nonce;
+#[cfg(feature = "miner")]
+pub mod shares;
+#[cfg(feature = "miner")]
+pub mod socks;
+#[cfg(feature = "miner")]
+pub mod stats;
+#[cfg(feature = "miner")]
+pub mod submission;
+#[cfg(feature = "miner")]
+pub mod summary;
+#[cfg(feature = "miner")]
+pub mod supervisor;
+#[cfg(feature = "miner")]
+pub mod targets;
+#[cfg(feature = "miner")]
+pub mod telemetry;
+#[cfg(feature = "miner")]
+pub mod thermal;
+#[cfg(feature = "miner")]
+pub mod transactions;
+#[cfg(all(feature = "miner", unix))]
+pub mod systemd;
+#[cfg(feature = "miner")]
+pub mod notifier;
+#[cfg(feature = "miner")]
+pub mod panic_hook;
+#[cfg(feature = "miner")]
+pub mod packet_factory;
+#[cfg(feature = "miner")]
+pub mod peer_filter;
+#[cfg(feature = "miner")]
+pub mod peers;
+#[cfg(feature = "miner")]
+pub mod preflight;
+#[cfg(feature = "miner")]
+pub mod priority;
+#[cfg(feature = "miner")]
+pub mod profile;
+#[cfg(feature = "miner")]
+pub mod proxy;
+#[cfg(feature = "quic")]
+pub mod quic;
+#[cfg(feature = "miner")]
+pub mod reload;
+#[cfg(feature = "miner")]
+pub mod rig;
+#[cfg(feature = "miner")]
+pub mod rng;
+#[cfg(feature = "miner")]
+pub mod secret;
+#[cfg(feature = "miner")]
+pub mod selftest;
+#[cfg(feature = "miner")]
+pub mod sensors;
+#[cfg(feature = "miner")]
+pub mod settings;
+#[cfg(feature = "miner")]
+pub mod capture;
+#[cfg(feature = "miner")]
+pub mod cli;
+#[cfg(feature = "miner")]
+pub mod bandwidth;
+#[cfg(feature = "miner")]
+pub mod clock;
+#[cfg(feature = "miner")]
+pub mod config_file;
+#[cfg(feature = "miner")]
+pub mod connection;
+#[cfg(feature = "miner")]
+pub mod cores;
+#[cfg(feature = "miner")]
+pub mod cpu_quota;
+#[cfg(feature = "miner")]
+pub mod degradation;
+#[cfg(feature = "miner")]
+pub mod energy;
+#[cfg(feature = "miner")]
+pub mod computors;
+#[cfg(feature = "miner")]
+pub mod estimator;
+#[cfg(feature = "miner")]
+pub mod farm;
+#[cfg(feature = "miner")]
+pub mod fingerprint;
+#[cfg(feature = "miner")]
+pub mod fourq;
+#[cfg(feature = "miner")]
+pub mod health;
+#[cfg(feature = "miner")]
+pub mod histogram;
+#[cfg(feature = "miner")]
+pub mod identity;
+#[cfg(feature = "miner")]
+pub mod instance;
+#[cfg(feature = "miner")]
+pub mod journal;
+#[cfg(feature = "miner")]
+pub mod keys;
+#[cfg(feature = "miner")]
+pub mod logger;
+#[cfg(feature = "tui")]
+pub mod tui;
+#[cfg(feature = "miner")]
+pub mod wizard;
+#[cfg(feature = "wasm")]
+pub mod wasm;
@@ -0,0 +1,84 @@
+//! The logger of `pretty_env_logger`, with filters that can be replaced while running so
+//! a change of `RUST_LOG` applies without a restart.
+
+use std::env;
+use std::sync::RwLock;
+use std::sync::atomic::{AtomicBool, Ordering};
+use log::{LevelFilter, Log, Metadata, Record};
+use pretty_env_logger::env_logger::Logger;
+use lib::env_names::ENV_RUST_LOG;
+use crate::rig::get_worker_name;
+
+/// The logger records are forwarded to, `None` until `init` is called.
+static INNER: RwLock<Option<Logger>> = RwLock::new(None);
+
+/// Name of the rig every record is prefixed with, `None` to log the records as they are.
+static WORKER_NAME: RwLock<Option<String>> = RwLock::new(None);
+
+/// Whether logging is turned off, e.g. while the dashboard owns the terminal.
+static IS_MUTED: AtomicBool = AtomicBool::new(false);
+
+struct ReloadableLogger;
+
+impl Log for ReloadableLogger {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        !IS_MUTED.load(Ordering::Relaxed) && INNER.read().unwrap().as_ref().is_some_and(|inner| inner.enabled(metadata))
+    }
+
+    fn log(&self, record: &Record) {
+        if !IS_MUTED.load(Ordering::Relaxed) {
+            if let Some(inner) = INNER.read().unwrap().as_ref() {
+                match WORKER_NAME.read().unwrap().as_deref() {
+                    Some(name) => inner.log(&Record::builder()
+                        .args(format_args!("[{name}] {}", record.args()))
+                        .metadata(record.metadata().clone())
+                        .module_path(record.module_path())
+                        .file(record.file())
+                        .line(record.line())
+                        .build()),
+                    None => inner.log(record),
+                }
+            }
+        }
+    }
+
+    fn flush(&self) {
+        if let Some(inner) = INNER.read().unwrap().as_ref() {
+            inner.flush();
+        }
+    }
+}
+
+/// Initializes the logger with the filters of `RUST_LOG`, like `pretty_env_logger::init_timed`,
+/// prefixing the records with `WORKER_NAME` if set.
+///
+/// # Panics
+/// If a logger is already set.
+pub fn init() {
+    set_filters(&env::var(ENV_RUST_LOG).unwrap_or_default());
+    *WORKER_NAME.write().unwrap() = get_worker_name();
+    log::set_logger(&ReloadableLogger).expect("the logger is initialized once");
+}
+
+/// Replaces the filters of the logger.
+///
+/// # Arguments
+/// * `filters` - Filters in the `RUST_LOG` syntax, empty to only log errors.
+pub fn set_filters(filters: &str) {
+    let inner = pretty_env_logger::formatted_timed_builder().parse_filters(filters).build();
+    let max_level = inner.filter();
+    *INNER.write().unwrap() = Some(inner);
+    if !IS_MUTED.load(Ordering::Relaxed) {
+        log::set_max_level(max_level);
+    }
+}
+
+/// Turns logging off or back on.
+///
+/// # Arguments
+/// * `is_muted` - `true` to drop every record, `false` to apply the filters again.
+pub fn set_muted(is_muted: bool) {
+    IS_MUTED.store(is_muted, Ordering::Relaxed);
+    let max_level = INNER.read().unwrap().as_ref().map_or(LevelFilter::Off, Logger::filter);
+    log::set_max_level(if is_muted { LevelFilter::Off } else { max_level });
+}
@@ -1,100 +1,751 @@
-use qiner::miner::Miner;
-use tokio;
-use lib::types::{Id, PublicKey64, STACK_SIZE};
+use clap::Parser;
+use qiner::capture::{self, describe, read_capture};
+use qiner::config_file::{DEFAULT_STRUCTURED_CONFIG_FILE, is_structured_config, load_structured_config, migrate_config};
+use qiner::cli::{Cli, Command, KeysCommand, LONG_VERSION, SendArgs};
+use qiner::cores::{CorePolicy, detect_topology};
+use qiner::cpu_quota::{get_available_cpus, get_cpu_quota};
+use qiner::degradation::{AlertConfig, DEGRADED_EXIT_CODE, watch_degradation};
+use qiner::farm::{FarmReporter, farm_report_task};
+use qiner::health::{HealthMonitor, UNHEALTHY_EXIT_CODE, serve_health, wait_unhealthy};
+use qiner::instance::{DUPLICATE_INSTANCE_EXIT_CODE, InstanceLock, InstanceLockError};
+use qiner::priority::Priority;
+use qiner::journal::{self, parse_solutions};
+use qiner::metrics::{MetricsExporter, metrics_task};
+use qiner::mocknode::{MockNode, MockNodeConfig};
+use qiner::miner::{Miner, Solution};
+use lib::types::{PublicKey64, STACK_SIZE};
 use std::{env};
-use std::mem::{size_of, transmute};
-use std::sync::Arc;
+use std::collections::HashSet;
+use std::net::SocketAddr;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::sync::atomic::AtomicUsize;
+use std::time::{Duration, Instant};
 use tokio::runtime::Builder;
-use qiner::converters::get_public_key_64_from_id;
-use lib::env_names::{ENV_ID, ENV_NUMBER_OF_THREADS, ENV_SERVER_IP, ENV_SERVER_PORT};
-use qiner::network::Packet;
-use lib::types::network::protocols::BROADCAST_MESSAGE;
-use tokio::io::AsyncWriteExt;
+use qiner::converters::{get_public_key_64_from_id, parse_id, public_key_to_hex};
+use lib::env_names::{ENV_ALGORITHM, ENV_CORE_POLICY, ENV_ID, ENV_KEYSTORE_PASSPHRASE, ENV_NUMBER_OF_THREADS, ENV_SERVER_IP, ENV_SERVER_PORT, ENV_PEER_ALLOWLIST, ENV_PEER_DENYLIST, ENV_RUST_LOG, ENV_POOL_SERVER, ENV_RANDOM_SEED, ENV_SHARE_SERVER, ENV_SOLUTION_THRESHOLD, ENV_SPILL_FILE};
+use qiner::network::{NetStats, Packet, RequestResponseHeader};
+use qiner::notifier::{Notifier, NotifyEvent};
+use qiner::packet_factory::PacketFactory;
+use qiner::fingerprint::config_fingerprint;
+use qiner::stats::{HashrateAlertSink, StatsConfig, StatsSampler, stats_task};
+use qiner::peers::{parse_public_peers, PEER_SCORES_FILE, PeerBook};
+use qiner::preflight::{self, check_config, check_cpu_features, check_peers};
+use qiner::proxy::run_proxy;
+#[cfg(feature = "quic")]
+use qiner::peers::QUIC_SCHEME;
+#[cfg(feature = "quic")]
+use qiner::quic::QuicSender;
+use qiner::reload::{ConfigChange, watch_config_task};
+use qiner::rng::{self, RandomSource};
+use qiner::selftest::run_self_test;
+use qiner::identity::{convert_key, derive_identity, generate_seed};
+use qiner::keys::{EncryptedSeed, KdfCost, KeyRole, Keys, Keystore};
+use qiner::wizard::{DEFAULT_CONFIG_FILE, run_init, set_config_value};
+use qiner::shares::send_shares_task;
+use qiner::socks::{self, SocksProxy};
+use qiner::submission::{ConnectionOutcome, PendingSolution, RetryQueue, SUBMISSION_STATS_FILE, SubmissionPacing, SubmissionStats, save_submission_stats_task};
+use qiner::summary::SessionReporter;
+use qiner::balance::balance_task;
+use qiner::clock::tick_info_task;
+use qiner::computors::computors_task;
+use qiner::targets::{TargetSelector, probe_pool_task};
+use qiner::thermal::{ThermalThrottle, thermal_throttle_task};
+use qiner::telemetry::{SolutionStage, SolutionTracer};
+use qiner::transactions::TransactionSender;
+use qiner::supervisor::supervise_workers;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
 use tokio::net::TcpStream;
-use lib::random_seed::get_random_seed;
-use lib::solution_threshold::get_solution_threshold;
-use lib::version::get_version;
+use qiner::settings::{NodeSettings, ProcessSettings, SettingError, Settings, SubmissionSettings};
+use lib::types::network::{NUMBER_OF_EXCHANGED_PEERS, Protocol};
+use lib::types::network::protocols::EXCHANGE_PUBLIC_PEERS;
+use lib::version::format_version;
 
-/// Retrieve the number of threads from the environment variable.
+/// Loads the settings, logging the variables without a default that are missing or invalid.
+///
+/// # Arguments
+/// * `mining_id` - The ID of the mining seed of the keystore, mined for when `ID` is not set
+/// * `simulated_node` - The address of the mock node of `--simulate`, which overrides the configuration
 ///
 /// # Returns
-/// The number of threads as a `usize`.
-/// Returns a default value of 4 if parsing fails.
-fn get_number_of_threads() -> usize {
-    env::var(ENV_NUMBER_OF_THREADS).unwrap_or_else(|_| "4".to_string()).parse::<usize>().unwrap_or(4)
+/// The settings if mining can start, `None` otherwise.
+fn load_settings(mining_id: Option<&str>, simulated_node: Option<SocketAddr>) -> Option<Settings> {
+    let configured = |name: &str| env::var(name).ok()
+        .filter(|value| !value.trim().is_empty())
+        .or_else(|| mining_id.filter(|_| name == ENV_ID).map(str::to_string));
+    let value = |name: &str| match simulated_node {
+        Some(node) => get_simulated_value(name, node, &configured),
+        None => configured(name),
+    };
+    let checks = check_config(value);
+    let invalid = checks.iter().filter(|check| !check.is_ok).map(|check| check.name.as_str()).collect::<Vec<_>>();
+    if !invalid.is_empty() {
+        log::error!("Missing or invalid configuration: {}. Run `qiner init` to create it.", invalid.join(", "));
+        return None;
+    }
+
+    Settings::from_lookup(value).ok()
 }
 
-/// Retrieve the server IP address from the environment variable.
+/// Loads the settings of the nodes, for the subcommands that do not mine.
 ///
 /// # Returns
-/// The server IP address as a `String`.
-/// Returns an empty string if the environment variable is not set.
-fn get_server_ip() -> String {
-    env::var(ENV_SERVER_IP).unwrap_or_default()
+/// The settings, `None` after logging the variables that are invalid.
+fn load_node_settings() -> Option<NodeSettings> {
+    NodeSettings::load().map_err(|errors| log::error!("Invalid configuration: {}", describe_errors(&errors))).ok()
 }
 
-/// Retrieve the server port from the environment variable.
+/// Loads the settings of the submission, for the subcommands that do not mine.
 ///
 /// # Returns
-/// The server port as a `String`.
-/// Returns an empty string if the environment variable is not set.
-fn get_server_port() -> String {
-    env::var(ENV_SERVER_PORT).unwrap_or_default()
+/// The settings, `None` after logging the variables that are invalid.
+fn load_submission_settings() -> Option<SubmissionSettings> {
+    SubmissionSettings::load().map_err(|errors| log::error!("Invalid configuration: {}", describe_errors(&errors))).ok()
 }
 
-/// Retrieve the ID from the environment variable.
-///
-/// # Returns
-/// The ID as a `String`.
-/// Returns an empty string if the environment variable is not set.
-fn get_id() -> String {
-    env::var(ENV_ID).unwrap_or_default()
+/// Lists the variables that are missing or invalid, with why.
+fn describe_errors(errors: &[SettingError]) -> String {
+    errors.iter().map(ToString::to_string).collect::<Vec<_>>().join(", ")
 }
 
-#[tokio::main]
-async fn main() {
-    // Initialize dotenv
-    dotenv::dotenv().ok();
+fn main() {
+    // Parse the command line
+    let cli = Cli::parse();
+
+    #[cfg(not(feature = "tui"))]
+    if cli.tui {
+        eprintln!("Qiner was built without the `tui` feature, rebuild with `--features tui` to use `--tui`");
+        std::process::exit(1);
+    }
+
+    #[cfg(not(feature = "quic"))]
+    if matches!(&cli.command, Some(Command::Proxy(args)) if args.quic_listen.is_some()) {
+        eprintln!("Qiner was built without the `quic` feature, rebuild with `--features quic` to use `--quic-listen`");
+        std::process::exit(1);
+    }
+
+    // Create the configuration interactively, offering the values of the existing file as defaults
+    if let Some(Command::Init(args)) = &cli.command {
+        dotenv::from_path(&args.config).ok();
+        match run_init(&args.config) {
+            Ok(_) => std::process::exit(0),
+            Err(err) => {
+                eprintln!("Failed to create the configuration: {err}");
+                std::process::exit(1);
+            }
+        }
+    }
+
+    // Generate a new identity, which needs neither the configuration nor the runtime
+    if let Some(Command::GenerateId(args)) = &cli.command {
+        std::process::exit(generate_id(args.write.then_some(args.config.as_path())));
+    }
+
+    // Convert a key to its other representations
+    if let Some(Command::Convert(args)) = &cli.command {
+        std::process::exit(convert(&args.value));
+    }
+
+    // Print a packet capture
+    if let Some(Command::Dump(args)) = &cli.command {
+        std::process::exit(dump(&args.file));
+    }
+
+    // Convert the legacy configuration, before it is loaded into the environment
+    if let Some(Command::MigrateConfig(args)) = &cli.command {
+        match migrate_config(&args.env_file, &args.config) {
+            Ok(count) => {
+                println!("{count} settings of {} written to {}, which Qiner now loads instead", args.env_file.display(), args.config.display());
+                std::process::exit(0);
+            }
+            Err(err) => {
+                eprintln!("Failed to migrate the configuration: {err}");
+                std::process::exit(1);
+            }
+        }
+    }
+
+    // The passphrase of the keystore is only taken from the environment Qiner starts in, never from the configuration next to the keystore
+    let keystore_passphrase = env::var(ENV_KEYSTORE_PASSPHRASE).ok().filter(|passphrase| !passphrase.is_empty()).map(zeroize::Zeroizing::new);
+
+    // Load the structured configuration, or the legacy `.env`, keeping the file to reload its settings while mining
+    let structured_config = Path::new(DEFAULT_STRUCTURED_CONFIG_FILE);
+    let config_file = if structured_config.exists() {
+        if let Err(err) = load_structured_config(structured_config) {
+            eprintln!("Invalid {}: {err}", structured_config.display());
+            std::process::exit(1);
+        }
+        Some(structured_config.to_path_buf())
+    } else {
+        dotenv::dotenv().ok()
+    };
+
+    // Fill the settings the environment leaves unset with those of the profile
+    let profile_settings = cli.profile.map(|profile| profile.apply(get_available_cpus()));
+
+    // Check the configuration, the peers and the CPU without mining
+    if let Some(Command::Check) = &cli.command {
+        std::process::exit(run_check());
+    }
+
+    // The files and the priority of the process, which every command below needs
+    let process = ProcessSettings::load().unwrap_or_else(|errors| {
+        eprintln!("Invalid configuration: {}", describe_errors(&errors));
+        std::process::exit(1);
+    });
+
+    // Manage the keystore, which needs the configuration for its location only
+    if let Some(Command::Keys(command)) = &cli.command {
+        std::process::exit(run_keys(command, &process.keystore_file));
+    }
 
     // Initialize the logger
-    pretty_env_logger::init_timed();
+    qiner::logger::init();
+    if let Some(config_file) = config_file.as_ref().filter(|config_file| !is_structured_config(config_file)) {
+        log::info!("{} is in the legacy format, `qiner migrate-config` converts it to {DEFAULT_STRUCTURED_CONFIG_FILE}", config_file.display());
+    } else if config_file.is_some() && Path::new(DEFAULT_CONFIG_FILE).exists() {
+        log::warn!("{DEFAULT_CONFIG_FILE} is ignored, the settings are loaded from {DEFAULT_STRUCTURED_CONFIG_FILE}");
+    }
+    if keystore_passphrase.is_none() && env::var_os(ENV_KEYSTORE_PASSPHRASE).is_some() {
+        log::warn!("{ENV_KEYSTORE_PASSPHRASE} is ignored in the configuration file, which would leave the passphrase next to the keystore");
+    }
+    if let (Some(profile), Some(names)) = (cli.profile, &profile_settings) {
+        log::info!("Profile: {profile}, setting {}", if names.is_empty() { "nothing, every setting is explicit".to_string() } else { names.join(", ") });
+    }
+
+    // Record every message exchanged with the peers, for `qiner dump`
+    if let Some(path) = &process.packet_capture {
+        match capture::init(path) {
+            Ok(_) => log::warn!("Capturing every message exchanged with the peers to {}", path.display()),
+            Err(err) => log::error!("Failed to open the packet capture {}: {:?}", path.display(), err),
+        }
+    }
 
-    // Retrieve the number of threads
-    let number_of_threads = get_number_of_threads() + 1;
-    let stack_size = STACK_SIZE * number_of_threads;
+    // Measure the random generators
+    if let Some(Command::Bench) = &cli.command {
+        std::process::exit(run_bench());
+    }
 
-    // Build the Tokio runtime with a specified number of worker threads and stack size
+    // Refuse to mine with primitives producing wrong results, e.g. a miscompiled SIMD path
+    if let Some(Command::Selftest) = &cli.command {
+        let checks = run_self_test();
+        println!("{}", serde_json::to_string_pretty(&preflight::report(&checks)).unwrap_or_default());
+        std::process::exit(if checks.iter().all(|check| check.is_ok) { 0 } else { 1 });
+    }
+    if cli.command.is_none() {
+        let failed = run_self_test().into_iter().filter(|check| !check.is_ok).collect::<Vec<_>>();
+        for check in &failed {
+            log::error!("Self-test of {} failed: {}", check.name, check.detail);
+        }
+        if !failed.is_empty() {
+            log::error!("Refusing to mine with broken primitives, check the build and the CPU");
+            std::process::exit(1);
+        }
+    }
+
+    // Lower the priority before the threads are spawned, so they inherit it
+    let priority = process.priority;
+    match priority.apply() {
+        Ok(_) if priority != Priority::Normal => log::info!("Priority: {priority}"),
+        Ok(_) => {}
+        Err(err) => log::warn!("Failed to set the priority to {priority}: {:?}", err),
+    }
+
+    // Unlock the mining seed before the runtime is built, since the prompt and the key derivation block
+    let keys = match cli.command.is_none() && !cli.simulate {
+        true => unlock_mining_keys(keystore_passphrase.as_deref().map(String::as_str), &process.keystore_file).unwrap_or_else(|err| {
+            log::error!("Failed to unlock the keystore: {err}");
+            std::process::exit(1);
+        }),
+        false => Keys::default(),
+    };
+
+    // Build the Tokio runtime, the mining threads are spawned apart from its workers
     Builder::new_multi_thread()
-        .worker_threads(number_of_threads)
-        .thread_stack_size(stack_size)
+        .thread_stack_size(STACK_SIZE)
         .enable_all()
         .build()
         .unwrap()
         .block_on(async {
-            async_main().await;
+            let nodes = match &cli.command {
+                Some(Command::Proxy(_) | Command::Replay(_) | Command::Send(_)) => load_node_settings().unwrap_or_else(|| std::process::exit(1)),
+                _ => NodeSettings::default(),
+            };
+            match cli.command {
+                Some(Command::Proxy(args)) => {
+                    let submission = load_submission_settings().unwrap_or_else(|| std::process::exit(1));
+                    let upstream = nodes.get_addrs().into_iter().next().unwrap_or_default();
+                    let protocol = nodes.get_peer_protocol(&upstream);
+                    run_proxy(args.listen, args.quic_listen, upstream, args.spill_file, protocol, (!args.no_keep_alive).then_some(args.keep_alive), submission.max_bytes_per_hour).await;
+                }
+                Some(Command::Replay(args)) => {
+                    let submission = load_submission_settings().unwrap_or_else(|| std::process::exit(1));
+                    std::process::exit(replay(&args.file, args.peer, &nodes, &submission.pacing).await)
+                }
+                Some(Command::Send(args)) => std::process::exit(send(args, keystore_passphrase.as_deref().map(String::as_str), &nodes, &process.keystore_file).await),
+                Some(Command::Init(_) | Command::MigrateConfig(_) | Command::GenerateId(_) | Command::Convert(_) | Command::Dump(_) | Command::Check | Command::Keys(_) | Command::Bench | Command::Selftest) => unreachable!("runs before the runtime is built"),
+                None => async_main(cli, config_file, keys).await,
+            }
         });
 }
 
+/// Generates a random seed and prints the identity derived from it.
+///
+/// # Arguments
+/// * `config` - Configuration file the identity is written to as `ID`, if any
+///
+/// # Returns
+/// The exit code of the process.
+fn generate_id(config: Option<&Path>) -> i32 {
+    let seed = generate_seed();
+    let Some(identity) = derive_identity(seed.expose()) else {
+        eprintln!("Failed to derive the identity of the generated seed");
+        return 1;
+    };
+
+    let id = String::from_utf8_lossy(&identity.id).to_string();
+    println!("Seed: {}", seed.as_str());
+    println!("Identity: {id}");
+    println!("Public key: {}", public_key_to_hex(&identity.public_key));
+    println!("Write the seed down and keep it secret: it is the only way to use this identity.");
+
+    if let Some(config) = config {
+        if let Err(err) = set_config_value(config, ENV_ID, &id) {
+            eprintln!("Failed to write the identity to {}: {err}", config.display());
+            return 1;
+        }
+        println!("Identity written to {} as {ENV_ID}", config.display());
+    }
+
+    0
+}
+
+/// Runs a `keys` subcommand.
+///
+/// # Arguments
+/// * `command` - The subcommand
+/// * `path` - The keystore file
+///
+/// # Returns
+/// The exit code of the process.
+fn run_keys(command: &KeysCommand, path: &Path) -> i32 {
+    let mut keystore = match Keystore::read(path) {
+        Ok(keystore) => keystore,
+        Err(err) => {
+            eprintln!("{err}");
+            return 1;
+        }
+    };
+
+    match command {
+        KeysCommand::List => {
+            let mut is_empty = true;
+            for (role, id) in keystore.list() {
+                println!("{}: {id}", role.name());
+                is_empty = false;
+            }
+            if is_empty {
+                println!("No seed in {}", path.display());
+            }
+            0
+        }
+        KeysCommand::Import(args) => {
+            let result = read_seed().and_then(|seed| {
+                let passphrase = zeroize::Zeroizing::new(rpassword::prompt_password("Passphrase: ").map_err(|err| format!("failed to read the passphrase: {err}"))?);
+                let confirmation = zeroize::Zeroizing::new(rpassword::prompt_password("Passphrase again: ").map_err(|err| format!("failed to read the passphrase: {err}"))?);
+                if passphrase.is_empty() || passphrase != confirmation {
+                    return Err("the passphrases are empty or differ".to_string());
+                }
+                EncryptedSeed::encrypt(args.role, seed.trim().as_bytes(), &passphrase, KdfCost::default())
+            });
+            let encrypted = match result {
+                Ok(encrypted) => encrypted,
+                Err(err) => {
+                    eprintln!("Failed to import the seed: {err}");
+                    return 1;
+                }
+            };
+            let id = encrypted.id.clone();
+            keystore.insert(args.role, encrypted);
+            if let Err(err) = keystore.write(path) {
+                eprintln!("{err}");
+                return 1;
+            }
+            println!("The {} seed of {id} is encrypted in {}", args.role.name(), path.display());
+            0
+        }
+    }
+}
+
+/// Reads a seed from the standard input, never from the command line where other users of the machine see it.
+///
+/// # Returns
+/// The line read, or why it could not be read.
+fn read_seed() -> Result<zeroize::Zeroizing<String>, String> {
+    eprintln!("Seed:");
+    let mut seed = zeroize::Zeroizing::new(String::new());
+    std::io::stdin().read_line(&mut seed).map_err(|err| format!("failed to read the seed: {err}"))?;
+    Ok(seed)
+}
+
+/// Unlocks the keys transactions are signed with: the operator seed of the keystore, else its
+/// mining seed, else a seed read from the standard input as the operator one.
+///
+/// # Arguments
+/// * `passphrase` - The passphrase of the environment Qiner was started in, `None` to ask for it
+/// * `path` - The keystore file
+///
+/// # Returns
+/// The keys, read-only if the standard input gave no seed, or why they could not be unlocked.
+fn unlock_signing_keys(passphrase: Option<&str>, path: &Path) -> Result<Keys, String> {
+    let keystore = Keystore::read(path)?;
+    let Some((role, encrypted)) = [KeyRole::Operator, KeyRole::Mining].into_iter().find_map(|role| keystore.get(role).map(|encrypted| (role, encrypted))) else {
+        let seed = read_seed()?;
+        if seed.trim().is_empty() {
+            return Ok(Keys::default());
+        }
+        let identity = derive_identity(seed.trim().as_bytes()).ok_or("invalid seed: expected 55 lowercase letters")?;
+        return Ok(Keys::new(None, Some(identity)));
+    };
+    let passphrase = get_keystore_passphrase(role, encrypted, passphrase)?;
+    let identity = encrypted.decrypt(role, &passphrase)?;
+    Ok(match role {
+        KeyRole::Mining => Keys::new(Some(identity), None),
+        KeyRole::Operator => Keys::new(None, Some(identity)),
+    })
+}
+
+/// Gets the passphrase of a seed of the keystore: the one Qiner was started with if any, else asked on the terminal.
+///
+/// # Arguments
+/// * `role` - The role of the seed, named in the prompt
+/// * `encrypted` - The encrypted seed, whose ID is named in the prompt
+/// * `passphrase` - The passphrase of the environment Qiner was started in, never of the configuration file
+///
+/// # Returns
+/// The passphrase, or why there is none: no variable and no terminal to ask on.
+fn get_keystore_passphrase(role: KeyRole, encrypted: &EncryptedSeed, passphrase: Option<&str>) -> Result<zeroize::Zeroizing<String>, String> {
+    if let Some(passphrase) = passphrase {
+        return Ok(zeroize::Zeroizing::new(passphrase.to_string()));
+    }
+    if !std::io::IsTerminal::is_terminal(&std::io::stdin()) {
+        return Err(format!("the {} seed of {} is encrypted, set {ENV_KEYSTORE_PASSPHRASE} or start Qiner in a terminal", role.name(), encrypted.id));
+    }
+    rpassword::prompt_password(format!("Passphrase of the {} seed of {}: ", role.name(), encrypted.id))
+        .map(zeroize::Zeroizing::new)
+        .map_err(|err| format!("failed to read the passphrase: {err}"))
+}
+
+/// Unlocks the mining seed of the keystore at startup, whose ID is mined for when `ID` is not set.
+///
+/// # Arguments
+/// * `passphrase` - The passphrase of the environment Qiner was started in, `None` to ask for it
+/// * `path` - The keystore file
+///
+/// # Returns
+/// The keys, without a mining identity if the keystore has no mining seed, or why the seed could
+/// not be unlocked or is not the one of `ID`.
+fn unlock_mining_keys(passphrase: Option<&str>, path: &Path) -> Result<Keys, String> {
+    let keystore = Keystore::read(path)?;
+    let Some(encrypted) = keystore.get(KeyRole::Mining) else {
+        return Ok(Keys::default());
+    };
+    let passphrase = get_keystore_passphrase(KeyRole::Mining, encrypted, passphrase)?;
+    let identity = encrypted.decrypt(KeyRole::Mining, &passphrase)?;
+    if let Some(id) = env::var(ENV_ID).ok().filter(|id| !id.trim().is_empty()) {
+        if !id.trim().eq_ignore_ascii_case(&encrypted.id) {
+            return Err(format!("{ENV_ID} is {}, but the mining seed of {} is the one of {}", id.trim(), path.display(), encrypted.id));
+        }
+    }
+    Ok(Keys::new(Some(identity), None))
+}
+
+/// Runs the preflight checks and prints their report as JSON.
+///
+/// # Returns
+/// The exit code of the process, `0` only if every check passed.
+fn run_check() -> i32 {
+    let mut checks = check_config(|name| env::var(name).ok());
+
+    // The peers are checked against the valid settings only, the invalid ones are reported above
+    let nodes = NodeSettings::load().unwrap_or_default();
+    let mut peers = nodes.get_addrs();
+    peers.extend(nodes.pool_server.clone());
+    checks.extend(check_peers(&peers, &nodes.peer_filter, SocksProxy::from_env().as_ref().map(SocksProxy::get_addr)));
+    checks.extend(check_cpu_features());
+
+    let report = preflight::report(&checks);
+    println!("{}", serde_json::to_string_pretty(&report).unwrap_or_default());
+    if report["ok"] == true { 0 } else { 1 }
+}
+
+/// Measures the throughput of every random source on one thread.
+///
+/// # Returns
+/// The exit code of the process.
+fn run_bench() -> i32 {
+    for source in [RandomSource::Hardware, RandomSource::ChaCha] {
+        let per_second = rng::measure(source, Duration::from_secs(1));
+        println!("Random source {source}: {:.1} M numbers/s", per_second / 1e6);
+    }
+    0
+}
+
+/// Prints the seed, identity and public key forms of a key given in any of them.
+///
+/// # Arguments
+/// * `value` - A seed, an identity or a hex public key
+///
+/// # Returns
+/// The exit code of the process.
+fn convert(value: &str) -> i32 {
+    match convert_key(value) {
+        Ok(forms) => {
+            if let Some(seed) = &forms.seed {
+                println!("Seed: {}", seed.as_str());
+            }
+            println!("Identity: {}", forms.id);
+            println!("Public key: {}", public_key_to_hex(&forms.public_key));
+            0
+        }
+        Err(err) => {
+            eprintln!("Invalid key: {err}");
+            1
+        }
+    }
+}
+
+/// Prints the records of a packet capture, the messages of each field by field.
+///
+/// # Arguments
+/// * `file` - The capture file, as written with `PACKET_CAPTURE`.
+///
+/// # Returns
+/// The exit code of the process, `1` if the file cannot be read.
+fn dump(file: &Path) -> i32 {
+    match read_capture(file) {
+        Ok(records) => {
+            for record in records {
+                let at = record.at.duration_since(std::time::UNIX_EPOCH).unwrap_or_default();
+                println!("{}.{:06} {} {} {} bytes", at.as_secs(), at.subsec_micros(), record.direction, record.peer, record.data.len());
+                for line in describe(&record.data) {
+                    println!("  {line}");
+                }
+            }
+            0
+        }
+        Err(err) => {
+            eprintln!("Failed to read the capture {}: {err}", file.display());
+            1
+        }
+    }
+}
+
+/// Solution threshold of `--simulate`, reached by most nonces.
+const SIMULATE_SOLUTION_THRESHOLD: usize = 1;
+
+/// Identity mined for when `--simulate` runs without `ID`, the one of the seed made of 55 `a`.
+const SIMULATE_ID: &str = "BZBQFLLBNCXEMGLOBHUVFTLUPLVCPQUASSILFABOFFBCADQSSUPNWLZBQEXK";
+
+/// Starts the mock node of `--simulate`.
+///
+/// # Arguments
+/// * `nodes` - The settings of the nodes, whose protocol version the mock node expects
+///
+/// # Returns
+/// The mock node, or `None` if it could not be started.
+async fn start_simulation(nodes: &NodeSettings) -> Option<MockNode> {
+    let config = MockNodeConfig { protocol: Some(nodes.get_protocol()), respond: true, ..Default::default() };
+    match MockNode::start("127.0.0.1:0", config).await {
+        Ok(node) => {
+            log::warn!("Simulating: solution threshold {SIMULATE_SOLUTION_THRESHOLD}, solutions sent to the mock node on {}", node.local_addr());
+            Some(node)
+        }
+        Err(err) => {
+            log::error!("Failed to start the mock node: {:?}", err);
+            None
+        }
+    }
+}
+
+/// Gets the value of a variable for `--simulate`, the configuration being left as it is.
+///
+/// The mock node replaces the nodes, the pool, the share server and the peer filters are unset
+/// so nothing reaches a real node, and unsent solutions are spilled to a temporary file instead
+/// of `SPILL_FILE`.
+///
+/// # Arguments
+/// * `name` - The name of the variable
+/// * `node` - The address of the mock node
+/// * `configured` - Gets the value of a variable in the configuration
+///
+/// # Returns
+/// The value, `None` if the variable is not set.
+fn get_simulated_value(name: &str, node: SocketAddr, configured: &dyn Fn(&str) -> Option<String>) -> Option<String> {
+    match name {
+        ENV_SERVER_IP => Some(node.ip().to_string()),
+        ENV_SERVER_PORT => Some(node.port().to_string()),
+        ENV_SOLUTION_THRESHOLD => Some(SIMULATE_SOLUTION_THRESHOLD.to_string()),
+        ENV_SPILL_FILE => Some(env::temp_dir().join("qiner-simulate-spill.txt").to_string_lossy().into_owned()),
+        ENV_POOL_SERVER | ENV_SHARE_SERVER | ENV_PEER_ALLOWLIST | ENV_PEER_DENYLIST => None,
+        ENV_ID => configured(name).or_else(|| Some(SIMULATE_ID.to_string())),
+        ENV_RANDOM_SEED => configured(name).or_else(|| Some("0".to_string())),
+        _ => configured(name),
+    }
+}
+
+/// Asynchronous task logging what the mock node of `--simulate` received.
+///
+/// # Arguments
+/// * `node` - The mock node
+async fn simulation_task(node: MockNode) {
+    loop {
+        tokio::time::sleep(Duration::from_secs(10)).await;
+
+        let stats = node.stats();
+        log::info!(
+            "mock node: {} connections | {} packets | {} solutions | {} invalid",
+            stats.connections,
+            stats.packets,
+            stats.solutions.len(),
+            stats.invalid.len()
+        );
+        if let Some(reason) = stats.invalid.last() {
+            log::warn!("The mock node rejected a packet: {reason}");
+        }
+    }
+}
+
 /// Main asynchronous function that runs the mining process and TCP communication
-async fn async_main() {
+///
+/// # Arguments
+/// * `cli` - Parsed command line options
+/// * `config_file` - The `qiner.toml` or `.env` file the configuration was loaded from, watched for changes
+async fn async_main(cli: Cli, config_file: Option<PathBuf>, keys: Keys) {
+    // The mock node replaces the configured nodes, which a reload of the .env file would bring back
+    let simulation = match cli.simulate {
+        true => match load_node_settings() {
+            Some(nodes) => match start_simulation(&nodes).await {
+                Some(node) => Some(node),
+                None => return,
+            },
+            None => return,
+        },
+        false => None,
+    };
+    let config_file = config_file.filter(|_| simulation.is_none());
+
+    let mining_id = keys.get(KeyRole::Mining).map(|identity| String::from_utf8_lossy(&identity.id).into_owned());
+    let Some(settings) = load_settings(mining_id.as_deref(), simulation.as_ref().map(MockNode::local_addr)) else {
+        return;
+    };
+
     // Retrieve environment variables and other configurations
-    let number_of_threads = get_number_of_threads();
-    let ip_raw = get_server_ip();
-    let port_raw = get_server_port();
-    let id_raw = get_id();
-    let version = get_version();
-    let random_seed = get_random_seed();
-    let solution_threshold = get_solution_threshold();
+    let core_policy = settings.threads.core_policy;
+    let placement = settings.threads.get_placement();
+    let number_of_threads = placement.get_num_threads();
+    let keccak_lanes = settings.threads.keccak_lanes;
+    let scheduler = settings.threads.scheduler;
+    let random_source = settings.threads.random_source;
+    rng::set_random_source(random_source);
+    let algorithm = settings.algorithm;
+    let ip_raw = settings.nodes.server_ip.clone();
+    let port_raw = settings.nodes.server_port.unwrap_or_default().to_string();
+    let id_raw = settings.id.clone();
+    let version = settings.nodes.version;
+    let random_seed = settings.random_seed;
+    let solution_threshold = settings.solution_threshold;
+    let share_threshold = settings.submission.share_threshold;
+    let share_server = settings.submission.share_server.clone();
+    let pool_server = settings.nodes.pool_server.clone();
+    let pool_failover = settings.nodes.pool_failover;
+    let solution_max_age = settings.submission.solution_max_age;
+    let solution_max_in_memory = settings.submission.solution_max_in_memory;
+    let pacing = settings.submission.pacing;
+    let socks = SocksProxy::from_env();
+    let notifier = Notifier::from_env();
+    let farm_reporter = FarmReporter::from_env();
+    let metrics_exporter = MetricsExporter::from_env();
+    let mut stats_config = StatsConfig::from_env();
+    if let Some(stats_interval) = cli.stats_interval {
+        stats_config.set_interval(stats_interval);
+    }
+    stats_config.set_verbosity(cli.get_verbosity());
+    let tracer = SolutionTracer::from_env();
+    let stall_timeout = settings.process.stall_timeout;
+    let spill_file = settings.process.spill_file.clone();
+    let panic_exit = settings.process.panic_exit;
 
     // Display retrieved information
-    log::info!("Version: {:?}", version);
+    log::info!("Qiner {LONG_VERSION}");
+    log::info!("Version: {} ({})", format_version(&version), if settings.nodes.is_version_overridden { "from VERSION" } else { "built-in" });
     log::info!("Random seed: {:?}", random_seed);
+    if let Some(socks) = &socks {
+        log::info!("Connecting through the SOCKS proxy {} ({})", socks.get_addr(), if socks.is_isolated() { "isolated" } else { "not isolated" });
+    }
+    log::info!(
+        "Algorithm: v{} {}, {} ({})",
+        algorithm.version,
+        algorithm.name,
+        settings.algorithm_params,
+        if settings.are_algorithm_params_overridden { "overridden" } else { "of the version" }
+    );
     log::info!("Solution threshold: {:?}", solution_threshold);
+    log::info!("Solution max age: {:?}", solution_max_age);
+    log::info!("Solutions in memory: {}", solution_max_in_memory.map_or("unlimited".to_string(), |max| format!("at most {max}, the others wait in {}", spill_file.display())));
+    log::info!("Submission pacing: {:?}", pacing);
+    if let Some(share_threshold) = share_threshold {
+        log::info!("Share threshold: {} (server: {})", share_threshold, share_server.as_deref().unwrap_or("none"));
+        if share_threshold >= solution_threshold {
+            log::warn!("The share threshold is not below the solution threshold, every share is a solution");
+        }
+    }
+    log::info!("Worker name: {}", qiner::rig::get_worker_name().as_deref().unwrap_or("none"));
+    log::info!("Stall timeout: {:?}", stall_timeout);
+    log::info!("Spill file: {} (exit on panic: {})", spill_file.display(), panic_exit);
+    log::info!("Notifications: {}", if notifier.is_some() { "enabled" } else { "disabled" });
+    log::info!("Solution traces: {}", tracer.as_ref().map_or("disabled", |tracer| tracer.get_url()));
+    match &farm_reporter {
+        Some(farm_reporter) => log::info!(
+            "Farm reports: every {:?} as {} ({})",
+            farm_reporter.get_interval(),
+            farm_reporter.get_rig_name(),
+            if farm_reporter.is_signed() { "signed" } else { "unsigned" }
+        ),
+        None => log::info!("Farm reports: disabled"),
+    }
+    match &metrics_exporter {
+        Some(metrics_exporter) => log::info!("Metrics: every {:?} to {:?}", metrics_exporter.get_interval(), metrics_exporter.get_backend()),
+        None => log::info!("Metrics: disabled"),
+    }
+    log::info!("Stats: every {:?} to {:?} ({:?})", stats_config.get_interval(), stats_config.get_sinks(), stats_config.get_verbosity());
     log::info!("IP address: {ip_raw}");
     log::info!("Port: {port_raw}");
-    log::info!("Id: {id_raw}");
-    log::info!("Available cores: {}", num_cpus::get());
+    if let Some(pool_server) = &pool_server {
+        log::info!("Pool: {pool_server} (solo fallback after {:?})", pool_failover);
+    }
+    log::info!("Id: {id_raw} ({})", if keys.is_read_only() { "read-only, no seed held" } else { "seed unlocked from the keystore" });
+    log::info!("Available cores: {} (CPU quota: {})", num_cpus::get(), get_cpu_quota().map_or("none".to_string(), |quota| format!("{quota:.2}")));
     log::info!("Number of threads: {}", number_of_threads);
+    match detect_topology() {
+        Some(topology) => {
+            let (performance, efficiency) = placement.counts();
+            log::info!(
+                "Cores: {} P-cores, {} E-cores (policy: {core_policy}, {performance} threads on P-cores, {efficiency} on E-cores)",
+                topology.performance.len(),
+                topology.efficiency.len()
+            );
+        }
+        None if core_policy != CorePolicy::All => log::warn!("{ENV_CORE_POLICY}={core_policy} needs P-cores and E-cores, threads are not pinned"),
+        None => {}
+    }
+    log::info!("Keccak lanes: {}", keccak_lanes);
+    log::info!("Scheduler: {}", scheduler.name());
+    log::info!("Code path: {}", qiner::arch::code_path().name());
+    match qiner::arch::hardware_random_health() {
+        Ok(()) => log::info!("Random generator: {random_source}, from RDRAND"),
+        Err(reason) => log::info!("Random generator: {random_source}, from the OS generator, RDRAND {reason}"),
+    }
 
     // Convert ID to a byte array
     let id = match id_raw.as_bytes().try_into() {
@@ -112,118 +763,949 @@ async fn async_main() {
         return;
     }
 
+    // Fingerprint what the rig mines, to spot the rigs of a farm left on an old seed or ID
+    let config_fingerprint = config_fingerprint(&public_key, &settings);
+    log::info!("Config fingerprint: {config_fingerprint}");
+    stats_config.set_config_fingerprint(config_fingerprint.clone());
+
+    // Refuse to mine for an ID another instance on this machine already mines for, which would submit the same solutions twice
+    let _instance_lock = if settings.process.instance_lock {
+        match InstanceLock::acquire(InstanceLock::lock_file(&env::temp_dir(), &id_raw)) {
+            Ok(lock) => {
+                log::debug!("Holding the instance lock {}", lock.get_path().display());
+                Some(lock)
+            }
+            Err(err @ InstanceLockError::Held { .. }) => {
+                log::error!("Another Qiner already mines for this ID on this machine ({err}), set INSTANCE_LOCK=0 to run both anyway");
+                std::process::exit(DUPLICATE_INSTANCE_EXIT_CODE);
+            }
+            Err(err) => {
+                log::warn!("Failed to take the instance lock, a second instance for this ID will not be detected: {err}");
+                None
+            }
+        }
+    } else {
+        None
+    };
+
     // Initialize the miner with the public key and number of threads
-    let arc_miner = Arc::new(Miner::new(public_key, number_of_threads));
+    let settings = Arc::new(settings);
+    let arc_miner = Arc::new(Miner::new(public_key, &settings, placement, keccak_lanes, share_threshold, scheduler, algorithm));
+    let mut retry_queue = RetryQueue::new(solution_max_age);
+    retry_queue.set_max_in_memory(solution_max_in_memory);
+    let retry_queue = Arc::new(Mutex::new(retry_queue));
+
+    // Log panics, spilling unsent solutions before exiting
+    qiner::panic_hook::install(arc_miner.clone(), retry_queue.clone(), spill_file.clone(), panic_exit);
+
+    // Resubmit the solutions spilled by a previous run
+    match journal::take_spilled(&spill_file) {
+        Ok(nonces) if !nonces.is_empty() => {
+            log::info!("Loaded {} spilled solutions from {}", nonces.len(), spill_file.display());
+            arc_miner.found_nonce.lock().await.extend(nonces.into_iter().map(Solution::new));
+        }
+        Ok(_) => {}
+        Err(err) => log::error!("Failed to load spilled solutions from {}: {:?}", spill_file.display(), err),
+    }
+
     Miner::run(&arc_miner);
 
-    // Display task for monitoring mining progress
-    let sent_score_counter = Arc::new(tokio::sync::Mutex::new(0usize));
+    // Restart workers that exited or stalled
+    tokio::spawn(supervise_workers(arc_miner.clone(), stall_timeout));
+
+    // Totals of the submitted solutions, kept across restarts within the epoch, but not for simulations
+    let submission_stats_file = simulation.is_none().then(|| settings.process.state_dir.join(SUBMISSION_STATS_FILE));
+    let submission_stats = Arc::new(SubmissionStats::new(&random_seed, submission_stats_file));
+    tokio::spawn(save_submission_stats_task(submission_stats.clone()));
+    let net_stats = Arc::new(NetStats::default());
+    net_stats.get_bandwidth().set_max_bytes_per_hour(settings.submission.max_bytes_per_hour);
+
+    let addrs = settings.nodes.get_addrs();
+    let addr = addrs.join(", ");
+    let nodes = PeerBook::new(addrs, &settings.nodes.peer_filter, Some(settings.process.state_dir.join(PEER_SCORES_FILE)));
+    if !settings.nodes.peer_filter.is_empty() {
+        log::info!("Allowed nodes: {}", nodes.get_peers().iter().map(|peer| peer.addr.as_str()).collect::<Vec<_>>().join(", "));
+    }
+    if nodes.get_peers().is_empty() && pool_server.is_none() {
+        log::error!("No node is allowed, solutions cannot be submitted!");
+    }
+
+    // Print the session summary on exit and on SIGUSR1
+    let reporter = Arc::new(SessionReporter::new(
+        arc_miner.clone(),
+        net_stats.clone(),
+        submission_stats.clone(),
+        serde_json::json!({
+            "version": version,
+            "solution_threshold": solution_threshold,
+            "algorithm": algorithm.version,
+            "number_of_neurons": settings.algorithm_params.number_of_neurons,
+            "mining_data_length": settings.algorithm_params.mining_data_length,
+            "solution_max_age_secs": solution_max_age.as_secs(),
+            "solution_max_in_memory": solution_max_in_memory,
+            "number_of_threads": number_of_threads,
+            "core_policy": core_policy.to_string(),
+            "keccak_lanes": keccak_lanes,
+            "scheduler": scheduler.name(),
+            "random_source": random_source.name(),
+            "code_path": qiner::arch::code_path().name(),
+            "server": addr,
+            "pool": pool_server,
+            "id": id_raw,
+            "worker_name": qiner::rig::get_worker_name(),
+            "config_fingerprint": config_fingerprint,
+        }),
+        settings.process.summary_file.clone(),
+    ));
+    tokio::spawn(signal_task(reporter.clone(), arc_miner.clone(), retry_queue.clone(), spill_file.clone()));
+
+    // Report what the mock node received, to follow the solutions through the pipeline
+    if let Some(node) = simulation {
+        tokio::spawn(simulation_task(node));
+    }
+
+    // Post the stats of the rig to the farm dashboard
+    if let Some(farm_reporter) = farm_reporter {
+        tokio::spawn(farm_report_task(farm_reporter, reporter.clone(), id_raw.clone()));
+    }
+
+    // Push the metrics of the session to statsd or graphite
+    if let Some(metrics_exporter) = metrics_exporter {
+        tokio::spawn(metrics_task(metrics_exporter, reporter.clone()));
+    }
+
+    // Report health to container orchestrators, and exit once unhealthy if asked to
+    let health_monitor = Arc::new(HealthMonitor::new(arc_miner.clone(), net_stats.clone(), settings.process.health_stall_timeout, settings.process.health_connect_timeout));
+    if let Some(port) = settings.process.health_port {
+        tokio::spawn(serve_health(health_monitor.clone(), port));
+    }
+    if cli.health_exit_on_stall {
+        let reporter = reporter.clone();
+        let arc_miner = arc_miner.clone();
+        let retry_queue = retry_queue.clone();
+        let spill_file = spill_file.clone();
+        tokio::spawn(async move {
+            let report = wait_unhealthy(health_monitor).await;
+            log::error!("Miner is unhealthy, exiting: {}", report);
+            shutdown(&reporter, &arc_miner, &retry_queue, &spill_file, UNHEALTHY_EXIT_CODE).await;
+        });
+    }
+
+    // Stop threads while the CPU is too hot
+    if let Some(throttle) = ThermalThrottle::from_env() {
+        log::info!("Thermal limit: {:.1} °C", throttle.get_limit());
+        tokio::spawn(thermal_throttle_task(arc_miner.clone(), throttle));
+    }
 
-    // Launch the display information task
-    let display_info_future = display_info_task(arc_miner.clone(), sent_score_counter.clone());
+    // Alert on a sustained drop of the hashrate or the solution rate, and exit on it if asked to
+    let alert_config = AlertConfig::from_env();
+    if alert_config.is_enabled() {
+        let reporter = reporter.clone();
+        let arc_miner = arc_miner.clone();
+        let retry_queue = retry_queue.clone();
+        let notifier = notifier.clone();
+        let spill_file = spill_file.clone();
+        let is_exit = cli.exit_on_degradation;
+        tokio::spawn(async move {
+            let degradations = watch_degradation(arc_miner.clone(), alert_config, notifier, is_exit).await;
+            log::error!("Mining is degraded, exiting: {} limit(s) not met", degradations.len());
+            shutdown(&reporter, &arc_miner, &retry_queue, &spill_file, DEGRADED_EXIT_CODE).await;
+        });
+    } else if cli.exit_on_degradation {
+        log::warn!("--exit-on-degradation is ignored, every ALERT_* limit is disabled");
+    }
+
+    // Tell systemd the miner is running and keep its watchdog fed while mining progresses
+    #[cfg(unix)]
+    {
+        qiner::systemd::notify_ready();
+        if let Some(interval) = qiner::systemd::watchdog_interval() {
+            log::info!("systemd watchdog interval: {:?}", interval);
+            tokio::spawn(qiner::systemd::watchdog_task(arc_miner.clone(), interval));
+        }
+    }
+
+    // Deliver shares to the share server
+    let shares_sent = Arc::new(AtomicUsize::new(0));
+    if share_threshold.is_some() {
+        tokio::spawn(send_shares_task(arc_miner.clone(), share_server, socks.clone(), settings.clone(), public_key, shares_sent.clone(), net_stats.clone()));
+    }
+
+    // Launch the stats task, or the dashboard in TUI mode
+    let stats_future = {
+        let arc_miner = arc_miner.clone();
+        let submission_stats = submission_stats.clone();
+        let net_stats = net_stats.clone();
+        let notifier = notifier.clone();
+        #[cfg(feature = "tui")]
+        let addr = addr.clone();
+        #[cfg(feature = "tui")]
+        let reporter = reporter.clone();
+        #[cfg(feature = "tui")]
+        let own_protocol = settings.nodes.get_protocol();
+
+        async move {
+            #[cfg(feature = "tui")]
+            if cli.tui {
+                dashboard_task(arc_miner, submission_stats, net_stats, addr, own_protocol, reporter).await;
+                return;
+            }
+
+            let mut sinks = stats_config.create_sinks();
+            sinks.extend(notifier.and_then(HashrateAlertSink::new).map(|sink| Box::new(sink) as _));
+            stats_task(StatsSampler::new(arc_miner, submission_stats, shares_sent, net_stats), stats_config.get_interval(), sinks).await;
+        }
+    };
+
+    // Submit to the pool in priority, falling back to the node while the pool is unreachable
+    let targets = Arc::new(Mutex::new(TargetSelector::new(pool_server, nodes, pool_failover)));
+    tokio::spawn(probe_pool_task(targets.clone(), socks.clone()));
+
+    // Follow the epoch of the nodes, and compare the local clock to their ticks
+    net_stats.get_clock().set_max_skew(settings.nodes.max_clock_skew);
+    tokio::spawn(tick_info_task(targets.clone(), socks.clone(), net_stats.clone(), settings.clone()));
+    tokio::spawn(computors_task(targets.clone(), socks.clone(), net_stats.clone(), settings.clone(), public_key));
+
+    // Compare what the network credited the ID with to the solutions sent
+    if let Some(interval) = settings.nodes.balance_check_interval {
+        tokio::spawn(balance_task(targets.clone(), socks.clone(), net_stats.clone(), settings.clone(), public_key, submission_stats.clone(), interval));
+    }
+
+    // Apply the safe settings changed in the configuration file without restarting
+    if let Some(config_file) = config_file {
+        let arc_miner = arc_miner.clone();
+        let targets = targets.clone();
+        let settings = settings.clone();
+        tokio::spawn(watch_config_task(config_file, move |changes, value| apply_config_changes(changes, value, &settings, &arc_miner, &targets)));
+    }
 
     // Launch the TCP client task to send solutions to the server
-    let send_solution_future = send_solution_task(arc_miner.clone(), sent_score_counter.clone(), ip_raw, port_raw, public_key);
+    let send_solution_future = send_solution_task(arc_miner.clone(), submission_stats.clone(), net_stats.clone(), notifier, tracer, targets, settings.clone(), public_key, retry_queue, pacing, socks, spill_file.clone());
 
-    // Run the display and solution sending tasks concurrently
+    // Run the stats and solution sending tasks concurrently
     tokio::join!(
-        display_info_future,
+        stats_future,
         send_solution_future
     );
 
     println!("End");
 }
 
-/// Asynchronous task to display mining progress information
+/// Applies the reloadable settings changed in the configuration file, logging each of them
+///
+/// The settings are parsed again as on start; if any variable is invalid, nothing is applied.
 ///
 /// # Arguments
+/// * `changes` - The changed reloadable settings
+/// * `value` - Gets the current value of any variable
+/// * `current` - The settings Qiner started with, whose ID is kept if `ID` is not set
 /// * `arc_miner` - Shared reference to the Miner instance
-/// * `sent_score_counter` - Shared counter for sent scores
+/// * `targets` - The shared target selector, whose nodes are replaced when the peer settings change
+fn apply_config_changes(changes: &[ConfigChange], value: &dyn Fn(&str) -> Option<String>, current: &Settings, arc_miner: &Arc<Miner>, targets: &Mutex<TargetSelector>) {
+    let settings = match Settings::from_lookup(|name| value(name).or_else(|| (name == ENV_ID).then(|| current.id.clone()))) {
+        Ok(settings) => settings,
+        Err(errors) => {
+            log::warn!("Ignoring the changes of the configuration, it is invalid: {}", describe_errors(&errors));
+            return;
+        }
+    };
+
+    let mut are_peers_changed = false;
+    for change in changes {
+        match change.name.as_str() {
+            ENV_SOLUTION_THRESHOLD => {
+                log::info!("Solution threshold changed from {} to {}", arc_miner.get_solution_threshold(), settings.solution_threshold);
+                arc_miner.set_solution_threshold(settings.solution_threshold);
+            }
+            ENV_NUMBER_OF_THREADS => {
+                // Only the spawned threads can be activated, more need a restart
+                let threads = settings.threads.number_of_threads.unwrap_or_else(get_available_cpus);
+                if threads > arc_miner.get_num_threads() {
+                    log::warn!("{ENV_NUMBER_OF_THREADS}={threads} is above the {} spawned threads, restart Qiner to apply it", arc_miner.get_num_threads());
+                }
+                arc_miner.set_active_threads(threads);
+                log::info!("Active threads changed to {}", arc_miner.get_active_threads());
+            }
+            ENV_ALGORITHM if settings.algorithm.version != arc_miner.get_algorithm().version() => {
+                log::info!("Algorithm changed to v{} {}, restarting the mining threads", settings.algorithm.version, settings.algorithm.name);
+                Miner::set_algorithm(arc_miner, settings.algorithm);
+            }
+            ENV_ALGORITHM => {}
+            ENV_RUST_LOG => {
+                // Logged first, the new filters may hide it
+                log::info!("Log filters changed to {}", change.new.as_deref().unwrap_or("unset"));
+                qiner::logger::set_filters(&value(ENV_RUST_LOG).unwrap_or_default());
+            }
+            _ => are_peers_changed = true,
+        }
+    }
+
+    if are_peers_changed {
+        let nodes = PeerBook::new(settings.nodes.get_addrs(), &settings.nodes.peer_filter, Some(settings.process.state_dir.join(PEER_SCORES_FILE)));
+        let node_addrs = nodes.get_peers().iter().map(|peer| peer.addr.as_str()).collect::<Vec<_>>().join(", ");
+        if node_addrs.is_empty() {
+            log::warn!("Nodes changed, no node is allowed");
+        } else {
+            log::info!("Nodes changed to {node_addrs}");
+        }
+        targets.lock().unwrap().set_nodes(nodes);
+    }
+}
+
+/// Asynchronous task printing the session summary on SIGUSR1, and before exiting on Ctrl-C or SIGTERM
+///
+/// Before exiting, the solutions not sent yet are spilled so the next start resubmits them.
+///
+/// # Arguments
+/// * `reporter` - Session reporter producing the summary
+/// * `arc_miner` - Shared reference to the Miner instance
+/// * `retry_queue` - Shared queue of solutions waiting to be submitted
+/// * `spill_file` - File unsent solutions are spilled to
+async fn signal_task(reporter: Arc<SessionReporter>, arc_miner: Arc<Miner>, retry_queue: Arc<Mutex<RetryQueue>>, spill_file: PathBuf) {
+    #[cfg(unix)]
+    {
+        use tokio::signal::unix::{signal, SignalKind};
+
+        let (mut terminate, mut user_defined) = match (signal(SignalKind::terminate()), signal(SignalKind::user_defined1())) {
+            (Ok(terminate), Ok(user_defined)) => (terminate, user_defined),
+            _ => {
+                log::error!("Failed to install the signal handlers");
+                return;
+            }
+        };
+
+        loop {
+            tokio::select! {
+                _ = tokio::signal::ctrl_c() => break,
+                _ = terminate.recv() => break,
+                _ = user_defined.recv() => reporter.report().await,
+            }
+        }
+    }
+
+    #[cfg(not(unix))]
+    if let Err(err) = tokio::signal::ctrl_c().await {
+        log::error!("Failed to install the Ctrl-C handler: {:?}", err);
+        return;
+    }
+
+    shutdown(&reporter, &arc_miner, &retry_queue, &spill_file, 0).await;
+}
+
+/// Spills the solutions not sent yet, saves the submission totals, prints the session summary and exits the process
+///
+/// # Arguments
+/// * `reporter` - Session reporter producing the summary
+/// * `arc_miner` - Shared reference to the Miner instance
+/// * `retry_queue` - Shared queue of solutions waiting to be submitted
+/// * `spill_file` - File unsent solutions are spilled to
+/// * `exit_code` - Exit code of the process
+async fn shutdown(reporter: &SessionReporter, arc_miner: &Miner, retry_queue: &Mutex<RetryQueue>, spill_file: &Path, exit_code: i32) -> ! {
+    #[cfg(unix)]
+    qiner::systemd::notify_stopping();
+
+    // Flush the solutions not sent yet
+    let mut nonces = arc_miner.found_nonce.lock().await.drain(..).map(|solution| solution.nonce).collect::<Vec<_>>();
+    nonces.extend(retry_queue.lock().unwrap().nonces());
+    if !nonces.is_empty() {
+        match journal::spill(spill_file, &nonces) {
+            Ok(_) => log::info!("Spilled {} unsent solutions to {}", nonces.len(), spill_file.display()),
+            Err(err) => log::error!("Failed to spill {} unsent solutions: {:?}", nonces.len(), err),
+        }
+    }
+
+    reporter.get_submission_stats().save();
+    reporter.report().await;
+    std::process::exit(exit_code);
+}
+
+/// Interval between two checks for solutions to submit
+const SUBMISSION_POLL_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Submits recorded solutions to a node, with the packets and the pacing of the miner.
+///
+/// # Arguments
+/// * `file` - The file of the solutions, see `parse_solutions` for the formats
+/// * `peer` - The node, the first of `SERVER_IP`/`SERVER_PORT` if `None`
+/// * `nodes` - The settings of the nodes, giving the protocol version of the packets
+/// * `pacing` - The limits on how fast the solutions are sent
 ///
 /// # Returns
-/// An async future
-async fn display_info_task(arc_miner: Arc<Miner>, sent_score_counter: Arc<tokio::sync::Mutex<usize>>) -> impl std::future::Future<Output = ()> {
-    let mut prev_iter_value: usize = 0;
+/// The exit code of the process, `0` only if every solution was written to the node.
+async fn replay(file: &Path, peer: Option<String>, nodes: &NodeSettings, pacing: &SubmissionPacing) -> i32 {
+    let content = match std::fs::read_to_string(file) {
+        Ok(content) => content,
+        Err(err) => {
+            log::error!("Failed to read {}: {:?}", file.display(), err);
+            return 1;
+        }
+    };
+    let public_key = env::var(ENV_ID).ok().and_then(|id| parse_id(&id));
+    let mut solutions = match parse_solutions(&content, public_key.as_ref()) {
+        Ok(solutions) => solutions,
+        Err(err) => {
+            log::error!("Invalid solutions in {}: {err}", file.display());
+            return 1;
+        }
+    };
+    let mut seen = std::collections::HashSet::new();
+    solutions.retain(|solution| seen.insert(*solution));
 
-    loop {
-        let score = arc_miner.get_score();
-        let sent_scores = *sent_score_counter.lock().await;
-        let it_per_sec = arc_miner.get_iter_counter() - prev_iter_value;
-        prev_iter_value = arc_miner.get_iter_counter();
+    let Some(peer) = peer.or_else(|| nodes.get_addrs().into_iter().next()) else {
+        log::error!("No node to replay to, set --peer or {ENV_SERVER_IP}");
+        return 1;
+    };
+    let protocol = nodes.get_peer_protocol(&peer);
+    let socks = SocksProxy::from_env();
+    let net_stats = NetStats::default();
+    log::info!("Replaying {} solutions from {} to {peer}", solutions.len(), file.display());
+
+    let mut sent = 0;
+    'connections: for (connection_idx, connection) in solutions.chunks(pacing.max_packets_per_connection.unwrap_or(usize::MAX)).enumerate() {
+        if connection_idx > 0 {
+            tokio::time::sleep(pacing.min_connect_interval).await;
+        }
+        let mut stream = match socks::connect(socks.as_ref(), &peer).await {
+            Ok(stream) => stream,
+            Err(err) => {
+                log::error!("Failed to connect to {peer}: {:?}", err);
+                break;
+            }
+        };
+
+        let mut remaining = connection;
+        for (batch_idx, batch_size) in pacing.batches(connection.len()).into_iter().enumerate() {
+            // Stay under the packets per second limit
+            if batch_idx > 0 {
+                tokio::time::sleep(Duration::from_secs(1)).await;
+            }
+            let (batch, rest) = remaining.split_at(batch_size);
+            remaining = rest;
+
+            let data = batch.iter().flat_map(|(public_key, nonce)| Packet::solutions_to_bytes(protocol, public_key, [nonce])).collect::<Vec<u8>>();
+            if let Err(err) = stream.write_all(&data).await {
+                log::error!("Failed to send data: {:?}", err);
+                break 'connections;
+            }
+            capture::record_sent(&peer, &data);
+            sent += batch.len();
+        }
+        check_node_protocol(&mut stream, &net_stats, &peer, protocol).await;
+    }
+
+    log::info!("Replayed {sent} of {} solutions to {peer}", solutions.len());
+    if sent == solutions.len() { 0 } else { 1 }
+}
+
+/// Signs a transfer with the seed of the keystore, or read from the standard input, and sends it to
+/// the nodes, scheduled again for later ticks until it is executed.
+///
+/// # Arguments
+/// * `args` - The destination, amount and tick of the transfer, and the nodes it is sent to
+/// * `passphrase` - The passphrase of the environment Qiner was started in, `None` to ask for it
+/// * `nodes` - The settings of the nodes, the transfer is sent to those of `SERVER_IP` without `--peer`
+/// * `keystore_file` - The keystore the seed is unlocked from
+///
+/// # Returns
+/// The exit code: `0` once the transaction is executed, `1` otherwise.
+async fn send(args: SendArgs, passphrase: Option<&str>, nodes: &NodeSettings, keystore_file: &Path) -> i32 {
+    let Some(destination_public_key) = parse_id(&args.to) else {
+        log::error!("Invalid destination {}: expected 60 uppercase letters with a valid checksum", args.to);
+        return 1;
+    };
+    let peers = args.peer.map_or_else(|| nodes.get_addrs(), |peer| vec![peer]);
+    if peers.is_empty() {
+        log::error!("No node to send to, set --peer or {ENV_SERVER_IP}");
+        return 1;
+    }
+
+    let keys = match unlock_signing_keys(passphrase, keystore_file) {
+        Ok(keys) => keys,
+        Err(err) => {
+            log::error!("No identity to sign with: {err}");
+            return 1;
+        }
+    };
+    let identity = match keys.get_signing_identity("A transfer") {
+        Ok(identity) => identity,
+        Err(err) => {
+            log::error!("{err}");
+            return 1;
+        }
+    };
+
+    let peers = peers.into_iter().map(|peer| {
+        let protocol = nodes.get_peer_protocol(&peer);
+        (peer, protocol)
+    }).collect();
+    let sender = TransactionSender::new(peers, SocksProxy::from_env(), args.tick_offset, args.attempts);
+    match sender.send_transfer(identity, destination_public_key, args.amount, args.tick).await {
+        Ok(id) => {
+            println!("{id}");
+            0
+        }
+        Err(err) => {
+            log::error!("Failed to send {} qu to {}: {err}", args.amount, args.to.trim());
+            1
+        }
+    }
+}
+
+/// Time to wait for the first message of a node after submitting to it.
+const NODE_MESSAGE_TIMEOUT: Duration = Duration::from_millis(500);
 
-        log::info!("{} scores | sent scores {} | {} it/s", score, sent_scores, it_per_sec);
+/// Reads the header of the first message a node sends and compares its protocol version to ours.
+///
+/// Nodes drop packets of another protocol version without a word, so a node on a newer
+/// protocol than `VERSION` claims is reported loudly, once per change of its version.
+/// The payload of the message is left unread.
+///
+/// # Arguments
+/// * `stream` - The connection to the node, after the solutions were written
+/// * `net_stats` - Shared network counters, recording the protocol version of the node
+/// * `addr` - The address of the node, for the logs
+/// * `own_protocol` - The protocol version solutions are sent with
+///
+/// # Returns
+/// The header of the message, the closest thing to an acknowledgement of the solutions, `None` if the node sent none.
+async fn check_node_protocol(stream: &mut TcpStream, net_stats: &NetStats, addr: &str, own_protocol: Protocol) -> Option<RequestResponseHeader> {
+    let mut header = [0u8; size_of::<RequestResponseHeader>()];
+    match tokio::time::timeout(NODE_MESSAGE_TIMEOUT, stream.read_exact(&mut header)).await {
+        Ok(Ok(_)) => {}
+        Ok(Err(err)) => {
+            log::debug!("No message from {addr}: {:?}", err);
+            return None;
+        }
+        Err(_) => {
+            log::debug!("No message from {addr} within {:?}", NODE_MESSAGE_TIMEOUT);
+            return None;
+        }
+    }
+    capture::record_received(addr, &header);
+    let header = RequestResponseHeader::from_bytes(&header)?;
 
-        tokio::time::sleep(tokio::time::Duration::from_secs(1)).await;
+    let node_protocol = header.get_protocol();
+    let previous = net_stats.record_node_protocol(node_protocol, own_protocol);
+    if previous == Some(node_protocol) {
+        return Some(header);
+    }
+    if node_protocol > own_protocol {
+        log::error!(
+            "Node {addr} runs protocol {node_protocol} but VERSION gives protocol {own_protocol}: the node drops every solution sent, update VERSION or PEER_PROTOCOLS"
+        );
+    } else if node_protocol < own_protocol {
+        log::warn!("Node {addr} runs protocol {node_protocol}, older than protocol {own_protocol} given by VERSION");
+    } else {
+        log::info!("Node {addr} runs protocol {node_protocol}, matching VERSION");
     }
+    Some(header)
+}
+
+/// Reads the public peers a node sent, after the header of their message.
+///
+/// # Arguments
+/// * `stream` - The connection to the node
+/// * `header` - The header of the message, read by `check_node_protocol`
+/// * `addr` - The address of the node, whose port the peers share
+///
+/// # Returns
+/// The addresses of the peers, empty if the message is not a public peers exchange or could not be read.
+async fn read_public_peers(stream: &mut TcpStream, header: &RequestResponseHeader, addr: &str) -> Vec<String> {
+    let mut payload = [0u8; NUMBER_OF_EXCHANGED_PEERS * 4];
+    if header.get_type() != EXCHANGE_PUBLIC_PEERS || header.get_size() != size_of::<RequestResponseHeader>() + payload.len() {
+        return Vec::new();
+    }
+    match tokio::time::timeout(NODE_MESSAGE_TIMEOUT, stream.read_exact(&mut payload)).await {
+        Ok(Ok(_)) => parse_public_peers(&payload, addr.rsplit_once(':').map_or("", |(_, port)| port)),
+        _ => Vec::new(),
+    }
+}
+
+/// Asynchronous task running the TUI dashboard until the user quits, then exiting the process
+///
+/// Logging is turned off while the dashboard owns the terminal.
+///
+/// # Arguments
+/// * `arc_miner` - Shared reference to the Miner instance
+/// * `submission_stats` - Shared totals of the submitted solutions of the epoch
+/// * `net_stats` - Shared network counters
+/// * `addr` - Address of the server, as `ip:port`
+/// * `own_protocol` - The protocol version solutions are sent with
+/// * `reporter` - Session reporter printing the summary on exit
+#[cfg(feature = "tui")]
+async fn dashboard_task(
+    arc_miner: Arc<Miner>,
+    submission_stats: Arc<SubmissionStats>,
+    net_stats: Arc<NetStats>,
+    addr: String,
+    own_protocol: Protocol,
+    reporter: Arc<SessionReporter>,
+) {
+    qiner::logger::set_muted(true);
+
+    let dashboard = qiner::tui::Dashboard::new(arc_miner, net_stats, submission_stats, addr, own_protocol);
+    let result = tokio::task::spawn_blocking(move || dashboard.run()).await;
+
+    qiner::logger::set_muted(false);
+    if let Ok(Err(err)) = result {
+        log::error!("Dashboard: {:?}", err);
+    }
+
+    reporter.report().await;
+    std::process::exit(0);
 }
 
 /// Asynchronous task to send mining solutions to the server
 ///
+/// Found solutions are moved into a retry queue; failed submissions are retried with
+/// a per-solution exponential backoff until they exceed the maximum age.
+///
 /// # Arguments
 /// * `arc_miner` - Shared reference to the Miner instance
-/// * `sent_score_counter` - Shared counter for sent scores
-/// * `ip_raw` - IP address of the server
-/// * `port_raw` - Port of the server
+/// * `submission_stats` - Shared totals of the submitted solutions of the epoch
+/// * `net_stats` - Shared network counters
+/// * `notifier` - Optional notifier for found/sent solutions and connection failures
+/// * `tracer` - Optional tracer of the stages of every solution
+/// * `targets` - Selects the pool or the node as the address solutions are sent to
+/// * `settings` - The settings, giving the protocol version of the packets
 /// * `public_key` - Public key used for mining
-///
-/// # Returns
-/// An async future
+/// * `retry_queue` - Shared queue of solutions waiting to be submitted
+/// * `pacing` - Limits on connections and packets per second
+/// * `socks` - The SOCKS proxy solutions are sent through, `None` to connect directly
+/// * `spill_file` - File the solutions over the in-memory cap of the queue wait in
+#[allow(clippy::too_many_arguments)]
 async fn send_solution_task(
     arc_miner: Arc<Miner>,
-    sent_score_counter: Arc<tokio::sync::Mutex<usize>>,
-    ip_raw: String,
-    port_raw: String,
-    public_key: PublicKey64
-) -> impl std::future::Future<Output = ()> {
+    submission_stats: Arc<SubmissionStats>,
+    net_stats: Arc<NetStats>,
+    notifier: Option<Notifier>,
+    tracer: Option<SolutionTracer>,
+    targets: Arc<Mutex<TargetSelector>>,
+    settings: Arc<Settings>,
+    public_key: PublicKey64,
+    retry_queue: Arc<Mutex<RetryQueue>>,
+    pacing: SubmissionPacing,
+    socks: Option<SocksProxy>,
+    spill_file: PathBuf,
+) {
+    let mut failing_since: Option<Instant> = None;
+    // Solutions over the in-memory cap spilled since the start, until they are back in the queue
+    let mut overflowed = 0;
+    let mut is_failure_notified = false;
+    let mut last_connect_at: Option<Instant> = None;
+    // Shared with the connections of a round, which run concurrently
+    let tracer = Arc::new(Mutex::new(tracer));
+    #[cfg(feature = "quic")]
+    let mut quic_sender = QuicSender::new();
+    // Packets are built as soon as the solutions are found, sending them only writes their bytes
+    let packets = PacketFactory::spawn(settings.nodes.get_protocol(), public_key, net_stats.clone());
+
     loop {
-        tokio::time::sleep(tokio::time::Duration::from_secs(1)).await;
-        
-        let is_nonce_exists = !arc_miner.found_nonce.lock().await.is_empty();
+        tokio::time::sleep(SUBMISSION_POLL_INTERVAL).await;
+
+        // Move freshly found solutions into the retry queue
+        let found = arc_miner.found_nonce.lock().await.drain(..).collect::<Vec<_>>();
+        if !found.is_empty() {
+            submission_stats.record_found(found.len());
+            if let Some(notifier) = &notifier {
+                notifier.notify(NotifyEvent::SolutionFound(found.len()));
+            }
+            if let Some(tracer) = tracer.lock().unwrap().as_mut() {
+                tracer.found(&found);
+            }
+            packets.prebuild(found.iter().map(|solution| solution.nonce));
+            let duplicates = retry_queue.lock().unwrap().push_new(found);
+            if duplicates > 0 {
+                log::warn!("Dropped {duplicates} duplicate solutions");
+            }
+        }
 
-        if is_nonce_exists {
-            let addr = format!("{ip_raw}:{port_raw}");
+        // Keep the queue within its cap while the nodes are unreachable, the newest solutions waiting on disk
+        let overflow = retry_queue.lock().unwrap().take_overflow();
+        if !overflow.is_empty() {
+            let nonces = overflow.iter().map(|solution| solution.nonce).collect::<Vec<_>>();
+            match journal::spill(&spill_file, &nonces) {
+                Ok(_) => {
+                    overflowed += nonces.len();
+                    log::warn!("{} solutions over the in-memory cap spilled to {}, {overflowed} waiting there", nonces.len(), spill_file.display());
+                }
+                Err(err) => {
+                    log::error!("Failed to spill solutions to {}, keeping them in memory: {:?}", spill_file.display(), err);
+                    retry_queue.lock().unwrap().push_new(overflow);
+                }
+            }
+        }
+
+        // Bring the spilled solutions back as the queue drains, once the nodes are reachable again
+        let room = retry_queue.lock().unwrap().get_room();
+        if overflowed > 0 && room > 0 && failing_since.is_none() {
+            match journal::take_spilled_at_most(&spill_file, room) {
+                Ok(nonces) => {
+                    overflowed = overflowed.saturating_sub(nonces.len());
+                    log::info!("{} spilled solutions back in the queue, {overflowed} still waiting in {}", nonces.len(), spill_file.display());
+                    packets.prebuild(nonces.iter().copied());
+                    retry_queue.lock().unwrap().push_new(nonces.into_iter().map(Solution::new));
+                }
+                Err(err) => log::error!("Failed to read the spilled solutions back from {}: {:?}", spill_file.display(), err),
+            }
+        }
+
+        // Respect the minimum delay between two connections
+        if last_connect_at.is_some_and(|connected_at| connected_at.elapsed() < pacing.min_connect_interval) {
+            continue;
+        }
 
+        // The queue is only locked briefly and never across an await, so the panic hook can spill it
+        let (pruned, ready) = {
+            let mut queue = retry_queue.lock().unwrap();
+            let limit = pacing.packets_per_round();
+            if let Some(epoch) = net_stats.get_epoch() {
+                let seed_epoch = queue.get_seed_epoch();
+                let previous = queue.set_epoch(epoch);
+                if previous.is_some_and(|previous| previous != epoch) {
+                    arc_miner.reset_epoch_counters();
+                    log::info!("Epoch {epoch} started, the epoch counters start over");
+                }
+                if previous != Some(epoch) && seed_epoch.is_some_and(|seed_epoch| seed_epoch < epoch) {
+                    log::error!("Nodes moved to epoch {epoch} but the random seed is from epoch {}: update RANDOM_SEED, its solutions are dropped", seed_epoch.unwrap());
+                }
+            }
+            let stale = queue.prune_past_epochs();
+            if stale > 0 {
+                submission_stats.record_expired(stale);
+                log::warn!("Dropped {stale} solutions mined in a past epoch");
+            }
+            let pruned = queue.prune_expired(Instant::now());
+            let ready = queue.take_ready_at_most(Instant::now(), limit);
+            let waiting = queue.nonces().chain(ready.iter().map(|pending| pending.solution.nonce)).collect::<HashSet<_>>();
+            packets.retain(|nonce| waiting.contains(nonce));
+            (pruned, ready)
+        };
+        if pruned > 0 {
+            submission_stats.record_failed(pruned);
+            let max_age = retry_queue.lock().unwrap().get_max_age();
+            log::warn!("Dropped {pruned} solutions older than {:?}", max_age);
+            if let Some(tracer) = tracer.lock().unwrap().as_mut() {
+                tracer.prune(max_age);
+            }
+        }
+
+        if !ready.is_empty() {
+            last_connect_at = Some(Instant::now());
+            let addr = targets.lock().unwrap().current().to_string();
+            let protocol = settings.nodes.get_peer_protocol(&addr);
             log::info!("Connecting to {addr}");
-            let mut stream_result = TcpStream::connect(addr).await;
 
-            match stream_result.as_mut() {
-                Err(err) => {
-                    log::error!("Failed to connect: {:?}", err);
+            // QUIC relays get every ready solution on a single stream, without the pacing meant for nodes
+            #[cfg(feature = "quic")]
+            if let Some(quic_addr) = addr.strip_prefix(QUIC_SCHEME) {
+                if socks.is_some() {
+                    log::error!("QUIC cannot go through the SOCKS proxy, not sending to {addr}");
+                    retry_queue.lock().unwrap().reschedule(ready, Instant::now());
+                    continue;
+                }
+                net_stats.record_connect_attempt();
+                let data_for_send = packets.to_bytes(ready.iter().map(|pending| &pending.solution.nonce), protocol);
+                let write_started_at = Instant::now();
+                let send_result = quic_sender.send(quic_addr, &data_for_send).await;
+                let mut targets = targets.lock().unwrap();
+                targets.record_result(&addr, send_result.is_ok(), Instant::now());
+                targets.record_write(&addr, write_started_at.elapsed(), send_result.is_ok());
+                match send_result {
+                    Ok(_) => {
+                        capture::record_sent(&addr, &data_for_send);
+                        log::info!("QUIC: sent {} packets to {quic_addr} in {:?}", ready.len(), write_started_at.elapsed());
+                        net_stats.record_connect_success();
+                        net_stats.record_sent(data_for_send.len(), ready.len());
+                        ready.iter().for_each(|pending| net_stats.record_latency(pending.solution.found_at.elapsed()));
+                        submission_stats.record_sent(ready.len());
+                        retry_queue.lock().unwrap().mark_submitted(&ready);
+                        failing_since = None;
+                    }
+                    Err(err) => {
+                        log::error!("QUIC: failed to send to {quic_addr}: {:?}", err);
+                        net_stats.record_connect_failure();
+                        failing_since.get_or_insert_with(Instant::now);
+                        retry_queue.lock().unwrap().reschedule(ready, Instant::now());
+                    }
+                }
+                continue;
+            }
+
+            // A backlog is split across several connections, each paced on its own
+            let mut remaining = ready;
+            let mut connections = tokio::task::JoinSet::new();
+            for share_size in pacing.shares(remaining.len()) {
+                let share = remaining.drain(..share_size).collect::<Vec<_>>();
+                connections.spawn(send_over_connection(
+                    share, addr.clone(), protocol, socks.clone(), pacing, packets.clone(), targets.clone(), net_stats.clone(),
+                    submission_stats.clone(), retry_queue.clone(), notifier.clone(), tracer.clone(),
+                ));
+            }
+            let mut outcomes = Vec::new();
+            while let Some(outcome) = connections.join_next().await {
+                match outcome {
+                    Ok(outcome) => outcomes.push(outcome),
+                    Err(err) => log::error!("Connection task failed: {:?}", err),
                 }
-                Ok(stream) => {
-                    // Wait for the socket to be writable
-                    if let Err(err) = stream.writable().await {
-                        log::error!("Writable: {:?}", err);
-                    } else {
-                        // Grab data
-                        let data_for_send = {
-                            let found_nonce = arc_miner.found_nonce.lock().await;
-                            found_nonce.iter().map(|nonce| {
-                                let packet = Packet::new(&BROADCAST_MESSAGE, &public_key, nonce);
-                                unsafe { transmute::<Packet, [u8; size_of::<Packet>()]>(packet) }
-                            }).collect::<Vec<[u8; size_of::<Packet>()]>>().into_iter().flatten().collect::<Vec<u8>>()
-                        };
-
-                        let packet_num = data_for_send.len() / size_of::<Packet>();
-                        log::info!("TCP: will be sent {packet_num} packets({} Bytes)", data_for_send.len());
-
-                        // Send data
-                        log::info!("TCP: send data...");
-                        let write_result = stream.write_all(data_for_send.as_slice()).await;
-                        if let Err(err) = write_result {
-                            log::error!("Failed to send data: {:?}", err);
-                        } else {
-                            let mut lock = sent_score_counter.lock().await;
-                            *lock += packet_num;
-                        }
-
-                        // Deleting nonce that have been sent
-                        arc_miner.found_nonce.lock().await.drain(0..packet_num);
+            }
+            let (connected, written, answered) = ConnectionOutcome::aggregate(&outcomes);
+            if outcomes.len() > 1 {
+                log::info!("TCP: {connected} of {} connections to {addr}, {written} packets written, {answered} connections answered", outcomes.len());
+            }
+
+            if connected > 0 {
+                failing_since = None;
+                is_failure_notified = false;
+            } else {
+                // Notify once the connection has been failing for long enough
+                let failing_since = *failing_since.get_or_insert_with(Instant::now);
+                if let Some(notifier) = &notifier {
+                    if !is_failure_notified && failing_since.elapsed() >= notifier.get_connection_failure_after() {
+                        notifier.notify(NotifyEvent::ConnectionFailing(failing_since.elapsed()));
+                        is_failure_notified = true;
                     }
                 }
             }
+
+            let waiting = retry_queue.lock().unwrap().len();
+            if waiting > 0 {
+                log::info!("{waiting} solutions waiting for retry");
+            }
+        }
+    }
+}
+
+/// Sends a share of the ready solutions over one connection to the node: connects, writes them in
+/// batches paced per second, then waits for the node to answer. The solutions not written are
+/// rescheduled.
+///
+/// # Arguments
+/// * `share` - The solutions sent over this connection
+/// * `addr` - The node
+/// * `protocol` - The protocol version of the packets to the node
+/// * `socks` - The SOCKS proxy the node is reached through, `None` to connect directly
+/// * `pacing` - The pacing of the packets over the connection
+/// * `packets` - The prebuilt packets of the solutions
+/// * `targets` - The shared target selector, recording the results of the node
+/// * `net_stats` - Shared network counters
+/// * `submission_stats` - The totals of the epoch
+/// * `retry_queue` - The queue the solutions not written go back to
+/// * `notifier` - Notifies the solutions sent, if enabled
+/// * `tracer` - Traces the stages of the solutions, if enabled
+///
+/// # Returns
+/// Whether the connection was established, the packets written and whether the node answered after them.
+#[allow(clippy::too_many_arguments)]
+async fn send_over_connection(
+    share: Vec<PendingSolution>,
+    addr: String,
+    protocol: Protocol,
+    socks: Option<SocksProxy>,
+    pacing: SubmissionPacing,
+    packets: Arc<PacketFactory>,
+    targets: Arc<Mutex<TargetSelector>>,
+    net_stats: Arc<NetStats>,
+    submission_stats: Arc<SubmissionStats>,
+    retry_queue: Arc<Mutex<RetryQueue>>,
+    notifier: Option<Notifier>,
+    tracer: Arc<Mutex<Option<SolutionTracer>>>,
+) -> ConnectionOutcome {
+    net_stats.record_connect_attempt();
+    let stream_result = socks::connect(socks.as_ref(), &addr).await;
+    targets.lock().unwrap().record_result(&addr, stream_result.is_ok(), Instant::now());
+
+    let mut stream = match stream_result {
+        Ok(stream) => stream,
+        Err(err) => {
+            net_stats.record_connect_failure();
+            log::error!("Failed to connect: {:?}", err);
+            if let Some(tracer) = tracer.lock().unwrap().as_mut() {
+                tracer.record_failure(share.iter().map(|pending| &pending.solution.nonce), SolutionStage::Connected, &err.to_string());
+            }
+            retry_queue.lock().unwrap().reschedule(share, Instant::now());
+            return ConnectionOutcome::default();
+        }
+    };
+    net_stats.record_connect_success();
+    if let Some(tracer) = tracer.lock().unwrap().as_mut() {
+        tracer.record(share.iter().map(|pending| &pending.solution.nonce), SolutionStage::Connected, &[("server.address", addr.clone())]);
+    }
+    let mut outcome = ConnectionOutcome { is_connected: true, ..ConnectionOutcome::default() };
+
+    // Wait for the socket to be writable
+    if let Err(err) = stream.writable().await {
+        log::error!("Writable: {:?}", err);
+        if let Some(tracer) = tracer.lock().unwrap().as_mut() {
+            tracer.record_failure(share.iter().map(|pending| &pending.solution.nonce), SolutionStage::Written, &err.to_string());
+        }
+        retry_queue.lock().unwrap().reschedule(share, Instant::now());
+        return outcome;
+    }
+
+    let mut remaining = share;
+    let mut written = Vec::new();
+    for (batch_idx, batch_size) in pacing.batches(remaining.len()).into_iter().enumerate() {
+        // Stay under the packets per second limit
+        if batch_idx > 0 {
+            tokio::time::sleep(Duration::from_secs(1)).await;
+        }
+        let batch = remaining.drain(..batch_size).collect::<Vec<_>>();
+
+        // Grab data
+        let data_for_send = packets.to_bytes(batch.iter().map(|pending| &pending.solution.nonce), protocol);
+        if let Some(tracer) = tracer.lock().unwrap().as_mut() {
+            tracer.record(batch.iter().map(|pending| &pending.solution.nonce), SolutionStage::Serialized, &[("qubic.batch_size", batch.len().to_string())]);
+        }
+
+        let packet_num = batch.len();
+        log::info!("TCP: will be sent {packet_num} packets({} Bytes)", data_for_send.len());
+
+        // Send data
+        log::info!("TCP: send data...");
+        let write_started_at = Instant::now();
+        let write_result = stream.write_all(data_for_send.as_slice()).await;
+        targets.lock().unwrap().record_write(&addr, write_started_at.elapsed(), write_result.is_ok());
+        if let Err(err) = write_result {
+            log::error!("Failed to send data: {:?}", err);
+            if let Some(tracer) = tracer.lock().unwrap().as_mut() {
+                tracer.record_failure(batch.iter().chain(remaining.iter()).map(|pending| &pending.solution.nonce), SolutionStage::Written, &err.to_string());
+            }
+            retry_queue.lock().unwrap().reschedule(batch.into_iter().chain(remaining.drain(..)), Instant::now());
+            break;
+        }
+
+        capture::record_sent(&addr, &data_for_send);
+        submission_stats.record_sent(packet_num);
+
+        retry_queue.lock().unwrap().mark_submitted(&batch);
+        if let Some(tracer) = tracer.lock().unwrap().as_mut() {
+            tracer.record(batch.iter().map(|pending| &pending.solution.nonce), SolutionStage::Written, &[]);
         }
+        written.extend(batch.iter().map(|pending| pending.solution.nonce));
+        net_stats.record_sent(data_for_send.len(), packet_num);
+        batch.iter().for_each(|pending| net_stats.record_latency(pending.solution.found_at.elapsed()));
 
-        tokio::time::sleep(tokio::time::Duration::from_secs(1)).await;
+        if let Some(notifier) = &notifier {
+            notifier.notify(NotifyEvent::SolutionSent(packet_num));
+        }
+    }
+    outcome.written = written.len();
+
+    let message = check_node_protocol(&mut stream, &net_stats, &addr, protocol).await;
+    outcome.is_answered = message.is_some();
+    if let Some(header) = message {
+        let peers = read_public_peers(&mut stream, &header, &addr).await;
+        targets.lock().unwrap().learn_nodes(peers);
+    }
+    if outcome.is_answered {
+        submission_stats.record_answered_connection();
+    }
+    if let Some(tracer) = tracer.lock().unwrap().as_mut() {
+        if outcome.is_answered {
+            tracer.record(&written, SolutionStage::Answered, &[]);
+        }
+        tracer.finish(&written);
     }
+    outcome
 }
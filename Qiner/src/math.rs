@@ -11,32 +11,283 @@ use lib::types::{KECCAK_ROUND, Nonce64, PublicKey64, State64, STATE_SIZE_64};
 /// * `S` - The size of the output array.
 ///
 /// # Example
-/// ```
+/// ```ignore
 /// use lib::types::{PublicKey64, Nonce64};
-/// let public_key: PublicKey64 = [0; 64];
-/// let nonce: Nonce64 = [0; 32];
+/// let public_key: PublicKey64 = [0; 4];
+/// let nonce: Nonce64 = [0; 4];
 /// let mut output: [u64; 4] = [0; 4];
 /// random_64(&public_key, &nonce, &mut output);
 /// ```
+#[cfg(any(test, feature = "miner"))]
 pub(crate) fn random_64<const S: usize>(public_key: &PublicKey64, nonce: &Nonce64, output: &mut [u64; S]) {
-    // Initialize the state array with default values
-    let mut state: State64 = State64::default();
+    random_64_masked(public_key, nonce, output, u64::MAX);
+}
 
-    // Copy the public key into the beginning of the state array
-    state[..public_key.len()].copy_from_slice(public_key);
+/// Generates a random sequence like `random_64`, masking every word on the fly.
+///
+/// Masking while copying the keccak output saves a second pass over the output array.
+///
+/// # Arguments
+/// * `public_key` - A reference to the public key used for generating the random sequence.
+/// * `nonce` - A reference to the nonce used for generating the random sequence.
+/// * `output` - A mutable reference to an array where the generated random sequence will be stored.
+/// * `mask` - The mask applied to every generated word.
+///
+/// # Type Parameters
+/// * `S` - The size of the output array.
+#[cfg(any(test, feature = "miner"))]
+pub(crate) fn random_64_masked<const S: usize>(public_key: &PublicKey64, nonce: &Nonce64, output: &mut [u64; S], mask: u64) {
+    random_64_keyed(&KeyState::new(public_key), nonce, output, mask);
+}
 
-    // Copy the nonce into the state array immediately following the public key
-    state[public_key.len()..public_key.len() + nonce.len()].copy_from_slice(nonce);
+/// The keccak state of a public key before a nonce is absorbed.
+///
+/// The first permutation already mixes the nonce in, so no permutation work can be shared
+/// between nonces: only building the state is, which the mining threads do once per run.
+#[derive(Debug, Clone)]
+pub struct KeyState {
+    state: State64,
+}
 
-    // Split the output array into chunks of the size of the state array
-    let mut chunks_mut = output.chunks_mut(STATE_SIZE_64);
+impl KeyState {
+    /// Creates the state of a public key.
+    ///
+    /// # Arguments
+    /// * `public_key` - The public key, at the beginning of the state.
+    ///
+    /// # Returns
+    /// The state, zeroed after the public key.
+    pub fn new(public_key: &PublicKey64) -> Self {
+        let mut state = State64::default();
+        state[..public_key.len()].copy_from_slice(public_key);
+        KeyState { state }
+    }
+}
 
-    // Process each chunk by applying the keccak-p1600 permutation
-    while let Some(chunk) = chunks_mut.next() {
+/// Generates a random sequence like `random_64_masked`, from the state of the public key.
+///
+/// # Arguments
+/// * `key_state` - The state of the public key used for generating the random sequence.
+/// * `nonce` - A reference to the nonce used for generating the random sequence.
+/// * `output` - The slice where the generated random sequence will be stored, its length being chosen at runtime.
+/// * `mask` - The mask applied to every generated word.
+pub(crate) fn random_64_keyed(key_state: &KeyState, nonce: &Nonce64, output: &mut [u64], mask: u64) {
+    let mut state = key_state.state;
+
+    // Copy the nonce into the state array immediately following the public key
+    state[NONCE_OFFSET..NONCE_OFFSET + nonce.len()].copy_from_slice(nonce);
+
+    // Process each state-sized chunk of the output by applying the keccak-p1600 permutation
+    for chunk in output.chunks_mut(STATE_SIZE_64) {
         // Apply the keccak-p1600 permutation to the state array
         keccak::p1600(&mut state, KECCAK_ROUND);
 
-        // Copy the resulting state array into the current chunk of the output array
-        chunk.clone_from_slice(&state[..chunk.len()]);
+        // Copy the masked state array into the current chunk of the output array
+        for (item, word) in chunk.iter_mut().zip(state.iter()) {
+            *item = word & mask;
+        }
+    }
+}
+
+/// Position of the nonce in the keccak state, right after the public key.
+const NONCE_OFFSET: usize = size_of::<PublicKey64>() / size_of::<u64>();
+
+/// Number of nonces `random_64_x4` expands in one pass.
+pub const KECCAK_LANES: usize = 4;
+
+/// Rotation offsets of the keccak rho step.
+#[cfg(target_arch = "x86_64")]
+const RHO: [u32; 24] = [
+    1, 3, 6, 10, 15, 21, 28, 36, 45, 55, 2, 14, 27, 41, 56, 8, 25, 43, 62, 18, 39, 61, 20, 44,
+];
+
+/// Lane permutation of the keccak pi step.
+#[cfg(target_arch = "x86_64")]
+const PI: [usize; 24] = [
+    10, 7, 11, 17, 18, 3, 5, 16, 8, 21, 24, 4, 15, 23, 19, 13, 12, 2, 20, 14, 22, 9, 6, 1,
+];
+
+/// Round constants of the keccak iota step, keccak-p using the last ones.
+#[cfg(target_arch = "x86_64")]
+const RC: [u64; 24] = [
+    0x0000000000000001, 0x0000000000008082, 0x800000000000808a, 0x8000000080008000,
+    0x000000000000808b, 0x0000000080000001, 0x8000000080008081, 0x8000000000008009,
+    0x000000000000008a, 0x0000000000000088, 0x0000000080008009, 0x000000008000000a,
+    0x000000008000808b, 0x800000000000008b, 0x8000000000008089, 0x8000000000008003,
+    0x8000000000008002, 0x8000000000000080, 0x000000000000800a, 0x800000008000000a,
+    0x8000000080008081, 0x8000000000008080, 0x0000000080000001, 0x8000000080008008,
+];
+
+/// Generates the masked random sequences of four nonces at once, same as four calls to `random_64_masked`.
+///
+/// With AVX2, the four keccak states are permuted together, one state per 64-bit SIMD lane.
+/// Without AVX2, the sequences are generated one after the other.
+///
+/// # Arguments
+/// * `public_key` - A reference to the public key used for generating the random sequences.
+/// * `nonces` - The nonces used for generating the random sequences.
+/// * `outputs` - The arrays where the generated random sequences will be stored, one per nonce.
+/// * `mask` - The mask applied to every generated word.
+///
+/// # Type Parameters
+/// * `S` - The size of the output arrays.
+#[cfg(any(test, feature = "miner"))]
+pub(crate) fn random_64_x4<const S: usize>(public_key: &PublicKey64, nonces: &[Nonce64; KECCAK_LANES], outputs: [&mut [u64; S]; KECCAK_LANES], mask: u64) {
+    let [output0, output1, output2, output3] = outputs;
+    random_64_x4_keyed(&KeyState::new(public_key), nonces, [output0, output1, output2, output3], mask);
+}
+
+/// Generates the random sequences of four nonces like `random_64_x4`, from the state of the public key.
+///
+/// # Arguments
+/// * `key_state` - The state of the public key shared by the four sequences.
+/// * `nonces` - The nonces, one per sequence.
+/// * `outputs` - The slices receiving the sequences, one per nonce, all of the same length.
+/// * `mask` - The mask applied to every generated word.
+///
+/// # Panics
+/// If the outputs do not all have the same length.
+pub(crate) fn random_64_x4_keyed(key_state: &KeyState, nonces: &[Nonce64; KECCAK_LANES], outputs: [&mut [u64]; KECCAK_LANES], mask: u64) {
+    assert!(outputs.iter().all(|output| output.len() == outputs[0].len()), "the outputs must have the same length");
+
+    #[cfg(target_arch = "x86_64")]
+    if crate::arch::has_keccak_x4() {
+        // Safety: AVX2 support was just checked
+        unsafe { random_64_x4_avx2(key_state, nonces, outputs, mask) };
+        return;
     }
+
+    for (nonce, output) in nonces.iter().zip(outputs) {
+        random_64_keyed(key_state, nonce, output, mask);
+    }
+}
+
+/// AVX2 implementation of `random_64_x4`.
+#[cfg(target_arch = "x86_64")]
+#[target_feature(enable = "avx2")]
+fn random_64_x4_avx2(key_state: &KeyState, nonces: &[Nonce64; KECCAK_LANES], outputs: [&mut [u64]; KECCAK_LANES], mask: u64) {
+    use core::arch::x86_64::*;
+
+    // Lane `i` of every word holds the state of nonce `i`
+    let mut state = [_mm256_setzero_si256(); STATE_SIZE_64];
+    for (idx, &word) in key_state.state[..NONCE_OFFSET].iter().enumerate() {
+        state[idx] = _mm256_set1_epi64x(word as i64);
+    }
+    for idx in 0..nonces[0].len() {
+        state[NONCE_OFFSET + idx] = _mm256_set_epi64x(nonces[3][idx] as i64, nonces[2][idx] as i64, nonces[1][idx] as i64, nonces[0][idx] as i64);
+    }
+
+    let mask = _mm256_set1_epi64x(mask as i64);
+    let [output0, output1, output2, output3] = outputs;
+    let mut lanes = [0u64; KECCAK_LANES];
+    let length = output0.len();
+    for offset in (0..length).step_by(STATE_SIZE_64) {
+        keccak_p1600_x4(&mut state, KECCAK_ROUND);
+
+        for (idx, word) in state.iter().take(length - offset).enumerate() {
+            // Safety: `lanes` holds exactly 256 bits, unaligned stores are allowed
+            unsafe { _mm256_storeu_si256(lanes.as_mut_ptr() as *mut __m256i, _mm256_and_si256(*word, mask)) };
+            output0[offset + idx] = lanes[0];
+            output1[offset + idx] = lanes[1];
+            output2[offset + idx] = lanes[2];
+            output3[offset + idx] = lanes[3];
+        }
+    }
+}
+
+/// Applies the keccak-p1600 permutation to four states at once, one per 64-bit lane.
+///
+/// # Arguments
+/// * `state` - The interleaved states.
+/// * `round_count` - The number of rounds, the last ones of keccak-f1600 being applied.
+#[cfg(target_arch = "x86_64")]
+#[target_feature(enable = "avx2")]
+fn keccak_p1600_x4(state: &mut [core::arch::x86_64::__m256i; STATE_SIZE_64], round_count: usize) {
+    use core::arch::x86_64::*;
+
+    #[inline(always)]
+    fn rotate_left(value: __m256i, n: u32) -> __m256i {
+        // Safety: only called from AVX2 enabled functions
+        unsafe {
+            _mm256_or_si256(
+                _mm256_sllv_epi64(value, _mm256_set1_epi64x(n as i64)),
+                _mm256_srlv_epi64(value, _mm256_set1_epi64x(64 - n as i64)),
+            )
+        }
+    }
+
+    for &rc in &RC[RC.len() - round_count..] {
+        // Theta
+        let mut columns = [_mm256_setzero_si256(); 5];
+        for (x, column) in columns.iter_mut().enumerate() {
+            *column = _mm256_xor_si256(
+                _mm256_xor_si256(state[x], state[x + 5]),
+                _mm256_xor_si256(_mm256_xor_si256(state[x + 10], state[x + 15]), state[x + 20]),
+            );
+        }
+        for x in 0..5 {
+            let delta = _mm256_xor_si256(columns[(x + 4) % 5], rotate_left(columns[(x + 1) % 5], 1));
+            for y in 0..5 {
+                state[5 * y + x] = _mm256_xor_si256(state[5 * y + x], delta);
+            }
+        }
+
+        // Rho and pi
+        let mut last = state[1];
+        for x in 0..24 {
+            let current = state[PI[x]];
+            state[PI[x]] = rotate_left(last, RHO[x]);
+            last = current;
+        }
+
+        // Chi
+        for y in (0..STATE_SIZE_64).step_by(5) {
+            let row = [state[y], state[y + 1], state[y + 2], state[y + 3], state[y + 4]];
+            for x in 0..5 {
+                state[y + x] = _mm256_xor_si256(row[x], _mm256_andnot_si256(row[(x + 1) % 5], row[(x + 2) % 5]));
+            }
+        }
+
+        // Iota
+        state[0] = _mm256_xor_si256(state[0], _mm256_set1_epi64x(rc as i64));
+    }
+}
+
+#[test]
+/// Tests that expanding four nonces at once matches four single expansions, partial last chunk included.
+fn test_random_64_x4() {
+    let public_key: PublicKey64 = [1, 2, 3, 4];
+    let nonces: [Nonce64; KECCAK_LANES] = [[5, 6, 7, 8], [0; 4], [u64::MAX; 4], [9, 10, 11, 12]];
+    let mask = 0x003F_FFFF_003F_FFFF;
+
+    let mut expected = [[0u64; 60]; KECCAK_LANES];
+    for (nonce, output) in nonces.iter().zip(expected.iter_mut()) {
+        random_64(&public_key, nonce, output);
+        output.iter_mut().for_each(|word| *word &= mask);
+    }
+
+    let mut outputs = [[0u64; 60]; KECCAK_LANES];
+    let [output0, output1, output2, output3] = &mut outputs;
+    random_64_x4(&public_key, &nonces, [output0, output1, output2, output3], mask);
+
+    assert_eq!(outputs, expected);
+}
+
+#[test]
+/// Tests that sequences generated from a shared key state match the ones generated from the public key.
+fn test_random_64_keyed() {
+    let public_key: PublicKey64 = [1, 2, 3, 4];
+    let key_state = KeyState::new(&public_key);
+    let mask = 0x003F_FFFF_003F_FFFF;
+
+    for nonce in [[5, 6, 7, 8], [0; 4], [u64::MAX; 4]] {
+        let mut expected = [0u64; 60];
+        let mut output = [0u64; 60];
+        random_64_masked(&public_key, &nonce, &mut expected, mask);
+        random_64_keyed(&key_state, &nonce, &mut output, mask);
+        assert_eq!(output, expected);
+    }
+
+    // The state is not changed by generating sequences
+    assert_eq!(key_state.state, KeyState::new(&public_key).state);
 }
@@ -0,0 +1,389 @@
+//! Typed builders of the messages sent to the nodes, each producing its framed bytes.
+//!
+//! A message only gives its type and its payload: the size, protocol and dejavu of the header
+//! are filled here, and so are the gamming of the payload and the signature of the broadcast
+//! messages and transactions, so a new message type does not handle them by hand again.
+
+use std::mem::size_of;
+use std::net::Ipv4Addr;
+use lib::types::network::protocols::{BROADCAST_MESSAGE, BROADCAST_TRANSACTION, EXCHANGE_PUBLIC_PEERS, REQUEST_COMPUTORS, REQUEST_CURRENT_TICK_INFO, REQUEST_ENTITY};
+use lib::types::network::{Key, KeyAndNonce, NUMBER_OF_EXCHANGED_PEERS, Protocol, Type};
+use lib::types::{Id, Nonce, Nonce64, PublicKey64, Signature};
+use crate::converters::{get_id_from_public_key_64, get_public_key_64_from_public_key};
+use crate::crypto::{k12_32, k12_into};
+use crate::identity::Identity;
+use crate::network::{Packet, RequestResponseHeader};
+use crate::rng;
+
+/// Size of the header every message starts with.
+const HEADER_SIZE: usize = size_of::<RequestResponseHeader>();
+
+/// Message type of a solution, the first byte of the gamming key of its broadcast message.
+pub const MESSAGE_TYPE_SOLUTION: u8 = 0;
+
+/// A message to a node, framed with its header.
+pub trait Request {
+    /// Type of the message in its header
+    const TYPE: Type;
+    /// Whether the nodes relay the message to their peers, its dejavu being zero, or drop it after reading it, its dejavu being random
+    const IS_RELAYED: bool;
+
+    /// Gets the payload of the message, after the header.
+    fn payload(&self) -> Vec<u8>;
+
+    /// Frames the message with its header.
+    ///
+    /// # Arguments
+    /// * `protocol` - The protocol version, from the settings.
+    ///
+    /// # Returns
+    /// The bytes of the message, header included, or why the message does not fit in a header.
+    fn to_bytes(&self, protocol: Protocol) -> Result<Vec<u8>, String> {
+        let payload = self.payload();
+        let mut header = RequestResponseHeader::new(&Self::TYPE, &(HEADER_SIZE + payload.len()), &protocol)?;
+        if !Self::IS_RELAYED {
+            header.randomize_dejavu();
+        }
+        let mut bytes = header.to_bytes().to_vec();
+        bytes.extend_from_slice(&payload);
+        Ok(bytes)
+    }
+}
+
+/// Builds a broadcast message: its payload is gammed with a key only a message of the wanted type
+/// derives, and followed by a random signature, the nodes not checking the signature of solutions.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BroadcastMessageBuilder {
+    source_public_key: PublicKey64,
+    destination_public_key: PublicKey64,
+    message_type: u8,
+    payload: Vec<u8>,
+}
+
+impl BroadcastMessageBuilder {
+    /// Creates a new `BroadcastMessageBuilder`, from no source, of a solution without payload.
+    ///
+    /// # Arguments
+    /// * `destination_public_key` - The public key of the computor the message is for.
+    ///
+    /// # Returns
+    /// A new `BroadcastMessageBuilder`.
+    pub fn new(destination_public_key: PublicKey64) -> Self {
+        BroadcastMessageBuilder {
+            source_public_key: PublicKey64::default(),
+            destination_public_key,
+            message_type: MESSAGE_TYPE_SOLUTION,
+            payload: Vec::new(),
+        }
+    }
+
+    /// Creates the builder of the broadcast message of a solution.
+    ///
+    /// # Arguments
+    /// * `computor_public_key` - The public key of the computor.
+    /// * `nonce` - The nonce of the solution.
+    ///
+    /// # Returns
+    /// The builder of the solution.
+    pub fn solution(computor_public_key: PublicKey64, nonce: &Nonce64) -> Self {
+        BroadcastMessageBuilder::new(computor_public_key).payload(nonce.iter().flat_map(|value| value.to_ne_bytes()).collect())
+    }
+
+    /// Sets the public key the message is from.
+    pub fn source(mut self, source_public_key: PublicKey64) -> Self {
+        self.source_public_key = source_public_key;
+        self
+    }
+
+    /// Sets the message type the gamming key must start with.
+    pub fn message_type(mut self, message_type: u8) -> Self {
+        self.message_type = message_type;
+        self
+    }
+
+    /// Sets the payload, gammed when the message is built.
+    pub fn payload(mut self, payload: Vec<u8>) -> Self {
+        self.payload = payload;
+        self
+    }
+}
+
+impl Request for BroadcastMessageBuilder {
+    const TYPE: Type = BROADCAST_MESSAGE;
+    const IS_RELAYED: bool = true;
+
+    /// Gets the payload: source, destination, gamming nonce, gammed payload and signature.
+    ///
+    /// The gamming nonce is drawn again until the gamming key, derived from a zero shared key,
+    /// starts with the message type, 256 draws on average.
+    fn payload(&self) -> Vec<u8> {
+        let mut shared_key_and_gamming_nonce: KeyAndNonce = [0; size_of::<KeyAndNonce>()];
+        let mut gamming_key = Key::default();
+        let mut gamming_nonce = Nonce::default();
+        loop {
+            gamming_nonce.chunks_mut(size_of::<u64>()).for_each(|items| items.copy_from_slice(&rng::random_u64().to_ne_bytes()));
+            shared_key_and_gamming_nonce[gamming_key.len()..].copy_from_slice(&gamming_nonce);
+            k12_into(&shared_key_and_gamming_nonce, &mut gamming_key);
+            if gamming_key[0] == self.message_type {
+                break;
+            }
+        }
+
+        let mut gamma = vec![0u8; self.payload.len()];
+        k12_into(&gamming_key, &mut gamma);
+
+        let mut bytes = Vec::with_capacity(2 * size_of::<PublicKey64>() + size_of::<Nonce>() + self.payload.len() + size_of::<Signature>());
+        bytes.extend(self.source_public_key.iter().flat_map(|value| value.to_ne_bytes()));
+        bytes.extend(self.destination_public_key.iter().flat_map(|value| value.to_ne_bytes()));
+        bytes.extend_from_slice(&gamming_nonce);
+        bytes.extend(self.payload.iter().zip(gamma).map(|(value, gamma)| value ^ gamma));
+        bytes.extend(Packet::get_random_signature().iter().flat_map(|value| value.to_ne_bytes()));
+        bytes
+    }
+}
+
+/// Builds a transaction, transferring an amount to a destination and optionally calling it with an input.
+///
+/// Unlike solutions, the nodes verify the signature of transactions, so a transaction is sent once
+/// signed by the identity it is from, and is only executed if it reaches a computor before its tick.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TransactionBuilder {
+    source_public_key: PublicKey64,
+    destination_public_key: PublicKey64,
+    amount: i64,
+    tick: u32,
+    input_type: u16,
+    input: Vec<u8>,
+    signature: Signature,
+}
+
+impl TransactionBuilder {
+    /// Creates the builder of a transfer, unsigned and without input.
+    ///
+    /// # Arguments
+    /// * `destination_public_key` - The public key receiving the amount.
+    /// * `amount` - The amount transferred.
+    /// * `tick` - The tick the transaction is scheduled for.
+    ///
+    /// # Returns
+    /// The builder of the transfer.
+    pub fn transfer(destination_public_key: PublicKey64, amount: i64, tick: u32) -> Self {
+        TransactionBuilder {
+            source_public_key: PublicKey64::default(),
+            destination_public_key,
+            amount,
+            tick,
+            input_type: 0,
+            input: Vec::new(),
+            signature: Signature::default(),
+        }
+    }
+
+    /// Sets the input of the transaction, e.g. the procedure of a contract and its arguments.
+    pub fn input(mut self, input_type: u16, input: Vec<u8>) -> Self {
+        self.input_type = input_type;
+        self.input = input;
+        self
+    }
+
+    /// Signs the transaction, from the public key of the identity.
+    ///
+    /// # Arguments
+    /// * `identity` - The identity the amount is taken from.
+    ///
+    /// # Returns
+    /// The signed transaction.
+    pub fn sign(mut self, identity: &Identity) -> Self {
+        self.source_public_key = get_public_key_64_from_public_key(&identity.public_key);
+        self.signature = identity.sign(&self.get_digest());
+        self
+    }
+
+    /// Gets the tick the transaction is scheduled for.
+    pub fn get_tick(&self) -> u32 {
+        self.tick
+    }
+
+    /// Gets the hash of the transaction without its signature, the message signed.
+    pub fn get_digest(&self) -> [u8; 32] {
+        k12_32(&self.unsigned_payload())
+    }
+
+    /// Gets the ID of the transaction, as shown by the explorers: the lowercase identity of the hash of the signed transaction.
+    pub fn get_id(&self) -> String {
+        let digest = k12_32(&self.payload());
+        let mut id: Id = [0; 60];
+        get_id_from_public_key_64(&std::array::from_fn(|idx| u64::from_le_bytes(digest[idx * 8..idx * 8 + 8].try_into().unwrap())), &mut id);
+        String::from_utf8_lossy(&id).to_ascii_lowercase()
+    }
+
+    /// Gets the payload before the signature: source, destination, amount, tick, input type, input size and input.
+    fn unsigned_payload(&self) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity(2 * size_of::<PublicKey64>() + 16 + self.input.len() + size_of::<Signature>());
+        bytes.extend(self.source_public_key.iter().flat_map(|value| value.to_le_bytes()));
+        bytes.extend(self.destination_public_key.iter().flat_map(|value| value.to_le_bytes()));
+        bytes.extend_from_slice(&self.amount.to_le_bytes());
+        bytes.extend_from_slice(&self.tick.to_le_bytes());
+        bytes.extend_from_slice(&self.input_type.to_le_bytes());
+        bytes.extend_from_slice(&(self.input.len() as u16).to_le_bytes());
+        bytes.extend_from_slice(&self.input);
+        bytes
+    }
+}
+
+impl Request for TransactionBuilder {
+    const TYPE: Type = BROADCAST_TRANSACTION;
+    const IS_RELAYED: bool = true;
+
+    /// Gets the payload: the transaction followed by its signature.
+    fn payload(&self) -> Vec<u8> {
+        let mut bytes = self.unsigned_payload();
+        bytes.extend(self.signature.iter().flat_map(|value| value.to_le_bytes()));
+        bytes
+    }
+}
+
+/// Requests the current tick info, a message every node answers, without relaying it.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct RequestTickInfo;
+
+impl Request for RequestTickInfo {
+    const TYPE: Type = REQUEST_CURRENT_TICK_INFO;
+    const IS_RELAYED: bool = false;
+
+    /// Gets the payload, none: the header alone is the request.
+    fn payload(&self) -> Vec<u8> {
+        Vec::new()
+    }
+}
+
+/// Requests the computors of the epoch, without relaying it.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct RequestComputors;
+
+impl Request for RequestComputors {
+    const TYPE: Type = REQUEST_COMPUTORS;
+    const IS_RELAYED: bool = false;
+
+    /// Gets the payload, none: the header alone is the request.
+    fn payload(&self) -> Vec<u8> {
+        Vec::new()
+    }
+}
+
+/// Requests the entity of a public key in the spectrum, its balance and transfers, without relaying it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RequestEntity {
+    public_key: PublicKey64,
+}
+
+impl RequestEntity {
+    /// Creates a new `RequestEntity`.
+    ///
+    /// # Arguments
+    /// * `public_key` - The public key whose entity is requested.
+    ///
+    /// # Returns
+    /// The message.
+    pub fn new(public_key: PublicKey64) -> Self {
+        RequestEntity { public_key }
+    }
+}
+
+impl Request for RequestEntity {
+    const TYPE: Type = REQUEST_ENTITY;
+    const IS_RELAYED: bool = false;
+
+    /// Gets the payload, the public key.
+    fn payload(&self) -> Vec<u8> {
+        self.public_key.iter().flat_map(|value| value.to_ne_bytes()).collect()
+    }
+}
+
+/// Shares public peers, the message nodes send first on every connection.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ExchangePublicPeers {
+    peers: [Ipv4Addr; NUMBER_OF_EXCHANGED_PEERS],
+}
+
+impl ExchangePublicPeers {
+    /// Creates a new `ExchangePublicPeers`.
+    ///
+    /// # Arguments
+    /// * `peers` - The addresses shared, only the first `NUMBER_OF_EXCHANGED_PEERS` being sent.
+    ///
+    /// # Returns
+    /// The message, the missing peers left unspecified.
+    pub fn new(peers: &[Ipv4Addr]) -> Self {
+        let mut shared = [Ipv4Addr::UNSPECIFIED; NUMBER_OF_EXCHANGED_PEERS];
+        shared.iter_mut().zip(peers).for_each(|(shared, peer)| *shared = *peer);
+        ExchangePublicPeers { peers: shared }
+    }
+}
+
+impl Request for ExchangePublicPeers {
+    const TYPE: Type = EXCHANGE_PUBLIC_PEERS;
+    const IS_RELAYED: bool = false;
+
+    /// Gets the payload, the 4 octets of every peer.
+    fn payload(&self) -> Vec<u8> {
+        self.peers.iter().flat_map(|peer| peer.octets()).collect()
+    }
+}
+
+#[test]
+/// Tests that every message is framed with its type and size and that broadcast messages are gammed.
+fn test_requests() {
+    use crate::peers::parse_public_peers;
+
+    let public_key: PublicKey64 = [1, 2, 3, 4];
+    let nonce: Nonce64 = [5, 6, 7, u64::MAX];
+    let solution = BroadcastMessageBuilder::solution(public_key, &nonce).to_bytes(142).unwrap();
+    assert_eq!(solution.len(), size_of::<Packet>());
+    assert_eq!(Packet::validate_solution(&solution, Some(142)), Ok((public_key, nonce)));
+    assert!(RequestResponseHeader::from_bytes(&solution).unwrap().is_dejavu_zero());
+
+    // Another message type grinds another gamming key, which the gamma of the payload comes from
+    let message = BroadcastMessageBuilder::new(public_key).source([9; 4]).message_type(7).payload(vec![0; 10]).to_bytes(142).unwrap();
+    assert_eq!(message.len(), HEADER_SIZE + 96 + 10 + 64);
+    let mut shared_key_and_gamming_nonce: KeyAndNonce = [0; size_of::<KeyAndNonce>()];
+    shared_key_and_gamming_nonce[32..].copy_from_slice(&message[HEADER_SIZE + 64..HEADER_SIZE + 96]);
+    let mut gamming_key = Key::default();
+    k12_into(&shared_key_and_gamming_nonce, &mut gamming_key);
+    assert_eq!(gamming_key[0], 7);
+    let mut gamma = [0u8; 10];
+    k12_into(&gamming_key, &mut gamma);
+    assert_eq!(message[HEADER_SIZE + 96..HEADER_SIZE + 106], gamma);
+    assert!(BroadcastMessageBuilder::new(public_key).payload(vec![0; 1 << 24]).to_bytes(142).is_err());
+
+    let request = RequestTickInfo.to_bytes(142).unwrap();
+    let header = RequestResponseHeader::from_bytes(&request).unwrap();
+    assert_eq!((request.len(), header.get_size(), header.get_type(), header.get_protocol()), (HEADER_SIZE, HEADER_SIZE, REQUEST_CURRENT_TICK_INFO, 142));
+
+    let computors = RequestComputors.to_bytes(142).unwrap();
+    assert_eq!((computors.len(), RequestResponseHeader::from_bytes(&computors).unwrap().get_type()), (HEADER_SIZE, REQUEST_COMPUTORS));
+
+    let entity = RequestEntity::new(public_key).to_bytes(142).unwrap();
+    let header = RequestResponseHeader::from_bytes(&entity).unwrap();
+    assert_eq!((entity.len(), header.get_size(), header.get_type()), (HEADER_SIZE + 32, HEADER_SIZE + 32, REQUEST_ENTITY));
+    assert_eq!(entity[HEADER_SIZE..HEADER_SIZE + 8], 1u64.to_ne_bytes());
+
+    let identity = crate::identity::derive_identity(&[b'a'; crate::identity::SEED_LENGTH]).unwrap();
+    let transaction = TransactionBuilder::transfer([9, 8, 7, 6], 1000, 20_000_000).sign(&identity);
+    let bytes = transaction.to_bytes(142).unwrap();
+    let header = RequestResponseHeader::from_bytes(&bytes).unwrap();
+    assert_eq!((bytes.len(), header.get_type(), header.is_dejavu_zero()), (HEADER_SIZE + 80 + 64, BROADCAST_TRANSACTION, true));
+    assert_eq!(bytes[HEADER_SIZE..HEADER_SIZE + 32], identity.public_key);
+    assert_eq!(bytes[HEADER_SIZE + 64..HEADER_SIZE + 76], [1000i64.to_le_bytes().as_slice(), &20_000_000u32.to_le_bytes()].concat());
+    assert_eq!(transaction.get_digest(), k12_32(&bytes[HEADER_SIZE..HEADER_SIZE + 80]));
+    assert_eq!(bytes[HEADER_SIZE + 80..], identity.sign(&transaction.get_digest()).iter().flat_map(|value| value.to_le_bytes()).collect::<Vec<_>>());
+    assert_eq!(transaction.get_id().len(), 60);
+    assert!(transaction.get_id().bytes().all(|letter| letter.is_ascii_lowercase()));
+    let with_input = TransactionBuilder::transfer([9, 8, 7, 6], 0, 20_000_000).input(3, vec![1, 2]).sign(&identity).to_bytes(142).unwrap();
+    assert_eq!((with_input.len(), &with_input[HEADER_SIZE + 76..HEADER_SIZE + 82]), (HEADER_SIZE + 82 + 64, &[3, 0, 2, 0, 1, 2][..]));
+
+    let peers = ExchangePublicPeers::new(&[Ipv4Addr::new(10, 0, 0, 1), Ipv4Addr::new(10, 0, 0, 2)]).to_bytes(142).unwrap();
+    assert_eq!(RequestResponseHeader::from_bytes(&peers).unwrap().get_size(), HEADER_SIZE + 16);
+    let payload: &[u8; NUMBER_OF_EXCHANGED_PEERS * 4] = peers[HEADER_SIZE..].try_into().unwrap();
+    assert_eq!(parse_public_peers(payload, "21841"), vec!["10.0.0.1:21841".to_string(), "10.0.0.2:21841".to_string()]);
+}
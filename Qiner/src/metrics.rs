@@ -0,0 +1,312 @@
+//! Pushes the counters and gauges of the session to a statsd or graphite server, for farms
+//! already collecting their metrics there.
+
+use std::env;
+use std::sync::Arc;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use serde_json::Value;
+use tokio::io::AsyncWriteExt;
+use tokio::net::{TcpStream, UdpSocket};
+use lib::env_names::{ENV_METRICS_BACKEND, ENV_METRICS_INTERVAL, ENV_METRICS_PREFIX};
+use crate::rig::{get_worker_name, to_metric_segment};
+use crate::summary::SessionReporter;
+
+/// Default interval between two pushes of the metrics.
+const DEFAULT_METRICS_INTERVAL: Duration = Duration::from_secs(10);
+
+/// Default prefix of the metric names.
+const DEFAULT_METRICS_PREFIX: &str = "qiner";
+
+/// Largest statsd datagram, so it fits the MTU of common networks without fragmenting.
+const MAX_DATAGRAM_SIZE: usize = 1432;
+
+/// Timeout applied to every connection to the graphite server.
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// The metrics pushed, by summary field: counters keep growing, gauges are sampled.
+const METRICS: [(&str, MetricKind, &[&str]); 15] = [
+    ("iterations", MetricKind::Counter, &["iterations"]),
+    ("solutions_found", MetricKind::Counter, &["solutions_found"]),
+    ("solutions_sent", MetricKind::Counter, &["solutions_sent"]),
+    ("answered_connections", MetricKind::Counter, &["answered_connections"]),
+    ("solutions_failed", MetricKind::Counter, &["solutions_failed"]),
+    ("solutions_expired", MetricKind::Counter, &["solutions_expired"]),
+    ("shares_found", MetricKind::Counter, &["shares_found"]),
+    ("worker_restarts", MetricKind::Counter, &["worker_restarts"]),
+    ("network.packets_sent", MetricKind::Counter, &["network", "packets_sent"]),
+    ("network.bytes_sent", MetricKind::Counter, &["network", "bytes_sent"]),
+    ("network.connect_attempts", MetricKind::Counter, &["network", "connect_attempts"]),
+    ("network.connect_failures", MetricKind::Counter, &["network", "connect_failures"]),
+    ("network.average_latency_ms", MetricKind::Gauge, &["network", "average_latency_ms"]),
+    ("network.max_latency_ms", MetricKind::Gauge, &["network", "max_latency_ms"]),
+    ("network.average_packet_build_us", MetricKind::Gauge, &["network", "average_packet_build_us"]),
+];
+
+/// How a metric evolves.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MetricKind {
+    /// A total that only grows during the session.
+    Counter,
+    /// A value that can go up and down.
+    Gauge,
+}
+
+/// A value of a metric at one moment.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Metric {
+    /// The name, without the prefix
+    pub name: String,
+    pub kind: MetricKind,
+    pub value: f64,
+}
+
+/// The server the metrics are pushed to.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum MetricsBackend {
+    /// A statsd server, as `host:port`, receiving UDP datagrams.
+    Statsd(String),
+    /// A graphite server, as `host:port`, receiving the plaintext protocol over TCP.
+    Graphite(String),
+}
+
+impl MetricsBackend {
+    /// Parses the backend from its URL.
+    ///
+    /// # Arguments
+    /// * `url` - `statsd://host:port` or `graphite://host:port`.
+    ///
+    /// # Returns
+    /// The backend, or why the URL is invalid.
+    pub fn parse(url: &str) -> Result<Self, String> {
+        let (scheme, addr) = url.trim().split_once("://").ok_or_else(|| "expected statsd://host:port or graphite://host:port".to_string())?;
+        let addr = addr.trim_end_matches('/');
+        if addr.rsplit_once(':').is_none_or(|(host, port)| host.is_empty() || port.parse::<u16>().is_err()) {
+            return Err(format!("expected host:port, got {addr:?}"));
+        }
+        match scheme {
+            "statsd" => Ok(MetricsBackend::Statsd(addr.to_string())),
+            "graphite" => Ok(MetricsBackend::Graphite(addr.to_string())),
+            _ => Err(format!("unknown backend {scheme:?}, expected statsd or graphite")),
+        }
+    }
+}
+
+/// Periodically pushes the metrics of the session to a statsd or graphite server.
+///
+/// Disabled unless `METRICS_BACKEND` is set.
+#[derive(Debug, Clone)]
+pub struct MetricsExporter {
+    backend: MetricsBackend,
+    prefix: String,
+    interval: Duration,
+}
+
+impl MetricsExporter {
+    /// Creates a `MetricsExporter` from the environment variables.
+    ///
+    /// # Returns
+    /// `Some(MetricsExporter)` if a valid backend is configured, `None` otherwise.
+    pub fn from_env() -> Option<Self> {
+        let url = env::var(ENV_METRICS_BACKEND).ok().filter(|url| !url.trim().is_empty())?;
+        let backend = match MetricsBackend::parse(&url) {
+            Ok(backend) => backend,
+            Err(err) => {
+                log::error!("Invalid {ENV_METRICS_BACKEND}, metrics are not exported: {err}");
+                return None;
+            }
+        };
+
+        // Rigs sharing the server are told apart by their name unless a prefix is given
+        let prefix = env::var(ENV_METRICS_PREFIX).ok()
+            .map(|prefix| prefix.trim().trim_end_matches('.').to_string())
+            .or_else(|| get_worker_name().map(|name| format!("{DEFAULT_METRICS_PREFIX}.{}", to_metric_segment(&name))))
+            .unwrap_or_else(|| DEFAULT_METRICS_PREFIX.to_string());
+
+        let interval = env::var(ENV_METRICS_INTERVAL).ok()
+            .and_then(|value| value.trim().parse::<u64>().ok())
+            .filter(|secs| *secs > 0)
+            .map(Duration::from_secs)
+            .unwrap_or(DEFAULT_METRICS_INTERVAL);
+
+        Some(MetricsExporter { backend, prefix, interval })
+    }
+
+    /// Gets the server the metrics are pushed to.
+    pub fn get_backend(&self) -> &MetricsBackend {
+        &self.backend
+    }
+
+    /// Gets the interval between two pushes.
+    pub fn get_interval(&self) -> Duration {
+        self.interval
+    }
+
+    /// Prefixes the name of a metric.
+    fn full_name(&self, name: &str) -> String {
+        if self.prefix.is_empty() { name.to_string() } else { format!("{}.{name}", self.prefix) }
+    }
+
+    /// Formats the metrics as statsd lines.
+    ///
+    /// # Arguments
+    /// * `metrics` - The current metrics.
+    /// * `previous` - The metrics of the previous push, counters being sent as increments.
+    ///
+    /// # Returns
+    /// One line per metric, counters that did not change being left out.
+    pub fn statsd_lines(&self, metrics: &[Metric], previous: &[Metric]) -> Vec<String> {
+        metrics.iter().filter_map(|metric| match metric.kind {
+            MetricKind::Counter => {
+                let before = previous.iter().find(|previous| previous.name == metric.name).map_or(0.0, |previous| previous.value);
+                let increment = metric.value - before;
+                (increment > 0.0).then(|| format!("{}:{increment}|c", self.full_name(&metric.name)))
+            }
+            MetricKind::Gauge => Some(format!("{}:{}|g", self.full_name(&metric.name), metric.value)),
+        }).collect()
+    }
+
+    /// Formats the metrics as graphite plaintext lines, which always carry the current value.
+    ///
+    /// # Arguments
+    /// * `metrics` - The current metrics.
+    /// * `timestamp` - The Unix time of the values.
+    pub fn graphite_lines(&self, metrics: &[Metric], timestamp: u64) -> Vec<String> {
+        metrics.iter().map(|metric| format!("{} {} {timestamp}", self.full_name(&metric.name), metric.value)).collect()
+    }
+
+    /// Pushes the metrics to the backend.
+    ///
+    /// # Arguments
+    /// * `metrics` - The current metrics.
+    /// * `previous` - The metrics of the previous push.
+    async fn push(&self, metrics: &[Metric], previous: &[Metric]) -> std::io::Result<()> {
+        match &self.backend {
+            MetricsBackend::Statsd(addr) => {
+                let socket = UdpSocket::bind("0.0.0.0:0").await?;
+                socket.connect(addr).await?;
+                for datagram in pack_datagrams(self.statsd_lines(metrics, previous)) {
+                    socket.send(datagram.as_bytes()).await?;
+                }
+                Ok(())
+            }
+            MetricsBackend::Graphite(addr) => {
+                let timestamp = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default().as_secs();
+                let mut body = self.graphite_lines(metrics, timestamp).join("\n");
+                body.push('\n');
+                let mut stream = tokio::time::timeout(CONNECT_TIMEOUT, TcpStream::connect(addr)).await??;
+                stream.write_all(body.as_bytes()).await?;
+                stream.shutdown().await
+            }
+        }
+    }
+}
+
+/// Reads the metrics from the session summary.
+///
+/// # Arguments
+/// * `summary` - The session summary, see `SessionReporter::summary`.
+/// * `it_per_sec` - The iteration rate since the previous push.
+///
+/// # Returns
+/// Every metric, with the iteration rate and the per-thread iteration counts.
+pub fn summary_metrics(summary: &Value, it_per_sec: f64) -> Vec<Metric> {
+    let mut metrics = METRICS.iter().map(|(name, kind, path)| Metric {
+        name: name.to_string(),
+        kind: *kind,
+        value: path.iter().fold(summary, |value, key| &value[key]).as_f64().unwrap_or_default(),
+    }).collect::<Vec<_>>();
+
+    metrics.push(Metric { name: "it_per_sec".to_string(), kind: MetricKind::Gauge, value: it_per_sec });
+    for thread in summary["per_thread"].as_array().into_iter().flatten() {
+        metrics.push(Metric {
+            name: format!("thread.{}.iterations", thread["thread"]),
+            kind: MetricKind::Counter,
+            value: thread["iterations"].as_f64().unwrap_or_default(),
+        });
+    }
+    metrics
+}
+
+/// Groups statsd lines into datagrams of at most `MAX_DATAGRAM_SIZE` bytes.
+fn pack_datagrams(lines: Vec<String>) -> Vec<String> {
+    let mut datagrams: Vec<String> = Vec::new();
+    for line in lines {
+        match datagrams.last_mut() {
+            Some(datagram) if datagram.len() + 1 + line.len() <= MAX_DATAGRAM_SIZE => {
+                datagram.push('\n');
+                datagram.push_str(&line);
+            }
+            _ => datagrams.push(line),
+        }
+    }
+    datagrams
+}
+
+/// Asynchronous task pushing the metrics periodically.
+///
+/// # Arguments
+/// * `exporter` - The metrics exporter.
+/// * `session_reporter` - The session reporter the metrics are taken from.
+pub async fn metrics_task(exporter: MetricsExporter, session_reporter: Arc<SessionReporter>) {
+    let mut previous = Vec::new();
+    let mut is_failing = false;
+
+    loop {
+        tokio::time::sleep(exporter.get_interval()).await;
+
+        let summary = session_reporter.summary().await;
+        let iterations = summary["iterations"].as_f64().unwrap_or_default();
+        let prev_iterations = previous.iter().find(|metric: &&Metric| metric.name == "iterations").map_or(0.0, |metric| metric.value);
+        let metrics = summary_metrics(&summary, (iterations - prev_iterations) / exporter.get_interval().as_secs_f64());
+
+        // Failures are logged once until the backend is reachable again
+        match exporter.push(&metrics, &previous).await {
+            Ok(_) => {
+                if is_failing {
+                    log::info!("Metrics are pushed to {:?} again", exporter.get_backend());
+                }
+                is_failing = false;
+                previous = metrics;
+            }
+            Err(err) => {
+                if !is_failing {
+                    log::warn!("Failed to push the metrics to {:?}: {}", exporter.get_backend(), err);
+                }
+                is_failing = true;
+            }
+        }
+    }
+}
+
+#[test]
+/// Tests the parsing of the backend and that counters are sent to statsd as increments.
+fn test_metrics_lines() {
+    assert_eq!(MetricsBackend::parse("statsd://localhost:8125"), Ok(MetricsBackend::Statsd("localhost:8125".to_string())));
+    assert_eq!(MetricsBackend::parse("graphite://10.0.0.1:2003/"), Ok(MetricsBackend::Graphite("10.0.0.1:2003".to_string())));
+    assert!(MetricsBackend::parse("statsd://localhost").is_err());
+    assert!(MetricsBackend::parse("influx://localhost:8086").is_err());
+
+    let summary = serde_json::json!({
+        "iterations": 1000,
+        "solutions_found": 2,
+        "per_thread": [{ "thread": 0, "iterations": 1000 }],
+        "network": { "packets_sent": 2, "average_latency_ms": 15 },
+    });
+    let metrics = summary_metrics(&summary, 100.0);
+    let exporter = MetricsExporter { backend: MetricsBackend::Statsd(String::new()), prefix: "rig1".to_string(), interval: DEFAULT_METRICS_INTERVAL };
+
+    let lines = exporter.statsd_lines(&metrics, &[]);
+    assert!(lines.contains(&"rig1.iterations:1000|c".to_string()));
+    assert!(lines.contains(&"rig1.network.average_latency_ms:15|g".to_string()));
+    assert!(lines.contains(&"rig1.it_per_sec:100|g".to_string()));
+    assert!(lines.contains(&"rig1.thread.0.iterations:1000|c".to_string()));
+    assert!(!lines.iter().any(|line| line.starts_with("rig1.shares_found")));
+
+    let mut next = metrics.clone();
+    next[0].value = 1500.0;
+    let lines = exporter.statsd_lines(&next, &metrics);
+    assert_eq!(lines.iter().filter(|line| line.ends_with("|c")).collect::<Vec<_>>(), ["rig1.iterations:500|c"]);
+
+    assert_eq!(exporter.graphite_lines(&metrics[..1], 1_700_000_000), ["rig1.iterations 1000 1700000000"]);
+    assert_eq!(pack_datagrams(vec!["a".repeat(1000), "b".repeat(400), "c".repeat(100)]).len(), 2);
+}
@@ -1,74 +1,499 @@
-use std::arch::x86_64::_rdrand64_step;
-use std::collections::HashMap;
-use std::mem::{size_of, zeroed};
 use std::sync::{Arc};
-use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
 use std::thread;
-use std::thread::ThreadId;
-use lib::solution_threshold::get_solution_threshold;
+use std::time::{Duration, Instant};
+use crate::settings::Settings;
+use crate::arch::CodePath;
+use crate::algorithm::{Algorithm, AlgorithmEntry};
+use crate::backend::Backend;
+use crate::cores::{self, CoreType, ThreadPlacement};
+use crate::crypto::k12_into;
+use crate::histogram::{SCORE_BUCKETS, ScoreCounts, ScoreHistogram};
+use crate::math::KECCAK_LANES;
+use crate::nonce::NonceStream;
+use crate::score::Scorer;
+use lib::params::AlgorithmParams;
 use lib::types::{
     MiningItemData,
-    MiningData,
-    NeuronLink,
-    NeuronLinks64,
-    NeuronValue,
-    NeuronValues,
     Nonce64,
     PublicKey64,
-    Seed,
     Seed64,
-    MINING_DATA_LENGTH,
-    NEURON_MOD_BITS,
-    NUMBER_OF_NEURONS,
-    NUMBER_OF_NEURONS_64,
 };
+pub use crate::score::NeuronData;
+use lib::types::STACK_SIZE;
 
-/// Container for neuron data specific to each thread
-#[derive(Debug, Clone, Default)]
-pub struct NeuronContainer {
-    neuron_data: HashMap<ThreadId, NeuronData>,
+/// Time an idle mining thread sleeps before checking again whether it may work
+const IDLE_SLEEP: Duration = Duration::from_millis(100);
+
+/// Time a mining thread counts iterations locally before folding them into the shared counters,
+/// short enough for the rates refreshed every second: a nonce takes a fraction of a second
+const COUNTER_FLUSH_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Number of nonce batches per active thread scheduled in one round of the rayon pool
+#[cfg(feature = "rayon")]
+const RAYON_TASKS_PER_THREAD: usize = 16;
+
+/// How nonce attempts are scheduled on the mining threads
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Scheduler {
+    /// One endless mining loop per thread
+    #[default]
+    Threads,
+    /// Nonce batches scheduled as tasks of a rayon pool, so fast cores steal the work of slow ones
+    #[cfg(feature = "rayon")]
+    Rayon,
 }
 
-impl NeuronContainer {
-    /// Retrieve or initialize neuron data for the given thread
+impl Scheduler {
+    /// Parses the scheduler from its name
     ///
     /// # Arguments
-    /// * `thread_id` - A reference to the ThreadId for which to retrieve the neuron data
+    /// * `name` - `threads` or `rayon`
     ///
     /// # Returns
-    /// A mutable reference to the NeuronData associated with the provided thread ID
-    pub fn get_mut_data(&mut self, thread_id: &ThreadId) -> &mut NeuronData {
-        self.neuron_data.entry(thread_id.clone()).or_default()
+    /// The scheduler, or why the name is not supported by this build.
+    pub fn parse(name: &str) -> Result<Self, String> {
+        match name.trim().to_ascii_lowercase().as_str() {
+            "threads" => Ok(Scheduler::Threads),
+            #[cfg(feature = "rayon")]
+            "rayon" => Ok(Scheduler::Rayon),
+            #[cfg(not(feature = "rayon"))]
+            "rayon" => Err("rayon needs a build with --features rayon".to_string()),
+            other => Err(format!("unknown scheduler {other:?}, expected threads or rayon")),
+        }
+    }
+
+    /// Gets the name of the scheduler
+    pub fn name(&self) -> &'static str {
+        match self {
+            Scheduler::Threads => "threads",
+            #[cfg(feature = "rayon")]
+            Scheduler::Rayon => "rayon",
+        }
     }
 }
 
-/// Structure holding neuron links and values
-#[derive(Debug, Clone, Default)]
-pub struct NeuronData {
-    neuron_links: NeuronLinks64,
-    neuron_values: NeuronValues,
+/// State owned by the worker of a mining thread
+///
+/// Nothing in here is shared, so the worker loop needs no locking to use it.
+#[derive(Debug)]
+pub struct WorkerContext {
+    /// Index of the mining thread
+    idx: usize,
+    /// Generation of the worker, outdated once the supervisor replaced it
+    generation: usize,
+    /// Evaluates the nonces of this thread, which never collide with the ones of other threads
+    backend: Box<dyn Backend>,
+    /// Solutions found but not handed over to the submission task yet
+    found: Vec<Solution>,
+    /// Shares found but not handed over to the share task yet
+    shares: Vec<Solution>,
+    /// Iterations not folded into the shared counters yet
+    pending_iterations: usize,
+    /// Last time the iterations were folded into the shared counters
+    flushed_at: Instant,
+    /// Scores not folded into the shared histogram yet
+    score_counts: ScoreCounts,
 }
 
-impl NeuronData {
-    /// Creates a new instance of NeuronData
+impl WorkerContext {
+    /// Creates the context of a mining thread worker
+    ///
+    /// # Arguments
+    /// * `algorithm` - The algorithm scoring the nonces
+    /// * `idx` - Index of the mining thread
+    /// * `generation` - Generation of the worker
+    /// * `keccak_lanes` - Number of nonces whose links are generated in one pass, 1 or `KECCAK_LANES`
+    ///
+    /// # Returns
+    /// A new instance of the WorkerContext struct
+    pub fn new(algorithm: &dyn Algorithm, idx: usize, generation: usize, keccak_lanes: usize) -> Self {
+        WorkerContext {
+            idx,
+            generation,
+            backend: algorithm.create_backend(NonceStream::new(idx, generation), keccak_lanes),
+            found: Vec::new(),
+            shares: Vec::new(),
+            pending_iterations: 0,
+            flushed_at: Instant::now(),
+            score_counts: [0; SCORE_BUCKETS],
+        }
+    }
+
+    /// Evaluate the next batch of nonces of the stream, one pass of the backend
+    ///
+    /// # Arguments
+    /// * `miner` - The miner the worker belongs to
+    fn mine(&mut self, miner: &Miner) {
+        for (nonce, score) in self.backend.solve_batch(self.backend.batch_size()) {
+            self.score_counts[ScoreHistogram::bucket(score)] += 1;
+            if score >= miner.shared.get_solution_threshold() {
+                miner.score_counter.fetch_add(1, Ordering::Relaxed);
+                self.found.push(Solution::new(nonce));
+            }
+            // Solutions are shares too, so a pool credits them
+            if miner.shared.share_threshold.is_some_and(|share_threshold| score >= share_threshold) {
+                miner.share_counter.fetch_add(1, Ordering::Relaxed);
+                self.shares.push(Solution::new(nonce));
+            }
+            self.pending_iterations += 1;
+        }
+    }
+
+    /// Hand the found solutions and shares over to the miner, without waiting for a busy lock
+    ///
+    /// # Arguments
+    /// * `miner` - The miner the worker belongs to
+    fn try_hand_over(&mut self, miner: &Miner) {
+        if !self.found.is_empty() {
+            if let Ok(mut lock) = miner.found_nonce.try_lock() {
+                lock.append(&mut self.found);
+            }
+        }
+        if !self.shares.is_empty() {
+            if let Ok(mut lock) = miner.found_shares.try_lock() {
+                lock.append(&mut self.shares);
+            }
+        }
+    }
+
+    /// Fold the iterations counted locally into the shared counters of the miner
+    ///
+    /// # Arguments
+    /// * `miner` - The miner owning the shared counters
+    fn flush_iterations(&mut self, miner: &Miner) {
+        self.flushed_at = Instant::now();
+        if self.pending_iterations > 0 {
+            miner.iteration_counter.fetch_add(self.pending_iterations, Ordering::Relaxed);
+            miner.thread_iteration_counters[self.idx].fetch_add(self.pending_iterations, Ordering::Relaxed);
+            miner.score_histogram.add(&mut self.score_counts);
+            self.pending_iterations = 0;
+        }
+    }
+
+    /// Check if the iterations counted locally are due to be folded into the shared counters
+    fn is_flush_due(&self) -> bool {
+        self.flushed_at.elapsed() >= COUNTER_FLUSH_INTERVAL
+    }
+}
+
+/// A nonce that reached the solution threshold
+#[derive(Debug, Clone, Copy)]
+pub struct Solution {
+    pub nonce: Nonce64,
+    /// Moment the nonce was found, used to measure submission latency
+    pub found_at: Instant,
+    /// Epoch of the random seed the nonce was mined against, `None` until a node reported it
+    pub epoch: Option<u16>,
+}
+
+impl Solution {
+    /// Creates a new Solution found right now
+    ///
+    /// # Arguments
+    /// * `nonce` - The nonce that reached the solution threshold
+    ///
+    /// # Returns
+    /// A new instance of the Solution struct
+    pub fn new(nonce: Nonce64) -> Self {
+        Solution {
+            nonce,
+            found_at: Instant::now(),
+            epoch: None,
+        }
+    }
+}
+
+/// Counters since the start of the epoch
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct EpochCounters {
+    /// Time since the epoch started, or since the miner was created within the first epoch
+    pub duration: Duration,
+    pub iterations: usize,
+    pub solutions_found: usize,
+    /// Shares found, solutions included
+    pub shares_found: usize,
+}
+
+/// The counters of the miner at one moment, the single source of the displays and exporters
+///
+/// The counters are those since the process started, `epoch` holds the ones of the current epoch.
+#[derive(Debug, Clone, PartialEq)]
+pub struct MinerSnapshot {
+    /// Time since the miner was created
+    pub uptime: Duration,
+    pub iterations: usize,
+    pub solutions_found: usize,
+    /// Shares found, solutions included
+    pub shares_found: usize,
+    /// Number of evaluated nonces per score
+    pub score_counts: ScoreCounts,
+    /// Iterations of every mining thread
+    pub thread_iterations: Vec<usize>,
+    pub solution_threshold: usize,
+    pub share_threshold: Option<usize>,
+    pub active_threads: usize,
+    pub is_paused: bool,
+    pub worker_restarts: usize,
+    /// Name of the algorithm scoring the nonces
+    pub algorithm: &'static str,
+    /// Name of the backend of the last spawned worker, empty before the first one
+    pub backend: &'static str,
+    /// Hex of the first bytes of the hash of the random seed, telling the mining data apart
+    pub seed_fingerprint: String,
+    pub epoch: EpochCounters,
+}
+
+impl MinerSnapshot {
+    /// Measure the iterations done since a marker, and move the marker to this snapshot
+    ///
+    /// Every consumer keeps its own marker, so rates measured over different intervals do not interfere.
+    ///
+    /// # Arguments
+    /// * `marker` - The marker of the consumer, moved to this snapshot
+    ///
+    /// # Returns
+    /// The iterations done since the marker, in total and per thread
+    pub fn iterations_since(&self, marker: &mut IterationMarker) -> IterationDelta {
+        self.iterations_since_at(marker, Instant::now())
+    }
+
+    fn iterations_since_at(&self, marker: &mut IterationMarker, now: Instant) -> IterationDelta {
+        let delta = IterationDelta {
+            iterations: self.iterations.saturating_sub(marker.iterations),
+            thread_iterations: self.thread_iterations.iter().enumerate()
+                .map(|(idx, iterations)| iterations.saturating_sub(marker.thread_iterations.get(idx).copied().unwrap_or_default()))
+                .collect(),
+            elapsed: now.saturating_duration_since(marker.marked_at),
+        };
+        *marker = IterationMarker {
+            iterations: self.iterations,
+            thread_iterations: self.thread_iterations.clone(),
+            marked_at: now,
+        };
+        delta
+    }
+}
+
+/// The iteration counters as one consumer last saw them
+#[derive(Debug, Clone)]
+pub struct IterationMarker {
+    iterations: usize,
+    thread_iterations: Vec<usize>,
+    marked_at: Instant,
+}
+
+impl IterationMarker {
+    /// Create a marker at zero iterations, now, to measure the first delta from the start of mining
     pub fn new() -> Self {
-        NeuronData {
-            neuron_links: [0; NUMBER_OF_NEURONS_64 * 2],
-            neuron_values: [NeuronValue::MAX; NUMBER_OF_NEURONS],
+        IterationMarker {
+            iterations: 0,
+            thread_iterations: Vec::new(),
+            marked_at: Instant::now(),
         }
     }
+
+    /// Get the time elapsed since the marker was last moved
+    pub fn elapsed(&self) -> Duration {
+        self.marked_at.elapsed()
+    }
+}
+
+impl Default for IterationMarker {
+    fn default() -> Self {
+        IterationMarker::new()
+    }
+}
+
+/// The iterations done between two snapshots
+#[derive(Debug, Clone, PartialEq)]
+pub struct IterationDelta {
+    pub iterations: usize,
+    /// Iterations of every mining thread
+    pub thread_iterations: Vec<usize>,
+    pub elapsed: Duration,
+}
+
+impl IterationDelta {
+    /// Get the iterations per second over the delta
+    pub fn it_per_sec(&self) -> f64 {
+        self.iterations as f64 / self.elapsed.as_secs_f64().max(f64::EPSILON)
+    }
+
+    /// Get the iterations per second of every mining thread over the delta
+    pub fn thread_it_per_sec(&self) -> Vec<f64> {
+        let elapsed = self.elapsed.as_secs_f64().max(f64::EPSILON);
+        self.thread_iterations.iter().map(|iterations| *iterations as f64 / elapsed).collect()
+    }
+}
+
+/// Data every mining thread reads, only the solution threshold changes while mining
+#[derive(Debug)]
+pub struct MinerShared {
+    /// Atomic so the threshold can be reloaded while mining
+    solution_threshold: AtomicUsize,
+    share_threshold: Option<usize>,
+    scorer: Scorer,
+}
+
+impl MinerShared {
+    /// Creates the data shared by the mining threads
+    ///
+    /// # Arguments
+    /// * `public_key` - A PublicKey64 used for generating neuron links
+    /// * `params` - The parameters of the algorithm
+    /// * `mining_data` - The mining data scores are computed against, of `params.mining_data_length` words
+    /// * `solution_threshold` - The score a nonce must reach to be a solution
+    /// * `share_threshold` - The score a nonce must reach to be reported as a share, if shares are enabled
+    ///
+    /// # Returns
+    /// A new instance of the MinerShared struct
+    ///
+    /// # Panics
+    /// If the parameters are invalid or do not match the length of the mining data.
+    pub fn new(public_key: PublicKey64, params: AlgorithmParams, mining_data: Box<[MiningItemData]>, solution_threshold: usize, share_threshold: Option<usize>) -> Self {
+        MinerShared {
+            solution_threshold: AtomicUsize::new(solution_threshold),
+            share_threshold,
+            scorer: Scorer::new(public_key, params, mining_data),
+        }
+    }
+
+    /// Generate the mining data of a random seed
+    ///
+    /// # Arguments
+    /// * `random_seed` - The random seed of the network
+    /// * `params` - The parameters of the algorithm, giving the length of the mining data
+    ///
+    /// # Returns
+    /// The mining data, `params.mining_data_length` words
+    pub fn generate_mining_data(random_seed: &Seed64, params: &AlgorithmParams) -> Box<[MiningItemData]> {
+        Scorer::generate_mining_data(random_seed, params)
+    }
+
+    /// Get the parameters of the algorithm
+    pub fn get_params(&self) -> &AlgorithmParams {
+        self.scorer.get_params()
+    }
+
+    /// Get the score a nonce must reach to be a solution
+    pub fn get_solution_threshold(&self) -> usize {
+        self.solution_threshold.load(Ordering::Relaxed)
+    }
+
+    /// Set the score a nonce must reach to be a solution
+    ///
+    /// # Arguments
+    /// * `solution_threshold` - The new threshold, applied from the next evaluated nonce
+    pub fn set_solution_threshold(&self, solution_threshold: usize) {
+        self.solution_threshold.store(solution_threshold, Ordering::Relaxed);
+    }
+
+    /// Get the score a nonce must reach to be reported as a share, `None` if shares are disabled
+    pub fn get_share_threshold(&self) -> Option<usize> {
+        self.share_threshold
+    }
+
+    /// Check whether a nonce is a solution, using the provided neuron data
+    ///
+    /// # Arguments
+    /// * `nonce` - The nonce to evaluate
+    /// * `neuron_data` - A mutable reference to NeuronData for storing neuron links and values
+    ///
+    /// # Returns
+    /// A boolean indicating whether a solution was found
+    pub fn find_solution(&self, nonce: &Nonce64, neuron_data: &mut NeuronData) -> bool {
+        self.expand_links(nonce, neuron_data);
+        self.evaluate(neuron_data)
+    }
+
+    /// Generate the neuron links of a nonce
+    ///
+    /// # Arguments
+    /// * `nonce` - The nonce to generate the links of
+    /// * `neuron_data` - A mutable reference to NeuronData receiving the links
+    pub fn expand_links(&self, nonce: &Nonce64, neuron_data: &mut NeuronData) {
+        self.scorer.expand_links(nonce, neuron_data);
+    }
+
+    /// Generate the neuron links of four nonces in one pass
+    ///
+    /// # Arguments
+    /// * `nonces` - The nonces to generate the links of
+    /// * `neuron_data` - The NeuronData receiving the links, one per nonce
+    pub fn expand_links_x4(&self, nonces: &[Nonce64; KECCAK_LANES], neuron_data: [&mut NeuronData; KECCAK_LANES]) {
+        self.scorer.expand_links_x4(nonces, neuron_data);
+    }
+
+    /// Check whether the nonce whose links were generated in the neuron data is a solution
+    ///
+    /// # Arguments
+    /// * `neuron_data` - A mutable reference to NeuronData holding the links of the nonce
+    ///
+    /// # Returns
+    /// A boolean indicating whether a solution was found
+    pub fn evaluate(&self, neuron_data: &mut NeuronData) -> bool {
+        self.score(neuron_data) >= self.get_solution_threshold()
+    }
+
+    /// Compute the score of the nonce whose links were generated in the neuron data
+    ///
+    /// # Arguments
+    /// * `neuron_data` - A mutable reference to NeuronData holding the links of the nonce
+    ///
+    /// # Returns
+    /// The score of the nonce
+    pub fn score(&self, neuron_data: &mut NeuronData) -> usize {
+        self.scorer.score(neuron_data)
+    }
+
+    /// Compute the score of the nonce whose links were generated in the neuron data, with the given code path
+    ///
+    /// # Arguments
+    /// * `code_path` - The code path to run, which the CPU must support
+    /// * `neuron_data` - A mutable reference to NeuronData holding the links of the nonce
+    ///
+    /// # Returns
+    /// The score of the nonce
+    ///
+    /// # Panics
+    /// If the CPU does not support the code path.
+    pub fn score_with(&self, code_path: CodePath, neuron_data: &mut NeuronData) -> usize {
+        self.scorer.score_with(code_path, neuron_data)
+    }
 }
 
 /// Main mining structure
-#[derive(Debug, Clone)]
+///
+/// The immutable mining data lives in a shared `MinerShared`, while the counters and the
+/// worker state are owned here. `Miner` is not `Clone`: share it through an `Arc`.
+#[derive(Debug)]
 pub struct Miner {
-    solution_threshold: usize,
+    shared: Arc<MinerShared>,
+    /// Replaced when switching algorithms, workers keep the backend they were created with until restarted
+    algorithm: std::sync::RwLock<Arc<dyn Algorithm>>,
     num_threads: usize,
-    mining_data: MiningData,
-    public_key: PublicKey64,
-    score_counter: Arc<AtomicUsize>,
-    iteration_counter: Arc<AtomicUsize>,
-    pub found_nonce: Arc<tokio::sync::Mutex<Vec<Nonce64>>>,
+    placement: ThreadPlacement,
+    keccak_lanes: usize,
+    scheduler: Scheduler,
+    score_counter: AtomicUsize,
+    share_counter: AtomicUsize,
+    iteration_counter: AtomicUsize,
+    thread_iteration_counters: Vec<AtomicUsize>,
+    score_histogram: ScoreHistogram,
+    active_threads: AtomicUsize,
+    is_paused: AtomicBool,
+    worker_generations: Vec<AtomicUsize>,
+    worker_restarts: AtomicUsize,
+    workers: std::sync::Mutex<Vec<Option<thread::JoinHandle<()>>>>,
+    /// Name of the backend of the last spawned worker
+    backend: std::sync::RwLock<&'static str>,
+    seed_fingerprint: String,
+    created_at: Instant,
+    /// Moment the epoch started with the lifetime counters at that moment, subtracted from them for the epoch counters
+    epoch_start: std::sync::Mutex<(Instant, EpochCounters)>,
+    pub found_nonce: tokio::sync::Mutex<Vec<Solution>>,
+    pub found_shares: tokio::sync::Mutex<Vec<Solution>>,
 }
 
 impl Miner {
@@ -76,172 +501,530 @@ impl Miner {
     ///
     /// # Arguments
     /// * `public_key` - A PublicKey64 used for generating neuron links
-    /// * `num_threads` - The number of threads to be used in the mining process
+    /// * `settings` - The settings, giving the random seed and the solution threshold
+    /// * `placement` - The number of threads to be used in the mining process and the cores they are pinned to
+    /// * `keccak_lanes` - Number of nonces each thread generates the links of in one pass, 1 or `KECCAK_LANES`
+    /// * `share_threshold` - The score a nonce must reach to be reported as a share, `None` to disable shares
+    /// * `scheduler` - How nonce attempts are scheduled on the threads
+    /// * `algorithm` - The algorithm scoring the nonces
     ///
     /// # Returns
     /// A new instance of the Miner struct
-    pub fn new(public_key: PublicKey64, num_threads: usize) -> Self {
-        // Generate a random seed for mining data initialization
-        let random_seed = Miner::generate_random_seed();
+    pub fn new(public_key: PublicKey64, settings: &Settings, placement: ThreadPlacement, keccak_lanes: usize, share_threshold: Option<usize>, scheduler: Scheduler, algorithm: &AlgorithmEntry) -> Self {
+        let num_threads = placement.get_num_threads();
 
-        // Initialize mining data with zeroes
-        let mut mining_data: MiningData;
-        unsafe {
-            mining_data = zeroed::<MiningData>();
-        }
+        // Generate a random seed for mining data initialization
+        let random_seed = Miner::generate_random_seed(settings);
 
         // Generate mining data based on the random seed
-        crate::math::random_64(&random_seed, &random_seed, &mining_data);
+        let params = settings.algorithm_params;
+        let mining_data = MinerShared::generate_mining_data(&random_seed, &params);
+
+        let shared = Arc::new(MinerShared::new(public_key, params, mining_data, settings.solution_threshold, share_threshold));
 
         Miner {
-            solution_threshold: get_solution_threshold(),
+            algorithm: std::sync::RwLock::new((algorithm.create)(shared.clone())),
+            shared,
             num_threads,
-            mining_data,
-            public_key,
-            score_counter: Arc::new(AtomicUsize::new(0)),
-            iteration_counter: Arc::new(AtomicUsize::new(0)),
-            found_nonce: Arc::new(tokio::sync::Mutex::new(Vec::new())),
+            placement,
+            keccak_lanes,
+            scheduler,
+            score_counter: AtomicUsize::new(0),
+            share_counter: AtomicUsize::new(0),
+            iteration_counter: AtomicUsize::new(0),
+            thread_iteration_counters: (0..num_threads).map(|_| AtomicUsize::new(0)).collect(),
+            score_histogram: ScoreHistogram::default(),
+            active_threads: AtomicUsize::new(num_threads),
+            is_paused: AtomicBool::new(false),
+            worker_generations: (0..num_threads).map(|_| AtomicUsize::new(0)).collect(),
+            worker_restarts: AtomicUsize::new(0),
+            workers: std::sync::Mutex::new(Vec::new()),
+            backend: std::sync::RwLock::new(""),
+            seed_fingerprint: seed_fingerprint(&settings.random_seed),
+            created_at: Instant::now(),
+            epoch_start: std::sync::Mutex::new((Instant::now(), EpochCounters::default())),
+            found_nonce: tokio::sync::Mutex::new(Vec::new()),
+            found_shares: tokio::sync::Mutex::new(Vec::new()),
+        }
+    }
+
+    /// Take a snapshot of every counter of the miner
+    ///
+    /// # Returns
+    /// The counters, consistent enough for displays: each is read once, without locking the workers
+    pub fn snapshot(&self) -> MinerSnapshot {
+        let (iterations, solutions_found, shares_found) = (self.get_iteration_count(), self.get_score(), self.get_share_count());
+        let (epoch_started_at, at_epoch_start) = *self.epoch_start.lock().unwrap();
+        MinerSnapshot {
+            uptime: self.created_at.elapsed(),
+            iterations,
+            solutions_found,
+            shares_found,
+            score_counts: self.get_score_counts(),
+            thread_iterations: self.get_thread_iteration_counts(),
+            solution_threshold: self.get_solution_threshold(),
+            share_threshold: self.get_share_threshold(),
+            active_threads: self.get_active_threads(),
+            is_paused: self.is_paused(),
+            worker_restarts: self.get_worker_restarts(),
+            algorithm: self.get_algorithm().name(),
+            backend: *self.backend.read().unwrap(),
+            seed_fingerprint: self.seed_fingerprint.clone(),
+            epoch: EpochCounters {
+                duration: epoch_started_at.elapsed(),
+                iterations: iterations.saturating_sub(at_epoch_start.iterations),
+                solutions_found: solutions_found.saturating_sub(at_epoch_start.solutions_found),
+                shares_found: shares_found.saturating_sub(at_epoch_start.shares_found),
+            },
         }
     }
 
+    /// Start the counters of a new epoch from zero, e.g. once the nodes report a new epoch
+    ///
+    /// The counters since the process started are kept, so totals are never lost.
+    pub fn reset_epoch_counters(&self) {
+        let lifetime = EpochCounters {
+            duration: Duration::ZERO,
+            iterations: self.get_iteration_count(),
+            solutions_found: self.get_score(),
+            shares_found: self.get_share_count(),
+        };
+        *self.epoch_start.lock().unwrap() = (Instant::now(), lifetime);
+    }
+
+    /// Mark the iteration counters as they are now, to measure a rate from
+    pub fn marker(&self) -> IterationMarker {
+        let mut marker = IterationMarker::new();
+        self.snapshot().iterations_since(&mut marker);
+        marker
+    }
+
+    /// Measure the iterations done since a marker, and move the marker to now
+    ///
+    /// # Arguments
+    /// * `marker` - The marker of the consumer
+    ///
+    /// # Returns
+    /// The iterations done since the marker, in total and per thread
+    pub fn iterations_since(&self, marker: &mut IterationMarker) -> IterationDelta {
+        self.snapshot().iterations_since(marker)
+    }
+
     /// Get the current score
     ///
     /// # Returns
     /// The current score as a usize
     pub fn get_score(&self) -> usize {
-        self.score_counter.load(Ordering::SeqCst)
+        self.score_counter.load(Ordering::Relaxed)
+    }
+
+    /// Get the number of shares found, solutions included
+    pub fn get_share_count(&self) -> usize {
+        self.share_counter.load(Ordering::Relaxed)
     }
 
     /// Get the current iteration count
     ///
+    /// Mining threads fold their iterations every `COUNTER_FLUSH_INTERVAL`, so the count may lag
+    /// behind by the iterations of that interval per thread.
+    ///
     /// # Returns
     /// The current iteration count as a usize
     pub fn get_iteration_count(&self) -> usize {
-        self.iteration_counter.load(Ordering::SeqCst)
+        self.iteration_counter.load(Ordering::Relaxed)
+    }
+
+    /// Get the score a nonce must reach to be a solution
+    pub fn get_solution_threshold(&self) -> usize {
+        self.shared.get_solution_threshold()
+    }
+
+    /// Set the score a nonce must reach to be a solution, e.g. when the configuration is reloaded
+    pub fn set_solution_threshold(&self, solution_threshold: usize) {
+        self.shared.set_solution_threshold(solution_threshold);
+    }
+
+    /// Get the score a nonce must reach to be reported as a share, `None` if shares are disabled
+    pub fn get_share_threshold(&self) -> Option<usize> {
+        self.shared.get_share_threshold()
+    }
+
+    /// Get the data shared by the mining threads
+    pub fn get_shared(&self) -> Arc<MinerShared> {
+        self.shared.clone()
+    }
+
+    /// Get the algorithm scoring the nonces
+    pub fn get_algorithm(&self) -> Arc<dyn Algorithm> {
+        self.algorithm.read().unwrap().clone()
     }
 
-    /// Generate a random 64-bit seed using the RDRAND instruction
+    /// Switch to another algorithm, replacing every worker so it scores with the new one
+    ///
+    /// # Arguments
+    /// * `miner` - An Arc-wrapped instance of the Miner struct
+    /// * `algorithm` - The algorithm to score the next nonces with
+    pub fn set_algorithm(miner: &Arc<Miner>, algorithm: &AlgorithmEntry) {
+        *miner.algorithm.write().unwrap() = (algorithm.create)(miner.shared.clone());
+
+        let workers = miner.workers.lock().unwrap().len();
+        for idx in 0..workers {
+            Miner::replace_worker(miner, idx);
+        }
+    }
+
+    /// Get the iteration count of every mining thread
+    ///
+    /// # Returns
+    /// The iteration counts, indexed by thread
+    pub fn get_thread_iteration_counts(&self) -> Vec<usize> {
+        self.thread_iteration_counters.iter().map(|counter| counter.load(Ordering::Relaxed)).collect()
+    }
+
+    /// Get the number of evaluated nonces per score
+    ///
+    /// Like the iteration count, the counts lag behind by up to `COUNTER_FLUSH_INTERVAL` per thread.
+    pub fn get_score_counts(&self) -> ScoreCounts {
+        self.score_histogram.get_counts()
+    }
+
+    /// Get the number of spawned mining threads
+    pub fn get_num_threads(&self) -> usize {
+        self.num_threads
+    }
+
+    /// Get the core type a mining thread is pinned to
+    ///
+    /// # Returns
+    /// The core type, `None` if the thread is not pinned
+    pub fn get_core_type(&self, idx: usize) -> Option<CoreType> {
+        self.placement.core_type(idx)
+    }
+
+    /// Get the number of mining threads allowed to work
+    pub fn get_active_threads(&self) -> usize {
+        self.active_threads.load(Ordering::Relaxed)
+    }
+
+    /// Set the number of mining threads allowed to work
+    ///
+    /// Threads above the limit idle until it is raised again.
+    ///
+    /// # Arguments
+    /// * `active_threads` - The new limit, clamped between 1 and the number of spawned threads
+    pub fn set_active_threads(&self, active_threads: usize) {
+        self.active_threads.store(active_threads.clamp(1, self.num_threads.max(1)), Ordering::Relaxed);
+    }
+
+    /// Check if mining is paused
+    pub fn is_paused(&self) -> bool {
+        self.is_paused.load(Ordering::Relaxed)
+    }
+
+    /// Pause or resume mining on all threads
+    ///
+    /// # Arguments
+    /// * `is_paused` - `true` to pause, `false` to resume
+    pub fn set_paused(&self, is_paused: bool) {
+        self.is_paused.store(is_paused, Ordering::Relaxed);
+    }
+
+    /// Get the random seed of the mining data as 64-bit words
+    ///
+    /// # Arguments
+    /// * `settings` - The settings holding the random seed
     ///
     /// # Returns
     /// A 64-bit seed of type Seed64
-    fn generate_random_seed() -> Seed64 {
-        let seed = lib::random_seed::get_random_seed();
-        unsafe { std::mem::transmute(seed) }
+    fn generate_random_seed(settings: &Settings) -> Seed64 {
+        unsafe { std::mem::transmute(settings.random_seed) }
     }
 
-    /// Find a solution using the provided nonce and neuron data
+    /// Check whether a nonce is a solution, using the provided neuron data
     ///
     /// # Arguments
-    /// * `nonce` - A mutable reference to a Nonce64 for storing the generated nonce
+    /// * `nonce` - The nonce to evaluate
     /// * `neuron_data` - A mutable reference to NeuronData for storing neuron links and values
     ///
     /// # Returns
     /// A boolean indicating whether a solution was found
-    pub fn find_solution(&self, nonce: &mut Nonce64, neuron_data: &mut NeuronData) -> bool {
-        // Generate a random nonce
-        nonce.iter_mut().for_each(|item| { *item = generate_random_u64(); });
+    pub fn find_solution(&self, nonce: &Nonce64, neuron_data: &mut NeuronData) -> bool {
+        self.shared.find_solution(nonce, neuron_data)
+    }
+
+    /// Get the number of workers restarted by the supervisor
+    pub fn get_worker_restarts(&self) -> usize {
+        self.worker_restarts.load(Ordering::Relaxed)
+    }
 
-        // Generate neuron links based on public key and nonce
-        crate::math::random_64(&self.public_key, nonce, &mut neuron_data.neuron_links);
+    /// Check if the worker of a mining thread has exited, e.g. after a panic
+    ///
+    /// # Arguments
+    /// * `idx` - Index of the mining thread
+    pub fn is_worker_finished(&self, idx: usize) -> bool {
+        self.workers.lock().unwrap().get(idx).and_then(Option::as_ref).is_some_and(|worker| worker.is_finished())
+    }
+
+    /// Run the mining process across multiple threads
+    ///
+    /// # Arguments
+    /// * `miner` - An Arc-wrapped instance of the Miner struct
+    pub fn run(miner: &Arc<Miner>) {
+        let workers = match miner.scheduler {
+            Scheduler::Threads => (0..miner.num_threads).map(|idx| Some(Miner::spawn_worker(miner, idx))).collect(),
+            #[cfg(feature = "rayon")]
+            Scheduler::Rayon => vec![Some(Miner::spawn_pool(miner))],
+        };
+        *miner.workers.lock().unwrap() = workers;
+    }
 
-        // Mask neuron links to fit neuron mod bits
-        for idx in 0..NUMBER_OF_NEURONS_64 {
-            neuron_data.neuron_links[idx] &= NEURON_MOD_BITS;
-            neuron_data.neuron_links[NUMBER_OF_NEURONS_64 + idx] &= NEURON_MOD_BITS;
+    /// Replace the worker of a mining thread with a new one
+    ///
+    /// The new worker starts with the next generation and mines at once. The previous worker is
+    /// detached, never joined: it exits at its next check of its generation, once its iteration
+    /// returns, and a worker stuck for good only keeps its own thread.
+    ///
+    /// # Arguments
+    /// * `miner` - An Arc-wrapped instance of the Miner struct
+    /// * `idx` - Index of the mining thread
+    pub fn restart_worker(miner: &Arc<Miner>, idx: usize) {
+        Miner::replace_worker(miner, idx);
+        miner.worker_restarts.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Replace the worker of a mining thread, without counting it as a restart
+    ///
+    /// # Arguments
+    /// * `miner` - An Arc-wrapped instance of the Miner struct
+    /// * `idx` - Index of the mining thread, ignored by the rayon scheduler
+    fn replace_worker(miner: &Arc<Miner>, idx: usize) {
+        // Every thread belongs to the single rayon pool, which is replaced as a whole
+        #[cfg(feature = "rayon")]
+        if miner.scheduler == Scheduler::Rayon {
+            miner.worker_generations.iter().for_each(|generation| { generation.fetch_add(1, Ordering::SeqCst); });
+
+            // Dropping the handle of the previous pool detaches it
+            miner.workers.lock().unwrap()[0] = Some(Miner::spawn_pool(miner));
+            return;
         }
 
-        // Mining logic with neuron values and mining data
-        let mut remaining_iterations = MINING_DATA_LENGTH;
-        let mut score: usize = 0;
+        miner.worker_generations[idx].fetch_add(1, Ordering::SeqCst);
 
-        loop {
-            let prev_value0 = neuron_data.neuron_values[NUMBER_OF_NEURONS - 1];
-            let prev_value1 = neuron_data.neuron_values[NUMBER_OF_NEURONS - 2];
+        // Dropping the handle of the previous worker detaches it
+        miner.workers.lock().unwrap()[idx] = Some(Miner::spawn_worker(miner, idx));
+    }
 
-            for idx in 0..NUMBER_OF_NEURONS_64 {
-                let left_idx = idx * 2;
-                let right_idx = idx * 2 + 1;
+    /// Spawn the worker of a mining thread on its own OS thread, off the async runtime
+    ///
+    /// # Arguments
+    /// * `miner` - An Arc-wrapped instance of the Miner struct
+    /// * `idx` - Index of the mining thread
+    ///
+    /// # Returns
+    /// The handle of the spawned worker
+    fn spawn_worker(miner: &Arc<Miner>, idx: usize) -> thread::JoinHandle<()> {
+        let miner_clone = miner.clone();
+        let generation = miner.worker_generations[idx].load(Ordering::SeqCst);
+
+        thread::Builder::new()
+            .name(format!("qiner-miner-{idx}"))
+            .stack_size(STACK_SIZE)
+            .spawn(move || {
+                cores::pin_current_thread(miner_clone.placement.cpus(idx));
+                let mut context = WorkerContext::new(miner_clone.get_algorithm().as_ref(), idx, generation, miner_clone.keccak_lanes);
+                *miner_clone.backend.write().unwrap() = context.backend.name();
+                Miner::work(&miner_clone, &mut context);
+            })
+            .expect("failed to spawn a mining thread")
+    }
+
+    /// Spawn the rayon pool mining with every thread
+    ///
+    /// # Arguments
+    /// * `miner` - An Arc-wrapped instance of the Miner struct
+    ///
+    /// # Returns
+    /// The handle of the thread driving the pool
+    #[cfg(feature = "rayon")]
+    fn spawn_pool(miner: &Arc<Miner>) -> thread::JoinHandle<()> {
+        let miner_clone = miner.clone();
+        let generation = miner.worker_generations[0].load(Ordering::SeqCst);
 
-                let left_neuron0 = (neuron_data.neuron_links[left_idx] as NeuronLink) as usize;
-                let right_neuron0 = ((neuron_data.neuron_links[left_idx] >> size_of::<NeuronLink>() * 8) as NeuronLink) as usize;
+        thread::Builder::new()
+            .name("qiner-pool".to_string())
+            .spawn(move || {
+                Miner::work_pool(&miner_clone, generation);
+            })
+            .expect("failed to spawn the mining pool thread")
+    }
 
-                let left_neuron1 = (neuron_data.neuron_links[right_idx] as NeuronLink) as usize;
-                let right_neuron1 = ((neuron_data.neuron_links[right_idx] >> size_of::<NeuronLink>() * 8) as NeuronLink) as usize;
+    /// Mine with a rayon pool until the supervisor replaces it
+    ///
+    /// Nonces are evaluated in rounds of `RAYON_TASKS_PER_THREAD` batches per active thread.
+    /// Idle threads steal the batches of busy ones, so efficiency cores take a smaller share
+    /// of the round instead of lagging behind. Each pool thread keeps the context of its index.
+    ///
+    /// # Arguments
+    /// * `miner` - The miner the pool belongs to
+    /// * `generation` - Generation of the pool
+    #[cfg(feature = "rayon")]
+    fn work_pool(miner: &Miner, generation: usize) {
+        let algorithm = miner.get_algorithm();
+        let contexts = (0..miner.num_threads)
+            .map(|idx| std::sync::Mutex::new(WorkerContext::new(algorithm.as_ref(), idx, generation, miner.keccak_lanes)))
+            .collect::<Vec<_>>();
+        if let Some(context) = contexts.first() {
+            *miner.backend.write().unwrap() = context.lock().unwrap().backend.name();
+        }
+        let flush_all = || contexts.iter().for_each(|context| {
+            let mut context = context.lock().unwrap();
+            context.flush_iterations(miner);
+            context.try_hand_over(miner);
+        });
+        let mut pool: Option<rayon::ThreadPool> = None;
 
-                let and_result0 = neuron_data.neuron_values[left_neuron0] & neuron_data.neuron_values[right_neuron0];
-                let and_result1 = neuron_data.neuron_values[left_neuron1] & neuron_data.neuron_values[right_neuron1];
-                neuron_data.neuron_values[left_idx] = !(and_result0);
-                neuron_data.neuron_values[right_idx] = !(and_result1);
+        // Exit once the supervisor replaced this pool
+        while miner.worker_generations[0].load(Ordering::Relaxed) == generation {
+            let active_threads = if miner.is_paused() { 0 } else { miner.get_active_threads() };
+            if active_threads == 0 {
+                flush_all();
+                thread::sleep(IDLE_SLEEP);
+                continue;
             }
 
-            let current_value0 = neuron_data.neuron_values[NUMBER_OF_NEURONS - 1];
-            let current_value1 = neuron_data.neuron_values[NUMBER_OF_NEURONS - 2];
-
-            let mining_data_chunk = self.mining_data[score >> 6];
-            let bit_is_set = ((mining_data_chunk >> (score & 63) as MiningItemData) & 1) as u8;
-            if current_value0 != prev_value0 && current_value1 == prev_value1 {
-                if bit_is_set == 0 {
-                    break;
-                }
-                score += 1;
-            } else if current_value1 != prev_value1 && current_value0 == prev_value0 {
-                if bit_is_set == 1 {
-                    break;
-                }
-                score += 1;
-            } else {
-                remaining_iterations -= 1;
-                if remaining_iterations == 0 {
-                    break;
+            // The pool only has the active threads, so the others are idle
+            if pool.as_ref().is_none_or(|pool| pool.current_num_threads() != active_threads) {
+                flush_all();
+                let placement = miner.placement.clone();
+                let builder = rayon::ThreadPoolBuilder::new()
+                    .num_threads(active_threads)
+                    .stack_size(STACK_SIZE)
+                    .thread_name(|idx| format!("qiner-miner-{idx}"))
+                    .start_handler(move |idx| { cores::pin_current_thread(placement.cpus(idx)); });
+                match builder.build() {
+                    Ok(new_pool) => pool = Some(new_pool),
+                    Err(err) => {
+                        log::error!("Failed to build the rayon pool: {err}");
+                        thread::sleep(IDLE_SLEEP);
+                        continue;
+                    }
                 }
             }
+
+            if let Some(pool) = &pool {
+                pool.scope(|scope| {
+                    for _ in 0..active_threads * RAYON_TASKS_PER_THREAD {
+                        scope.spawn(|_| {
+                            let mut context = contexts[rayon::current_thread_index().unwrap_or_default()].lock().unwrap();
+                            context.mine(miner);
+                            context.try_hand_over(miner);
+                            if context.is_flush_due() {
+                                context.flush_iterations(miner);
+                            }
+                        });
+                    }
+                });
+            }
         }
+        flush_all();
 
-        score >= self.solution_threshold
+        // Hand over the solutions and shares that could not be queued yet
+        for context in &contexts {
+            let mut context = context.lock().unwrap();
+            if !context.found.is_empty() {
+                miner.found_nonce.blocking_lock().append(&mut context.found);
+            }
+            if !context.shares.is_empty() {
+                miner.found_shares.blocking_lock().append(&mut context.shares);
+            }
+        }
     }
 
-    /// Run the mining process across multiple threads
+    /// Mine with the worker of a mining thread until the supervisor replaces it
     ///
     /// # Arguments
-    /// * `miner` - An Arc-wrapped instance of the Miner struct
-    pub fn run(miner: &Arc<Miner>) {
-        for idx in 0..miner.num_threads {
-            let miner_clone = miner.clone();
+    /// * `miner` - The miner the worker belongs to
+    /// * `context` - The state owned by the worker
+    fn work(miner: &Miner, context: &mut WorkerContext) {
+        let idx = context.idx;
 
-            tokio::spawn(async move {
-                let mut nonce: Nonce64 = Nonce64::default();
-                let mut neuron_data = NeuronData::default();
-                let mut nonce_for_send: Vec<Nonce64> = Vec::new();
+        // Exit once the supervisor replaced this worker
+        while miner.worker_generations[idx].load(Ordering::Relaxed) == context.generation {
+            // Idle while paused or while this thread is above the active limit
+            if miner.is_paused() || idx >= miner.get_active_threads() {
+                context.flush_iterations(miner);
+                thread::sleep(IDLE_SLEEP);
+                continue;
+            }
 
-                loop {
-                    log::debug!("[{}] Finding solution in Thread Id ({:?})", idx, thread::current().id());
+            log::debug!("[{}] Finding solution in Thread Id ({:?})", idx, thread::current().id());
 
-                    if miner_clone.find_solution(&mut nonce, &mut neuron_data) {
-                        miner_clone.score_counter.fetch_add(1, Ordering::Relaxed);
-                        nonce_for_send.push(nonce);
-                    }
+            context.mine(miner);
 
-                    if !nonce_for_send.is_empty() {
-                        if let Ok(mut lock) = miner_clone.found_nonce.try_lock() {
-                            lock.append(&mut nonce_for_send);
-                        }
-                    }
+            context.try_hand_over(miner);
 
-                    miner_clone.iteration_counter.fetch_add(1, Ordering::Relaxed);
-                }
-            });
+            if context.is_flush_due() {
+                context.flush_iterations(miner);
+            }
+        }
+        context.flush_iterations(miner);
+
+        // Hand over the solutions and shares that could not be queued yet
+        if !context.found.is_empty() {
+            miner.found_nonce.blocking_lock().append(&mut context.found);
+        }
+        if !context.shares.is_empty() {
+            miner.found_shares.blocking_lock().append(&mut context.shares);
         }
     }
 }
 
-/// Generate a random 64-bit number using the RDRAND instruction
+/// Fingerprints the random seed, so snapshots tell the mining data apart without showing the seed
+///
+/// # Arguments
+/// * `random_seed` - The random seed of the mining data
 ///
 /// # Returns
-/// A 64-bit random number
-fn generate_random_u64() -> u64 {
-    let mut value: u64 = 0;
-    unsafe {
-        _rdrand64_step(&mut value);
-    }
-    value
+/// The hex of the first 4 bytes of the KangarooTwelve hash of the seed
+fn seed_fingerprint(random_seed: &[u8]) -> String {
+    let mut fingerprint = [0u8; 4];
+    k12_into(random_seed, &mut fingerprint);
+    fingerprint.iter().map(|byte| format!("{byte:02x}")).collect()
+}
+
+#[test]
+/// Tests that every marker measures its own delta from the snapshots it saw.
+fn test_iteration_marker() {
+    let now = Instant::now();
+    let snapshot = |iterations: usize, thread_iterations: Vec<usize>| MinerSnapshot {
+        uptime: Duration::ZERO,
+        iterations,
+        solutions_found: 0,
+        shares_found: 0,
+        score_counts: [0; SCORE_BUCKETS],
+        thread_iterations,
+        solution_threshold: 0,
+        share_threshold: None,
+        active_threads: 2,
+        is_paused: false,
+        worker_restarts: 0,
+        algorithm: "",
+        backend: "",
+        seed_fingerprint: String::new(),
+        epoch: EpochCounters::default(),
+    };
+    let mut display = IterationMarker { marked_at: now, ..IterationMarker::new() };
+    let mut exporter = display.clone();
+
+    let delta = snapshot(300, vec![100, 200]).iterations_since_at(&mut display, now + Duration::from_secs(2));
+    assert_eq!(delta, IterationDelta { iterations: 300, thread_iterations: vec![100, 200], elapsed: Duration::from_secs(2) });
+    assert_eq!(delta.it_per_sec(), 150.0);
+    assert_eq!(delta.thread_it_per_sec(), vec![50.0, 100.0]);
+
+    let delta = snapshot(500, vec![200, 300]).iterations_since_at(&mut display, now + Duration::from_secs(4));
+    assert_eq!((delta.iterations, delta.thread_iterations, delta.elapsed), (200, vec![100, 100], Duration::from_secs(2)));
+
+    // The exporter did not see the first snapshot
+    let delta = snapshot(500, vec![200, 300]).iterations_since_at(&mut exporter, now + Duration::from_secs(5));
+    assert_eq!((delta.iterations, delta.it_per_sec()), (500, 100.0));
 }
@@ -0,0 +1,296 @@
+//! A fake Qubic node, for end-to-end tests and local development without a real node.
+//!
+//! It accepts the connections of miners, validates the framing of every packet they send,
+//! records the solutions, and optionally responds with the current tick info like a node. Entity
+//! requests are answered with a configured balance.
+
+use std::fs::OpenOptions;
+use std::io::{self, Write};
+use std::mem::size_of;
+use std::net::SocketAddr;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use lib::types::network::protocols::{REQUEST_CURRENT_TICK_INFO, REQUEST_ENTITY, RESPOND_CURRENT_TICK_INFO, RESPOND_ENTITY};
+use lib::types::network::Protocol;
+use lib::types::{Nonce64, PublicKey64};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+use crate::journal::nonce_to_hex;
+use crate::network::{Packet, RequestResponseHeader};
+
+/// Default address the mock node listens on.
+pub const DEFAULT_MOCK_NODE_LISTEN: &str = "127.0.0.1:21841";
+
+/// Size of the header every packet starts with.
+const HEADER_SIZE: usize = size_of::<RequestResponseHeader>();
+
+/// Largest packet accepted, bigger ones close the connection.
+const MAX_PACKET_SIZE: usize = 64 * 1024;
+
+/// Size of the current tick info: duration, epoch, tick, aligned and misaligned votes, initial tick.
+const TICK_INFO_SIZE: usize = 16;
+
+/// Size of a public key, the payload of an entity request.
+const PUBLIC_KEY_SIZE: usize = 32;
+
+/// Size of the amounts, transfer counts and ticks of an entity, then its tick and spectrum index.
+const ENTITY_FIELDS_SIZE: usize = 40;
+
+/// Number of siblings proving an entity is in the spectrum.
+const NUMBER_OF_SIBLINGS: usize = 24;
+
+/// How the mock node behaves.
+#[derive(Debug, Clone, Default)]
+pub struct MockNodeConfig {
+    /// Protocol version the packets must have, any if `None`
+    pub protocol: Option<Protocol>,
+    /// Whether to respond with the current tick info to every packet
+    pub respond: bool,
+    /// Epoch of the tick info
+    pub epoch: u16,
+    /// Tick of the tick info
+    pub tick: u32,
+    /// Balance of every entity requested, `0` for public keys not in the spectrum
+    pub balance: u64,
+    /// File the received solutions are appended to, one `<public key> <nonce>` hex pair per line
+    pub record_file: Option<PathBuf>,
+}
+
+/// What the mock node received so far.
+#[derive(Debug, Clone, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct MockNodeStats {
+    pub connections: usize,
+    pub packets: usize,
+    /// Current tick info requests, the keep-alives of the miner
+    pub tick_info_requests: usize,
+    /// Valid solutions, in the order they were received
+    #[cfg_attr(feature = "serde", serde(with = "crate::forms::solutions"))]
+    pub solutions: Vec<(PublicKey64, Nonce64)>,
+    /// Why the invalid packets were rejected
+    pub invalid: Vec<String>,
+}
+
+/// A running mock node.
+#[derive(Debug)]
+pub struct MockNode {
+    local_addr: SocketAddr,
+    stats: Arc<Mutex<MockNodeStats>>,
+}
+
+impl MockNode {
+    /// Starts a mock node on the current Tokio runtime.
+    ///
+    /// # Arguments
+    /// * `listen` - The address to listen on, with port `0` to let the OS pick one.
+    /// * `config` - How the node behaves.
+    ///
+    /// # Returns
+    /// The running node, or the error of binding the address.
+    pub async fn start(listen: &str, config: MockNodeConfig) -> io::Result<MockNode> {
+        let listener = TcpListener::bind(listen).await?;
+        let local_addr = listener.local_addr()?;
+        let stats = Arc::new(Mutex::new(MockNodeStats::default()));
+        tokio::spawn(accept_task(listener, Arc::new(config), stats.clone()));
+        Ok(MockNode { local_addr, stats })
+    }
+
+    /// Gets the address the node listens on.
+    pub fn local_addr(&self) -> SocketAddr {
+        self.local_addr
+    }
+
+    /// Gets what the node received so far.
+    pub fn stats(&self) -> MockNodeStats {
+        self.stats.lock().unwrap().clone()
+    }
+}
+
+/// Asynchronous task accepting the connections of miners.
+///
+/// # Arguments
+/// * `listener` - The listener of the node
+/// * `config` - How the node behaves
+/// * `stats` - What the node received
+async fn accept_task(listener: TcpListener, config: Arc<MockNodeConfig>, stats: Arc<Mutex<MockNodeStats>>) {
+    loop {
+        match listener.accept().await {
+            Ok((stream, peer)) => {
+                log::debug!("Miner connected from {peer}");
+                stats.lock().unwrap().connections += 1;
+                tokio::spawn(read_packets(stream, config.clone(), stats.clone()));
+            }
+            Err(err) => log::warn!("Failed to accept a miner: {:?}", err),
+        }
+    }
+}
+
+/// Reads the packets of a miner until it disconnects or sends a malformed header.
+///
+/// # Arguments
+/// * `stream` - The connection of the miner
+/// * `config` - How the node behaves
+/// * `stats` - What the node received
+async fn read_packets(mut stream: TcpStream, config: Arc<MockNodeConfig>, stats: Arc<Mutex<MockNodeStats>>) {
+    loop {
+        let mut header = [0u8; HEADER_SIZE];
+        if stream.read_exact(&mut header).await.is_err() {
+            return;
+        }
+
+        // The size is the first 3 bytes, little endian, header included
+        let size = header[0] as usize | (header[1] as usize) << 8 | (header[2] as usize) << 16;
+        if !(HEADER_SIZE..=MAX_PACKET_SIZE).contains(&size) {
+            log::warn!("Dropping a miner sending a packet of {size} bytes");
+            stats.lock().unwrap().invalid.push(format!("packet of {size} bytes"));
+            return;
+        }
+
+        let mut bytes = header.to_vec();
+        bytes.resize(size, 0);
+        if stream.read_exact(&mut bytes[HEADER_SIZE..]).await.is_err() {
+            return;
+        }
+
+        // Current tick info requests are always answered, as by a real node
+        if RequestResponseHeader::from_bytes(&bytes).is_some_and(|header| header.get_type() == REQUEST_CURRENT_TICK_INFO) {
+            stats.lock().unwrap().tick_info_requests += 1;
+            let protocol = config.protocol.unwrap_or(bytes[3]);
+            if stream.write_all(&tick_info_to_bytes(protocol, config.epoch, config.tick)).await.is_err() {
+                return;
+            }
+            continue;
+        }
+
+        if RequestResponseHeader::from_bytes(&bytes).is_some_and(|header| header.get_type() == REQUEST_ENTITY) && size == HEADER_SIZE + PUBLIC_KEY_SIZE {
+            let protocol = config.protocol.unwrap_or(bytes[3]);
+            if stream.write_all(&entity_to_bytes(protocol, &bytes[HEADER_SIZE..], config.balance, config.tick)).await.is_err() {
+                return;
+            }
+            continue;
+        }
+
+        let result = Packet::validate_solution(&bytes, config.protocol);
+        {
+            let mut stats = stats.lock().unwrap();
+            stats.packets += 1;
+            match result {
+                Ok((public_key, nonce)) => {
+                    log::info!("Solution {} for {}", nonce_to_hex(&nonce), nonce_to_hex(&public_key));
+                    if let Some(record_file) = &config.record_file {
+                        if let Err(err) = record(record_file, &public_key, &nonce) {
+                            log::error!("Failed to record the solution to {}: {:?}", record_file.display(), err);
+                        }
+                    }
+                    stats.solutions.push((public_key, nonce));
+                }
+                Err(err) => {
+                    log::warn!("Invalid packet: {err}");
+                    stats.invalid.push(err);
+                }
+            }
+        }
+
+        if config.respond {
+            let protocol = config.protocol.unwrap_or(bytes[3]);
+            if stream.write_all(&tick_info_to_bytes(protocol, config.epoch, config.tick)).await.is_err() {
+                return;
+            }
+        }
+    }
+}
+
+/// Builds the entity message a node sends, its balance received in a single transfer.
+///
+/// # Arguments
+/// * `protocol` - The protocol version of the message.
+/// * `public_key` - The public key requested.
+/// * `balance` - The balance of the entity, `0` for a public key not in the spectrum.
+/// * `tick` - The tick the entity is read at.
+///
+/// # Returns
+/// The bytes of the message, header included, with zero siblings.
+fn entity_to_bytes(protocol: Protocol, public_key: &[u8], balance: u64, tick: u32) -> Vec<u8> {
+    let size = HEADER_SIZE + PUBLIC_KEY_SIZE + ENTITY_FIELDS_SIZE + PUBLIC_KEY_SIZE * NUMBER_OF_SIBLINGS;
+    let header = RequestResponseHeader::new(&RESPOND_ENTITY, &size, &protocol).expect("an entity fits in a header");
+    let transfers = u32::from(balance > 0);
+    let mut bytes = header.to_bytes().to_vec();
+    bytes.extend_from_slice(public_key);
+    bytes.extend_from_slice(&balance.to_le_bytes());
+    bytes.extend_from_slice(&0u64.to_le_bytes());
+    bytes.extend_from_slice(&transfers.to_le_bytes());
+    bytes.extend_from_slice(&0u32.to_le_bytes());
+    bytes.extend_from_slice(&(transfers * tick).to_le_bytes());
+    bytes.extend_from_slice(&0u32.to_le_bytes());
+    bytes.extend_from_slice(&tick.to_le_bytes());
+    bytes.extend_from_slice(&(if balance > 0 { 0i32 } else { -1 }).to_le_bytes());
+    bytes.resize(size, 0);
+    bytes
+}
+
+/// Builds the current tick info message a node sends.
+///
+/// # Arguments
+/// * `protocol` - The protocol version of the message.
+/// * `epoch` - The current epoch.
+/// * `tick` - The current tick.
+///
+/// # Returns
+/// The bytes of the message, header included.
+fn tick_info_to_bytes(protocol: Protocol, epoch: u16, tick: u32) -> Vec<u8> {
+    let header = RequestResponseHeader::new(&RESPOND_CURRENT_TICK_INFO, &(HEADER_SIZE + TICK_INFO_SIZE), &protocol).expect("a tick info fits in a header");
+    let mut bytes = header.to_bytes().to_vec();
+    bytes.extend_from_slice(&1000u16.to_le_bytes());
+    bytes.extend_from_slice(&epoch.to_le_bytes());
+    bytes.extend_from_slice(&tick.to_le_bytes());
+    bytes.extend_from_slice(&[0; 4]);
+    bytes.extend_from_slice(&tick.to_le_bytes());
+    bytes
+}
+
+/// Appends a solution to the record file.
+///
+/// # Arguments
+/// * `path` - The record file.
+/// * `public_key` - The computor public key of the solution.
+/// * `nonce` - The nonce of the solution.
+fn record(path: &Path, public_key: &PublicKey64, nonce: &Nonce64) -> io::Result<()> {
+    let mut file = OpenOptions::new().create(true).append(true).open(path)?;
+    writeln!(file, "{} {}", nonce_to_hex(public_key), nonce_to_hex(nonce))
+}
+
+#[test]
+/// Tests that solutions are recorded, bad packets rejected, and the tick info sent back.
+fn test_mock_node() {
+    use lib::types::network::protocols::BROADCAST_MESSAGE;
+
+    let record_file = std::env::temp_dir().join(format!("qiner-mocknode-test-{}.txt", std::process::id()));
+    let config = MockNodeConfig { protocol: Some(142), respond: true, epoch: 150, tick: 20_000_000, balance: 0, record_file: Some(record_file.clone()) };
+
+    let runtime = tokio::runtime::Builder::new_current_thread().enable_all().build().unwrap();
+    let stats = runtime.block_on(async {
+        let node = MockNode::start("127.0.0.1:0", config).await.unwrap();
+        let mut stream = TcpStream::connect(node.local_addr()).await.unwrap();
+
+        let mut bytes = Packet::solutions_to_bytes(142, &[1, 2, 3, 4], [&[5, 6, 7, 8], &[9, 10, 11, 12]]);
+        bytes.extend(Packet::solutions_to_bytes(141, &[1, 2, 3, 4], [&[0; 4]]));
+        stream.write_all(&bytes).await.unwrap();
+
+        let mut response = [0u8; 3 * (HEADER_SIZE + TICK_INFO_SIZE)];
+        stream.read_exact(&mut response).await.unwrap();
+        let header = RequestResponseHeader::from_bytes(&response).unwrap();
+        assert_eq!((header.get_protocol(), header.get_type(), header.get_size()), (142, RESPOND_CURRENT_TICK_INFO, 24));
+        assert_eq!(response[10..12], 150u16.to_le_bytes());
+
+        // A header announcing more than the largest packet closes the connection
+        stream.write_all(&[0xFF, 0xFF, 0xFF, 142, 0, 0, 0, BROADCAST_MESSAGE]).await.unwrap();
+        assert_eq!(stream.read(&mut response).await.unwrap(), 0);
+        node.stats()
+    });
+
+    assert_eq!((stats.connections, stats.packets), (1, 3));
+    assert_eq!(stats.solutions, [([1, 2, 3, 4], [5, 6, 7, 8]), ([1, 2, 3, 4], [9, 10, 11, 12])]);
+    assert_eq!(stats.invalid, ["protocol 141 instead of 142", "packet of 16777215 bytes"]);
+    assert_eq!(std::fs::read_to_string(&record_file).unwrap().lines().count(), 2);
+    std::fs::remove_file(record_file).unwrap();
+}
@@ -1,14 +1,75 @@
-use std::arch::x86_64::{_rdrand32_step, _rdrand64_step};
-use std::mem::{size_of, transmute, transmute_copy, zeroed};
+use std::mem::{offset_of, size_of, transmute, zeroed};
 use std::ptr;
-use k12::digest::{ExtendableOutputReset, Update};
-use k12::KangarooTwelve;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+use lib::types::network::protocols::BROADCAST_MESSAGE;
 use lib::types::network::{Dejavu, Key, KeyAndNonce, Protocol, Size, Type};
-use lib::types::{Gamma, Nonce, Nonce64, NUMBER_OF_NONCE, NUMBER_OF_NONCE_64, PublicKey64, Signature};
-use lib::version::get_version;
+use lib::types::{Gamma, Nonce, Nonce64, PublicKey64, Signature};
+use crate::bandwidth::BandwidthBudget;
+use crate::clock::ClockSkew;
+use crate::crypto::k12_into;
+use crate::messages::{BroadcastMessageBuilder, Request};
+use crate::rng;
+
+/// Largest size of a message, the header included, that fits in the 3 bytes of the size field.
+pub const MAX_MESSAGE_SIZE: usize = (1 << 24) - 1;
+
+/// Packs the size of a message into the size field of its header.
+///
+/// # Arguments
+/// * `size` - The size of the message, the header included.
+///
+/// # Returns
+/// The 3 bytes of the size, little endian, or why the size does not fit.
+pub fn pack_size(size: usize) -> Result<Size, String> {
+    if size > MAX_MESSAGE_SIZE {
+        return Err(format!("message of {size} bytes, more than the {MAX_MESSAGE_SIZE} bytes of a header"));
+    }
+    let [byte_0, byte_1, byte_2, _] = (size as u32).to_le_bytes();
+    Ok([byte_0, byte_1, byte_2])
+}
+
+/// Unpacks the size field of a header.
+///
+/// # Arguments
+/// * `size` - The 3 bytes of the size, little endian.
+///
+/// # Returns
+/// The size of the message, the header included.
+pub fn unpack_size(size: Size) -> usize {
+    u32::from_le_bytes([size[0], size[1], size[2], 0]) as usize
+}
+
+/// Packs a dejavu into the dejavu field of a header.
+///
+/// # Arguments
+/// * `dejavu` - The dejavu, only its low 24 bits fitting in the field.
+///
+/// # Returns
+/// The 3 bytes of the dejavu, little endian.
+pub fn pack_dejavu(dejavu: u32) -> Dejavu {
+    let [byte_0, byte_1, byte_2, _] = dejavu.to_le_bytes();
+    [byte_0, byte_1, byte_2]
+}
+
+/// Unpacks the dejavu field of a header.
+///
+/// # Arguments
+/// * `dejavu` - The 3 bytes of the dejavu, little endian.
+///
+/// # Returns
+/// The dejavu.
+pub fn unpack_dejavu(dejavu: Dejavu) -> u32 {
+    u32::from_le_bytes([dejavu[0], dejavu[1], dejavu[2], 0])
+}
 
 /// Struct representing the header of a request/response.
+///
+/// Laid out as on the wire: size, protocol, dejavu and type.
 #[derive(Default, Debug, Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[repr(C)]
 pub struct RequestResponseHeader {
     size: Size,
     protocol: Protocol,
@@ -16,23 +77,27 @@ pub struct RequestResponseHeader {
     r#type: Type,
 }
 
+/// Size of a header on the wire.
+const HEADER_SIZE: usize = size_of::<RequestResponseHeader>();
+
 impl RequestResponseHeader {
     /// Creates a new `RequestResponseHeader`.
     ///
     /// # Arguments
     /// * `in_type` - The type of the request/response.
     /// * `in_size` - The size of the request/response.
+    /// * `in_protocol` - The protocol version, from the settings.
     ///
     /// # Returns
-    /// A new `RequestResponseHeader`.
-    pub fn new(in_type: &Type, in_size: &usize) -> Self {
+    /// A new `RequestResponseHeader`, or why the size does not fit in it.
+    pub fn new(in_type: &Type, in_size: &usize, in_protocol: &Protocol) -> Result<Self, String> {
         let mut header: RequestResponseHeader = Default::default();
-        header.set_size(in_size);
-        header.set_protocol();
+        header.set_size(in_size)?;
+        header.set_protocol(in_protocol);
         header.zeroed_dejavu();
         header.set_type(in_type);
 
-        header
+        Ok(header)
     }
 
     /// Gets the size of the request/response.
@@ -40,19 +105,19 @@ impl RequestResponseHeader {
     /// # Returns
     /// The size of the request/response.
     pub fn get_size(&self) -> usize {
-        unsafe {
-            ptr::read_unaligned(&self.size as *const Size as *const usize)
-        }
+        unpack_size(self.size)
     }
 
     /// Sets the size of the request/response.
     ///
     /// # Arguments
     /// * `new_size` - The new size of the request/response.
-    pub fn set_size(&mut self, new_size: &usize) {
-        unsafe {
-            self.size = transmute_copy::<usize, Size>(new_size);
-        }
+    ///
+    /// # Returns
+    /// `Ok`, or why the size does not fit in the 3 bytes of the field, which is then left unchanged.
+    pub fn set_size(&mut self, new_size: &usize) -> Result<(), String> {
+        self.size = pack_size(*new_size)?;
+        Ok(())
     }
 
     /// Gets the protocol version.
@@ -63,10 +128,74 @@ impl RequestResponseHeader {
         self.protocol
     }
 
-    /// Sets the protocol version to the current version.
-    pub fn set_protocol(&mut self) {
-        let version = get_version();
-        self.protocol = version[1];
+    /// Sets the protocol version.
+    ///
+    /// # Arguments
+    /// * `new_protocol` - The new protocol version.
+    pub fn set_protocol(&mut self, new_protocol: &Protocol) {
+        self.protocol = *new_protocol;
+    }
+
+    /// Sets the protocol version of every message of a buffer, for a peer expecting another protocol than the one they were built with.
+    ///
+    /// # Arguments
+    /// * `bytes` - The messages, back to back as they are written to a peer.
+    /// * `new_protocol` - The protocol version of the peer.
+    ///
+    /// # Returns
+    /// The number of messages changed, the bytes after a truncated or empty message being left as they are.
+    pub fn set_protocol_of_messages(bytes: &mut [u8], new_protocol: &Protocol) -> usize {
+        let mut offset = 0;
+        let mut changed = 0;
+        while let Some(mut header) = RequestResponseHeader::from_bytes(&bytes[offset..]) {
+            let size = header.get_size();
+            if size < HEADER_SIZE || offset + size > bytes.len() {
+                break;
+            }
+            header.set_protocol(new_protocol);
+            bytes[offset..offset + HEADER_SIZE].copy_from_slice(&header.to_bytes());
+            offset += size;
+            changed += 1;
+        }
+        changed
+    }
+
+    /// Reads a header from the first bytes of a message.
+    ///
+    /// # Arguments
+    /// * `bytes` - The bytes of the message, at least the size of a header.
+    ///
+    /// # Returns
+    /// The header, or `None` if there are not enough bytes.
+    pub fn from_bytes(bytes: &[u8]) -> Option<Self> {
+        let bytes: &[u8; HEADER_SIZE] = bytes.get(..HEADER_SIZE)?.try_into().ok()?;
+        Some(RequestResponseHeader {
+            size: [bytes[0], bytes[1], bytes[2]],
+            protocol: bytes[3],
+            dejavu: [bytes[4], bytes[5], bytes[6]],
+            r#type: bytes[7],
+        })
+    }
+
+    /// Gets the bytes of the header, as written to a peer.
+    pub fn to_bytes(&self) -> [u8; HEADER_SIZE] {
+        [self.size[0], self.size[1], self.size[2], self.protocol, self.dejavu[0], self.dejavu[1], self.dejavu[2], self.r#type]
+    }
+
+    /// Gets the dejavu field.
+    ///
+    /// # Returns
+    /// The dejavu, `0` for a message the node may relay to its peers.
+    pub fn get_dejavu(&self) -> u32 {
+        unpack_dejavu(self.dejavu)
+    }
+
+    /// Sets the dejavu field.
+    ///
+    /// # Arguments
+    /// * `new_dejavu` - The new dejavu, only its low 24 bits being kept.
+    pub fn set_dejavu(&mut self, new_dejavu: u32) {
+        self.dejavu = pack_dejavu(new_dejavu);
     }
 
     /// Checks if the dejavu field is zeroed.
@@ -74,26 +203,17 @@ impl RequestResponseHeader {
     /// # Returns
     /// `true` if the dejavu field is zeroed, `false` otherwise.
     pub fn is_dejavu_zero(&self) -> bool {
-        self.dejavu.iter().all(|item| *item == 0u8)
+        self.get_dejavu() == 0
     }
 
     /// Zeroes the dejavu field.
     pub fn zeroed_dejavu(&mut self) {
-        unsafe {
-            self.dejavu = zeroed::<Dejavu>();
-        }
+        self.set_dejavu(0);
     }
 
     /// Randomizes the dejavu field using a random 32-bit integer.
     pub fn randomize_dejavu(&mut self) {
-        assert!(size_of::<Dejavu>() <= size_of::<u32>());
-
-        let mut random: u32 = 0;
-        unsafe { _rdrand32_step(&mut random) };
-
-        unsafe {
-            self.dejavu = transmute_copy::<u32, Dejavu>(&random);
-        }
+        self.set_dejavu(rng::random_u64() as u32);
     }
 
     /// Gets the type of the request/response.
@@ -115,9 +235,15 @@ impl RequestResponseHeader {
 
 /// Struct representing a message.
 #[derive(Default, Debug, Copy, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[repr(C)]
+#[allow(dead_code)]
 pub struct Message {
+    #[cfg_attr(feature = "serde", serde(with = "crate::forms::id"))]
     source_public_key: PublicKey64,
+    #[cfg_attr(feature = "serde", serde(with = "crate::forms::id"))]
     destination_public_key: PublicKey64,
+    #[cfg_attr(feature = "serde", serde(with = "crate::forms::hex"))]
     gamming_nonce: Nonce64,
 }
 
@@ -132,14 +258,43 @@ impl Message {
 }
 
 /// Struct representing a packet.
+///
+/// Fields are only ever read through the byte view of the packet sent over the wire, so
+/// the fields keep their declaration order, the header first.
 #[derive(Debug, Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[repr(C)]
+#[allow(dead_code)]
 pub struct Packet {
     header: RequestResponseHeader,
     message: Message,
+    #[cfg_attr(feature = "serde", serde(with = "crate::forms::hex"))]
     solution_nonce: Nonce64,
+    #[cfg_attr(feature = "serde", serde(with = "crate::forms::hex"))]
     signature: Signature,
 }
 
+// The structures are sent as their bytes, so any change of their layout would corrupt every
+// submission: the sizes and offsets are those the Qubic node reads
+const _: () = {
+    assert!(size_of::<RequestResponseHeader>() == 8);
+    assert!(offset_of!(RequestResponseHeader, size) == 0);
+    assert!(offset_of!(RequestResponseHeader, protocol) == 3);
+    assert!(offset_of!(RequestResponseHeader, dejavu) == 4);
+    assert!(offset_of!(RequestResponseHeader, r#type) == 7);
+
+    assert!(size_of::<Message>() == 96);
+    assert!(offset_of!(Message, source_public_key) == 0);
+    assert!(offset_of!(Message, destination_public_key) == 32);
+    assert!(offset_of!(Message, gamming_nonce) == 64);
+
+    assert!(size_of::<Packet>() == 200);
+    assert!(offset_of!(Packet, header) == 0);
+    assert!(offset_of!(Packet, message) == 8);
+    assert!(offset_of!(Packet, solution_nonce) == 104);
+    assert!(offset_of!(Packet, signature) == 136);
+};
+
 impl Packet {
     /// Creates a new `Packet`.
     ///
@@ -147,97 +302,488 @@ impl Packet {
     /// * `r#type` - The type of the packet.
     /// * `computor_public_key` - The public key of the computor.
     /// * `in_nonce` - The nonce to be used in the packet.
+    /// * `in_protocol` - The protocol version, from the settings.
     ///
     /// # Returns
     /// A new `Packet`.
-    pub fn new(r#type: &Type, computor_public_key: &PublicKey64, in_nonce: &Nonce64) -> Self {
-        //*****************************
-        // Header
-        //*****************************
+    pub fn new(r#type: &Type, computor_public_key: &PublicKey64, in_nonce: &Nonce64, in_protocol: &Protocol) -> Self {
+        let bytes = BroadcastMessageBuilder::solution(*computor_public_key, in_nonce).to_bytes(*in_protocol).expect("a packet fits in a header");
+        debug_assert_eq!(bytes.len(), size_of::<Packet>());
+        let mut packet = unsafe { ptr::read_unaligned(bytes.as_ptr() as *const Packet) };
+        packet.header.set_type(r#type);
+        packet
+    }
 
-        let header: RequestResponseHeader = RequestResponseHeader::new(r#type, &size_of::<Packet>());
+    /// Generates a random signature.
+    ///
+    /// # Returns
+    /// A random `Signature`.
+    pub fn get_random_signature() -> Signature {
+        let mut signature = Signature::default();
+        signature.iter_mut().for_each(|item: &mut u64| *item = rng::random_u64());
 
-        //*****************************
-        // Message
-        //*****************************
+        signature
+    }
 
-        let mut message = Message::default();
-        message.source_public_key = PublicKey64::default();
-        message.destination_public_key = *computor_public_key;
+    /// Recovers the computor public key and the solution nonce of a broadcast packet.
+    ///
+    /// The gamming key is derived from a zero shared key, so any peer can reverse the gamming.
+    ///
+    /// # Arguments
+    /// * `bytes` - The bytes of the packet, as written by `solutions_to_bytes`.
+    ///
+    /// # Returns
+    /// The public key and the nonce, or `None` if the bytes are not a broadcast solution packet.
+    pub fn decode_solution(bytes: &[u8]) -> Option<(PublicKey64, Nonce64)> {
+        Packet::validate_solution(bytes, None).ok()
+    }
 
-        let mut kangaroo_twelve = KangarooTwelve::default();
+    /// Checks the framing of a broadcast solution packet and recovers its public key and nonce.
+    ///
+    /// # Arguments
+    /// * `bytes` - The bytes of the packet, as written by `solutions_to_bytes`.
+    /// * `protocol` - The protocol version the packet must have, any if `None`.
+    ///
+    /// # Returns
+    /// The public key and the nonce, or why the bytes are not a valid broadcast solution packet.
+    pub fn validate_solution(bytes: &[u8], protocol: Option<Protocol>) -> Result<(PublicKey64, Nonce64), String> {
+        if bytes.len() != size_of::<Packet>() {
+            return Err(format!("{} bytes instead of {}", bytes.len(), size_of::<Packet>()));
+        }
+        let packet = unsafe { ptr::read_unaligned(bytes.as_ptr() as *const Packet) };
+        if packet.header.get_size() != size_of::<Packet>() {
+            return Err(format!("header size {} instead of {}", packet.header.get_size(), size_of::<Packet>()));
+        }
+        if packet.header.get_type() != BROADCAST_MESSAGE {
+            return Err(format!("type {} instead of {BROADCAST_MESSAGE}", packet.header.get_type()));
+        }
+        if let Some(protocol) = protocol.filter(|protocol| *protocol != packet.header.get_protocol()) {
+            return Err(format!("protocol {} instead of {protocol}", packet.header.get_protocol()));
+        }
 
+        // Same derivation as in `new`: gamming key, then gamma
         let mut shared_key_and_gamming_nonce: KeyAndNonce = unsafe { zeroed::<KeyAndNonce>() };
+        let gamming_nonce = unsafe { transmute::<Nonce64, Nonce>(packet.message.gamming_nonce) };
+        shared_key_and_gamming_nonce[Key::default().len()..].copy_from_slice(gamming_nonce.as_slice());
+
         let mut gamming_key: Key = Key::default();
-        let mut nonce_buffer: Nonce = Nonce::default();
+        k12_into(shared_key_and_gamming_nonce.as_slice(), gamming_key.as_mut());
+        // Nodes only treat messages whose gamming key starts with 0 as solutions
+        if gamming_key[0] != 0 {
+            return Err(format!("gamming key of message type {} instead of 0", gamming_key[0]));
+        }
 
-        let nonce_chunk_size = NUMBER_OF_NONCE / NUMBER_OF_NONCE_64;
-        loop {
-            nonce_buffer.chunks_mut(nonce_chunk_size).for_each(|items| {
-                let item_64 = items.as_mut_ptr() as *mut u64;
-                unsafe {
-                    _rdrand64_step(item_64.as_mut().unwrap());
-                }
-            });
+        let mut gamma: Gamma = Gamma::default();
+        k12_into(gamming_key.as_slice(), gamma.as_mut_slice());
 
-            shared_key_and_gamming_nonce[(gamming_key.len())..].copy_from_slice(nonce_buffer.as_slice());
+        let mut nonce = unsafe { transmute::<Nonce64, Nonce>(packet.solution_nonce) };
+        nonce.iter_mut().zip(gamma.iter()).for_each(|(nonce_value, gamma_value)| *nonce_value ^= *gamma_value);
 
-            kangaroo_twelve.update(shared_key_and_gamming_nonce.as_slice());
-            kangaroo_twelve.finalize_xof_reset_into(gamming_key.as_mut());
+        Ok((packet.message.destination_public_key, unsafe { transmute::<Nonce, Nonce64>(nonce) }))
+    }
 
-            if (gamming_key[0]) == 0 {
-                break;
-            }
+    /// Builds the broadcast packets of solutions, back to back as they are written to a peer.
+    ///
+    /// # Arguments
+    /// * `protocol` - The protocol version, from the settings.
+    /// * `computor_public_key` - The public key of the computor.
+    /// * `nonces` - The nonces of the solutions.
+    ///
+    /// # Returns
+    /// The bytes of all the packets.
+    pub fn solutions_to_bytes<'a>(protocol: Protocol, computor_public_key: &PublicKey64, nonces: impl IntoIterator<Item = &'a Nonce64>) -> Vec<u8> {
+        nonces.into_iter().flat_map(|nonce| Packet::new(&BROADCAST_MESSAGE, computor_public_key, nonce, &protocol).to_bytes()).collect()
+    }
+
+    /// Gets the bytes of the packet, as written to a peer.
+    pub fn to_bytes(self) -> [u8; size_of::<Packet>()] {
+        unsafe { transmute::<Packet, [u8; size_of::<Packet>()]>(self) }
+    }
+}
+
+/// Struct representing the current tick info a node answers a current tick info request with.
+///
+/// Laid out as on the wire, after the header.
+#[derive(Default, Debug, Clone, Copy, PartialEq)]
+#[repr(C)]
+pub struct TickInfo {
+    /// Duration of the last tick, in milliseconds
+    pub tick_duration: u16,
+    pub epoch: u16,
+    pub tick: u32,
+    pub number_of_aligned_votes: u16,
+    pub number_of_misaligned_votes: u16,
+    /// First tick of the epoch
+    pub initial_tick: u32,
+}
+
+impl TickInfo {
+    /// Parses the payload of a current tick info response.
+    ///
+    /// # Arguments
+    /// * `bytes` - The payload, after the header.
+    ///
+    /// # Returns
+    /// The tick info, `None` if the payload is too short.
+    pub fn from_bytes(bytes: &[u8]) -> Option<Self> {
+        if bytes.len() < size_of::<TickInfo>() {
+            return None;
+        }
+        let u16_at = |offset: usize| u16::from_le_bytes([bytes[offset], bytes[offset + 1]]);
+        let u32_at = |offset: usize| u32::from_le_bytes(bytes[offset..offset + 4].try_into().unwrap());
+        Some(TickInfo {
+            tick_duration: u16_at(0),
+            epoch: u16_at(2),
+            tick: u32_at(4),
+            number_of_aligned_votes: u16_at(8),
+            number_of_misaligned_votes: u16_at(10),
+            initial_tick: u32_at(12),
+        })
+    }
+}
+
+/// Struct representing the entity of a public key in the spectrum, as a node answers an entity request.
+///
+/// Laid out as on the wire, after the header, without the siblings proving it is in the spectrum.
+#[derive(Default, Debug, Clone, Copy, PartialEq)]
+#[repr(C)]
+pub struct Entity {
+    pub public_key: PublicKey64,
+    pub incoming_amount: i64,
+    pub outgoing_amount: i64,
+    pub number_of_incoming_transfers: u32,
+    pub number_of_outgoing_transfers: u32,
+    pub latest_incoming_transfer_tick: u32,
+    pub latest_outgoing_transfer_tick: u32,
+    /// Tick the entity was read at
+    pub tick: u32,
+    /// Index of the entity in the spectrum, negative if the public key is not in it
+    pub spectrum_index: i32,
+}
+
+impl Entity {
+    /// Parses the payload of an entity response.
+    ///
+    /// # Arguments
+    /// * `bytes` - The payload, after the header.
+    ///
+    /// # Returns
+    /// The entity, `None` if the payload is too short.
+    pub fn from_bytes(bytes: &[u8]) -> Option<Self> {
+        if bytes.len() < size_of::<Entity>() {
+            return None;
         }
-        message.gamming_nonce = unsafe { transmute::<Nonce, Nonce64>(nonce_buffer) };
+        let u32_at = |offset: usize| u32::from_le_bytes(bytes[offset..offset + 4].try_into().unwrap());
+        let i64_at = |offset: usize| i64::from_le_bytes(bytes[offset..offset + 8].try_into().unwrap());
+        let mut public_key = PublicKey64::default();
+        public_key.iter_mut().enumerate().for_each(|(index, value)| *value = u64::from_le_bytes(bytes[index * 8..index * 8 + 8].try_into().unwrap()));
+        Some(Entity {
+            public_key,
+            incoming_amount: i64_at(32),
+            outgoing_amount: i64_at(40),
+            number_of_incoming_transfers: u32_at(48),
+            number_of_outgoing_transfers: u32_at(52),
+            latest_incoming_transfer_tick: u32_at(56),
+            latest_outgoing_transfer_tick: u32_at(60),
+            tick: u32_at(64),
+            spectrum_index: u32_at(68) as i32,
+        })
+    }
 
-        //*****************************
-        // Solution nonce
-        //*****************************
+    /// Gets the balance, the amount received minus the amount spent.
+    pub fn get_balance(&self) -> i64 {
+        self.incoming_amount - self.outgoing_amount
+    }
 
-        // Get Gamma
-        let mut gamma: Gamma = Gamma::default();
-        kangaroo_twelve.update(gamming_key.as_slice());
-        kangaroo_twelve.finalize_xof_reset_into(gamma.as_mut_slice());
-
-        // Make solution nonce 
-        let nonce_u8_ptr = in_nonce.as_ptr() as *const Nonce;
-        unsafe {
-            nonce_buffer.iter_mut().zip(nonce_u8_ptr.read().iter()).zip(gamma.as_slice()).for_each(|((nonce_buffer_value, in_nonce_value), gamma_value)| {
-                *nonce_buffer_value = *in_nonce_value ^ *gamma_value;
-            });
+    /// Checks if the public key is in the spectrum, having ever received an amount.
+    pub fn is_in_spectrum(&self) -> bool {
+        self.spectrum_index >= 0
+    }
+}
+
+/// Counters describing how the miner interacts with the network.
+///
+/// Shared between the submission task, which records events, and the display task,
+/// which reports them, so slow submission can be told apart from low hashrate.
+#[derive(Debug, Default)]
+pub struct NetStats {
+    bytes_sent: AtomicU64,
+    packets_sent: AtomicU64,
+    connect_attempts: AtomicU64,
+    connect_failures: AtomicU64,
+    latency_total_ms: AtomicU64,
+    latency_max_ms: AtomicU64,
+    packets_built: AtomicU64,
+    packet_build_total_us: AtomicU64,
+    failing_since: Mutex<Option<Instant>>,
+    node_protocol: Mutex<Option<Protocol>>,
+    protocol_mismatches: AtomicU64,
+    epoch: Mutex<Option<u16>>,
+    /// Epoch of the last computor list and the index of the ID in it
+    computor: Mutex<Option<(u16, Option<usize>)>>,
+    bandwidth: BandwidthBudget,
+    clock: ClockSkew,
+}
+
+impl NetStats {
+    /// Records an attempt to connect to the server.
+    pub fn record_connect_attempt(&self) {
+        self.connect_attempts.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Records a failed attempt to connect to the server.
+    pub fn record_connect_failure(&self) {
+        self.connect_failures.fetch_add(1, Ordering::Relaxed);
+        self.failing_since.lock().unwrap().get_or_insert_with(Instant::now);
+    }
+
+    /// Records a successful connection to the server.
+    pub fn record_connect_success(&self) {
+        *self.failing_since.lock().unwrap() = None;
+    }
+
+    /// Records a successful write of packets to the server.
+    ///
+    /// # Arguments
+    /// * `bytes` - The number of bytes written.
+    /// * `packets` - The number of packets written.
+    pub fn record_sent(&self, bytes: usize, packets: usize) {
+        self.bandwidth.record(bytes);
+        self.bytes_sent.fetch_add(bytes as u64, Ordering::Relaxed);
+        self.packets_sent.fetch_add(packets as u64, Ordering::Relaxed);
+    }
+
+    /// Records the time elapsed between finding a solution and sending it.
+    ///
+    /// # Arguments
+    /// * `latency` - The time from found to sent.
+    pub fn record_latency(&self, latency: Duration) {
+        let latency_ms = latency.as_millis() as u64;
+        self.latency_total_ms.fetch_add(latency_ms, Ordering::Relaxed);
+        self.latency_max_ms.fetch_max(latency_ms, Ordering::Relaxed);
+    }
+
+    /// Records the building of solution packets.
+    ///
+    /// # Arguments
+    /// * `packets` - The number of packets built.
+    /// * `elapsed` - The time the building took, on one thread.
+    pub fn record_packet_build(&self, packets: usize, elapsed: Duration) {
+        self.packets_built.fetch_add(packets as u64, Ordering::Relaxed);
+        self.packet_build_total_us.fetch_add(elapsed.as_micros() as u64, Ordering::Relaxed);
+    }
+
+    /// Records the protocol version of a message received from a node.
+    ///
+    /// # Arguments
+    /// * `protocol` - The protocol byte of the message header.
+    /// * `own_protocol` - The protocol version packets are sent with.
+    ///
+    /// # Returns
+    /// The previously seen protocol version, `None` if no message was received before.
+    pub fn record_node_protocol(&self, protocol: Protocol, own_protocol: Protocol) -> Option<Protocol> {
+        if protocol != own_protocol {
+            self.protocol_mismatches.fetch_add(1, Ordering::Relaxed);
         }
-        let solution_nonce = unsafe { transmute::<Nonce, Nonce64>(nonce_buffer) };
-
-        //*****************************
-        // Signature
-        //*****************************
-        let signature = Packet::get_random_signature();
-
-        //*****************************
-        // Packet
-        //*****************************
-
-        Packet {
-            header,
-            message,
-            solution_nonce,
-            signature,
+        self.node_protocol.lock().unwrap().replace(protocol)
+    }
+
+    /// Records the epoch of a tick info received from a node.
+    ///
+    /// # Arguments
+    /// * `epoch` - The epoch of the tick info.
+    pub fn record_epoch(&self, epoch: u16) {
+        *self.epoch.lock().unwrap() = Some(epoch);
+    }
+
+    /// Gets the epoch of the last tick info received from a node.
+    pub fn get_epoch(&self) -> Option<u16> {
+        *self.epoch.lock().unwrap()
+    }
+
+    /// Records the position of the ID among the computors of an epoch.
+    ///
+    /// # Arguments
+    /// * `epoch` - The epoch of the computor list.
+    /// * `index` - The index of the ID among the computors, `None` if it is not one of them.
+    pub fn record_computor(&self, epoch: u16, index: Option<usize>) {
+        *self.computor.lock().unwrap() = Some((epoch, index));
+    }
+
+    /// Gets the epoch of the last computor list received from a node and the index of the ID in it.
+    pub fn get_computor(&self) -> Option<(u16, Option<usize>)> {
+        *self.computor.lock().unwrap()
+    }
+
+    /// Gets the protocol version of the last message received from a node.
+    pub fn get_node_protocol(&self) -> Option<Protocol> {
+        *self.node_protocol.lock().unwrap()
+    }
+
+    /// Gets the number of messages received with another protocol version than ours.
+    pub fn get_protocol_mismatches(&self) -> u64 {
+        self.protocol_mismatches.load(Ordering::Relaxed)
+    }
+
+    /// Gets the total number of bytes sent.
+    pub fn get_bytes_sent(&self) -> u64 {
+        self.bytes_sent.load(Ordering::Relaxed)
+    }
+
+    /// Gets the total number of packets sent.
+    pub fn get_packets_sent(&self) -> u64 {
+        self.packets_sent.load(Ordering::Relaxed)
+    }
+
+    /// Gets the total number of connect attempts.
+    pub fn get_connect_attempts(&self) -> u64 {
+        self.connect_attempts.load(Ordering::Relaxed)
+    }
+
+    /// Gets the total number of failed connect attempts.
+    pub fn get_connect_failures(&self) -> u64 {
+        self.connect_failures.load(Ordering::Relaxed)
+    }
+
+    /// Gets the moment connect attempts started failing.
+    ///
+    /// # Returns
+    /// The time of the first failure since the last successful connect, or `None` if the last attempt succeeded.
+    pub fn get_failing_since(&self) -> Option<Instant> {
+        *self.failing_since.lock().unwrap()
+    }
+
+    /// Gets the average time from found to sent over all sent packets.
+    ///
+    /// # Returns
+    /// The average latency, or zero if nothing has been sent yet.
+    pub fn get_average_latency(&self) -> Duration {
+        let packets_sent = self.get_packets_sent();
+        if packets_sent == 0 {
+            return Duration::ZERO;
         }
+        Duration::from_millis(self.latency_total_ms.load(Ordering::Relaxed) / packets_sent)
     }
 
-    /// Generates a random signature.
+    /// Gets the average time building a solution packet took.
     ///
     /// # Returns
-    /// A random `Signature`.
-    pub fn get_random_signature() -> Signature {
-        let mut signature = Signature::default();
-        signature.iter_mut().for_each(|item: &mut u64| {
-            unsafe {
-                _rdrand64_step(item);
-            }
-        });
+    /// The average build time, or zero if no packet has been built yet.
+    pub fn get_average_packet_build_time(&self) -> Duration {
+        let packets_built = self.packets_built.load(Ordering::Relaxed);
+        if packets_built == 0 {
+            return Duration::ZERO;
+        }
+        Duration::from_micros(self.packet_build_total_us.load(Ordering::Relaxed) / packets_built)
+    }
 
-        signature
+    /// Gets the longest time from found to sent observed so far.
+    pub fn get_max_latency(&self) -> Duration {
+        Duration::from_millis(self.latency_max_ms.load(Ordering::Relaxed))
+    }
+
+    /// Gets the bandwidth accounting, which the sent packets are counted in and the other traffic is checked against.
+    pub fn get_bandwidth(&self) -> &BandwidthBudget {
+        &self.bandwidth
+    }
+
+    /// Gets the clock skew detection, fed with the tick info of the nodes.
+    pub fn get_clock(&self) -> &ClockSkew {
+        &self.clock
+    }
+}
+
+/// Serializes a snapshot of the counters, the latencies in milliseconds.
+#[cfg(feature = "serde")]
+impl serde::Serialize for NetStats {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        use serde::ser::SerializeStruct;
+
+        let mut stats = serializer.serialize_struct("NetStats", 13)?;
+        stats.serialize_field("bytes_sent", &self.get_bytes_sent())?;
+        stats.serialize_field("packets_sent", &self.get_packets_sent())?;
+        stats.serialize_field("connect_attempts", &self.get_connect_attempts())?;
+        stats.serialize_field("connect_failures", &self.get_connect_failures())?;
+        stats.serialize_field("average_latency_ms", &(self.get_average_latency().as_millis() as u64))?;
+        stats.serialize_field("max_latency_ms", &(self.get_max_latency().as_millis() as u64))?;
+        stats.serialize_field("average_packet_build_us", &(self.get_average_packet_build_time().as_micros() as u64))?;
+        stats.serialize_field("node_protocol", &self.get_node_protocol())?;
+        stats.serialize_field("protocol_mismatches", &self.get_protocol_mismatches())?;
+        stats.serialize_field("epoch", &self.get_epoch())?;
+        stats.serialize_field("bytes_last_hour", &self.bandwidth.get_bytes_last_hour())?;
+        stats.serialize_field("deferred_sends", &self.bandwidth.get_deferred())?;
+        stats.serialize_field("clock_skew_ms", &self.clock.get_skew_ms())?;
+        stats.end()
+    }
+}
+
+#[test]
+/// Tests that the public key and the nonce of a solution packet can be recovered.
+fn test_decode_solution() {
+    let public_key: PublicKey64 = [1, 2, 3, 4];
+    let nonce: Nonce64 = [5, 6, 7, u64::MAX];
+    let bytes = Packet::solutions_to_bytes(142, &public_key, [&nonce]);
+
+    assert_eq!(Packet::decode_solution(&bytes), Some((public_key, nonce)));
+    assert_eq!(Packet::decode_solution(&bytes[1..]), None);
+    assert_eq!(Packet::validate_solution(&bytes, Some(142)), Ok((public_key, nonce)));
+    assert_eq!(Packet::validate_solution(&bytes, Some(143)), Err("protocol 142 instead of 143".to_string()));
+    // Another gamming nonce gives another message type, for all but 1 in 256 of them
+    let mut corrupted = bytes.clone();
+    assert!((0..=u8::MAX).any(|value| {
+        corrupted[size_of::<RequestResponseHeader>() + 64] = value;
+        Packet::validate_solution(&corrupted, None).is_err_and(|err| err.starts_with("gamming key"))
+    }));
+
+    // The header leads the packet, as the node expects it
+    assert_eq!(bytes[..3], (size_of::<Packet>() as u32).to_le_bytes()[..3]);
+    assert_eq!(bytes[7], BROADCAST_MESSAGE);
+
+    let header = RequestResponseHeader::from_bytes(&bytes).unwrap();
+    assert_eq!(header.get_protocol(), 142);
+    assert_eq!(header.get_type(), BROADCAST_MESSAGE);
+    assert!(RequestResponseHeader::from_bytes(&bytes[..7]).is_none());
+
+    let net_stats = NetStats::default();
+    assert_eq!(net_stats.record_node_protocol(142, 142), None);
+    assert_eq!(net_stats.record_node_protocol(143, 142), Some(142));
+    assert_eq!(net_stats.get_node_protocol(), Some(143));
+    assert_eq!(net_stats.get_protocol_mismatches(), 1);
+}
+
+#[test]
+/// Tests that the size and the dejavu are packed within their 3 bytes and oversized messages are refused.
+fn test_header_fields() {
+    assert_eq!(pack_size(0), Ok([0, 0, 0]));
+    assert_eq!(pack_size(232), Ok([232, 0, 0]));
+    assert_eq!(pack_size(MAX_MESSAGE_SIZE), Ok([0xFF, 0xFF, 0xFF]));
+    assert!(pack_size(MAX_MESSAGE_SIZE + 1).is_err());
+    assert_eq!(unpack_size([0x01, 0x02, 0x03]), 0x030201);
+    assert_eq!(pack_dejavu(0x1234_5678), [0x78, 0x56, 0x34]);
+    assert_eq!(unpack_dejavu([0x78, 0x56, 0x34]), 0x34_5678);
+
+    let mut header = RequestResponseHeader::new(&BROADCAST_MESSAGE, &MAX_MESSAGE_SIZE, &142).unwrap();
+    assert!(RequestResponseHeader::new(&BROADCAST_MESSAGE, &(1 << 24), &142).is_err());
+    assert!(header.set_size(&usize::MAX).is_err());
+    // The fields after the size never leak into it
+    header.set_dejavu(u32::MAX);
+    assert_eq!(header.get_size(), MAX_MESSAGE_SIZE);
+    assert_eq!(header.get_dejavu(), 0xFF_FFFF);
+    assert_eq!(header.to_bytes(), [0xFF, 0xFF, 0xFF, 142, 0xFF, 0xFF, 0xFF, BROADCAST_MESSAGE]);
+    let read = RequestResponseHeader::from_bytes(&header.to_bytes()).unwrap();
+    assert_eq!((read.get_size(), read.get_protocol(), read.get_dejavu(), read.get_type()), (MAX_MESSAGE_SIZE, 142, 0xFF_FFFF, BROADCAST_MESSAGE));
+    assert!(RequestResponseHeader::from_bytes(&[0; 7]).is_none());
+
+    // A peer on another protocol gets every message of a batch with its protocol, the payloads untouched
+    let nonces: [Nonce64; 2] = [[1, 2, 3, 4], [5, 6, 7, 8]];
+    let built = Packet::solutions_to_bytes(142, &[9; 4], &nonces);
+    let mut bytes = built.clone();
+    assert_eq!(RequestResponseHeader::set_protocol_of_messages(&mut bytes, &140), 2);
+    for ((packet, built), nonce) in bytes.chunks(size_of::<Packet>()).zip(built.chunks(size_of::<Packet>())).zip(nonces) {
+        assert_eq!(packet[HEADER_SIZE..], built[HEADER_SIZE..]);
+        assert_eq!(Packet::validate_solution(packet, Some(140)), Ok(([9; 4], nonce)));
     }
+    // A truncated message is left as it is
+    assert_eq!(RequestResponseHeader::set_protocol_of_messages(&mut bytes[..300], &141), 1);
+    assert_eq!(RequestResponseHeader::from_bytes(&bytes[size_of::<Packet>()..]).unwrap().get_protocol(), 140);
 }
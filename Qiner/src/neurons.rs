@@ -0,0 +1,185 @@
+use alloc::boxed::Box;
+use alloc::vec;
+use lib::types::{NeuronValue, NUMBER_OF_NEURONS};
+use crate::arch;
+
+/// Storage of the neuron values.
+///
+/// Neurons are only ever updated as the NAND of two neurons starting from all bits set,
+/// so a neuron is either all ones or all zeros and is exposed as a `bool`.
+pub trait NeuronValueStore {
+    /// Gets the value of a neuron, `true` when all its bits are set.
+    fn get(&self, idx: usize) -> bool;
+
+    /// Sets the value of a neuron.
+    fn set(&mut self, idx: usize, value: bool);
+
+    /// Sets the value of a neuron without checking the index, for the score kernel.
+    ///
+    /// # Safety
+    /// `idx` must be below `number_of_neurons()`.
+    unsafe fn set_unchecked(&mut self, idx: usize, value: bool);
+
+    /// Gets the number of neurons.
+    fn number_of_neurons(&self) -> usize;
+
+    /// Sets all neurons, the state every nonce attempt starts from.
+    fn reset(&mut self);
+
+    /// Hints the CPU to load the value of a neuron into the cache ahead of its use.
+    fn prefetch(&self, idx: usize);
+}
+
+/// Neuron values stored one byte per neuron, the layout of the reference miner.
+#[derive(Debug, Clone)]
+pub struct ByteNeuronValues {
+    values: Box<[NeuronValue]>,
+}
+
+impl ByteNeuronValues {
+    /// Creates neuron values with all neurons set.
+    ///
+    /// # Arguments
+    /// * `number_of_neurons` - The number of neurons of the algorithm.
+    pub fn new(number_of_neurons: usize) -> Self {
+        ByteNeuronValues {
+            // Built on the heap, the array does not fit on the stack of every thread
+            values: vec![NeuronValue::MAX; number_of_neurons].into_boxed_slice(),
+        }
+    }
+}
+
+impl Default for ByteNeuronValues {
+    fn default() -> Self {
+        ByteNeuronValues::new(NUMBER_OF_NEURONS)
+    }
+}
+
+impl NeuronValueStore for ByteNeuronValues {
+    #[inline(always)]
+    fn get(&self, idx: usize) -> bool {
+        self.values[idx] != 0
+    }
+
+    #[inline(always)]
+    fn set(&mut self, idx: usize, value: bool) {
+        self.values[idx] = if value { NeuronValue::MAX } else { 0 };
+    }
+
+    #[inline(always)]
+    unsafe fn set_unchecked(&mut self, idx: usize, value: bool) {
+        *self.values.get_unchecked_mut(idx) = if value { NeuronValue::MAX } else { 0 };
+    }
+
+    fn number_of_neurons(&self) -> usize {
+        self.values.len()
+    }
+
+    fn reset(&mut self) {
+        self.values.fill(NeuronValue::MAX);
+    }
+
+    #[inline(always)]
+    fn prefetch(&self, idx: usize) {
+        arch::prefetch(&self.values[idx]);
+    }
+}
+
+/// Neuron values packed one bit per neuron, 64 neurons per word.
+///
+/// The working set is 8 times smaller than `ByteNeuronValues`, so it stays in cache.
+#[derive(Debug, Clone)]
+pub struct PackedNeuronValues64 {
+    words: Box<[u64]>,
+    /// The last word may be partly used
+    number_of_neurons: usize,
+}
+
+impl PackedNeuronValues64 {
+    /// Creates neuron values with all neurons set.
+    ///
+    /// # Arguments
+    /// * `number_of_neurons` - The number of neurons of the algorithm.
+    pub fn new(number_of_neurons: usize) -> Self {
+        PackedNeuronValues64 {
+            words: vec![u64::MAX; number_of_neurons.div_ceil(u64::BITS as usize)].into_boxed_slice(),
+            number_of_neurons,
+        }
+    }
+}
+
+impl Default for PackedNeuronValues64 {
+    fn default() -> Self {
+        PackedNeuronValues64::new(NUMBER_OF_NEURONS)
+    }
+}
+
+impl NeuronValueStore for PackedNeuronValues64 {
+    #[inline(always)]
+    fn get(&self, idx: usize) -> bool {
+        (self.words[idx >> 6] >> (idx & 63)) & 1 != 0
+    }
+
+    #[inline(always)]
+    fn set(&mut self, idx: usize, value: bool) {
+        assert!(idx < self.number_of_neurons, "neuron {idx} out of range");
+        // Safety: the index was just checked
+        unsafe { self.set_unchecked(idx, value) };
+    }
+
+    #[inline(always)]
+    unsafe fn set_unchecked(&mut self, idx: usize, value: bool) {
+        let word = self.words.get_unchecked_mut(idx >> 6);
+        let mask = 1u64 << (idx & 63);
+        *word = (*word & !mask) | (u64::from(value) << (idx & 63));
+    }
+
+    fn number_of_neurons(&self) -> usize {
+        self.number_of_neurons
+    }
+
+    fn reset(&mut self) {
+        self.words.fill(u64::MAX);
+    }
+
+    #[inline(always)]
+    fn prefetch(&self, idx: usize) {
+        arch::prefetch(&self.words[idx >> 6]);
+    }
+}
+
+/// Neuron values used by the miner, packed unless the `byte-neurons` feature selects the reference layout.
+#[cfg(not(feature = "byte-neurons"))]
+pub type ActiveNeuronValues = PackedNeuronValues64;
+
+/// Neuron values used by the miner, packed unless the `byte-neurons` feature selects the reference layout.
+#[cfg(feature = "byte-neurons")]
+pub type ActiveNeuronValues = ByteNeuronValues;
+
+#[test]
+/// Tests that both layouts agree on a sequence of NAND updates.
+fn test_neuron_layouts_agree() {
+    let mut bytes = ByteNeuronValues::default();
+    let mut packed = PackedNeuronValues64::default();
+
+    let mut state = 0x9e3779b97f4a7c15u64;
+    for _ in 0..100_000 {
+        // xorshift, spreading the indices over the whole neuron range
+        state ^= state << 13;
+        state ^= state >> 7;
+        state ^= state << 17;
+        let target = (state as usize) % NUMBER_OF_NEURONS;
+        let left = ((state >> 22) as usize) % NUMBER_OF_NEURONS;
+        let right = ((state >> 42) as usize) % NUMBER_OF_NEURONS;
+
+        bytes.set(target, !(bytes.get(left) & bytes.get(right)));
+        packed.set(target, !(packed.get(left) & packed.get(right)));
+        assert_eq!(bytes.get(target), packed.get(target));
+    }
+
+    assert!((0..NUMBER_OF_NEURONS).all(|idx| bytes.get(idx) == packed.get(idx)));
+
+    bytes.reset();
+    packed.reset();
+    assert!((0..NUMBER_OF_NEURONS).all(|idx| bytes.get(idx) && packed.get(idx)));
+}
@@ -0,0 +1,179 @@
+use std::env;
+use std::time::Duration;
+use lib::env_names::{
+    ENV_NOTIFY_CONNECTION_FAILURE_MINUTES,
+    ENV_NOTIFY_EVENTS,
+    ENV_NOTIFY_MIN_HASHRATE,
+    ENV_NOTIFY_TELEGRAM_CHAT_ID,
+    ENV_NOTIFY_TELEGRAM_TOKEN,
+    ENV_NOTIFY_WEBHOOK_URL,
+};
+
+/// Character used to split the list of notification events.
+const EVENTS_SPLIT_CHAR: char = ',';
+
+/// Default number of minutes the connection must fail before a notification is sent.
+const DEFAULT_CONNECTION_FAILURE_MINUTES: u64 = 10;
+
+/// Timeout applied to every notification request.
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Events that can trigger a notification.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum NotifyEvent {
+    /// Solutions were found by the miner.
+    SolutionFound(usize),
+    /// Solutions were sent to the server.
+    SolutionSent(usize),
+    /// The connection to the server has been failing for the given duration.
+    ConnectionFailing(Duration),
+    /// The hashrate dropped below the configured minimum.
+    HashrateDropped(usize),
+    /// Mining has been degraded for the sustained duration, described by the message.
+    MiningDegraded(String),
+    /// Mining recovered from a degradation.
+    MiningRecovered,
+}
+
+impl NotifyEvent {
+    /// Gets the name used to enable the event in `NOTIFY_EVENTS`.
+    pub fn name(&self) -> &'static str {
+        match self {
+            NotifyEvent::SolutionFound(_) => "found",
+            NotifyEvent::SolutionSent(_) => "sent",
+            NotifyEvent::ConnectionFailing(_) => "connection",
+            NotifyEvent::HashrateDropped(_) => "hashrate",
+            NotifyEvent::MiningDegraded(_) | NotifyEvent::MiningRecovered => "degraded",
+        }
+    }
+
+    /// Formats the human-readable message for the event.
+    pub fn message(&self) -> String {
+        match self {
+            NotifyEvent::SolutionFound(count) => format!("Qiner: found {count} solution(s)"),
+            NotifyEvent::SolutionSent(count) => format!("Qiner: sent {count} solution(s)"),
+            NotifyEvent::ConnectionFailing(duration) => {
+                format!("Qiner: connection to the server has been failing for {} minutes", duration.as_secs() / 60)
+            }
+            NotifyEvent::HashrateDropped(it_per_sec) => format!("Qiner: hashrate dropped to {it_per_sec} it/s"),
+            NotifyEvent::MiningDegraded(message) => format!("Qiner: mining degraded: {message}"),
+            NotifyEvent::MiningRecovered => "Qiner: mining recovered".to_string(),
+        }
+    }
+}
+
+/// Destination of notifications.
+#[derive(Debug, Clone)]
+enum NotifyTarget {
+    /// Discord-compatible webhook receiving `{"content": ...}`.
+    Webhook(String),
+    /// Telegram bot sending messages to a chat.
+    Telegram { token: String, chat_id: String },
+}
+
+/// Sends notifications about solutions and failures to webhooks.
+///
+/// Disabled unless `NOTIFY_WEBHOOK_URL` or `NOTIFY_TELEGRAM_TOKEN` and `NOTIFY_TELEGRAM_CHAT_ID` are set.
+#[derive(Debug, Clone)]
+pub struct Notifier {
+    targets: Vec<NotifyTarget>,
+    events: Vec<String>,
+    connection_failure_after: Duration,
+    min_hashrate: usize,
+}
+
+impl Notifier {
+    /// Creates a `Notifier` from the environment variables.
+    ///
+    /// # Returns
+    /// `Some(Notifier)` if at least one target is configured, `None` otherwise.
+    pub fn from_env() -> Option<Self> {
+        let mut targets = Vec::new();
+
+        if let Ok(url) = env::var(ENV_NOTIFY_WEBHOOK_URL) {
+            targets.push(NotifyTarget::Webhook(url.trim().to_string()));
+        }
+
+        if let (Ok(token), Ok(chat_id)) = (env::var(ENV_NOTIFY_TELEGRAM_TOKEN), env::var(ENV_NOTIFY_TELEGRAM_CHAT_ID)) {
+            targets.push(NotifyTarget::Telegram {
+                token: token.trim().to_string(),
+                chat_id: chat_id.trim().to_string(),
+            });
+        }
+
+        if targets.is_empty() {
+            return None;
+        }
+
+        // All events are enabled unless a list is given
+        let events = env::var(ENV_NOTIFY_EVENTS)
+            .unwrap_or_else(|_| "found,sent,connection,hashrate,degraded".to_string())
+            .split(EVENTS_SPLIT_CHAR)
+            .map(|event| event.trim().to_lowercase())
+            .filter(|event| !event.is_empty())
+            .collect();
+
+        let connection_failure_minutes = env::var(ENV_NOTIFY_CONNECTION_FAILURE_MINUTES).ok()
+            .and_then(|value| value.trim().parse::<u64>().ok())
+            .unwrap_or(DEFAULT_CONNECTION_FAILURE_MINUTES);
+
+        let min_hashrate = env::var(ENV_NOTIFY_MIN_HASHRATE).ok()
+            .and_then(|value| value.trim().parse::<usize>().ok())
+            .unwrap_or(0);
+
+        Some(Notifier {
+            targets,
+            events,
+            connection_failure_after: Duration::from_secs(connection_failure_minutes * 60),
+            min_hashrate,
+        })
+    }
+
+    /// Gets how long the connection must fail before `ConnectionFailing` is notified.
+    pub fn get_connection_failure_after(&self) -> Duration {
+        self.connection_failure_after
+    }
+
+    /// Gets the hashrate below which `HashrateDropped` is notified, `0` meaning disabled.
+    pub fn get_min_hashrate(&self) -> usize {
+        self.min_hashrate
+    }
+
+    /// Checks if the event is enabled.
+    pub fn is_enabled(&self, event: &NotifyEvent) -> bool {
+        self.events.iter().any(|name| name == event.name())
+    }
+
+    /// Sends the notification for the event to all targets, if the event is enabled.
+    ///
+    /// Requests run on the blocking pool and failures are only logged, so a broken
+    /// webhook never delays mining or submission.
+    ///
+    /// # Arguments
+    /// * `event` - The event to notify about.
+    pub fn notify(&self, event: NotifyEvent) {
+        if !self.is_enabled(&event) {
+            return;
+        }
+
+        let message = event.message();
+        for target in self.targets.clone() {
+            let message = message.clone();
+            tokio::task::spawn_blocking(move || {
+                let result = match &target {
+                    NotifyTarget::Webhook(url) => ureq::post(url)
+                        .timeout(REQUEST_TIMEOUT)
+                        .send_json(ureq::json!({ "content": message })),
+                    NotifyTarget::Telegram { token, chat_id } => ureq::post(&format!("https://api.telegram.org/bot{token}/sendMessage"))
+                        .timeout(REQUEST_TIMEOUT)
+                        .send_json(ureq::json!({ "chat_id": chat_id, "text": message })),
+                };
+
+                // The error is reduced to its kind, since the URL may contain a bot token
+                if let Err(err) = result {
+                    log::warn!("Failed to send notification: {}", err.kind());
+                }
+            });
+        }
+    }
+}
@@ -0,0 +1,201 @@
+//! Building of the solution packets ahead of their submission.
+//!
+//! `Packet::new` grinds K12 until the gamming key of the packet starts with 0, which is too slow
+//! to do while a connection to a node is open. The factory builds the packets on the blocking
+//! pool as soon as their nonces are found, so writing to a node only moves their bytes. A packet
+//! is kept until its solution leaves the retry queue, its bytes being written again on a retry.
+//! A burst of solutions, e.g. the spilled ones coming back after an outage, is split across
+//! the blocking pool so its packets are built in parallel.
+
+use std::collections::HashMap;
+use std::mem::size_of;
+use std::sync::{Arc, Mutex};
+use std::time::Instant;
+use lib::types::network::protocols::BROADCAST_MESSAGE;
+use lib::types::network::Protocol;
+use lib::types::{Nonce64, PublicKey64};
+use tokio::sync::mpsc;
+use crate::network::{NetStats, Packet, RequestResponseHeader};
+
+/// Size of a solution packet on the wire.
+pub const PACKET_SIZE: usize = size_of::<Packet>();
+
+/// Fewest packets a blocking task builds, a smaller burst being built by a single task.
+pub const MIN_PACKETS_PER_BUILD_TASK: usize = 16;
+
+/// Builds the solution packets of a computor on the blocking pool and keeps them until submitted.
+#[derive(Debug)]
+pub struct PacketFactory {
+    protocol: Protocol,
+    public_key: PublicKey64,
+    /// Packets built for the solutions still waiting, by nonce
+    built: Mutex<HashMap<Nonce64, [u8; PACKET_SIZE]>>,
+    /// Counters the build time is recorded in
+    net_stats: Arc<NetStats>,
+    sender: mpsc::UnboundedSender<Vec<Nonce64>>,
+}
+
+impl PacketFactory {
+    /// Creates a new `PacketFactory` and spawns its worker, within a Tokio runtime.
+    ///
+    /// # Arguments
+    /// * `protocol` - The protocol version, from the settings.
+    /// * `public_key` - The public key of the computor.
+    /// * `net_stats` - Shared network counters, recording the build time.
+    ///
+    /// # Returns
+    /// The factory, shared with its worker.
+    pub fn spawn(protocol: Protocol, public_key: PublicKey64, net_stats: Arc<NetStats>) -> Arc<Self> {
+        let (sender, receiver) = mpsc::unbounded_channel();
+        let factory = Arc::new(PacketFactory { protocol, public_key, built: Mutex::new(HashMap::new()), net_stats, sender });
+        tokio::spawn(build_task(factory.clone(), receiver));
+        factory
+    }
+
+    /// Queues the packets of freshly found solutions to be built in the background.
+    ///
+    /// # Arguments
+    /// * `nonces` - The nonces of the solutions.
+    pub fn prebuild(&self, nonces: impl IntoIterator<Item = Nonce64>) {
+        let nonces = nonces.into_iter().collect::<Vec<_>>();
+        if !nonces.is_empty() {
+            // The worker only stops with the factory, so the send cannot fail while it is in use
+            let _ = self.sender.send(nonces);
+        }
+    }
+
+    /// Gets the bytes of the packets of solutions, back to back as they are written to a peer.
+    ///
+    /// # Arguments
+    /// * `nonces` - The nonces of the solutions.
+    /// * `protocol` - The protocol version of the peer, the packets being built with the one of the settings.
+    ///
+    /// # Returns
+    /// The bytes of all the packets, those not built yet being built now.
+    pub fn to_bytes<'a>(&self, nonces: impl IntoIterator<Item = &'a Nonce64>, protocol: Protocol) -> Vec<u8> {
+        let mut bytes = {
+            let built = self.built.lock().unwrap();
+            nonces.into_iter().flat_map(|nonce| match built.get(nonce) {
+                Some(bytes) => *bytes,
+                None => self.build(nonce),
+            }).collect::<Vec<_>>()
+        };
+        if protocol != self.protocol {
+            RequestResponseHeader::set_protocol_of_messages(&mut bytes, &protocol);
+        }
+        bytes
+    }
+
+    /// Drops the packets of the solutions that left the retry queue.
+    ///
+    /// # Arguments
+    /// * `keep` - Whether the solution of a nonce is still waiting.
+    pub fn retain(&self, keep: impl Fn(&Nonce64) -> bool) {
+        self.built.lock().unwrap().retain(|nonce, _| keep(nonce));
+    }
+
+    /// Gets the number of packets built and kept.
+    pub fn len(&self) -> usize {
+        self.built.lock().unwrap().len()
+    }
+
+    /// Checks if no packet is kept.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    fn build(&self, nonce: &Nonce64) -> [u8; PACKET_SIZE] {
+        let started_at = Instant::now();
+        let bytes = Packet::new(&BROADCAST_MESSAGE, &self.public_key, nonce, &self.protocol).to_bytes();
+        self.net_stats.record_packet_build(1, started_at.elapsed());
+        bytes
+    }
+
+    /// Builds the packets not built yet, the lock being only held to store each of them.
+    fn build_missing(&self, nonces: &[Nonce64]) {
+        for nonce in nonces {
+            if self.built.lock().unwrap().contains_key(nonce) {
+                continue;
+            }
+            let bytes = self.build(nonce);
+            self.built.lock().unwrap().insert(*nonce, bytes);
+        }
+    }
+}
+
+/// Computes how many packets each blocking task builds out of a burst.
+///
+/// # Arguments
+/// * `packets` - The number of packets of the burst.
+/// * `workers` - The number of tasks that may run at once, e.g. the CPUs.
+///
+/// # Returns
+/// The packets per task, at least `MIN_PACKETS_PER_BUILD_TASK` so small bursts are not scattered.
+pub fn get_build_chunk_size(packets: usize, workers: usize) -> usize {
+    packets.div_ceil(workers.max(1)).max(MIN_PACKETS_PER_BUILD_TASK)
+}
+
+/// Builds the queued packets one burst at a time, the chunks of a burst in parallel on the blocking pool.
+///
+/// # Arguments
+/// * `factory` - The factory keeping the packets.
+/// * `receiver` - The nonces queued by `prebuild`.
+async fn build_task(factory: Arc<PacketFactory>, mut receiver: mpsc::UnboundedReceiver<Vec<Nonce64>>) {
+    let workers = std::thread::available_parallelism().map_or(1, |workers| workers.get());
+    while let Some(nonces) = receiver.recv().await {
+        let started_at = Instant::now();
+        let chunk_size = get_build_chunk_size(nonces.len(), workers);
+        let tasks = nonces.chunks(chunk_size).map(|chunk| {
+            let factory = factory.clone();
+            let chunk = chunk.to_vec();
+            tokio::task::spawn_blocking(move || factory.build_missing(&chunk))
+        }).collect::<Vec<_>>();
+        let task_count = tasks.len();
+        for task in tasks {
+            if let Err(err) = task.await {
+                log::error!("Failed to build solution packets: {:?}", err);
+            }
+        }
+        if task_count > 1 {
+            log::debug!("Built {} solution packets on {task_count} threads in {:?}", nonces.len(), started_at.elapsed());
+        }
+    }
+}
+
+#[test]
+/// Tests that packets built ahead decode to their solution, are kept until dropped and are built on demand otherwise.
+fn test_packet_factory() {
+    let runtime = tokio::runtime::Builder::new_current_thread().enable_all().build().unwrap();
+    runtime.block_on(async {
+        let public_key: PublicKey64 = [1, 2, 3, 4];
+        let nonces: [Nonce64; 2] = [[5, 6, 7, 8], [9, 10, 11, 12]];
+        let net_stats = Arc::new(NetStats::default());
+        let factory = PacketFactory::spawn(142, public_key, net_stats.clone());
+        factory.prebuild(nonces);
+        while factory.len() < nonces.len() {
+            tokio::time::sleep(std::time::Duration::from_millis(10)).await;
+        }
+
+        let bytes = factory.to_bytes(&nonces, 142);
+        assert_eq!(bytes.len(), 2 * PACKET_SIZE);
+        assert_eq!(Packet::validate_solution(&bytes[PACKET_SIZE..], Some(142)), Ok((public_key, nonces[1])));
+        // A retry writes the same packets
+        assert_eq!(factory.to_bytes(&nonces, 142), bytes);
+        // A peer on another protocol gets the same packets with its protocol
+        let patched = factory.to_bytes(&nonces, 140);
+        assert_eq!(Packet::validate_solution(&patched[PACKET_SIZE..], Some(140)), Ok((public_key, nonces[1])));
+        assert_eq!(patched[8..PACKET_SIZE], bytes[8..PACKET_SIZE]);
+
+        factory.retain(|nonce| *nonce == nonces[0]);
+        assert_eq!(factory.len(), 1);
+        let missing: Nonce64 = [13, 14, 15, 16];
+        assert_eq!(Packet::decode_solution(&factory.to_bytes([&missing], 142)), Some((public_key, missing)));
+        assert_eq!(factory.len(), 1);
+        assert!(net_stats.get_average_packet_build_time() > std::time::Duration::ZERO);
+    });
+
+    // Bursts are split across the workers, small ones stay on one task
+    assert_eq!(get_build_chunk_size(1000, 8), 125);
+    assert_eq!(get_build_chunk_size(40, 8), MIN_PACKETS_PER_BUILD_TASK);
+    assert_eq!(get_build_chunk_size(3, 0), MIN_PACKETS_PER_BUILD_TASK);
+}
@@ -0,0 +1,67 @@
+use std::panic;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex, TryLockError};
+use std::thread;
+use lib::types::Nonce64;
+use crate::journal;
+use crate::miner::Miner;
+use crate::submission::RetryQueue;
+
+/// Exit code of the process after a panic, distinct from Rust's default `101`.
+pub const PANIC_EXIT_CODE: i32 = 70;
+
+/// Installs a panic hook logging panics as a structured entry.
+///
+/// If `exit_on_panic` is set, the unsent solutions are then spilled to `spill_file` and the
+/// process exits with `PANIC_EXIT_CODE`, so systemd or docker can restart it. Otherwise the
+/// panicking task ends and the worker supervisor takes care of it.
+///
+/// # Arguments
+/// * `miner` - Shared reference to the Miner instance, holding the freshly found solutions.
+/// * `retry_queue` - Shared queue of solutions waiting to be submitted.
+/// * `spill_file` - File unsent solutions are spilled to.
+/// * `exit_on_panic` - Whether to exit the process after a panic.
+pub fn install(miner: Arc<Miner>, retry_queue: Arc<Mutex<RetryQueue>>, spill_file: PathBuf, exit_on_panic: bool) {
+    let previous_hook = panic::take_hook();
+
+    panic::set_hook(Box::new(move |info| {
+        let thread = thread::current();
+        let location = info.location()
+            .map(|location| format!("{}:{}", location.file(), location.line()))
+            .unwrap_or_else(|| "unknown".to_string());
+
+        log::error!(
+            target: "panic",
+            "thread={:?} location={} message={:?} exit={}",
+            thread.name().unwrap_or("unnamed"),
+            location,
+            info.payload_as_str().unwrap_or("non-string payload"),
+            exit_on_panic
+        );
+        previous_hook(info);
+
+        if !exit_on_panic {
+            return;
+        }
+
+        // Never block here: the panicking thread may be the one holding a lock
+        let mut nonces: Vec<Nonce64> = Vec::new();
+        if let Ok(found_nonce) = miner.found_nonce.try_lock() {
+            nonces.extend(found_nonce.iter().map(|solution| solution.nonce));
+        }
+        match retry_queue.try_lock() {
+            Ok(queue) => nonces.extend(queue.nonces()),
+            Err(TryLockError::Poisoned(poisoned)) => nonces.extend(poisoned.into_inner().nonces()),
+            Err(TryLockError::WouldBlock) => log::error!(target: "panic", "retry queue is locked, its solutions are lost"),
+        }
+
+        if !nonces.is_empty() {
+            match journal::spill(&spill_file, &nonces) {
+                Ok(_) => log::error!(target: "panic", "spilled {} solutions to {}", nonces.len(), spill_file.display()),
+                Err(err) => log::error!(target: "panic", "failed to spill {} solutions: {:?}", nonces.len(), err),
+            }
+        }
+
+        std::process::exit(PANIC_EXIT_CODE);
+    }));
+}
@@ -0,0 +1,160 @@
+use std::net::{IpAddr, SocketAddr};
+use crate::peers::{PEERS_SPLIT_CHAR, QUIC_SCHEME};
+
+/// A network in CIDR notation, a single IP being a network of one address.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Cidr {
+    addr: IpAddr,
+    prefix_len: u32,
+}
+
+impl Cidr {
+    /// Parses a network such as `10.0.0.0/8`, `2001:db8::/32` or a single IP.
+    ///
+    /// # Arguments
+    /// * `value` - The network to parse.
+    ///
+    /// # Returns
+    /// The network, or `None` if invalid.
+    pub fn parse(value: &str) -> Option<Self> {
+        let (addr, prefix_len) = match value.trim().split_once('/') {
+            Some((addr, prefix_len)) => (addr.parse::<IpAddr>().ok()?, Some(prefix_len.parse::<u32>().ok()?)),
+            None => (value.trim().parse::<IpAddr>().ok()?, None),
+        };
+
+        let max_len = if addr.is_ipv4() { 32 } else { 128 };
+        let prefix_len = prefix_len.unwrap_or(max_len);
+        (prefix_len <= max_len).then_some(Cidr { addr, prefix_len })
+    }
+
+    /// Checks if the network contains an IP, IPv4-mapped IPv6 addresses matching IPv4 networks.
+    pub fn contains(&self, ip: IpAddr) -> bool {
+        let ip = match ip {
+            IpAddr::V6(ip) => ip.to_ipv4_mapped().map_or(IpAddr::V6(ip), IpAddr::V4),
+            ip => ip,
+        };
+
+        match (self.addr, ip) {
+            (IpAddr::V4(network), IpAddr::V4(ip)) => {
+                let mask = u32::MAX.checked_shl(32 - self.prefix_len).unwrap_or(0);
+                u32::from(network) & mask == u32::from(ip) & mask
+            }
+            (IpAddr::V6(network), IpAddr::V6(ip)) => {
+                let mask = u128::MAX.checked_shl(128 - self.prefix_len).unwrap_or(0);
+                u128::from(network) & mask == u128::from(ip) & mask
+            }
+            _ => false,
+        }
+    }
+}
+
+/// Allowlist and denylist of the nodes Qiner may submit to.
+///
+/// A node is allowed unless it is denied, and, when the allowlist is not empty, only if
+/// it is allowed. Nodes given by host name cannot be matched, so they are only allowed
+/// without an allowlist.
+#[derive(Debug, Clone, Default)]
+pub struct PeerFilter {
+    allow: Vec<Cidr>,
+    deny: Vec<Cidr>,
+}
+
+impl PeerFilter {
+    /// Creates a filter from comma-separated lists of networks, invalid entries being logged and skipped.
+    ///
+    /// # Arguments
+    /// * `allow` - The allowed networks, empty to allow all
+    /// * `deny` - The denied networks
+    ///
+    /// # Returns
+    /// A new `PeerFilter`.
+    pub fn new(allow: &str, deny: &str) -> Self {
+        PeerFilter {
+            allow: parse_list(allow),
+            deny: parse_list(deny),
+        }
+    }
+
+    /// Creates a filter from lists of networks parsed beforehand, e.g. with `parse_networks`.
+    ///
+    /// # Arguments
+    /// * `allow` - The allowed networks, empty to allow all
+    /// * `deny` - The denied networks
+    ///
+    /// # Returns
+    /// A new `PeerFilter`.
+    pub fn from_networks(allow: Vec<Cidr>, deny: Vec<Cidr>) -> Self {
+        PeerFilter { allow, deny }
+    }
+
+    /// Checks if the filter lets every node through.
+    pub fn is_empty(&self) -> bool {
+        self.allow.is_empty() && self.deny.is_empty()
+    }
+
+    /// Checks if a node may be submitted to.
+    ///
+    /// # Arguments
+    /// * `addr` - The address of the node, as `ip:port`, with or without `QUIC_SCHEME`
+    pub fn is_allowed(&self, addr: &str) -> bool {
+        let Ok(addr) = addr.strip_prefix(QUIC_SCHEME).unwrap_or(addr).parse::<SocketAddr>() else {
+            return self.allow.is_empty();
+        };
+
+        let ip = addr.ip();
+        !self.deny.iter().any(|cidr| cidr.contains(ip)) && (self.allow.is_empty() || self.allow.iter().any(|cidr| cidr.contains(ip)))
+    }
+}
+
+/// Parses a comma-separated list of networks, rejecting it if an entry is invalid.
+///
+/// # Arguments
+/// * `value` - The list to parse, empty for none.
+///
+/// # Returns
+/// The networks, or which entry is invalid.
+pub fn parse_networks(value: &str) -> Result<Vec<Cidr>, String> {
+    value.split(PEERS_SPLIT_CHAR)
+        .map(str::trim)
+        .filter(|entry| !entry.is_empty())
+        .map(|entry| Cidr::parse(entry).ok_or_else(|| format!("invalid network {entry:?}")))
+        .collect()
+}
+
+/// Parses a comma-separated list of networks.
+fn parse_list(value: &str) -> Vec<Cidr> {
+    value.split(PEERS_SPLIT_CHAR)
+        .map(str::trim)
+        .filter(|entry| !entry.is_empty())
+        .filter_map(|entry| {
+            let cidr = Cidr::parse(entry);
+            if cidr.is_none() {
+                log::warn!("Ignoring invalid network {entry:?}");
+            }
+            cidr
+        })
+        .collect()
+}
+
+#[test]
+/// Tests CIDR matching and the precedence of the denylist.
+fn test_peer_filter() {
+    let filter = PeerFilter::new("10.0.0.0/8, 192.168.1.7, 2001:db8::/32", "10.6.0.0/16");
+    assert!(filter.is_allowed("10.1.2.3:21841"));
+    assert!(!filter.is_allowed("10.6.2.3:21841"));
+    assert!(filter.is_allowed("192.168.1.7:21841"));
+    assert!(!filter.is_allowed("192.168.1.8:21841"));
+    assert!(filter.is_allowed("[2001:db8::1]:21841"));
+    assert!(filter.is_allowed("[::ffff:10.1.2.3]:21841"));
+    assert!(!filter.is_allowed("node.example:21841"));
+
+    let filter = PeerFilter::new("", "1.2.3.0/24, 0.0.0.0/33");
+    assert!(!filter.is_allowed("1.2.3.4:21841"));
+    assert!(filter.is_allowed("1.2.4.4:21841"));
+    assert!(filter.is_allowed("node.example:21841"));
+    assert!(PeerFilter::default().is_empty());
+
+    // The settings reject a list with an invalid entry instead of skipping it
+    assert_eq!(parse_networks("1.2.3.0/24, 0.0.0.0/33"), Err("invalid network \"0.0.0.0/33\"".to_string()));
+    assert_eq!(parse_networks(" 1.2.3.0/24, ").map(|networks| networks.len()), Ok(1));
+}
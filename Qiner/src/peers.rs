@@ -0,0 +1,335 @@
+use std::fs;
+use std::io;
+use std::net::Ipv4Addr;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+use lib::types::network::NUMBER_OF_EXCHANGED_PEERS;
+use serde_json::{json, Value};
+use crate::peer_filter::PeerFilter;
+
+/// Name of the file the peer scores are persisted to, in the state directory.
+pub const PEER_SCORES_FILE: &str = "qiner-peers.json";
+
+/// Character used to split the list of peers.
+pub const PEERS_SPLIT_CHAR: char = ',';
+
+/// Prefix of the peers reached over QUIC with the `quic` feature, e.g. `quic://10.0.0.2:21841`.
+pub const QUIC_SCHEME: &str = "quic://";
+
+/// Weight of the latest outcome in the moving averages.
+const SMOOTHING: f64 = 0.2;
+
+/// Success rate below which a peer is only used when every peer is as flaky.
+const FLAKY_SUCCESS_RATE: f64 = 0.5;
+
+/// Write latency, in milliseconds, at which the score of a peer is halved.
+const LATENCY_HALVING_MS: f64 = 200.0;
+
+/// Maximum number of learned peers kept besides the configured ones.
+const MAX_LEARNED_PEERS: usize = 16;
+
+/// Quality of a single peer, as moving averages of its recent outcomes.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PeerScore {
+    pub addr: String,
+    /// Moving average of connect and write successes, from 0 to 1
+    pub success_rate: f64,
+    /// Moving average of the write latency, in milliseconds
+    pub latency_ms: f64,
+    pub attempts: u64,
+    /// Whether the peer was learned from the public peers of a node rather than configured
+    pub is_learned: bool,
+}
+
+impl PeerScore {
+    /// Creates the score of a peer never tried, which is trusted until it fails.
+    fn new(addr: String, is_learned: bool) -> Self {
+        PeerScore {
+            addr,
+            success_rate: 1.0,
+            latency_ms: 0.0,
+            attempts: 0,
+            is_learned,
+        }
+    }
+
+    /// Checks if the peer fails too often to be preferred.
+    pub fn is_flaky(&self) -> bool {
+        self.success_rate < FLAKY_SUCCESS_RATE
+    }
+
+    /// Gets the score of the peer, higher is better.
+    pub fn score(&self) -> f64 {
+        self.success_rate / (1.0 + self.latency_ms / LATENCY_HALVING_MS)
+    }
+
+    fn record_outcome(&mut self, is_success: bool) {
+        let outcome = if is_success { 1.0 } else { 0.0 };
+        self.success_rate += SMOOTHING * (outcome - self.success_rate);
+        self.attempts += 1;
+    }
+
+    fn record_latency(&mut self, latency: Duration) {
+        let latency_ms = latency.as_secs_f64() * 1000.0;
+        self.latency_ms = if self.latency_ms == 0.0 { latency_ms } else { self.latency_ms + SMOOTHING * (latency_ms - self.latency_ms) };
+    }
+}
+
+/// Scores of the configured and learned peers, used to prefer the best one for submissions.
+///
+/// Peers are scored on connect and write successes and write latency; no response is
+/// read from the peers yet, so responsiveness is not taken into account. Learned peers
+/// are only used when every configured peer is flaky. Scores and learned peers are
+/// persisted so a restart keeps demoting the flaky peers and has more than the
+/// configured peers to fall back to.
+#[derive(Debug)]
+pub struct PeerBook {
+    peers: Vec<PeerScore>,
+    filter: PeerFilter,
+    file: Option<PathBuf>,
+}
+
+impl PeerBook {
+    /// Creates the book of the configured peers, restoring the scores and the learned peers persisted in the file.
+    ///
+    /// # Arguments
+    /// * `addrs` - The addresses of the peers, in order of preference on equal scores
+    /// * `filter` - The allowlist and denylist, peers it rejects are left out
+    /// * `file` - The file the scores are persisted to, `None` to keep them in memory
+    ///
+    /// # Returns
+    /// A new `PeerBook`.
+    pub fn new(addrs: Vec<String>, filter: &PeerFilter, file: Option<PathBuf>) -> Self {
+        let persisted = file.as_deref().map(load_scores).unwrap_or_default();
+        let mut peers = addrs.into_iter().filter(|addr| {
+            let is_allowed = filter.is_allowed(addr);
+            if !is_allowed {
+                log::warn!("Peer {addr} is not allowed, skipping it");
+            }
+            is_allowed
+        }).map(|addr| {
+            let score = persisted.iter().find(|score| score.addr == addr).cloned();
+            PeerScore { is_learned: false, ..score.unwrap_or_else(|| PeerScore::new(addr, false)) }
+        }).collect::<Vec<_>>();
+
+        // Learned peers follow the configured ones, unless they were configured since
+        let learned = persisted.into_iter()
+            .filter(|score| score.is_learned && filter.is_allowed(&score.addr))
+            .filter(|score| !peers.iter().any(|peer| peer.addr == score.addr))
+            .take(MAX_LEARNED_PEERS)
+            .collect::<Vec<_>>();
+        if !learned.is_empty() {
+            log::info!("Restored {} learned peers", learned.len());
+        }
+        peers.extend(learned);
+
+        PeerBook { peers, filter: filter.clone(), file }
+    }
+
+    /// Gets the scores of all peers.
+    pub fn get_peers(&self) -> &[PeerScore] {
+        &self.peers
+    }
+
+    /// Gets the address of the best peer, the first one when scores are equal.
+    ///
+    /// Peers that are not flaky come first, then configured peers before learned ones.
+    pub fn best(&self) -> &str {
+        let mut best: Option<&PeerScore> = None;
+        for peer in &self.peers {
+            let is_better = match best {
+                None => true,
+                Some(best) => {
+                    let (rank, best_rank) = ((!peer.is_flaky(), !peer.is_learned), (!best.is_flaky(), !best.is_learned));
+                    rank > best_rank || (rank == best_rank && peer.score() > best.score())
+                }
+            };
+            if is_better {
+                best = Some(peer);
+            }
+        }
+        best.map_or("", |peer| peer.addr.as_str())
+    }
+
+    /// Records the outcome of a connection attempt.
+    ///
+    /// # Arguments
+    /// * `addr` - The address of the peer
+    /// * `is_success` - Whether the connection succeeded
+    pub fn record_connect(&mut self, addr: &str, is_success: bool) {
+        if let Some(peer) = self.peers.iter_mut().find(|peer| peer.addr == addr) {
+            peer.record_outcome(is_success);
+            if !is_success && peer.is_flaky() {
+                log::warn!("Peer {addr} demoted, success rate {:.0}%", peer.success_rate * 100.0);
+            }
+            self.save();
+        }
+    }
+
+    /// Records the outcome of a write to a connected peer.
+    ///
+    /// # Arguments
+    /// * `addr` - The address of the peer
+    /// * `latency` - The time the write took
+    /// * `is_success` - Whether the write succeeded
+    pub fn record_write(&mut self, addr: &str, latency: Duration, is_success: bool) {
+        if let Some(peer) = self.peers.iter_mut().find(|peer| peer.addr == addr) {
+            peer.record_outcome(is_success);
+            if is_success {
+                peer.record_latency(latency);
+            }
+            self.save();
+        }
+    }
+
+    /// Learns peers from the public peers of a node.
+    ///
+    /// Known and not allowed peers are skipped. Once `MAX_LEARNED_PEERS` are known, a new peer
+    /// only replaces the worst flaky learned peer.
+    ///
+    /// # Arguments
+    /// * `addrs` - The addresses of the peers
+    pub fn learn(&mut self, addrs: Vec<String>) {
+        let mut is_changed = false;
+        for addr in addrs {
+            if self.peers.iter().any(|peer| peer.addr == addr) || !self.filter.is_allowed(&addr) {
+                continue;
+            }
+
+            if self.peers.iter().filter(|peer| peer.is_learned).count() >= MAX_LEARNED_PEERS {
+                let worst = self.peers.iter().enumerate()
+                    .filter(|(_, peer)| peer.is_learned && peer.is_flaky())
+                    .min_by(|(_, a), (_, b)| a.score().total_cmp(&b.score()))
+                    .map(|(idx, _)| idx);
+                let Some(worst) = worst else {
+                    continue;
+                };
+                log::debug!("Forgetting peer {}", self.peers[worst].addr);
+                self.peers.remove(worst);
+            }
+
+            log::debug!("Learned peer {addr}");
+            self.peers.push(PeerScore::new(addr, true));
+            is_changed = true;
+        }
+
+        if is_changed {
+            self.save();
+        }
+    }
+
+    /// Persists the scores, failures are only logged.
+    fn save(&self) {
+        let Some(file) = &self.file else {
+            return;
+        };
+
+        let scores = self.peers.iter().map(|peer| json!({
+            "addr": peer.addr,
+            "success_rate": peer.success_rate,
+            "latency_ms": peer.latency_ms,
+            "attempts": peer.attempts,
+            "is_learned": peer.is_learned,
+        })).collect::<Vec<_>>();
+        if let Err(err) = fs::write(file, Value::Array(scores).to_string()) {
+            log::warn!("Failed to save the peer scores to {}: {:?}", file.display(), err);
+        }
+    }
+}
+
+/// Loads the persisted peer scores.
+///
+/// # Arguments
+/// * `path` - The file the scores are persisted to.
+///
+/// # Returns
+/// The persisted scores, empty if the file does not exist or is invalid.
+fn load_scores(path: &Path) -> Vec<PeerScore> {
+    let content = match fs::read_to_string(path) {
+        Ok(content) => content,
+        Err(err) if err.kind() == io::ErrorKind::NotFound => return Vec::new(),
+        Err(err) => {
+            log::warn!("Failed to load the peer scores from {}: {:?}", path.display(), err);
+            return Vec::new();
+        }
+    };
+
+    let scores = serde_json::from_str::<Value>(&content).ok();
+    scores.as_ref().and_then(Value::as_array).map(|scores| scores.iter().filter_map(|score| {
+        Some(PeerScore {
+            addr: score["addr"].as_str()?.to_string(),
+            success_rate: score["success_rate"].as_f64()?.clamp(0.0, 1.0),
+            latency_ms: score["latency_ms"].as_f64()?.max(0.0),
+            attempts: score["attempts"].as_u64().unwrap_or_default(),
+            is_learned: score["is_learned"].as_bool().unwrap_or_default(),
+        })
+    }).collect()).unwrap_or_default()
+}
+
+/// Parses the payload of a public peers exchange.
+///
+/// # Arguments
+/// * `payload` - The IPv4 addresses, 4 bytes each
+/// * `port` - The port of the peers, nodes share theirs
+///
+/// # Returns
+/// The addresses of the peers as `ip:port`, without the unset and duplicate ones.
+pub fn parse_public_peers(payload: &[u8; NUMBER_OF_EXCHANGED_PEERS * 4], port: &str) -> Vec<String> {
+    let mut addrs = Vec::new();
+    for ip in payload.chunks_exact(4).map(|octets| Ipv4Addr::new(octets[0], octets[1], octets[2], octets[3])) {
+        let addr = format!("{ip}:{port}");
+        if !ip.is_unspecified() && !addrs.contains(&addr) {
+            addrs.push(addr);
+        }
+    }
+    addrs
+}
+
+#[test]
+/// Tests that flaky and slow peers are demoted and that scores survive a restart.
+fn test_peer_book() {
+    let file = std::env::temp_dir().join(format!("qiner-peers-test-{}.json", std::process::id()));
+    let _ = fs::remove_file(&file);
+    let addrs = vec!["a:1".to_string(), "b:1".to_string(), "c:1".to_string()];
+
+    let mut book = PeerBook::new(addrs.clone(), &PeerFilter::default(), Some(file.clone()));
+    assert_eq!(book.best(), "a:1");
+
+    for _ in 0..4 {
+        book.record_connect("a:1", false);
+    }
+    assert!(book.get_peers()[0].is_flaky());
+    assert_eq!(book.best(), "b:1");
+
+    book.record_write("b:1", Duration::from_millis(400), true);
+    book.record_write("c:1", Duration::from_millis(10), true);
+    assert_eq!(book.best(), "c:1");
+
+    let restored = PeerBook::new(addrs.clone(), &PeerFilter::default(), Some(file.clone()));
+    assert_eq!(restored.get_peers(), book.get_peers());
+    assert_eq!(restored.best(), "c:1");
+
+    let filtered = PeerBook::new(vec!["1.2.3.4:1".to_string(), "5.6.7.8:1".to_string()], &PeerFilter::new("", "1.2.3.0/24"), None);
+    assert_eq!(filtered.best(), "5.6.7.8:1");
+
+    // Learned peers are only used once the configured ones are flaky, and survive a restart
+    let learned = parse_public_peers(&[1, 2, 3, 4, 0, 0, 0, 0, 5, 6, 7, 8, 1, 2, 3, 4], "21841");
+    assert_eq!(learned, ["1.2.3.4:21841", "5.6.7.8:21841"]);
+    book.learn(learned);
+    assert_eq!(book.best(), "c:1");
+    let mut restored = PeerBook::new(addrs.clone(), &PeerFilter::default(), Some(file.clone()));
+    assert_eq!(restored.get_peers(), book.get_peers());
+    for addr in &addrs {
+        for _ in 0..4 {
+            restored.record_connect(addr, false);
+        }
+    }
+    assert_eq!(restored.best(), "1.2.3.4:21841");
+
+    // Configured peers are never demoted to learned ones, and denied learned peers are dropped
+    let restored = PeerBook::new(vec!["5.6.7.8:21841".to_string()], &PeerFilter::new("", "1.2.3.0/24"), Some(file.clone()));
+    assert_eq!(restored.get_peers().len(), 1);
+    assert!(!restored.get_peers()[0].is_learned);
+
+    let _ = fs::remove_file(&file);
+}
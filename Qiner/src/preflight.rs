@@ -0,0 +1,183 @@
+use std::net::{TcpStream, ToSocketAddrs};
+use std::time::Duration;
+use serde_json::{json, Value};
+use lib::env_names::{ENV_ID, ENV_NUMBER_OF_THREADS, ENV_RANDOM_SEED, ENV_SERVER_IP, ENV_SERVER_PORT, ENV_SOLUTION_THRESHOLD, ENV_VERSION};
+use crate::settings::Settings;
+use lib::version::{DEFAULT_VERSION, format_version, parse_version};
+use crate::arch;
+use crate::converters::parse_id;
+use crate::peer_filter::PeerFilter;
+use crate::peers::QUIC_SCHEME;
+
+/// Timeout of the connectivity probe of every peer.
+const PROBE_TIMEOUT: Duration = Duration::from_secs(3);
+
+/// Outcome of a single preflight check.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Check {
+    /// What was checked, e.g. a variable name or `peer 1.2.3.4:21841`
+    pub name: String,
+    pub is_ok: bool,
+    /// Why the check failed, or what was found
+    pub detail: String,
+}
+
+impl Check {
+    fn new(name: impl Into<String>, is_ok: bool, detail: impl Into<String>) -> Self {
+        Check { name: name.into(), is_ok, detail: detail.into() }
+    }
+}
+
+/// Validates the configuration values Qiner needs to mine.
+///
+/// # Arguments
+/// * `value` - Gets the value of a variable, `None` if it is not set.
+///
+/// # Returns
+/// One check per variable Qiner needs, and one per other variable that is invalid.
+pub fn check_config(value: impl Fn(&str) -> Option<String>) -> Vec<Check> {
+    let errors = Settings::from_lookup(&value).err().unwrap_or_default();
+
+    // The number of threads is optional, but must be valid when set
+    let names = [ENV_ID, ENV_SERVER_IP, ENV_SERVER_PORT, ENV_VERSION, ENV_RANDOM_SEED, ENV_SOLUTION_THRESHOLD].into_iter()
+        .chain(value(ENV_NUMBER_OF_THREADS).is_some().then_some(ENV_NUMBER_OF_THREADS))
+        .collect::<Vec<_>>();
+    let others = errors.iter()
+        .filter(|error| !names.contains(&error.name))
+        .map(|error| Check::new(error.name, false, error.reason.clone()));
+
+    let checks = names.iter().map(|&name| {
+        if let Some(error) = errors.iter().find(|error| error.name == name) {
+            return Check::new(name, false, error.reason.clone());
+        }
+
+        let raw = value(name).map(|raw| raw.trim().to_string()).unwrap_or_default();
+        match name {
+            // The settings only check the letters of the ID, not its checksum
+            ENV_ID if parse_id(&raw).is_none() => Check::new(name, false, "invalid ID or checksum"),
+            ENV_ID => Check::new(name, true, "checksum valid"),
+            ENV_VERSION => match parse_version(&raw) {
+                Some(version) => Check::new(name, true, format_version(&version)),
+                None => Check::new(name, true, format!("{} (built-in)", format_version(&DEFAULT_VERSION))),
+            },
+            _ => Check::new(name, true, raw),
+        }
+    });
+    checks.chain(others).collect()
+}
+
+/// Resolves every peer and checks that it accepts connections.
+///
+/// # Arguments
+/// * `addrs` - The addresses of the peers, as `host:port`.
+/// * `filter` - The allowlist and denylist, peers it rejects fail without being probed.
+/// * `socks` - The SOCKS proxy the peers are reached through, probed instead of them so they do not see the IP of the rig.
+///
+/// # Returns
+/// One check per peer, and one for the SOCKS proxy.
+pub fn check_peers(addrs: &[String], filter: &PeerFilter, socks: Option<&str>) -> Vec<Check> {
+    let peers = addrs.iter().map(|addr| {
+        let name = format!("peer {addr}");
+        if !filter.is_allowed(addr) {
+            return Check::new(name, false, "rejected by the allowlist or denylist");
+        }
+        if addr.starts_with(QUIC_SCHEME) {
+            // QUIC relays are only reached by the miner, over UDP
+            return match socks {
+                Some(_) => Check::new(name, false, "QUIC cannot go through the SOCKS proxy"),
+                None if cfg!(feature = "quic") => Check::new(name, true, "QUIC, not probed"),
+                None => Check::new(name, false, "QUIC needs the `quic` feature"),
+            };
+        }
+        if socks.is_some() {
+            return Check::new(name, true, "reached through the SOCKS proxy, not probed");
+        }
+        probe(name, addr)
+    }).collect::<Vec<_>>();
+
+    socks.map(|socks| probe(format!("SOCKS proxy {socks}"), socks)).into_iter().chain(peers).collect()
+}
+
+/// Resolves an address and checks that it accepts connections.
+fn probe(name: String, addr: &str) -> Check {
+    let resolved = match addr.to_socket_addrs() {
+        Ok(resolved) => resolved.collect::<Vec<_>>(),
+        Err(err) => return Check::new(name, false, format!("cannot be resolved: {err}")),
+    };
+    match resolved.iter().find(|resolved| TcpStream::connect_timeout(resolved, PROBE_TIMEOUT).is_ok()) {
+        Some(connected) => Check::new(name, true, format!("connected to {connected}")),
+        None if resolved.is_empty() => Check::new(name, false, "resolves to no address"),
+        None => Check::new(name, false, "does not accept connections"),
+    }
+}
+
+/// Checks the CPU features the miner uses.
+///
+/// Without RDRAND the nonces come from the OS generator, and without AVX2 the miner uses
+/// the slower Keccak, so both only show in the detail.
+///
+/// # Returns
+/// One check per feature.
+pub fn check_cpu_features() -> Vec<Check> {
+    let rdrand = match arch::hardware_random_health() {
+        Ok(()) => "available".to_string(),
+        Err(reason) => format!("{reason}, using the OS random generator"),
+    };
+    let has_avx2 = arch::has_keccak_x4();
+    vec![
+        Check::new("cpu rdrand", true, rdrand),
+        Check::new("cpu avx2", true, if has_avx2 { "available" } else { "not available, using the slower Keccak" }),
+    ]
+}
+
+/// Builds the machine-readable report of the checks.
+///
+/// # Arguments
+/// * `checks` - The outcomes of the checks.
+///
+/// # Returns
+/// A JSON object with the overall `ok` flag and every check.
+pub fn report(checks: &[Check]) -> Value {
+    json!({
+        "ok": checks.iter().all(|check| check.is_ok),
+        "checks": checks.iter().map(|check| json!({
+            "name": check.name,
+            "ok": check.is_ok,
+            "detail": check.detail,
+        })).collect::<Vec<_>>(),
+    })
+}
+
+#[test]
+/// Tests that invalid and missing values fail and that the report sums them up.
+fn test_check_config() {
+    let config = [
+        (ENV_ID, "UBAZRCVPOZTDKGCBNPGYFUPLZXDDNHSEGJRTAJKWJBHJDKHMAKVVFAKCZGRA"),
+        (ENV_SERVER_IP, "127.0.0.1"),
+        (ENV_SERVER_PORT, "21841"),
+        (ENV_VERSION, "1.142.1"),
+        (ENV_RANDOM_SEED, "1,0,233,9,136,69,43,139"),
+        (ENV_NUMBER_OF_THREADS, "0"),
+        (lib::env_names::ENV_KECCAK_LANES, "3"),
+    ];
+    let checks = check_config(|name| config.iter().find(|(key, _)| *key == name).map(|(_, value)| value.to_string()));
+
+    let failed = checks.iter().filter(|check| !check.is_ok).map(|check| check.name.as_str()).collect::<Vec<_>>();
+    assert_eq!(failed, [ENV_ID, ENV_SOLUTION_THRESHOLD, ENV_NUMBER_OF_THREADS, lib::env_names::ENV_KECCAK_LANES]);
+
+    let report = report(&checks);
+    assert_eq!(report["ok"], false);
+    assert_eq!(report["checks"].as_array().unwrap().len(), checks.len());
+    assert_eq!(report["checks"][5]["detail"], "not set");
+
+    let unset = check_config(|_| None);
+    assert!(unset.iter().find(|check| check.name == ENV_VERSION).unwrap().is_ok);
+
+    let peers = check_peers(&["10.0.0.1:21841".to_string()], &PeerFilter::new("", "10.0.0.0/8"), None);
+    assert!(!peers[0].is_ok);
+
+    // Behind a SOCKS proxy only the proxy is probed
+    let peers = check_peers(&["10.0.0.1:21841".to_string(), "quic://10.0.0.2:21842".to_string()], &PeerFilter::default(), Some("127.0.0.1:1"));
+    assert_eq!(peers.iter().map(|check| check.is_ok).collect::<Vec<_>>(), [false, true, false]);
+    assert_eq!(peers[0].name, "SOCKS proxy 127.0.0.1:1");
+}
@@ -0,0 +1,99 @@
+//! Scheduling priority of the process, so mining can yield the CPU to the other programs of the machine.
+
+use std::fmt;
+use std::io;
+
+/// How the OS schedules the threads of Qiner against the other programs.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Priority {
+    /// Only the time no other program wants, nice 19 or the idle priority class
+    Idle,
+    /// Behind the interactive programs, nice 10 or the below normal priority class
+    Low,
+    /// The priority Qiner was started with
+    #[default]
+    Normal,
+}
+
+impl Priority {
+    /// Parses the priority from its name
+    ///
+    /// # Arguments
+    /// * `name` - `idle`, `low` or `normal`
+    ///
+    /// # Returns
+    /// The priority, or why the name is unknown.
+    pub fn parse(name: &str) -> Result<Self, String> {
+        match name.trim().to_ascii_lowercase().as_str() {
+            "idle" => Ok(Priority::Idle),
+            "low" => Ok(Priority::Low),
+            "normal" => Ok(Priority::Normal),
+            other => Err(format!("unknown priority {other:?}, expected idle, low or normal")),
+        }
+    }
+
+    /// Gets the name of the priority
+    pub fn name(&self) -> &'static str {
+        match self {
+            Priority::Idle => "idle",
+            Priority::Low => "low",
+            Priority::Normal => "normal",
+        }
+    }
+
+    /// Lowers the priority of the process, to be called before the threads are spawned since
+    /// Linux keeps a priority per thread, which new threads inherit.
+    ///
+    /// # Returns
+    /// The error of the OS, `Ok` without a change for `Normal` and on platforms without priorities.
+    pub fn apply(&self) -> io::Result<()> {
+        if *self == Priority::Normal {
+            return Ok(());
+        }
+
+        #[cfg(unix)]
+        {
+            /// The `which` of `setpriority` for a process
+            const PRIO_PROCESS: i32 = 0;
+
+            extern "C" {
+                fn setpriority(which: i32, who: u32, priority: i32) -> i32;
+            }
+
+            let nice = if *self == Priority::Idle { 19 } else { 10 };
+            if unsafe { setpriority(PRIO_PROCESS, 0, nice) } != 0 {
+                return Err(io::Error::last_os_error());
+            }
+            Ok(())
+        }
+        #[cfg(windows)]
+        {
+            use std::ffi::c_void;
+
+            const IDLE_PRIORITY_CLASS: u32 = 0x40;
+            const BELOW_NORMAL_PRIORITY_CLASS: u32 = 0x4000;
+
+            #[link(name = "kernel32")]
+            extern "system" {
+                fn GetCurrentProcess() -> *mut c_void;
+                fn SetPriorityClass(process: *mut c_void, priority_class: u32) -> i32;
+            }
+
+            let priority_class = if *self == Priority::Idle { IDLE_PRIORITY_CLASS } else { BELOW_NORMAL_PRIORITY_CLASS };
+            if unsafe { SetPriorityClass(GetCurrentProcess(), priority_class) } == 0 {
+                return Err(io::Error::last_os_error());
+            }
+            Ok(())
+        }
+        #[cfg(not(any(unix, windows)))]
+        {
+            Ok(())
+        }
+    }
+}
+
+impl fmt::Display for Priority {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.name())
+    }
+}
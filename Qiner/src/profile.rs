@@ -0,0 +1,115 @@
+//! Tuning presets for users who would rather not read about every setting.
+//!
+//! A profile only fills the settings the environment and the `.env` file leave unset, so any
+//! variable given explicitly still wins over the preset.
+
+use std::env;
+use std::fmt;
+use lib::env_names::{ENV_NUMBER_OF_THREADS, ENV_PRIORITY, ENV_STATS_INTERVAL};
+use crate::priority::Priority;
+use crate::stats::Verbosity;
+
+/// A preset of the threads, the priority and the stats.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Profile {
+    /// Half the CPUs at idle priority, quiet stats every minute: mining without being noticed
+    Eco,
+    /// Every CPU but one at low priority, stats every 10 seconds: the machine stays responsive
+    Balanced,
+    /// Every CPU at normal priority, stats every second: the hashrate above all
+    Max,
+}
+
+impl Profile {
+    /// Parses the profile from its name
+    ///
+    /// # Arguments
+    /// * `name` - `eco`, `balanced` or `max`
+    ///
+    /// # Returns
+    /// The profile, or why the name is unknown.
+    pub fn parse(name: &str) -> Result<Self, String> {
+        match name.trim().to_ascii_lowercase().as_str() {
+            "eco" => Ok(Profile::Eco),
+            "balanced" => Ok(Profile::Balanced),
+            "max" => Ok(Profile::Max),
+            other => Err(format!("unknown profile {other:?}, expected eco, balanced or max")),
+        }
+    }
+
+    /// Gets the name of the profile
+    pub fn name(&self) -> &'static str {
+        match self {
+            Profile::Eco => "eco",
+            Profile::Balanced => "balanced",
+            Profile::Max => "max",
+        }
+    }
+
+    /// Gets the settings of the profile.
+    ///
+    /// # Arguments
+    /// * `available_cpus` - The number of CPUs the process may use
+    ///
+    /// # Returns
+    /// The variables and their values.
+    pub fn settings(&self, available_cpus: usize) -> Vec<(&'static str, String)> {
+        let (threads, priority, stats_interval) = match self {
+            Profile::Eco => (available_cpus / 2, Priority::Idle, 60),
+            Profile::Balanced => (available_cpus.saturating_sub(1), Priority::Low, 10),
+            Profile::Max => (available_cpus, Priority::Normal, 1),
+        };
+        vec![
+            (ENV_NUMBER_OF_THREADS, threads.max(1).to_string()),
+            (ENV_PRIORITY, priority.name().to_string()),
+            (ENV_STATS_INTERVAL, stats_interval.to_string()),
+        ]
+    }
+
+    /// Gets how much of the progress the console logs, unless `--quiet` or `--verbose` is given.
+    pub fn verbosity(&self) -> Verbosity {
+        match self {
+            Profile::Eco => Verbosity::Quiet,
+            Profile::Balanced | Profile::Max => Verbosity::Normal,
+        }
+    }
+
+    /// Sets the variables of the profile that are not set yet, to be called before any thread is spawned.
+    ///
+    /// # Arguments
+    /// * `available_cpus` - The number of CPUs the process may use
+    ///
+    /// # Returns
+    /// The variables set, the others being given explicitly.
+    pub fn apply(&self, available_cpus: usize) -> Vec<&'static str> {
+        self.settings(available_cpus).into_iter()
+            .filter(|(name, _)| env::var(name).map_or(true, |value| value.trim().is_empty()))
+            .map(|(name, value)| {
+                env::set_var(name, value);
+                name
+            })
+            .collect()
+    }
+}
+
+impl fmt::Display for Profile {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+#[test]
+/// Tests the settings of the profiles on small and large machines.
+fn test_profiles() {
+    assert_eq!(Profile::parse(" Eco"), Ok(Profile::Eco));
+    assert!(Profile::parse("turbo").is_err());
+
+    let threads = |profile: Profile, available_cpus| profile.settings(available_cpus)[0].1.clone();
+    assert_eq!(threads(Profile::Eco, 16), "8");
+    assert_eq!(threads(Profile::Balanced, 16), "15");
+    assert_eq!(threads(Profile::Max, 16), "16");
+    // A single CPU still mines
+    assert_eq!(threads(Profile::Eco, 1), "1");
+    assert_eq!(threads(Profile::Balanced, 1), "1");
+    assert_eq!(Profile::Eco.settings(4)[1], (ENV_PRIORITY, "idle".to_string()));
+}
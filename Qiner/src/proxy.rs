@@ -0,0 +1,349 @@
+use std::collections::HashMap;
+use std::fs::{self, OpenOptions};
+use std::io::{self, Write};
+use std::mem::size_of;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use lib::types::{Nonce64, PublicKey64};
+use lib::types::network::Protocol;
+use tokio::io::{AsyncRead, AsyncReadExt};
+use tokio::net::TcpListener;
+use crate::bandwidth::BandwidthBudget;
+use crate::connection::NodeConnection;
+use crate::journal::{nonce_from_hex, nonce_to_hex};
+use crate::network::{Packet, RequestResponseHeader};
+use crate::submission::{backoff_delay, DEFAULT_SOLUTION_MAX_AGE};
+
+/// Default address the proxy listens on for LAN miners.
+pub const DEFAULT_PROXY_LISTEN: &str = "0.0.0.0:21841";
+
+/// Default file the solutions not forwarded yet are spilled to when the proxy stops.
+pub const DEFAULT_PROXY_SPILL_FILE: &str = "qiner-proxy-spill.txt";
+
+/// Size of the header every packet starts with.
+const HEADER_SIZE: usize = size_of::<RequestResponseHeader>();
+
+/// Largest packet accepted from a LAN miner, bigger ones close the connection.
+const MAX_PACKET_SIZE: usize = 64 * 1024;
+
+/// Interval between two forwarding rounds.
+const FORWARD_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Interval between two proxy stats lines.
+const STATS_INTERVAL: Duration = Duration::from_secs(60);
+
+/// A solution received from a LAN miner, waiting to be forwarded upstream.
+#[derive(Debug, Clone, Copy)]
+struct PendingForward {
+    public_key: PublicKey64,
+    nonce: Nonce64,
+    attempts: u32,
+    next_attempt_at: Instant,
+}
+
+/// State shared by the LAN connections and the upstream connection.
+#[derive(Debug, Default)]
+struct ProxyState {
+    /// Solutions already received, with the moment they were first received
+    seen: HashMap<(PublicKey64, Nonce64), Instant>,
+    /// Solutions waiting to be forwarded
+    queue: Vec<PendingForward>,
+    received: usize,
+    duplicates: usize,
+    forwarded: usize,
+}
+
+impl ProxyState {
+    /// Queues a solution unless it was already received.
+    ///
+    /// # Arguments
+    /// * `public_key` - The computor public key of the solution.
+    /// * `nonce` - The nonce of the solution.
+    fn accept(&mut self, public_key: PublicKey64, nonce: Nonce64) {
+        self.received += 1;
+        let now = Instant::now();
+        if self.seen.insert((public_key, nonce), now).is_some() {
+            self.duplicates += 1;
+            return;
+        }
+        self.queue.push(PendingForward {
+            public_key,
+            nonce,
+            attempts: 0,
+            next_attempt_at: now,
+        });
+    }
+
+    /// Removes the solutions whose next forwarding attempt is due.
+    fn take_ready(&mut self, now: Instant) -> Vec<PendingForward> {
+        let (ready, waiting) = self.queue.drain(..).partition(|pending| pending.next_attempt_at <= now);
+        self.queue = waiting;
+        ready
+    }
+
+    /// Puts solutions back after a failed forwarding attempt, delaying their next attempt.
+    fn reschedule(&mut self, failed: Vec<PendingForward>, now: Instant) {
+        self.queue.extend(failed.into_iter().map(|mut pending| {
+            pending.attempts += 1;
+            pending.next_attempt_at = now + backoff_delay(pending.attempts);
+            pending
+        }));
+    }
+}
+
+/// Runs the LAN proxy until Ctrl-C.
+///
+/// LAN miners point their `SERVER_IP`/`SERVER_PORT` to the proxy, which decodes their
+/// solution packets, drops duplicates and forwards the rest upstream over one connection,
+/// retried with backoff and kept alive in between. Solutions not forwarded yet are spilled when the proxy stops and
+/// queued again on the next start.
+///
+/// # Arguments
+/// * `listen` - The address to listen on for LAN miners
+/// * `quic_listen` - The UDP address to listen on for LAN miners submitting over QUIC, with the `quic` feature
+/// * `upstream` - The address of the node
+/// * `spill_file` - File the solutions not forwarded yet are spilled to
+/// * `protocol` - The protocol version forwarded packets are sent with
+/// * `keep_alive_interval` - The interval between two keep-alives of the idle upstream connection, `None` to send none
+/// * `max_bytes_per_hour` - The cap on the bytes sent upstream per hour, keep-alives are deferred once it is reached
+#[cfg_attr(not(feature = "quic"), allow(unused_variables))]
+pub async fn run_proxy(listen: String, quic_listen: Option<String>, upstream: String, spill_file: PathBuf, protocol: Protocol, keep_alive_interval: Option<Duration>, max_bytes_per_hour: Option<u64>) {
+    let state = Arc::new(Mutex::new(ProxyState::default()));
+
+    match take_spilled(&spill_file) {
+        Ok(spilled) if !spilled.is_empty() => {
+            log::info!("Loaded {} spilled solutions from {}", spilled.len(), spill_file.display());
+            let mut state = state.lock().unwrap();
+            spilled.into_iter().for_each(|(public_key, nonce)| state.accept(public_key, nonce));
+        }
+        Ok(_) => {}
+        Err(err) => log::error!("Failed to load spilled solutions from {}: {:?}", spill_file.display(), err),
+    }
+
+    let listener = match TcpListener::bind(&listen).await {
+        Ok(listener) => listener,
+        Err(err) => {
+            log::error!("Failed to listen on {listen}: {:?}", err);
+            return;
+        }
+    };
+    log::info!("Proxy listening on {listen}, forwarding to {upstream} (keep-alive: {:?})", keep_alive_interval);
+
+    tokio::spawn(accept_task(listener, state.clone()));
+    #[cfg(feature = "quic")]
+    if let Some(quic_listen) = quic_listen {
+        match crate::quic::listen(&quic_listen) {
+            Ok(endpoint) => {
+                log::info!("Proxy listening on {quic_listen} for QUIC");
+                tokio::spawn(accept_quic_task(endpoint, state.clone()));
+            }
+            Err(err) => log::error!("Failed to listen on {quic_listen} for QUIC: {:?}", err),
+        }
+    }
+    let mut connection = NodeConnection::new(upstream, protocol, keep_alive_interval);
+    connection.set_bandwidth(Arc::new(BandwidthBudget::new(max_bytes_per_hour)));
+    tokio::spawn(forward_task(connection, state.clone(), protocol));
+    tokio::spawn(stats_task(state.clone()));
+
+    if let Err(err) = tokio::signal::ctrl_c().await {
+        log::error!("Failed to install the Ctrl-C handler: {:?}", err);
+        return;
+    }
+
+    let queue = state.lock().unwrap().queue.iter().map(|pending| (pending.public_key, pending.nonce)).collect::<Vec<_>>();
+    if !queue.is_empty() {
+        match spill(&spill_file, &queue) {
+            Ok(_) => log::info!("Spilled {} solutions to {}", queue.len(), spill_file.display()),
+            Err(err) => log::error!("Failed to spill {} solutions: {:?}", queue.len(), err),
+        }
+    }
+}
+
+/// Asynchronous task accepting LAN miner connections.
+///
+/// # Arguments
+/// * `listener` - The listener of the proxy
+/// * `state` - The shared proxy state
+async fn accept_task(listener: TcpListener, state: Arc<Mutex<ProxyState>>) {
+    loop {
+        match listener.accept().await {
+            Ok((stream, peer)) => {
+                log::debug!("LAN miner connected from {peer}");
+                tokio::spawn(read_packets(stream, state.clone()));
+            }
+            Err(err) => log::warn!("Failed to accept a LAN miner: {:?}", err),
+        }
+    }
+}
+
+/// Asynchronous task accepting LAN miner connections over QUIC, each submission being a stream of packets.
+///
+/// # Arguments
+/// * `endpoint` - The QUIC endpoint of the proxy
+/// * `state` - The shared proxy state
+#[cfg(feature = "quic")]
+async fn accept_quic_task(endpoint: quinn::Endpoint, state: Arc<Mutex<ProxyState>>) {
+    while let Some(incoming) = endpoint.accept().await {
+        let state = state.clone();
+        tokio::spawn(async move {
+            let connection = match incoming.await {
+                Ok(connection) => connection,
+                Err(err) => {
+                    log::warn!("Failed to accept a LAN miner over QUIC: {:?}", err);
+                    return;
+                }
+            };
+            log::debug!("LAN miner connected over QUIC from {}", connection.remote_address());
+            while let Ok(stream) = connection.accept_uni().await {
+                tokio::spawn(read_packets(stream, state.clone()));
+            }
+        });
+    }
+}
+
+/// Reads the packets of a LAN miner until it disconnects, queuing its solutions.
+///
+/// # Arguments
+/// * `stream` - The connection of the LAN miner, or one of its QUIC streams
+/// * `state` - The shared proxy state
+async fn read_packets(mut stream: impl AsyncRead + Unpin, state: Arc<Mutex<ProxyState>>) {
+    loop {
+        let mut header = [0u8; HEADER_SIZE];
+        if stream.read_exact(&mut header).await.is_err() {
+            return;
+        }
+
+        // The size is the first 3 bytes, little endian, header included
+        let size = header[0] as usize | (header[1] as usize) << 8 | (header[2] as usize) << 16;
+        if !(HEADER_SIZE..=MAX_PACKET_SIZE).contains(&size) {
+            log::warn!("Dropping a LAN miner sending a packet of {size} bytes");
+            return;
+        }
+
+        let mut bytes = header.to_vec();
+        bytes.resize(size, 0);
+        if stream.read_exact(&mut bytes[HEADER_SIZE..]).await.is_err() {
+            return;
+        }
+
+        match Packet::decode_solution(&bytes) {
+            Some((public_key, nonce)) => state.lock().unwrap().accept(public_key, nonce),
+            None => log::debug!("Ignoring a packet of type {} from a LAN miner", header[HEADER_SIZE - 1]),
+        }
+    }
+}
+
+/// Asynchronous task forwarding the queued solutions over a single upstream connection.
+///
+/// While nothing is ready, the connection is kept alive, so a dead one is replaced before solutions are written to it.
+///
+/// # Arguments
+/// * `upstream` - The connection to the node
+/// * `state` - The shared proxy state
+/// * `protocol` - The protocol version forwarded packets are sent with
+async fn forward_task(mut upstream: NodeConnection, state: Arc<Mutex<ProxyState>>, protocol: Protocol) {
+    loop {
+        tokio::time::sleep(FORWARD_INTERVAL).await;
+
+        let ready = {
+            let mut state = state.lock().unwrap();
+            let now = Instant::now();
+            state.seen.retain(|_, received_at| now.saturating_duration_since(*received_at) < DEFAULT_SOLUTION_MAX_AGE);
+            state.take_ready(now)
+        };
+        if ready.is_empty() {
+            if let Err(err) = upstream.keep_alive().await {
+                log::warn!("Connection to {} lost while idle: {}", upstream.get_addr(), err);
+            }
+            continue;
+        }
+
+        let data = ready.iter().flat_map(|pending| Packet::solutions_to_bytes(protocol, &pending.public_key, [&pending.nonce])).collect::<Vec<u8>>();
+        let result = upstream.write_all(&data).await;
+
+        let mut state = state.lock().unwrap();
+        match result {
+            Ok(_) => {
+                log::info!("Forwarded {} solutions to {}", ready.len(), upstream.get_addr());
+                state.forwarded += ready.len();
+            }
+            Err(err) => {
+                log::error!("Failed to forward {} solutions to {}: {:?}", ready.len(), upstream.get_addr(), err);
+                state.reschedule(ready, Instant::now());
+            }
+        }
+    }
+}
+
+/// Asynchronous task logging the proxy counters.
+///
+/// # Arguments
+/// * `state` - The shared proxy state
+async fn stats_task(state: Arc<Mutex<ProxyState>>) {
+    loop {
+        tokio::time::sleep(STATS_INTERVAL).await;
+
+        let state = state.lock().unwrap();
+        log::info!(
+            "proxy: {} received | {} duplicates | {} forwarded | {} queued",
+            state.received,
+            state.duplicates,
+            state.forwarded,
+            state.queue.len()
+        );
+    }
+}
+
+/// Appends solutions to the proxy spill file, one `<public key> <nonce>` hex pair per line.
+///
+/// # Arguments
+/// * `path` - The spill file.
+/// * `solutions` - The public keys and nonces to spill.
+fn spill(path: &Path, solutions: &[(PublicKey64, Nonce64)]) -> io::Result<()> {
+    let mut file = OpenOptions::new().create(true).append(true).open(path)?;
+    for (public_key, nonce) in solutions {
+        writeln!(file, "{} {}", nonce_to_hex(public_key), nonce_to_hex(nonce))?;
+    }
+    file.sync_all()
+}
+
+/// Reads back the solutions of the proxy spill file and removes it.
+///
+/// # Arguments
+/// * `path` - The spill file.
+///
+/// # Returns
+/// The spilled public keys and nonces, empty if the file does not exist. Invalid lines are skipped.
+fn take_spilled(path: &Path) -> io::Result<Vec<(PublicKey64, Nonce64)>> {
+    let content = match fs::read_to_string(path) {
+        Ok(content) => content,
+        Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(err) => return Err(err),
+    };
+
+    let solutions = content.lines().filter_map(|line| {
+        let (public_key, nonce) = line.split_once(' ')?;
+        Some((nonce_from_hex(public_key)?, nonce_from_hex(nonce)?))
+    }).collect();
+    fs::remove_file(path)?;
+
+    Ok(solutions)
+}
+
+#[test]
+/// Tests that duplicates are dropped and failed forwards wait for their backoff.
+fn test_proxy_state() {
+    let mut state = ProxyState::default();
+    state.accept([1; 4], [2; 4]);
+    state.accept([1; 4], [2; 4]);
+    state.accept([3; 4], [2; 4]);
+    assert_eq!((state.received, state.duplicates, state.queue.len()), (3, 1, 2));
+
+    let now = Instant::now();
+    let ready = state.take_ready(now);
+    assert_eq!(ready.len(), 2);
+    state.reschedule(ready, now);
+    assert!(state.take_ready(now).is_empty());
+    assert_eq!(state.take_ready(now + backoff_delay(1)).len(), 2);
+}
@@ -0,0 +1,196 @@
+//! Experimental QUIC transport of the solutions, enabled by the `quic` feature.
+//!
+//! Nodes only speak TCP, so QUIC is for relays that support it, such as `qiner proxy
+//! --quic-listen`. Packets keep the framing of the TCP protocol, each submission on its own
+//! unidirectional stream of a connection reused between submissions, so a lost datagram only
+//! delays its own submission. The connection is encrypted but the relay is not authenticated,
+//! as with TCP, so it presents a self-signed certificate the miner does not verify.
+
+use std::io;
+use std::net::SocketAddr;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use quinn::crypto::rustls::{QuicClientConfig, QuicServerConfig};
+use quinn::{ClientConfig, Connection, Endpoint, IdleTimeout, ServerConfig, TransportConfig};
+use rustls::client::danger::{HandshakeSignatureValid, ServerCertVerified, ServerCertVerifier};
+use rustls::crypto::{verify_tls12_signature, verify_tls13_signature, CryptoProvider};
+use rustls::pki_types::{CertificateDer, PrivateKeyDer, PrivatePkcs8KeyDer, ServerName, UnixTime};
+use rustls::{DigitallySignedStruct, SignatureScheme};
+
+/// Application protocol negotiated by the miner and the relay.
+const ALPN: &[u8] = b"qiner";
+
+/// Name the self-signed certificate of the relay is issued to.
+const SERVER_NAME: &str = "qiner";
+
+/// Time without any packet after which a connection, or a handshake, is given up.
+const IDLE_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Interval between two pings of an idle connection, keeping it open between submissions.
+const KEEP_ALIVE_INTERVAL: Duration = Duration::from_secs(4);
+
+/// Gets the crypto provider of the QUIC connections.
+fn provider() -> Arc<CryptoProvider> {
+    Arc::new(rustls::crypto::ring::default_provider())
+}
+
+/// Accepts any certificate of the relay, only checking that the handshake is signed by its key.
+#[derive(Debug)]
+struct SkipServerVerification(Arc<CryptoProvider>);
+
+impl ServerCertVerifier for SkipServerVerification {
+    fn verify_server_cert(&self, _: &CertificateDer<'_>, _: &[CertificateDer<'_>], _: &ServerName<'_>, _: &[u8], _: UnixTime) -> Result<ServerCertVerified, rustls::Error> {
+        Ok(ServerCertVerified::assertion())
+    }
+
+    fn verify_tls12_signature(&self, message: &[u8], cert: &CertificateDer<'_>, dss: &DigitallySignedStruct) -> Result<HandshakeSignatureValid, rustls::Error> {
+        verify_tls12_signature(message, cert, dss, &self.0.signature_verification_algorithms)
+    }
+
+    fn verify_tls13_signature(&self, message: &[u8], cert: &CertificateDer<'_>, dss: &DigitallySignedStruct) -> Result<HandshakeSignatureValid, rustls::Error> {
+        verify_tls13_signature(message, cert, dss, &self.0.signature_verification_algorithms)
+    }
+
+    fn supported_verify_schemes(&self) -> Vec<SignatureScheme> {
+        self.0.signature_verification_algorithms.supported_schemes()
+    }
+}
+
+/// Creates the transport configuration of the miner, which keeps its connection alive.
+fn transport_config() -> Arc<TransportConfig> {
+    let mut transport = TransportConfig::default();
+    transport.max_idle_timeout(Some(IdleTimeout::try_from(IDLE_TIMEOUT).unwrap()));
+    transport.keep_alive_interval(Some(KEEP_ALIVE_INTERVAL));
+    Arc::new(transport)
+}
+
+/// Creates the client configuration of the miner.
+fn client_config() -> io::Result<ClientConfig> {
+    let mut crypto = rustls::ClientConfig::builder_with_provider(provider())
+        .with_protocol_versions(&[&rustls::version::TLS13])
+        .map_err(io::Error::other)?
+        .dangerous()
+        .with_custom_certificate_verifier(Arc::new(SkipServerVerification(provider())))
+        .with_no_client_auth();
+    crypto.alpn_protocols = vec![ALPN.to_vec()];
+    let mut config = ClientConfig::new(Arc::new(QuicClientConfig::try_from(crypto).map_err(io::Error::other)?));
+    config.transport_config(transport_config());
+    Ok(config)
+}
+
+/// Creates the server configuration of the relay, with a new self-signed certificate.
+fn server_config() -> io::Result<ServerConfig> {
+    let certified = rcgen::generate_simple_self_signed(vec![SERVER_NAME.to_string()]).map_err(io::Error::other)?;
+    let key = PrivateKeyDer::Pkcs8(PrivatePkcs8KeyDer::from(certified.signing_key.serialize_der()));
+    let mut crypto = rustls::ServerConfig::builder_with_provider(provider())
+        .with_protocol_versions(&[&rustls::version::TLS13])
+        .map_err(io::Error::other)?
+        .with_no_client_auth()
+        .with_single_cert(vec![certified.cert.der().clone()], key)
+        .map_err(io::Error::other)?;
+    crypto.alpn_protocols = vec![ALPN.to_vec()];
+    Ok(ServerConfig::with_crypto(Arc::new(QuicServerConfig::try_from(crypto).map_err(io::Error::other)?)))
+}
+
+/// Creates the endpoint of a relay.
+///
+/// # Arguments
+/// * `listen` - The UDP address to listen on, as `ip:port`
+///
+/// # Returns
+/// The endpoint, whose connections are accepted with `Endpoint::accept`.
+pub fn listen(listen: &str) -> io::Result<Endpoint> {
+    let addr = listen.parse::<SocketAddr>().map_err(|err| io::Error::new(io::ErrorKind::InvalidInput, err))?;
+    Endpoint::server(server_config()?, addr)
+}
+
+/// Sender of solutions over QUIC, keeping its connection open between submissions.
+#[derive(Debug, Default)]
+pub struct QuicSender {
+    endpoint: Option<Endpoint>,
+    /// The open connection, with the address it was opened to
+    connection: Option<(String, Connection)>,
+}
+
+impl QuicSender {
+    /// Creates a new `QuicSender`, its endpoint is only bound on the first submission.
+    pub fn new() -> Self {
+        QuicSender::default()
+    }
+
+    /// Connects to a relay unless the connection to it is still open.
+    async fn connect(&mut self, addr: &str) -> io::Result<Connection> {
+        if let Some((connected_addr, connection)) = &self.connection {
+            if connected_addr == addr && connection.close_reason().is_none() {
+                return Ok(connection.clone());
+            }
+        }
+        self.connection = None;
+
+        let socket_addr = tokio::net::lookup_host(addr).await?.next().ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, format!("no address for {addr}")))?;
+        let endpoint = match &self.endpoint {
+            Some(endpoint) => endpoint,
+            None => {
+                let bind_addr = if socket_addr.is_ipv4() { "0.0.0.0:0" } else { "[::]:0" };
+                let mut endpoint = Endpoint::client(bind_addr.parse().unwrap())?;
+                endpoint.set_default_client_config(client_config()?);
+                self.endpoint.insert(endpoint)
+            }
+        };
+
+        let connecting_at = Instant::now();
+        let connection = endpoint.connect(socket_addr, SERVER_NAME).map_err(io::Error::other)?.await?;
+        log::info!("QUIC: connected to {addr} in {:?}", connecting_at.elapsed());
+        self.connection = Some((addr.to_string(), connection.clone()));
+        Ok(connection)
+    }
+
+    /// Sends packets on a new stream, connecting first if needed.
+    ///
+    /// # Arguments
+    /// * `addr` - The address of the relay, as `host:port` without the scheme
+    /// * `data` - The packets, framed as over TCP
+    ///
+    /// # Returns
+    /// The error of the connection or of the write, after which the connection is closed.
+    pub async fn send(&mut self, addr: &str, data: &[u8]) -> io::Result<()> {
+        let connection = self.connect(addr).await?;
+        let result = async {
+            let mut stream = connection.open_uni().await?;
+            stream.write_all(data).await?;
+            stream.finish()?;
+            stream.stopped().await.map_err(io::Error::other)?;
+            Ok(())
+        }.await;
+
+        if result.is_err() {
+            connection.close(0u32.into(), b"write failed");
+            self.connection = None;
+        }
+        result
+    }
+}
+
+#[test]
+/// Tests that packets sent over QUIC arrive unchanged, over one connection reused between submissions.
+fn test_quic() {
+    let runtime = tokio::runtime::Builder::new_current_thread().enable_all().build().unwrap();
+    runtime.block_on(async {
+        let relay = listen("127.0.0.1:0").unwrap();
+        let addr = relay.local_addr().unwrap().to_string();
+        let received = tokio::spawn(async move {
+            let connection = relay.accept().await.unwrap().await.unwrap();
+            let mut received = Vec::new();
+            for _ in 0..2 {
+                let mut stream = connection.accept_uni().await.unwrap();
+                received.push(stream.read_to_end(1024).await.unwrap());
+            }
+            received
+        });
+
+        let mut sender = QuicSender::new();
+        sender.send(&addr, b"first packets").await.unwrap();
+        sender.send(&addr, b"second packets").await.unwrap();
+        assert_eq!(received.await.unwrap(), [b"first packets".to_vec(), b"second packets".to_vec()]);
+    });
+}
@@ -0,0 +1,181 @@
+//! Watches the configuration file and reloads the settings that are safe to change while mining.
+
+use std::collections::{BTreeMap, BTreeSet};
+use std::env;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+use notify::{Event, RecursiveMode, Watcher};
+use lib::env_names::{ENV_ALGORITHM, ENV_NUMBER_OF_THREADS, ENV_PEER_ALLOWLIST, ENV_PEER_DENYLIST, ENV_RUST_LOG, ENV_SERVER_IP, ENV_SERVER_PORT, ENV_SOLUTION_THRESHOLD};
+use crate::config_file::{is_structured_config, read_structured_config};
+
+/// Settings applied while mining, every other one needs a restart.
+pub const RELOADABLE_SETTINGS: [&str; 8] = [
+    ENV_SOLUTION_THRESHOLD,
+    ENV_NUMBER_OF_THREADS,
+    ENV_ALGORITHM,
+    ENV_RUST_LOG,
+    ENV_SERVER_IP,
+    ENV_SERVER_PORT,
+    ENV_PEER_ALLOWLIST,
+    ENV_PEER_DENYLIST,
+];
+
+/// Time the file must be left alone before it is read, editors save in several steps.
+const RELOAD_DEBOUNCE: Duration = Duration::from_millis(500);
+
+/// The variables of a configuration file, by name.
+pub type ConfigValues = BTreeMap<String, String>;
+
+/// A variable whose value changed in the configuration file.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ConfigChange {
+    pub name: String,
+    /// The previous value, `None` if the variable was not in the file
+    pub old: Option<String>,
+    /// The new value, `None` if the variable was removed from the file
+    pub new: Option<String>,
+}
+
+/// Reads the variables of a configuration file, without touching the environment.
+///
+/// # Arguments
+/// * `path` - The configuration file, in the `.env` format or the structured one.
+///
+/// # Returns
+/// The variables, or why the file cannot be read.
+pub fn read_config(path: &Path) -> Result<ConfigValues, String> {
+    if is_structured_config(path) {
+        return read_structured_config(path);
+    }
+
+    // Deprecated in favor of loading the file into the environment, which is what must not happen here
+    #[allow(deprecated)]
+    dotenv::from_path_iter(path)
+        .map_err(|err| err.to_string())?
+        .map(|item| item.map_err(|err| err.to_string()))
+        .collect()
+}
+
+/// Lists the variables added, removed or changed between two versions of the file.
+///
+/// # Arguments
+/// * `old` - The variables before the change.
+/// * `new` - The variables after the change.
+///
+/// # Returns
+/// The changes, sorted by name.
+pub fn diff_config(old: &ConfigValues, new: &ConfigValues) -> Vec<ConfigChange> {
+    old.keys().chain(new.keys()).collect::<BTreeSet<_>>().into_iter()
+        .filter(|name| old.get(*name) != new.get(*name))
+        .map(|name| ConfigChange { name: name.clone(), old: old.get(name).cloned(), new: new.get(name).cloned() })
+        .collect()
+}
+
+/// Checks whether a setting is applied while mining.
+pub fn is_reloadable(name: &str) -> bool {
+    RELOADABLE_SETTINGS.contains(&name)
+}
+
+/// Asynchronous task applying the changes of the configuration file while mining.
+///
+/// Variables also set in the environment keep the value of the environment, as they do
+/// on start. Changes of settings that are not reloadable are only logged.
+///
+/// # Arguments
+/// * `path` - The configuration file loaded on start.
+/// * `apply` - Applies the changes of reloadable settings, given the lookup of the current value of any variable.
+pub async fn watch_config_task(path: PathBuf, mut apply: impl FnMut(&[ConfigChange], &dyn Fn(&str) -> Option<String>) + Send + 'static) {
+    let mut current = match read_config(&path) {
+        Ok(values) => values,
+        Err(err) => {
+            log::error!("Failed to read {}, settings will not be reloaded: {err}", path.display());
+            return;
+        }
+    };
+
+    // dotenv does not override the environment, so the values differing from the file come from it
+    let from_file = current.keys().cloned().collect::<BTreeSet<_>>();
+    let from_environment = current.iter()
+        .filter(|(name, value)| env::var(name).ok().as_ref() != Some(*value))
+        .map(|(name, _)| name.clone())
+        .collect::<BTreeSet<_>>();
+
+    // The directory is watched, as editors often replace the file instead of writing to it
+    let (sender, mut receiver) = tokio::sync::mpsc::unbounded_channel();
+    let file_name = path.file_name().map(ToOwned::to_owned);
+    let watcher = notify::recommended_watcher(move |event: notify::Result<Event>| {
+        if event.is_ok_and(|event| event.paths.iter().any(|changed| changed.file_name() == file_name.as_deref())) {
+            let _ = sender.send(());
+        }
+    });
+    let dir = path.parent().filter(|dir| !dir.as_os_str().is_empty()).unwrap_or(Path::new("."));
+    let _watcher = match watcher.and_then(|mut watcher| watcher.watch(dir, RecursiveMode::NonRecursive).map(|_| watcher)) {
+        Ok(watcher) => watcher,
+        Err(err) => {
+            log::error!("Failed to watch {}, settings will not be reloaded: {err}", path.display());
+            return;
+        }
+    };
+    log::info!("Watching {} for setting changes", path.display());
+
+    while receiver.recv().await.is_some() {
+        tokio::time::sleep(RELOAD_DEBOUNCE).await;
+        while receiver.try_recv().is_ok() {}
+
+        let values = match read_config(&path) {
+            Ok(values) => values,
+            Err(err) => {
+                log::warn!("Failed to reload {}, keeping the current settings: {err}", path.display());
+                continue;
+            }
+        };
+
+        let mut reloadable = Vec::new();
+        for change in diff_config(&current, &values) {
+            if from_environment.contains(&change.name) {
+                log::warn!("{} changed in {}, but the environment sets it and takes precedence", change.name, path.display());
+            } else if is_reloadable(&change.name) {
+                reloadable.push(change);
+            } else {
+                log::warn!("{} changed in {}, restart Qiner to apply it", change.name, path.display());
+            }
+        }
+        current = values;
+
+        if !reloadable.is_empty() {
+            let value = |name: &str| match current.get(name) {
+                Some(value) if !from_environment.contains(name) => Some(value.clone()),
+                None if from_file.contains(name) && !from_environment.contains(name) => None,
+                _ => env::var(name).ok(),
+            };
+            apply(&reloadable, &value);
+        }
+    }
+}
+
+#[test]
+/// Tests that additions, removals and changes are listed, and which of them are reloadable.
+fn test_diff_config() {
+    let old = ConfigValues::from([
+        (ENV_SOLUTION_THRESHOLD.to_string(), "30".to_string()),
+        ("ID".to_string(), "A".to_string()),
+        (ENV_SERVER_PORT.to_string(), "21841".to_string()),
+    ]);
+    let new = ConfigValues::from([
+        (ENV_SOLUTION_THRESHOLD.to_string(), "31".to_string()),
+        ("ID".to_string(), "A".to_string()),
+        (ENV_RUST_LOG.to_string(), "debug".to_string()),
+    ]);
+
+    let changes = diff_config(&old, &new);
+    assert_eq!(changes, [
+        ConfigChange { name: ENV_RUST_LOG.to_string(), old: None, new: Some("debug".to_string()) },
+        ConfigChange { name: ENV_SERVER_PORT.to_string(), old: Some("21841".to_string()), new: None },
+        ConfigChange { name: ENV_SOLUTION_THRESHOLD.to_string(), old: Some("30".to_string()), new: Some("31".to_string()) },
+    ]);
+    assert!(diff_config(&new, &new).is_empty());
+
+    assert!(changes.iter().all(|change| is_reloadable(&change.name)));
+    assert!(!is_reloadable("ID"));
+    assert!(!is_reloadable("RANDOM_SEED"));
+}
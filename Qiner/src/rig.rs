@@ -0,0 +1,46 @@
+//! The name of the rig, labeling the logs, the metrics, the farm reports and the solution traces,
+//! so operators of several rigs can tell which machine found a solution or has a problem.
+
+use std::env;
+use lib::env_names::{ENV_RIG_NAME, ENV_WORKER_NAME};
+
+/// Gets the name given to the rig.
+///
+/// # Returns
+/// The name of `WORKER_NAME`, or of `RIG_NAME` its former name, `None` if neither is set.
+pub fn get_worker_name() -> Option<String> {
+    env::var(ENV_WORKER_NAME).ok()
+        .or_else(|| env::var(ENV_RIG_NAME).ok())
+        .map(|name| name.trim().to_string())
+        .filter(|name| !name.is_empty())
+}
+
+/// Gets the host name of the machine.
+///
+/// # Returns
+/// The host name, `None` if it is unknown.
+pub fn get_host_name() -> Option<String> {
+    env::var("HOSTNAME").ok()
+        .or_else(|| env::var("COMPUTERNAME").ok())
+        .or_else(|| std::fs::read_to_string("/etc/hostname").ok())
+        .map(|name| name.trim().to_string())
+        .filter(|name| !name.is_empty())
+}
+
+/// Turns a name into a segment of a dotted metric name.
+///
+/// # Arguments
+/// * `name` - The name of the rig, e.g. `"rack 2.gpu-01"`.
+///
+/// # Returns
+/// The name with every character but letters, digits, `-` and `_` replaced by `_`, e.g. `"rack_2_gpu-01"`.
+pub fn to_metric_segment(name: &str) -> String {
+    name.chars().map(|char| if char.is_ascii_alphanumeric() || char == '-' || char == '_' { char } else { '_' }).collect()
+}
+
+#[test]
+/// Tests that names keep their safe characters in metric names.
+fn test_metric_segment() {
+    assert_eq!(to_metric_segment("rig-01_a"), "rig-01_a");
+    assert_eq!(to_metric_segment("rack 2.gpu/01"), "rack_2_gpu_01");
+}
@@ -0,0 +1,140 @@
+//! Random numbers for nonces and packet fillers.
+//!
+//! RDRAND is a slow microcoded instruction, so by default it only seeds a ChaCha12 generator
+//! per thread, which then produces the numbers the miner needs in bulk.
+
+use std::cell::RefCell;
+use std::fmt;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::{Duration, Instant};
+use rand_chacha::rand_core::{RngCore, SeedableRng};
+use rand_chacha::ChaCha12Rng;
+use crate::arch;
+
+/// Where random numbers come from
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum RandomSource {
+    /// A ChaCha12 generator per thread, seeded from the hardware or OS generator
+    #[default]
+    ChaCha,
+    /// Every number from RDRAND, or from the OS generator without it
+    Hardware,
+}
+
+impl RandomSource {
+    /// Parses the source from its name
+    ///
+    /// # Arguments
+    /// * `name` - `chacha` or `hardware`
+    ///
+    /// # Returns
+    /// The source, or why the name is unknown.
+    pub fn parse(name: &str) -> Result<Self, String> {
+        match name.trim().to_ascii_lowercase().as_str() {
+            "chacha" => Ok(RandomSource::ChaCha),
+            "hardware" => Ok(RandomSource::Hardware),
+            other => Err(format!("unknown random source {other:?}, expected chacha or hardware")),
+        }
+    }
+
+    /// Gets the name of the source
+    pub fn name(&self) -> &'static str {
+        match self {
+            RandomSource::ChaCha => "chacha",
+            RandomSource::Hardware => "hardware",
+        }
+    }
+
+    /// Generates a random 64-bit number from the source
+    ///
+    /// # Returns
+    /// A 64-bit random number
+    pub fn next_u64(&self) -> u64 {
+        match self {
+            RandomSource::ChaCha => CHACHA.with(|chacha| chacha.borrow_mut().next_u64()),
+            RandomSource::Hardware => arch::random_u64(),
+        }
+    }
+}
+
+impl fmt::Display for RandomSource {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+/// Set when the numbers come from the hardware generator directly, ChaCha12 is the default.
+static USE_HARDWARE: AtomicBool = AtomicBool::new(false);
+
+thread_local! {
+    /// Generator of the thread, seeded on first use.
+    static CHACHA: RefCell<ChaCha12Rng> = RefCell::new(ChaCha12Rng::from_seed(hardware_seed()));
+}
+
+/// Draws a ChaCha12 seed from the hardware or OS generator.
+fn hardware_seed() -> [u8; 32] {
+    let mut seed = [0u8; 32];
+    seed.chunks_exact_mut(8).for_each(|chunk| chunk.copy_from_slice(&arch::random_u64().to_ne_bytes()));
+    seed
+}
+
+/// Selects where the random numbers of every thread come from.
+///
+/// # Arguments
+/// * `source` - The source
+pub fn set_random_source(source: RandomSource) {
+    USE_HARDWARE.store(source == RandomSource::Hardware, Ordering::Relaxed);
+}
+
+/// Gets where the random numbers come from.
+pub fn random_source() -> RandomSource {
+    if USE_HARDWARE.load(Ordering::Relaxed) { RandomSource::Hardware } else { RandomSource::ChaCha }
+}
+
+/// Generates a random 64-bit number from the selected source.
+///
+/// # Returns
+/// A 64-bit random number
+pub fn random_u64() -> u64 {
+    random_source().next_u64()
+}
+
+/// Measures how many numbers a source generates on the calling thread.
+///
+/// # Arguments
+/// * `source` - The source
+/// * `duration` - How long to generate numbers
+///
+/// # Returns
+/// The number of 64-bit numbers generated per second
+pub fn measure(source: RandomSource, duration: Duration) -> f64 {
+    let start = Instant::now();
+    let mut count = 0u64;
+    let mut sink = 0u64;
+    while start.elapsed() < duration {
+        for _ in 0..1024 {
+            sink ^= source.next_u64();
+        }
+        count += 1024;
+    }
+    std::hint::black_box(sink);
+    count as f64 / start.elapsed().as_secs_f64()
+}
+
+#[test]
+/// Tests the parsing of the sources and that both produce distinct values.
+fn test_random_source() {
+    assert_eq!(RandomSource::parse(" ChaCha "), Ok(RandomSource::ChaCha));
+    assert_eq!(RandomSource::parse("hardware"), Ok(RandomSource::Hardware));
+    assert!(RandomSource::parse("rdseed").unwrap_err().contains("expected chacha or hardware"));
+    assert_eq!(random_source(), RandomSource::default());
+
+    for source in [RandomSource::ChaCha, RandomSource::Hardware] {
+        let values = (0..256).map(|_| source.next_u64()).collect::<std::collections::HashSet<_>>();
+        assert_eq!(values.len(), 256, "{source}");
+    }
+
+    // Each thread has its own stream
+    let other = std::thread::spawn(|| RandomSource::ChaCha.next_u64()).join().unwrap();
+    assert_ne!(other, RandomSource::ChaCha.next_u64());
+}
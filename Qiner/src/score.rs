@@ -0,0 +1,305 @@
+//! The scoring of a nonce, shared by the miner and the verification of nonces.
+//!
+//! Only needs the math of the crate and `alloc`, so it builds without the `miner` and `std`
+//! features, e.g. for wasm32 or embedded verifiers.
+
+use alloc::boxed::Box;
+use alloc::string::String;
+use alloc::vec;
+use lib::params::AlgorithmParams;
+use lib::types::{MiningItemData, NeuronLink, Nonce64, PublicKey64, Seed64};
+use crate::arch::{self, CodePath};
+use crate::math::{KeyState, KECCAK_LANES};
+use crate::neurons::{ActiveNeuronValues, NeuronValueStore};
+
+/// Number of neuron pairs ahead of the current one whose values are prefetched
+#[cfg(feature = "prefetch")]
+const PREFETCH_DISTANCE: usize = 16;
+
+/// Structure holding neuron links and values
+#[derive(Debug, Clone)]
+pub struct NeuronData {
+    neuron_links: Box<[u64]>,
+    neuron_values: ActiveNeuronValues,
+}
+
+impl NeuronData {
+    /// Creates a new instance of NeuronData, with zeroed links and all neurons set
+    ///
+    /// `NeuronData::default()` is the same for the current parameters. The neuron values are reset
+    /// before every nonce attempt anyway, so a NeuronData can be reused for any number of attempts.
+    ///
+    /// # Arguments
+    /// * `params` - The parameters of the algorithm, giving the number of neurons
+    pub fn new(params: &AlgorithmParams) -> Self {
+        NeuronData {
+            neuron_links: vec![0; params.number_of_neurons_64() * 2].into_boxed_slice(),
+            neuron_values: ActiveNeuronValues::new(params.number_of_neurons),
+        }
+    }
+}
+
+impl Default for NeuronData {
+    fn default() -> Self {
+        NeuronData::new(&AlgorithmParams::CURRENT)
+    }
+}
+
+
+/// The mining data and the public key nonces are scored against
+#[derive(Debug)]
+pub struct Scorer {
+    params: AlgorithmParams,
+    /// `params.mining_data_length` words
+    mining_data: Box<[MiningItemData]>,
+    /// Keccak state of the public key, the neuron links of every nonce start from it
+    key_state: KeyState,
+}
+
+impl Scorer {
+    /// Creates the scorer of a public key
+    ///
+    /// # Arguments
+    /// * `public_key` - A PublicKey64 used for generating neuron links
+    /// * `params` - The parameters of the algorithm
+    /// * `mining_data` - The mining data scores are computed against, of `params.mining_data_length` words
+    ///
+    /// # Returns
+    /// A new instance of the Scorer struct
+    ///
+    /// # Panics
+    /// If the parameters are invalid or do not match the length of the mining data.
+    pub fn new(public_key: PublicKey64, params: AlgorithmParams, mining_data: Box<[MiningItemData]>) -> Self {
+        if let Err(err) = params.validate() {
+            panic!("invalid algorithm parameters: {err}");
+        }
+        assert_eq!(mining_data.len(), params.mining_data_length, "the mining data does not match the algorithm parameters");
+
+        Scorer {
+            params,
+            mining_data,
+            key_state: KeyState::new(&public_key),
+        }
+    }
+
+    /// Generate the mining data of a random seed
+    ///
+    /// # Arguments
+    /// * `random_seed` - The random seed of the network
+    /// * `params` - The parameters of the algorithm, giving the length of the mining data
+    ///
+    /// # Returns
+    /// The mining data, `params.mining_data_length` words
+    pub fn generate_mining_data(random_seed: &Seed64, params: &AlgorithmParams) -> Box<[MiningItemData]> {
+        let mut mining_data = vec![0; params.mining_data_length].into_boxed_slice();
+        crate::math::random_64_keyed(&KeyState::new(random_seed), random_seed, &mut mining_data, u64::MAX);
+        mining_data
+    }
+
+    /// Get the parameters of the algorithm
+    pub fn get_params(&self) -> &AlgorithmParams {
+        &self.params
+    }
+
+    /// Generate the neuron links of a nonce
+    ///
+    /// # Arguments
+    /// * `nonce` - The nonce to generate the links of
+    /// * `neuron_data` - A mutable reference to NeuronData receiving the links
+    pub fn expand_links(&self, nonce: &Nonce64, neuron_data: &mut NeuronData) {
+        // Generate neuron links based on public key and nonce, masked to fit neuron mod bits
+        crate::math::random_64_keyed(&self.key_state, nonce, &mut neuron_data.neuron_links, self.params.neuron_mod_bits());
+    }
+
+    /// Generate the neuron links of four nonces in one pass
+    ///
+    /// # Arguments
+    /// * `nonces` - The nonces to generate the links of
+    /// * `neuron_data` - The NeuronData receiving the links, one per nonce
+    pub fn expand_links_x4(&self, nonces: &[Nonce64; KECCAK_LANES], neuron_data: [&mut NeuronData; KECCAK_LANES]) {
+        let [data0, data1, data2, data3] = neuron_data;
+        crate::math::random_64_x4_keyed(
+            &self.key_state,
+            nonces,
+            [&mut data0.neuron_links, &mut data1.neuron_links, &mut data2.neuron_links, &mut data3.neuron_links],
+            self.params.neuron_mod_bits(),
+        );
+    }
+
+    /// Compute the score of the nonce whose links were generated in the neuron data
+    ///
+    /// # Arguments
+    /// * `neuron_data` - A mutable reference to NeuronData holding the links of the nonce
+    ///
+    /// # Returns
+    /// The score of the nonce
+    pub fn score(&self, neuron_data: &mut NeuronData) -> usize {
+        self.score_with(arch::code_path(), neuron_data)
+    }
+
+    /// Compute the score of the nonce whose links were generated in the neuron data, with the given code path
+    ///
+    /// # Arguments
+    /// * `code_path` - The code path to run, which the CPU must support
+    /// * `neuron_data` - A mutable reference to NeuronData holding the links of the nonce
+    ///
+    /// # Returns
+    /// The score of the nonce
+    ///
+    /// # Panics
+    /// If the CPU does not support the code path.
+    pub fn score_with(&self, code_path: CodePath, neuron_data: &mut NeuronData) -> usize {
+        assert!(code_path.is_supported(), "the CPU does not support the {} code path", code_path.name());
+        match code_path {
+            CodePath::Baseline => self.score_kernel(neuron_data),
+            // Safety: support of the code path was just checked
+            #[cfg(target_arch = "x86_64")]
+            CodePath::X86_64V3 => unsafe { self.score_x86_64_v3(neuron_data) },
+            // Without any detected feature, the native kernel is a plain function
+            #[cfg(qiner_native)]
+            #[allow(unused_unsafe)]
+            CodePath::Native => unsafe { self.score_native(neuron_data) },
+        }
+    }
+
+    /// `score_kernel` compiled for the x86-64-v3 level.
+    #[cfg(target_arch = "x86_64")]
+    #[target_feature(enable = "avx2,bmi1,bmi2,fma,lzcnt,movbe")]
+    fn score_x86_64_v3(&self, neuron_data: &mut NeuronData) -> usize {
+        self.score_kernel(neuron_data)
+    }
+
+    /// `score_kernel` compiled for the CPU features of the build machine.
+    #[cfg(qiner_native)]
+    #[cfg_attr(qiner_native_feature = "sse4.1", target_feature(enable = "sse4.1"))]
+    #[cfg_attr(qiner_native_feature = "sse4.2", target_feature(enable = "sse4.2"))]
+    #[cfg_attr(qiner_native_feature = "popcnt", target_feature(enable = "popcnt"))]
+    #[cfg_attr(qiner_native_feature = "avx", target_feature(enable = "avx"))]
+    #[cfg_attr(qiner_native_feature = "avx2", target_feature(enable = "avx2"))]
+    #[cfg_attr(qiner_native_feature = "bmi1", target_feature(enable = "bmi1"))]
+    #[cfg_attr(qiner_native_feature = "bmi2", target_feature(enable = "bmi2"))]
+    #[cfg_attr(qiner_native_feature = "fma", target_feature(enable = "fma"))]
+    #[cfg_attr(qiner_native_feature = "lzcnt", target_feature(enable = "lzcnt"))]
+    #[cfg_attr(qiner_native_feature = "avx512f", target_feature(enable = "avx512f"))]
+    fn score_native(&self, neuron_data: &mut NeuronData) -> usize {
+        self.score_kernel(neuron_data)
+    }
+
+    /// The score computation, inlined into the function of every code path so it is compiled for its CPU features
+    #[inline(always)]
+    fn score_kernel(&self, neuron_data: &mut NeuronData) -> usize {
+        // Every attempt starts from all neurons set, never from the state left by the previous nonce
+        neuron_data.neuron_values.reset();
+
+        let number_of_neurons = self.params.number_of_neurons;
+        let number_of_neurons_64 = self.params.number_of_neurons_64();
+        let max_score = self.params.max_score();
+
+        // Mining logic with neuron values and mining data
+        let mut remaining_iterations = self.params.mining_data_length;
+        let mut score: usize = 0;
+
+        loop {
+            let prev_value0 = neuron_data.neuron_values.get(number_of_neurons - 1);
+            let prev_value1 = neuron_data.neuron_values.get(number_of_neurons - 2);
+
+            // Exact pairs let the compiler drop the bounds checks of the links
+            let neuron_values = &mut neuron_data.neuron_values;
+            assert!(neuron_values.number_of_neurons() >= number_of_neurons, "the neuron data does not match the algorithm parameters");
+            let neuron_links = &neuron_data.neuron_links[..number_of_neurons_64 * 2];
+            for (idx, links) in neuron_links.chunks_exact(2).enumerate() {
+                // Links are stored as (left, right) pairs, so the values gathered a few pairs ahead are known
+                #[cfg(feature = "prefetch")]
+                if idx + PREFETCH_DISTANCE < number_of_neurons_64 {
+                    let ahead = (idx + PREFETCH_DISTANCE) * 2;
+                    for link in [neuron_links[ahead], neuron_links[ahead + 1]] {
+                        neuron_values.prefetch((link as NeuronLink) as usize);
+                        neuron_values.prefetch(((link >> NeuronLink::BITS) as NeuronLink) as usize);
+                    }
+                }
+
+                let left_idx = idx * 2;
+                let right_idx = idx * 2 + 1;
+
+                let left_neuron0 = (links[0] as NeuronLink) as usize;
+                let right_neuron0 = ((links[0] >> NeuronLink::BITS) as NeuronLink) as usize;
+
+                let left_neuron1 = (links[1] as NeuronLink) as usize;
+                let right_neuron1 = ((links[1] >> NeuronLink::BITS) as NeuronLink) as usize;
+
+                let and_result0 = neuron_values.get(left_neuron0) & neuron_values.get(right_neuron0);
+                let and_result1 = neuron_values.get(left_neuron1) & neuron_values.get(right_neuron1);
+                // Safety: both indices are below the number of neurons, checked against the store above
+                unsafe {
+                    neuron_values.set_unchecked(left_idx, !(and_result0));
+                    neuron_values.set_unchecked(right_idx, !(and_result1));
+                }
+            }
+
+            let current_value0 = neuron_data.neuron_values.get(number_of_neurons - 1);
+            let current_value1 = neuron_data.neuron_values.get(number_of_neurons - 2);
+
+            // Every bit of the mining data is matched, there is nothing left to score
+            if score == max_score {
+                break;
+            }
+
+            let mining_data_chunk = self.mining_data[score >> 6];
+            let bit_is_set = ((mining_data_chunk >> (score & 63) as MiningItemData) & 1) as u8;
+            if current_value0 != prev_value0 && current_value1 == prev_value1 {
+                if bit_is_set == 0 {
+                    break;
+                }
+                score += 1;
+            } else if current_value1 != prev_value1 && current_value0 == prev_value0 {
+                if bit_is_set == 1 {
+                    break;
+                }
+                score += 1;
+            } else {
+                remaining_iterations -= 1;
+                if remaining_iterations == 0 {
+                    break;
+                }
+            }
+        }
+
+        score
+    }
+}
+
+/// Computes the score of a nonce, from scratch
+///
+/// Generates the mining data and allocates the neuron data on every call: the miner keeps a
+/// `Scorer` instead, this is for verifying a few nonces.
+///
+/// # Arguments
+/// * `public_key` - The public key the nonce was found for
+/// * `nonce` - The nonce to score
+/// * `random_seed` - The random seed of the network
+/// * `params` - The parameters of the algorithm
+///
+/// # Returns
+/// The score of the nonce, or why the parameters are invalid
+pub fn score_nonce(public_key: &PublicKey64, nonce: &Nonce64, random_seed: &Seed64, params: &AlgorithmParams) -> Result<usize, String> {
+    params.validate()?;
+
+    let scorer = Scorer::new(*public_key, *params, Scorer::generate_mining_data(random_seed, params));
+    let mut neuron_data = NeuronData::new(params);
+    scorer.expand_links(nonce, &mut neuron_data);
+    Ok(scorer.score(&mut neuron_data))
+}
+
+#[test]
+/// Tests that a nonce scores the same from scratch as with a reused scorer, and the rejection of invalid parameters.
+fn test_score_nonce() {
+    let params = AlgorithmParams { number_of_neurons: 4096, mining_data_length: 4 };
+    let scorer = Scorer::new([1, 2, 3, 4], params, Scorer::generate_mining_data(&[5; 4], &params));
+    let mut neuron_data = NeuronData::new(&params);
+
+    for nonce in [[0; 4], [6, 7, 8, 9], [u64::MAX; 4]] {
+        scorer.expand_links(&nonce, &mut neuron_data);
+        assert_eq!(score_nonce(&[1, 2, 3, 4], &nonce, &[5; 4], &params), Ok(scorer.score(&mut neuron_data)));
+    }
+    assert!(score_nonce(&[0; 4], &[0; 4], &[0; 4], &AlgorithmParams { number_of_neurons: 4000, ..params }).is_err());
+}
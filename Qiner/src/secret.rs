@@ -0,0 +1,137 @@
+//! Key material kept out of logs and memory dumps.
+//!
+//! Farm machines are often shared, so a seed must not outlive its use: secrets are wiped when
+//! dropped, redacted from `Debug`, and their pages are locked so they are never swapped to disk.
+
+use std::fmt;
+use zeroize::Zeroize;
+
+/// A value wiped from memory when dropped and never printed by `Debug`
+///
+/// The value is boxed so moving the secret does not leave copies on the stack, and its pages
+/// are locked in RAM on a best-effort basis.
+pub struct Secret<T: Zeroize> {
+    value: Box<T>,
+    locked: bool,
+}
+
+impl<T: Zeroize> Secret<T> {
+    /// Moves a value into a secret
+    ///
+    /// # Arguments
+    /// * `value` - The value, best built in place with `expose_mut` as the argument is a copy
+    ///
+    /// # Returns
+    /// The secret, locked in RAM if the OS allows it
+    pub fn new(value: T) -> Self {
+        let value = Box::new(value);
+        let locked = lock_memory(value.as_ref() as *const T as *const u8, size_of::<T>());
+        Secret { value, locked }
+    }
+
+    /// Gets the value, which should not be copied out of the secret
+    pub fn expose(&self) -> &T {
+        &self.value
+    }
+
+    /// Gets the value to fill it in place
+    pub fn expose_mut(&mut self) -> &mut T {
+        &mut self.value
+    }
+
+    /// Checks whether the pages of the value are locked in RAM
+    ///
+    /// # Returns
+    /// `false` if locking failed, usually because of `RLIMIT_MEMLOCK`, or is not supported on this OS
+    pub fn is_locked(&self) -> bool {
+        self.locked
+    }
+}
+
+impl<T: Zeroize + Clone> Clone for Secret<T> {
+    fn clone(&self) -> Self {
+        Secret::new(self.value.as_ref().clone())
+    }
+}
+
+impl<T: Zeroize + PartialEq> PartialEq for Secret<T> {
+    fn eq(&self, other: &Self) -> bool {
+        self.value == other.value
+    }
+}
+
+impl<T: Zeroize + Eq> Eq for Secret<T> {}
+
+impl<T: Zeroize> fmt::Debug for Secret<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("Secret([REDACTED])")
+    }
+}
+
+impl<T: Zeroize> Drop for Secret<T> {
+    fn drop(&mut self) {
+        self.value.zeroize();
+        if self.locked {
+            // Locks do not stack, a page shared with another secret is unlocked with the first one
+            unlock_memory(self.value.as_ref() as *const T as *const u8, size_of::<T>());
+        }
+    }
+}
+
+/// Locks memory in RAM so it is never written to swap.
+///
+/// # Arguments
+/// * `address` - Start of the memory
+/// * `length` - Number of bytes
+///
+/// # Returns
+/// `true` if the pages are locked, `false` if locking failed or is not supported on this OS.
+fn lock_memory(address: *const u8, length: usize) -> bool {
+    if length == 0 {
+        return false;
+    }
+
+    #[cfg(unix)]
+    {
+        extern "C" {
+            fn mlock(address: *const u8, length: usize) -> i32;
+        }
+
+        unsafe { mlock(address, length) == 0 }
+    }
+    #[cfg(not(unix))]
+    {
+        let _ = address;
+        false
+    }
+}
+
+/// Unlocks memory locked with `lock_memory`.
+fn unlock_memory(address: *const u8, length: usize) {
+    #[cfg(unix)]
+    {
+        extern "C" {
+            fn munlock(address: *const u8, length: usize) -> i32;
+        }
+
+        unsafe { munlock(address, length); }
+    }
+    #[cfg(not(unix))]
+    {
+        let _ = (address, length);
+    }
+}
+
+#[test]
+/// Tests that secrets are redacted from `Debug` and still compare and clone by value.
+fn test_secret() {
+    let mut secret = Secret::new([0u8; 8]);
+    secret.expose_mut().copy_from_slice(b"password");
+    assert_eq!(format!("{secret:?}"), "Secret([REDACTED])");
+    assert_eq!(secret.expose(), b"password");
+
+    let copy = secret.clone();
+    assert_eq!(copy, secret);
+    assert_eq!(copy.is_locked(), secret.is_locked());
+    assert!(!format!("{:?}", Some(copy)).contains("password"));
+}
@@ -0,0 +1,144 @@
+//! Known answer tests of the primitives the miner relies on, run before mining so a
+//! miscompiled SIMD path cannot silently produce garbage nonces.
+
+use std::thread;
+use std::time::Instant;
+use lib::params::AlgorithmParams;
+use lib::types::{MiningData, Nonce64, PublicKey64, State64, MINING_DATA_LENGTH, STACK_SIZE};
+use crate::arch::{CodePath, compiled_code_paths};
+use crate::crypto::k12_32;
+use crate::identity::derive_identity;
+use crate::math::{random_64, random_64_keyed, random_64_masked, random_64_x4, KeyState, KECCAK_LANES};
+use crate::miner::{MinerShared, NeuronData};
+use crate::preflight::Check;
+
+/// First lanes of Keccak-f[1600] (24 rounds) applied to the zero state.
+const KECCAK_F1600_ZERO: [u64; 4] = [0xF125_8F79_40E1_DDE7, 0x84D5_CCF9_33C0_478A, 0xD598_261E_A65A_A9EE, 0xBD15_4730_6F80_494D];
+
+/// KangarooTwelve of the empty message, 32 bytes of output.
+const K12_EMPTY: [u8; 32] = [
+    0x1A, 0xC2, 0xD4, 0x50, 0xFC, 0x3B, 0x42, 0x05, 0xD1, 0x9D, 0xA7, 0xBF, 0xCA, 0x1B, 0x37, 0x51,
+    0x3C, 0x08, 0x03, 0x57, 0x7A, 0xC7, 0x16, 0x7F, 0x06, 0xFE, 0x2C, 0xE1, 0xF0, 0xEF, 0x39, 0xE5,
+];
+
+/// Identity of the seed made of 55 `a`, as produced by the Qubic wallet.
+const ALL_A_IDENTITY: &[u8; 60] = b"BZBQFLLBNCXEMGLOBHUVFTLUPLVCPQUASSILFABOFFBCADQSSUPNWLZBQEXK";
+
+/// Public key, nonce and mining data seed of the end-to-end score check.
+const SCORE_PUBLIC_KEY: PublicKey64 = [1, 2, 3, 4];
+const SCORE_NONCE: Nonce64 = [34, 6, 7, 8];
+const SCORE_SEED: PublicKey64 = [9, 10, 11, 12];
+
+/// Score of `SCORE_NONCE` on the mining data of `SCORE_SEED`.
+const SCORE_EXPECTED: usize = 5;
+
+/// Runs every known answer test.
+///
+/// # Returns
+/// One check per primitive.
+pub fn run_self_test() -> Vec<Check> {
+    let started_at = Instant::now();
+
+    // The neuron data is built on the stack before being boxed, and debug builds keep a
+    // few copies of it there, so the thread gets more room than a mining thread
+    let score_check = thread::Builder::new()
+        .stack_size(STACK_SIZE * 4)
+        .spawn(check_score)
+        .map_err(|err| err.to_string())
+        .and_then(|handle| handle.join().map_err(|_| "panicked".to_string()))
+        .unwrap_or_else(|err| Check { name: "score".to_string(), is_ok: false, detail: err });
+
+    let checks = vec![
+        check_keccak(),
+        check_keccak_x4(),
+        check_kangaroo_twelve(),
+        check_identity(),
+        score_check,
+    ];
+    log::debug!("Self-test took {:?}", started_at.elapsed());
+    checks
+}
+
+/// Checks the scalar Keccak permutation against its known answer.
+fn check_keccak() -> Check {
+    let mut state = State64::default();
+    keccak::p1600(&mut state, 24);
+    compare("keccak-p1600", &state[..4], &KECCAK_F1600_ZERO)
+}
+
+/// Checks that the four-lane Keccak (AVX2 when available) and the Keccak from a shared key state match the scalar one.
+fn check_keccak_x4() -> Check {
+    let public_key: PublicKey64 = [1, 2, 3, 4];
+    let nonces: [Nonce64; KECCAK_LANES] = [[5, 6, 7, 8], [0; 4], [u64::MAX; 4], [9, 10, 11, 12]];
+    let mask = 0x003F_FFFF_003F_FFFF;
+
+    let mut expected = [[0u64; 60]; KECCAK_LANES];
+    for (nonce, output) in nonces.iter().zip(expected.iter_mut()) {
+        random_64_masked(&public_key, nonce, output, mask);
+    }
+
+    let mut outputs = [[0u64; 60]; KECCAK_LANES];
+    let [output0, output1, output2, output3] = &mut outputs;
+    random_64_x4(&public_key, &nonces, [output0, output1, output2, output3], mask);
+
+    let key_state = KeyState::new(&public_key);
+    let mut keyed_outputs = [[0u64; 60]; KECCAK_LANES];
+    for (nonce, output) in nonces.iter().zip(keyed_outputs.iter_mut()) {
+        random_64_keyed(&key_state, nonce, output, mask);
+    }
+
+    let actual = [outputs.as_flattened(), keyed_outputs.as_flattened()].concat();
+    compare("keccak-p1600 x4", &actual, &expected.as_flattened().repeat(2))
+}
+
+/// Checks KangarooTwelve against its known answer.
+fn check_kangaroo_twelve() -> Check {
+    compare("kangarootwelve", &k12_32(&[]), &K12_EMPTY)
+}
+
+/// Checks the identity derivation, KangarooTwelve and FourQ together, against the wallet.
+fn check_identity() -> Check {
+    let id = derive_identity(&[b'a'; 55]).map(|identity| identity.id);
+    compare("identity", &id.unwrap_or([0; 60]), ALL_A_IDENTITY)
+}
+
+/// Computes the score of a fixed nonce with both Keccak paths and every supported code path, and checks it against its known answer.
+fn check_score() -> Check {
+    let mut mining_data: MiningData = [0; MINING_DATA_LENGTH];
+    random_64(&SCORE_SEED, &SCORE_SEED, &mut mining_data);
+    let shared = MinerShared::new(SCORE_PUBLIC_KEY, AlgorithmParams::CURRENT, Box::new(mining_data), usize::MAX, None);
+
+    let mut lanes = (0..KECCAK_LANES).map(|_| Box::new(NeuronData::default())).collect::<Vec<_>>();
+    shared.expand_links(&SCORE_NONCE, &mut lanes[0]);
+    let score = shared.score(&mut lanes[0]);
+
+    let [data0, data1, data2, data3] = lanes.as_mut_slice() else {
+        unreachable!("one neuron data per lane");
+    };
+    shared.expand_links_x4(&[SCORE_NONCE; KECCAK_LANES], [data0, data1, data2, data3]);
+    let score_x4 = shared.score(&mut lanes[KECCAK_LANES - 1]);
+
+    let mut scores = vec![score, score_x4];
+    for code_path in compiled_code_paths().into_iter().filter(CodePath::is_supported) {
+        scores.push(shared.score_with(code_path, &mut lanes[0]));
+    }
+    compare("score", &scores, &vec![SCORE_EXPECTED; scores.len()])
+}
+
+/// Builds the check of computed values against their expected values.
+fn compare<T: PartialEq + std::fmt::Debug>(name: &str, actual: &[T], expected: &[T]) -> Check {
+    let mismatch = actual.iter().zip(expected).position(|(actual, expected)| actual != expected);
+    match mismatch {
+        None if actual.len() == expected.len() => Check { name: name.to_string(), is_ok: true, detail: "passed".to_string() },
+        None => Check { name: name.to_string(), is_ok: false, detail: format!("expected {} values, got {}", expected.len(), actual.len()) },
+        Some(idx) => Check { name: name.to_string(), is_ok: false, detail: format!("value {idx}: expected {:?}, got {:?}", expected[idx], actual[idx]) },
+    }
+}
+
+#[test]
+/// Tests that every primitive passes its known answer test.
+fn test_self_test() {
+    for check in run_self_test() {
+        assert!(check.is_ok, "{}: {}", check.name, check.detail);
+    }
+}
@@ -0,0 +1,103 @@
+/// Reads the temperatures reported by the thermal zones of the machine.
+///
+/// # Returns
+/// The temperatures in degrees Celsius, one per thermal zone, empty if none is available.
+#[cfg(target_os = "linux")]
+pub fn read_temperatures() -> Vec<f64> {
+    let Ok(entries) = std::fs::read_dir("/sys/class/thermal") else {
+        return Vec::new();
+    };
+
+    let mut zones = entries
+        .filter_map(|entry| entry.ok())
+        .filter(|entry| entry.file_name().to_string_lossy().starts_with("thermal_zone"))
+        .map(|entry| entry.path())
+        .collect::<Vec<_>>();
+    zones.sort();
+
+    zones.iter()
+        .filter_map(|zone| std::fs::read_to_string(zone.join("temp")).ok())
+        .filter_map(|content| parse_millidegrees(&content))
+        .collect()
+}
+
+/// Reads the temperatures reported by the thermal zones of the machine.
+///
+/// # Returns
+/// Always empty, temperatures are not read on this platform.
+#[cfg(not(target_os = "linux"))]
+pub fn read_temperatures() -> Vec<f64> {
+    Vec::new()
+}
+
+/// Names of the hwmon drivers reporting the temperature of the CPU package.
+#[cfg(target_os = "linux")]
+const CPU_HWMON_NAMES: [&str; 4] = ["coretemp", "k10temp", "zenpower", "cpu_thermal"];
+
+/// Reads the temperature of the CPU package, the hottest sensor of the CPU.
+///
+/// The hwmon sensors of the CPU drivers are read first, then the thermal zones.
+///
+/// # Returns
+/// The temperature in degrees Celsius, or `None` if no sensor is available.
+#[cfg(target_os = "linux")]
+pub fn read_package_temperature() -> Option<f64> {
+    let hwmons = std::fs::read_dir("/sys/class/hwmon").map(|entries| entries.filter_map(|entry| entry.ok()).map(|entry| entry.path()).collect::<Vec<_>>()).unwrap_or_default();
+    let cpu_temperatures = hwmons.iter()
+        .filter(|hwmon| std::fs::read_to_string(hwmon.join("name")).is_ok_and(|name| CPU_HWMON_NAMES.contains(&name.trim())))
+        .filter_map(|hwmon| std::fs::read_dir(hwmon).ok())
+        .flat_map(|entries| entries.filter_map(|entry| entry.ok()).map(|entry| entry.path()))
+        .filter(|path| path.file_name().is_some_and(|name| name.to_string_lossy().starts_with("temp") && name.to_string_lossy().ends_with("_input")))
+        .filter_map(|path| std::fs::read_to_string(path).ok())
+        .filter_map(|content| parse_millidegrees(&content))
+        .collect::<Vec<_>>();
+
+    let temperatures = if cpu_temperatures.is_empty() { read_temperatures() } else { cpu_temperatures };
+    temperatures.into_iter().reduce(f64::max)
+}
+
+/// Reads the temperature of the CPU package, the hottest ACPI thermal zone.
+///
+/// WMI is queried through PowerShell, which needs administrator rights on most machines.
+///
+/// # Returns
+/// The temperature in degrees Celsius, or `None` if no sensor is available.
+#[cfg(windows)]
+pub fn read_package_temperature() -> Option<f64> {
+    let output = std::process::Command::new("powershell")
+        .args(["-NoProfile", "-NonInteractive", "-Command", "(Get-CimInstance -Namespace root/wmi -ClassName MSAcpi_ThermalZoneTemperature).CurrentTemperature"])
+        .output()
+        .ok()?;
+    String::from_utf8_lossy(&output.stdout).lines().filter_map(parse_decikelvin).reduce(f64::max)
+}
+
+/// Reads the temperature of the CPU package.
+///
+/// # Returns
+/// Always `None`, temperatures are not read on this platform.
+#[cfg(not(any(target_os = "linux", windows)))]
+pub fn read_package_temperature() -> Option<f64> {
+    None
+}
+
+/// Parses a temperature in tenths of a kelvin, the unit of the ACPI thermal zones of WMI.
+///
+/// # Arguments
+/// * `content` - A line of the output of the WMI query, e.g. `"3132"`.
+///
+/// # Returns
+/// The temperature in degrees Celsius, or `None` if invalid.
+pub fn parse_decikelvin(content: &str) -> Option<f64> {
+    content.trim().parse::<u32>().ok().filter(|decikelvin| *decikelvin > 0).map(|decikelvin| decikelvin as f64 / 10.0 - 273.15)
+}
+
+/// Parses a temperature in thousandths of a degree, the unit of the Linux thermal zones.
+///
+/// # Arguments
+/// * `content` - The content of a `temp` file, e.g. `"45000\n"`.
+///
+/// # Returns
+/// The temperature in degrees Celsius, or `None` if invalid.
+pub fn parse_millidegrees(content: &str) -> Option<f64> {
+    content.trim().parse::<i64>().ok().map(|millidegrees| millidegrees as f64 / 1000.0)
+}
@@ -0,0 +1,652 @@
+//! The settings of Qiner, read from the environment, which the configuration file and the CLI
+//! fill, and parsed and validated once.
+
+use std::env;
+use std::fmt;
+use std::path::PathBuf;
+use std::str::FromStr;
+use std::time::Duration;
+use lib::env_names::{
+    ENV_ALGORITHM, ENV_BALANCE_CHECK_MINUTES, ENV_BANDWIDTH_MAX_BYTES_PER_HOUR, ENV_CLOCK_SKEW_MAX_SECONDS, ENV_CORE_POLICY, ENV_HEALTH_CONNECT_MINUTES,
+    ENV_HEALTH_PORT, ENV_HEALTH_STALL_TIMEOUT, ENV_ID, ENV_INSTANCE_LOCK, ENV_KECCAK_LANES, ENV_KEYSTORE_FILE, ENV_MINING_DATA_LENGTH, ENV_NUMBER_OF_NEURONS,
+    ENV_NUMBER_OF_THREADS, ENV_PACKET_CAPTURE, ENV_PANIC_EXIT, ENV_PEER_ALLOWLIST, ENV_PEER_DENYLIST, ENV_PEER_PROTOCOLS, ENV_POOL_FAILOVER_MINUTES,
+    ENV_POOL_SERVER, ENV_PRIORITY, ENV_RANDOM_SEED, ENV_RANDOM_SOURCE, ENV_SCHEDULER, ENV_SERVER_IP, ENV_SERVER_PORT, ENV_SHARE_SERVER, ENV_SHARE_THRESHOLD,
+    ENV_SOLUTION_MAX_AGE, ENV_SOLUTION_MAX_IN_MEMORY, ENV_SOLUTION_THRESHOLD, ENV_SPILL_FILE, ENV_STALL_TIMEOUT, ENV_STATE_DIR,
+    ENV_SUBMIT_CONNECTIONS_PER_NODE, ENV_SUBMIT_MAX_PACKETS_PER_CONNECTION, ENV_SUBMIT_MAX_PACKETS_PER_SECOND, ENV_SUBMIT_MIN_CONNECT_INTERVAL_MS,
+    ENV_SUMMARY_FILE, ENV_VERSION,
+};
+use lib::params::AlgorithmParams;
+use lib::random_seed::parse_random_seed;
+use lib::types::network::Protocol;
+use lib::types::{Seed, Version};
+use lib::version::{DEFAULT_VERSION, parse_version};
+use crate::algorithm::AlgorithmEntry;
+use crate::clock::DEFAULT_MAX_CLOCK_SKEW;
+use crate::cores::{CorePolicy, ThreadPlacement, detect_topology};
+use crate::cpu_quota::get_available_cpus;
+use crate::health::{DEFAULT_HEALTH_CONNECT_TIMEOUT, DEFAULT_HEALTH_STALL_TIMEOUT};
+use crate::journal::DEFAULT_SPILL_FILE;
+use crate::keys::DEFAULT_KEYSTORE_FILE;
+use crate::math::KECCAK_LANES;
+use crate::miner::Scheduler;
+use crate::peer_filter::{PeerFilter, parse_networks};
+use crate::priority::Priority;
+use crate::rng::RandomSource;
+use crate::submission::{DEFAULT_MIN_CONNECT_INTERVAL, DEFAULT_SOLUTION_MAX_AGE, DEFAULT_SOLUTION_MAX_IN_MEMORY, SubmissionPacing};
+use crate::supervisor::DEFAULT_STALL_TIMEOUT;
+use crate::targets::DEFAULT_POOL_FAILOVER;
+
+/// Number of letters of an ID.
+const ID_LENGTH: usize = 60;
+
+/// The mining settings, parsed and validated once.
+#[derive(Debug, Clone)]
+pub struct Settings {
+    /// The 60 uppercase letters of the ID; its checksum is not verified here
+    pub id: String,
+    /// The nodes, whose `SERVER_IP` and `SERVER_PORT` are set
+    pub nodes: NodeSettings,
+    pub random_seed: Seed,
+    pub solution_threshold: usize,
+    /// The algorithm scoring the nonces, the one of `version` unless `ALGORITHM` is set
+    pub algorithm: &'static AlgorithmEntry,
+    /// The parameters of the scoring algorithm, those of `version` unless overridden
+    pub algorithm_params: AlgorithmParams,
+    /// Whether `algorithm_params` was changed by `NUMBER_OF_NEURONS` or `MINING_DATA_LENGTH`
+    pub are_algorithm_params_overridden: bool,
+    pub threads: ThreadSettings,
+    pub submission: SubmissionSettings,
+    pub process: ProcessSettings,
+}
+
+/// The settings of the nodes, which the subcommands that do not mine need as well.
+#[derive(Debug, Clone, Default)]
+pub struct NodeSettings {
+    /// The comma-separated IPs of the nodes, empty if not set
+    pub server_ip: String,
+    pub server_port: Option<u16>,
+    pub version: Version,
+    /// Whether `version` comes from `VERSION` rather than `DEFAULT_VERSION`
+    pub is_version_overridden: bool,
+    /// The protocol versions of the peers that do not run the one of `version`
+    pub peer_protocols: PeerProtocols,
+    /// The pool solutions are submitted to in priority, as `ip:port`
+    pub pool_server: Option<String>,
+    /// How long the pool must be unreachable before falling back to the nodes
+    pub pool_failover: Duration,
+    /// The allowlist and denylist of the nodes
+    pub peer_filter: PeerFilter,
+    /// The skew of the local clock beyond which it is reported, `None` to never report it
+    pub max_clock_skew: Option<Duration>,
+    /// The interval between two checks of the balance of the ID, `None` to never check it
+    pub balance_check_interval: Option<Duration>,
+}
+
+/// The settings of the mining threads.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ThreadSettings {
+    /// The number of mining threads, `None` to use every available CPU
+    pub number_of_threads: Option<usize>,
+    /// Which cores the threads run on
+    pub core_policy: CorePolicy,
+    /// Number of nonces each thread generates the neuron links of in one pass, 1 or `KECCAK_LANES`
+    pub keccak_lanes: usize,
+    /// How nonce attempts are scheduled on the threads
+    pub scheduler: Scheduler,
+    /// Where the random numbers of the nonces and packets come from
+    pub random_source: RandomSource,
+}
+
+/// The settings of the submission of the solutions and the shares.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SubmissionSettings {
+    /// The maximum age of a pending solution
+    pub solution_max_age: Duration,
+    /// The number of solutions kept in memory while waiting, `None` for no cap
+    pub solution_max_in_memory: Option<usize>,
+    pub pacing: SubmissionPacing,
+    /// The cap on the bytes sent per hour, `None` for no cap
+    pub max_bytes_per_hour: Option<u64>,
+    /// The score a nonce must reach to be reported as a share, `None` to disable shares
+    pub share_threshold: Option<usize>,
+    /// The address shares are sent to, as `ip:port`
+    pub share_server: Option<String>,
+}
+
+/// The settings of the process: its files, its priority and how it is supervised.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ProcessSettings {
+    pub priority: Priority,
+    /// The directory state kept across restarts is stored in
+    pub state_dir: PathBuf,
+    pub keystore_file: PathBuf,
+    /// The file unsent solutions are spilled to
+    pub spill_file: PathBuf,
+    /// The file the session summary is written to
+    pub summary_file: Option<PathBuf>,
+    /// The file the messages exchanged with the peers are captured to
+    pub packet_capture: Option<PathBuf>,
+    /// Whether the process exits after a panic
+    pub panic_exit: bool,
+    /// Whether a second instance mining for the same ID is refused
+    pub instance_lock: bool,
+    /// The time without progress after which a worker is restarted, `None` to only restart exited workers
+    pub stall_timeout: Option<Duration>,
+    /// The port of the health endpoint
+    pub health_port: Option<u16>,
+    /// The time without any iteration after which the miner is unhealthy
+    pub health_stall_timeout: Duration,
+    /// The time connect attempts may keep failing before the miner is unhealthy
+    pub health_connect_timeout: Duration,
+}
+
+/// Protocol versions of the peers running patched nodes, which expect another protocol than `VERSION`.
+///
+/// Given as `PEER_PROTOCOLS=1.2.3.4=141, 5.6.7.8:21842=140`: a peer given by its host alone
+/// matches it on any port, and a peer given with its port takes precedence over its host.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct PeerProtocols {
+    overrides: Vec<(String, Protocol)>,
+}
+
+impl PeerProtocols {
+    /// Parses a comma-separated list of `peer=protocol`.
+    ///
+    /// # Arguments
+    /// * `value` - The list to parse, empty for none.
+    ///
+    /// # Returns
+    /// The protocols of the peers, or why an entry is invalid.
+    ///
+    /// # Examples
+    /// ```
+    /// use qiner::settings::PeerProtocols;
+    ///
+    /// let protocols = PeerProtocols::parse("1.2.3.4=141, 1.2.3.4:21842=140").unwrap();
+    /// assert_eq!(protocols.get("1.2.3.4:21841"), Some(141));
+    /// assert_eq!(protocols.get("1.2.3.4:21842"), Some(140));
+    /// assert_eq!(protocols.get("5.6.7.8:21841"), None);
+    /// assert!(PeerProtocols::parse("1.2.3.4=300").is_err());
+    /// ```
+    pub fn parse(value: &str) -> Result<Self, String> {
+        let overrides = value.split(',')
+            .map(str::trim)
+            .filter(|entry| !entry.is_empty())
+            .map(|entry| {
+                let (peer, protocol) = entry.rsplit_once('=').ok_or_else(|| format!("expected peer=protocol, got {entry:?}"))?;
+                let protocol = protocol.trim().parse::<Protocol>().map_err(|_| format!("invalid protocol version in {entry:?}"))?;
+                let peer = peer.trim();
+                if peer.is_empty() {
+                    return Err(format!("no peer in {entry:?}"));
+                }
+                Ok((peer.to_string(), protocol))
+            })
+            .collect::<Result<Vec<_>, String>>()?;
+        Ok(PeerProtocols { overrides })
+    }
+
+    /// Gets the protocol version of a peer.
+    ///
+    /// # Arguments
+    /// * `addr` - The address of the peer, as `host:port`, with or without a scheme such as `quic://`.
+    ///
+    /// # Returns
+    /// The protocol version of the peer, or `None` if it runs the one of `VERSION`.
+    pub fn get(&self, addr: &str) -> Option<Protocol> {
+        let addr = addr.rsplit_once("://").map_or(addr, |(_, addr)| addr);
+        let host = addr.rsplit_once(':').map_or(addr, |(host, _)| host);
+        let unbracket = |host: &str| host.trim_start_matches('[').trim_end_matches(']').to_string();
+        self.overrides.iter().find(|(peer, _)| peer == addr)
+            .or_else(|| self.overrides.iter().find(|(peer, _)| unbracket(peer) == unbracket(host)))
+            .map(|(_, protocol)| *protocol)
+    }
+
+    /// Checks if every peer runs the protocol of `VERSION`.
+    pub fn is_empty(&self) -> bool {
+        self.overrides.is_empty()
+    }
+}
+
+/// A variable that is missing or invalid.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SettingError {
+    pub name: &'static str,
+    pub reason: String,
+}
+
+impl fmt::Display for SettingError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {}", self.name, self.reason)
+    }
+}
+
+/// Reads the variables of the settings, collecting every one that is missing or invalid.
+struct Parser<F> {
+    value: F,
+    errors: Vec<SettingError>,
+}
+
+impl<F: Fn(&str) -> Option<String>> Parser<F> {
+    fn new(value: F) -> Self {
+        Parser { value, errors: Vec::new() }
+    }
+
+    /// Parses a variable, recording why it is missing or invalid.
+    ///
+    /// # Arguments
+    /// * `name` - The name of the variable.
+    /// * `is_required` - Whether the variable must be set.
+    /// * `parse` - Parses the trimmed value, or tells why it is invalid.
+    ///
+    /// # Returns
+    /// The parsed value, `None` if the variable is not set, empty or invalid.
+    fn parse<T>(&mut self, name: &'static str, is_required: bool, parse: impl Fn(&str) -> Result<T, String>) -> Option<T> {
+        let raw = (self.value)(name).map(|raw| raw.trim().to_string()).filter(|raw| !raw.is_empty());
+        let result = match &raw {
+            Some(raw) => parse(raw).map(Some),
+            None if is_required => Err("not set".to_string()),
+            None => Ok(None),
+        };
+        result.unwrap_or_else(|reason| {
+            self.errors.push(SettingError { name, reason });
+            None
+        })
+    }
+
+    /// Gets the settings if every variable was valid.
+    fn finish<T>(self, settings: T) -> Result<T, Vec<SettingError>> {
+        if self.errors.is_empty() { Ok(settings) } else { Err(self.errors) }
+    }
+}
+
+
+/// Parses a number.
+fn parse_number<T: FromStr>(value: &str) -> Result<T, String> {
+    value.parse::<T>().map_err(|_| "not a number".to_string())
+}
+
+/// Parses a duration given in seconds.
+fn parse_seconds(value: &str) -> Result<Duration, String> {
+    parse_number::<u64>(value).map(Duration::from_secs)
+}
+
+/// Parses a duration given in minutes.
+fn parse_minutes(value: &str) -> Result<Duration, String> {
+    parse_number::<u64>(value).map(|minutes| Duration::from_secs(minutes.saturating_mul(60)))
+}
+
+/// Parses a switch, on unless `0` or `false`.
+fn parse_switch(value: &str) -> Result<bool, String> {
+    match value.to_ascii_lowercase().as_str() {
+        "1" | "true" => Ok(true),
+        "0" | "false" => Ok(false),
+        _ => Err("expected 1, 0, true or false".to_string()),
+    }
+}
+
+impl Settings {
+    /// Loads the settings from the environment variables.
+    ///
+    /// # Returns
+    /// The settings, or every variable that is missing or invalid.
+    ///
+    /// # Examples
+    /// ```
+    /// use std::env;
+    /// use qiner::settings::Settings;
+    /// use lib::env_names::ENV_SOLUTION_THRESHOLD;
+    ///
+    /// env::remove_var(ENV_SOLUTION_THRESHOLD);
+    /// let errors = Settings::load().unwrap_err();
+    /// assert!(errors.iter().any(|error| error.name == ENV_SOLUTION_THRESHOLD));
+    /// ```
+    pub fn load() -> Result<Settings, Vec<SettingError>> {
+        Settings::from_lookup(|name| env::var(name).ok())
+    }
+
+    /// Parses the settings from the values of the variables.
+    ///
+    /// # Arguments
+    /// * `value` - Gets the value of a variable, `None` if it is not set.
+    ///
+    /// # Returns
+    /// The settings, or every variable that is missing or invalid, in the order they are listed in `Settings`.
+    pub fn from_lookup(value: impl Fn(&str) -> Option<String>) -> Result<Settings, Vec<SettingError>> {
+        let mut parser = Parser::new(value);
+
+        let id = parser.parse(ENV_ID, true, |id| {
+            let is_valid = id.len() == ID_LENGTH && id.bytes().all(|letter| letter.is_ascii_uppercase());
+            if is_valid { Ok(id.to_string()) } else { Err(format!("expected {ID_LENGTH} uppercase letters")) }
+        });
+        let nodes = NodeSettings::parse(&mut parser, true);
+        let random_seed = parser.parse(ENV_RANDOM_SEED, true, parse_random_seed);
+        let solution_threshold = parser.parse(ENV_SOLUTION_THRESHOLD, true, parse_number::<usize>);
+        let algorithm = parser.parse(ENV_ALGORITHM, false, AlgorithmEntry::parse);
+        let version_params = AlgorithmParams::for_version(&nodes.version);
+        let number_of_neurons = parser.parse(ENV_NUMBER_OF_NEURONS, false, |neurons| {
+            let neurons = parse_number::<usize>(neurons)?;
+            AlgorithmParams { number_of_neurons: neurons, ..version_params }.validate().map(|_| neurons)
+        });
+        let mining_data_length = parser.parse(ENV_MINING_DATA_LENGTH, false, |length| {
+            let length = parse_number::<usize>(length)?;
+            AlgorithmParams { mining_data_length: length, ..version_params }.validate().map(|_| length)
+        });
+        let threads = ThreadSettings::parse(&mut parser);
+        let submission = SubmissionSettings::parse(&mut parser);
+        let process = ProcessSettings::parse(&mut parser);
+
+        parser.finish(Settings {
+            id: id.unwrap_or_default(),
+            random_seed: random_seed.unwrap_or_default(),
+            solution_threshold: solution_threshold.unwrap_or_default(),
+            algorithm: algorithm.unwrap_or_else(|| AlgorithmEntry::for_qubic_version(&nodes.version)),
+            nodes,
+            are_algorithm_params_overridden: number_of_neurons.is_some() || mining_data_length.is_some(),
+            algorithm_params: AlgorithmParams {
+                number_of_neurons: number_of_neurons.unwrap_or(version_params.number_of_neurons),
+                mining_data_length: mining_data_length.unwrap_or(version_params.mining_data_length),
+            },
+            threads,
+            submission,
+            process,
+        })
+    }
+}
+
+impl NodeSettings {
+    /// Loads the settings of the nodes from the environment variables.
+    ///
+    /// # Returns
+    /// The settings, or every variable that is invalid.
+    pub fn load() -> Result<NodeSettings, Vec<SettingError>> {
+        NodeSettings::from_lookup(|name| env::var(name).ok())
+    }
+
+    /// Parses the settings of the nodes from the values of the variables, none of them being required.
+    ///
+    /// # Arguments
+    /// * `value` - Gets the value of a variable, `None` if it is not set.
+    ///
+    /// # Returns
+    /// The settings, or every variable that is invalid.
+    pub fn from_lookup(value: impl Fn(&str) -> Option<String>) -> Result<NodeSettings, Vec<SettingError>> {
+        let mut parser = Parser::new(value);
+        let nodes = NodeSettings::parse(&mut parser, false);
+        parser.finish(nodes)
+    }
+
+    /// Parses the settings of the nodes.
+    ///
+    /// # Arguments
+    /// * `parser` - The variables, recording the invalid ones.
+    /// * `are_nodes_required` - Whether `SERVER_IP` and `SERVER_PORT` must be set.
+    fn parse(parser: &mut Parser<impl Fn(&str) -> Option<String>>, are_nodes_required: bool) -> NodeSettings {
+        let server_ip = parser.parse(ENV_SERVER_IP, are_nodes_required, |ip| Ok(ip.to_string()));
+        let server_port = parser.parse(ENV_SERVER_PORT, are_nodes_required, |port| port.parse::<u16>().map_err(|_| "not a port number".to_string()));
+        let version = parser.parse(ENV_VERSION, false, |version| parse_version(version).ok_or_else(|| "expected 3 numbers separated by dots".to_string()));
+        let peer_protocols = parser.parse(ENV_PEER_PROTOCOLS, false, PeerProtocols::parse);
+        let pool_server = parser.parse(ENV_POOL_SERVER, false, |addr| Ok(addr.to_string()));
+        let pool_failover = parser.parse(ENV_POOL_FAILOVER_MINUTES, false, parse_minutes);
+        let allow = parser.parse(ENV_PEER_ALLOWLIST, false, parse_networks);
+        let deny = parser.parse(ENV_PEER_DENYLIST, false, parse_networks);
+        // `0` never reports the skew
+        let max_clock_skew = parser.parse(ENV_CLOCK_SKEW_MAX_SECONDS, false, parse_seconds);
+        // `0` never checks the balance
+        let balance_check_interval = parser.parse(ENV_BALANCE_CHECK_MINUTES, false, parse_minutes);
+
+        NodeSettings {
+            server_ip: server_ip.unwrap_or_default(),
+            server_port,
+            is_version_overridden: version.is_some(),
+            version: version.unwrap_or(DEFAULT_VERSION),
+            peer_protocols: peer_protocols.unwrap_or_default(),
+            pool_server,
+            pool_failover: pool_failover.unwrap_or(DEFAULT_POOL_FAILOVER),
+            peer_filter: PeerFilter::from_networks(allow.unwrap_or_default(), deny.unwrap_or_default()),
+            max_clock_skew: max_clock_skew.map_or(Some(DEFAULT_MAX_CLOCK_SKEW), |skew| (!skew.is_zero()).then_some(skew)),
+            balance_check_interval: balance_check_interval.filter(|interval| !interval.is_zero()),
+        }
+    }
+
+    /// Gets the addresses of the nodes.
+    ///
+    /// # Returns
+    /// The addresses as `ip:port`, in the configured order, none if `SERVER_PORT` is not set.
+    pub fn get_addrs(&self) -> Vec<String> {
+        let Some(port) = self.server_port else {
+            return Vec::new();
+        };
+        self.server_ip.split(',')
+            .map(str::trim)
+            .filter(|ip| !ip.is_empty())
+            .map(|ip| format!("{ip}:{port}"))
+            .collect()
+    }
+
+    /// Gets the protocol version packets are sent with.
+    ///
+    /// # Returns
+    /// The minor number of the version.
+    pub fn get_protocol(&self) -> Protocol {
+        self.version[1]
+    }
+
+    /// Gets the protocol version packets are sent to a peer with.
+    ///
+    /// # Arguments
+    /// * `addr` - The address of the peer.
+    ///
+    /// # Returns
+    /// The protocol version given for the peer by `PEER_PROTOCOLS`, or the one of `version`.
+    pub fn get_peer_protocol(&self, addr: &str) -> Protocol {
+        self.peer_protocols.get(addr).unwrap_or(self.get_protocol())
+    }
+}
+
+impl ThreadSettings {
+    /// Parses the settings of the mining threads.
+    ///
+    /// # Arguments
+    /// * `parser` - The variables, recording the invalid ones.
+    fn parse(parser: &mut Parser<impl Fn(&str) -> Option<String>>) -> ThreadSettings {
+        let number_of_threads = parser.parse(ENV_NUMBER_OF_THREADS, false, |threads| {
+            threads.parse::<usize>().ok().filter(|threads| *threads > 0).ok_or_else(|| "a positive number is required".to_string())
+        });
+        let core_policy = parser.parse(ENV_CORE_POLICY, false, CorePolicy::parse);
+        let keccak_lanes = parser.parse(ENV_KECCAK_LANES, false, |lanes| match parse_number::<usize>(lanes)? {
+            lanes @ (1 | KECCAK_LANES) => Ok(lanes),
+            _ => Err(format!("expected 1 or {KECCAK_LANES}")),
+        });
+        let scheduler = parser.parse(ENV_SCHEDULER, false, Scheduler::parse);
+        let random_source = parser.parse(ENV_RANDOM_SOURCE, false, RandomSource::parse);
+
+        ThreadSettings {
+            number_of_threads,
+            core_policy: core_policy.unwrap_or_default(),
+            keccak_lanes: keccak_lanes.unwrap_or(1),
+            scheduler: scheduler.unwrap_or_default(),
+            random_source: random_source.unwrap_or_default(),
+        }
+    }
+
+    /// Places the mining threads on the cores of the CPU.
+    ///
+    /// # Returns
+    /// The placement, whose threads are not pinned unless the CPU has P-cores and E-cores.
+    pub fn get_placement(&self) -> ThreadPlacement {
+        ThreadPlacement::plan(self.core_policy, detect_topology().as_ref(), self.number_of_threads, get_available_cpus())
+    }
+}
+
+impl SubmissionSettings {
+    /// Loads the settings of the submission from the environment variables, for the subcommands that do not mine.
+    ///
+    /// # Returns
+    /// The settings, or every variable that is invalid.
+    pub fn load() -> Result<SubmissionSettings, Vec<SettingError>> {
+        let mut parser = Parser::new(|name: &str| env::var(name).ok());
+        let submission = SubmissionSettings::parse(&mut parser);
+        parser.finish(submission)
+    }
+
+    /// Parses the settings of the submission.
+    ///
+    /// # Arguments
+    /// * `parser` - The variables, recording the invalid ones.
+    fn parse(parser: &mut Parser<impl Fn(&str) -> Option<String>>) -> SubmissionSettings {
+        let solution_max_age = parser.parse(ENV_SOLUTION_MAX_AGE, false, parse_seconds);
+        // `0` removes the cap
+        let solution_max_in_memory = parser.parse(ENV_SOLUTION_MAX_IN_MEMORY, false, parse_number::<usize>);
+        // `0` disables the packet limits
+        let max_packets_per_connection = parser.parse(ENV_SUBMIT_MAX_PACKETS_PER_CONNECTION, false, parse_number::<usize>);
+        let max_packets_per_second = parser.parse(ENV_SUBMIT_MAX_PACKETS_PER_SECOND, false, parse_number::<usize>);
+        let min_connect_interval = parser.parse(ENV_SUBMIT_MIN_CONNECT_INTERVAL_MS, false, |interval| parse_number::<u64>(interval).map(Duration::from_millis));
+        let connections_per_node = parser.parse(ENV_SUBMIT_CONNECTIONS_PER_NODE, false, |connections| {
+            connections.parse::<usize>().ok().filter(|connections| *connections > 0).ok_or_else(|| "a positive number is required".to_string())
+        });
+        // `0` removes the cap
+        let max_bytes_per_hour = parser.parse(ENV_BANDWIDTH_MAX_BYTES_PER_HOUR, false, parse_number::<u64>);
+        let share_threshold = parser.parse(ENV_SHARE_THRESHOLD, false, parse_number::<usize>);
+        let share_server = parser.parse(ENV_SHARE_SERVER, false, |addr| Ok(addr.to_string()));
+
+        SubmissionSettings {
+            solution_max_age: solution_max_age.unwrap_or(DEFAULT_SOLUTION_MAX_AGE),
+            solution_max_in_memory: solution_max_in_memory.map_or(Some(DEFAULT_SOLUTION_MAX_IN_MEMORY), |max| (max > 0).then_some(max)),
+            pacing: SubmissionPacing {
+                max_packets_per_connection: max_packets_per_connection.filter(|limit| *limit > 0),
+                max_packets_per_second: max_packets_per_second.filter(|limit| *limit > 0),
+                min_connect_interval: min_connect_interval.unwrap_or(DEFAULT_MIN_CONNECT_INTERVAL),
+                connections_per_node: connections_per_node.unwrap_or(1),
+            },
+            max_bytes_per_hour: max_bytes_per_hour.filter(|max| *max > 0),
+            share_threshold,
+            share_server,
+        }
+    }
+}
+
+impl ProcessSettings {
+    /// Loads the settings of the process from the environment variables, before anything else is.
+    ///
+    /// # Returns
+    /// The settings, or every variable that is invalid.
+    pub fn load() -> Result<ProcessSettings, Vec<SettingError>> {
+        let mut parser = Parser::new(|name: &str| env::var(name).ok());
+        let process = ProcessSettings::parse(&mut parser);
+        parser.finish(process)
+    }
+
+    /// Parses the settings of the process.
+    ///
+    /// # Arguments
+    /// * `parser` - The variables, recording the invalid ones.
+    fn parse(parser: &mut Parser<impl Fn(&str) -> Option<String>>) -> ProcessSettings {
+        let priority = parser.parse(ENV_PRIORITY, false, Priority::parse);
+        let state_dir = parser.parse(ENV_STATE_DIR, false, |dir| Ok(PathBuf::from(dir))).unwrap_or_else(|| PathBuf::from("."));
+        let keystore_file = parser.parse(ENV_KEYSTORE_FILE, false, |file| Ok(PathBuf::from(file)));
+        let spill_file = parser.parse(ENV_SPILL_FILE, false, |file| Ok(PathBuf::from(file)));
+        let summary_file = parser.parse(ENV_SUMMARY_FILE, false, |file| Ok(PathBuf::from(file)));
+        let packet_capture = parser.parse(ENV_PACKET_CAPTURE, false, |file| Ok(PathBuf::from(file)));
+        let panic_exit = parser.parse(ENV_PANIC_EXIT, false, parse_switch);
+        let instance_lock = parser.parse(ENV_INSTANCE_LOCK, false, parse_switch);
+        // `0` only restarts the workers that exited
+        let stall_timeout = parser.parse(ENV_STALL_TIMEOUT, false, parse_seconds);
+        let health_port = parser.parse(ENV_HEALTH_PORT, false, |port| port.parse::<u16>().map_err(|_| "not a port number".to_string()));
+        let health_stall_timeout = parser.parse(ENV_HEALTH_STALL_TIMEOUT, false, parse_seconds);
+        let health_connect_timeout = parser.parse(ENV_HEALTH_CONNECT_MINUTES, false, parse_minutes);
+
+        ProcessSettings {
+            priority: priority.unwrap_or_default(),
+            keystore_file: keystore_file.unwrap_or_else(|| state_dir.join(DEFAULT_KEYSTORE_FILE)),
+            state_dir,
+            spill_file: spill_file.unwrap_or_else(|| PathBuf::from(DEFAULT_SPILL_FILE)),
+            summary_file,
+            packet_capture,
+            panic_exit: panic_exit.unwrap_or(true),
+            instance_lock: instance_lock.unwrap_or(true),
+            stall_timeout: stall_timeout.map_or(Some(DEFAULT_STALL_TIMEOUT), |timeout| (!timeout.is_zero()).then_some(timeout)),
+            health_port,
+            health_stall_timeout: health_stall_timeout.unwrap_or(DEFAULT_HEALTH_STALL_TIMEOUT),
+            health_connect_timeout: health_connect_timeout.unwrap_or(DEFAULT_HEALTH_CONNECT_TIMEOUT),
+        }
+    }
+}
+
+#[test]
+/// Tests that valid settings are parsed and that every invalid variable is reported.
+fn test_settings() {
+    let config = [
+        (ENV_ID, "BZBQFLLBNCXEMGLOBHUVFTLUPLVCPQUASSILFABOFFBCADQSSUPNWLZBQEXK"),
+        (ENV_SERVER_IP, "127.0.0.1, 127.0.0.2"),
+        (ENV_SERVER_PORT, "21841"),
+        (ENV_RANDOM_SEED, "0x0100e909"),
+        (ENV_SOLUTION_THRESHOLD, " 22 "),
+    ];
+    let lookup = |overrides: &'static [(&'static str, &'static str)]| move |name: &str| {
+        overrides.iter().chain(config.iter()).find(|(key, _)| *key == name).map(|(_, value)| value.to_string())
+    };
+
+    let settings = Settings::from_lookup(lookup(&[])).unwrap();
+    assert_eq!(settings.nodes.server_port, Some(21841));
+    assert_eq!(settings.nodes.get_addrs(), ["127.0.0.1:21841", "127.0.0.2:21841"]);
+    assert_eq!(settings.nodes.version, DEFAULT_VERSION);
+    assert!(!settings.nodes.is_version_overridden);
+    assert_eq!(settings.random_seed[..4], [1, 0, 233, 9]);
+    assert_eq!(settings.solution_threshold, 22);
+    assert_eq!(settings.threads.number_of_threads, None);
+    assert_eq!(settings.threads.keccak_lanes, 1);
+    assert_eq!(settings.algorithm.version, AlgorithmEntry::for_qubic_version(&DEFAULT_VERSION).version);
+    assert_eq!(settings.algorithm_params, AlgorithmParams::CURRENT);
+    assert_eq!(settings.nodes.pool_failover, DEFAULT_POOL_FAILOVER);
+    assert_eq!(settings.nodes.max_clock_skew, Some(DEFAULT_MAX_CLOCK_SKEW));
+    assert_eq!(settings.submission.pacing, SubmissionPacing::default());
+    assert_eq!(settings.submission.solution_max_in_memory, Some(DEFAULT_SOLUTION_MAX_IN_MEMORY));
+    assert_eq!(settings.process.keystore_file, PathBuf::from(".").join(DEFAULT_KEYSTORE_FILE));
+    assert!(settings.process.panic_exit && settings.process.instance_lock);
+
+    let settings = Settings::from_lookup(lookup(&[(ENV_VERSION, "1.143.0"), (ENV_NUMBER_OF_THREADS, "4")])).unwrap();
+    assert_eq!(settings.nodes.get_protocol(), 143);
+    assert!(settings.nodes.is_version_overridden);
+    assert_eq!(settings.threads.number_of_threads, Some(4));
+    assert_eq!(settings.nodes.get_peer_protocol("127.0.0.1:21841"), 143);
+
+    let settings = Settings::from_lookup(lookup(&[(ENV_PEER_PROTOCOLS, "127.0.0.2=140, [::1]:21842=139")])).unwrap();
+    assert_eq!(settings.nodes.get_peer_protocol("127.0.0.2:21841"), 140);
+    assert_eq!(settings.nodes.get_peer_protocol("quic://127.0.0.2:21843"), 140);
+    assert_eq!(settings.nodes.get_peer_protocol("[::1]:21842"), 139);
+    assert_eq!(settings.nodes.get_peer_protocol("[::1]:21841"), DEFAULT_VERSION[1]);
+
+    let settings = Settings::from_lookup(lookup(&[(ENV_NUMBER_OF_NEURONS, "1024"), (ENV_MINING_DATA_LENGTH, "4")])).unwrap();
+    assert_eq!(settings.algorithm_params, AlgorithmParams { number_of_neurons: 1024, mining_data_length: 4 });
+    assert!(settings.are_algorithm_params_overridden);
+
+    // `0` turns the optional limits off
+    let settings = Settings::from_lookup(lookup(&[(ENV_STALL_TIMEOUT, "0"), (ENV_CLOCK_SKEW_MAX_SECONDS, "0"), (ENV_SOLUTION_MAX_IN_MEMORY, "0"), (ENV_SUBMIT_MAX_PACKETS_PER_SECOND, "0"), (ENV_STATE_DIR, "/var/lib/qiner"), (ENV_PANIC_EXIT, "FALSE")])).unwrap();
+    assert_eq!(settings.process.stall_timeout, None);
+    assert_eq!(settings.nodes.max_clock_skew, None);
+    assert_eq!(settings.submission.solution_max_in_memory, None);
+    assert_eq!(settings.submission.pacing.max_packets_per_second, None);
+    assert_eq!(settings.process.keystore_file, PathBuf::from("/var/lib/qiner").join(DEFAULT_KEYSTORE_FILE));
+    assert!(!settings.process.panic_exit);
+
+    let errors = Settings::from_lookup(lookup(&[
+        (ENV_ID, "abc"),
+        (ENV_SERVER_PORT, "70000"),
+        (ENV_SOLUTION_THRESHOLD, ""),
+        (ENV_NUMBER_OF_THREADS, "0"),
+        (ENV_NUMBER_OF_NEURONS, "1000"),
+        (ENV_PEER_PROTOCOLS, "127.0.0.2"),
+        (ENV_PEER_DENYLIST, "10.0.0.0/33"),
+        (ENV_KECCAK_LANES, "3"),
+        (ENV_PANIC_EXIT, "maybe"),
+    ])).unwrap_err();
+    let names = errors.iter().map(|error| error.name).collect::<Vec<_>>();
+    assert_eq!(names, [ENV_ID, ENV_SERVER_PORT, ENV_PEER_PROTOCOLS, ENV_PEER_DENYLIST, ENV_SOLUTION_THRESHOLD, ENV_NUMBER_OF_NEURONS, ENV_NUMBER_OF_THREADS, ENV_KECCAK_LANES, ENV_PANIC_EXIT]);
+    assert_eq!(errors[4].to_string(), "SOLUTION_THRESHOLD: not set");
+
+    // The subcommands that do not mine only need valid node settings
+    let nodes = NodeSettings::from_lookup(|name| (name == ENV_VERSION).then(|| "1.143.0".to_string())).unwrap();
+    assert!(nodes.get_addrs().is_empty());
+    assert_eq!(nodes.get_peer_protocol("127.0.0.1:21841"), 143);
+    assert_eq!(NodeSettings::from_lookup(|name| (name == ENV_VERSION).then(|| "x".to_string())).unwrap_err()[0].name, ENV_VERSION);
+}
@@ -0,0 +1,57 @@
+use std::sync::Arc;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::Duration;
+use crate::settings::Settings;
+use lib::types::PublicKey64;
+use tokio::io::AsyncWriteExt;
+use crate::capture;
+use crate::miner::Miner;
+use crate::network::{NetStats, Packet};
+use crate::socks::{self, SocksProxy};
+
+/// Interval between two deliveries of the found shares.
+const SHARE_SEND_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Asynchronous task delivering the found shares to a pool or private proxy.
+///
+/// Shares are best effort: unlike solutions, the ones that cannot be delivered are
+/// dropped instead of retried, and they wait while the bandwidth cap is reached.
+/// Without a share server, shares are only counted.
+///
+/// # Arguments
+/// * `miner` - Shared reference to the Miner instance
+/// * `addr` - Address of the share server, `None` to only count shares
+/// * `socks` - The SOCKS proxy shares are sent through, `None` to connect directly
+/// * `settings` - The settings, giving the protocol version of the packets
+/// * `public_key` - The public key of the computor
+/// * `shares_sent` - Shared counter for sent shares
+/// * `net_stats` - Shared network counters, whose bandwidth cap the shares must fit in
+pub async fn send_shares_task(miner: Arc<Miner>, addr: Option<String>, socks: Option<SocksProxy>, settings: Arc<Settings>, public_key: PublicKey64, shares_sent: Arc<AtomicUsize>, net_stats: Arc<NetStats>) {
+    loop {
+        tokio::time::sleep(SHARE_SEND_INTERVAL).await;
+
+        let shares = miner.found_shares.lock().await.drain(..).collect::<Vec<_>>();
+        let Some(addr) = addr.as_ref().filter(|_| !shares.is_empty()) else {
+            continue;
+        };
+
+        let data = Packet::solutions_to_bytes(settings.nodes.get_peer_protocol(addr), &public_key, shares.iter().map(|share| &share.nonce));
+        if !net_stats.get_bandwidth().try_consume(data.len()) {
+            miner.found_shares.lock().await.extend(shares);
+            continue;
+        }
+        let result = match socks::connect(socks.as_ref(), addr).await {
+            Ok(mut stream) => stream.write_all(&data).await,
+            Err(err) => Err(err),
+        };
+
+        match result {
+            Ok(_) => {
+                capture::record_sent(addr, &data);
+                shares_sent.fetch_add(shares.len(), Ordering::Relaxed);
+                log::debug!("Sent {} shares to {addr}", shares.len());
+            }
+            Err(err) => log::warn!("Failed to send {} shares to {addr}: {:?}", shares.len(), err),
+        }
+    }
+}
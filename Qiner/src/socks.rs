@@ -0,0 +1,259 @@
+//! Submission through a SOCKS5 proxy, e.g. the `SocksPort` of Tor, so nodes and pools do not
+//! see the IP of the rig.
+//!
+//! Host names are resolved by the proxy, not locally. With isolation, every connection
+//! authenticates with new credentials: Tor isolates streams by SOCKS credentials, so each
+//! submission goes over its own circuit and exit, and submissions cannot be linked by IP.
+
+use std::env;
+use std::io;
+use std::mem::size_of;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use lib::env_names::{ENV_SOCKS_ISOLATION, ENV_SOCKS_PROXY};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpStream;
+
+/// Version byte of every SOCKS5 message.
+const SOCKS_VERSION: u8 = 5;
+
+/// Authentication method without credentials.
+const METHOD_NO_AUTH: u8 = 0;
+
+/// Authentication method with a username and a password.
+const METHOD_USERNAME_PASSWORD: u8 = 2;
+
+/// Method the proxy replies when it accepts none of the offered ones.
+const METHOD_NONE_ACCEPTABLE: u8 = 0xff;
+
+/// Command opening a TCP connection.
+const COMMAND_CONNECT: u8 = 1;
+
+/// Address types of the requests and replies.
+const ADDRESS_IPV4: u8 = 1;
+const ADDRESS_DOMAIN: u8 = 3;
+const ADDRESS_IPV6: u8 = 4;
+
+/// A SOCKS5 proxy the connections to the nodes and pools go through.
+#[derive(Debug, Clone)]
+pub struct SocksProxy {
+    addr: String,
+    is_isolated: bool,
+    /// Number of connections opened, giving every connection its own credentials
+    connections: Arc<AtomicU64>,
+    /// Random prefix of the credentials, so circuits are not shared across restarts either
+    session: u64,
+}
+
+impl SocksProxy {
+    /// Creates a new `SocksProxy`.
+    ///
+    /// # Arguments
+    /// * `addr` - The address of the proxy, as `host:port`
+    /// * `is_isolated` - Whether every connection uses its own credentials, and so its own Tor circuit
+    ///
+    /// # Returns
+    /// A new `SocksProxy`.
+    pub fn new(addr: String, is_isolated: bool) -> Self {
+        let mut session = [0u8; 8];
+        getrandom::fill(&mut session).ok();
+        SocksProxy {
+            addr,
+            is_isolated,
+            connections: Arc::new(AtomicU64::new(0)),
+            session: u64::from_le_bytes(session),
+        }
+    }
+
+    /// Retrieve the proxy from the environment variables.
+    ///
+    /// # Returns
+    /// The proxy of `SOCKS_PROXY`, isolated unless `SOCKS_ISOLATION` is `false` or `0`,
+    /// or `None` if `SOCKS_PROXY` is not set.
+    pub fn from_env() -> Option<Self> {
+        let addr = env::var(ENV_SOCKS_PROXY).ok().map(|value| value.trim().to_string()).filter(|value| !value.is_empty())?;
+        let is_isolated = env::var(ENV_SOCKS_ISOLATION).map_or(true, |value| !matches!(value.trim().to_lowercase().as_str(), "false" | "0"));
+        Some(SocksProxy::new(addr, is_isolated))
+    }
+
+    /// Gets the address of the proxy.
+    pub fn get_addr(&self) -> &str {
+        &self.addr
+    }
+
+    /// Checks if every connection uses its own credentials.
+    pub fn is_isolated(&self) -> bool {
+        self.is_isolated
+    }
+
+    /// Opens a connection to a node or a pool through the proxy.
+    ///
+    /// # Arguments
+    /// * `target` - The address to connect to, as `host:port`
+    ///
+    /// # Returns
+    /// The connection, ready to be written to as if opened directly.
+    pub async fn connect(&self, target: &str) -> io::Result<TcpStream> {
+        let (host, port) = target.rsplit_once(':')
+            .and_then(|(host, port)| Some((host.trim_start_matches('[').trim_end_matches(']'), port.parse::<u16>().ok()?)))
+            .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, format!("invalid address {target}")))?;
+
+        let mut stream = TcpStream::connect(&self.addr).await?;
+
+        // Offer credentials only when isolating
+        let method = if self.is_isolated { METHOD_USERNAME_PASSWORD } else { METHOD_NO_AUTH };
+        stream.write_all(&[SOCKS_VERSION, 1, method]).await?;
+        let mut reply = [0u8; 2];
+        stream.read_exact(&mut reply).await?;
+        if reply[0] != SOCKS_VERSION || reply[1] == METHOD_NONE_ACCEPTABLE || reply[1] != method {
+            return Err(io::Error::other(format!("proxy {} refused the authentication method {method}", self.addr)));
+        }
+
+        if method == METHOD_USERNAME_PASSWORD {
+            let username = format!("qiner-{:016x}-{}", self.session, self.connections.fetch_add(1, Ordering::Relaxed));
+            let mut auth = vec![1, username.len() as u8];
+            auth.extend_from_slice(username.as_bytes());
+            auth.extend_from_slice(&[1, b'x']);
+            stream.write_all(&auth).await?;
+            stream.read_exact(&mut reply).await?;
+            if reply[1] != 0 {
+                return Err(io::Error::other(format!("proxy {} refused the credentials", self.addr)));
+            }
+        }
+
+        let mut request = vec![SOCKS_VERSION, COMMAND_CONNECT, 0];
+        match host.parse::<IpAddr>() {
+            Ok(IpAddr::V4(ip)) => {
+                request.push(ADDRESS_IPV4);
+                request.extend_from_slice(&ip.octets());
+            }
+            Ok(IpAddr::V6(ip)) => {
+                request.push(ADDRESS_IPV6);
+                request.extend_from_slice(&ip.octets());
+            }
+            Err(_) if host.len() <= u8::MAX as usize => {
+                request.extend_from_slice(&[ADDRESS_DOMAIN, host.len() as u8]);
+                request.extend_from_slice(host.as_bytes());
+            }
+            Err(_) => return Err(io::Error::new(io::ErrorKind::InvalidInput, format!("host name too long in {target}"))),
+        }
+        request.extend_from_slice(&port.to_be_bytes());
+        stream.write_all(&request).await?;
+
+        // The reply ends with the address the proxy bound, of variable length
+        let mut reply = [0u8; 4];
+        stream.read_exact(&mut reply).await?;
+        if reply[0] != SOCKS_VERSION || reply[1] != 0 {
+            return Err(io::Error::new(io::ErrorKind::ConnectionRefused, format!("proxy {} failed to connect to {target}: {}", self.addr, reply_error(reply[1]))));
+        }
+        let address_len = match reply[3] {
+            ADDRESS_IPV4 => size_of::<Ipv4Addr>(),
+            ADDRESS_IPV6 => size_of::<Ipv6Addr>(),
+            ADDRESS_DOMAIN => stream.read_u8().await? as usize,
+            address_type => return Err(io::Error::other(format!("proxy {} replied the unknown address type {address_type}", self.addr))),
+        };
+        let mut bound = vec![0u8; address_len + size_of::<u16>()];
+        stream.read_exact(&mut bound).await?;
+
+        Ok(stream)
+    }
+}
+
+/// Opens a connection, through the proxy if there is one.
+///
+/// # Arguments
+/// * `socks` - The proxy, `None` to connect directly
+/// * `target` - The address to connect to, as `host:port`
+///
+/// # Returns
+/// The connection.
+pub async fn connect(socks: Option<&SocksProxy>, target: &str) -> io::Result<TcpStream> {
+    match socks {
+        Some(socks) => socks.connect(target).await,
+        None => TcpStream::connect(target).await,
+    }
+}
+
+/// Describes the error code of a SOCKS5 reply.
+fn reply_error(code: u8) -> &'static str {
+    match code {
+        1 => "general failure",
+        2 => "not allowed by the ruleset",
+        3 => "network unreachable",
+        4 => "host unreachable",
+        5 => "connection refused",
+        6 => "TTL expired",
+        7 => "command not supported",
+        8 => "address type not supported",
+        _ => "unknown error",
+    }
+}
+
+#[test]
+/// Tests the handshake with a proxy, that host names are left to it and that connections are isolated.
+fn test_socks_proxy() {
+    use tokio::net::TcpListener;
+
+    let runtime = tokio::runtime::Builder::new_current_thread().enable_all().build().unwrap();
+    runtime.block_on(async {
+        // A proxy that records the credentials and the target of every connection, then echoes
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let proxy_addr = listener.local_addr().unwrap().to_string();
+        let server = tokio::spawn(async move {
+            let mut requests = Vec::new();
+            for _ in 0..3 {
+                let (mut stream, _) = listener.accept().await.unwrap();
+                let mut greeting = [0u8; 3];
+                stream.read_exact(&mut greeting).await.unwrap();
+                stream.write_all(&[SOCKS_VERSION, greeting[2]]).await.unwrap();
+
+                let mut username = String::new();
+                if greeting[2] == METHOD_USERNAME_PASSWORD {
+                    let mut header = [0u8; 2];
+                    stream.read_exact(&mut header).await.unwrap();
+                    let mut auth = vec![0u8; header[1] as usize + 1];
+                    stream.read_exact(&mut auth).await.unwrap();
+                    username = String::from_utf8(auth[..header[1] as usize].to_vec()).unwrap();
+                    stream.read_exact(&mut vec![0u8; auth[header[1] as usize] as usize]).await.unwrap();
+                    stream.write_all(&[1, 0]).await.unwrap();
+                }
+
+                let mut request = [0u8; 5];
+                stream.read_exact(&mut request).await.unwrap();
+                assert_eq!(request[..3], [SOCKS_VERSION, COMMAND_CONNECT, 0]);
+                let mut target = vec![0u8; if request[3] == ADDRESS_DOMAIN { request[4] as usize } else { 3 } + 2];
+                stream.read_exact(&mut target).await.unwrap();
+                let target = match request[3] {
+                    ADDRESS_DOMAIN => String::from_utf8(target[..target.len() - 2].to_vec()).unwrap(),
+                    _ => Ipv4Addr::new(request[4], target[0], target[1], target[2]).to_string(),
+                };
+                requests.push((username, target));
+                stream.write_all(&[SOCKS_VERSION, 0, 0, ADDRESS_IPV4, 0, 0, 0, 0, 0, 0]).await.unwrap();
+
+                let mut data = [0u8; 4];
+                stream.read_exact(&mut data).await.unwrap();
+                stream.write_all(&data).await.unwrap();
+            }
+            requests
+        });
+
+        let isolated = SocksProxy::new(proxy_addr.clone(), true);
+        let shared = SocksProxy::new(proxy_addr, false);
+        for (socks, target) in [(&isolated, "node.example:21841"), (&isolated, "10.0.0.2:21841"), (&shared, "10.0.0.3:21841")] {
+            let mut stream = connect(Some(socks), target).await.unwrap();
+            stream.write_all(b"ping").await.unwrap();
+            let mut echo = [0u8; 4];
+            stream.read_exact(&mut echo).await.unwrap();
+            assert_eq!(&echo, b"ping");
+        }
+
+        let requests = server.await.unwrap();
+        assert_eq!(requests.iter().map(|(_, target)| target.as_str()).collect::<Vec<_>>(), ["node.example", "10.0.0.2", "10.0.0.3"]);
+        assert!(requests[0].0.starts_with("qiner-") && requests[1].0.starts_with("qiner-"));
+        assert_ne!(requests[0].0, requests[1].0);
+        assert!(requests[2].0.is_empty());
+
+        assert!(isolated.connect("no-port").await.is_err());
+    });
+}
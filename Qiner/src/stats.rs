@@ -0,0 +1,789 @@
+//! Samples the counters of the miner on an interval and fans the snapshots out to sinks: the
+//! console, JSON log lines, a Prometheus endpoint and a file.
+
+use std::env;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use serde_json::{json, Value};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+use lib::env_names::{ENV_STATS_FILE, ENV_STATS_INTERVAL, ENV_STATS_PROMETHEUS_PORT, ENV_STATS_SINKS};
+use lib::types::network::Protocol;
+use crate::energy::{EnergyMeter, EnergyReading};
+use crate::estimator::{expected_solutions, expected_time_between_solutions, format_duration, probability_of_at_most};
+use crate::histogram::{near_misses, NearMiss};
+use crate::miner::{EpochCounters, IterationMarker, Miner};
+use crate::network::NetStats;
+use crate::rig::get_worker_name;
+use crate::notifier::{Notifier, NotifyEvent};
+use crate::submission::SubmissionStats;
+
+/// Default interval between two snapshots.
+const DEFAULT_STATS_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Default port of the Prometheus endpoint.
+const DEFAULT_STATS_PROMETHEUS_PORT: u16 = 9184;
+
+/// Default file the `file` sink writes the last snapshot to.
+const DEFAULT_STATS_FILE: &str = "stats.json";
+
+/// Interval between two comparisons of found and expected solutions on the console.
+const LUCK_REPORT_INTERVAL: Duration = Duration::from_secs(10 * 60);
+
+/// Probability below which finding so few solutions is reported as suspicious.
+const UNLIKELY_LUCK_PROBABILITY: f64 = 0.01;
+
+/// The network counters at one moment.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct NetSnapshot {
+    pub packets_sent: u64,
+    pub bytes_sent: u64,
+    pub connect_attempts: u64,
+    pub connect_failures: u64,
+    pub average_latency: Duration,
+    pub max_latency: Duration,
+    /// Average time building a solution packet took, the K12 grinding of its gamming key
+    pub average_packet_build: Duration,
+    /// Protocol version of the last message received from a node
+    pub node_protocol: Option<Protocol>,
+    pub protocol_mismatches: u64,
+    /// Bytes sent over the last hour, the window of `BANDWIDTH_MAX_BYTES_PER_HOUR`
+    pub bytes_last_hour: u64,
+    /// Keep-alives and shares deferred by the bandwidth cap
+    pub deferred_sends: u64,
+    /// Skew of the local clock against the ticks of the nodes in milliseconds, positive when ahead, `None` until estimated
+    pub clock_skew_ms: Option<i64>,
+}
+
+/// The counters of the miner, of submission and of the network at one moment, handed to every sink.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct StatsSnapshot {
+    /// Time since mining started
+    pub uptime: Duration,
+    pub iterations: usize,
+    /// Iterations per second since the previous snapshot
+    pub it_per_sec: f64,
+    /// Iterations per second since mining started, far steadier than `it_per_sec`
+    pub average_it_per_sec: f64,
+    pub solution_threshold: usize,
+    pub solutions_found: usize,
+    /// Solutions sent in the epoch, restarts included, like the answered connections and the failed solutions
+    pub solutions_sent: u64,
+    /// Connections the node answered after the solutions were written, solutions are never acknowledged
+    pub answered_connections: u64,
+    /// Solutions dropped unsent once older than the maximum age
+    pub solutions_failed: u64,
+    /// Solutions dropped unsent once the epoch they were mined in passed
+    pub solutions_expired: u64,
+    /// Shares found, `None` without a share threshold
+    pub shares_found: Option<usize>,
+    pub shares_sent: usize,
+    pub worker_restarts: usize,
+    /// Iterations of every mining thread
+    pub thread_iterations: Vec<usize>,
+    /// Nonces reaching the scores right below the threshold
+    pub near_misses: Vec<NearMiss>,
+    pub network: NetSnapshot,
+    /// Counters of the current epoch, the others being since the process started
+    pub epoch: EpochCounters,
+    /// Energy of the CPU packages since the sampler started, `None` without readable RAPL counters
+    pub energy: Option<EnergyReading>,
+}
+
+impl StatsSnapshot {
+    /// Formats the snapshot as JSON, latencies in milliseconds.
+    pub fn to_json(&self) -> Value {
+        json!({
+            "uptime_secs": self.uptime.as_secs(),
+            "iterations": self.iterations,
+            "it_per_sec": self.it_per_sec,
+            "average_it_per_sec": self.average_it_per_sec,
+            "solution_threshold": self.solution_threshold,
+            "solutions_found": self.solutions_found,
+            "solutions_sent": self.solutions_sent,
+            "answered_connections": self.answered_connections,
+            "solutions_failed": self.solutions_failed,
+            "solutions_expired": self.solutions_expired,
+            "shares_found": self.shares_found,
+            "shares_sent": self.shares_sent,
+            "worker_restarts": self.worker_restarts,
+            "thread_iterations": self.thread_iterations,
+            "epoch": {
+                "secs": self.epoch.duration.as_secs(),
+                "iterations": self.epoch.iterations,
+                "solutions_found": self.epoch.solutions_found,
+                "shares_found": self.epoch.shares_found,
+            },
+            "energy": self.energy.map(|energy| json!({
+                "joules": energy.joules,
+                "watts": energy.watts,
+                "joules_per_solution": energy.joules_per_solution(self.solutions_found),
+                "it_per_joule": energy.it_per_joule(self.iterations),
+            })),
+            "network": {
+                "packets_sent": self.network.packets_sent,
+                "bytes_sent": self.network.bytes_sent,
+                "connect_attempts": self.network.connect_attempts,
+                "connect_failures": self.network.connect_failures,
+                "average_latency_ms": self.network.average_latency.as_millis() as u64,
+                "max_latency_ms": self.network.max_latency.as_millis() as u64,
+                "average_packet_build_us": self.network.average_packet_build.as_micros() as u64,
+                "node_protocol": self.network.node_protocol,
+                "protocol_mismatches": self.network.protocol_mismatches,
+                "bytes_last_hour": self.network.bytes_last_hour,
+                "deferred_sends": self.network.deferred_sends,
+                "clock_skew_ms": self.network.clock_skew_ms,
+            },
+        })
+    }
+
+    /// Formats the snapshot in the Prometheus text exposition format.
+    ///
+    /// # Arguments
+    /// * `worker_name` - The name of the rig, the `worker` label of every metric, `None` for no label
+    /// * `config_fingerprint` - The fingerprint of the settings, the `fingerprint` label of `qiner_config_info`, `None` to leave it out
+    ///
+    /// # Returns
+    /// One `qiner_`-prefixed metric per counter, the clock skew once estimated, the energy with RAPL counters,
+    /// shares only with a share threshold.
+    pub fn to_prometheus(&self, worker_name: Option<&str>, config_fingerprint: Option<&str>) -> String {
+        let mut metrics = vec![
+            ("uptime_seconds", "gauge", self.uptime.as_secs_f64()),
+            ("iterations_total", "counter", self.iterations as f64),
+            ("it_per_sec", "gauge", self.it_per_sec),
+            ("solution_threshold", "gauge", self.solution_threshold as f64),
+            ("solutions_found_total", "counter", self.solutions_found as f64),
+            ("solutions_sent_total", "counter", self.solutions_sent as f64),
+            ("answered_connections_total", "counter", self.answered_connections as f64),
+            ("solutions_failed_total", "counter", self.solutions_failed as f64),
+            ("solutions_expired_total", "counter", self.solutions_expired as f64),
+            ("worker_restarts_total", "counter", self.worker_restarts as f64),
+            ("epoch_iterations", "gauge", self.epoch.iterations as f64),
+            ("epoch_solutions_found", "gauge", self.epoch.solutions_found as f64),
+            ("network_packets_sent_total", "counter", self.network.packets_sent as f64),
+            ("network_bytes_sent_total", "counter", self.network.bytes_sent as f64),
+            ("network_connect_attempts_total", "counter", self.network.connect_attempts as f64),
+            ("network_connect_failures_total", "counter", self.network.connect_failures as f64),
+            ("network_average_latency_ms", "gauge", self.network.average_latency.as_millis() as f64),
+            ("network_max_latency_ms", "gauge", self.network.max_latency.as_millis() as f64),
+            ("network_average_packet_build_us", "gauge", self.network.average_packet_build.as_micros() as f64),
+            ("network_protocol_mismatches_total", "counter", self.network.protocol_mismatches as f64),
+            ("network_bytes_last_hour", "gauge", self.network.bytes_last_hour as f64),
+            ("network_deferred_sends_total", "counter", self.network.deferred_sends as f64),
+        ];
+        if let Some(clock_skew_ms) = self.network.clock_skew_ms {
+            metrics.push(("clock_skew_seconds", "gauge", clock_skew_ms as f64 / 1000.0));
+        }
+        if let Some(energy) = self.energy {
+            metrics.push(("energy_joules_total", "counter", energy.joules));
+            metrics.push(("power_watts", "gauge", energy.watts));
+            metrics.push(("it_per_joule", "gauge", energy.it_per_joule(self.iterations)));
+            if let Some(joules_per_solution) = energy.joules_per_solution(self.solutions_found) {
+                metrics.push(("joules_per_solution", "gauge", joules_per_solution));
+            }
+        }
+        if let Some(shares_found) = self.shares_found {
+            metrics.push(("shares_found_total", "counter", shares_found as f64));
+            metrics.push(("shares_sent_total", "counter", self.shares_sent as f64));
+        }
+
+        let worker_label = worker_name.map(|name| format!("worker=\"{}\"", name.replace('\\', "\\\\").replace('"', "\\\"")));
+        let labels = worker_label.as_ref().map_or(String::new(), |label| format!("{{{label}}}"));
+        let mut exposition = metrics.iter().map(|(name, kind, value)| format!("# TYPE qiner_{name} {kind}\nqiner_{name}{labels} {value}\n")).collect::<String>();
+        if let Some(config_fingerprint) = config_fingerprint {
+            let labels = worker_label.into_iter().chain([format!("fingerprint=\"{config_fingerprint}\"")]).collect::<Vec<_>>().join(",");
+            exposition.push_str(&format!("# TYPE qiner_config_info gauge\nqiner_config_info{{{labels}}} 1\n"));
+        }
+        exposition
+    }
+}
+
+/// Receives every snapshot of the miner.
+pub trait StatsSink: Send {
+    /// Records a snapshot.
+    ///
+    /// # Arguments
+    /// * `snapshot` - The counters of the miner, sampled right before.
+    fn record(&mut self, snapshot: &StatsSnapshot);
+}
+
+/// How much of the progress the console logs.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Verbosity {
+    /// Only the snapshots where solutions were found or sent, for long-running headless miners.
+    Quiet,
+    /// The progress, the shares and the network on every snapshot.
+    #[default]
+    Normal,
+    /// Also the iterations of every thread.
+    Verbose,
+}
+
+/// Where the snapshots go.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SinkKind {
+    /// Human-readable lines in the log, with the expected time between solutions and the luck reports.
+    Console,
+    /// One JSON object per snapshot in the log.
+    Json,
+    /// `GET /metrics` in the Prometheus text format.
+    Prometheus,
+    /// The last snapshot as JSON, replaced on every snapshot.
+    File,
+}
+
+impl SinkKind {
+    /// Parses a sink from its name.
+    ///
+    /// # Arguments
+    /// * `name` - `console`, `json`, `prometheus` or `file`.
+    ///
+    /// # Returns
+    /// The sink, or why the name is invalid.
+    pub fn parse(name: &str) -> Result<Self, String> {
+        match name.trim().to_lowercase().as_str() {
+            "console" => Ok(SinkKind::Console),
+            "json" => Ok(SinkKind::Json),
+            "prometheus" => Ok(SinkKind::Prometheus),
+            "file" => Ok(SinkKind::File),
+            name => Err(format!("unknown sink {name:?}, expected console, json, prometheus or file")),
+        }
+    }
+}
+
+/// Which sinks receive the snapshots, and how often.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StatsConfig {
+    interval: Duration,
+    sinks: Vec<SinkKind>,
+    prometheus_port: u16,
+    file: PathBuf,
+    verbosity: Verbosity,
+    /// Name of the rig, the `worker` label of the Prometheus metrics
+    worker_name: Option<String>,
+    /// Fingerprint of the settings, the `fingerprint` label of `qiner_config_info`
+    config_fingerprint: Option<String>,
+}
+
+impl Default for StatsConfig {
+    fn default() -> Self {
+        StatsConfig {
+            interval: DEFAULT_STATS_INTERVAL,
+            sinks: vec![SinkKind::Console],
+            prometheus_port: DEFAULT_STATS_PROMETHEUS_PORT,
+            file: PathBuf::from(DEFAULT_STATS_FILE),
+            verbosity: Verbosity::Normal,
+            worker_name: None,
+            config_fingerprint: None,
+        }
+    }
+}
+
+impl StatsConfig {
+    /// Creates a `StatsConfig` from the environment variables.
+    ///
+    /// # Returns
+    /// The configuration, the console alone every second by default. Unknown sinks are logged and skipped.
+    pub fn from_env() -> Self {
+        let mut config = StatsConfig::default();
+
+        if let Some(sinks) = env::var(ENV_STATS_SINKS).ok().filter(|sinks| !sinks.trim().is_empty()) {
+            config.sinks.clear();
+            for name in sinks.split(',').filter(|name| !name.trim().is_empty()) {
+                match SinkKind::parse(name) {
+                    Ok(sink) if !config.sinks.contains(&sink) => config.sinks.push(sink),
+                    Ok(_) => {}
+                    Err(err) => log::warn!("Ignoring invalid {ENV_STATS_SINKS} entry: {err}"),
+                }
+            }
+        }
+
+        if let Some(interval) = env::var(ENV_STATS_INTERVAL).ok().and_then(|value| value.trim().parse::<u64>().ok()).filter(|secs| *secs > 0) {
+            config.interval = Duration::from_secs(interval);
+        }
+        if let Some(port) = env::var(ENV_STATS_PROMETHEUS_PORT).ok().and_then(|value| value.trim().parse::<u16>().ok()) {
+            config.prometheus_port = port;
+        }
+        if let Some(file) = env::var(ENV_STATS_FILE).ok().filter(|file| !file.trim().is_empty()) {
+            config.file = PathBuf::from(file.trim());
+        }
+        config.worker_name = get_worker_name();
+        config
+    }
+
+    /// Gets the interval between two snapshots.
+    pub fn get_interval(&self) -> Duration {
+        self.interval
+    }
+
+    /// Sets the interval between two snapshots, e.g. from the command line.
+    ///
+    /// # Arguments
+    /// * `interval` - The new interval.
+    pub fn set_interval(&mut self, interval: Duration) {
+        self.interval = interval;
+    }
+
+    /// Gets how much of the progress the console logs.
+    pub fn get_verbosity(&self) -> Verbosity {
+        self.verbosity
+    }
+
+    /// Sets how much of the progress the console logs.
+    ///
+    /// # Arguments
+    /// * `verbosity` - The new verbosity.
+    pub fn set_verbosity(&mut self, verbosity: Verbosity) {
+        self.verbosity = verbosity;
+    }
+
+    /// Sets the fingerprint of the settings, exported with the Prometheus metrics.
+    ///
+    /// # Arguments
+    /// * `config_fingerprint` - The fingerprint, see `config_fingerprint`.
+    pub fn set_config_fingerprint(&mut self, config_fingerprint: String) {
+        self.config_fingerprint = Some(config_fingerprint);
+    }
+
+    /// Gets the sinks receiving the snapshots.
+    pub fn get_sinks(&self) -> &[SinkKind] {
+        &self.sinks
+    }
+
+    /// Creates the sinks, spawning the Prometheus endpoint if it is one of them.
+    pub fn create_sinks(&self) -> Vec<Box<dyn StatsSink>> {
+        self.sinks.iter().map(|sink| -> Box<dyn StatsSink> {
+            match sink {
+                SinkKind::Console => Box::new(ConsoleSink::new(self.verbosity)),
+                SinkKind::Json => Box::new(JsonLogSink),
+                SinkKind::Prometheus => {
+                    let sink = PrometheusSink::new(self.worker_name.clone(), self.config_fingerprint.clone());
+                    tokio::spawn(serve_prometheus(sink.get_exposition(), self.prometheus_port));
+                    Box::new(sink)
+                }
+                SinkKind::File => Box::new(FileSink::new(self.file.clone())),
+            }
+        }).collect()
+    }
+}
+
+/// Logs the progress of mining, and every 10 minutes how the solutions found compare with the expectation.
+#[derive(Debug, Default)]
+pub struct ConsoleSink {
+    verbosity: Verbosity,
+    last_luck_report: Duration,
+    last_solutions: (usize, u64),
+    last_uptime: Duration,
+    last_thread_iterations: Vec<usize>,
+}
+
+impl ConsoleSink {
+    /// Creates a new `ConsoleSink`.
+    ///
+    /// # Arguments
+    /// * `verbosity` - How much of the progress is logged.
+    pub fn new(verbosity: Verbosity) -> Self {
+        ConsoleSink { verbosity, ..Default::default() }
+    }
+
+    /// Logs the iterations of every thread, and their rate since the previous snapshot.
+    fn log_threads(&mut self, snapshot: &StatsSnapshot) {
+        let since_last = snapshot.uptime.saturating_sub(self.last_uptime).as_secs_f64().max(f64::EPSILON);
+        for (idx, iterations) in snapshot.thread_iterations.iter().enumerate() {
+            let previous = self.last_thread_iterations.get(idx).copied().unwrap_or_default();
+            log::info!("Thread #{idx}: {iterations} iterations ({:.0} it/s)", iterations.saturating_sub(previous) as f64 / since_last);
+        }
+        self.last_uptime = snapshot.uptime;
+        self.last_thread_iterations.clone_from(&snapshot.thread_iterations);
+    }
+}
+
+impl StatsSink for ConsoleSink {
+    fn record(&mut self, snapshot: &StatsSnapshot) {
+        // Quiet consoles only log when solutions are found or sent
+        let solutions = (snapshot.solutions_found, snapshot.solutions_sent);
+        let are_solutions_changed = solutions != self.last_solutions;
+        self.last_solutions = solutions;
+        if self.verbosity == Verbosity::Quiet && !are_solutions_changed {
+            return;
+        }
+
+        let eta = expected_time_between_solutions(snapshot.solution_threshold, snapshot.average_it_per_sec)
+            .map(format_duration)
+            .unwrap_or_else(|| "-".to_string());
+        log::info!(
+            "{} scores | sent scores {} | {:.0} it/s | expected 1 solution every {}",
+            snapshot.solutions_found,
+            snapshot.solutions_sent,
+            snapshot.it_per_sec,
+            eta
+        );
+        if let Some(shares_found) = snapshot.shares_found {
+            log::info!("{} shares | sent shares {}", shares_found, snapshot.shares_sent);
+        }
+        if self.verbosity == Verbosity::Quiet {
+            return;
+        }
+        if snapshot.worker_restarts > 0 {
+            log::info!("{} worker restarts", snapshot.worker_restarts);
+        }
+        if let Some(energy) = snapshot.energy {
+            log::info!(
+                "{:.1} W | {:.0} it/J | {} per solution",
+                energy.watts,
+                energy.it_per_joule(snapshot.iterations),
+                energy.joules_per_solution(snapshot.solutions_found).map_or("-".to_string(), |joules| format!("{joules:.0} J"))
+            );
+        }
+        if self.verbosity == Verbosity::Verbose {
+            self.log_threads(snapshot);
+        }
+
+        // Periodically compare found solutions with the expectation, to tell bad luck from misconfiguration
+        if snapshot.uptime.saturating_sub(self.last_luck_report) >= LUCK_REPORT_INTERVAL {
+            self.last_luck_report = snapshot.uptime;
+
+            let expected = expected_solutions(snapshot.solution_threshold, snapshot.iterations);
+            let probability = probability_of_at_most(snapshot.solutions_found, expected);
+            log::info!(
+                "{} scores in {}, {:.2} expected (probability of finding this few: {:.1}%)",
+                snapshot.solutions_found,
+                format_duration(snapshot.uptime),
+                expected,
+                probability * 100.0
+            );
+            if probability < UNLIKELY_LUCK_PROBABILITY {
+                log::warn!("Finding this few solutions is very unlikely, check the threshold, seed and version configuration");
+            }
+
+            // Nonces close to the threshold are frequent enough to check the score distribution early
+            let near_misses = snapshot.near_misses.iter()
+                .map(|near_miss| format!("{}+: {} ({:.1} expected)", near_miss.score, near_miss.count, near_miss.expected))
+                .collect::<Vec<_>>();
+            if !near_misses.is_empty() {
+                log::info!("Near misses: {}", near_misses.join(" | "));
+            }
+        }
+
+        let network = &snapshot.network;
+        if network.connect_attempts > 0 {
+            log::info!(
+                "net: {} packets ({} Bytes) sent | {} connects ({} failed) | found->sent avg {} ms, max {} ms | node protocol {} ({} mismatches)",
+                network.packets_sent,
+                network.bytes_sent,
+                network.connect_attempts,
+                network.connect_failures,
+                network.average_latency.as_millis(),
+                network.max_latency.as_millis(),
+                network.node_protocol.map_or("unknown".to_string(), |protocol| protocol.to_string()),
+                network.protocol_mismatches
+            );
+        }
+    }
+}
+
+/// Logs every snapshot as one JSON object, for log collectors.
+#[derive(Debug, Default)]
+pub struct JsonLogSink;
+
+impl StatsSink for JsonLogSink {
+    fn record(&mut self, snapshot: &StatsSnapshot) {
+        log::info!("{}", snapshot.to_json());
+    }
+}
+
+/// Keeps the Prometheus exposition of the last snapshot, served by `serve_prometheus`.
+#[derive(Debug, Default)]
+pub struct PrometheusSink {
+    exposition: Arc<Mutex<String>>,
+    worker_name: Option<String>,
+    config_fingerprint: Option<String>,
+}
+
+impl PrometheusSink {
+    /// Creates a new `PrometheusSink`.
+    ///
+    /// # Arguments
+    /// * `worker_name` - The name of the rig, the `worker` label of every metric, `None` for no label.
+    /// * `config_fingerprint` - The fingerprint of the settings, `None` to leave `qiner_config_info` out.
+    pub fn new(worker_name: Option<String>, config_fingerprint: Option<String>) -> Self {
+        PrometheusSink { worker_name, config_fingerprint, ..Default::default() }
+    }
+
+    /// Gets the exposition shared with the endpoint.
+    pub fn get_exposition(&self) -> Arc<Mutex<String>> {
+        self.exposition.clone()
+    }
+}
+
+impl StatsSink for PrometheusSink {
+    fn record(&mut self, snapshot: &StatsSnapshot) {
+        *self.exposition.lock().unwrap() = snapshot.to_prometheus(self.worker_name.as_deref(), self.config_fingerprint.as_deref());
+    }
+}
+
+/// Writes the last snapshot as JSON to a file, for scripts polling the rig.
+///
+/// The snapshot is written next to the file then renamed over it, so readers never see a partial one.
+#[derive(Debug)]
+pub struct FileSink {
+    file: PathBuf,
+    is_failing: bool,
+}
+
+impl FileSink {
+    /// Creates a new `FileSink`.
+    ///
+    /// # Arguments
+    /// * `file` - The file replaced with every snapshot.
+    pub fn new(file: PathBuf) -> Self {
+        FileSink { file, is_failing: false }
+    }
+}
+
+impl StatsSink for FileSink {
+    fn record(&mut self, snapshot: &StatsSnapshot) {
+        let mut temporary = self.file.clone().into_os_string();
+        temporary.push(".tmp");
+        let result = std::fs::write(&temporary, snapshot.to_json().to_string()).and_then(|_| std::fs::rename(&temporary, &self.file));
+
+        // Failures are logged once until the file is writable again
+        match result {
+            Ok(_) => self.is_failing = false,
+            Err(err) => {
+                if !self.is_failing {
+                    log::warn!("Failed to write the stats to {}: {:?}", self.file.display(), err);
+                }
+                self.is_failing = true;
+            }
+        }
+    }
+}
+
+/// Notifies once when the hashrate drops below the minimum, then again only after it recovered.
+#[derive(Debug)]
+pub struct HashrateAlertSink {
+    notifier: Notifier,
+    is_hashrate_low: bool,
+}
+
+impl HashrateAlertSink {
+    /// Creates a new `HashrateAlertSink`.
+    ///
+    /// # Arguments
+    /// * `notifier` - The notifier, whose minimum hashrate is checked.
+    ///
+    /// # Returns
+    /// The sink, or `None` if the notifier has no minimum hashrate.
+    pub fn new(notifier: Notifier) -> Option<Self> {
+        (notifier.get_min_hashrate() > 0).then_some(HashrateAlertSink { notifier, is_hashrate_low: false })
+    }
+}
+
+impl StatsSink for HashrateAlertSink {
+    fn record(&mut self, snapshot: &StatsSnapshot) {
+        let it_per_sec = snapshot.it_per_sec as usize;
+        let is_below = it_per_sec < self.notifier.get_min_hashrate();
+        if is_below && !self.is_hashrate_low {
+            self.notifier.notify(NotifyEvent::HashrateDropped(it_per_sec));
+        }
+        self.is_hashrate_low = is_below;
+    }
+}
+
+/// Samples the counters of the miner into snapshots.
+pub struct StatsSampler {
+    miner: Arc<Miner>,
+    submission_stats: Arc<SubmissionStats>,
+    shares_sent: Arc<AtomicUsize>,
+    net_stats: Arc<NetStats>,
+    started_at: Instant,
+    marker: IterationMarker,
+    /// The RAPL counters, `None` if unreadable
+    energy: Option<EnergyMeter>,
+}
+
+impl StatsSampler {
+    /// Creates a new `StatsSampler`, mining starting now, measuring the energy if RAPL counters are readable.
+    ///
+    /// # Arguments
+    /// * `miner` - Shared reference to the Miner instance.
+    /// * `submission_stats` - Shared totals of the submitted solutions of the epoch.
+    /// * `shares_sent` - Shared counter for sent shares.
+    /// * `net_stats` - Shared network counters.
+    pub fn new(miner: Arc<Miner>, submission_stats: Arc<SubmissionStats>, shares_sent: Arc<AtomicUsize>, net_stats: Arc<NetStats>) -> Self {
+        StatsSampler {
+            miner,
+            submission_stats,
+            shares_sent,
+            net_stats,
+            started_at: Instant::now(),
+            marker: IterationMarker::new(),
+            energy: EnergyMeter::open(),
+        }
+    }
+
+    /// Samples the counters.
+    ///
+    /// # Returns
+    /// The snapshot, its iteration rate measured since the previous one.
+    pub async fn sample(&mut self) -> StatsSnapshot {
+        let miner = self.miner.snapshot();
+        let iterations = miner.iterations;
+        let it_per_sec = miner.iterations_since(&mut self.marker).it_per_sec();
+
+        let uptime = self.started_at.elapsed();
+        let solution_threshold = miner.solution_threshold;
+        StatsSnapshot {
+            uptime,
+            iterations,
+            it_per_sec,
+            average_it_per_sec: iterations as f64 / uptime.as_secs_f64().max(f64::EPSILON),
+            solution_threshold,
+            solutions_found: miner.solutions_found,
+            solutions_sent: self.submission_stats.get_sent(),
+            answered_connections: self.submission_stats.get_answered_connections(),
+            solutions_failed: self.submission_stats.get_failed(),
+            solutions_expired: self.submission_stats.get_expired(),
+            shares_found: miner.share_threshold.map(|_| miner.shares_found),
+            shares_sent: self.shares_sent.load(Ordering::Relaxed),
+            worker_restarts: miner.worker_restarts,
+            near_misses: near_misses(&miner.score_counts, solution_threshold),
+            thread_iterations: miner.thread_iterations,
+            epoch: miner.epoch,
+            energy: self.energy.as_mut().map(EnergyMeter::read),
+            network: NetSnapshot {
+                packets_sent: self.net_stats.get_packets_sent(),
+                bytes_sent: self.net_stats.get_bytes_sent(),
+                connect_attempts: self.net_stats.get_connect_attempts(),
+                connect_failures: self.net_stats.get_connect_failures(),
+                average_latency: self.net_stats.get_average_latency(),
+                max_latency: self.net_stats.get_max_latency(),
+                average_packet_build: self.net_stats.get_average_packet_build_time(),
+                node_protocol: self.net_stats.get_node_protocol(),
+                protocol_mismatches: self.net_stats.get_protocol_mismatches(),
+                bytes_last_hour: self.net_stats.get_bandwidth().get_bytes_last_hour(),
+                deferred_sends: self.net_stats.get_bandwidth().get_deferred(),
+                clock_skew_ms: self.net_stats.get_clock().get_skew_ms(),
+            },
+        }
+    }
+}
+
+/// Asynchronous task sampling the miner and handing every snapshot to the sinks.
+///
+/// # Arguments
+/// * `sampler` - The sampler of the counters.
+/// * `interval` - The interval between two snapshots.
+/// * `sinks` - The sinks receiving the snapshots.
+pub async fn stats_task(mut sampler: StatsSampler, interval: Duration, mut sinks: Vec<Box<dyn StatsSink>>) {
+    loop {
+        let snapshot = sampler.sample().await;
+        for sink in sinks.iter_mut() {
+            sink.record(&snapshot);
+        }
+
+        tokio::time::sleep(interval).await;
+    }
+}
+
+/// Asynchronous task serving `GET /metrics` over plain HTTP.
+///
+/// # Arguments
+/// * `exposition` - The exposition of the last snapshot, see `PrometheusSink`.
+/// * `port` - The port to listen on, on all interfaces.
+pub async fn serve_prometheus(exposition: Arc<Mutex<String>>, port: u16) {
+    let listener = match TcpListener::bind(("0.0.0.0", port)).await {
+        Ok(listener) => listener,
+        Err(err) => {
+            log::error!("Failed to listen for Prometheus scrapes on port {port}: {:?}", err);
+            return;
+        }
+    };
+    log::info!("Prometheus endpoint listening on http://0.0.0.0:{port}/metrics");
+
+    loop {
+        match listener.accept().await {
+            Ok((stream, _)) => {
+                tokio::spawn(answer_scrape(exposition.clone(), stream));
+            }
+            Err(err) => log::warn!("Failed to accept a Prometheus scrape: {:?}", err),
+        }
+    }
+}
+
+/// Answers a single Prometheus scrape.
+///
+/// # Arguments
+/// * `exposition` - The exposition of the last snapshot.
+/// * `stream` - The connection of the client.
+async fn answer_scrape(exposition: Arc<Mutex<String>>, mut stream: TcpStream) {
+    // Only the request line matters
+    let mut buffer = [0u8; 1024];
+    let read = match stream.read(&mut buffer).await {
+        Ok(read) => read,
+        Err(_) => return,
+    };
+    let request = String::from_utf8_lossy(&buffer[..read]);
+    let path = request.split_whitespace().nth(1).unwrap_or_default();
+
+    let (status, body) = if path == "/metrics" {
+        ("200 OK", exposition.lock().unwrap().clone())
+    } else {
+        ("404 Not Found", String::new())
+    };
+
+    let response = format!(
+        "HTTP/1.1 {status}\r\nContent-Type: text/plain; version=0.0.4\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{body}",
+        body.len()
+    );
+    if let Err(err) = stream.write_all(response.as_bytes()).await {
+        log::debug!("Failed to answer a Prometheus scrape: {:?}", err);
+    }
+}
+
+#[test]
+/// Tests the parsing of the sinks and the JSON, Prometheus and file forms of a snapshot.
+fn test_stats_sinks() {
+    assert_eq!(SinkKind::parse(" Prometheus"), Ok(SinkKind::Prometheus));
+    assert!(SinkKind::parse("graphite").is_err());
+
+    let snapshot = StatsSnapshot {
+        uptime: Duration::from_secs(60),
+        iterations: 6000,
+        it_per_sec: 100.0,
+        solutions_found: 2,
+        network: NetSnapshot { average_latency: Duration::from_millis(15), ..Default::default() },
+        ..Default::default()
+    };
+
+    let json = snapshot.to_json();
+    assert_eq!(json["iterations"], 6000);
+    assert_eq!(json["shares_found"], Value::Null);
+    assert_eq!(json["network"]["average_latency_ms"], 15);
+
+    let exposition = snapshot.to_prometheus(None, None);
+    assert!(exposition.contains("# TYPE qiner_iterations_total counter\nqiner_iterations_total 6000\n"));
+    assert!(exposition.contains("qiner_it_per_sec 100\n"));
+    assert!(!exposition.contains("shares"));
+    assert!(!exposition.contains("watts"));
+    let with_energy = StatsSnapshot { energy: Some(EnergyReading { joules: 120.0, watts: 2.0 }), ..snapshot.clone() };
+    assert_eq!(with_energy.to_json()["energy"]["joules_per_solution"], 60.0);
+    assert!(with_energy.to_prometheus(None, None).contains("qiner_it_per_joule 50\n"));
+    assert!(StatsSnapshot { shares_found: Some(3), ..snapshot.clone() }.to_prometheus(None, None).contains("qiner_shares_found_total 3\n"));
+    assert!(snapshot.to_prometheus(Some("rig \"7\""), None).contains("qiner_iterations_total{worker=\"rig \\\"7\\\"\"} 6000\n"));
+    assert!(!exposition.contains("qiner_config_info"));
+    assert!(snapshot.to_prometheus(Some("rig7"), Some("0123abcd")).ends_with("qiner_config_info{worker=\"rig7\",fingerprint=\"0123abcd\"} 1\n"));
+    assert!(snapshot.to_prometheus(None, Some("0123abcd")).contains("qiner_config_info{fingerprint=\"0123abcd\"} 1\n"));
+
+    let mut prometheus = PrometheusSink::default();
+    prometheus.record(&snapshot);
+    assert_eq!(*prometheus.get_exposition().lock().unwrap(), exposition);
+
+    let file = env::temp_dir().join(format!("qiner-stats-{}.json", std::process::id()));
+    FileSink::new(file.clone()).record(&snapshot);
+    assert_eq!(serde_json::from_str::<Value>(&std::fs::read_to_string(&file).unwrap()).unwrap(), json);
+    std::fs::remove_file(file).unwrap();
+}
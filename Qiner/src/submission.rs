@@ -0,0 +1,693 @@
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::time::{Duration, Instant};
+use serde_json::{json, Value};
+use lib::types::{Nonce64, Seed};
+use crate::miner::Solution;
+
+/// Delay before the first retry of a failed submission.
+pub const RETRY_BASE_DELAY: Duration = Duration::from_secs(1);
+
+/// Upper bound for the delay between two retries of the same submission.
+pub const RETRY_MAX_DELAY: Duration = Duration::from_secs(60);
+
+/// Default age after which a pending solution is dropped instead of retried.
+pub const DEFAULT_SOLUTION_MAX_AGE: Duration = Duration::from_secs(24 * 60 * 60);
+
+/// Default maximum number of solutions waiting in memory, the others waiting in the spill file.
+pub const DEFAULT_SOLUTION_MAX_IN_MEMORY: usize = 10_000;
+
+/// Default minimum delay between two connections to the node.
+pub const DEFAULT_MIN_CONNECT_INTERVAL: Duration = Duration::from_secs(2);
+
+/// File the submission totals are persisted to, in the state directory.
+pub const SUBMISSION_STATS_FILE: &str = "qiner-submissions.json";
+
+/// Interval between two saves of the submission totals.
+const SUBMISSION_STATS_SAVE_INTERVAL: Duration = Duration::from_secs(30);
+
+/// Limits on how fast solutions are submitted, so node operator rate expectations are met.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SubmissionPacing {
+    /// Maximum number of packets sent over one connection, `None` for no limit
+    pub max_packets_per_connection: Option<usize>,
+    /// Maximum number of packets sent per second, `None` for no limit
+    pub max_packets_per_second: Option<usize>,
+    /// Minimum delay between two connections
+    pub min_connect_interval: Duration,
+    /// Maximum number of connections opened to the node at once, each with its own packet limits
+    pub connections_per_node: usize,
+}
+
+impl Default for SubmissionPacing {
+    fn default() -> Self {
+        SubmissionPacing {
+            max_packets_per_connection: None,
+            max_packets_per_second: None,
+            min_connect_interval: DEFAULT_MIN_CONNECT_INTERVAL,
+            connections_per_node: 1,
+        }
+    }
+}
+
+impl SubmissionPacing {
+    /// Splits the packets of one connection into the batches sent one second apart.
+    ///
+    /// # Arguments
+    /// * `packets` - The number of packets sent over the connection.
+    ///
+    /// # Returns
+    /// The size of the batches, which add up to `packets`.
+    pub fn batches(&self, packets: usize) -> Vec<usize> {
+        let batch_size = self.max_packets_per_second.unwrap_or(packets).max(1);
+        (0..packets).step_by(batch_size).map(|start| batch_size.min(packets - start)).collect()
+    }
+
+    /// Gets the number of packets taken from the queue for one round of connections.
+    ///
+    /// # Returns
+    /// The packets per connection times the connections to the node, `usize::MAX` without a limit.
+    pub fn packets_per_round(&self) -> usize {
+        self.max_packets_per_connection.map_or(usize::MAX, |limit| limit.saturating_mul(self.connections_per_node.max(1)))
+    }
+
+    /// Splits the packets of a round across the connections to the node.
+    ///
+    /// A connection is only added when the packets would not fit in one batch of the others,
+    /// so a node gets a single connection unless a backlog is being flushed.
+    ///
+    /// # Arguments
+    /// * `packets` - The number of packets of the round.
+    ///
+    /// # Returns
+    /// The number of packets of each connection, which add up to `packets` and differ by one at most.
+    pub fn shares(&self, packets: usize) -> Vec<usize> {
+        let batch_size = self.max_packets_per_connection.unwrap_or(usize::MAX).min(self.max_packets_per_second.unwrap_or(usize::MAX)).max(1);
+        let connections = packets.div_ceil(batch_size).clamp(1, self.connections_per_node.max(1)).min(packets);
+        (0..connections).map(|idx| packets / connections + usize::from(idx < packets % connections)).collect()
+    }
+}
+
+/// What became of the packets of one connection to the node.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct ConnectionOutcome {
+    /// Whether the connection was established
+    pub is_connected: bool,
+    /// Number of packets written
+    pub written: usize,
+    /// Whether the node answered after the packets were written, which says nothing about the solutions
+    pub is_answered: bool,
+}
+
+impl ConnectionOutcome {
+    /// Adds up the outcomes of the connections of a round.
+    ///
+    /// # Arguments
+    /// * `outcomes` - The outcome of each connection.
+    ///
+    /// # Returns
+    /// The number of established connections, the packets written over all of them and the number of
+    /// connections the node answered.
+    pub fn aggregate(outcomes: &[ConnectionOutcome]) -> (usize, usize, usize) {
+        outcomes.iter().fold((0, 0, 0), |(connected, written, answered), outcome| {
+            (connected + usize::from(outcome.is_connected), written + outcome.written, answered + usize::from(outcome.is_answered))
+        })
+    }
+}
+
+/// A solution waiting to be submitted, with its retry bookkeeping.
+#[derive(Debug, Clone, Copy)]
+pub struct PendingSolution {
+    pub solution: Solution,
+    /// Number of failed submission attempts so far
+    pub attempts: u32,
+    /// Earliest moment the next attempt may happen
+    pub next_attempt_at: Instant,
+}
+
+/// Queue of solutions waiting to be submitted.
+///
+/// Every solution is retried with its own exponential backoff, and solutions older than
+/// the configured maximum age are pruned so stale work from a previous epoch is not sent forever.
+/// Once the nodes report the epoch, solutions are tagged with the epoch of the random seed and
+/// dropped as soon as the nodes move past it.
+/// Submitted nonces are remembered for the same maximum age, so a duplicate is dropped
+/// instead of being broadcast again.
+#[derive(Debug)]
+pub struct RetryQueue {
+    pending: Vec<PendingSolution>,
+    /// Nonces of the pending solutions, so duplicates are found without scanning the queue
+    queued: HashSet<Nonce64>,
+    /// Nonces already submitted, with the moment they were found
+    submitted: HashMap<Nonce64, Instant>,
+    max_age: Duration,
+    /// Epoch of the random seed, the first one the nodes reported
+    seed_epoch: Option<u16>,
+    /// Current epoch, the last one the nodes reported
+    epoch: Option<u16>,
+    /// Number of pending solutions beyond which the newest ones are spilled to disk, `None` for no cap
+    max_in_memory: Option<usize>,
+}
+
+impl RetryQueue {
+    /// Creates a new, empty `RetryQueue`.
+    ///
+    /// # Arguments
+    /// * `max_age` - Age after which a pending solution is dropped.
+    ///
+    /// # Returns
+    /// A new `RetryQueue`.
+    pub fn new(max_age: Duration) -> Self {
+        RetryQueue {
+            pending: Vec::new(),
+            queued: HashSet::new(),
+            submitted: HashMap::new(),
+            max_age,
+            seed_epoch: None,
+            epoch: None,
+            max_in_memory: None,
+        }
+    }
+
+    /// Gets the age after which a pending solution is dropped.
+    pub fn get_max_age(&self) -> Duration {
+        self.max_age
+    }
+
+    /// Gets the number of pending solutions beyond which the newest ones are spilled to disk.
+    pub fn get_max_in_memory(&self) -> Option<usize> {
+        self.max_in_memory
+    }
+
+    /// Sets the number of pending solutions beyond which the newest ones are spilled to disk.
+    ///
+    /// # Arguments
+    /// * `max_in_memory` - The cap, `None` to keep every solution in memory.
+    pub fn set_max_in_memory(&mut self, max_in_memory: Option<usize>) {
+        self.max_in_memory = max_in_memory;
+    }
+
+    /// Gets the number of solutions the queue takes before reaching its cap.
+    pub fn get_room(&self) -> usize {
+        self.max_in_memory.map_or(usize::MAX, |max| max.saturating_sub(self.pending.len()))
+    }
+
+    /// Removes the solutions beyond the cap, the oldest ones staying since they expire first.
+    ///
+    /// # Returns
+    /// The most recently found solutions over the cap, to be spilled to disk.
+    pub fn take_overflow(&mut self) -> Vec<Solution> {
+        let Some(max) = self.max_in_memory.filter(|max| self.pending.len() > *max) else {
+            return Vec::new();
+        };
+        self.pending.sort_by_key(|pending| pending.solution.found_at);
+        let overflow = self.pending.split_off(max);
+        overflow.iter().for_each(|pending| { self.queued.remove(&pending.solution.nonce); });
+        overflow.into_iter().map(|pending| pending.solution).collect()
+    }
+
+    /// Gets the epoch of the random seed, the first one the nodes reported.
+    pub fn get_seed_epoch(&self) -> Option<u16> {
+        self.seed_epoch
+    }
+
+    /// Records the epoch the nodes report, the first one being the epoch of the random seed.
+    ///
+    /// The solutions pending without an epoch are tagged with the epoch of the random seed.
+    ///
+    /// # Arguments
+    /// * `epoch` - The epoch of the last tick info.
+    ///
+    /// # Returns
+    /// The previously reported epoch, `None` if none was reported before.
+    pub fn set_epoch(&mut self, epoch: u16) -> Option<u16> {
+        if self.seed_epoch.is_none() {
+            self.seed_epoch = Some(epoch);
+            self.pending.iter_mut().for_each(|pending| {
+                pending.solution.epoch.get_or_insert(epoch);
+            });
+        }
+        self.epoch.replace(epoch)
+    }
+
+    /// Gets the nonces of all the solutions waiting to be submitted.
+    pub fn nonces(&self) -> impl Iterator<Item = Nonce64> + '_ {
+        self.pending.iter().map(|pending| pending.solution.nonce)
+    }
+
+    /// Gets the number of solutions waiting to be submitted.
+    pub fn len(&self) -> usize {
+        self.pending.len()
+    }
+
+    /// Checks if no solution is waiting to be submitted.
+    pub fn is_empty(&self) -> bool {
+        self.pending.is_empty()
+    }
+
+    /// Adds freshly found solutions, ready to be submitted right away.
+    ///
+    /// Solutions already pending or already submitted are dropped.
+    ///
+    /// # Arguments
+    /// * `solutions` - The solutions to add.
+    ///
+    /// # Returns
+    /// The number of dropped duplicates.
+    pub fn push_new(&mut self, solutions: impl IntoIterator<Item = Solution>) -> usize {
+        let mut duplicates = 0;
+        for solution in solutions {
+            if self.submitted.contains_key(&solution.nonce) || !self.queued.insert(solution.nonce) {
+                duplicates += 1;
+                continue;
+            }
+            let mut solution = solution;
+            solution.epoch = solution.epoch.or(self.seed_epoch);
+            self.pending.push(PendingSolution {
+                solution,
+                attempts: 0,
+                next_attempt_at: solution.found_at,
+            });
+        }
+        duplicates
+    }
+
+    /// Remembers solutions as submitted, so they are not submitted again.
+    ///
+    /// # Arguments
+    /// * `submitted` - The solutions that were submitted.
+    pub fn mark_submitted<'a>(&mut self, submitted: impl IntoIterator<Item = &'a PendingSolution>) {
+        self.submitted.extend(submitted.into_iter().map(|pending| (pending.solution.nonce, pending.solution.found_at)));
+    }
+
+    /// Removes the solutions whose next attempt is due.
+    ///
+    /// # Arguments
+    /// * `now` - The current time.
+    ///
+    /// # Returns
+    /// The solutions to submit now.
+    pub fn take_ready(&mut self, now: Instant) -> Vec<PendingSolution> {
+        self.take_ready_at_most(now, usize::MAX)
+    }
+
+    /// Removes the oldest solutions whose next attempt is due, up to a limit.
+    ///
+    /// # Arguments
+    /// * `now` - The current time.
+    /// * `limit` - The maximum number of solutions to take.
+    ///
+    /// # Returns
+    /// The solutions to submit now, the others staying queued.
+    pub fn take_ready_at_most(&mut self, now: Instant, limit: usize) -> Vec<PendingSolution> {
+        let mut ready = Vec::new();
+        let queued = &mut self.queued;
+        self.pending.retain(|pending| {
+            let is_taken = ready.len() < limit && pending.next_attempt_at <= now;
+            if is_taken {
+                queued.remove(&pending.solution.nonce);
+                ready.push(*pending);
+            }
+            !is_taken
+        });
+        ready
+    }
+
+    /// Puts solutions back into the queue after a failed attempt, delaying their next attempt.
+    ///
+    /// # Arguments
+    /// * `failed` - The solutions whose submission failed.
+    /// * `now` - The current time.
+    pub fn reschedule(&mut self, failed: impl IntoIterator<Item = PendingSolution>, now: Instant) {
+        let queued = &mut self.queued;
+        self.pending.extend(failed.into_iter().filter(|pending| queued.insert(pending.solution.nonce)).map(|mut pending| {
+            pending.attempts += 1;
+            pending.next_attempt_at = now + backoff_delay(pending.attempts);
+            pending
+        }));
+    }
+
+    /// Drops the solutions older than the maximum age, and forgets the submitted ones as old.
+    ///
+    /// # Arguments
+    /// * `now` - The current time.
+    ///
+    /// # Returns
+    /// The number of dropped pending solutions.
+    pub fn prune_expired(&mut self, now: Instant) -> usize {
+        let before = self.pending.len();
+        let max_age = self.max_age;
+        let queued = &mut self.queued;
+        self.pending.retain(|pending| {
+            let is_kept = now.saturating_duration_since(pending.solution.found_at) < max_age;
+            if !is_kept {
+                queued.remove(&pending.solution.nonce);
+            }
+            is_kept
+        });
+        self.submitted.retain(|_, found_at| now.saturating_duration_since(*found_at) < max_age);
+        before - self.pending.len()
+    }
+
+    /// Drops the solutions mined against the random seed of a past epoch.
+    ///
+    /// # Returns
+    /// The number of dropped pending solutions.
+    pub fn prune_past_epochs(&mut self) -> usize {
+        let Some(epoch) = self.epoch else {
+            return 0;
+        };
+        let before = self.pending.len();
+        let queued = &mut self.queued;
+        self.pending.retain(|pending| {
+            let is_kept = pending.solution.epoch.is_none_or(|mined_epoch| mined_epoch >= epoch);
+            if !is_kept {
+                queued.remove(&pending.solution.nonce);
+            }
+            is_kept
+        });
+        before - self.pending.len()
+    }
+}
+
+/// Computes the delay before the next attempt of a submission.
+///
+/// # Arguments
+/// * `attempts` - Number of failed attempts so far.
+///
+/// # Returns
+/// The delay, doubling with every attempt and capped at `RETRY_MAX_DELAY`.
+pub fn backoff_delay(attempts: u32) -> Duration {
+    let exponent = attempts.saturating_sub(1).min(16);
+    RETRY_BASE_DELAY.saturating_mul(1 << exponent).min(RETRY_MAX_DELAY)
+}
+
+/// Totals of the solutions going through submission, kept across restarts within an epoch.
+///
+/// The totals are saved periodically and on exit, and restored on start as long as the random
+/// seed did not change, so restarting the process does not zero the totals of the epoch.
+#[derive(Debug, Default)]
+pub struct SubmissionStats {
+    found: AtomicU64,
+    sent: AtomicU64,
+    answered_connections: AtomicU64,
+    failed: AtomicU64,
+    expired: AtomicU64,
+    /// Hex of the random seed of the epoch the totals belong to
+    epoch: String,
+    file: Option<PathBuf>,
+    is_changed: AtomicBool,
+}
+
+impl SubmissionStats {
+    /// Creates the `SubmissionStats` of an epoch, restoring the persisted totals of the same epoch.
+    ///
+    /// # Arguments
+    /// * `random_seed` - The random seed identifying the epoch.
+    /// * `file` - The file the totals are persisted to, `None` to keep them in memory.
+    ///
+    /// # Returns
+    /// The totals, zero if the file does not exist, is invalid or belongs to another epoch.
+    pub fn new(random_seed: &Seed, file: Option<PathBuf>) -> Self {
+        let epoch = random_seed.iter().map(|byte| format!("{byte:02x}")).collect::<String>();
+        let stats = SubmissionStats { epoch, file, ..Default::default() };
+
+        let Some(saved) = stats.file.as_deref().and_then(load_submission_stats) else {
+            return stats;
+        };
+        if saved["epoch"].as_str() == Some(stats.epoch.as_str()) {
+            for (name, total) in [("found", &stats.found), ("sent", &stats.sent), ("answered_connections", &stats.answered_connections), ("failed", &stats.failed), ("expired", &stats.expired)] {
+                total.store(saved[name].as_u64().unwrap_or_default(), Ordering::Relaxed);
+            }
+        }
+        stats
+    }
+
+    /// Records solutions moved from the miner to submission.
+    pub fn record_found(&self, solutions: usize) {
+        self.add(&self.found, solutions);
+    }
+
+    /// Records solutions written to a node.
+    pub fn record_sent(&self, solutions: usize) {
+        self.add(&self.sent, solutions);
+    }
+
+    /// Records a connection the node answered after the solutions were written, nodes never
+    /// acknowledge the solutions themselves.
+    pub fn record_answered_connection(&self) {
+        self.add(&self.answered_connections, 1);
+    }
+
+    /// Records solutions dropped without being written, once older than the maximum age.
+    pub fn record_failed(&self, solutions: usize) {
+        self.add(&self.failed, solutions);
+    }
+
+    /// Records solutions dropped without being written, once the epoch they were mined in passed.
+    pub fn record_expired(&self, solutions: usize) {
+        self.add(&self.expired, solutions);
+    }
+
+    /// Adds solutions to a total.
+    fn add(&self, total: &AtomicU64, solutions: usize) {
+        if solutions > 0 {
+            total.fetch_add(solutions as u64, Ordering::Relaxed);
+            self.is_changed.store(true, Ordering::Relaxed);
+        }
+    }
+
+    /// Gets the number of solutions found in the epoch.
+    pub fn get_found(&self) -> u64 {
+        self.found.load(Ordering::Relaxed)
+    }
+
+    /// Gets the number of solutions sent in the epoch.
+    pub fn get_sent(&self) -> u64 {
+        self.sent.load(Ordering::Relaxed)
+    }
+
+    /// Gets the number of connections of the epoch the node answered after the solutions were written.
+    pub fn get_answered_connections(&self) -> u64 {
+        self.answered_connections.load(Ordering::Relaxed)
+    }
+
+    /// Gets the number of solutions dropped unsent in the epoch.
+    pub fn get_failed(&self) -> u64 {
+        self.failed.load(Ordering::Relaxed)
+    }
+
+    /// Gets the number of solutions dropped unsent in the epoch because the epoch they were mined in passed.
+    pub fn get_expired(&self) -> u64 {
+        self.expired.load(Ordering::Relaxed)
+    }
+
+    /// Persists the totals if they changed since the last save, failures are only logged.
+    ///
+    /// The totals are written next to the file then renamed over it, so a crash never leaves a partial file.
+    pub fn save(&self) {
+        let Some(file) = &self.file else {
+            return;
+        };
+        if !self.is_changed.swap(false, Ordering::Relaxed) {
+            return;
+        }
+
+        let totals = json!({
+            "epoch": self.epoch,
+            "found": self.get_found(),
+            "sent": self.get_sent(),
+            "answered_connections": self.get_answered_connections(),
+            "failed": self.get_failed(),
+            "expired": self.get_expired(),
+        });
+        let mut temporary = file.clone().into_os_string();
+        temporary.push(".tmp");
+        if let Err(err) = fs::write(&temporary, totals.to_string()).and_then(|_| fs::rename(&temporary, file)) {
+            log::warn!("Failed to save the submission totals to {}: {:?}", file.display(), err);
+            self.is_changed.store(true, Ordering::Relaxed);
+        }
+    }
+}
+
+/// Loads the persisted submission totals.
+///
+/// # Arguments
+/// * `path` - The file the totals are persisted to.
+///
+/// # Returns
+/// The totals, `None` if the file does not exist or is invalid.
+fn load_submission_stats(path: &Path) -> Option<Value> {
+    let content = match fs::read_to_string(path) {
+        Ok(content) => content,
+        Err(err) if err.kind() == io::ErrorKind::NotFound => return None,
+        Err(err) => {
+            log::warn!("Failed to load the submission totals from {}: {:?}", path.display(), err);
+            return None;
+        }
+    };
+    serde_json::from_str::<Value>(&content).ok().filter(Value::is_object)
+}
+
+/// Asynchronous task saving the submission totals periodically.
+///
+/// # Arguments
+/// * `stats` - The submission totals.
+pub async fn save_submission_stats_task(stats: Arc<SubmissionStats>) {
+    loop {
+        tokio::time::sleep(SUBMISSION_STATS_SAVE_INTERVAL).await;
+        stats.save();
+    }
+}
+
+#[test]
+/// Tests that the backoff delay doubles with every attempt and stays capped.
+fn test_backoff_delay() {
+    assert_eq!(backoff_delay(1), Duration::from_secs(1));
+    assert_eq!(backoff_delay(2), Duration::from_secs(2));
+    assert_eq!(backoff_delay(3), Duration::from_secs(4));
+    assert_eq!(backoff_delay(7), RETRY_MAX_DELAY);
+    assert_eq!(backoff_delay(u32::MAX), RETRY_MAX_DELAY);
+}
+
+#[test]
+/// Tests that failed solutions wait for their backoff and expired ones are pruned.
+fn test_retry_queue() {
+    let mut queue = RetryQueue::new(Duration::from_secs(10));
+    let solution = Solution::new(Default::default());
+    let now = solution.found_at;
+    queue.push_new([solution]);
+
+    let ready = queue.take_ready(now);
+    assert_eq!(ready.len(), 1);
+    assert!(queue.is_empty());
+
+    queue.reschedule(ready, now);
+    assert!(queue.take_ready(now).is_empty());
+    assert_eq!(queue.take_ready(now + RETRY_BASE_DELAY).len(), 1);
+
+    queue.push_new([solution]);
+    assert_eq!(queue.prune_expired(now + Duration::from_secs(10)), 1);
+    assert!(queue.is_empty());
+}
+
+#[test]
+/// Tests that pending and submitted solutions are not queued again.
+fn test_retry_queue_duplicates() {
+    let mut queue = RetryQueue::new(Duration::from_secs(10));
+    let solution = Solution::new([1, 2, 3, 4]);
+    let now = solution.found_at;
+
+    assert_eq!(queue.push_new([solution, solution]), 1);
+    let ready = queue.take_ready(now);
+    queue.reschedule(ready, now);
+    assert_eq!(queue.push_new([solution]), 1);
+    assert_eq!(queue.len(), 1);
+    let ready = queue.take_ready(now + RETRY_MAX_DELAY);
+    queue.mark_submitted(&ready);
+    assert_eq!(queue.push_new([solution]), 1);
+    assert!(queue.is_empty());
+
+    queue.prune_expired(now + Duration::from_secs(10));
+    assert_eq!(queue.push_new([solution]), 0);
+}
+
+#[test]
+/// Tests that solutions are tagged with the epoch of the seed and dropped once the nodes move past it.
+fn test_retry_queue_epochs() {
+    let mut queue = RetryQueue::new(DEFAULT_SOLUTION_MAX_AGE);
+    queue.push_new([Solution::new([1; 4])]);
+    assert_eq!(queue.prune_past_epochs(), 0);
+
+    // The first epoch reported is the one of the seed, and tags the solutions found before
+    assert_eq!(queue.set_epoch(150), None);
+    queue.push_new([Solution::new([2; 4])]);
+    assert_eq!(queue.get_seed_epoch(), Some(150));
+    assert_eq!(queue.prune_past_epochs(), 0);
+
+    // Solutions found after the epoch passed are mined against the same stale seed
+    assert_eq!(queue.set_epoch(151), Some(150));
+    queue.push_new([Solution::new([3; 4])]);
+    assert_eq!(queue.prune_past_epochs(), 3);
+    assert!(queue.is_empty());
+    assert_eq!(queue.get_seed_epoch(), Some(150));
+}
+
+#[test]
+/// Tests that the newest solutions over the cap are taken out and the room left for the spilled ones.
+fn test_retry_queue_overflow() {
+    let mut queue = RetryQueue::new(DEFAULT_SOLUTION_MAX_AGE);
+    queue.push_new((0..5).map(|idx| Solution::new([idx; 4])));
+    assert!(queue.take_overflow().is_empty());
+    assert_eq!(queue.get_room(), usize::MAX);
+
+    queue.set_max_in_memory(Some(3));
+    let overflow = queue.take_overflow();
+    assert_eq!(overflow.iter().map(|solution| solution.nonce[0]).collect::<Vec<_>>(), [3, 4]);
+    assert_eq!(queue.len(), 3);
+    assert_eq!(queue.get_room(), 0);
+    queue.take_ready(Instant::now());
+    assert_eq!(queue.get_room(), 3);
+    // Spilled solutions come back once there is room
+    assert_eq!(queue.push_new(overflow), 0);
+}
+
+#[test]
+/// Tests that pacing limits the packets taken per connection and splits them per second.
+fn test_submission_pacing() {
+    let now = Instant::now();
+    let mut queue = RetryQueue::new(DEFAULT_SOLUTION_MAX_AGE);
+    queue.push_new((0..5u64).map(|idx| Solution { nonce: [idx; 4], found_at: now, epoch: None }));
+
+    let ready = queue.take_ready_at_most(now, 3);
+    assert_eq!(ready.iter().map(|pending| pending.solution.nonce[0]).collect::<Vec<_>>(), vec![0, 1, 2]);
+    assert_eq!(queue.len(), 2);
+
+    let pacing = SubmissionPacing { max_packets_per_second: Some(2), ..SubmissionPacing::default() };
+    assert_eq!(pacing.batches(5), vec![2, 2, 1]);
+    assert_eq!(SubmissionPacing::default().batches(5), vec![5]);
+    assert!(SubmissionPacing::default().batches(0).is_empty());
+
+    // Extra connections only for what one connection would send in several batches
+    let pacing = SubmissionPacing { max_packets_per_connection: Some(3), connections_per_node: 4, ..SubmissionPacing::default() };
+    assert_eq!(pacing.packets_per_round(), 12);
+    assert_eq!(pacing.shares(2), vec![2]);
+    assert_eq!(pacing.shares(7), vec![3, 2, 2]);
+    assert_eq!(pacing.shares(12), vec![3, 3, 3, 3]);
+    assert!(pacing.shares(0).is_empty());
+    assert_eq!(SubmissionPacing { connections_per_node: 4, ..SubmissionPacing::default() }.shares(100), vec![100]);
+    assert_eq!(SubmissionPacing::default().packets_per_round(), usize::MAX);
+
+    let outcomes = [
+        ConnectionOutcome { is_connected: true, written: 3, is_answered: true },
+        ConnectionOutcome { is_connected: true, written: 2, is_answered: false },
+        ConnectionOutcome::default(),
+    ];
+    assert_eq!(ConnectionOutcome::aggregate(&outcomes), (2, 5, 1));
+}
+
+#[test]
+/// Tests that the totals survive a restart within the epoch and start over with a new one.
+fn test_submission_stats() {
+    let file = std::env::temp_dir().join(format!("qiner-submissions-test-{}.json", std::process::id()));
+    let _ = fs::remove_file(&file);
+
+    let stats = SubmissionStats::new(&[1; 32], Some(file.clone()));
+    stats.record_found(3);
+    stats.record_sent(2);
+    stats.record_answered_connection();
+    stats.record_failed(1);
+    stats.save();
+
+    let restored = SubmissionStats::new(&[1; 32], Some(file.clone()));
+    assert_eq!((restored.get_found(), restored.get_sent(), restored.get_answered_connections(), restored.get_failed()), (3, 2, 1, 1));
+
+    let next_epoch = SubmissionStats::new(&[2; 32], Some(file.clone()));
+    assert_eq!(next_epoch.get_sent(), 0);
+
+    let _ = fs::remove_file(&file);
+}
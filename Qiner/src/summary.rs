@@ -0,0 +1,154 @@
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::Instant;
+use serde_json::{json, Value};
+use crate::estimator::format_duration;
+use crate::histogram;
+use crate::miner::Miner;
+use crate::network::NetStats;
+use crate::submission::SubmissionStats;
+
+/// Produces the session summary printed on exit or on request.
+///
+/// The summary is logged and, if a file is configured, also written as JSON so
+/// tuning runs can be compared afterwards.
+pub struct SessionReporter {
+    miner: Arc<Miner>,
+    net_stats: Arc<NetStats>,
+    submission_stats: Arc<SubmissionStats>,
+    config: Value,
+    output_file: Option<PathBuf>,
+    started_at: Instant,
+}
+
+impl SessionReporter {
+    /// Creates a new `SessionReporter`, the session starting now.
+    ///
+    /// # Arguments
+    /// * `miner` - Shared reference to the Miner instance.
+    /// * `net_stats` - Shared network counters.
+    /// * `submission_stats` - Shared totals of the submitted solutions of the epoch.
+    /// * `config` - Snapshot of the configuration, included as is in the summary.
+    /// * `output_file` - Optional file the JSON summary is written to.
+    ///
+    /// # Returns
+    /// A new `SessionReporter`.
+    pub fn new(
+        miner: Arc<Miner>,
+        net_stats: Arc<NetStats>,
+        submission_stats: Arc<SubmissionStats>,
+        config: Value,
+        output_file: Option<PathBuf>,
+    ) -> Self {
+        SessionReporter {
+            miner,
+            net_stats,
+            submission_stats,
+            config,
+            output_file,
+            started_at: Instant::now(),
+        }
+    }
+
+    /// Gets the totals of the submitted solutions of the epoch.
+    pub fn get_submission_stats(&self) -> &SubmissionStats {
+        &self.submission_stats
+    }
+
+    /// Builds the summary of the session so far.
+    ///
+    /// # Returns
+    /// The summary as a JSON value.
+    pub async fn summary(&self) -> Value {
+        let runtime = self.started_at.elapsed().as_secs_f64().max(f64::EPSILON);
+        let miner = self.miner.snapshot();
+        let iterations = miner.iterations;
+
+        let per_thread = miner.thread_iterations.iter().enumerate().map(|(idx, iterations)| {
+            json!({
+                "thread": idx,
+                "core_type": self.miner.get_core_type(idx).map(|core_type| core_type.name()),
+                "iterations": iterations,
+                "average_it_per_sec": *iterations as f64 / runtime,
+            })
+        }).collect::<Vec<Value>>();
+
+        json!({
+            "runtime_secs": runtime,
+            "iterations": iterations,
+            "average_it_per_sec": iterations as f64 / runtime,
+            "solutions_found": miner.solutions_found,
+            "solutions_sent": self.submission_stats.get_sent(),
+            "answered_connections": self.submission_stats.get_answered_connections(),
+            "solutions_failed": self.submission_stats.get_failed(),
+            "solutions_expired": self.submission_stats.get_expired(),
+            "shares_found": miner.shares_found,
+            "worker_restarts": miner.worker_restarts,
+            "algorithm": miner.algorithm,
+            "backend": miner.backend,
+            "seed_fingerprint": miner.seed_fingerprint,
+            "epoch": {
+                "secs": miner.epoch.duration.as_secs(),
+                "iterations": miner.epoch.iterations,
+                "solutions_found": miner.epoch.solutions_found,
+                "shares_found": miner.epoch.shares_found,
+            },
+            "per_thread": per_thread,
+            "score_histogram": histogram::report(&miner.score_counts, miner.solution_threshold),
+            "network": {
+                "packets_sent": self.net_stats.get_packets_sent(),
+                "bytes_sent": self.net_stats.get_bytes_sent(),
+                "connect_attempts": self.net_stats.get_connect_attempts(),
+                "connect_failures": self.net_stats.get_connect_failures(),
+                "average_latency_ms": self.net_stats.get_average_latency().as_millis() as u64,
+                "max_latency_ms": self.net_stats.get_max_latency().as_millis() as u64,
+                "computor": self.net_stats.get_computor().map(|(epoch, index)| json!({ "epoch": epoch, "index": index })),
+            },
+            "config": self.config,
+        })
+    }
+
+    /// Logs the summary of the session and writes it to the output file, if configured.
+    pub async fn report(&self) {
+        let summary = self.summary().await;
+
+        log::info!("===== Session summary =====");
+        log::info!("Runtime: {}", format_duration(self.started_at.elapsed()));
+        log::info!("Iterations: {} ({:.1} it/s on average)", summary["iterations"], summary["average_it_per_sec"].as_f64().unwrap_or_default());
+        log::info!("Solutions: {} found | {} sent", summary["solutions_found"], summary["solutions_sent"]);
+        if let Some(near_misses) = summary["score_histogram"]["near_misses"].as_array().filter(|near_misses| !near_misses.is_empty()) {
+            let near_misses = near_misses.iter()
+                .map(|near_miss| format!("{}+: {} ({:.1} expected)", near_miss["score"], near_miss["count"], near_miss["expected"].as_f64().unwrap_or_default()))
+                .collect::<Vec<_>>();
+            log::info!("Near misses: {}", near_misses.join(" | "));
+        }
+        log::info!(
+            "Network: {} connects ({} failed) | {} packets sent",
+            summary["network"]["connect_attempts"],
+            summary["network"]["connect_failures"],
+            summary["network"]["packets_sent"]
+        );
+        log::info!("Worker restarts: {}", summary["worker_restarts"]);
+        if let Some(per_thread) = summary["per_thread"].as_array() {
+            for thread in per_thread {
+                log::info!(
+                    "Thread #{}{}: {} iterations ({:.1} it/s)",
+                    thread["thread"],
+                    thread["core_type"].as_str().map_or(String::new(), |core_type| format!(" ({core_type})")),
+                    thread["iterations"],
+                    thread["average_it_per_sec"].as_f64().unwrap_or_default()
+                );
+            }
+        }
+
+        if let Some(output_file) = &self.output_file {
+            match serde_json::to_string_pretty(&summary) {
+                Ok(content) => match tokio::fs::write(output_file, content).await {
+                    Ok(_) => log::info!("Summary written to {}", output_file.display()),
+                    Err(err) => log::error!("Failed to write the summary to {}: {:?}", output_file.display(), err),
+                },
+                Err(err) => log::error!("Failed to serialize the summary: {:?}", err),
+            }
+        }
+    }
+}
@@ -0,0 +1,56 @@
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use crate::miner::Miner;
+
+/// Default time without progress after which a worker is considered stalled.
+pub const DEFAULT_STALL_TIMEOUT: Duration = Duration::from_secs(5 * 60);
+
+/// Interval between two checks of the workers.
+const CHECK_INTERVAL: Duration = Duration::from_secs(5);
+
+/// Progress of a single mining thread, as last seen by the supervisor.
+#[derive(Debug, Clone, Copy)]
+struct WorkerProgress {
+    iterations: usize,
+    progressed_at: Instant,
+}
+
+/// Asynchronous task watching the mining workers and restarting the ones that died or stalled.
+///
+/// A worker is restarted when its thread has exited (e.g. after a panic) or when its
+/// iteration counter has not moved for `stall_timeout` while it was allowed to work.
+///
+/// # Arguments
+/// * `miner` - Shared reference to the Miner instance
+/// * `stall_timeout` - Time without progress after which a worker is restarted, `None` to only restart exited workers
+pub async fn supervise_workers(miner: Arc<Miner>, stall_timeout: Option<Duration>) {
+    let mut progress = miner.get_thread_iteration_counts().into_iter().map(|iterations| WorkerProgress {
+        iterations,
+        progressed_at: Instant::now(),
+    }).collect::<Vec<WorkerProgress>>();
+
+    loop {
+        tokio::time::sleep(CHECK_INTERVAL).await;
+
+        let thread_counts = miner.get_thread_iteration_counts();
+        let active_threads = miner.get_active_threads();
+
+        for (idx, (progress, iterations)) in progress.iter_mut().zip(thread_counts).enumerate() {
+            // Idle workers are not expected to progress
+            if iterations != progress.iterations || miner.is_paused() || idx >= active_threads {
+                *progress = WorkerProgress { iterations, progressed_at: Instant::now() };
+            }
+
+            if miner.is_worker_finished(idx) {
+                log::error!("Worker #{idx} exited unexpectedly, restarting it");
+            } else if stall_timeout.is_some_and(|stall_timeout| progress.progressed_at.elapsed() >= stall_timeout) {
+                log::error!("Worker #{idx} made no progress for {:?}, restarting it", progress.progressed_at.elapsed());
+            } else {
+                continue;
+            }
+
+            Miner::restart_worker(&miner, idx);
+            progress.progressed_at = Instant::now();
+        }
+    }
+}
@@ -0,0 +1,66 @@
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use sd_notify::NotifyState;
+use crate::miner::Miner;
+
+/// Notifies systemd that the miner is running.
+///
+/// Does nothing when Qiner is not started by systemd with `Type=notify`.
+pub fn notify_ready() {
+    if let Err(err) = sd_notify::notify(false, &[NotifyState::Ready]) {
+        log::warn!("Failed to notify systemd: {:?}", err);
+    }
+}
+
+/// Notifies systemd that Qiner is shutting down.
+pub fn notify_stopping() {
+    if let Err(err) = sd_notify::notify(false, &[NotifyState::Stopping]) {
+        log::warn!("Failed to notify systemd: {:?}", err);
+    }
+}
+
+/// Gets the watchdog interval requested by systemd through `WatchdogSec`.
+///
+/// # Returns
+/// The interval, or `None` if the watchdog is not enabled for this process.
+pub fn watchdog_interval() -> Option<Duration> {
+    let mut usec = 0u64;
+    sd_notify::watchdog_enabled(false, &mut usec).then(|| Duration::from_micros(usec))
+}
+
+/// Asynchronous task sending watchdog pings to systemd while mining progresses.
+///
+/// Pings are sent twice per watchdog interval, but only if the iteration counter moved
+/// within the last interval (or mining is paused), so systemd restarts a miner whose
+/// workers are all stuck even though the process is alive. The status line is updated
+/// with the current hashrate at the same time.
+///
+/// # Arguments
+/// * `miner` - Shared reference to the Miner instance
+/// * `interval` - The watchdog interval requested by systemd
+pub async fn watchdog_task(miner: Arc<Miner>, interval: Duration) {
+    let mut marker = miner.marker();
+    let mut progressed_at = Instant::now();
+
+    loop {
+        tokio::time::sleep(interval / 2).await;
+
+        let snapshot = miner.snapshot();
+        let delta = snapshot.iterations_since(&mut marker);
+        let it_per_sec = delta.it_per_sec();
+
+        if delta.iterations > 0 || snapshot.is_paused {
+            progressed_at = Instant::now();
+        }
+
+        if progressed_at.elapsed() >= interval {
+            log::warn!("No mining progress for {:?}, withholding the systemd watchdog ping", progressed_at.elapsed());
+            continue;
+        }
+
+        let status = format!("{:.1} it/s | {} scores", it_per_sec, snapshot.solutions_found);
+        if let Err(err) = sd_notify::notify(false, &[NotifyState::Watchdog, NotifyState::Status(&status)]) {
+            log::warn!("Failed to ping the systemd watchdog: {:?}", err);
+        }
+    }
+}
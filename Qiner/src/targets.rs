@@ -0,0 +1,180 @@
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use crate::peers::PeerBook;
+use crate::socks::{self, SocksProxy};
+
+/// Default time the pool must be unreachable before falling back to solo submission.
+pub const DEFAULT_POOL_FAILOVER: Duration = Duration::from_secs(5 * 60);
+
+/// Interval between two connection attempts to the pool while in solo mode.
+const POOL_PROBE_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Where solutions are submitted.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TargetMode {
+    /// Solutions go to the pool.
+    Pool,
+    /// Solutions go directly to the node.
+    Solo,
+}
+
+/// Selects the endpoint solutions are submitted to.
+///
+/// The pool has priority when configured. Once it has been unreachable for the failover
+/// duration, submission falls back to the best scored node, and switches back as soon as
+/// the pool accepts connections again.
+#[derive(Debug)]
+pub struct TargetSelector {
+    pool: Option<String>,
+    nodes: PeerBook,
+    failover_after: Duration,
+    mode: TargetMode,
+    pool_failing_since: Option<Instant>,
+}
+
+impl TargetSelector {
+    /// Creates a new `TargetSelector`, starting with the pool if there is one.
+    ///
+    /// # Arguments
+    /// * `pool` - Address of the pool, `None` to always submit to the node
+    /// * `nodes` - Scores of the nodes
+    /// * `failover_after` - Time the pool must be unreachable before falling back to the node
+    ///
+    /// # Returns
+    /// A new `TargetSelector`.
+    pub fn new(pool: Option<String>, nodes: PeerBook, failover_after: Duration) -> Self {
+        let mode = if pool.is_some() { TargetMode::Pool } else { TargetMode::Solo };
+        TargetSelector {
+            pool,
+            nodes,
+            failover_after,
+            mode,
+            pool_failing_since: None,
+        }
+    }
+
+    /// Gets the current submission mode.
+    pub fn get_mode(&self) -> TargetMode {
+        self.mode
+    }
+
+    /// Gets the address of the pool, if configured.
+    pub fn get_pool(&self) -> Option<&str> {
+        self.pool.as_deref()
+    }
+
+    /// Replaces the nodes, e.g. when the peer settings are reloaded.
+    ///
+    /// # Arguments
+    /// * `nodes` - Scores of the new nodes
+    pub fn set_nodes(&mut self, nodes: PeerBook) {
+        self.nodes = nodes;
+    }
+
+    /// Learns nodes from the public peers of a node, to fall back to.
+    ///
+    /// # Arguments
+    /// * `addrs` - The addresses of the nodes
+    pub fn learn_nodes(&mut self, addrs: Vec<String>) {
+        self.nodes.learn(addrs);
+    }
+
+    /// Gets the nodes solutions fall back to.
+    pub fn get_nodes(&self) -> &PeerBook {
+        &self.nodes
+    }
+
+    /// Gets the address solutions are currently submitted to.
+    pub fn current(&self) -> &str {
+        match (self.mode, &self.pool) {
+            (TargetMode::Pool, Some(pool)) => pool,
+            _ => self.nodes.best(),
+        }
+    }
+
+    /// Records the outcome of a connection attempt, switching mode when needed.
+    ///
+    /// # Arguments
+    /// * `addr` - The address the attempt was made to
+    /// * `is_success` - Whether the connection succeeded
+    /// * `now` - The current time
+    pub fn record_result(&mut self, addr: &str, is_success: bool, now: Instant) {
+        if self.pool.as_deref() != Some(addr) {
+            self.nodes.record_connect(addr, is_success);
+            return;
+        }
+
+        if is_success {
+            self.pool_failing_since = None;
+            if self.mode == TargetMode::Solo {
+                log::info!("Pool {addr} is reachable again, switching back to pool submission");
+                self.mode = TargetMode::Pool;
+            }
+            return;
+        }
+
+        let failing_since = *self.pool_failing_since.get_or_insert(now);
+        if self.mode == TargetMode::Pool && now.saturating_duration_since(failing_since) >= self.failover_after {
+            log::warn!("Pool {addr} unreachable for {:?}, falling back to solo submission to {}", self.failover_after, self.nodes.best());
+            self.mode = TargetMode::Solo;
+        }
+    }
+
+    /// Records the outcome of a write to a connected address.
+    ///
+    /// # Arguments
+    /// * `addr` - The address written to
+    /// * `latency` - The time the write took
+    /// * `is_success` - Whether the write succeeded
+    pub fn record_write(&mut self, addr: &str, latency: Duration, is_success: bool) {
+        if self.pool.as_deref() != Some(addr) {
+            self.nodes.record_write(addr, latency, is_success);
+        }
+    }
+}
+
+/// Asynchronous task probing the pool while in solo mode, so submission switches back once it recovers.
+///
+/// # Arguments
+/// * `targets` - The shared target selector
+/// * `socks` - The SOCKS proxy the pool is reached through, `None` to connect directly
+pub async fn probe_pool_task(targets: Arc<Mutex<TargetSelector>>, socks: Option<SocksProxy>) {
+    loop {
+        tokio::time::sleep(POOL_PROBE_INTERVAL).await;
+
+        let pool = {
+            let targets = targets.lock().unwrap();
+            match (targets.get_mode(), targets.get_pool()) {
+                (TargetMode::Solo, Some(pool)) => pool.to_string(),
+                _ => continue,
+            }
+        };
+
+        let is_success = socks::connect(socks.as_ref(), &pool).await.is_ok();
+        targets.lock().unwrap().record_result(&pool, is_success, Instant::now());
+    }
+}
+
+#[test]
+/// Tests the fallback to solo after the failover duration and the switch back to the pool.
+fn test_target_failover() {
+    let now = Instant::now();
+    let mut targets = TargetSelector::new(Some("pool:1".to_string()), PeerBook::new(vec!["node:2".to_string()], &crate::peer_filter::PeerFilter::default(), None), Duration::from_secs(60));
+    assert_eq!(targets.current(), "pool:1");
+
+    targets.record_result("pool:1", false, now);
+    targets.record_result("pool:1", false, now + Duration::from_secs(59));
+    assert_eq!(targets.get_mode(), TargetMode::Pool);
+
+    targets.record_result("pool:1", false, now + Duration::from_secs(60));
+    assert_eq!(targets.current(), "node:2");
+
+    // Node failures do not affect the mode
+    targets.record_result("node:2", false, now + Duration::from_secs(61));
+    assert_eq!(targets.get_mode(), TargetMode::Solo);
+
+    targets.record_result("pool:1", true, now + Duration::from_secs(120));
+    assert_eq!(targets.current(), "pool:1");
+
+    assert_eq!(TargetSelector::new(None, PeerBook::new(vec!["node:2".to_string()], &crate::peer_filter::PeerFilter::default(), None), DEFAULT_POOL_FAILOVER).current(), "node:2");
+}
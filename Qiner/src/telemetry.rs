@@ -0,0 +1,327 @@
+//! Traces of the lifecycle of every solution, from the moment it is found until the node
+//! answers, exported as OpenTelemetry spans over OTLP/HTTP with the JSON encoding.
+
+use std::collections::HashMap;
+use std::collections::hash_map::Entry;
+use std::env;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use serde_json::{json, Value};
+use lib::env_names::{ENV_OTEL_EXPORTER_OTLP_ENDPOINT, ENV_OTEL_EXPORTER_OTLP_HEADERS, ENV_OTEL_SERVICE_NAME};
+use lib::types::Nonce64;
+use crate::miner::Solution;
+use crate::rig::get_worker_name;
+
+/// Default name of the service the spans are reported for.
+const DEFAULT_SERVICE_NAME: &str = "qiner";
+
+/// Path of the traces on the OTLP/HTTP endpoint.
+const TRACES_PATH: &str = "/v1/traces";
+
+/// Timeout applied to every export request.
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// `SPAN_KIND_INTERNAL` of OTLP.
+const SPAN_KIND_INTERNAL: u8 = 1;
+
+/// `STATUS_CODE_OK` and `STATUS_CODE_ERROR` of OTLP.
+const STATUS_CODE_OK: u8 = 1;
+const STATUS_CODE_ERROR: u8 = 2;
+
+/// A step of the submission of a solution.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SolutionStage {
+    /// The solution entered the retry queue.
+    Queued,
+    /// The solution was serialized into a packet.
+    Serialized,
+    /// A connection to the node was opened.
+    Connected,
+    /// The packet was written to the connection.
+    Written,
+    /// The node sent a message after the packet, solutions themselves are never acknowledged.
+    Answered,
+}
+
+impl SolutionStage {
+    /// Gets the name of the span ending at this stage.
+    pub fn name(&self) -> &'static str {
+        match self {
+            SolutionStage::Queued => "queued",
+            SolutionStage::Serialized => "serialized",
+            SolutionStage::Connected => "connected",
+            SolutionStage::Written => "written",
+            SolutionStage::Answered => "answered",
+        }
+    }
+}
+
+/// The time spent reaching a stage.
+#[derive(Debug, Clone, PartialEq)]
+struct StageSpan {
+    stage: SolutionStage,
+    start: SystemTime,
+    end: SystemTime,
+    attributes: Vec<(&'static str, String)>,
+    /// Why the stage was not reached, `None` if it was
+    error: Option<String>,
+}
+
+/// The stages a solution went through so far.
+#[derive(Debug, Clone)]
+struct SolutionTrace {
+    found_at: SystemTime,
+    last_at: SystemTime,
+    spans: Vec<StageSpan>,
+}
+
+/// Records the stages of the solutions being submitted and exports their traces once done.
+///
+/// Disabled unless `OTEL_EXPORTER_OTLP_ENDPOINT` is set. Every solution is one trace, made of
+/// a `solution` span from the moment it was found to the last stage, with one child span per
+/// stage covering the time since the previous one. Failed attempts are kept as error spans.
+#[derive(Debug)]
+pub struct SolutionTracer {
+    url: String,
+    headers: Vec<(String, String)>,
+    service_name: String,
+    /// Name of the rig, the `service.instance.id` of the traces
+    worker_name: Option<String>,
+    traces: HashMap<Nonce64, SolutionTrace>,
+}
+
+impl SolutionTracer {
+    /// Creates a `SolutionTracer` from the environment variables.
+    ///
+    /// # Returns
+    /// `Some(SolutionTracer)` if an OTLP endpoint is configured, `None` otherwise.
+    pub fn from_env() -> Option<Self> {
+        let endpoint = env::var(ENV_OTEL_EXPORTER_OTLP_ENDPOINT).ok()
+            .map(|endpoint| endpoint.trim().trim_end_matches('/').to_string())
+            .filter(|endpoint| !endpoint.is_empty())?;
+
+        // Comma-separated `key=value` pairs, as read by the OpenTelemetry SDKs
+        let headers = env::var(ENV_OTEL_EXPORTER_OTLP_HEADERS).unwrap_or_default()
+            .split(',')
+            .filter_map(|header| header.split_once('='))
+            .map(|(key, value)| (key.trim().to_string(), value.trim().to_string()))
+            .filter(|(key, _)| !key.is_empty())
+            .collect();
+
+        let service_name = env::var(ENV_OTEL_SERVICE_NAME).ok()
+            .map(|name| name.trim().to_string())
+            .filter(|name| !name.is_empty())
+            .unwrap_or_else(|| DEFAULT_SERVICE_NAME.to_string());
+
+        Some(SolutionTracer { url: format!("{endpoint}{TRACES_PATH}"), headers, service_name, worker_name: get_worker_name(), traces: HashMap::new() })
+    }
+
+    /// Gets the URL the traces are posted to.
+    pub fn get_url(&self) -> &str {
+        &self.url
+    }
+
+    /// Starts the traces of freshly found solutions, which are queued right away.
+    ///
+    /// # Arguments
+    /// * `solutions` - The solutions found.
+    pub fn found<'a>(&mut self, solutions: impl IntoIterator<Item = &'a Solution>) {
+        let now = SystemTime::now();
+        for solution in solutions {
+            let found_at = now.checked_sub(solution.found_at.elapsed()).unwrap_or(now);
+            if let Entry::Vacant(entry) = self.traces.entry(solution.nonce) {
+                let queued = StageSpan { stage: SolutionStage::Queued, start: found_at, end: now, attributes: Vec::new(), error: None };
+                entry.insert(SolutionTrace { found_at, last_at: now, spans: vec![queued] });
+            }
+        }
+    }
+
+    /// Records that solutions reached a stage.
+    ///
+    /// # Arguments
+    /// * `nonces` - The nonces of the solutions.
+    /// * `stage` - The stage reached.
+    /// * `attributes` - Attributes of the stage, e.g. the address of the node.
+    pub fn record<'a>(&mut self, nonces: impl IntoIterator<Item = &'a Nonce64>, stage: SolutionStage, attributes: &[(&'static str, String)]) {
+        self.push(nonces, stage, attributes, None);
+    }
+
+    /// Records that solutions failed to reach a stage.
+    ///
+    /// # Arguments
+    /// * `nonces` - The nonces of the solutions.
+    /// * `stage` - The stage that was not reached.
+    /// * `reason` - Why the stage was not reached.
+    pub fn record_failure<'a>(&mut self, nonces: impl IntoIterator<Item = &'a Nonce64>, stage: SolutionStage, reason: &str) {
+        self.push(nonces, stage, &[], Some(reason.to_string()));
+    }
+
+    fn push<'a>(&mut self, nonces: impl IntoIterator<Item = &'a Nonce64>, stage: SolutionStage, attributes: &[(&'static str, String)], error: Option<String>) {
+        let now = SystemTime::now();
+        for nonce in nonces {
+            if let Some(trace) = self.traces.get_mut(nonce) {
+                trace.spans.push(StageSpan { stage, start: trace.last_at, end: now, attributes: attributes.to_vec(), error: error.clone() });
+                trace.last_at = now;
+            }
+        }
+    }
+
+    /// Drops the traces of solutions that will not be submitted anymore.
+    ///
+    /// # Arguments
+    /// * `max_age` - The age after which solutions are dropped from the retry queue.
+    pub fn prune(&mut self, max_age: Duration) {
+        let now = SystemTime::now();
+        self.traces.retain(|_, trace| now.duration_since(trace.found_at).unwrap_or_default() < max_age);
+    }
+
+    /// Ends the traces of submitted solutions and exports them in the background.
+    ///
+    /// # Arguments
+    /// * `nonces` - The nonces of the submitted solutions.
+    pub fn finish<'a>(&mut self, nonces: impl IntoIterator<Item = &'a Nonce64>) {
+        let spans = nonces.into_iter()
+            .filter_map(|nonce| self.traces.remove(nonce).map(|trace| (nonce, trace)))
+            .flat_map(|(nonce, trace)| trace_spans(nonce, &trace))
+            .collect::<Vec<_>>();
+        if spans.is_empty() {
+            return;
+        }
+
+        let body = self.export_request(spans).to_string();
+        let url = self.url.clone();
+        let headers = self.headers.clone();
+        tokio::spawn(async move {
+            let result = tokio::task::spawn_blocking(move || {
+                let mut request = ureq::post(&url)
+                    .timeout(REQUEST_TIMEOUT)
+                    .set("Content-Type", "application/json");
+                for (key, value) in &headers {
+                    request = request.set(key, value);
+                }
+                // The error is reduced to its kind, since the headers may contain a token
+                request.send_string(&body).map(|_| ()).map_err(|err| err.kind())
+            }).await;
+
+            match result {
+                Ok(Ok(_)) => log::debug!("Solution traces exported"),
+                Ok(Err(kind)) => log::warn!("Failed to export the solution traces: {}", kind),
+                Err(err) => log::warn!("Failed to export the solution traces: {:?}", err),
+            }
+        });
+    }
+
+    /// Builds the attributes of the resource: the service, and the rig if named.
+    fn resource_attributes(&self) -> Vec<Value> {
+        std::iter::once(attribute("service.name", &self.service_name))
+            .chain(self.worker_name.iter().map(|name| attribute("service.instance.id", name)))
+            .collect()
+    }
+
+    /// Builds the OTLP `ExportTraceServiceRequest` of spans.
+    fn export_request(&self, spans: Vec<Value>) -> Value {
+        json!({
+            "resourceSpans": [{
+                "resource": { "attributes": self.resource_attributes() },
+                "scopeSpans": [{
+                    "scope": { "name": "qiner", "version": env!("CARGO_PKG_VERSION") },
+                    "spans": spans,
+                }],
+            }],
+        })
+    }
+}
+
+/// Builds the OTLP spans of the trace of a solution.
+///
+/// The trace and span IDs come from the nonce, which is random, so no generator is needed.
+///
+/// # Arguments
+/// * `nonce` - The nonce of the solution.
+/// * `trace` - The stages of the solution.
+///
+/// # Returns
+/// The `solution` span followed by one span per stage.
+fn trace_spans(nonce: &Nonce64, trace: &SolutionTrace) -> Vec<Value> {
+    let trace_id = format!("{:016x}{:016x}", nonce[0], nonce[1]);
+    let root_id = format!("{:016x}", nonce[2]);
+    let is_error = trace.spans.last().is_some_and(|span| span.error.is_some());
+    let attempts = trace.spans.iter().filter(|span| span.stage == SolutionStage::Connected).count();
+
+    let root = json!({
+        "traceId": trace_id,
+        "spanId": root_id,
+        "name": "solution",
+        "kind": SPAN_KIND_INTERNAL,
+        "startTimeUnixNano": unix_nanos(trace.found_at),
+        "endTimeUnixNano": unix_nanos(trace.last_at),
+        "attributes": [
+            attribute("qubic.nonce", &nonce.iter().map(|word| format!("{word:016x}")).collect::<String>()),
+            json!({ "key": "qubic.attempts", "value": { "intValue": attempts.to_string() } }),
+        ],
+        "status": { "code": if is_error { STATUS_CODE_ERROR } else { STATUS_CODE_OK } },
+    });
+
+    std::iter::once(root).chain(trace.spans.iter().enumerate().map(|(idx, span)| {
+        let mut status = json!({ "code": STATUS_CODE_OK });
+        if let Some(error) = &span.error {
+            status = json!({ "code": STATUS_CODE_ERROR, "message": error });
+        }
+        json!({
+            "traceId": trace_id,
+            "spanId": format!("{:016x}", nonce[3].wrapping_add(idx as u64 + 1)),
+            "parentSpanId": root_id,
+            "name": span.stage.name(),
+            "kind": SPAN_KIND_INTERNAL,
+            "startTimeUnixNano": unix_nanos(span.start),
+            "endTimeUnixNano": unix_nanos(span.end),
+            "attributes": span.attributes.iter().map(|(key, value)| attribute(key, value)).collect::<Vec<_>>(),
+            "status": status,
+        })
+    })).collect()
+}
+
+/// Builds an OTLP string attribute.
+fn attribute(key: &str, value: &str) -> Value {
+    json!({ "key": key, "value": { "stringValue": value } })
+}
+
+/// Formats a time as the nanoseconds since the Unix epoch, as a string like OTLP/JSON expects for 64-bit integers.
+fn unix_nanos(time: SystemTime) -> String {
+    time.duration_since(UNIX_EPOCH).unwrap_or_default().as_nanos().to_string()
+}
+
+#[test]
+/// Tests that a trace has a root span and one child span per stage, failed attempts included.
+fn test_solution_trace_spans() {
+    let found_at = UNIX_EPOCH + Duration::from_secs(1_700_000_000);
+    let at = |millis: u64| found_at + Duration::from_millis(millis);
+    let span = |stage, start, end, error: Option<&str>| StageSpan { stage, start: at(start), end: at(end), attributes: Vec::new(), error: error.map(str::to_string) };
+    let trace = SolutionTrace {
+        found_at,
+        last_at: at(1500),
+        spans: vec![
+            span(SolutionStage::Queued, 0, 100, None),
+            span(SolutionStage::Connected, 100, 200, Some("connection refused")),
+            span(SolutionStage::Connected, 200, 1200, None),
+            StageSpan { attributes: vec![("server.address", "127.0.0.1:21841".to_string())], ..span(SolutionStage::Serialized, 1200, 1201, None) },
+            span(SolutionStage::Written, 1201, 1300, None),
+            span(SolutionStage::Answered, 1300, 1500, None),
+        ],
+    };
+
+    let spans = trace_spans(&[0x1111, 0x2222, 0x3333, 0x4444], &trace);
+    assert_eq!(spans.len(), 7);
+    assert_eq!(spans[0]["traceId"], "00000000000011110000000000002222");
+    assert_eq!(spans[0]["spanId"], "0000000000003333");
+    assert_eq!(spans[0]["startTimeUnixNano"], "1700000000000000000");
+    assert_eq!(spans[0]["endTimeUnixNano"], "1700000001500000000");
+    assert_eq!(spans[0]["attributes"][1]["value"]["intValue"], "2");
+    assert_eq!(spans[0]["status"]["code"], STATUS_CODE_OK);
+
+    assert!(spans[1..].iter().all(|span| span["parentSpanId"] == "0000000000003333" && span["traceId"] == spans[0]["traceId"]));
+    assert_eq!(spans[1]["spanId"], "0000000000004445");
+    assert_eq!(spans[2]["name"], "connected");
+    assert_eq!(spans[2]["status"]["message"], "connection refused");
+    assert_eq!(spans[4]["attributes"][0]["value"]["stringValue"], "127.0.0.1:21841");
+    assert_eq!(spans[6]["name"], "answered");
+}
@@ -0,0 +1,135 @@
+//! Throttling of the mining threads on the temperature of the CPU package.
+//!
+//! Above the limit, one thread is stopped at every check, down to a single one. Once the
+//! package is cooler than the limit minus the hysteresis, the stopped threads resume one at a
+//! time, so the temperature settles instead of swinging between full speed and throttled.
+
+use std::env;
+use std::sync::Arc;
+use std::time::Duration;
+use lib::env_names::{ENV_THERMAL_HYSTERESIS, ENV_THERMAL_LIMIT};
+use crate::miner::Miner;
+use crate::sensors::read_package_temperature;
+
+/// Default number of degrees below the limit the package must cool to before threads resume.
+pub const DEFAULT_THERMAL_HYSTERESIS: f64 = 5.0;
+
+/// Interval between two readings of the temperature.
+const THERMAL_CHECK_INTERVAL: Duration = Duration::from_secs(10);
+
+/// Decides the active threads from the temperature of the CPU package.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ThermalThrottle {
+    /// Temperature above which threads are stopped, in degrees Celsius
+    limit: f64,
+    /// Degrees below the limit the package must cool to before threads resume
+    hysteresis: f64,
+    /// Threads stopped because of the temperature
+    stopped: usize,
+}
+
+impl ThermalThrottle {
+    /// Creates a new `ThermalThrottle`, no thread stopped yet.
+    ///
+    /// # Arguments
+    /// * `limit` - The temperature above which threads are stopped, in degrees Celsius
+    /// * `hysteresis` - The degrees below the limit the package must cool to before threads resume
+    ///
+    /// # Returns
+    /// A new `ThermalThrottle`.
+    pub fn new(limit: f64, hysteresis: f64) -> Self {
+        ThermalThrottle { limit, hysteresis, stopped: 0 }
+    }
+
+    /// Creates a `ThermalThrottle` from the environment variables.
+    ///
+    /// # Returns
+    /// The throttle of `THERMAL_LIMIT`, with the hysteresis of `THERMAL_HYSTERESIS` or
+    /// `DEFAULT_THERMAL_HYSTERESIS`, or `None` if the limit is not set, `0` or invalid.
+    pub fn from_env() -> Option<Self> {
+        let value = |name: &str| env::var(name).ok().and_then(|value| value.trim().parse::<f64>().ok()).filter(|value| *value > 0.0);
+        let limit = value(ENV_THERMAL_LIMIT)?;
+        Some(ThermalThrottle::new(limit, value(ENV_THERMAL_HYSTERESIS).unwrap_or(DEFAULT_THERMAL_HYSTERESIS)))
+    }
+
+    /// Gets the temperature above which threads are stopped, in degrees Celsius.
+    pub fn get_limit(&self) -> f64 {
+        self.limit
+    }
+
+    /// Gets the number of threads stopped because of the temperature.
+    pub fn get_stopped(&self) -> usize {
+        self.stopped
+    }
+
+    /// Decides the active threads after a reading of the temperature.
+    ///
+    /// # Arguments
+    /// * `temperature` - The temperature of the CPU package, in degrees Celsius
+    /// * `active_threads` - The number of active threads
+    ///
+    /// # Returns
+    /// The number of threads to keep active: one less while too hot, one more of the stopped ones once cool.
+    pub fn next(&mut self, temperature: f64, active_threads: usize) -> usize {
+        if temperature > self.limit && active_threads > 1 {
+            self.stopped += 1;
+            active_threads - 1
+        } else if temperature < self.limit - self.hysteresis && self.stopped > 0 {
+            self.stopped -= 1;
+            active_threads + 1
+        } else {
+            active_threads
+        }
+    }
+}
+
+/// Asynchronous task throttling the mining threads while the CPU package is too hot.
+///
+/// # Arguments
+/// * `miner` - Shared reference to the Miner instance
+/// * `throttle` - The limits of the temperature
+pub async fn thermal_throttle_task(miner: Arc<Miner>, mut throttle: ThermalThrottle) {
+    if tokio::task::spawn_blocking(read_package_temperature).await.ok().flatten().is_none() {
+        log::warn!("No CPU temperature sensor found, THERMAL_LIMIT is ignored");
+        return;
+    }
+
+    loop {
+        tokio::time::sleep(THERMAL_CHECK_INTERVAL).await;
+
+        let Some(temperature) = tokio::task::spawn_blocking(read_package_temperature).await.ok().flatten() else {
+            continue;
+        };
+        let active_threads = miner.get_active_threads();
+        let next = throttle.next(temperature, active_threads);
+        if next < active_threads {
+            log::warn!("CPU at {temperature:.1} °C, above {:.1} °C: throttled to {next} active threads", throttle.get_limit());
+        } else if next > active_threads {
+            log::info!("CPU cooled to {temperature:.1} °C: {next} active threads, {} still throttled", throttle.get_stopped());
+        }
+        if next != active_threads {
+            miner.set_active_threads(next);
+        }
+    }
+}
+
+#[test]
+/// Tests that threads stop one at a time while too hot, down to one, and resume only below the hysteresis.
+fn test_thermal_throttle() {
+    let mut throttle = ThermalThrottle::new(85.0, 5.0);
+    assert_eq!(throttle.next(80.0, 4), 4);
+    assert_eq!(throttle.next(90.0, 4), 3);
+    assert_eq!(throttle.next(90.0, 3), 2);
+    assert_eq!(throttle.next(90.0, 2), 1);
+    assert_eq!(throttle.next(95.0, 1), 1);
+    assert_eq!(throttle.get_stopped(), 3);
+
+    // Between the limit and the hysteresis nothing changes
+    assert_eq!(throttle.next(82.0, 1), 1);
+    assert_eq!(throttle.next(79.0, 1), 2);
+    assert_eq!(throttle.next(79.0, 2), 3);
+    assert_eq!(throttle.next(79.0, 3), 4);
+    // Threads the throttle did not stop are left alone
+    assert_eq!(throttle.next(70.0, 4), 4);
+    assert_eq!(throttle.get_stopped(), 0);
+}
@@ -0,0 +1,223 @@
+//! Scheduling of transactions against the ticks of the network.
+//!
+//! A computor only executes a transaction it received before the tick the transaction is for, and
+//! drops it once that tick passed. A transaction is therefore scheduled a few ticks ahead of the
+//! current tick of the nodes and sent to all of them. Once its tick passed, the entity of the
+//! source tells whether it was executed, and if it was not, it is signed again for a later tick.
+
+use std::time::Duration;
+use lib::types::network::Protocol;
+use lib::types::PublicKey64;
+use tokio::io::AsyncWriteExt;
+use crate::balance::request_entity;
+use crate::capture;
+use crate::clock::request_tick_info;
+use crate::converters::get_public_key_64_from_public_key;
+use crate::identity::Identity;
+use crate::messages::{Request, TransactionBuilder};
+use crate::network::Entity;
+use crate::socks::{self, SocksProxy};
+
+/// Default number of ticks between the current tick and the tick of a transaction.
+pub const DEFAULT_TICK_OFFSET: u32 = 10;
+
+/// Default number of ticks a transaction is scheduled for before giving up.
+pub const DEFAULT_ATTEMPTS: usize = 3;
+
+/// Time to wait for a node to answer a request.
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Interval between two checks of whether the tick of a transaction passed.
+const TICK_POLL_INTERVAL: Duration = Duration::from_secs(3);
+
+/// Longest wait for the tick of a transaction to pass, beyond which the nodes are considered stuck.
+const TICK_WAIT_TIMEOUT: Duration = Duration::from_secs(10 * 60);
+
+/// Chooses the tick of a transaction.
+///
+/// # Arguments
+/// * `requested` - The tick asked for, `None` to schedule it `offset` ticks ahead.
+/// * `current_tick` - The current tick of the nodes.
+/// * `offset` - The number of ticks between the current tick and the scheduled one.
+///
+/// # Returns
+/// The tick, or why the requested one cannot be executed anymore.
+pub fn schedule_tick(requested: Option<u32>, current_tick: u32, offset: u32) -> Result<u32, String> {
+    match requested {
+        Some(tick) if tick > current_tick => Ok(tick),
+        Some(tick) => Err(format!("tick {tick} already passed, the nodes are at tick {current_tick}")),
+        None => Ok(current_tick.saturating_add(offset.max(1))),
+    }
+}
+
+/// Checks if a transfer was executed, from the entity of its source before and after its tick.
+///
+/// # Arguments
+/// * `before` - The entity before the transfer was sent.
+/// * `after` - The entity once the tick of the transfer passed.
+/// * `amount` - The amount of the transfer.
+///
+/// # Returns
+/// `true` if a transfer of at least the amount left the source since.
+pub fn is_executed(before: &Entity, after: &Entity, amount: i64) -> bool {
+    after.number_of_outgoing_transfers > before.number_of_outgoing_transfers && after.outgoing_amount - before.outgoing_amount >= amount
+}
+
+/// Sends transactions to the nodes and follows their ticks.
+#[derive(Debug, Clone)]
+pub struct TransactionSender {
+    /// The nodes, with the protocol version of each
+    peers: Vec<(String, Protocol)>,
+    socks: Option<SocksProxy>,
+    tick_offset: u32,
+    attempts: usize,
+}
+
+impl TransactionSender {
+    /// Creates a new `TransactionSender`.
+    ///
+    /// # Arguments
+    /// * `peers` - The nodes every transaction is sent to, with the protocol version of each.
+    /// * `socks` - The SOCKS proxy the nodes are reached through, `None` to connect directly.
+    /// * `tick_offset` - The number of ticks between the current tick and the tick of a transaction.
+    /// * `attempts` - The number of ticks a transaction is scheduled for before giving up.
+    ///
+    /// # Returns
+    /// A new `TransactionSender`.
+    pub fn new(peers: Vec<(String, Protocol)>, socks: Option<SocksProxy>, tick_offset: u32, attempts: usize) -> Self {
+        TransactionSender { peers, socks, tick_offset, attempts: attempts.max(1) }
+    }
+
+    /// Sends a transfer, scheduled again for a later tick until it is executed.
+    ///
+    /// # Arguments
+    /// * `identity` - The identity the amount is taken from, which signs the transfer.
+    /// * `destination_public_key` - The public key receiving the amount.
+    /// * `amount` - The amount transferred.
+    /// * `requested_tick` - The tick of the first attempt, `None` to schedule it ahead of the current tick.
+    ///
+    /// # Returns
+    /// The ID of the executed transaction, or why it was not executed.
+    pub async fn send_transfer(&self, identity: &Identity, destination_public_key: PublicKey64, amount: i64, requested_tick: Option<u32>) -> Result<String, String> {
+        let source_public_key = get_public_key_64_from_public_key(&identity.public_key);
+        for attempt in 0..self.attempts {
+            let current_tick = self.request_tick().await.ok_or("no tick info from any node")?;
+            let tick = schedule_tick(requested_tick.filter(|_| attempt == 0), current_tick, self.tick_offset)?;
+            let before = self.request_entity(source_public_key).await;
+
+            let transaction = TransactionBuilder::transfer(destination_public_key, amount, tick).sign(identity);
+            let id = transaction.get_id();
+            let sent = self.broadcast(&transaction).await;
+            if sent == 0 {
+                return Err("the transaction could not be sent to any node".to_string());
+            }
+            log::info!("Transaction {id} for tick {tick} sent to {sent} of {} nodes at tick {current_tick}", self.peers.len());
+
+            let Some(before) = before else {
+                log::warn!("No entity of the source from any node, the execution of transaction {id} is not checked");
+                return Ok(id);
+            };
+            let after = self.wait_for_tick(source_public_key, tick).await.ok_or_else(|| format!("tick {tick} did not pass within {TICK_WAIT_TIMEOUT:?}"))?;
+            if is_executed(&before, &after, amount) {
+                log::info!("Transaction {id} executed in tick {tick}, the balance of the source is {} qu", after.get_balance());
+                return Ok(id);
+            }
+            log::warn!("Transaction {id} was not executed in tick {tick}");
+        }
+        Err(format!("the transaction was not executed after {} attempts", self.attempts))
+    }
+
+    /// Writes a transaction to every node.
+    ///
+    /// # Returns
+    /// The number of nodes the transaction was written to.
+    async fn broadcast(&self, transaction: &TransactionBuilder) -> usize {
+        let mut sent = 0;
+        for (peer, protocol) in &self.peers {
+            let bytes = match transaction.to_bytes(*protocol) {
+                Ok(bytes) => bytes,
+                Err(err) => {
+                    log::error!("Failed to build the transaction: {err}");
+                    continue;
+                }
+            };
+            let result = tokio::time::timeout(REQUEST_TIMEOUT, async {
+                let mut stream = socks::connect(self.socks.as_ref(), peer).await?;
+                stream.write_all(&bytes).await?;
+                stream.flush().await
+            }).await;
+            match result {
+                Ok(Ok(())) => {
+                    capture::record_sent(peer, &bytes);
+                    sent += 1;
+                }
+                Ok(Err(err)) => log::warn!("Failed to send the transaction to {peer}: {:?}", err),
+                Err(_) => log::warn!("Failed to send the transaction to {peer} within {:?}", REQUEST_TIMEOUT),
+            }
+        }
+        sent
+    }
+
+    /// Gets the highest current tick of the nodes.
+    async fn request_tick(&self) -> Option<u32> {
+        let mut ticks = Vec::new();
+        for (peer, protocol) in &self.peers {
+            let result = tokio::time::timeout(REQUEST_TIMEOUT, async {
+                let mut stream = socks::connect(self.socks.as_ref(), peer).await?;
+                request_tick_info(&mut stream, *protocol).await
+            }).await;
+            match result {
+                Ok(Ok(tick_info)) => ticks.push(tick_info.tick),
+                Ok(Err(err)) => log::debug!("No tick info from {peer}: {:?}", err),
+                Err(_) => log::debug!("No tick info from {peer} within {:?}", REQUEST_TIMEOUT),
+            }
+        }
+        ticks.into_iter().max()
+    }
+
+    /// Gets the entity of a public key from the first node answering.
+    async fn request_entity(&self, public_key: PublicKey64) -> Option<Entity> {
+        for (peer, protocol) in &self.peers {
+            let result = tokio::time::timeout(REQUEST_TIMEOUT, async {
+                let mut stream = socks::connect(self.socks.as_ref(), peer).await?;
+                request_entity(&mut stream, public_key, *protocol).await
+            }).await;
+            match result {
+                Ok(Ok(entity)) => return Some(entity),
+                Ok(Err(err)) => log::debug!("No entity from {peer}: {:?}", err),
+                Err(_) => log::debug!("No entity from {peer} within {:?}", REQUEST_TIMEOUT),
+            }
+        }
+        None
+    }
+
+    /// Waits for a node to have processed a tick.
+    ///
+    /// # Returns
+    /// The entity of the public key after the tick, `None` if no node went past it in time.
+    async fn wait_for_tick(&self, public_key: PublicKey64, tick: u32) -> Option<Entity> {
+        let deadline = tokio::time::Instant::now() + TICK_WAIT_TIMEOUT;
+        while tokio::time::Instant::now() < deadline {
+            tokio::time::sleep(TICK_POLL_INTERVAL).await;
+            if let Some(entity) = self.request_entity(public_key).await.filter(|entity| entity.tick > tick) {
+                return Some(entity);
+            }
+        }
+        None
+    }
+}
+
+#[test]
+/// Tests the tick a transaction is scheduled for and the detection of its execution.
+fn test_schedule_transaction() {
+    assert_eq!(schedule_tick(None, 1000, DEFAULT_TICK_OFFSET), Ok(1010));
+    assert_eq!(schedule_tick(None, 1000, 0), Ok(1001));
+    assert_eq!(schedule_tick(Some(1005), 1000, DEFAULT_TICK_OFFSET), Ok(1005));
+    assert!(schedule_tick(Some(1000), 1000, DEFAULT_TICK_OFFSET).is_err());
+
+    let before = Entity { incoming_amount: 5000, outgoing_amount: 100, number_of_outgoing_transfers: 1, tick: 1000, ..Default::default() };
+    let after = Entity { outgoing_amount: 1100, number_of_outgoing_transfers: 2, latest_outgoing_transfer_tick: 1010, tick: 1011, ..before };
+    assert!(is_executed(&before, &after, 1000));
+    assert!(!is_executed(&before, &after, 1001));
+    assert!(!is_executed(&before, &Entity { tick: 1011, ..before }, 1000));
+}
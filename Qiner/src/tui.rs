@@ -0,0 +1,210 @@
+use std::collections::VecDeque;
+use std::io;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use ratatui::crossterm::event::{self, Event, KeyCode, KeyEventKind};
+use ratatui::layout::{Constraint, Layout, Rect};
+use ratatui::style::{Color, Style, Stylize};
+use ratatui::text::Line;
+use ratatui::widgets::{Block, Borders, Paragraph, Sparkline};
+use ratatui::{DefaultTerminal, Frame};
+use crate::estimator::{expected_time_between_solutions, format_duration};
+use crate::miner::{IterationMarker, Miner};
+use lib::types::network::Protocol;
+use crate::network::NetStats;
+use crate::submission::SubmissionStats;
+
+/// Interval between two samples of the miner counters.
+const SAMPLE_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Number of samples kept for every sparkline.
+const HISTORY_LENGTH: usize = 120;
+
+/// Live dashboard replacing the log output in `--tui` mode.
+pub struct Dashboard {
+    miner: Arc<Miner>,
+    net_stats: Arc<NetStats>,
+    submission_stats: Arc<SubmissionStats>,
+    addr: String,
+    own_protocol: Protocol,
+    started_at: Instant,
+    marker: IterationMarker,
+    thread_history: Vec<VecDeque<u64>>,
+}
+
+impl Dashboard {
+    /// Creates a new `Dashboard`.
+    ///
+    /// # Arguments
+    /// * `miner` - Shared reference to the Miner instance.
+    /// * `net_stats` - Shared network counters.
+    /// * `submission_stats` - Shared totals of the submitted solutions of the epoch.
+    /// * `addr` - Address of the server, as `ip:port`.
+    /// * `own_protocol` - The protocol version solutions are sent with.
+    ///
+    /// # Returns
+    /// A new `Dashboard`.
+    pub fn new(miner: Arc<Miner>, net_stats: Arc<NetStats>, submission_stats: Arc<SubmissionStats>, addr: String, own_protocol: Protocol) -> Self {
+        let marker = miner.marker();
+        let thread_history = vec![VecDeque::with_capacity(HISTORY_LENGTH); miner.get_num_threads()];
+
+        Dashboard {
+            miner,
+            net_stats,
+            submission_stats,
+            addr,
+            own_protocol,
+            started_at: Instant::now(),
+            marker,
+            thread_history,
+        }
+    }
+
+    /// Runs the dashboard until the user quits.
+    ///
+    /// Blocks the current thread, so it must be run on the blocking pool.
+    ///
+    /// # Returns
+    /// `Ok(())` once the user pressed `q`, or the terminal error.
+    pub fn run(mut self) -> io::Result<()> {
+        let mut terminal = ratatui::init();
+        let result = self.event_loop(&mut terminal);
+        ratatui::restore();
+        result
+    }
+
+    /// Draws the dashboard and handles key presses.
+    fn event_loop(&mut self, terminal: &mut DefaultTerminal) -> io::Result<()> {
+        loop {
+            if self.marker.elapsed() >= SAMPLE_INTERVAL {
+                self.sample();
+            }
+
+            terminal.draw(|frame| self.draw(frame))?;
+
+            let timeout = SAMPLE_INTERVAL.saturating_sub(self.marker.elapsed());
+            if !event::poll(timeout)? {
+                continue;
+            }
+
+            if let Event::Key(key) = event::read()? {
+                if key.kind != KeyEventKind::Press {
+                    continue;
+                }
+
+                match key.code {
+                    KeyCode::Char('q') | KeyCode::Esc => return Ok(()),
+                    KeyCode::Char('p') | KeyCode::Char(' ') => self.miner.set_paused(!self.miner.is_paused()),
+                    KeyCode::Char('+') | KeyCode::Up => self.miner.set_active_threads(self.miner.get_active_threads() + 1),
+                    KeyCode::Char('-') | KeyCode::Down => {
+                        self.miner.set_active_threads(self.miner.get_active_threads().saturating_sub(1))
+                    }
+                    _ => {}
+                }
+            }
+        }
+    }
+
+    /// Samples the per-thread iteration counters into the sparkline history.
+    fn sample(&mut self) {
+        let delta = self.miner.iterations_since(&mut self.marker);
+
+        for (history, it_per_sec) in self.thread_history.iter_mut().zip(delta.thread_it_per_sec()) {
+            if history.len() == HISTORY_LENGTH {
+                history.pop_front();
+            }
+            history.push_back(it_per_sec.round() as u64);
+        }
+    }
+
+    /// Draws the whole dashboard.
+    fn draw(&self, frame: &mut Frame) {
+        let [header_area, stats_area, threads_area, footer_area] = Layout::vertical([
+            Constraint::Length(3),
+            Constraint::Length(5),
+            Constraint::Min(0),
+            Constraint::Length(1),
+        ]).areas(frame.area());
+
+        self.draw_header(frame, header_area);
+        self.draw_stats(frame, stats_area);
+        self.draw_threads(frame, threads_area);
+
+        frame.render_widget(
+            Paragraph::new(" q quit | p pause/resume | +/- active threads").style(Style::default().fg(Color::DarkGray)),
+            footer_area,
+        );
+    }
+
+    /// Draws the mining state and uptime.
+    fn draw_header(&self, frame: &mut Frame, area: Rect) {
+        let miner = self.miner.snapshot();
+        let state = if miner.is_paused { "PAUSED".yellow().bold() } else { "MINING".green().bold() };
+        let uptime = self.started_at.elapsed().as_secs();
+
+        let line = Line::from(vec![
+            state,
+            format!(
+                " | threads {}/{} | uptime {:02}:{:02}:{:02}",
+                miner.active_threads,
+                miner.thread_iterations.len(),
+                uptime / 3600,
+                uptime / 60 % 60,
+                uptime % 60
+            ).into(),
+        ]);
+
+        frame.render_widget(Paragraph::new(line).block(Block::default().borders(Borders::ALL).title(" Qiner ")), area);
+    }
+
+    /// Draws the solution and peer counters.
+    fn draw_stats(&self, frame: &mut Frame, area: Rect) {
+        let miner = self.miner.snapshot();
+        let total_it_per_sec: u64 = self.thread_history.iter().filter_map(|history| history.back()).sum();
+        let sent_scores = self.submission_stats.get_sent();
+        let connect_attempts = self.net_stats.get_connect_attempts();
+        let connect_failures = self.net_stats.get_connect_failures();
+        let node_protocol = self.net_stats.get_node_protocol();
+        let is_node_newer = node_protocol.is_some_and(|protocol| protocol > self.own_protocol);
+        let average_it_per_sec = miner.iterations as f64 / self.started_at.elapsed().as_secs_f64();
+        let eta = expected_time_between_solutions(miner.solution_threshold, average_it_per_sec)
+            .map(format_duration)
+            .unwrap_or_else(|| "-".to_string());
+
+        let lines = vec![
+            Line::from(format!("{} it/s | {} iterations", total_it_per_sec, miner.iterations)),
+            Line::from(format!("solutions found {} | sent {} | expected 1 every {}", miner.solutions_found, sent_scores, eta)),
+            Line::from(format!(
+                "peer {} | {} connects ({} failed) | found->sent avg {} ms | node protocol {}",
+                self.addr,
+                connect_attempts,
+                connect_failures,
+                self.net_stats.get_average_latency().as_millis(),
+                node_protocol.map_or("?".to_string(), |protocol| protocol.to_string())
+            )).style(if is_node_newer { Style::default().fg(Color::Red) } else { Style::default() }),
+        ];
+
+        frame.render_widget(Paragraph::new(lines).block(Block::default().borders(Borders::ALL).title(" Stats ")), area);
+    }
+
+    /// Draws one it/s sparkline per mining thread.
+    fn draw_threads(&self, frame: &mut Frame, area: Rect) {
+        let block = Block::default().borders(Borders::ALL).title(" Threads (it/s) ");
+        let inner = block.inner(area);
+        frame.render_widget(block, area);
+
+        let rows = Layout::vertical(vec![Constraint::Length(1); self.thread_history.len()]).split(inner);
+        for (idx, (history, row)) in self.thread_history.iter().zip(rows.iter()).enumerate() {
+            let [label_area, sparkline_area] = Layout::horizontal([Constraint::Length(14), Constraint::Min(0)]).areas(*row);
+
+            let is_active = idx < self.miner.get_active_threads();
+            let style = if is_active { Style::default().fg(Color::Cyan) } else { Style::default().fg(Color::DarkGray) };
+
+            // Keep the most recent samples that fit in the row
+            let skip = history.len().saturating_sub(sparkline_area.width as usize);
+            let data = history.iter().skip(skip).copied().collect::<Vec<u64>>();
+            frame.render_widget(Paragraph::new(format!("#{:<3}{:>6}", idx, history.back().unwrap_or(&0))), label_area);
+            frame.render_widget(Sparkline::default().data(&data).style(style), sparkline_area);
+        }
+    }
+}
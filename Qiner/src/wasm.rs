@@ -0,0 +1,50 @@
+//! JavaScript bindings of the ID conversions and the scoring, for web wallets and explorers.
+//!
+//! Built for wasm32 with `--no-default-features --features wasm`, see the README. Keys, nonces
+//! and seeds are passed as 32-byte `Uint8Array`s, in the order they are sent over the network.
+
+use wasm_bindgen::prelude::*;
+use lib::params::AlgorithmParams;
+use lib::types::{Id, PublicKey, PublicKey64};
+use crate::converters::{get_id_from_public_key_64, get_public_key_64_from_public_key, get_public_key_from_public_key_64, parse_id};
+use crate::score;
+
+/// Reads 32 bytes as little endian 64-bit words.
+///
+/// # Arguments
+/// * `name` - The name of the argument, for the error
+/// * `bytes` - The bytes given by JavaScript
+///
+/// # Returns
+/// The words, or why the bytes are not 32 bytes long.
+fn read_words(name: &str, bytes: &[u8]) -> Result<PublicKey64, JsError> {
+    let bytes: &PublicKey = bytes.try_into().map_err(|_| JsError::new(&format!("{name} must be 32 bytes, got {}", bytes.len())))?;
+    Ok(get_public_key_64_from_public_key(bytes))
+}
+
+/// Converts an ID to its public key, checking its checksum.
+///
+/// # Returns
+/// The 32 bytes of the public key, or `undefined` if the ID is malformed or its checksum does not match.
+#[wasm_bindgen(js_name = idToPublicKey)]
+pub fn id_to_public_key(id: &str) -> Option<Vec<u8>> {
+    parse_id(id).map(|public_key| get_public_key_from_public_key_64(&public_key).to_vec())
+}
+
+/// Converts the 32 bytes of a public key to its ID.
+#[wasm_bindgen(js_name = publicKeyToId)]
+pub fn public_key_to_id(public_key: &[u8]) -> Result<String, JsError> {
+    let mut id: Id = [0; 60];
+    get_id_from_public_key_64(&read_words("the public key", public_key)?, &mut id);
+    Ok(String::from_utf8_lossy(&id).into_owned())
+}
+
+/// Computes the score of a nonce, without threads.
+///
+/// The current network needs `numberOfNeurons = 4194304` and `miningDataLength = 1024`.
+#[wasm_bindgen(js_name = scoreNonce)]
+pub fn score_nonce(public_key: &[u8], nonce: &[u8], random_seed: &[u8], number_of_neurons: usize, mining_data_length: usize) -> Result<usize, JsError> {
+    let params = AlgorithmParams { number_of_neurons, mining_data_length };
+    score::score_nonce(&read_words("the public key", public_key)?, &read_words("the nonce", nonce)?, &read_words("the random seed", random_seed)?, &params)
+        .map_err(|err| JsError::new(&err))
+}
@@ -0,0 +1,271 @@
+use std::env;
+use std::fmt::Display;
+use std::fs;
+use std::io::{self, BufRead, Write};
+use std::net::{SocketAddr, TcpStream};
+use std::path::Path;
+use std::time::Duration;
+use lib::env_names::{ENV_ID, ENV_NUMBER_OF_THREADS, ENV_RANDOM_SEED, ENV_SERVER_IP, ENV_SERVER_PORT, ENV_SOLUTION_THRESHOLD, ENV_VERSION};
+use lib::random_seed::parse_random_seed;
+use lib::types::PORT;
+use lib::version::{DEFAULT_VERSION, format_version, parse_version};
+use crate::converters::parse_id;
+use crate::cpu_quota::get_available_cpus;
+use crate::identity::{derive_identity, generate_seed};
+use crate::peers::PEERS_SPLIT_CHAR;
+
+/// Default file the configuration is written to, the file Qiner loads on start.
+pub const DEFAULT_CONFIG_FILE: &str = ".env";
+
+/// Timeout of the connectivity probe of every node.
+const PROBE_TIMEOUT: Duration = Duration::from_secs(3);
+
+/// Asks the user for the configuration on the terminal and writes it to a file.
+///
+/// Values already in the environment (e.g. loaded from an existing `.env`) are offered
+/// as defaults.
+///
+/// # Arguments
+/// * `path` - The configuration file to write.
+///
+/// # Returns
+/// `true` if the configuration was written, `false` if the user cancelled.
+pub fn run_init(path: &Path) -> io::Result<bool> {
+    let stdin = io::stdin();
+    let mut input = stdin.lock();
+    let mut output = io::stdout();
+
+    if path.exists() && !ask_yes_no(&mut input, &mut output, &format!("{} already exists, overwrite it?", path.display()), false)? {
+        return Ok(false);
+    }
+
+    let entries = ask_config(&mut input, &mut output, probe_node)?;
+    fs::write(path, render_config(&entries))?;
+    writeln!(output, "Configuration written to {}, start mining with `qiner`", path.display())?;
+    Ok(true)
+}
+
+/// Asks every configuration value, validating it before moving on.
+///
+/// # Arguments
+/// * `input` - Where the answers are read from.
+/// * `output` - Where the questions are written to.
+/// * `probe` - Checks if a node accepts connections, given its `ip:port`.
+///
+/// # Returns
+/// The configuration entries, as variable names and values.
+pub fn ask_config<R: BufRead, W: Write>(input: &mut R, output: &mut W, probe: impl Fn(&str) -> bool) -> io::Result<Vec<(&'static str, String)>> {
+    let default = |name: &str, fallback: String| env::var(name).ok().filter(|value| !value.trim().is_empty()).unwrap_or(fallback);
+
+    let mut id = ask_valid(input, output, "Computor ID (60 uppercase letters, or `generate` for a new one)", &default(ENV_ID, String::new()), |value| {
+        match value.trim() {
+            "generate" => Ok(value.trim().to_string()),
+            value => parse_id(value).map(|_| value.to_string()).ok_or("invalid ID or checksum"),
+        }
+    })?;
+    if id == "generate" {
+        let seed = generate_seed();
+        let identity = derive_identity(seed.expose()).expect("generated seeds are valid");
+        id = String::from_utf8_lossy(&identity.id).to_string();
+        writeln!(output, "Seed: {}", seed.as_str())?;
+        writeln!(output, "Identity: {id}")?;
+        writeln!(output, "Write the seed down and keep it secret, it is not saved and is the only way to use this identity")?;
+    }
+
+    let (server_ip, server_port) = loop {
+        let server_ip = ask_valid(input, output, "Node IPs, comma-separated", &default(ENV_SERVER_IP, String::new()), |value| {
+            let ips = value.split(PEERS_SPLIT_CHAR).map(str::trim).filter(|ip| !ip.is_empty()).collect::<Vec<_>>();
+            (!ips.is_empty()).then(|| ips.join(",")).ok_or("at least one IP is required")
+        })?;
+        let server_port = ask_valid(input, output, "Node port", &default(ENV_SERVER_PORT, PORT.to_string()), |value| {
+            value.trim().parse::<u16>().map(|port| port.to_string()).map_err(|_| "not a port number")
+        })?;
+
+        let unreachable = server_ip.split(PEERS_SPLIT_CHAR)
+            .map(|ip| format!("{ip}:{server_port}"))
+            .filter(|addr| !probe(addr))
+            .collect::<Vec<_>>();
+        if unreachable.is_empty() {
+            break (server_ip, server_port);
+        }
+
+        writeln!(output, "Could not connect to {}", unreachable.join(", "))?;
+        if ask_yes_no(input, output, "Keep these nodes anyway?", false)? {
+            break (server_ip, server_port);
+        }
+    };
+
+    let number_of_threads = ask_valid(input, output, "Number of threads", &default(ENV_NUMBER_OF_THREADS, get_available_cpus().to_string()), |value| {
+        value.trim().parse::<usize>().ok().filter(|threads| *threads > 0).map(|threads| threads.to_string()).ok_or("a positive number is required")
+    })?;
+
+    // An empty version keeps following the version built into Qiner
+    let question = format!("Qubic version, empty for the built-in {}", format_version(&DEFAULT_VERSION));
+    let version = ask_valid(input, output, &question, &default(ENV_VERSION, String::new()), |value| {
+        match value.trim() {
+            "" => Ok(String::new()),
+            value => parse_version(value).map(|_| value.to_string()).ok_or("expected 3 numbers separated by dots"),
+        }
+    })?;
+
+    let random_seed = ask_valid(input, output, "Random seed (comma-separated numbers, 0x hex or base64)", &default(ENV_RANDOM_SEED, String::new()), |value| {
+        parse_random_seed(value).map(|_| value.trim().to_string())
+    })?;
+
+    let solution_threshold = ask_valid(input, output, "Solution threshold", &default(ENV_SOLUTION_THRESHOLD, String::new()), |value| {
+        value.trim().parse::<usize>().map(|threshold| threshold.to_string()).map_err(|_| "not a number")
+    })?;
+
+    Ok(vec![
+        (ENV_ID, id),
+        (ENV_SERVER_IP, server_ip),
+        (ENV_SERVER_PORT, server_port),
+        (ENV_NUMBER_OF_THREADS, number_of_threads),
+        (ENV_VERSION, version),
+        (ENV_RANDOM_SEED, random_seed),
+        (ENV_SOLUTION_THRESHOLD, solution_threshold),
+    ])
+}
+
+/// Renders configuration entries in the `.env` format.
+///
+/// # Arguments
+/// * `entries` - The variable names and values.
+///
+/// # Returns
+/// One `NAME=value` line per entry, entries with an empty value being left out.
+pub fn render_config(entries: &[(&str, String)]) -> String {
+    entries.iter().filter(|(_, value)| !value.is_empty()).map(|(name, value)| format!("{name}={value}\n")).collect()
+}
+
+/// Sets a variable in a configuration file, replacing its line or appending one.
+///
+/// # Arguments
+/// * `path` - The configuration file, created if it does not exist.
+/// * `name` - The variable name.
+/// * `value` - The variable value.
+pub fn set_config_value(path: &Path, name: &str, value: &str) -> io::Result<()> {
+    let content = match fs::read_to_string(path) {
+        Ok(content) => content,
+        Err(err) if err.kind() == io::ErrorKind::NotFound => String::new(),
+        Err(err) => return Err(err),
+    };
+
+    let line = format!("{name}={value}");
+    let mut is_replaced = false;
+    let mut lines = content.lines().map(|existing| {
+        let is_match = existing.split_once('=').is_some_and(|(existing_name, _)| existing_name.trim() == name);
+        if is_match && !is_replaced {
+            is_replaced = true;
+            return line.clone();
+        }
+        existing.to_string()
+    }).collect::<Vec<_>>();
+    if !is_replaced {
+        lines.push(line);
+    }
+
+    fs::write(path, lines.join("\n") + "\n")
+}
+
+/// Checks if a node accepts connections.
+///
+/// # Arguments
+/// * `addr` - The address of the node, as `ip:port`.
+pub fn probe_node(addr: &str) -> bool {
+    addr.parse::<SocketAddr>().is_ok_and(|addr| TcpStream::connect_timeout(&addr, PROBE_TIMEOUT).is_ok())
+}
+
+/// Asks a question until the answer is valid, an empty answer taking the default.
+///
+/// # Arguments
+/// * `input` - Where the answer is read from.
+/// * `output` - Where the question is written to.
+/// * `question` - The question.
+/// * `default` - The default answer, empty for none.
+/// * `validate` - Normalizes a valid answer, or explains why it is invalid.
+///
+/// # Returns
+/// The normalized answer, or an error if the input ended.
+fn ask_valid<R: BufRead, W: Write, E: Display>(
+    input: &mut R,
+    output: &mut W,
+    question: &str,
+    default: &str,
+    validate: impl Fn(&str) -> Result<String, E>,
+) -> io::Result<String> {
+    loop {
+        let answer = ask(input, output, question, default)?;
+        match validate(&answer) {
+            Ok(value) => return Ok(value),
+            Err(reason) => writeln!(output, "Invalid value: {reason}")?,
+        }
+    }
+}
+
+/// Asks a yes/no question.
+fn ask_yes_no<R: BufRead, W: Write>(input: &mut R, output: &mut W, question: &str, default: bool) -> io::Result<bool> {
+    let answer = ask(input, output, &format!("{question} (y/n)"), if default { "y" } else { "n" })?;
+    Ok(answer.trim().eq_ignore_ascii_case("y") || answer.trim().eq_ignore_ascii_case("yes"))
+}
+
+/// Asks a question, an empty answer taking the default.
+fn ask<R: BufRead, W: Write>(input: &mut R, output: &mut W, question: &str, default: &str) -> io::Result<String> {
+    if default.is_empty() {
+        write!(output, "{question}: ")?;
+    } else {
+        write!(output, "{question} [{default}]: ")?;
+    }
+    output.flush()?;
+
+    let mut answer = String::new();
+    if input.read_line(&mut answer)? == 0 {
+        return Err(io::Error::new(io::ErrorKind::UnexpectedEof, "no more input"));
+    }
+
+    let answer = answer.trim();
+    Ok(if answer.is_empty() { default.to_string() } else { answer.to_string() })
+}
+
+#[test]
+/// Tests that invalid answers are asked again and unreachable nodes need a confirmation.
+fn test_ask_config() {
+    let answers = [
+        "UBAZRCVPOZTDKGCBNPGYFUPLZXDDNHSEGJRTAJKWJBHJDKHMAKVVFAKCZGRA",
+        "UBAZRCVPOZTDKGCBNPGYFUPLZXDDNHSEGJRTAJKWJBHJDKHMAKVVFAKCZGRI",
+        "10.0.0.1, 10.0.0.2",
+        "21841",
+        "y",
+        "0",
+        "4",
+        "1.142",
+        "1.142.1",
+        "1,0,233,9,136,69,43,139",
+        "22",
+    ];
+    let mut input = io::Cursor::new(answers.join("\n") + "\n");
+    let mut output = Vec::new();
+
+    let entries = ask_config(&mut input, &mut output, |addr| addr == "10.0.0.1:21841").unwrap();
+    let output = String::from_utf8(output).unwrap();
+    assert_eq!(output.matches("Invalid value").count(), 3);
+    assert!(output.contains("Could not connect to 10.0.0.2:21841"));
+
+    let config = render_config(&entries);
+    assert!(config.contains("SERVER_IP=10.0.0.1,10.0.0.2\n"));
+    assert!(config.contains("NUMBER_OF_THREADS=4\n"));
+    assert!(config.contains("VERSION=1.142.1\n"));
+}
+
+#[test]
+/// Tests that setting a value replaces its line and keeps the others.
+fn test_set_config_value() {
+    let path = env::temp_dir().join(format!("qiner-config-test-{}.env", std::process::id()));
+    fs::write(&path, "RUST_LOG=info\nID=OLD\n").unwrap();
+
+    set_config_value(&path, "ID", "NEW").unwrap();
+    set_config_value(&path, "VERSION", "1.142.1").unwrap();
+    assert_eq!(fs::read_to_string(&path).unwrap(), "RUST_LOG=info\nID=NEW\nVERSION=1.142.1\n");
+
+    let _ = fs::remove_file(&path);
+}
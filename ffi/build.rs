@@ -0,0 +1,19 @@
+//! Generates `include/qiner.h`, the C header of the exported functions.
+
+use std::env;
+use std::path::PathBuf;
+
+fn main() {
+    let crate_dir = PathBuf::from(env::var("CARGO_MANIFEST_DIR").unwrap());
+    println!("cargo:rerun-if-changed=src/lib.rs");
+    println!("cargo:rerun-if-changed=cbindgen.toml");
+
+    let config = cbindgen::Config::from_file(crate_dir.join("cbindgen.toml")).expect("invalid cbindgen.toml");
+    match cbindgen::generate_with_config(&crate_dir, config) {
+        Ok(bindings) => {
+            bindings.write_to_file(crate_dir.join("include").join("qiner.h"));
+        }
+        // A syntax error is reported by rustc with a better message
+        Err(err) => println!("cargo:warning=failed to generate the C header: {err}"),
+    }
+}
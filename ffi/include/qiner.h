@@ -0,0 +1,67 @@
+/* Generated by cbindgen from ffi/src/lib.rs, do not edit */
+
+#ifndef QINER_H
+#define QINER_H
+
+#include <stdarg.h>
+#include <stdbool.h>
+#include <stddef.h>
+#include <stdint.h>
+#include <stdlib.h>
+
+// Number of neurons of the current network, for `qiner_score`.
+#define QINER_NUMBER_OF_NEURONS 4194304
+
+// Number of 64-bit words of mining data of the current network, for `qiner_score`.
+#define QINER_MINING_DATA_LENGTH 1024
+
+// Number of letters of an ID, `qiner_pubkey_to_id` writes one more for the terminating NUL.
+#define QINER_ID_LENGTH 60
+
+// Computes the score of a nonce.
+//
+// # Arguments
+// * `public_key` - The 32 bytes of the public key of the computor.
+// * `nonce` - The 32 bytes of the nonce.
+// * `random_seed` - The 32 bytes of the random seed of the epoch.
+// * `number_of_neurons` - `QINER_NUMBER_OF_NEURONS`, or a power of two for test networks.
+// * `mining_data_length` - `QINER_MINING_DATA_LENGTH`, or the length of a test network.
+//
+// # Returns
+// The score, or -1 if a pointer is null or the parameters are invalid.
+//
+// # Safety
+// The pointers must be null or point to 32 readable bytes.
+int64_t qiner_score(const uint8_t *public_key,
+                    const uint8_t *nonce,
+                    const uint8_t *random_seed,
+                    size_t number_of_neurons,
+                    size_t mining_data_length);
+
+// Converts an ID to its public key, checking its checksum.
+//
+// # Arguments
+// * `id` - The NUL-terminated ID, 60 uppercase letters.
+// * `public_key` - The 32 bytes receiving the public key.
+//
+// # Returns
+// `true` if the ID is valid, `false` without writing the public key otherwise.
+//
+// # Safety
+// `id` must be null or a NUL-terminated string, `public_key` null or 32 writable bytes.
+bool qiner_id_to_pubkey(const char *id, uint8_t *public_key);
+
+// Converts a public key to its ID.
+//
+// # Arguments
+// * `public_key` - The 32 bytes of the public key.
+// * `id` - The `QINER_ID_LENGTH + 1` bytes receiving the NUL-terminated ID.
+//
+// # Returns
+// `false` if a pointer is null.
+//
+// # Safety
+// `public_key` must be null or point to 32 readable bytes, `id` null or 61 writable bytes.
+bool qiner_pubkey_to_id(const uint8_t *public_key, char *id);
+
+#endif  /* QINER_H */
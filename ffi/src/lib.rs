@@ -0,0 +1,144 @@
+//! C bindings of the scoring and the ID conversions.
+//!
+//! Built as `libqiner_ffi`, with the header generated in `include/qiner.h`, so C and C++ Qubic
+//! tooling verifies nonces and identities with the same code as the miner. Keys, nonces and
+//! seeds are passed as 32 bytes, in the order they are sent over the network.
+
+use std::ffi::{c_char, CStr};
+use std::slice;
+use lib::params::AlgorithmParams;
+use lib::types::{Id, PublicKey};
+use qiner::converters::{get_id_from_public_key_64, get_public_key_64_from_public_key, get_public_key_from_public_key_64, parse_id};
+use qiner::score;
+
+// The constants are literals, cbindgen does not resolve the ones of `lib`
+
+/// Number of neurons of the current network, for `qiner_score`.
+pub const QINER_NUMBER_OF_NEURONS: usize = 4_194_304;
+
+/// Number of 64-bit words of mining data of the current network, for `qiner_score`.
+pub const QINER_MINING_DATA_LENGTH: usize = 1024;
+
+/// Number of letters of an ID, `qiner_pubkey_to_id` writes one more for the terminating NUL.
+pub const QINER_ID_LENGTH: usize = 60;
+
+/// Reads 32 bytes as little endian 64-bit words.
+///
+/// # Safety
+/// `bytes` must be null or point to 32 readable bytes.
+unsafe fn read_words(bytes: *const u8) -> Option<[u64; 4]> {
+    let bytes: &PublicKey = slice::from_raw_parts(bytes.as_ref()?, 32).try_into().ok()?;
+    Some(get_public_key_64_from_public_key(bytes))
+}
+
+/// Computes the score of a nonce.
+///
+/// # Arguments
+/// * `public_key` - The 32 bytes of the public key of the computor.
+/// * `nonce` - The 32 bytes of the nonce.
+/// * `random_seed` - The 32 bytes of the random seed of the epoch.
+/// * `number_of_neurons` - `QINER_NUMBER_OF_NEURONS`, or a power of two for test networks.
+/// * `mining_data_length` - `QINER_MINING_DATA_LENGTH`, or the length of a test network.
+///
+/// # Returns
+/// The score, or -1 if a pointer is null or the parameters are invalid.
+///
+/// # Safety
+/// The pointers must be null or point to 32 readable bytes.
+#[no_mangle]
+pub unsafe extern "C" fn qiner_score(public_key: *const u8, nonce: *const u8, random_seed: *const u8, number_of_neurons: usize, mining_data_length: usize) -> i64 {
+    let (Some(public_key), Some(nonce), Some(random_seed)) = (read_words(public_key), read_words(nonce), read_words(random_seed)) else {
+        return -1;
+    };
+    let params = AlgorithmParams { number_of_neurons, mining_data_length };
+    score::score_nonce(&public_key, &nonce, &random_seed, &params).map_or(-1, |score| score as i64)
+}
+
+/// Converts an ID to its public key, checking its checksum.
+///
+/// # Arguments
+/// * `id` - The NUL-terminated ID, 60 uppercase letters.
+/// * `public_key` - The 32 bytes receiving the public key.
+///
+/// # Returns
+/// `true` if the ID is valid, `false` without writing the public key otherwise.
+///
+/// # Safety
+/// `id` must be null or a NUL-terminated string, `public_key` null or 32 writable bytes.
+#[no_mangle]
+pub unsafe extern "C" fn qiner_id_to_pubkey(id: *const c_char, public_key: *mut u8) -> bool {
+    if id.is_null() || public_key.is_null() {
+        return false;
+    }
+    let Some(public_key_64) = CStr::from_ptr(id).to_str().ok().and_then(parse_id) else {
+        return false;
+    };
+
+    slice::from_raw_parts_mut(public_key, 32).copy_from_slice(&get_public_key_from_public_key_64(&public_key_64));
+    true
+}
+
+/// Converts a public key to its ID.
+///
+/// # Arguments
+/// * `public_key` - The 32 bytes of the public key.
+/// * `id` - The `QINER_ID_LENGTH + 1` bytes receiving the NUL-terminated ID.
+///
+/// # Returns
+/// `false` if a pointer is null.
+///
+/// # Safety
+/// `public_key` must be null or point to 32 readable bytes, `id` null or 61 writable bytes.
+#[no_mangle]
+pub unsafe extern "C" fn qiner_pubkey_to_id(public_key: *const u8, id: *mut c_char) -> bool {
+    let Some(public_key) = read_words(public_key) else {
+        return false;
+    };
+    if id.is_null() {
+        return false;
+    }
+
+    let mut letters: Id = [0; QINER_ID_LENGTH];
+    get_id_from_public_key_64(&public_key, &mut letters);
+    let id = slice::from_raw_parts_mut(id as *mut u8, QINER_ID_LENGTH + 1);
+    id[..QINER_ID_LENGTH].copy_from_slice(&letters);
+    id[QINER_ID_LENGTH] = 0;
+    true
+}
+
+#[test]
+/// Tests the round trip of the ID conversions and that the score matches the miner.
+fn test_ffi() {
+    use qiner::score::{NeuronData, Scorer};
+
+    assert_eq!(AlgorithmParams { number_of_neurons: QINER_NUMBER_OF_NEURONS, mining_data_length: QINER_MINING_DATA_LENGTH }, AlgorithmParams::CURRENT);
+
+    let public_key: PublicKey = std::array::from_fn(|idx| idx as u8 * 7);
+    let mut id = [0 as c_char; QINER_ID_LENGTH + 1];
+    let mut round_trip = [0u8; 32];
+    unsafe {
+        assert!(qiner_pubkey_to_id(public_key.as_ptr(), id.as_mut_ptr()));
+        assert!(qiner_id_to_pubkey(id.as_ptr(), round_trip.as_mut_ptr()));
+        assert!(!qiner_pubkey_to_id(std::ptr::null(), id.as_mut_ptr()));
+    }
+    assert_eq!(round_trip, public_key);
+    assert_eq!(id[QINER_ID_LENGTH], 0);
+
+    // A wrong checksum letter is rejected
+    id[QINER_ID_LENGTH - 1] = if id[QINER_ID_LENGTH - 1] == b'A' as c_char { b'B' } else { b'A' } as c_char;
+    assert!(!unsafe { qiner_id_to_pubkey(id.as_ptr(), round_trip.as_mut_ptr()) });
+
+    let nonce = [3u8; 32];
+    let random_seed = [9u8; 32];
+    let params = AlgorithmParams { number_of_neurons: 4096, mining_data_length: 4 };
+    let scorer = Scorer::new(get_public_key_64_from_public_key(&public_key), params, Scorer::generate_mining_data(&[0x0909_0909_0909_0909; 4], &params));
+    let mut neuron_data = NeuronData::new(&params);
+    scorer.expand_links(&[0x0303_0303_0303_0303; 4], &mut neuron_data);
+    let expected = scorer.score(&mut neuron_data) as i64;
+
+    unsafe {
+        assert_eq!(qiner_score(public_key.as_ptr(), nonce.as_ptr(), random_seed.as_ptr(), 4096, 4), expected);
+        assert_eq!(qiner_score(public_key.as_ptr(), nonce.as_ptr(), random_seed.as_ptr(), 4000, 4), -1);
+        assert_eq!(qiner_score(public_key.as_ptr(), std::ptr::null(), random_seed.as_ptr(), 4096, 4), -1);
+    }
+}
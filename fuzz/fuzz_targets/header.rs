@@ -0,0 +1,22 @@
+//! Message headers, the first bytes read from a node or a LAN miner.
+
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use qiner::network::RequestResponseHeader;
+
+fuzz_target!(|data: &[u8]| {
+    let Some(header) = RequestResponseHeader::from_bytes(data) else {
+        assert!(data.len() < size_of::<RequestResponseHeader>());
+        return;
+    };
+
+    // The size is the first 3 bytes, little endian, as the readers of the packets decode it
+    let size = data[0] as usize | (data[1] as usize) << 8 | (data[2] as usize) << 16;
+    assert_eq!(header.get_size(), size);
+    assert_eq!(header.get_protocol(), data[3]);
+    assert_eq!(header.get_type(), data[7]);
+    assert_eq!(header.is_dejavu_zero(), data[4..7] == [0; 3]);
+    // The bytes of the header read back unchanged
+    assert_eq!(header.to_bytes(), data[..size_of::<RequestResponseHeader>()]);
+});
@@ -0,0 +1,25 @@
+//! Identities, public keys and seeds given to `qiner convert`, `qiner init` or as `ID`.
+
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use lib::types::Id;
+use qiner::converters::{get_id_from_public_key_64, parse_id};
+use qiner::identity::convert_key;
+
+fuzz_target!(|data: &[u8]| {
+    let Ok(value) = std::str::from_utf8(data) else {
+        return;
+    };
+
+    // An ID with a valid checksum is the ID of its public key
+    if let Some(public_key) = parse_id(value) {
+        let mut id: Id = [0; 60];
+        get_id_from_public_key_64(&public_key, &mut id);
+        assert_eq!(&id[..], value.trim().as_bytes());
+    }
+
+    if let Ok(forms) = convert_key(value) {
+        assert!(parse_id(&forms.id).is_some());
+    }
+});
@@ -0,0 +1,19 @@
+//! Solution packets, as read by the proxy and the mock node from any peer.
+
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use qiner::network::Packet;
+
+fuzz_target!(|data: &[u8]| {
+    let Some(protocol) = data.first().copied() else {
+        return;
+    };
+    let bytes = &data[1..];
+
+    match Packet::validate_solution(bytes, Some(protocol)) {
+        // A valid packet decodes to the same solution whatever the expected protocol
+        Ok(solution) => assert_eq!(Packet::decode_solution(bytes), Some(solution)),
+        Err(reason) => assert!(!reason.is_empty()),
+    }
+});
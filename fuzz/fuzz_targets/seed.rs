@@ -0,0 +1,18 @@
+//! The random seed of `RANDOM_SEED` and the 55-letter seeds identities are derived from.
+
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use lib::random_seed::parse_random_seed;
+use qiner::converters::parse_id;
+use qiner::identity::derive_identity;
+
+fuzz_target!(|data: &[u8]| {
+    if let Ok(value) = std::str::from_utf8(data) {
+        let _ = parse_random_seed(value);
+    }
+
+    if let Some(identity) = derive_identity(data) {
+        assert!(parse_id(std::str::from_utf8(&identity.id).unwrap()).is_some());
+    }
+});
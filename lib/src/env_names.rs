@@ -1,7 +1,73 @@
 pub const ENV_NUMBER_OF_THREADS: &str = "NUMBER_OF_THREADS";
+pub const ENV_PRIORITY: &str = "PRIORITY";
 pub const ENV_ID: &str = "ID";
 pub const ENV_SERVER_IP: &str = "SERVER_IP";
 pub const ENV_SERVER_PORT: &str = "SERVER_PORT";
 pub const ENV_VERSION: &str = "VERSION";
 pub const ENV_RANDOM_SEED: &str = "RANDOM_SEED";
 pub const ENV_SOLUTION_THRESHOLD: &str = "SOLUTION_THRESHOLD";
+pub const ENV_SOLUTION_MAX_AGE: &str = "SOLUTION_MAX_AGE";
+pub const ENV_SOLUTION_MAX_IN_MEMORY: &str = "SOLUTION_MAX_IN_MEMORY";
+pub const ENV_NOTIFY_WEBHOOK_URL: &str = "NOTIFY_WEBHOOK_URL";
+pub const ENV_NOTIFY_TELEGRAM_TOKEN: &str = "NOTIFY_TELEGRAM_TOKEN";
+pub const ENV_NOTIFY_TELEGRAM_CHAT_ID: &str = "NOTIFY_TELEGRAM_CHAT_ID";
+pub const ENV_NOTIFY_EVENTS: &str = "NOTIFY_EVENTS";
+pub const ENV_NOTIFY_CONNECTION_FAILURE_MINUTES: &str = "NOTIFY_CONNECTION_FAILURE_MINUTES";
+pub const ENV_NOTIFY_MIN_HASHRATE: &str = "NOTIFY_MIN_HASHRATE";
+pub const ENV_SUMMARY_FILE: &str = "SUMMARY_FILE";
+pub const ENV_STALL_TIMEOUT: &str = "STALL_TIMEOUT";
+pub const ENV_SPILL_FILE: &str = "SPILL_FILE";
+pub const ENV_PANIC_EXIT: &str = "PANIC_EXIT";
+pub const ENV_INSTANCE_LOCK: &str = "INSTANCE_LOCK";
+pub const ENV_HEALTH_PORT: &str = "HEALTH_PORT";
+pub const ENV_HEALTH_STALL_TIMEOUT: &str = "HEALTH_STALL_TIMEOUT";
+pub const ENV_HEALTH_CONNECT_MINUTES: &str = "HEALTH_CONNECT_MINUTES";
+pub const ENV_KECCAK_LANES: &str = "KECCAK_LANES";
+pub const ENV_SHARE_THRESHOLD: &str = "SHARE_THRESHOLD";
+pub const ENV_SHARE_SERVER: &str = "SHARE_SERVER";
+pub const ENV_FARM_REPORT_URL: &str = "FARM_REPORT_URL";
+pub const ENV_FARM_REPORT_SECRET: &str = "FARM_REPORT_SECRET";
+pub const ENV_FARM_REPORT_INTERVAL: &str = "FARM_REPORT_INTERVAL";
+pub const ENV_RIG_NAME: &str = "RIG_NAME";
+pub const ENV_WORKER_NAME: &str = "WORKER_NAME";
+pub const ENV_POOL_SERVER: &str = "POOL_SERVER";
+pub const ENV_POOL_FAILOVER_MINUTES: &str = "POOL_FAILOVER_MINUTES";
+pub const ENV_STATE_DIR: &str = "STATE_DIR";
+pub const ENV_PEER_ALLOWLIST: &str = "PEER_ALLOWLIST";
+pub const ENV_PEER_DENYLIST: &str = "PEER_DENYLIST";
+pub const ENV_SUBMIT_MAX_PACKETS_PER_CONNECTION: &str = "SUBMIT_MAX_PACKETS_PER_CONNECTION";
+pub const ENV_SUBMIT_MAX_PACKETS_PER_SECOND: &str = "SUBMIT_MAX_PACKETS_PER_SECOND";
+pub const ENV_SUBMIT_MIN_CONNECT_INTERVAL_MS: &str = "SUBMIT_MIN_CONNECT_INTERVAL_MS";
+pub const ENV_RUST_LOG: &str = "RUST_LOG";
+pub const ENV_ALERT_MIN_HASHRATE: &str = "ALERT_MIN_HASHRATE";
+pub const ENV_ALERT_MIN_SOLUTIONS_PER_HOUR: &str = "ALERT_MIN_SOLUTIONS_PER_HOUR";
+pub const ENV_ALERT_HASHRATE_DROP_PERCENT: &str = "ALERT_HASHRATE_DROP_PERCENT";
+pub const ENV_ALERT_SUSTAINED_MINUTES: &str = "ALERT_SUSTAINED_MINUTES";
+pub const ENV_OTEL_EXPORTER_OTLP_ENDPOINT: &str = "OTEL_EXPORTER_OTLP_ENDPOINT";
+pub const ENV_OTEL_EXPORTER_OTLP_HEADERS: &str = "OTEL_EXPORTER_OTLP_HEADERS";
+pub const ENV_OTEL_SERVICE_NAME: &str = "OTEL_SERVICE_NAME";
+pub const ENV_METRICS_BACKEND: &str = "METRICS_BACKEND";
+pub const ENV_METRICS_PREFIX: &str = "METRICS_PREFIX";
+pub const ENV_METRICS_INTERVAL: &str = "METRICS_INTERVAL";
+pub const ENV_STATS_SINKS: &str = "STATS_SINKS";
+pub const ENV_STATS_INTERVAL: &str = "STATS_INTERVAL";
+pub const ENV_STATS_PROMETHEUS_PORT: &str = "STATS_PROMETHEUS_PORT";
+pub const ENV_STATS_FILE: &str = "STATS_FILE";
+pub const ENV_SCHEDULER: &str = "SCHEDULER";
+pub const ENV_CORE_POLICY: &str = "CORE_POLICY";
+pub const ENV_NUMBER_OF_NEURONS: &str = "NUMBER_OF_NEURONS";
+pub const ENV_MINING_DATA_LENGTH: &str = "MINING_DATA_LENGTH";
+pub const ENV_ALGORITHM: &str = "ALGORITHM";
+pub const ENV_RANDOM_SOURCE: &str = "RANDOM_SOURCE";
+pub const ENV_SOCKS_PROXY: &str = "SOCKS_PROXY";
+pub const ENV_SOCKS_ISOLATION: &str = "SOCKS_ISOLATION";
+pub const ENV_BANDWIDTH_MAX_BYTES_PER_HOUR: &str = "BANDWIDTH_MAX_BYTES_PER_HOUR";
+pub const ENV_CLOCK_SKEW_MAX_SECONDS: &str = "CLOCK_SKEW_MAX_SECONDS";
+pub const ENV_THERMAL_LIMIT: &str = "THERMAL_LIMIT";
+pub const ENV_THERMAL_HYSTERESIS: &str = "THERMAL_HYSTERESIS";
+pub const ENV_PACKET_CAPTURE: &str = "PACKET_CAPTURE";
+pub const ENV_PEER_PROTOCOLS: &str = "PEER_PROTOCOLS";
+pub const ENV_BALANCE_CHECK_MINUTES: &str = "BALANCE_CHECK_MINUTES";
+pub const ENV_KEYSTORE_FILE: &str = "KEYSTORE_FILE";
+pub const ENV_KEYSTORE_PASSPHRASE: &str = "KEYSTORE_PASSPHRASE";
+pub const ENV_SUBMIT_CONNECTIONS_PER_NODE: &str = "SUBMIT_CONNECTIONS_PER_NODE";
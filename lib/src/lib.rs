@@ -1,3 +1,8 @@
+#![cfg_attr(not(feature = "std"), no_std)]
+
+// `types` and `params` only need `alloc`, so the scoring builds without the standard library
+extern crate alloc;
+
 #[cfg(feature = "types")]
 pub mod types;
 #[cfg(feature = "version")]
@@ -6,5 +11,5 @@ pub mod version;
 pub mod random_seed;
 #[cfg(feature = "env_names")]
 pub mod env_names;
-#[cfg(feature = "solution_threshold")]
-pub mod solution_threshold;
+#[cfg(feature = "params")]
+pub mod params;
@@ -0,0 +1,112 @@
+use alloc::format;
+use alloc::string::{String, ToString};
+use core::fmt;
+use crate::types::{NeuronLink, Version, MINING_DATA_LENGTH, NUMBER_OF_NEURONS};
+
+/// Parameters of the scoring algorithm, which Qubic changes from one epoch to another.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct AlgorithmParams {
+    /// Number of neurons, a power of two so links can be reduced with a mask
+    pub number_of_neurons: usize,
+    /// Number of 64-bit words of mining data, which bound the score
+    pub mining_data_length: usize,
+}
+
+/// Parameters of every Qubic release that changed them, ordered by the first version using them.
+///
+/// Add an entry when a release changes the parameters, so older versions stay minable and testable.
+pub const KNOWN_PARAMS: &[(Version, AlgorithmParams)] = &[
+    ([1, 0, 0], AlgorithmParams::CURRENT),
+];
+
+impl AlgorithmParams {
+    /// Parameters of the version this release of Qiner targets.
+    pub const CURRENT: AlgorithmParams = AlgorithmParams {
+        number_of_neurons: NUMBER_OF_NEURONS,
+        mining_data_length: MINING_DATA_LENGTH,
+    };
+
+    /// Gets the parameters of a Qubic version.
+    ///
+    /// # Arguments
+    /// * `version` - The version of Qubic.
+    ///
+    /// # Returns
+    /// The parameters of the latest entry of `KNOWN_PARAMS` not after the version, `CURRENT` if there is none.
+    ///
+    /// # Examples
+    /// ```
+    /// use lib::params::AlgorithmParams;
+    ///
+    /// assert_eq!(AlgorithmParams::for_version(&[1, 142, 1]), AlgorithmParams::CURRENT);
+    /// ```
+    pub fn for_version(version: &Version) -> AlgorithmParams {
+        KNOWN_PARAMS.iter()
+            .rev()
+            .find(|(first_version, _)| first_version <= version)
+            .map_or(AlgorithmParams::CURRENT, |(_, params)| *params)
+    }
+
+    /// Checks that the miner can run with the parameters.
+    ///
+    /// # Returns
+    /// Why the parameters are invalid, if they are.
+    pub fn validate(&self) -> Result<(), String> {
+        if !self.number_of_neurons.is_power_of_two() || self.number_of_neurons < 2 {
+            return Err(format!("the number of neurons must be a power of two of at least 2, got {}", self.number_of_neurons));
+        }
+        if self.number_of_neurons as u64 > 1 << NeuronLink::BITS {
+            return Err(format!("the number of neurons must fit in a {}-bit link, got {}", NeuronLink::BITS, self.number_of_neurons));
+        }
+        if self.mining_data_length == 0 {
+            return Err("the mining data length must be positive".to_string());
+        }
+        Ok(())
+    }
+
+    /// Gets the number of 64-bit words holding the neuron links, two links per word.
+    pub fn number_of_neurons_64(&self) -> usize {
+        self.number_of_neurons * size_of::<NeuronLink>() / size_of::<u64>()
+    }
+
+    /// Gets the mask reducing both links of a word to the range of the neurons.
+    pub fn neuron_mod_bits(&self) -> u64 {
+        let mask = (self.number_of_neurons - 1) as u64;
+        (mask << NeuronLink::BITS) | mask
+    }
+
+    /// Gets the highest score, one per bit of mining data.
+    pub fn max_score(&self) -> usize {
+        self.mining_data_length * u64::BITS as usize
+    }
+}
+
+impl Default for AlgorithmParams {
+    fn default() -> Self {
+        AlgorithmParams::CURRENT
+    }
+}
+
+impl fmt::Display for AlgorithmParams {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} neurons, {} words of mining data", self.number_of_neurons, self.mining_data_length)
+    }
+}
+
+#[test]
+/// Tests the derived values and the validation of the parameters.
+fn test_algorithm_params() {
+    use crate::types::{NEURON_MOD_BITS, NUMBER_OF_NEURONS_64};
+
+    let current = AlgorithmParams::CURRENT;
+    assert_eq!(current.number_of_neurons_64(), NUMBER_OF_NEURONS_64);
+    assert_eq!(current.neuron_mod_bits(), NEURON_MOD_BITS);
+    assert_eq!(current.validate(), Ok(()));
+    assert_eq!(AlgorithmParams::for_version(&[0, 9, 0]), current);
+
+    let small = AlgorithmParams { number_of_neurons: 1024, mining_data_length: 4 };
+    assert_eq!((small.number_of_neurons_64(), small.neuron_mod_bits(), small.max_score()), (512, 0x3FF_0000_03FF, 256));
+    assert!(AlgorithmParams { number_of_neurons: 1000, ..small }.validate().is_err());
+    assert!(AlgorithmParams { mining_data_length: 0, ..small }.validate().is_err());
+}
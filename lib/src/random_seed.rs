@@ -2,29 +2,110 @@ use std::env;
 use crate::env_names::ENV_RANDOM_SEED;
 use crate::types::{RANDOM_SEED_SPLIT_CHAR, Seed, SeedItem};
 
+/// Formats accepted for the random seed, listed in parse errors.
+pub const RANDOM_SEED_FORMATS: &str = "comma-separated numbers from 0 to 255 (e.g. 1,0,233,9), \
+    0x-prefixed hex (e.g. 0x0100e909) or base64 (e.g. AQDpCQ==), of at most 32 bytes";
+
 /// Retrieves the random seed from the environment variable and parses it into a `Seed`.
 ///
 /// # Returns
-/// A `Seed` parsed from the environment variable `ENV_RANDOM_SEED`.
+/// A `Seed` parsed from the environment variable `ENV_RANDOM_SEED`, in any format `parse_random_seed` accepts.
 ///
 /// # Panics
-/// Panics if the environment variable `ENV_RANDOM_SEED` is not set or if any of the seed items cannot be parsed into a `SeedItem`.
+/// Panics if the environment variable `ENV_RANDOM_SEED` is not set or cannot be parsed, with the accepted formats.
 pub fn get_random_seed() -> Seed {
     // Retrieve the random seed string from the environment variable
     let random_seed_string = env::var(ENV_RANDOM_SEED).unwrap();
-    
-    // Split the string by the defined split character
-    let split = random_seed_string.split(RANDOM_SEED_SPLIT_CHAR);
-    
-    // Initialize a default Seed
+
+    match parse_random_seed(&random_seed_string) {
+        Ok(random_seed) => random_seed,
+        Err(err) => panic!("Invalid {ENV_RANDOM_SEED}: {err}"),
+    }
+}
+
+/// Parses a random seed, the missing trailing bytes being zero.
+///
+/// The format is detected: a `0x` prefix means hex, only digits and commas mean
+/// comma-separated numbers, and anything else is decoded as base64 (standard or URL-safe
+/// alphabet, padding optional).
+///
+/// # Arguments
+/// * `value` - The seed to parse, e.g. `1,0,233,9,136,69,43,139`, `0x0100e909` or `AQDpCQ==`.
+///
+/// # Returns
+/// The `Seed`, or why it is invalid along with the accepted formats.
+///
+/// # Examples
+/// ```
+/// use lib::random_seed::parse_random_seed;
+///
+/// let decimal = parse_random_seed("1,0,233,9").unwrap();
+/// assert_eq!(parse_random_seed("0x0100e909"), Ok(decimal));
+/// assert_eq!(parse_random_seed("AQDpCQ=="), Ok(decimal));
+/// assert!(parse_random_seed("0x1").is_err());
+/// ```
+pub fn parse_random_seed(value: &str) -> Result<Seed, String> {
+    let value = value.trim();
+    let bytes = if let Some(hex) = value.strip_prefix("0x").or_else(|| value.strip_prefix("0X")) {
+        decode_hex(hex)
+    } else if value.chars().all(|char| char.is_ascii_digit() || char == RANDOM_SEED_SPLIT_CHAR || char.is_whitespace()) {
+        decode_decimal(value)
+    } else {
+        decode_base64(value)
+    };
+
+    let bytes = bytes.map_err(|reason| format!("{reason}, expected {RANDOM_SEED_FORMATS}"))?;
     let mut random_seed = Seed::default();
-    
-    // Iterate over the split items and the seed items, parsing and assigning each value
-    for (split_item, seed_item) in split.zip(random_seed.as_mut()) {
-        *seed_item = split_item.trim().parse::<SeedItem>().unwrap();
+    if bytes.len() > random_seed.len() {
+        return Err(format!("{} bytes is too long, expected {RANDOM_SEED_FORMATS}", bytes.len()));
     }
-    
-    random_seed
+    random_seed[..bytes.len()].copy_from_slice(&bytes);
+
+    Ok(random_seed)
+}
+
+/// Decodes comma-separated numbers.
+fn decode_decimal(value: &str) -> Result<Vec<SeedItem>, String> {
+    value.split(RANDOM_SEED_SPLIT_CHAR)
+        .map(|item| item.trim().parse::<SeedItem>().map_err(|_| format!("{:?} is not a number from 0 to 255", item.trim())))
+        .collect()
+}
+
+/// Decodes hex digits, ignoring whitespace.
+fn decode_hex(value: &str) -> Result<Vec<SeedItem>, String> {
+    let digits = value.chars().filter(|char| !char.is_whitespace()).collect::<Vec<_>>();
+    if digits.len() % 2 != 0 {
+        return Err("hex has an odd number of digits".to_string());
+    }
+
+    digits.chunks(2).map(|pair| {
+        let pair = pair.iter().collect::<String>();
+        SeedItem::from_str_radix(&pair, 16).map_err(|_| format!("{pair:?} is not hex"))
+    }).collect()
+}
+
+/// Decodes base64, with the standard or the URL-safe alphabet and optional padding.
+fn decode_base64(value: &str) -> Result<Vec<SeedItem>, String> {
+    let symbols = value.trim_end_matches('=').bytes().map(|symbol| match symbol {
+        b'A'..=b'Z' => Ok(symbol - b'A'),
+        b'a'..=b'z' => Ok(symbol - b'a' + 26),
+        b'0'..=b'9' => Ok(symbol - b'0' + 52),
+        b'+' | b'-' => Ok(62),
+        b'/' | b'_' => Ok(63),
+        _ => Err(format!("{:?} is not base64", symbol as char)),
+    }).collect::<Result<Vec<_>, _>>()?;
+    if symbols.len() % 4 == 1 {
+        return Err("base64 is truncated".to_string());
+    }
+
+    // Every 4 symbols carry 3 bytes, a partial last group carrying one byte less than its symbols
+    let mut bytes = Vec::with_capacity(symbols.len() * 3 / 4);
+    for group in symbols.chunks(4) {
+        let bits = group.iter().enumerate().fold(0u32, |bits, (idx, symbol)| bits | (u32::from(*symbol) << (18 - 6 * idx)));
+        bytes.extend_from_slice(&bits.to_be_bytes()[1..group.len()]);
+    }
+
+    Ok(bytes)
 }
 
 #[test]
@@ -47,3 +128,28 @@ fn test_random_seed() {
     // Assert that the function output matches the expected Seed
     assert_eq!(expected_seed, get_random_seed());
 }
+
+#[test]
+/// Tests that `parse_random_seed` rejects invalid seeds instead of panicking.
+fn test_parse_random_seed() {
+    assert_eq!(parse_random_seed("1, 2,3").map(|seed| seed[..4].to_vec()), Ok(vec![1, 2, 3, 0]));
+    assert!(parse_random_seed("1,256").is_err());
+    assert!(parse_random_seed("1,,2").is_err());
+    assert!(parse_random_seed(&vec!["1"; 33].join(",")).is_err());
+}
+
+#[test]
+/// Tests the detection of the hex and base64 formats and their errors.
+fn test_parse_random_seed_formats() {
+    let seed: Seed = std::array::from_fn(|idx| (idx * 7) as SeedItem);
+    let hex = seed.iter().map(|item| format!("{item:02x}")).collect::<String>();
+    assert_eq!(parse_random_seed(&format!("0x{hex}")), Ok(seed));
+    assert_eq!(parse_random_seed(&format!("0X{}", hex.to_uppercase())), Ok(seed));
+    assert_eq!(parse_random_seed("AAcOFRwjKjE4P0ZNVFtiaXB3foWMk5qhqK-2vcTL0tk"), Ok(seed));
+    assert_eq!(parse_random_seed("AAcOFRwjKjE4P0ZNVFtiaXB3foWMk5qhqK+2vcTL0tk="), Ok(seed));
+
+    let error = parse_random_seed("0xzz").unwrap_err();
+    assert!(error.contains("not hex") && error.contains(RANDOM_SEED_FORMATS));
+    assert!(parse_random_seed("abc!").unwrap_err().contains("not base64"));
+    assert!(parse_random_seed(&format!("0x{hex}00")).unwrap_err().contains("too long"));
+}
@@ -1,4 +1,4 @@
-use std::mem::size_of;
+use core::mem::size_of;
 
 // Constants
 
@@ -15,7 +15,7 @@ pub const NUMBER_OF_NEURONS: usize = 4_194_304;
 pub const NUMBER_OF_NEURONS_64: usize = NUMBER_OF_NEURONS * size_of::<NeuronLink>() / size_of::<u64>();
 
 /// Bit mask for neuron modulus operations. Used to ensure neuron indices are within valid range.
-pub const NEURON_MOD_BITS: u64 = (((NUMBER_OF_NEURONS - 1) << size_of::<NeuronLink>() * 8) | (NUMBER_OF_NEURONS - 1)) as u64;
+pub const NEURON_MOD_BITS: u64 = (((NUMBER_OF_NEURONS - 1) as u64) << NeuronLink::BITS) | (NUMBER_OF_NEURONS - 1) as u64;
 
 /// Length of mining data, typically used in mining algorithms.
 pub const MINING_DATA_LENGTH: usize = 1024;
@@ -27,9 +27,11 @@ pub const KECCAK_ROUND: usize = 12;
 pub const SEED_ITEM_NUM: usize = 32;
 
 /// Character used to split version strings.
+#[cfg(feature = "version")]
 pub(crate) const VERSION_SPLIT_CHAR: char = '.';
 
 /// Character used to split random seed strings.
+#[cfg(feature = "random_seed")]
 pub(crate) const RANDOM_SEED_SPLIT_CHAR: char = ',';
 
 /// Default port number for network communication.
@@ -42,6 +44,9 @@ pub const STACK_SIZE: usize = 40 * 1024 * 1024;
 /// Number of neuron values in 64-bit words (deprecated, use `NUMBER_OF_NEURONS_64` instead).
 pub const NUMBER_OF_NEURON_VALUES_64: usize = size_of::<NeuronValues>() / size_of::<u64>();
 
+/// Number of 64-bit words holding the neuron values when packed one bit per neuron.
+pub const NUMBER_OF_PACKED_NEURON_VALUES: usize = NUMBER_OF_NEURONS / u64::BITS as usize;
+
 /// Number of items in a nonce array.
 pub const NUMBER_OF_NONCE: usize = 32;
 
@@ -83,6 +88,9 @@ pub type NeuronValue = u8;
 /// Represents an array of neuron values.
 pub type NeuronValues = [NeuronValue; NUMBER_OF_NEURONS];
 
+/// Represents an array of neuron values packed one bit per neuron, 64 neurons per word.
+pub type PackedNeuronValues = [u64; NUMBER_OF_PACKED_NEURON_VALUES];
+
 /// Represents an ID as an array of bytes.
 pub type Id = [u8; 60];
 
@@ -123,7 +131,7 @@ pub type NeuronValues64 = [NeuronValue64; NUMBER_OF_NEURONS_64];
 
 /// Module for network-related types and constants.
 pub mod network {
-    use std::mem::size_of;
+    use core::mem::size_of;
     use crate::types::NUMBER_OF_NONCE;
 
     /// Represents a size as an array of bytes.
@@ -158,12 +166,42 @@ pub mod network {
     /// Number of items in a key array in 64-bit words.
     pub const KEY_ITEM_NUM_64: usize = KEY_ITEM_NUM / size_of::<u64>();
 
+    /// Number of IPv4 addresses in a public peers exchange.
+    pub const NUMBER_OF_EXCHANGED_PEERS: usize = 4;
+
+    /// Number of computors of an epoch.
+    pub const NUMBER_OF_COMPUTORS: usize = 676;
+
     /// Module for protocol-related constants.
     pub mod protocols {
         use crate::types::network::Type;
 
+        /// Identifier for the public peers a node sends first on every connection.
+        pub const EXCHANGE_PUBLIC_PEERS: Type = 0;
+
         /// Identifier for broadcast messages.
         pub const BROADCAST_MESSAGE: Type = 1;
+
+        /// Identifier for the computors of the epoch, signed by the arbitrator.
+        pub const BROADCAST_COMPUTORS: Type = 2;
+
+        /// Identifier for the request of the computors of the epoch, answered with `BROADCAST_COMPUTORS`.
+        pub const REQUEST_COMPUTORS: Type = 11;
+
+        /// Identifier for transactions, relayed to every node.
+        pub const BROADCAST_TRANSACTION: Type = 24;
+
+        /// Identifier for the request of the current tick info of a node.
+        pub const REQUEST_CURRENT_TICK_INFO: Type = 27;
+
+        /// Identifier for the current tick info a node responds with.
+        pub const RESPOND_CURRENT_TICK_INFO: Type = 28;
+
+        /// Identifier for the request of the entity of a public key in the spectrum.
+        pub const REQUEST_ENTITY: Type = 31;
+
+        /// Identifier for the entity a node responds with.
+        pub const RESPOND_ENTITY: Type = 32;
     }
 }
 
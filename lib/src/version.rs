@@ -1,41 +1,38 @@
-use std::env;
-use crate::env_names::ENV_VERSION;
 use crate::types::{VERSION_SPLIT_CHAR, Version};
 
-/// Retrieves the version from the environment variable and parses it into a `Version`.
+/// Version of Qubic this release of Qiner targets, used when `VERSION` is not set.
 ///
-/// # Returns
-/// A `Version` parsed from the environment variable `ENV_VERSION`.
-///
-/// # Panics
-/// Panics if the environment variable `ENV_VERSION` is not set or if any of the version components
-/// cannot be parsed into a `u8`.
+/// Bumped with every Qubic release, so users do not have to maintain `VERSION` by hand.
+pub const DEFAULT_VERSION: Version = [1, 142, 1];
+
+/// Formats a version the way it is configured, e.g. `1.142.1`.
 ///
 /// # Examples
 /// ```
-/// use std::env;
-/// use crate::env_names::ENV_VERSION;
-/// use crate::get_version;
+/// use lib::version::format_version;
 ///
-/// env::set_var(ENV_VERSION, "1.141.0");
-/// let version = get_version();
-/// assert_eq!(version, [1, 141, 0]);
+/// assert_eq!(format_version(&[1, 142, 1]), "1.142.1");
 /// ```
-pub fn get_version() -> Version {
-    // Retrieve the version string from the environment variable
-    let found_version = env::var(ENV_VERSION).unwrap();
-    
-    // Split the string by the defined split character
-    let split = found_version.split(VERSION_SPLIT_CHAR);
-
-    // Initialize a default Version array
-    let mut version: Version = Version::default();
-    
-    // Iterate over the split items and their indices, parsing and assigning each value
-    split.into_iter().enumerate().for_each(|(idx, item)| {
-        version[idx] = item.trim().parse::<u8>().unwrap();
-    });
-
-    version
+pub fn format_version(version: &Version) -> String {
+    version.map(|item| item.to_string()).join(&VERSION_SPLIT_CHAR.to_string())
 }
 
+/// Parses a version such as `1.142.1`.
+///
+/// # Arguments
+/// * `value` - The version to parse.
+///
+/// # Returns
+/// The `Version`, or `None` if it does not have exactly 3 components fitting in a `u8`.
+///
+/// # Examples
+/// ```
+/// use lib::version::parse_version;
+///
+/// assert_eq!(parse_version("1.142.1"), Some([1, 142, 1]));
+/// assert_eq!(parse_version("1.300.1"), None);
+/// ```
+pub fn parse_version(value: &str) -> Option<Version> {
+    let items = value.trim().split(VERSION_SPLIT_CHAR).map(|item| item.trim().parse::<u8>().ok()).collect::<Option<Vec<_>>>()?;
+    items.try_into().ok()
+}